@@ -1,5 +1,29 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() {
     if std::env::var("CARGO_CFG_TARGET_OS").unwrap() == "windows" {
         embed_resource::compile("resources/simpliview.rc", embed_resource::NONE);
     }
-}
\ No newline at end of file
+
+    // Build date for the Info dialog (UTC). SOURCE_DATE_EPOCH keeps reproducible builds stable.
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()));
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    println!("cargo:rustc-env=SIMPLIVIEW_BUILD_DATE={:02}.{:02}.{}", day, month, year);
+}
+
+/// Gregorian date of a day count since 1970-01-01 (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
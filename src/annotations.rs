@@ -0,0 +1,506 @@
+//! "Anmerkungen": highlights, notes and other annotations of the open PDF
+//!
+//! Windows.Data.Pdf draws annotations only through their appearance streams and has no
+//! render option to switch them on or off, so notes made in other programs may show as a
+//! small icon or not at all, and their text never. The annotations are therefore read
+//! from the file (see pdf_structure.rs) and listed in a small window with page, type and
+//! text; the full text also shows as a tooltip. Choosing an entry shows the page with a
+//! marker around the annotation (WM_APP_ANNOTATION). Links and form fields are left out.
+//!
+//! Links are read separately when a PDF is loaded (`links`): page destinations,
+//! direct or named, and URIs. The view makes them clickable.
+
+use crate::pdf_structure::{get, Dict, Object, Pdf};
+use std::collections::HashMap;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Graphics::Gdi::{GetSysColorBrush, COLOR_WINDOW},
+        UI::{Controls::*, HiDpi::GetDpiForWindow, Input::KeyboardAndMouse::{SetFocus, VK_ESCAPE}, WindowsAndMessaging::*},
+    },
+};
+
+/// Posted to the owner when an entry is chosen; wparam is its index in the list passed to
+/// `AnnotationPanel::show`
+pub const WM_APP_ANNOTATION: u32 = WM_APP + 11;
+/// Posted to the owner by `start_read`; lparam is a boxed `ReadResult`
+pub const WM_APP_ANNOTATIONS_READ: u32 = WM_APP + 19;
+
+/// HRESULT_FROM_WIN32(ERROR_INVALID_DATA)
+const E_INVALID_DATA: HRESULT = HRESULT(0x8007000Du32 as i32);
+
+const CLASS_NAME: PCWSTR = w!("SimpliViewAnnotations");
+const IDC_LIST: i32 = 100;
+
+/// Subtypes that are not listed: links, form fields and the popups of notes
+const SKIPPED_TYPES: [&[u8]; 3] = [b"Link", b"Widget", b"Popup"];
+
+const TYPE_NAMES: [(&[u8], &str); 16] = [
+    (b"Text", "Notiz"),
+    (b"FreeText", "Textfeld"),
+    (b"Highlight", "Hervorhebung"),
+    (b"Underline", "Unterstreichung"),
+    (b"StrikeOut", "Durchstreichung"),
+    (b"Squiggly", "Wellenlinie"),
+    (b"Square", "Rechteck"),
+    (b"Circle", "Ellipse"),
+    (b"Line", "Linie"),
+    (b"Polygon", "Polygon"),
+    (b"PolyLine", "Linienzug"),
+    (b"Ink", "Freihand"),
+    (b"Stamp", "Stempel"),
+    (b"Caret", "Einfügemarke"),
+    (b"FileAttachment", "Dateianhang"),
+    (b"Redact", "Schwärzung"),
+];
+
+/// Columns: title and width in pixels at 96 DPI
+const COLUMNS: [(&str, i32); 4] = [("Seite", 50), ("Art", 100), ("Text", 220), ("Von", 90)];
+
+pub struct Annotation {
+    /// 0-based
+    pub page: usize,
+    /// German name of the type, e.g. "Hervorhebung"
+    pub kind: String,
+    /// /Contents, e.g. the text of a note
+    pub text: String,
+    pub author: String,
+    /// Left, top, right, bottom as fractions of the page as rendered (turned by its
+    /// /Rotate); `None` if the file gives no usable rectangle
+    pub rect: Option<[f32; 4]>,
+}
+
+/// A clickable area of a PDF page, read when the PDF is loaded (see `Document::links`)
+#[derive(Clone, Debug)]
+pub struct Link {
+    /// Left, top, right, bottom as fractions of the rendered page, like `Annotation::rect`
+    pub rect: [f32; 4],
+    pub target: LinkTarget,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    /// 0-based page of this document
+    Page(usize),
+    /// Web address or other URI, opened with the program registered for it
+    Uri(String),
+}
+
+/// Annotations read for a file, posted with WM_APP_ANNOTATIONS_READ
+pub struct ReadResult {
+    /// File read, so a late result of another file is dropped
+    pub path: String,
+    /// Number passed to `start_read`, telling apart requests for the same file
+    pub request: u64,
+    /// The annotations, or code and message of the error (`Error` cannot be sent between
+    /// threads)
+    pub annotations: std::result::Result<Vec<Annotation>, (HRESULT, String)>,
+}
+
+/// Reads the annotations of the PDF at `path` on a worker thread and posts them to `hwnd`.
+/// False if the thread could not be started.
+pub fn start_read(hwnd: HWND, path: String, request: u64) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView Annotations".to_string())
+        .spawn(move || {
+            let annotations = read(&path).map_err(|e| (e.code(), e.message().to_string()));
+            let result = Box::into_raw(Box::new(ReadResult { path, request, annotations }));
+            if !unsafe { PostMessageW(HWND(hwnd_raw), WM_APP_ANNOTATIONS_READ, WPARAM(0), LPARAM(result as isize)) }.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
+            }
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the result posted with WM_APP_ANNOTATIONS_READ
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_ANNOTATIONS_READ message and be taken only once.
+pub unsafe fn take_result(lparam: LPARAM) -> ReadResult {
+    *Box::from_raw(lparam.0 as *mut ReadResult)
+}
+
+/// All listed annotations of the PDF at `path`, by page
+pub fn read(path: &str) -> Result<Vec<Annotation>> {
+    let mut pdf = Pdf::read(path)?;
+    if pdf.is_encrypted() {
+        return Err(Error::new(
+            E_INVALID_DATA,
+            HSTRING::from("Die Anmerkungen verschlüsselter PDF-Dateien können nicht gelesen werden."),
+        ));
+    }
+    let mut annotations = Vec::new();
+    for_each_annotation(&mut pdf, |pdf, page, dict, rect| {
+        let Some(Object::Name(subtype)) = get(dict, b"Subtype") else { return };
+        if SKIPPED_TYPES.contains(&subtype.as_slice()) {
+            return;
+        }
+        let kind = TYPE_NAMES
+            .iter()
+            .find(|(name, _)| *name == subtype.as_slice())
+            .map_or_else(|| String::from_utf8_lossy(subtype).into_owned(), |(_, label)| label.to_string());
+        annotations.push(Annotation {
+            page,
+            kind,
+            text: text(pdf, dict, b"Contents"),
+            author: text(pdf, dict, b"T"),
+            rect,
+        });
+    })?;
+    Ok(annotations)
+}
+
+/// The link annotations of `pdf`, one list per page. Encrypted files (whose URIs are
+/// encrypted, too) have none.
+pub fn links(pdf: &mut Pdf) -> Result<Vec<Vec<Link>>> {
+    let (pages, _) = pdf.pages()?;
+    let mut links: Vec<Vec<Link>> = (0..pages.len()).map(|_| Vec::new()).collect();
+    if pdf.is_encrypted() {
+        return Ok(links);
+    }
+    let page_numbers: Vec<u32> = pages.iter().map(|(number, _)| *number).collect();
+    let mut named = None;
+    for_each_annotation(pdf, |pdf, page, dict, rect| {
+        if !get(dict, b"Subtype").is_some_and(|subtype| subtype.is_name(b"Link")) {
+            return;
+        }
+        let Some(rect) = rect else { return };
+        let target = match (get(dict, b"Dest"), get(dict, b"A").and_then(|action| pdf.resolve(action).ok())) {
+            (Some(dest), _) => destination(pdf, dest, &page_numbers, &mut named).map(LinkTarget::Page),
+            (None, Some(Object::Dict(action))) => {
+                let kind = get(&action, b"S");
+                if kind.is_some_and(|kind| kind.is_name(b"GoTo")) {
+                    get(&action, b"D").and_then(|dest| destination(pdf, dest, &page_numbers, &mut named)).map(LinkTarget::Page)
+                } else if kind.is_some_and(|kind| kind.is_name(b"URI")) {
+                    Some(text(pdf, &action, b"URI")).filter(|uri| !uri.is_empty()).map(LinkTarget::Uri)
+                } else {
+                    // Launch, JavaScript, links into other files etc. are not followed
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(target) = target {
+            links[page].push(Link { rect, target });
+        }
+    })?;
+    Ok(links)
+}
+
+/// Calls `f` with the page, the dictionary and the position (see `Annotation::rect`) of
+/// every annotation. Damaged annotations are skipped, not the whole file.
+fn for_each_annotation(pdf: &mut Pdf, mut f: impl FnMut(&mut Pdf, usize, &Dict, Option<[f32; 4]>)) -> Result<()> {
+    let (pages, _) = pdf.pages()?;
+    for (page, (number, inherited)) in pages.iter().enumerate() {
+        let page_object = pdf.object(*number)?;
+        let Some(annots) = page_object.as_dict().and_then(|dict| get(dict, b"Annots")).cloned() else {
+            continue;
+        };
+        let Ok(Object::Array(annots)) = pdf.resolve(&annots) else {
+            continue;
+        };
+        let page_box = page_box(pdf, inherited);
+        let rotation = get(inherited, b"Rotate").and_then(Object::as_integer).unwrap_or(0).rem_euclid(360);
+
+        for annot in annots {
+            let Ok(annot) = pdf.resolve(&annot) else { continue };
+            let Some(dict) = annot.as_dict() else { continue };
+            let rect = numbers(pdf, get(dict, b"Rect")).and_then(|rect| position(rect, page_box, rotation));
+            f(pdf, page, dict, rect);
+        }
+    }
+    Ok(())
+}
+
+/// Page (0-based) of a destination: an explicit one (`[page /XYZ ...]`), or a name looked
+/// up in the catalog's /Dests or its /Names tree. `named` caches those names.
+fn destination(pdf: &mut Pdf, dest: &Object, pages: &[u32], named: &mut Option<HashMap<String, Object>>) -> Option<usize> {
+    let dest = match pdf.resolve(dest).ok()? {
+        Object::Name(name) => {
+            let names = named.get_or_insert_with(|| named_destinations(pdf));
+            names.get(String::from_utf8_lossy(&name).as_ref())?.clone()
+        }
+        string @ Object::String(_) => {
+            let names = named.get_or_insert_with(|| named_destinations(pdf));
+            names.get(&string.as_text()?)?.clone()
+        }
+        dest => dest,
+    };
+    // Named destinations may be dictionaries with the array under /D
+    let dest = match pdf.resolve(&dest).ok()? {
+        Object::Dict(dict) => pdf.resolve(get(&dict, b"D")?).ok()?,
+        dest => dest,
+    };
+    let Object::Array(items) = dest else { return None };
+    match items.first()? {
+        Object::Reference(number) => pages.iter().position(|page| page == number),
+        // Only valid in links to other files, but some writers use it for their own pages
+        Object::Integer(page) => usize::try_from(*page).ok().filter(|&page| page < pages.len()),
+        _ => None,
+    }
+}
+
+/// Named destinations of the catalog: the PDF 1.1 /Dests dictionary and the /Dests name tree
+fn named_destinations(pdf: &mut Pdf) -> HashMap<String, Object> {
+    let mut names = HashMap::new();
+    let Some(catalog) = pdf.root().and_then(|root| pdf.object(root).ok()) else {
+        return names;
+    };
+    let Some(catalog) = catalog.as_dict() else { return names };
+    if let Some(Object::Dict(dests)) = get(catalog, b"Dests").and_then(|dests| pdf.resolve(dests).ok()) {
+        for (key, value) in dests {
+            names.insert(String::from_utf8_lossy(&key).into_owned(), value);
+        }
+    }
+    let tree = get(catalog, b"Names")
+        .and_then(|names| pdf.resolve(names).ok())
+        .and_then(|names| names.as_dict().and_then(|dict| get(dict, b"Dests")).cloned());
+    if let Some(tree) = tree {
+        name_tree(pdf, &tree, &mut names, 0);
+    }
+    names
+}
+
+fn name_tree(pdf: &mut Pdf, node: &Object, names: &mut HashMap<String, Object>, depth: usize) {
+    if depth > 32 {
+        return;
+    }
+    let Ok(Object::Dict(node)) = pdf.resolve(node) else { return };
+    if let Some(Ok(Object::Array(pairs))) = get(&node, b"Names").map(|pairs| pdf.resolve(pairs)) {
+        for pair in pairs.chunks_exact(2) {
+            if let Some(key) = pdf.resolve(&pair[0]).ok().and_then(|key| key.as_text()) {
+                names.insert(key, pair[1].clone());
+            }
+        }
+    }
+    if let Some(Ok(Object::Array(kids))) = get(&node, b"Kids").map(|kids| pdf.resolve(kids)) {
+        for kid in &kids {
+            name_tree(pdf, kid, names, depth + 1);
+        }
+    }
+}
+
+/// A text entry of `dict`, empty if missing
+fn text(pdf: &mut Pdf, dict: &Dict, key: &[u8]) -> String {
+    get(dict, key)
+        .and_then(|value| pdf.resolve(value).ok())
+        .and_then(|value| value.as_text())
+        .map(|text| text.replace("\r\n", "\n").replace('\r', "\n").trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Four numbers of a rectangle, normalized to left, bottom, right, top
+fn numbers(pdf: &mut Pdf, value: Option<&Object>) -> Option<[f64; 4]> {
+    let Object::Array(items) = pdf.resolve(value?).ok()? else {
+        return None;
+    };
+    let mut values = [0.0; 4];
+    for (value, item) in values.iter_mut().zip(&items) {
+        *value = pdf.resolve(item).ok()?.as_number()?;
+    }
+    (items.len() == 4).then(|| [values[0].min(values[2]), values[1].min(values[3]), values[0].max(values[2]), values[1].max(values[3])])
+}
+
+/// The visible area of the page: CropBox, else MediaBox, else US Letter
+fn page_box(pdf: &mut Pdf, inherited: &Dict) -> [f64; 4] {
+    numbers(pdf, get(inherited, b"CropBox"))
+        .or_else(|| numbers(pdf, get(inherited, b"MediaBox")))
+        .unwrap_or([0.0, 0.0, 612.0, 792.0])
+}
+
+/// `rect` in PDF units (origin bottom left) as fractions of the rendered page
+fn position(rect: [f64; 4], page_box: [f64; 4], rotation: i64) -> Option<[f32; 4]> {
+    let (width, height) = (page_box[2] - page_box[0], page_box[3] - page_box[1]);
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+    let corners = [
+        ((rect[0] - page_box[0]) / width, (page_box[3] - rect[3]) / height),
+        ((rect[2] - page_box[0]) / width, (page_box[3] - rect[1]) / height),
+    ];
+    // The renderer turns the page clockwise by /Rotate
+    let turned = corners.map(|(u, v)| match rotation {
+        90 => (1.0 - v, u),
+        180 => (1.0 - u, 1.0 - v),
+        270 => (v, 1.0 - u),
+        _ => (u, v),
+    });
+    let clamp = |value: f64| value.clamp(0.0, 1.0) as f32;
+    Some([
+        clamp(turned[0].0.min(turned[1].0)),
+        clamp(turned[0].1.min(turned[1].1)),
+        clamp(turned[0].0.max(turned[1].0)),
+        clamp(turned[0].1.max(turned[1].1)),
+    ])
+}
+
+/// Tool window with the annotation list, owned by the main window. Closing only hides it.
+pub struct AnnotationPanel {
+    hwnd: HWND,
+    list: HWND,
+}
+
+impl AnnotationPanel {
+    pub fn new(owner: HWND, instance: HMODULE) -> Result<Self> {
+        unsafe {
+            let wc = WNDCLASSEXW {
+                cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
+                lpfnWndProc: Some(panel_wnd_proc),
+                hInstance: instance,
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                hbrBackground: GetSysColorBrush(COLOR_WINDOW),
+                lpszClassName: CLASS_NAME,
+                ..Default::default()
+            };
+            let _ = RegisterClassExW(&wc);
+
+            let scale = |value: i32| value * GetDpiForWindow(owner).max(96) as i32 / 96;
+            let width = COLUMNS.iter().map(|&(_, width)| scale(width)).sum::<i32>() + scale(40);
+            let hwnd = CreateWindowExW(
+                WS_EX_TOOLWINDOW,
+                CLASS_NAME,
+                w!("Anmerkungen"),
+                WS_POPUP | WS_CAPTION | WS_SYSMENU | WS_THICKFRAME,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                width,
+                scale(320),
+                owner,
+                None,
+                instance,
+                None,
+            );
+            if hwnd.0 == 0 {
+                return Err(Error::from_win32());
+            }
+
+            let list = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                WC_LISTVIEWW,
+                None,
+                WS_CHILD | WS_VISIBLE | WS_TABSTOP | WINDOW_STYLE(LVS_REPORT | LVS_SINGLESEL | LVS_SHOWSELALWAYS),
+                0,
+                0,
+                0,
+                0,
+                hwnd,
+                HMENU(IDC_LIST as isize),
+                instance,
+                None,
+            );
+            if list.0 == 0 {
+                let error = Error::from_win32();
+                let _ = DestroyWindow(hwnd);
+                return Err(error);
+            }
+            let style = LVS_EX_FULLROWSELECT | LVS_EX_INFOTIP | LVS_EX_DOUBLEBUFFER;
+            SendMessageW(list, LVM_SETEXTENDEDLISTVIEWSTYLE, WPARAM(style as usize), LPARAM(style as isize));
+            for (i, (title, width)) in COLUMNS.iter().enumerate() {
+                let mut title: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+                let column = LVCOLUMNW {
+                    mask: LVCF_TEXT | LVCF_WIDTH,
+                    cx: scale(*width),
+                    pszText: PWSTR(title.as_mut_ptr()),
+                    ..Default::default()
+                };
+                SendMessageW(list, LVM_INSERTCOLUMNW, WPARAM(i), LPARAM(&column as *const _ as isize));
+            }
+
+            // Top right over the owner, clear of the toolbar
+            let mut owner_rect = RECT::default();
+            let _ = GetWindowRect(owner, &mut owner_rect);
+            let _ = SetWindowPos(hwnd, None, owner_rect.right - width - scale(24), owner_rect.top + scale(96), 0, 0, SWP_NOSIZE | SWP_NOZORDER);
+
+            Ok(Self { hwnd, list })
+        }
+    }
+
+    /// Fills the list with `annotations` and shows the window
+    pub fn show(&self, annotations: &[Annotation]) {
+        unsafe {
+            SendMessageW(self.list, LVM_DELETEALLITEMS, WPARAM(0), LPARAM(0));
+            for (i, annotation) in annotations.iter().enumerate() {
+                // Notes are shown on one line; the tooltip has the whole text
+                let texts = [(annotation.page + 1).to_string(), annotation.kind.clone(), annotation.text.replace('\n', " "), annotation.author.clone()];
+                for (column, text) in texts.iter().enumerate() {
+                    let mut text: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+                    let item = LVITEMW {
+                        mask: LVIF_TEXT,
+                        iItem: i as i32,
+                        iSubItem: column as i32,
+                        pszText: PWSTR(text.as_mut_ptr()),
+                        ..Default::default()
+                    };
+                    let message = if column == 0 { LVM_INSERTITEMW } else { LVM_SETITEMW };
+                    SendMessageW(self.list, message, WPARAM(0), LPARAM(&item as *const _ as isize));
+                }
+            }
+            let title = format!("Anmerkungen ({})", annotations.len());
+            let _ = SetWindowTextW(self.hwnd, &HSTRING::from(title));
+            ShowWindow(self.hwnd, SW_SHOW);
+            SetFocus(self.list);
+        }
+    }
+
+    pub fn hide(&self) {
+        unsafe {
+            ShowWindow(self.hwnd, SW_HIDE);
+        }
+    }
+}
+
+extern "system" fn panel_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    unsafe {
+        let list = GetDlgItem(hwnd, IDC_LIST);
+        match msg {
+            WM_SIZE => {
+                let (width, height) = ((lparam.0 & 0xFFFF) as i32, ((lparam.0 >> 16) & 0xFFFF) as i32);
+                let _ = SetWindowPos(list, None, 0, 0, width, height, SWP_NOZORDER);
+                LRESULT(0)
+            }
+            WM_SETFOCUS => {
+                SetFocus(list);
+                LRESULT(0)
+            }
+            WM_NOTIFY if (*(lparam.0 as *const NMHDR)).idFrom == IDC_LIST as usize => {
+                match (*(lparam.0 as *const NMHDR)).code {
+                    LVN_ITEMCHANGED => {
+                        let change = &*(lparam.0 as *const NMLISTVIEW);
+                        let selected = |state: u32| (state & LVIS_SELECTED.0) != 0;
+                        if selected(change.uNewState) && !selected(change.uOldState) {
+                            let owner = GetWindow(hwnd, GW_OWNER);
+                            let _ = PostMessageW(owner, WM_APP_ANNOTATION, WPARAM(change.iItem as usize), LPARAM(0));
+                        }
+                    }
+                    // Full text of the note (the "Text" column) when hovering an entry
+                    LVN_GETINFOTIPW => {
+                        let tip = &mut *(lparam.0 as *mut NMLVGETINFOTIPW);
+                        let mut text = vec![0u16; tip.cchTextMax.max(1) as usize];
+                        let item = LVITEMW {
+                            iSubItem: 2,
+                            pszText: PWSTR(text.as_mut_ptr()),
+                            cchTextMax: text.len() as i32,
+                            ..Default::default()
+                        };
+                        let len = SendMessageW(list, LVM_GETITEMTEXTW, WPARAM(tip.iItem as usize), LPARAM(&item as *const _ as isize)).0 as usize;
+                        if len > 0 {
+                            std::ptr::copy_nonoverlapping(text.as_ptr(), tip.pszText.0, len + 1);
+                        }
+                    }
+                    LVN_KEYDOWN if (*(lparam.0 as *const NMLVKEYDOWN)).wVKey == VK_ESCAPE.0 => {
+                        ShowWindow(hwnd, SW_HIDE);
+                    }
+                    _ => {}
+                }
+                LRESULT(0)
+            }
+            WM_CLOSE => {
+                ShowWindow(hwnd, SW_HIDE);
+                LRESULT(0)
+            }
+            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+}
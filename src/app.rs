@@ -1,28 +1,66 @@
 use crate::{
+    annotations::{Annotation, AnnotationPanel, LinkTarget, WM_APP_ANNOTATION, WM_APP_ANNOTATIONS_READ},
+    archive::ArchiveLoader,
+    cancel::{CancelToken, E_CANCELLED},
+    capabilities::Capabilities,
+    clipboard::ClipboardContent,
+    contact_sheet::Grid as ContactSheetGrid,
     d2d::D2DRenderer,
-    dialogs::FileDialogs,
-    document::{Document, PageLayout},
-    menu::ContextMenu,
-    pdf::PdfLoader,
+    debug_overlay::{DebugOverlay, FrameStats},
+    dialogs::{FileDialogs, UnappliedChangesChoice},
+    download::TempDownload,
+    hover_toolbar::{HoverAction, HoverToolbar, HOVER_TOOLBAR_TIMER, TIMER_INTERVAL_MS},
+    blink::WM_APP_DIFFERENCE,
+    folder_scan::{FolderScan, WM_APP_FOLDER_SCANNED},
+    folder_stats::{FolderStats, WM_APP_FOLDER_STATS},
+    folder_search::{SearchEvent, SearchHit, SearchJob, SearchMessage, SearchPanel, SearchRequest, WM_APP_SEARCH, WM_APP_SEARCH_REQUEST},
+    batch_rotate::{RotateJob, RotateReport, WM_APP_BATCH_ROTATE},
+    ocr::{OcrOutput, OcrPage, WM_APP_OCR},
+    deskew::WM_APP_SKEW,
+    pdf::WM_APP_PDF_STRUCTURE,
+    file_watch::{FileChange, FileWatcher, WM_APP_FILE_CHANGED},
+    inbox::{InboxWatcher, WM_APP_INBOX_FILE},
+    ipc::{IpcCommand, IpcRequest, WM_APP_IPC_COMMAND},
+    keep_awake::KeepAwake,
+    document::{Document, PageLayout, PageSpacing, PageState},
+    load_error::LoadFailure,
+    menu::{ContextMenu, MenuState},
+    menu_bar::MenuBar,
+    minimap::Minimap,
+    operation_log::OperationLog,
+    page_picker::PagePicker,
+    page_transform::PageTransform,
+    pins::Pin,
+    pdf::{Password, PdfLoader, RenderResolution},
+    print_job::{PdfPrinter, PrintJob},
     scroll::{ScrollAction, ScrollManager, LINE_SCROLL_PIXELS, clamp_scroll},
-    statusbar::StatusBar,
-    theme::Theme,
+    settings::{RenderQuality, WheelMode},
+    statusbar::{StatusBar, WM_APP_PROGRESS},
+    theme::{SystemSettings, Theme},
     toolbar::{Toolbar, ToolbarCommand, ToolbarType},
+    update_check::{UpdateStatus, WM_APP_UPDATE_CHECK},
+    utils::natural_sort_key,
+    view_history::{ViewHistory, ViewSnapshot},
     view_window::{ViewWindow, WM_APP_VIEW_PAINT},
+    wallpaper::WallpaperPosition,
+    watermark::Watermark,
     wic::WicLoader,
     window::Window,
+    window_level::WindowLevel,
 };
 use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use windows::{
     core::*,
     Win32::{
         Foundation::*,
         Graphics::{Direct2D::Common::*, Gdi::*},
-        Storage::FileSystem::*,
-        System::{DataExchange::*, Memory::*, Ole::CF_DIB},
+        System::Memory::*,
         UI::{
-            Controls::Dialogs::*,
+            Controls::{Dialogs::*, TB_SETHOTITEM, WM_MOUSELEAVE},
+            HiDpi::{AdjustWindowRectExForDpi, GetDpiForWindow},
             Input::KeyboardAndMouse::*,
             WindowsAndMessaging::*,
         },
@@ -33,6 +71,42 @@ use windows::{
 pub const WM_APP_DOCUMENT_LOADED: u32 = WM_APP + 1;
 #[allow(dead_code)]
 pub const WM_APP_DOCUMENT_ERROR: u32 = WM_APP + 2;
+/// Render a pending PDF page (wparam = page index) after the placeholder was painted
+pub const WM_APP_RENDER_PAGE: u32 = WM_APP + 3;
+
+/// Extensions picked up when browsing or batch-processing a folder
+pub const FOLDER_EXTENSIONS: [&str; 14] = ["pdf", "jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp", "gif", "heic", "avif", "zip", "cbz", "dcm"];
+
+/// Timer ID on the main window that hides the OSD notice
+const OSD_TIMER: usize = 2;
+const OSD_DURATION_MS: u32 = 2500;
+
+/// Timer ID on the main window that hides the toolbar slid in by the Kompaktmodus once the
+/// mouse has left it
+const COMPACT_TOOLBAR_TIMER: usize = 3;
+const COMPACT_TOOLBAR_CHECK_MS: u32 = 250;
+/// Rows at the top of the client area that slide the toolbar in (96 DPI)
+const COMPACT_REVEAL_ZONE: i32 = 4;
+
+/// Loads shorter than this finish without showing the progress bar
+const PROGRESS_DELAY: Duration = Duration::from_millis(300);
+
+/// Lower bounds of the minimum window size at 96 DPI: client width before the bars have
+/// been measured, and the view height kept between them
+const MIN_CLIENT_WIDTH: i32 = 240;
+const MIN_VIEW_HEIGHT: i32 = 120;
+
+/// A dragged rectangle corner this close to a page border or center line snaps to it, in
+/// screen pixels whatever the zoom
+const SNAP_DISTANCE: f32 = 6.0;
+
+/// Line between the view and the status bar, the only part of the main window's client
+/// area it paints itself
+const SEPARATOR_HEIGHT: i32 = 1;
+const SEPARATOR_COLOR: COLORREF = COLORREF(0x00BFBFBF);
+
+/// Policy value; 0 closes and switches files without asking about unapplied changes
+const POLICY_CONFIRM_UNAPPLIED_CHANGES: PCWSTR = w!("ConfirmUnappliedChanges");
 
 /// Navigation context determines how Back/Next buttons behave
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -45,22 +119,81 @@ pub enum NavigationContext {
     DocumentPaging,
 }
 
+/// How the zoom is chosen. The fit modes are computed again when the window or the page
+/// changes; `Fixed` keeps its zoom.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZoomMode {
+    /// Whole page in the window; in the continuous view the widest page fits the width
+    FitPage,
+    FitWidth,
+    FitHeight,
+    Fixed(f32),
+}
+
+impl ZoomMode {
+    /// Shown before the zoom in the status bar, `None` for a fixed zoom
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            ZoomMode::FitPage => Some("Anpassen"),
+            ZoomMode::FitWidth => Some("Breite"),
+            ZoomMode::FitHeight => Some("Höhe"),
+            ZoomMode::Fixed(_) => None,
+        }
+    }
+}
+
+/// Size chip and snap guides while a selection or zoom rectangle is dragged (view coordinates)
+struct DragFeedback {
+    text: String,
+    cursor: (f32, f32),
+    guides: Vec<(D2D_POINT_2F, D2D_POINT_2F)>,
+}
+
+/// Rectangle selected with Shift+drag, in pixels of the unrotated source page
+#[derive(Clone, Copy, Debug)]
+pub struct Selection {
+    pub page: usize,
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// View carried over to the next file in folder navigation ("Ansicht beibehalten").
+/// The position is stored relative to a page so it can be mapped onto a new layout.
+#[derive(Clone, Copy, Debug)]
+struct KeptView {
+    zoom_mode: ZoomMode,
+    rotation: i32,
+    page: usize,
+    /// Viewport center as fraction of the (rotated) page, 0..1
+    center_u: f32,
+    center_v: f32,
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub document: Option<Document>,
-    pub zoom: f32,
+    pub zoom: f32, // Effective zoom, set through `App::set_zoom_mode`
+    pub zoom_mode: ZoomMode,
     pub rotation: i32, // 0, 90, 180, 270
+    // "Standarddrehung für neue Dokumente" the document was opened with; not an unapplied change
+    pub default_rotation: i32,
+    // Extra rotation of single pages (hover toolbar), added to `rotation`
+    pub page_rotations: HashMap<usize, i32>,
     pub current_page: usize,
     pub total_pages: usize,
     pub file_path: Option<String>,
     #[allow(dead_code)]
     pub is_dark_theme: bool,
-    pub fit_to_page: bool,
+    pub auto_crop: bool, // "Ränder automatisch zuschneiden": pages are shown without white margins
     // Folder navigation
     pub folder_files: Vec<String>,
     pub folder_file_index: usize,
     pub folder_navigation_mode: bool, // true = navigate files, false = navigate pages
     pub folder_cache_valid: bool,     // true if folder_files is populated and current
+    pub folder_recursive: bool,       // "Unterordner einbeziehen": folder_files comes from a recursive walk
+    pub folder_root: Option<String>,  // Start of the recursive walk, for relative names in the statusbar
     pub navigation_context: NavigationContext, // Current navigation mode
     // Scroll state
     pub scroll_x: i32,        // Horizontal scroll offset (in scaled pixels)
@@ -70,6 +203,24 @@ pub struct AppState {
     // Multi-page view mode
     pub multi_page_view: bool,           // true = show all pages stacked, false = single page
     pub page_layout: Option<PageLayout>, // Cached layout for multi-page view
+    // Load failure shown in the canvas during folder navigation
+    pub load_error: Option<LoadFailure>,
+    pub failed_files: HashSet<String>, // Files that failed to load, skipped by Back/Next
+    pub selection: Option<Selection>,
+    // Display-only edits (rotation) that closing or switching files would discard
+    pub has_unapplied_changes: bool,
+}
+
+impl AppState {
+    /// Effective rotation of a page: document rotation plus its own
+    pub fn page_rotation(&self, page: usize) -> i32 {
+        (self.rotation + self.page_rotations.get(&page).copied().unwrap_or(0)) % 360
+    }
+
+    /// Any rotation that is only applied to the display
+    pub fn is_rotated(&self) -> bool {
+        self.rotation != self.default_rotation || !self.page_rotations.is_empty()
+    }
 }
 
 impl Default for AppState {
@@ -77,13 +228,18 @@ impl Default for AppState {
         Self {
             document: None,
             zoom: 1.0,
+            zoom_mode: ZoomMode::FitPage, // Default to fit to page
             rotation: 0,
+            default_rotation: 0,
+            page_rotations: HashMap::new(),
             current_page: 0,
             total_pages: 1,
             file_path: None,
             is_dark_theme: false,
-            fit_to_page: true, // Default to fit to page
+            auto_crop: crate::settings::auto_crop(),
             folder_files: Vec::new(),
+            folder_recursive: crate::settings::include_subfolders(),
+            folder_root: None,
             folder_file_index: 0,
             folder_navigation_mode: true,
             folder_cache_valid: false,
@@ -94,6 +250,10 @@ impl Default for AppState {
             content_height: 0,
             multi_page_view: true, // Default to multi-page view for PDFs
             page_layout: None,
+            load_error: None,
+            failed_files: HashSet::new(),
+            selection: None,
+            has_unapplied_changes: false,
         }
     }
 }
@@ -125,6 +285,29 @@ impl Drop for WaitCursorGuard {
     }
 }
 
+/// Settings from the command line and registry policy, applied when the app starts
+pub struct StartupOptions {
+    pub file_to_open: Option<String>,
+    pub restricted_path: Option<String>,
+    /// 1-based page from --page / deep link
+    pub start_page: Option<usize>,
+    pub capabilities: Capabilities,
+    pub watermark: Option<Watermark>,
+    /// Scan inbox folder from --watch
+    pub watch_folder: Option<String>,
+    /// Open dialog defaults from --default-filter / --start-folder
+    pub default_filter: Option<String>,
+    pub start_folder: Option<String>,
+    /// --confirm-exit: ask "SimpliView beenden?" unless the user opted out
+    pub confirm_exit: bool,
+    /// --pdf-dpi: PDF pages render at this resolution instead of "Renderqualität"
+    pub pdf_dpi: Option<u32>,
+    /// --topmost: "Immer im Vordergrund" for this session, whatever the setting
+    pub topmost: bool,
+    /// --password / --password-stdin: tried first when opening `file_to_open`
+    pub password: Option<Password>,
+}
+
 pub struct App {
     window: Window,
     view_window: ViewWindow,
@@ -132,23 +315,162 @@ pub struct App {
     top_toolbar: Toolbar,
     statusbar: StatusBar,
     context_menu: ContextMenu,
+    // Datei/Ansicht/Hilfe, attached while "Menüleiste anzeigen" is on and not in fullscreen
+    menu_bar: MenuBar,
     wic_loader: WicLoader,
     pdf_loader: PdfLoader,
+    archive_loader: ArchiveLoader,
+    // "Animationen anzeigen" and high contrast of Windows
+    system_settings: SystemSettings,
     dialogs: FileDialogs,
+    // Temp copy of a document opened from a URL, deleted on drop
+    temp_download: Option<TempDownload>,
+    // "Seiten als Dateien kopieren": the files last put on the clipboard, deleted on drop
+    page_files: Option<crate::page_files::PageFiles>,
     state: Arc<Mutex<AppState>>,
+    // Export/Print/Clipboard permissions (view-only mode)
+    capabilities: Capabilities,
+    // Compliance watermark for display, print, export and clipboard
+    watermark: Option<Watermark>,
     scroll_manager: ScrollManager,
     file_to_open: Option<String>,
+    // 1-based page from --page / deep link, applied after the initial open
+    start_page: Option<usize>,
     opened_from_cmdline: bool,
     open_disabled: bool,
     // Drag-to-pan state
     is_dragging: bool,
     drag_start_mouse: (i32, i32),
     drag_start_scroll: (i32, i32),
+    // "Übersichtsleiste" setting, and the strip is being clicked or dragged
+    minimap: bool,
+    minimap_drag: bool,
+    // "Erneut versuchen" button of the in-canvas error panel (view coordinates)
+    retry_button_rect: Option<D2D_RECT_F>,
+    // Shift+drag selection: page and source point where the drag started
+    selection_anchor: Option<(usize, f32, f32)>,
+    // Timings and cache statistics (Ctrl+Shift+D)
+    debug_overlay: DebugOverlay,
+    // Recent operations for the details of error boxes
+    operation_log: OperationLog,
+    // Ctrl+drag window/level of 16-bit grayscale pages: start point and window at the start
+    window_level_drag: Option<((i32, i32), WindowLevel)>,
+    // Right-drag zoom rectangle: start and current point (view coordinates)
+    zoom_drag: Option<((i32, i32), (i32, i32))>,
+    // Size chip and snap guides of the selection or zoom rectangle being dragged
+    drag_feedback: Option<DragFeedback>,
+    // Focus border on the view, only after reaching it with the keyboard
+    view_focus_visible: bool,
+    // Ask before unapplied changes are discarded (policy ConfirmUnappliedChanges = 0 turns it off)
+    confirm_unapplied_changes: bool,
+    // Ask "SimpliView beenden?" on WM_CLOSE (setting ConfirmExit or --confirm-exit)
+    confirm_exit: bool,
+    // The next WM_CLOSE comes from the IPC "close" command and is not confirmed
+    ipc_close: bool,
+    // Rotate/copy bar on the hovered page in continuous mode
+    hover_toolbar: HoverToolbar,
+    // Last view in folder navigation while "Ansicht beibehalten" is on (survives load failures)
+    kept_view: Option<KeptView>,
+    // Scan inbox: watched folder, paused by manual navigation, newest arrival while paused
+    watch_folder: Option<String>,
+    inbox: Option<InboxWatcher>,
+    inbox_paused: bool,
+    inbox_pending: Option<String>,
+    // Set while the inbox opens a file on its own: a load error skips the file silently
+    unattended_open: bool,
+    // Wheel delta not yet turned into a page change ("Mausrad: Seitenwechsel")
+    wheel_delta: i32,
+    // Blinkvergleich: comparison page shown while B is held, the page marked with Shift+B
+    // (with the file it belongs to) and "Differenz beim Blinkvergleich" for this session
+    blink: Option<usize>,
+    blink_mark: Option<(Option<String>, usize)>,
+    blink_difference: bool,
+    // Difference image of the last pair, and the pair whose difference is being computed
+    difference: Option<(crate::blink::Pair, Document)>,
+    difference_pending: Option<crate::blink::Pair>,
+    // "Schräglage automatisch korrigieren": measured tilt of the pages of the open document
+    // (`None` = nothing to correct), the page being measured and the pages shown as they are
+    deskew: bool,
+    skews: HashMap<usize, Option<f32>>,
+    skew_pending: Option<usize>,
+    skew_off: HashSet<usize>,
+    // Links and labels of the open PDF are read on a worker thread (`pdf::read_structure`);
+    // number of the last request, so the result of an earlier load of the file is dropped
+    structure_request: u64,
+    // "Doppelseiten teilen", and where the double pages of the open document are split
+    // (share of their width, "Teilung anpassen...")
+    split_spreads: bool,
+    split_position: f32,
+    // "Seitenabstand": gap between the pages and margin above and below them in continuous mode
+    page_spacing: PageSpacing,
+    // Strg+Z / Strg+Y for zoom, fit, rotation and layout commands
+    view_history: ViewHistory,
+    // Short notice at the top of the view, hidden by OSD_TIMER
+    osd_message: Option<String>,
+    // Kompaktmodus: the toolbar is slid in while the mouse is at the top edge
+    compact_toolbar: bool,
+    // "Tastenkürzel" overview (F1) shown over the view; any key or click closes it
+    shortcut_overlay: bool,
+    // Recursive folder scan running; Back/Next clicked meanwhile (true = Next)
+    folder_scanning: bool,
+    pending_folder_step: Option<bool>,
+    // Cancel flag of a running "Ordnerstatistik" (see folder_stats.rs)
+    folder_stats: Option<Arc<std::sync::atomic::AtomicBool>>,
+    // Cancel flag of a running "Alle drehen" (see batch_rotate.rs)
+    batch_rotate: Option<Arc<std::sync::atomic::AtomicBool>>,
+    // "Text erkennen (OCR)" running, and the words it found, in source pixels like the selection
+    ocr_running: bool,
+    ocr_words: Vec<Selection>,
+    // "Ordner durchsuchen" window (created on first use), the matches listed in it, the
+    // running search with its cancel flag, the last search id and files searched of total
+    search_panel: Option<SearchPanel>,
+    search_hits: Vec<SearchHit>,
+    search: Option<(u32, Arc<std::sync::atomic::AtomicBool>)>,
+    search_id: u32,
+    search_progress: (usize, usize),
+    // "Ausschnitt anheften" windows of the open document, some possibly closed by the user
+    pins: Vec<Pin>,
+    // "Anmerkungen" window (created on first use), the listed annotations of the open PDF
+    // and the marker of the chosen one, in source pixels like the selection
+    annotation_panel: Option<AnnotationPanel>,
+    annotations: Vec<Annotation>,
+    annotation_marker: Option<Selection>,
+    // Number of the last "Anmerkungen" read started; results of older ones are dropped
+    annotations_request: u64,
+    // PDF links: web links may be opened (not in restricted or view-only deployments), the
+    // cursor is over a link, and the link pressed with its mouse position
+    external_links: bool,
+    link_hover: bool,
+    link_press: Option<((i32, i32), LinkTarget)>,
+    // --pdf-dpi until a "Renderqualität" is chosen
+    pdf_dpi: Option<u32>,
+    // Password tried before asking: the one of the PDF being reloaded (see reload_document)
+    // or the one handed over for the file from the command line
+    known_password: Option<Password>,
+    startup_password: Option<Password>,
+    // Watcher of the open file and the change another program made to it since it was loaded
+    file_watch: Option<FileWatcher>,
+    source_change: Option<FileChange>,
 }
 
 impl App {
     #[allow(clippy::arc_with_non_send_sync)]
-    pub fn new(file_to_open: Option<String>, restricted_path: Option<String>) -> Result<Self> {
+    pub fn new(options: StartupOptions) -> Result<Self> {
+        let StartupOptions {
+            file_to_open,
+            restricted_path,
+            start_page,
+            capabilities,
+            watermark,
+            watch_folder,
+            default_filter,
+            start_folder,
+            confirm_exit,
+            pdf_dpi,
+            topmost,
+            password,
+        } = options;
+
         // Always use light mode - using Arc for internal state sharing within App
         let state = Arc::new(Mutex::new(AppState {
             is_dark_theme: false,
@@ -156,10 +478,17 @@ impl App {
         }));
 
         // Create main window
-        let window = Window::new("SimpliView", state.clone())?;
+        let mut window = Window::new("SimpliView", state.clone())?;
+        if topmost || crate::settings::topmost() {
+            window.set_topmost(true);
+        }
+        if crate::settings::compact_mode() {
+            window.set_compact(true);
+        }
 
         // Create top toolbar
-        let top_toolbar = Toolbar::new(window.hwnd(), window.instance(), ToolbarType::Top)?;
+        let mut top_toolbar = Toolbar::new(window.hwnd(), window.instance(), ToolbarType::Top)?;
+        top_toolbar.set_capabilities(capabilities.export, capabilities.print);
 
         // Create view window (canvas) for Direct2D rendering
         let view_window = ViewWindow::new(window.hwnd(), window.instance())?;
@@ -168,17 +497,43 @@ impl App {
         let statusbar = StatusBar::new(window.hwnd(), window.instance())?;
 
         // Initialize Direct2D renderer targeting the view window
-        let renderer = D2DRenderer::new(view_window.hwnd())?;
-
-        // Create context menu
-        let context_menu = ContextMenu::new()?;
+        let mut renderer = D2DRenderer::new(view_window.hwnd())?;
+        renderer.set_watermark(watermark.clone());
+
+        let system_settings = SystemSettings::query();
+        let mut hover_toolbar = HoverToolbar::new(crate::settings::hover_toolbar());
+        hover_toolbar.set_animate(system_settings.animations);
+
+        // Create context menu (no wallpaper command in restricted or view-only deployments)
+        let context_menu = ContextMenu::new(
+            restricted_path.is_none() && capabilities.export,
+            restricted_path.is_none().then(crate::settings::reopen_last_file),
+            hover_toolbar.is_enabled(),
+            crate::settings::keep_view(),
+            crate::settings::inbox_sound(),
+            crate::settings::include_subfolders(),
+            crate::settings::escape_exits(),
+        )?;
+        context_menu.set_capabilities(capabilities.export, capabilities.print);
+        context_menu.set_checked(crate::menu::IDM_MENU_BAR, crate::settings::menu_bar());
+
+        let menu_bar = MenuBar::new(capabilities.export, capabilities.print)?;
+        menu_bar.set_visible(window.hwnd(), crate::settings::menu_bar());
 
         // Initialize image and PDF loaders
         let wic_loader = WicLoader::new()?;
+        wic_loader.set_keep_high_bit_depth(crate::settings::high_bit_depth());
         let pdf_loader = PdfLoader::new();
+        pdf_loader.set_resolution(match pdf_dpi {
+            Some(dpi) => RenderResolution::Dpi(dpi),
+            None => RenderResolution::from_quality(crate::settings::render_quality()),
+        });
+        let archive_loader = ArchiveLoader::new();
+
+        let external_links = restricted_path.is_none() && capabilities.export;
 
         // Initialize file dialogs
-        let dialogs = FileDialogs::new(restricted_path);
+        let dialogs = FileDialogs::new(restricted_path, default_filter, start_folder);
 
         // Create scroll manager attached to the view window
         let scroll_manager = ScrollManager::new(view_window.hwnd());
@@ -193,17 +548,87 @@ impl App {
             top_toolbar,
             statusbar,
             context_menu,
+            menu_bar,
             wic_loader,
             pdf_loader,
+            archive_loader,
+            system_settings,
             dialogs,
+            temp_download: None,
+            page_files: None,
             state,
+            capabilities,
+            watermark,
             scroll_manager,
             file_to_open,
+            start_page,
             opened_from_cmdline,
             open_disabled,
             is_dragging: false,
             drag_start_mouse: (0, 0),
             drag_start_scroll: (0, 0),
+            minimap: crate::settings::minimap(),
+            minimap_drag: false,
+            retry_button_rect: None,
+            selection_anchor: None,
+            debug_overlay: DebugOverlay::default(),
+            operation_log: OperationLog::default(),
+            window_level_drag: None,
+            zoom_drag: None,
+            drag_feedback: None,
+            view_focus_visible: false,
+            confirm_unapplied_changes: crate::policy::read_dword(POLICY_CONFIRM_UNAPPLIED_CHANGES) != Some(0),
+            confirm_exit: crate::settings::confirm_exit().unwrap_or(confirm_exit),
+            ipc_close: false,
+            hover_toolbar,
+            kept_view: None,
+            watch_folder,
+            inbox: None,
+            inbox_paused: false,
+            inbox_pending: None,
+            unattended_open: false,
+            wheel_delta: 0,
+            blink: None,
+            blink_mark: None,
+            blink_difference: false,
+            difference: None,
+            difference_pending: None,
+            deskew: crate::settings::deskew(),
+            skews: HashMap::new(),
+            skew_pending: None,
+            skew_off: HashSet::new(),
+            structure_request: 0,
+            split_spreads: crate::settings::split_spreads(),
+            page_spacing: crate::settings::page_spacing(),
+            split_position: 0.5,
+            view_history: ViewHistory::default(),
+            osd_message: None,
+            compact_toolbar: false,
+            shortcut_overlay: false,
+            folder_scanning: false,
+            pending_folder_step: None,
+            folder_stats: None,
+            batch_rotate: None,
+            ocr_running: false,
+            ocr_words: Vec::new(),
+            search_panel: None,
+            search_hits: Vec::new(),
+            search: None,
+            search_id: 0,
+            search_progress: (0, 0),
+            pins: Vec::new(),
+            annotation_panel: None,
+            annotations: Vec::new(),
+            annotation_marker: None,
+            annotations_request: 0,
+            external_links,
+            link_hover: false,
+            link_press: None,
+            pdf_dpi,
+            known_password: None,
+            startup_password: password,
+            file_watch: None,
+            source_change: None,
         })
     }
 
@@ -216,14 +641,23 @@ impl App {
         }
 
         // Show and update window
+        if self.window.is_compact() {
+            self.show_bars();
+        }
         self.window.show();
 
+        // Remote control from the practice software (named pipe, see ipc.rs)
+        let safe_mode = crate::safe_mode::is_active();
+        if !safe_mode {
+            crate::ipc::start_server(hwnd);
+        }
+
         // Apply initial theme
         self.apply_theme();
 
         // Disable document-dependent buttons until a document is loaded
         self.top_toolbar.set_document_loaded(false);
-        self.top_toolbar.set_navigation_enabled(false);
+        self.top_toolbar.set_navigation_buttons(false, false);
         self.statusbar.set_document_loaded(false);
         self.context_menu.set_document_loaded(false);
 
@@ -234,9 +668,28 @@ impl App {
 
         // If a file was passed via command line, open it
         if let Some(path) = self.file_to_open.take() {
+            self.known_password = self.startup_password.take();
+            self.open_document(&path);
+            self.known_password = None;
+            if let Some(page) = self.start_page.take() {
+                self.go_to_page(page - 1);
+            }
+        } else if let Some(path) = self.last_file_to_reopen().filter(|_| self.watch_folder.is_none()) {
             self.open_document(&path);
         }
 
+        // --watch: show the newest file of the inbox unless a file was given
+        if let Some(folder) = self.watch_folder.take().filter(|_| !safe_mode) {
+            let has_document = self.state.lock().document.is_some();
+            self.start_inbox(&folder, !has_document);
+        }
+
+        if safe_mode {
+            self.statusbar.set_safe_mode(true);
+            self.show_osd("Abgesicherter Modus: Einstellungen, Eingangsordner und Fernsteuerung sind deaktiviert");
+        }
+        crate::safe_mode::startup_finished();
+
         // Main Message loop
         unsafe {
             let mut msg = MSG::default();
@@ -268,21 +721,77 @@ impl App {
     }
 
     fn handle_accelerator(&mut self, msg: &MSG) -> bool {
+        // The "Ordner durchsuchen" window handles its own keys (typing, Tab, Enter, Esc)
+        if self.search_panel.as_ref().is_some_and(|panel| panel.is_dialog_message(msg)) {
+            return true;
+        }
+
+        // The key or click that closes the shortcut overview does nothing else
+        if self.shortcut_overlay
+            && matches!(msg.message, WM_KEYDOWN | WM_SYSKEYDOWN | WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN)
+        {
+            self.shortcut_overlay = false;
+            self.invalidate();
+            return true;
+        }
+
+        // Releasing B ends the Blinkvergleich, wherever the focus is
+        if msg.message == WM_KEYUP && VIRTUAL_KEY(msg.wParam.0 as u16) == VK_B {
+            self.end_blink();
+        }
+
         if msg.message == WM_KEYDOWN {
             let key = VIRTUAL_KEY(msg.wParam.0 as u16);
             let ctrl = unsafe { GetKeyState(VK_CONTROL.0 as i32) } < 0;
 
+            // Tab / F6 (Shift = backwards) cycle the focus: toolbar -> document view -> status bar
+            if !ctrl && (key == VK_TAB || key == VK_F6) {
+                self.cycle_focus(unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0);
+                return true;
+            }
+
+            // A focused toolbar handles arrows, Enter and Space itself
+            let focus = unsafe { GetFocus() };
+            if !ctrl && (focus == self.top_toolbar.toolbar_hwnd() || focus == self.statusbar.toolbar_hwnd()) {
+                return false;
+            }
+
             match (ctrl, key) {
                 // Ctrl+O -> Open (disabled if file was passed via command line)
                 (true, VK_O) => { if !self.open_disabled { self.cmd_open(); } return true; }
-                // Ctrl+E -> Export
-                (true, VK_E) => { self.cmd_export(); return true; }
-                // Ctrl+C -> Copy to clipboard
-                (true, VK_C) => { self.cmd_copy_to_clipboard(); return true; }
-                // Ctrl+P -> Print
-                (true, VK_P) => { self.cmd_print(); return true; }
+                // Ctrl+E -> Export (ignored in view-only mode)
+                (true, VK_E) => { if self.capabilities.allows_shortcut(key) { self.cmd_export(); } return true; }
+                // Esc -> Cancel the innermost active mode (see handle_escape)
+                (false, VK_ESCAPE) => { self.handle_escape(); return true; }
+                // F1 -> Shortcut overview
+                (false, VK_F1) => { self.cmd_shortcuts(); return true; }
+                // F11 -> Fullscreen
+                (false, VK_F11) => { self.cmd_toggle_fullscreen(); return true; }
+                // F9 -> Compact mode
+                (false, VK_F9) => { self.cmd_toggle_compact(); return true; }
+                // Ctrl+Shift+T -> Always on top
+                (true, VK_T) if unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0 => { self.cmd_toggle_topmost(); return true; }
+                // Ctrl+Shift+D -> Debug overlay
+                (true, VK_D) if unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0 => {
+                    self.debug_overlay.toggle();
+                    self.invalidate();
+                    return true;
+                }
+                // Ctrl+C -> Copy to clipboard (ignored in view-only mode)
+                (true, VK_C) => { if self.capabilities.allows_shortcut(key) { self.cmd_copy_to_clipboard(); } return true; }
+                // Ctrl+V -> Paste image or copied file (disabled like Open)
+                (true, VK_V) => { if !self.open_disabled { self.cmd_paste(); } return true; }
+                // Ctrl+P -> Print (ignored in view-only mode)
+                (true, VK_P) => { if self.capabilities.allows_shortcut(key) { self.cmd_print(); } return true; }
+                // Ctrl+Shift+R -> Rotate 180°
+                (true, VK_R) if unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0 => { self.cmd_rotate_180(); return true; }
                 // Ctrl+Left -> Rotate left
                 (true, VK_LEFT) => { self.cmd_rotate_left(); return true; }
+                // Ctrl+Z -> Undo the last view change, Ctrl+Y or Ctrl+Shift+Z -> Redo
+                (true, VK_Z) => { self.restore_view(unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0); return true; }
+                (true, VK_Y) => { self.restore_view(true); return true; }
+                // Ctrl+G -> Go to page (number or PDF page label)
+                (true, VK_G) => { self.cmd_go_to_page(); return true; }
                 // Ctrl+Right -> Rotate right
                 (true, VK_RIGHT) => { self.cmd_rotate_right(); return true; }
                 // Numpad + -> Zoom in
@@ -298,6 +807,16 @@ impl App {
                 (false, VK_RIGHT) | (false, VK_NEXT) => { self.cmd_next_page(); return true; }
                 (false, VK_HOME) => { self.cmd_first_page(); return true; }
                 (false, VK_END) => { self.cmd_last_page(); return true; }
+                // B (held) -> Blinkvergleich, Shift+B -> mark the comparison page; the key
+                // repeat (lParam bit 30) keeps the comparison on screen
+                (false, VK_B) => {
+                    if unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0 {
+                        self.cmd_mark_blink_page();
+                    } else if msg.lParam.0 & (1 << 30) == 0 {
+                        self.start_blink();
+                    }
+                    return true;
+                }
                 _ => {}
             }
         }
@@ -344,9 +863,12 @@ impl App {
     }
 
     fn execute_toolbar_command(&mut self, cmd: ToolbarCommand) {
+        if !self.capabilities.allows(cmd) {
+            return;
+        }
         match cmd {
             ToolbarCommand::Open => self.cmd_open(),
-            ToolbarCommand::Export => self.cmd_export(),
+            ToolbarCommand::Export => { self.cmd_export(); }
             ToolbarCommand::RotateLeft => self.cmd_rotate_left(),
             ToolbarCommand::RotateRight => self.cmd_rotate_right(),
             ToolbarCommand::PrevPage => self.cmd_prev_page(),
@@ -365,11 +887,14 @@ impl App {
                 self.on_resize(width, height);
                 Some(LRESULT(0))
             }
+            WM_GETMINMAXINFO => {
+                self.on_get_min_max_info(lparam);
+                Some(LRESULT(0))
+            }
             WM_PAINT => {
-                // Main window paint - just validate. View window handles its own paint.
-                unsafe {
-                     let _ = ValidateRect(self.window.hwnd(), None);
-                }
+                // Main window paint - only the separator, WS_CLIPCHILDREN keeps it off the
+                // children. View window handles its own paint.
+                self.paint_separator();
                 Some(LRESULT(0))
             }
             WM_APP_VIEW_PAINT => {
@@ -377,12 +902,49 @@ impl App {
                 self.on_paint();
                 Some(LRESULT(0))
             }
+            WM_APP_RENDER_PAGE => {
+                self.render_pending_page(wparam.0);
+                Some(LRESULT(0))
+            }
+            WM_APP_IPC_COMMAND => {
+                let request = unsafe { crate::ipc::take_request(lparam) };
+                self.handle_ipc_command(&request);
+                Some(LRESULT(0))
+            }
+            WM_SYSCOMMAND
+                if crate::keep_awake::is_active()
+                    && matches!(wparam.0 as u32 & 0xFFF0, SC_SCREENSAVE | SC_MONITORPOWER) =>
+            {
+                // No screen saver or monitor power-off during a print job or presentation
+                Some(LRESULT(0))
+            }
+            WM_CLOSE => {
+                // Programmatic closes from the IPC interface are never confirmed
+                let ipc_close = std::mem::take(&mut self.ipc_close);
+                if !ipc_close && !self.confirm_close() {
+                    return Some(LRESULT(0));
+                }
+                // Returning None lets DefWindowProc destroy the window
+                if self.confirm_discard_changes() { None } else { Some(LRESULT(0)) }
+            }
+            WM_SETFOCUS => {
+                // Activating the window puts the keyboard focus into the document view
+                unsafe {
+                    SetFocus(self.view_window.hwnd());
+                }
+                Some(LRESULT(0))
+            }
             WM_CONTEXTMENU => {
                 let x = (lparam.0 & 0xFFFF) as i16 as i32;
                 let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+                self.context_menu.refresh(&self.menu_state());
                 self.context_menu.show(self.window.hwnd(), x, y);
                 Some(LRESULT(0))
             }
+            WM_INITMENUPOPUP => {
+                self.refresh_menu_bar();
+                Some(LRESULT(0))
+            }
             WM_MOUSEWHEEL => {
                 self.handle_mouse_wheel(wparam);
                 Some(LRESULT(0))
@@ -400,19 +962,126 @@ impl App {
                 Some(LRESULT(0))
             }
             WM_LBUTTONUP => {
-                self.handle_lbutton_up();
+                self.handle_lbutton_up(lparam);
+                Some(LRESULT(0))
+            }
+            WM_RBUTTONDOWN => {
+                self.handle_rbutton_down(lparam);
                 Some(LRESULT(0))
             }
+            WM_RBUTTONUP => {
+                // 1 = consumed as zoom rectangle, no context menu
+                Some(LRESULT(self.handle_rbutton_up() as isize))
+            }
             WM_MOUSEMOVE => {
                 self.handle_mouse_move(lparam);
                 Some(LRESULT(0))
             }
+            WM_MOUSELEAVE => {
+                self.track_hover_toolbar(None);
+                Some(LRESULT(0))
+            }
             WM_CAPTURECHANGED => {
                 self.handle_capture_changed();
                 Some(LRESULT(0))
             }
+            WM_APP_FOLDER_SCANNED => {
+                let scan = unsafe { crate::folder_scan::take_result(lparam) };
+                self.on_folder_scanned(scan);
+                Some(LRESULT(0))
+            }
+            WM_APP_FOLDER_STATS => {
+                let stats = unsafe { crate::folder_stats::take_result(lparam) };
+                self.on_folder_stats(stats);
+                Some(LRESULT(0))
+            }
+            WM_APP_BATCH_ROTATE => {
+                let report = unsafe { crate::batch_rotate::take_result(lparam) };
+                self.on_batch_rotate(report);
+                Some(LRESULT(0))
+            }
+            WM_APP_OCR => {
+                let output = unsafe { crate::ocr::take_result(lparam) };
+                self.on_ocr(output);
+                Some(LRESULT(0))
+            }
+            WM_APP_SKEW => {
+                let skew = unsafe { crate::deskew::take_result(lparam) };
+                self.on_skew(skew);
+                Some(LRESULT(0))
+            }
+            WM_APP_PDF_STRUCTURE => {
+                let result = unsafe { crate::pdf::take_structure(lparam) };
+                self.on_pdf_structure(result);
+                Some(LRESULT(0))
+            }
+            WM_APP_ANNOTATION => {
+                self.on_annotation_selected(wparam.0);
+                Some(LRESULT(0))
+            }
+            WM_APP_ANNOTATIONS_READ => {
+                let result = unsafe { crate::annotations::take_result(lparam) };
+                self.on_annotations_read(result);
+                Some(LRESULT(0))
+            }
+            WM_APP_SEARCH => {
+                let message = unsafe { crate::folder_search::take_message(lparam) };
+                self.on_search_message(message);
+                Some(LRESULT(0))
+            }
+            WM_APP_SEARCH_REQUEST => {
+                self.on_search_request(SearchRequest::from_wparam(wparam), lparam.0 as usize);
+                Some(LRESULT(0))
+            }
+            WM_APP_DIFFERENCE => {
+                let difference = unsafe { crate::blink::take_result(lparam) };
+                self.on_difference(difference);
+                Some(LRESULT(0))
+            }
+            WM_ACTIVATEAPP if wparam.0 == 0 => {
+                // The key-up of B goes to another application
+                self.end_blink();
+                None
+            }
+            WM_APP_UPDATE_CHECK => {
+                let status = unsafe { crate::update_check::take_result(lparam) };
+                self.on_update_check(status);
+                Some(LRESULT(0))
+            }
+            WM_APP_PROGRESS => {
+                match usize::try_from(lparam.0) {
+                    Ok(total) => self.statusbar.set_progress(wparam.0, total),
+                    Err(_) => self.statusbar.clear_progress(),
+                }
+                Some(LRESULT(0))
+            }
+            WM_APP_INBOX_FILE => {
+                let path = unsafe { crate::inbox::take_path(lparam) };
+                self.on_inbox_file(path);
+                Some(LRESULT(0))
+            }
+            WM_APP_FILE_CHANGED => {
+                let path = unsafe { crate::file_watch::take_path(lparam) };
+                self.on_file_changed(&path, FileChange::from_wparam(wparam));
+                Some(LRESULT(0))
+            }
+            WM_TIMER if wparam.0 == OSD_TIMER => {
+                self.hide_osd();
+                Some(LRESULT(0))
+            }
+            WM_TIMER if wparam.0 == COMPACT_TOOLBAR_TIMER => {
+                self.check_compact_toolbar();
+                Some(LRESULT(0))
+            }
+            WM_TIMER if wparam.0 == HOVER_TOOLBAR_TIMER => {
+                if self.hover_toolbar.tick() {
+                    self.invalidate();
+                }
+                self.update_hover_toolbar_timer();
+                Some(LRESULT(0))
+            }
             WM_SETCURSOR => {
-                if self.handle_set_cursor(lparam) {
+                if self.handle_set_cursor(wparam, lparam) {
                     Some(LRESULT(1))
                 } else {
                     None
@@ -432,11 +1101,16 @@ impl App {
                 }
                 None
             }
+            WM_DISPLAYCHANGE => {
+                self.on_display_change();
+                Some(LRESULT(0))
+            }
             WM_DPICHANGED => {
                 self.on_dpi_changed(lparam);
                 Some(LRESULT(0))
             }
-            WM_SETTINGCHANGE => {
+            WM_SETTINGCHANGE | WM_THEMECHANGED => {
+                self.on_system_settings_changed();
                 Some(LRESULT(0))
             }
             _ => None,
@@ -444,9 +1118,13 @@ impl App {
     }
 
     fn handle_command(&mut self, cmd_id: u16) {
+        // Export, print and clipboard commands are dropped in view-only mode
+        if !self.capabilities.allows_command(cmd_id) {
+            return;
+        }
         match cmd_id {
             100 => self.cmd_open(),
-            101 => self.cmd_export(),
+            101 => { self.cmd_export(); }
             102 => self.cmd_rotate_left(),
             103 => self.cmd_rotate_right(),
             104 => self.cmd_prev_page(),
@@ -455,17 +1133,92 @@ impl App {
             107 => self.cmd_close(),
             108 => self.cmd_print(),
             // Context menu commands
-            200 => self.cmd_fit_to_page(),
-            201 => self.cmd_rotate_left(),
-            202 => self.cmd_rotate_right(),
+            203 => self.cmd_toggle_reopen_last_file(),
+            204 => self.cmd_toggle_hover_toolbar(),
+            205 => self.cmd_toggle_keep_view(),
+            206 => self.cmd_toggle_inbox(),
+            207 => self.cmd_toggle_inbox_sound(),
+            208 => self.cmd_toggle_include_subfolders(),
+            209 => self.cmd_toggle_escape_exits(),
+            240 => self.set_wheel_mode(WheelMode::Scroll),
+            241 => self.set_wheel_mode(WheelMode::Zoom),
+            242 => self.set_wheel_mode(WheelMode::PageChange),
+            243 => crate::settings::set_invert_wheel(!crate::settings::invert_wheel()),
+            244 => self.set_render_quality(RenderQuality::Fast),
+            245 => self.set_render_quality(RenderQuality::Standard),
+            246 => self.set_render_quality(RenderQuality::High),
+            247 => self.set_render_quality(RenderQuality::Screen),
+            250..=254 => {
+                if let Some(zoom) = crate::menu::zoom_preset(cmd_id as u32) {
+                    self.set_zoom(zoom);
+                }
+            }
+            255 => self.cmd_zoom_custom(),
+            256 => self.cmd_fit_to_width(),
+            257 => self.cmd_fit_to_height(),
+            258 => self.cmd_fit_to_page(),
+            260 => self.set_continuous_view(true),
+            261 => self.set_continuous_view(false),
+            262 => self.cmd_toggle_fullscreen(),
+            263 => self.cmd_toggle_menu_bar(),
+            264 => self.cmd_toggle_high_bit_depth(),
+            265 => self.cmd_reset_window_level(),
+            266 => self.blink_difference = !self.blink_difference,
+            267 => self.restore_view(false),
+            268 => self.restore_view(true),
+            269 => self.cmd_annotations(),
+            270 => self.cmd_rotate_left(),
+            271 => self.cmd_rotate_right(),
+            272 => { self.cmd_export(); }
+            276 => self.cmd_contact_sheet(),
+            278 => self.cmd_extract_pages(),
+            298 => self.cmd_copy_page_files(),
+            279 => self.cmd_go_to_page(),
+            273 => self.cmd_print(),
+            299 => self.cmd_print_to_pdf(),
+            274 => self.cmd_properties(),
+            277 => self.cmd_folder_stats(),
+            248 => self.cmd_folder_search(),
+            305 => self.cmd_clear_text_index(),
+            249 => self.cmd_toggle_topmost(),
+            291 => self.cmd_toggle_compact(),
+            293 => self.cmd_toggle_auto_crop(),
+            294 => self.cmd_toggle_deskew(),
+            295 => self.cmd_toggle_page_deskew(),
+            296 => self.cmd_toggle_split_spreads(),
+            330 => self.set_page_spacing(PageSpacing::NARROW),
+            331 => self.set_page_spacing(PageSpacing::NORMAL),
+            332 => self.set_page_spacing(PageSpacing::WIDE),
+            333 => self.cmd_toggle_minimap(),
+            297 => self.cmd_adjust_split(),
+            292 => self.cmd_pin_selection(),
+            238 => self.cmd_batch_rotate(),
+            239 => self.cmd_ocr(),
+            275 => self.cmd_rotate_180(),
+            280..=287 => self.cmd_open_recent((cmd_id as u32 - crate::menu_bar::IDM_RECENT_FIRST) as usize),
+            290 => self.cmd_shortcuts(),
             // Statusbar zoom commands
             300 => self.cmd_zoom_out(),
             301 => self.cmd_zoom_in(),
             302 => self.cmd_fit_to_page(),
             303 => self.cmd_fit_to_height(),
             304 => self.cmd_fit_to_width(),
+            320..=327 => {
+                if let Some((pdf, rotation)) = crate::menu::default_rotation_command(cmd_id as u32) {
+                    crate::settings::set_default_rotation(pdf, rotation);
+                }
+            }
             399 => self.cmd_zoom_reset(),
-            _ => {}
+            402 => self.cmd_resume_inbox(),
+            403 => self.cmd_reset_settings(),
+            404 => self.reload_document(),
+            405 => self.statusbar.set_file_changed(false),
+            // Context menu wallpaper commands (monitor/position encoded in the ID)
+            id => {
+                if let Some((monitor, position)) = crate::menu::wallpaper_command(id as u32) {
+                    self.cmd_set_wallpaper(monitor, position);
+                }
+            }
         }
     }
 
@@ -474,23 +1227,34 @@ impl App {
         // [Top Toolbar]
         // [View Window]
         // [Status Bar]
+        // The menu bar is non-client area: showing it shrinks `height`, hiding it grows it
 
-        let top_height = self.top_toolbar.height();
-        let status_height = self.statusbar.height();
+        // Fullscreen hides both bars, the Kompaktmodus the toolbar until it is slid in
+        let top_height = if self.toolbar_shown() { self.top_toolbar.height() } else { 0 };
+        let separator = self.separator_rect(width, height);
+        let status_height = if self.statusbar_shown() { self.statusbar.height() + SEPARATOR_HEIGHT } else { 0 };
         let view_height = (height - top_height - status_height).max(0);
 
-        self.top_toolbar.resize(width, 0);
-        self.view_window.resize(0, top_height, width, view_height);
-        self.statusbar.resize(width, height);
+        // Move all children in one step, so no frame shows them half moved
+        unsafe {
+            let hdwp = BeginDeferWindowPos(3)
+                .and_then(|hdwp| self.top_toolbar.defer_resize(hdwp, width, 0))
+                .and_then(|hdwp| self.view_window.defer_resize(hdwp, 0, top_height, width, view_height))
+                .and_then(|hdwp| self.statusbar.defer_resize(hdwp, width, height));
+            if let Ok(hdwp) = hdwp {
+                EndDeferWindowPos(hdwp);
+            }
+            if let Some(rect) = separator {
+                let _ = InvalidateRect(self.window.hwnd(), Some(&rect), false);
+            }
+        }
 
         if width > 0 && view_height > 0 {
             let _ = self.renderer.resize(width as u32, view_height as u32);
         }
 
-        // If fit-to-page is enabled, we must recalculate zoom when window size changes
-        if self.state.lock().fit_to_page {
-            self.calculate_fit_zoom();
-        }
+        // A fit mode must be recalculated when the window size changes
+        self.reapply_zoom_mode();
 
         // Update content size (scrolling range) and scrollbars
         self.update_content_size();
@@ -498,13 +1262,38 @@ impl App {
         self.invalidate();
     }
 
+    /// The separator above the status bar, `None` while the status bar is hidden
+    fn separator_rect(&self, width: i32, height: i32) -> Option<RECT> {
+        self.statusbar_shown().then(|| {
+            let bottom = height - self.statusbar.height();
+            RECT { left: 0, top: bottom - SEPARATOR_HEIGHT, right: width, bottom }
+        })
+    }
+
+    fn paint_separator(&self) {
+        unsafe {
+            let hwnd = self.window.hwnd();
+            let mut ps = PAINTSTRUCT::default();
+            let hdc = BeginPaint(hwnd, &mut ps);
+            let mut client = RECT::default();
+            let _ = GetClientRect(hwnd, &mut client);
+            if let Some(rect) = self.separator_rect(client.right, client.bottom) {
+                let brush = CreateSolidBrush(self.system_settings.separator_color(SEPARATOR_COLOR));
+                FillRect(hdc, &rect, brush);
+                let _ = DeleteObject(brush);
+            }
+            let _ = EndPaint(hwnd, &ps);
+        }
+    }
+
     fn on_paint(&mut self) {
         // Lock state once for the frame
         let state = self.state.lock().clone();
+        let frame_start = Instant::now();
 
         if self.renderer.begin_draw().is_ok() {
             // Clear background (anthracite)
-            let bg_color = D2D1_COLOR_F { r: 0.22, g: 0.23, b: 0.25, a: 1.0 };
+            let bg_color = self.system_settings.canvas_color(crate::d2d::BACKGROUND_COLOR);
             self.renderer.clear(bg_color);
 
             if let Some(ref doc) = state.document {
@@ -515,9 +1304,9 @@ impl App {
                             doc,
                             layout,
                             state.zoom,
-                            state.rotation,
                             state.scroll_x,
                             state.scroll_y,
+                            |page| self.page_skew(page),
                         );
                     }
                 } else {
@@ -525,20 +1314,199 @@ impl App {
                     let _ = self.renderer.draw_document(
                         doc,
                         state.zoom,
-                        state.rotation,
+                        &self.page_transform(&state, state.current_page),
                         state.current_page,
                         state.scroll_x,
                         state.scroll_y,
                     );
                 }
+                if let Some(partner) = self.blink {
+                    self.draw_blink(&state, doc, partner, bg_color);
+                }
+            }
+
+            if let Some(rect) = state.selection.and_then(|sel| self.selection_view_rect(&state, &sel)) {
+                let _ = self.renderer.draw_selection(&rect);
+            }
+
+            if let Some(rect) = self.annotation_marker.and_then(|marker| self.selection_view_rect(&state, &marker)) {
+                let _ = self.renderer.draw_annotation_marker(&rect);
+            }
+
+            if !self.ocr_words.is_empty() {
+                let rects: Vec<D2D_RECT_F> = self.ocr_words.iter().filter_map(|word| self.selection_view_rect(&state, word)).collect();
+                let _ = self.renderer.draw_ocr_words(&rects);
+            }
+
+            if let Some(rect) = self.zoom_drag_rect() {
+                let _ = self.renderer.draw_selection(&rect);
+            }
+
+            if let Some(ref feedback) = self.drag_feedback {
+                let _ = self.renderer.draw_snap_guides(&feedback.guides);
+                let _ = self.renderer.draw_info_chip(&feedback.text, feedback.cursor.0, feedback.cursor.1);
+            }
+
+            if let Some((minimap, layout)) = self.minimap(&state).zip(state.page_layout.as_ref()) {
+                let _ = self.renderer.draw_minimap(&minimap, layout, state.scroll_y);
+            }
+
+            let mut hover_buttons = Vec::new();
+            if let Some((page, opacity)) = self.hover_toolbar.visible_page() {
+                if let Some(rect) = self.page_view_rect(&state, page).filter(|_| state.page_layout.is_some()) {
+                    hover_buttons = self.renderer.draw_hover_toolbar(&rect, &self.hover_actions(), opacity).unwrap_or_default();
+                }
+            }
+            self.hover_toolbar.set_buttons(hover_buttons);
+
+            self.retry_button_rect = None;
+            if let Some(ref failure) = state.load_error {
+                let filename = std::path::Path::new(&failure.path).file_name().and_then(|n| n.to_str()).unwrap_or("");
+                self.retry_button_rect = self.renderer.draw_error_panel(filename, &failure.message).ok().flatten();
+            }
+
+            if self.view_focus_visible && unsafe { GetFocus() } == self.view_window.hwnd() {
+                let _ = self.renderer.draw_focus_border();
+            }
+
+            if let Some(ref message) = self.osd_message {
+                let _ = self.renderer.draw_osd(message);
+            }
+
+            if self.shortcut_overlay {
+                let groups = crate::shortcuts::active_groups(&self.capabilities, !self.open_disabled);
+                let _ = self.renderer.draw_shortcut_overlay(&groups);
             }
 
-            // Draw 1px separator line at the bottom (above statusbar)
-            let separator_color = D2D1_COLOR_F { r: 0.75, g: 0.75, b: 0.75, a: 1.0 };
-            self.renderer.draw_bottom_separator(separator_color);
+            if self.debug_overlay.is_enabled() {
+                let (cache_entries, cache_bytes) = state.document.as_ref().map_or((0, 0), |doc| doc.cache_stats());
+                let stats = FrameStats {
+                    pages_drawn: self.renderer.pages_drawn(),
+                    cache_entries,
+                    cache_bytes,
+                    zoom: state.zoom,
+                    scroll_x: state.scroll_x,
+                    scroll_y: state.scroll_y,
+                    dpi: unsafe { GetDpiForWindow(self.window.hwnd()) },
+                };
+                let _ = self.renderer.draw_debug_overlay(self.debug_overlay.text(&stats));
+            }
 
             let _ = self.renderer.end_draw();
+            self.debug_overlay.set_frame_time(frame_start.elapsed());
+        }
+
+        self.view_window.set_accessible_name(&Self::accessible_name(&state));
+        let scroll_range = state.content_height - self.renderer.size().1 as i32;
+        let scroll_percent = (state.document.is_some() && scroll_range > 0)
+            .then(|| (state.scroll_y.clamp(0, scroll_range) as f32 * 100.0 / scroll_range as f32).round() as u32);
+        self.statusbar.set_scroll_percent(scroll_percent);
+
+        // Pages rendered since the layout was made are measured for "Ränder automatisch
+        // zuschneiden" now, and the layout follows their size
+        let remeasured = state.auto_crop
+            && state.document.as_ref().is_some_and(|doc| {
+                for page in Self::visible_pages(&state, self.renderer.size().1 as i32) {
+                    doc.display_rect(page, true);
+                }
+                doc.take_new_content_rects()
+            });
+
+        // Placeholders were drawn for pages not rendered yet - render the first one next
+        if let Some(page) = Self::first_pending_page(&state, self.renderer.size().1 as i32) {
+            unsafe {
+                let _ = PostMessageW(self.window.hwnd(), WM_APP_RENDER_PAGE, WPARAM(page), LPARAM(0));
+            }
+        }
+        if remeasured {
+            self.refit();
+        }
+        if self.deskew && self.skew_pending.is_none() {
+            self.measure_skew(&state);
+        }
+    }
+
+    /// Screen-reader name of the document view
+    fn accessible_name(state: &AppState) -> String {
+        if state.load_error.is_some() {
+            return "Dokumentansicht, Datei konnte nicht geöffnet werden".to_string();
+        }
+        match state.document {
+            Some(_) => format!(
+                "Dokumentansicht, Seite {} von {}, {}",
+                state.current_page + 1,
+                state.total_pages,
+                crate::statusbar::zoom_text(state.zoom)
+            ),
+            None => "Dokumentansicht, kein Dokument geöffnet".to_string(),
+        }
+    }
+
+    /// Pages in the view, none without a document
+    fn visible_pages(state: &AppState, viewport_height: i32) -> std::ops::Range<usize> {
+        let Some(ref doc) = state.document else { return 0..0 };
+        match state.page_layout {
+            Some(ref layout) if state.multi_page_view && state.total_pages > 1 => {
+                let (first, last) = doc.find_visible_pages(layout, state.scroll_y, viewport_height);
+                first..last
+            }
+            _ => state.current_page..state.current_page + 1,
+        }
+    }
+
+    /// First visible PDF page that still has to be rendered
+    fn first_pending_page(state: &AppState, viewport_height: i32) -> Option<usize> {
+        let doc = state.document.as_ref()?;
+        Self::visible_pages(state, viewport_height).find(|&page| doc.page_state(page) == PageState::Pending)
+    }
+
+    /// Renders a pending page into the current document and repaints
+    fn render_pending_page(&mut self, page: usize) {
+        {
+            let state = self.state.lock();
+            match state.document {
+                Some(ref doc) if doc.page_state(page) == PageState::Pending => doc.render_page(page),
+                _ => return,
+            }
+        }
+        self.invalidate();
+    }
+
+    /// Smallest window that shows the essential toolbar buttons and all status bar sections
+    /// without overlap. The widths come from the last layout, so they follow the DPI.
+    fn on_get_min_max_info(&self, lparam: LPARAM) {
+        let hwnd = self.window.hwnd();
+        unsafe {
+            let dpi = GetDpiForWindow(hwnd).max(96);
+            let scale = |px: i32| px * dpi as i32 / 96;
+            let client_width = self
+                .top_toolbar
+                .min_width()
+                .max(self.statusbar.min_width())
+                .max(scale(MIN_CLIENT_WIDTH));
+            let client_height = self.top_toolbar.height() + self.statusbar.height() + SEPARATOR_HEIGHT + scale(MIN_VIEW_HEIGHT);
+
+            let mut rect = RECT { left: 0, top: 0, right: client_width, bottom: client_height };
+            let style = WINDOW_STYLE(GetWindowLongW(hwnd, GWL_STYLE) as u32);
+            let ex_style = WINDOW_EX_STYLE(GetWindowLongW(hwnd, GWL_EXSTYLE) as u32);
+            let has_menu = GetMenu(hwnd).0 != 0;
+            let _ = AdjustWindowRectExForDpi(&mut rect, style, has_menu, ex_style, dpi);
+
+            let mmi = &mut *(lparam.0 as *mut MINMAXINFO);
+            mmi.ptMinTrackSize.x = rect.right - rect.left;
+            mmi.ptMinTrackSize.y = rect.bottom - rect.top;
+        }
+    }
+
+    /// Monitors were added, removed or changed resolution (WM_DISPLAYCHANGE). Fullscreen
+    /// ends if its monitor is gone, an off-screen window comes back onto the nearest work
+    /// area, and the bars are measured again since the DPI may have changed.
+    fn on_display_change(&mut self) {
+        if self.window.is_fullscreen() && !self.window.is_on_screen() {
+            self.cmd_toggle_fullscreen();
         }
+        self.window.fit_to_monitors();
+        self.relayout();
     }
 
     fn on_dpi_changed(&mut self, lparam: LPARAM) {
@@ -622,10 +1590,27 @@ impl App {
         let ctrl_down = unsafe { GetKeyState(VK_CONTROL.0 as i32) } < 0;
         let shift_down = unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0;
 
-        if ctrl_down {
-            // Ctrl+Wheel = Zoom
-            if delta > 0 { self.cmd_zoom_in(); } else { self.cmd_zoom_out(); }
-            return;
+        // "Mausrad" setting; Ctrl+Wheel switches between scrolling and zooming
+        let mode = match (crate::settings::wheel_mode(), ctrl_down) {
+            (mode, false) => mode,
+            (WheelMode::Zoom, true) => WheelMode::Scroll,
+            (_, true) => WheelMode::Zoom,
+        };
+        if mode != WheelMode::PageChange {
+            self.wheel_delta = 0;
+        }
+        let delta = if crate::settings::invert_wheel() && mode != WheelMode::Zoom { -delta } else { delta };
+
+        match mode {
+            WheelMode::Zoom => {
+                if delta > 0 { self.cmd_zoom_in(); } else { self.cmd_zoom_out(); }
+                return;
+            }
+            WheelMode::PageChange => {
+                self.change_page_by_wheel(delta as i32);
+                return;
+            }
+            WheelMode::Scroll => {}
         }
 
         // Get system wheel scroll lines
@@ -678,785 +1663,3266 @@ impl App {
         }
     }
 
-    /// Handle left mouse button down - start drag-to-pan if content is scrollable
-    fn handle_lbutton_down(&mut self, lparam: LPARAM) {
-        let x = (lparam.0 & 0xFFFF) as i16 as i32;
-        let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+    /// "Mausrad: Seitenwechsel": one page or file per full notch (WHEEL_DELTA). Precision
+    /// touchpads send many small deltas, which add up first; turning back starts over.
+    fn change_page_by_wheel(&mut self, delta: i32) {
+        if delta.signum() != self.wheel_delta.signum() {
+            self.wheel_delta = 0;
+        }
+        self.wheel_delta += delta;
+        while self.wheel_delta >= WHEEL_DELTA as i32 {
+            self.wheel_delta -= WHEEL_DELTA as i32;
+            self.cmd_prev_page();
+        }
+        while self.wheel_delta <= -(WHEEL_DELTA as i32) {
+            self.wheel_delta += WHEEL_DELTA as i32;
+            self.cmd_next_page();
+        }
+    }
 
-        // Check if content is larger than viewport (scrolling is possible)
-        let (viewport_width, viewport_height) = self.renderer.size();
-        let state = self.state.lock();
-        let can_scroll_h = state.content_width > viewport_width as i32;
-        let can_scroll_v = state.content_height > viewport_height as i32;
+    fn set_wheel_mode(&mut self, mode: WheelMode) {
+        crate::settings::set_wheel_mode(mode);
+        self.wheel_delta = 0;
+    }
 
-        if can_scroll_h || can_scroll_v {
-            // Start dragging
-            self.is_dragging = true;
-            self.drag_start_mouse = (x, y);
-            self.drag_start_scroll = (state.scroll_x, state.scroll_y);
-            drop(state);
+    /// "Renderqualität": applies to PDFs opened afterwards and replaces --pdf-dpi. An open
+    /// PDF is reloaded at the new size if the user agrees.
+    fn set_render_quality(&mut self, quality: RenderQuality) {
+        let previous = match self.pdf_dpi.take() {
+            Some(dpi) => RenderResolution::Dpi(dpi),
+            None => RenderResolution::from_quality(crate::settings::render_quality()),
+        };
+        crate::settings::set_render_quality(quality);
+        let resolution = RenderResolution::from_quality(quality);
+        self.pdf_loader.set_resolution(resolution);
 
-            // Capture mouse to receive events even outside window
-            unsafe {
-                SetCapture(self.view_window.hwnd());
-                // Change cursor to grabbing hand (IDC_SIZEALL for all-directions movement)
-                if let Ok(cursor) = LoadCursorW(None, IDC_SIZEALL) {
-                    SetCursor(cursor);
-                }
-            }
+        let pdf_open = self.state.lock().document.as_ref().is_some_and(|doc| doc.doc_type() == crate::document::DocumentType::Pdf);
+        if pdf_open && resolution != previous && crate::dialogs::confirm_reload_pdf(self.window.hwnd()) {
+            self.reload_document();
         }
     }
 
-    /// Handle left mouse button up - end drag-to-pan
-    fn handle_lbutton_up(&mut self) {
-        if self.is_dragging {
-            self.is_dragging = false;
+    /// Loads the open document again from its file, keeping page, zoom and rotation. A PDF
+    /// is opened with the password it was opened with before.
+    fn reload_document(&mut self) {
+        let (path, password, folder_mode, carries_changes) = {
+            let state = self.state.lock();
+            let Some(ref path) = state.file_path else { return };
+            let password = state.document.as_ref().and_then(|doc| doc.pdf_password()).map(|p| Password::new(p.to_string()));
+            let folder_mode = state.navigation_context == NavigationContext::FolderBrowsing;
+            (path.clone(), password, folder_mode, state.page_rotations.is_empty())
+        };
+        // The document rotation is kept with the view, page rotations would be lost
+        if !carries_changes && !self.confirm_discard_changes() {
+            return;
+        }
+        let view = self.capture_view();
+        self.known_password = password;
+        self.open_document_internal(&path, folder_mode, !folder_mode);
+        self.known_password = None;
+        let loaded = self.state.lock().load_error.is_none();
+        if let (Some(view), true) = (view, loaded) {
+            self.apply_kept_view(view);
+        }
+    }
 
-            // Release mouse capture
-            unsafe {
-                let _ = ReleaseCapture();
-                // Restore arrow cursor
-                if let Ok(cursor) = LoadCursorW(None, IDC_ARROW) {
-                    SetCursor(cursor);
+    /// Watches the shown file for changes by other programs (see file_watch.rs); `None`
+    /// stops watching. Folders that cannot be watched are left unwatched.
+    fn watch_file(&mut self, path: Option<&str>) {
+        self.file_watch = None;
+        self.source_change = None;
+        self.statusbar.set_file_changed(false);
+        self.statusbar.set_source_deleted(false);
+        self.file_watch = path.and_then(|path| FileWatcher::start(self.window.hwnd(), path).ok());
+    }
+
+    /// The open file was changed or deleted: offer "Neu laden", or mark it as deleted
+    fn on_file_changed(&mut self, path: &str, change: FileChange) {
+        if self.file_watch.is_none() || self.state.lock().file_path.as_deref() != Some(path) {
+            return;
+        }
+        self.operation_log.record(&format!("Datei {}: {}", if change == FileChange::Deleted { "gelöscht" } else { "geändert" }, path));
+        self.source_change = Some(change);
+        self.statusbar.set_file_changed(change == FileChange::Modified);
+        self.statusbar.set_source_deleted(change == FileChange::Deleted);
+    }
+
+    /// View rectangle of a page's (rotated) bounding box, placed exactly as the renderer draws it
+    fn page_view_rect(&self, state: &AppState, page: usize) -> Option<D2D_RECT_F> {
+        let doc = state.document.as_ref()?;
+        let (viewport_width, viewport_height) = self.renderer.size();
+        let (viewport_width, viewport_height) = (viewport_width as f32, viewport_height as f32);
+
+        match state.page_layout {
+            Some(ref layout) if state.multi_page_view && state.total_pages > 1 => {
+                let (page_w, page_h) = *layout.page_sizes.get(page)?;
+                let left = if layout.max_width as f32 <= viewport_width {
+                    (viewport_width - page_w as f32) / 2.0
+                } else {
+                    -(state.scroll_x as f32) + ((layout.max_width - page_w) / 2) as f32
+                };
+                let top = (layout.page_tops[page] - state.scroll_y) as f32;
+                Some(D2D_RECT_F { left, top, right: left + page_w as f32, bottom: top + page_h as f32 })
+            }
+            _ => {
+                if page != state.current_page {
+                    return None;
                 }
+                let (w, h) = doc.display_dimensions(page, state.auto_crop);
+                let rotation = state.page_rotation(page);
+                let (w, h) = if rotation == 90 || rotation == 270 { (h, w) } else { (w, h) };
+                let (w, h) = (w * state.zoom, h * state.zoom);
+                let left = if w <= viewport_width { (viewport_width - w) / 2.0 } else { -(state.scroll_x as f32) };
+                let top = if h <= viewport_height { (viewport_height - h) / 2.0 } else { -(state.scroll_y as f32) };
+                Some(D2D_RECT_F { left, top, right: left + w, bottom: top + h })
             }
         }
     }
 
-    /// Handle mouse move - pan if dragging
-    fn handle_mouse_move(&mut self, lparam: LPARAM) {
-        if !self.is_dragging {
-            return;
+    /// Page under a view point
+    fn page_at(&self, state: &AppState, x: f32, y: f32) -> Option<usize> {
+        (0..state.total_pages).find(|&page| {
+            self.page_view_rect(state, page)
+                .is_some_and(|r| x >= r.left && x < r.right && y >= r.top && y < r.bottom)
+        })
+    }
+
+    /// Target of the PDF link under a view point. Web links count only where they may be opened.
+    fn link_at(&self, x: f32, y: f32) -> Option<LinkTarget> {
+        let state = self.state.lock();
+        let doc = state.document.as_ref()?;
+        let page = self.page_at(&state, x, y)?;
+        let links = doc.links(page);
+        if links.is_empty() {
+            return None;
         }
+        let (sx, sy) = self.view_to_source(&state, page, x, y)?;
+        let (width, height) = doc.page_dimensions(page);
+        let (u, v) = (sx / width, sy / height);
+        links
+            .iter()
+            .find(|link| {
+                let [left, top, right, bottom] = link.rect;
+                u >= left && u <= right && v >= top && v <= bottom
+                    && (self.external_links || !matches!(link.target, LinkTarget::Uri(_)))
+            })
+            .map(|link| link.target.clone())
+    }
 
-        let x = (lparam.0 & 0xFFFF) as i16 as i32;
-        let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+    /// Shows the page of an internal link or opens a web link with its program. Links other
+    /// than http, https and mailto are confirmed first.
+    fn follow_link(&mut self, target: LinkTarget) {
+        match target {
+            LinkTarget::Page(page) => {
+                self.record_view();
+                self.go_to_page(page);
+            }
+            LinkTarget::Uri(uri) => {
+                if !self.external_links {
+                    return;
+                }
+                let scheme = uri.split(':').next().unwrap_or("").to_ascii_lowercase();
+                let trusted = matches!(scheme.as_str(), "http" | "https" | "mailto") && uri.contains(':');
+                if !trusted && !crate::dialogs::confirm_open_link(self.window.hwnd(), &uri) {
+                    return;
+                }
+                self.operation_log.record(&format!("Link öffnen: {}", uri));
+                let result = unsafe {
+                    windows::Win32::UI::Shell::ShellExecuteW(self.window.hwnd(), w!("open"), &HSTRING::from(uri.as_str()), None, None, SW_SHOWNORMAL)
+                };
+                // Values up to 32 are errors
+                if result.0 <= 32 {
+                    let e = Error::from_win32();
+                    self.show_error_details(&format!("Der Link konnte nicht geöffnet werden:\n{}", uri), Some(&e), None);
+                }
+            }
+        }
+    }
 
-        // Calculate delta from drag start
-        let delta_x = self.drag_start_mouse.0 - x;
-        let delta_y = self.drag_start_mouse.1 - y;
+    /// Inverse of the render transform: view point -> pixel of the unrotated source page.
+    /// Points outside the page are clamped to its edges.
+    fn view_to_source(&self, state: &AppState, page: usize, x: f32, y: f32) -> Option<(f32, f32)> {
+        let rect = self.page_view_rect(state, page)?;
+        let (x, y) = rotate_about_center(&rect, x, y, -self.page_skew(page));
+        let [src_left, src_top, src_right, src_bottom] = state.document.as_ref()?.display_rect(page, state.auto_crop);
+        // Normalized position in the rotated page
+        let u = ((x - rect.left) / (rect.right - rect.left)).clamp(0.0, 1.0);
+        let v = ((y - rect.top) / (rect.bottom - rect.top)).clamp(0.0, 1.0);
+        let (u, v) = match state.page_rotation(page) {
+            90 => (v, 1.0 - u),
+            180 => (1.0 - u, 1.0 - v),
+            270 => (1.0 - v, u),
+            _ => (u, v),
+        };
+        Some((src_left + u * (src_right - src_left), src_top + v * (src_bottom - src_top)))
+    }
 
-        // Calculate new scroll position (inverted - dragging down should scroll up)
-        let (viewport_width, viewport_height) = self.renderer.size();
-        let mut state = self.state.lock();
+    /// Forward render transform: source pixel -> view point
+    fn source_to_view(&self, state: &AppState, page: usize, sx: f32, sy: f32) -> Option<(f32, f32)> {
+        let rect = self.page_view_rect(state, page)?;
+        let [src_left, src_top, src_right, src_bottom] = state.document.as_ref()?.display_rect(page, state.auto_crop);
+        let (u, v) = ((sx - src_left) / (src_right - src_left), (sy - src_top) / (src_bottom - src_top));
+        let (u, v) = match state.page_rotation(page) {
+            90 => (1.0 - v, u),
+            180 => (1.0 - u, 1.0 - v),
+            270 => (v, 1.0 - u),
+            _ => (u, v),
+        };
+        let (x, y) = (rect.left + u * (rect.right - rect.left), rect.top + v * (rect.bottom - rect.top));
+        Some(rotate_about_center(&rect, x, y, self.page_skew(page)))
+    }
 
-        let max_x = (state.content_width - viewport_width as i32).max(0);
-        let max_y = (state.content_height - viewport_height as i32).max(0);
+    fn selection_view_rect(&self, state: &AppState, sel: &Selection) -> Option<D2D_RECT_F> {
+        let (x0, y0) = self.source_to_view(state, sel.page, sel.left, sel.top)?;
+        let (x1, y1) = self.source_to_view(state, sel.page, sel.right, sel.bottom)?;
+        Some(D2D_RECT_F { left: x0.min(x1), top: y0.min(y1), right: x0.max(x1), bottom: y0.max(y1) })
+    }
 
-        let new_x = (self.drag_start_scroll.0 + delta_x).clamp(0, max_x);
-        let new_y = (self.drag_start_scroll.1 + delta_y).clamp(0, max_y);
+    /// Handle left mouse button down - start drag-to-pan if content is scrollable
+    fn handle_lbutton_down(&mut self, lparam: LPARAM) {
+        self.view_focus_visible = false;
+        let x = (lparam.0 & 0xFFFF) as i16 as i32;
+        let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
 
-        let changed = new_x != state.scroll_x || new_y != state.scroll_y;
-        let is_multipage = state.multi_page_view && state.total_pages > 1;
+        // "Erneut versuchen" on the error panel
+        if let Some(button) = self.retry_button_rect {
+            let (fx, fy) = (x as f32, y as f32);
+            if fx >= button.left && fx <= button.right && fy >= button.top && fy <= button.bottom {
+                let failed_path = self.state.lock().load_error.as_ref().map(|f| f.path.clone());
+                if let Some(path) = failed_path {
+                    self.open_document_with_mode(&path, true);
+                }
+                return;
+            }
+        }
 
-        if changed {
-            state.scroll_x = new_x;
-            state.scroll_y = new_y;
-            drop(state);
+        // Click or drag in the "Übersichtsleiste" jumps there
+        let on_minimap = self.minimap(&self.state.lock()).is_some_and(|minimap| minimap.contains(x as f32, y as f32));
+        if on_minimap {
+            self.minimap_drag = true;
+            self.hover_toolbar.hide();
+            unsafe {
+                SetCapture(self.view_window.hwnd());
+            }
+            self.scroll_to_minimap(y as f32);
+            return;
+        }
 
-            self.scroll_manager.set_pos(SB_HORZ, new_x);
-            self.scroll_manager.set_pos(SB_VERT, new_y);
-            self.invalidate();
+        if let Some((page, action)) = self.hover_toolbar.hit_test(x as f32, y as f32) {
+            match action {
+                HoverAction::RotateLeft => self.rotate_page(page, 270),
+                HoverAction::RotateRight => self.rotate_page(page, 90),
+                HoverAction::Copy => self.copy_page_to_clipboard(page),
+            }
+            return;
+        }
 
-            // In multi-page mode, update the current page indicator
-            if is_multipage {
-                self.update_current_page_from_scroll();
+        // Ctrl+drag changes the window of 16-bit grayscale pages
+        if unsafe { GetKeyState(VK_CONTROL.0 as i32) } < 0 {
+            let window = {
+                let state = self.state.lock();
+                state.document.as_ref().and_then(|doc| doc.window_level(state.current_page))
+            };
+            if let Some(window) = window {
+                self.window_level_drag = Some(((x, y), window));
+                unsafe {
+                    SetCapture(self.view_window.hwnd());
+                }
+                return;
             }
         }
-    }
 
-    fn handle_capture_changed(&mut self) {
-        if self.is_dragging {
-            self.is_dragging = false;
-            // Capture is already lost/changed, just reset cursor
-            unsafe {
-                if let Ok(cursor) = LoadCursorW(None, IDC_ARROW) {
-                    SetCursor(cursor);
+        // Shift+drag selects a rectangle on the page under the cursor
+        if unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0 {
+            let state = self.state.lock().clone();
+            if let Some(page) = self.page_at(&state, x as f32, y as f32) {
+                let (x, y, _) = self.snap_to_page(&state, page, x as f32, y as f32);
+                if let Some((sx, sy)) = self.view_to_source(&state, page, x, y) {
+                    self.selection_anchor = Some((page, sx, sy));
+                    self.state.lock().selection = None;
+                    unsafe {
+                        SetCapture(self.view_window.hwnd());
+                    }
+                    self.invalidate();
                 }
             }
+            return;
         }
-    }
 
-    fn handle_set_cursor(&self, _lparam: LPARAM) -> bool {
-        if self.is_dragging {
+        self.link_press = self.link_at(x as f32, y as f32).map(|target| ((x, y), target));
+
+        // Check if content is larger than viewport (scrolling is possible)
+        let (viewport_width, viewport_height) = self.renderer.size();
+        let state = self.state.lock();
+        let can_scroll_h = state.content_width > viewport_width as i32;
+        let can_scroll_v = state.content_height > viewport_height as i32;
+
+        if can_scroll_h || can_scroll_v {
+            // Start dragging
+            self.is_dragging = true;
+            self.hover_toolbar.hide();
+            self.drag_start_mouse = (x, y);
+            self.drag_start_scroll = (state.scroll_x, state.scroll_y);
+            drop(state);
+
+            // Capture mouse to receive events even outside window
             unsafe {
+                SetCapture(self.view_window.hwnd());
+                // Change cursor to grabbing hand (IDC_SIZEALL for all-directions movement)
                 if let Ok(cursor) = LoadCursorW(None, IDC_SIZEALL) {
                     SetCursor(cursor);
-                    return true;
                 }
             }
         }
-        false
     }
 
-    /// Update current_page based on scroll position (for multi-page mode)
-    fn update_current_page_from_scroll(&mut self) {
-        let most_visible = self.get_most_visible_page();
-        let mut state = self.state.lock();
-
-        if state.current_page != most_visible {
-            state.current_page = most_visible;
-            let total = state.total_pages;
-            let path = state.file_path.clone();
-            drop(state);
+    /// Moves the keyboard focus to the next (or previous) of toolbar, view and status bar
+    fn cycle_focus(&mut self, backwards: bool) {
+        let targets = [
+            self.top_toolbar.toolbar_hwnd(),
+            self.view_window.hwnd(),
+            self.statusbar.toolbar_hwnd(),
+        ];
+        let current = targets.iter().position(|&hwnd| hwnd == unsafe { GetFocus() }).unwrap_or(1);
+        let next = if backwards { (current + targets.len() - 1) % targets.len() } else { (current + 1) % targets.len() };
 
-            // Update status bar with current page
-            self.update_page_display(most_visible, total, path.as_deref());
-            // Update navigation buttons for new page position
-            self.update_navigation_buttons();
+        unsafe {
+            // Leave no highlighted button behind in the toolbar losing the focus
+            SendMessageW(targets[current], TB_SETHOTITEM, WPARAM(usize::MAX), LPARAM(0));
         }
+        if targets[next] == self.view_window.hwnd() {
+            self.view_focus_visible = true;
+            unsafe {
+                SetFocus(targets[next]);
+            }
+        } else {
+            crate::utils::focus_toolbar(targets[next]);
+        }
+        self.invalidate();
     }
 
-    /// Update content size based on document, zoom, and rotation.
-    /// Manages scrollbar visibility and range.
-    fn update_content_size(&mut self) {
-        let (render_w, render_h) = self.renderer.size();
-        let viewport_width = render_w as i32;
-        let viewport_height = render_h as i32;
-
-        let mut state = self.state.lock();
-
-        if let Some(ref doc) = state.document {
-            // Check if we should use multi-page view
-            let use_multipage = state.multi_page_view && state.total_pages > 1;
-
-            if use_multipage {
-                // Multi-page view: compute full document layout
-                let layout = doc.compute_layout(state.zoom, state.rotation);
-                state.content_width = layout.max_width;
-                state.content_height = layout.total_height;
-                state.page_layout = Some(layout);
-            } else {
-                // Single page view: use current page dimensions
-                let (doc_width, doc_height) = doc.page_dimensions(state.current_page);
+    /// Right button down starts a zoom rectangle
+    fn handle_rbutton_down(&mut self, lparam: LPARAM) {
+        self.view_focus_visible = false;
+        if self.state.lock().document.is_none() || self.is_dragging || self.selection_anchor.is_some() {
+            return;
+        }
+        let x = (lparam.0 & 0xFFFF) as i16 as i32;
+        let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+        self.zoom_drag = Some(((x, y), (x, y)));
+        unsafe {
+            SetCapture(self.view_window.hwnd());
+        }
+    }
 
-                // Determine dimensions based on rotation
-                let (w, h) = if state.rotation == 90 || state.rotation == 270 {
-                    (doc_height, doc_width)
-                } else {
-                    (doc_width, doc_height)
-                };
+    /// Right button up: zooms into the dragged rectangle. Returns false for a click
+    /// (or a drag below the system drag threshold) so the context menu opens instead.
+    fn handle_rbutton_up(&mut self) -> bool {
+        let rect = self.zoom_drag_rect();
+        self.drag_feedback = None;
+        if self.zoom_drag.take().is_none() {
+            return false;
+        }
+        unsafe {
+            let _ = ReleaseCapture();
+        }
+        self.invalidate();
 
-                // Calculate effective content size in pixels
-                state.content_width = (w * state.zoom) as i32;
-                state.content_height = (h * state.zoom) as i32;
-                state.page_layout = None;
+        let (min_w, min_h) = unsafe { (GetSystemMetrics(SM_CXDRAG), GetSystemMetrics(SM_CYDRAG)) };
+        match rect {
+            Some(rect) if rect.right - rect.left > min_w as f32 || rect.bottom - rect.top > min_h as f32 => {
+                self.zoom_to_rect(&rect);
+                true
             }
+            _ => false,
+        }
+    }
 
-            // Clamp scroll positions to prevent scrolling past content
-            state.scroll_x = clamp_scroll(state.scroll_x, viewport_width, state.content_width);
-            state.scroll_y = clamp_scroll(state.scroll_y, viewport_height, state.content_height);
-
-            let scroll_x = state.scroll_x;
-            let scroll_y = state.scroll_y;
-            let content_w = state.content_width;
-            let content_h = state.content_height;
-            let fit_to_page = state.fit_to_page;
-            drop(state);
-
-            // Update scrollbars:
-            // - If "Fit to Page" is active in single-page mode, hide scrollbars
-            // - In multi-page mode, always show vertical scrollbar if content exceeds viewport
-            // - Otherwise, update based on content vs viewport size
-            if fit_to_page && content_h <= viewport_height && content_w <= viewport_width {
-                self.scroll_manager.hide_both();
-            } else {
-                self.scroll_manager.update_both(
-                    viewport_width,
-                    viewport_height,
-                    content_w,
-                    content_h,
-                    scroll_x,
-                    scroll_y,
-                );
+    /// Moves a dragged rectangle corner onto the border or a center line of `page` when it is
+    /// within SNAP_DISTANCE. Returns the point and the lines it snapped to, for highlighting.
+    fn snap_to_page(&self, state: &AppState, page: usize, x: f32, y: f32) -> (f32, f32, Vec<(D2D_POINT_2F, D2D_POINT_2F)>) {
+        let mut guides = Vec::new();
+        let Some(r) = self.page_view_rect(state, page) else { return (x, y, guides) };
+        let nearest = |value: f32, lines: [f32; 3]| {
+            lines
+                .into_iter()
+                .filter(|line| (line - value).abs() <= SNAP_DISTANCE)
+                .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+        };
+        let x = match nearest(x, [r.left, (r.left + r.right) / 2.0, r.right]) {
+            Some(line) => {
+                guides.push((D2D_POINT_2F { x: line, y: r.top }, D2D_POINT_2F { x: line, y: r.bottom }));
+                line
             }
-        } else {
-            // No document loaded
-            state.content_width = 0;
-            state.content_height = 0;
-            state.scroll_x = 0;
-            state.scroll_y = 0;
-            state.page_layout = None;
-            drop(state);
-            self.scroll_manager.hide_both();
+            None => x,
+        };
+        let y = match nearest(y, [r.top, (r.top + r.bottom) / 2.0, r.bottom]) {
+            Some(line) => {
+                guides.push((D2D_POINT_2F { x: r.left, y: line }, D2D_POINT_2F { x: r.right, y: line }));
+                line
+            }
+            None => y,
+        };
+        (x, y, guides)
+    }
+
+    /// Chip with the size of a dragged rectangle in source pixels, and in mm when the
+    /// resolution of `page` is known
+    fn drag_feedback(state: &AppState, page: Option<usize>, rect: &D2D_RECT_F, cursor: (i32, i32), guides: Vec<(D2D_POINT_2F, D2D_POINT_2F)>) -> DragFeedback {
+        let dpi = page.and_then(|page| state.document.as_ref()?.page_dpi(page));
+        let zoom = state.zoom.max(f32::EPSILON);
+        DragFeedback {
+            text: crate::document::format_page_size((rect.right - rect.left) / zoom, (rect.bottom - rect.top) / zoom, dpi),
+            cursor: (cursor.0 as f32, cursor.1 as f32),
+            guides,
         }
     }
 
-    fn apply_theme(&mut self) {
-        Theme::apply_to_window(self.window.hwnd(), false);
-        self.top_toolbar.set_dark_theme(false);
-        self.statusbar.set_dark_theme(false);
-        self.invalidate();
+    /// Zoom rectangle in view coordinates, normalized
+    fn zoom_drag_rect(&self) -> Option<D2D_RECT_F> {
+        let ((x0, y0), (x1, y1)) = self.zoom_drag?;
+        Some(D2D_RECT_F {
+            left: x0.min(x1) as f32,
+            top: y0.min(y1) as f32,
+            right: x0.max(x1) as f32,
+            bottom: y0.max(y1) as f32,
+        })
     }
 
-    // --- Command Handlers ---
+    /// Esc undoes one layer at a time, innermost first: a rectangle being dragged, the
+    /// selection, the OCR word frames, fullscreen, then the OSD notice. Only with nothing left to close does it end the app,
+    /// and only if "Esc beendet das Programm" is on.
+    fn handle_escape(&mut self) {
+        if let Some(cancel) = self.folder_stats.as_ref() {
+            cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+            self.show_osd("Ordnerstatistik wird abgebrochen...");
+        } else if let Some(cancel) = self.batch_rotate.as_ref() {
+            cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+            self.show_osd("Drehen wird nach der aktuellen Datei abgebrochen...");
+        } else if let Some((_, cancel)) = self.search.as_ref().filter(|(_, cancel)| !cancel.load(std::sync::atomic::Ordering::Relaxed)) {
+            cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+            self.show_osd("Die Ordnersuche wird abgebrochen...");
+        } else if self.zoom_drag.is_some() {
+            self.cancel_zoom_drag();
+        } else if self.selection_anchor.is_some() || self.state.lock().selection.is_some() {
+            self.drag_feedback = None;
+            if self.selection_anchor.take().is_some() {
+                unsafe {
+                    let _ = ReleaseCapture();
+                }
+            }
+            self.state.lock().selection = None;
+            self.invalidate();
+        } else if !self.ocr_words.is_empty() {
+            self.ocr_words.clear();
+            self.invalidate();
+        } else if self.window.is_fullscreen() {
+            self.cmd_toggle_fullscreen();
+        } else if self.osd_message.is_some() {
+            self.hide_osd();
+        } else if crate::settings::escape_exits() {
+            unsafe {
+                let _ = PostMessageW(self.window.hwnd(), WM_CLOSE, WPARAM(0), LPARAM(0));
+            }
+        }
+    }
 
-    fn cmd_info(&self) {
-        crate::dialogs::show_info(
-            self.window.hwnd(),
-            "SimpliView",
-            "SimpliView - Release 1.1.1\n\n© 2026 SimpliMed GmbH\n\nwww.simplimed.de",
-        );
+    fn cancel_zoom_drag(&mut self) {
+        self.drag_feedback = None;
+        if self.zoom_drag.take().is_some() {
+            unsafe {
+                let _ = ReleaseCapture();
+            }
+            self.invalidate();
+        }
     }
 
-    fn cmd_close(&self) { unsafe { let _ = PostMessageW(self.window.hwnd(), WM_CLOSE, WPARAM(0), LPARAM(0)); } }
+    /// Sets zoom and scroll so the view rectangle fills the viewport. Works in content
+    /// coordinates (rotated layout), so rotation needs no special handling.
+    fn zoom_to_rect(&mut self, rect: &D2D_RECT_F) {
+        self.record_view();
+        let (viewport_width, viewport_height) = self.renderer.size();
+        let (viewport_width, viewport_height) = (viewport_width as f32, viewport_height as f32);
+        if viewport_width <= 0.0 || viewport_height <= 0.0 {
+            return;
+        }
 
-    fn cmd_print(&mut self) {
-        let (doc, current_page, rotation, file_path, total_pages) = {
+        let (zoom, center_x, center_y, is_multipage) = {
             let state = self.state.lock();
-            if let Some(ref doc) = state.document {
-                (doc.clone(), state.current_page, state.rotation, state.file_path.clone(), state.total_pages)
+            let is_multipage = state.multi_page_view && state.total_pages > 1;
+            let (content_w, content_h) = (state.content_width as f32, state.content_height as f32);
+
+            // View position of the content origin, as placed by the renderer
+            let origin_x = if content_w <= viewport_width { (viewport_width - content_w) / 2.0 } else { -(state.scroll_x as f32) };
+            let origin_y = if !is_multipage && content_h <= viewport_height {
+                (viewport_height - content_h) / 2.0
             } else {
+                -(state.scroll_y as f32)
+            };
+
+            // Rectangle in zoom-independent content units, limited to the content
+            let to_units = |v: f32, origin: f32, max: f32| ((v - origin) / state.zoom).clamp(0.0, max / state.zoom);
+            let left = to_units(rect.left, origin_x, content_w);
+            let right = to_units(rect.right, origin_x, content_w);
+            let top = to_units(rect.top, origin_y, content_h);
+            let bottom = to_units(rect.bottom, origin_y, content_h);
+            if right - left < 1.0 || bottom - top < 1.0 {
                 return;
             }
+
+            let zoom = (viewport_width / (right - left))
+                .min(viewport_height / (bottom - top))
+                .clamp(Self::ZOOM_LEVELS[0], Self::ZOOM_LEVELS[Self::ZOOM_LEVELS.len() - 1]);
+            (zoom, (left + right) / 2.0, (top + bottom) / 2.0, is_multipage)
         };
 
-        // GDI print functions - manually linked since windows 0.48 doesn't expose them
-        #[link(name = "gdi32")]
-        extern "system" {
-            fn StartDocW(hdc: HDC, lpdi: *const DOCINFOW) -> i32;
-            fn EndDoc(hdc: HDC) -> i32;
-            fn StartPage(hdc: HDC) -> i32;
-            fn EndPage(hdc: HDC) -> i32;
+        self.set_zoom_mode(ZoomMode::Fixed(zoom));
+        self.update_content_size();
+
+        // Center the rectangle; update_content_size clamps and syncs the scrollbars
+        {
+            let mut state = self.state.lock();
+            state.scroll_x = (center_x * zoom - viewport_width / 2.0) as i32;
+            state.scroll_y = (center_y * zoom - viewport_height / 2.0) as i32;
         }
+        self.update_content_size();
 
-        #[repr(C)]
-        struct DOCINFOW {
-            cb_size: i32,
-            lpsz_doc_name: PCWSTR,
-            lpsz_output: PCWSTR,
-            lpsz_datatype: PCWSTR,
-            fw_type: u32,
+        if is_multipage {
+            self.update_current_page_from_scroll();
         }
+        self.invalidate();
+    }
 
+    /// Handle left mouse button up - end drag-to-pan or selection
+    fn handle_lbutton_up(&mut self, lparam: LPARAM) {
+        let link_press = self.link_press.take();
+
+        if std::mem::take(&mut self.minimap_drag) {
+            unsafe {
+                let _ = ReleaseCapture();
+            }
+            return;
+        }
+
+        if self.window_level_drag.take().is_some() {
+            unsafe {
+                let _ = ReleaseCapture();
+            }
+            return;
+        }
+
+        if self.selection_anchor.take().is_some() {
+            self.drag_feedback = None;
+            unsafe {
+                let _ = ReleaseCapture();
+            }
+            // A click without dragging clears the selection
+            let mut state = self.state.lock();
+            if state.selection.is_some_and(|s| s.right - s.left < 2.0 || s.bottom - s.top < 2.0) {
+                state.selection = None;
+            }
+            drop(state);
+            self.invalidate();
+            return;
+        }
+
+        if self.is_dragging {
+            self.is_dragging = false;
+
+            // Release mouse capture
+            unsafe {
+                let _ = ReleaseCapture();
+                // Restore arrow cursor
+                if let Ok(cursor) = LoadCursorW(None, IDC_ARROW) {
+                    SetCursor(cursor);
+                }
+            }
+        }
+
+        // A click on a link follows it; dragging from a link pans as usual
+        if let Some(((start_x, start_y), target)) = link_press {
+            let x = (lparam.0 & 0xFFFF) as i16 as i32;
+            let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+            if (x - start_x).abs() <= 4 && (y - start_y).abs() <= 4 {
+                self.follow_link(target);
+            }
+        }
+    }
+
+    /// Handle mouse move - pan if dragging, grow the selection if selecting
+    fn handle_mouse_move(&mut self, lparam: LPARAM) {
+        let x = (lparam.0 & 0xFFFF) as i16 as i32;
+        let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+
+        if self.minimap_drag {
+            self.scroll_to_minimap(y as f32);
+            return;
+        }
+
+        if self.is_dragging || self.zoom_drag.is_some() || self.selection_anchor.is_some() || self.window_level_drag.is_some() {
+            if !self.hover_toolbar.is_hidden() {
+                self.hover_toolbar.hide();
+                self.update_hover_toolbar_timer();
+                self.invalidate();
+            }
+        } else {
+            self.track_hover_toolbar(Some((x as f32, y as f32)));
+            self.reveal_compact_toolbar(y);
+        }
+
+        if let Some((start, _)) = self.zoom_drag {
+            let state = self.state.lock().clone();
+            let page = self.page_at(&state, x as f32, y as f32);
+            let (end_x, end_y, guides) = match page {
+                Some(page) => self.snap_to_page(&state, page, x as f32, y as f32),
+                None => (x as f32, y as f32, Vec::new()),
+            };
+            self.zoom_drag = Some((start, (end_x.round() as i32, end_y.round() as i32)));
+            self.drag_feedback = self.zoom_drag_rect().map(|rect| Self::drag_feedback(&state, page, &rect, (x, y), guides));
+            self.invalidate();
+            return;
+        }
+
+        if let Some(((start_x, start_y), start_window)) = self.window_level_drag {
+            let window = start_window.dragged(x - start_x, y - start_y);
+            if let Some(ref doc) = self.state.lock().document {
+                doc.set_window_level(Some(window));
+            }
+            self.show_osd(&window.label());
+            return;
+        }
+
+        if let Some((page, ax, ay)) = self.selection_anchor {
+            let state = self.state.lock().clone();
+            let (snapped_x, snapped_y, guides) = self.snap_to_page(&state, page, x as f32, y as f32);
+            if let Some((sx, sy)) = self.view_to_source(&state, page, snapped_x, snapped_y) {
+                let selection = Selection {
+                    page,
+                    left: ax.min(sx),
+                    top: ay.min(sy),
+                    right: ax.max(sx),
+                    bottom: ay.max(sy),
+                };
+                self.drag_feedback = self
+                    .selection_view_rect(&state, &selection)
+                    .map(|rect| Self::drag_feedback(&state, Some(page), &rect, (x, y), guides));
+                self.state.lock().selection = Some(selection);
+                self.invalidate();
+            }
+            return;
+        }
+
+        if !self.is_dragging {
+            let link_hover = self.link_at(x as f32, y as f32).is_some();
+            if link_hover != self.link_hover {
+                self.link_hover = link_hover;
+                unsafe {
+                    if let Ok(cursor) = LoadCursorW(None, if link_hover { IDC_HAND } else { IDC_ARROW }) {
+                        SetCursor(cursor);
+                    }
+                }
+            }
+            return;
+        }
+
+        // Calculate delta from drag start
+        let delta_x = self.drag_start_mouse.0 - x;
+        let delta_y = self.drag_start_mouse.1 - y;
+
+        // Calculate new scroll position (inverted - dragging down should scroll up)
+        let (viewport_width, viewport_height) = self.renderer.size();
+        let mut state = self.state.lock();
+
+        let max_x = (state.content_width - viewport_width as i32).max(0);
+        let max_y = (state.content_height - viewport_height as i32).max(0);
+
+        let new_x = (self.drag_start_scroll.0 + delta_x).clamp(0, max_x);
+        let new_y = (self.drag_start_scroll.1 + delta_y).clamp(0, max_y);
+
+        let changed = new_x != state.scroll_x || new_y != state.scroll_y;
+        let is_multipage = state.multi_page_view && state.total_pages > 1;
+
+        if changed {
+            state.scroll_x = new_x;
+            state.scroll_y = new_y;
+            drop(state);
+
+            self.scroll_manager.set_pos(SB_HORZ, new_x);
+            self.scroll_manager.set_pos(SB_VERT, new_y);
+            self.invalidate();
+
+            // In multi-page mode, update the current page indicator
+            if is_multipage {
+                self.update_current_page_from_scroll();
+            }
+        }
+    }
+
+    /// Feeds the cursor position (`None` = left the view) to the hover toolbar.
+    /// Only continuous mode has one; single page view has the toolbar buttons.
+    fn track_hover_toolbar(&mut self, cursor: Option<(f32, f32)>) {
+        if !self.hover_toolbar.is_enabled() {
+            return;
+        }
+        let (page, corner) = {
+            let state = self.state.lock().clone();
+            let continuous = state.page_layout.is_some() && state.load_error.is_none();
+            match cursor.filter(|_| continuous) {
+                Some((x, y)) => match self.page_at(&state, x, y) {
+                    Some(page) => {
+                        let corner = self.page_view_rect(&state, page).is_some_and(|r| HoverToolbar::in_corner(&r, x, y));
+                        (Some(page), corner)
+                    }
+                    None => (None, false),
+                },
+                None => (None, false),
+            }
+        };
+        if self.hover_toolbar.track(page, corner) {
+            self.invalidate();
+        }
+        // WM_MOUSELEAVE starts the fade when the cursor leaves the view directly
+        if cursor.is_some() && !self.hover_toolbar.is_hidden() {
+            let mut tme = TRACKMOUSEEVENT {
+                cbSize: std::mem::size_of::<TRACKMOUSEEVENT>() as u32,
+                dwFlags: TME_LEAVE,
+                hwndTrack: self.view_window.hwnd(),
+                dwHoverTime: 0,
+            };
+            unsafe {
+                let _ = TrackMouseEvent(&mut tme);
+            }
+        }
+        self.update_hover_toolbar_timer();
+    }
+
+    fn update_hover_toolbar_timer(&self) {
+        unsafe {
+            if self.hover_toolbar.needs_timer() {
+                // Setting an existing timer again only resets its interval
+                SetTimer(self.window.hwnd(), HOVER_TOOLBAR_TIMER, TIMER_INTERVAL_MS, None);
+            } else {
+                let _ = KillTimer(self.window.hwnd(), HOVER_TOOLBAR_TIMER);
+            }
+        }
+    }
+
+    /// Buttons of the hover toolbar; copy follows the clipboard capability
+    fn hover_actions(&self) -> Vec<HoverAction> {
+        let mut actions = vec![HoverAction::RotateLeft, HoverAction::RotateRight];
+        if self.capabilities.clipboard {
+            actions.push(HoverAction::Copy);
+        }
+        actions
+    }
+
+    fn handle_capture_changed(&mut self) {
+        self.minimap_drag = false;
+        self.selection_anchor = None;
+        self.window_level_drag = None;
+        let had_feedback = self.drag_feedback.take().is_some();
+        if self.zoom_drag.take().is_some() || had_feedback {
+            self.invalidate();
+        }
+        if self.is_dragging {
+            self.is_dragging = false;
+            // Capture is already lost/changed, just reset cursor
+            unsafe {
+                if let Ok(cursor) = LoadCursorW(None, IDC_ARROW) {
+                    SetCursor(cursor);
+                }
+            }
+        }
+    }
+
+    fn handle_set_cursor(&self, wparam: WPARAM, _lparam: LPARAM) -> bool {
+        if self.is_dragging {
+            unsafe {
+                if let Ok(cursor) = LoadCursorW(None, IDC_SIZEALL) {
+                    SetCursor(cursor);
+                    return true;
+                }
+            }
+        }
+        // wparam is the window under the cursor; toolbar and status bar keep their cursors
+        if self.link_hover && HWND(wparam.0 as isize) == self.view_window.hwnd() {
+            unsafe {
+                if let Ok(cursor) = LoadCursorW(None, IDC_HAND) {
+                    SetCursor(cursor);
+                    return true;
+                }
+            }
+        }
+        // Text recognition runs in the background; the window stays usable
+        if self.ocr_running && HWND(wparam.0 as isize) == self.view_window.hwnd() {
+            unsafe {
+                if let Ok(cursor) = LoadCursorW(None, IDC_APPSTARTING) {
+                    SetCursor(cursor);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Update current_page based on scroll position (for multi-page mode)
+    /// "Übersichtsleiste" of the continuous view, if switched on and the document is long
+    /// enough (see minimap.rs)
+    fn minimap(&self, state: &AppState) -> Option<Minimap> {
+        if !self.minimap || !state.multi_page_view || state.total_pages <= 1 || state.document.is_none() {
+            return None;
+        }
+        let (width, height) = self.renderer.size();
+        Minimap::new(state.page_layout.as_ref()?, width as i32, height as i32)
+    }
+
+    /// Scrolls so the view is centered on the document point under `y` in the
+    /// "Übersichtsleiste"
+    fn scroll_to_minimap(&mut self, y: f32) {
+        let (_, viewport_height) = self.renderer.size();
+        let mut state = self.state.lock();
+        let Some(minimap) = self.minimap(&state) else { return };
+        let max_y = (state.content_height - viewport_height as i32).max(0);
+        let new_y = minimap.scroll_for(y, viewport_height as i32).clamp(0, max_y);
+        if new_y == state.scroll_y {
+            return;
+        }
+        state.scroll_y = new_y;
+        drop(state);
+        self.scroll_manager.set_pos(SB_VERT, new_y);
+        self.update_current_page_from_scroll();
+        self.invalidate();
+    }
+
+    /// "Übersichtsleiste": shows or hides the strip for long documents
+    fn cmd_toggle_minimap(&mut self) {
+        self.minimap = !self.minimap;
+        self.minimap_drag = false;
+        crate::settings::set_minimap(self.minimap);
+        self.invalidate();
+        self.show_osd(if self.minimap { "Übersichtsleiste: ein" } else { "Übersichtsleiste: aus" });
+    }
+
+    fn update_current_page_from_scroll(&mut self) {
+        let most_visible = self.get_most_visible_page();
+        let mut state = self.state.lock();
+
+        if state.current_page != most_visible {
+            state.current_page = most_visible;
+            let total = state.total_pages;
+            let path = state.file_path.clone();
+            drop(state);
+
+            // Update status bar with current page
+            self.update_page_display(most_visible, total, path.as_deref());
+            // Update navigation buttons for new page position
+            self.update_navigation_buttons();
+        }
+    }
+
+    /// Update content size based on document, zoom, and rotation.
+    /// Manages scrollbar visibility and range.
+    fn update_content_size(&mut self) {
+        let (render_w, render_h) = self.renderer.size();
+        let viewport_width = render_w as i32;
+        let viewport_height = render_h as i32;
+
+        let mut state = self.state.lock();
+
+        if let Some(ref doc) = state.document {
+            // Check if we should use multi-page view
+            let use_multipage = state.multi_page_view && state.total_pages > 1;
+
+            if use_multipage {
+                // Multi-page view: compute full document layout
+                let layout = doc.compute_layout(state.zoom, state.auto_crop, self.page_spacing, |page| state.page_rotation(page));
+                // Pages measured just now are part of this layout already
+                doc.take_new_content_rects();
+                state.content_width = layout.max_width;
+                state.content_height = layout.total_height;
+                state.page_layout = Some(layout);
+            } else {
+                // Single page view: use current page dimensions
+                let (doc_width, doc_height) = doc.display_dimensions(state.current_page, state.auto_crop);
+                doc.take_new_content_rects();
+
+                // Determine dimensions based on rotation
+                let rotation = state.page_rotation(state.current_page);
+                let (w, h) = if rotation == 90 || rotation == 270 {
+                    (doc_height, doc_width)
+                } else {
+                    (doc_width, doc_height)
+                };
+
+                // Calculate effective content size in pixels
+                state.content_width = (w * state.zoom) as i32;
+                state.content_height = (h * state.zoom) as i32;
+                state.page_layout = None;
+            }
+
+            // Clamp scroll positions to prevent scrolling past content
+            state.scroll_x = clamp_scroll(state.scroll_x, viewport_width, state.content_width);
+            state.scroll_y = clamp_scroll(state.scroll_y, viewport_height, state.content_height);
+
+            let scroll_x = state.scroll_x;
+            let scroll_y = state.scroll_y;
+            let content_w = state.content_width;
+            let content_h = state.content_height;
+            let fit_to_page = state.zoom_mode == ZoomMode::FitPage;
+            drop(state);
+
+            // Update scrollbars:
+            // - If "Fit to Page" is active in single-page mode, hide scrollbars
+            // - In multi-page mode, always show vertical scrollbar if content exceeds viewport
+            // - Otherwise, update based on content vs viewport size
+            if fit_to_page && content_h <= viewport_height && content_w <= viewport_width {
+                self.scroll_manager.hide_both();
+            } else {
+                self.scroll_manager.update_both(
+                    viewport_width,
+                    viewport_height,
+                    content_w,
+                    content_h,
+                    scroll_x,
+                    scroll_y,
+                );
+            }
+        } else {
+            // No document loaded
+            state.content_width = 0;
+            state.content_height = 0;
+            state.scroll_x = 0;
+            state.scroll_y = 0;
+            state.page_layout = None;
+            drop(state);
+            self.scroll_manager.hide_both();
+        }
+    }
+
+    fn apply_theme(&mut self) {
+        Theme::apply_to_window(self.window.hwnd(), false);
+        self.top_toolbar.set_dark_theme(false);
+        self.statusbar.set_dark_theme(false);
+        self.invalidate();
+    }
+
+    /// Animations or the high-contrast theme were switched in Windows: the canvas, the
+    /// separator and the zoom text of the status bar are drawn again in the new colors
+    fn on_system_settings_changed(&mut self) {
+        let settings = SystemSettings::query();
+        if settings == self.system_settings {
+            return;
+        }
+        self.system_settings = settings;
+        self.hover_toolbar.set_animate(settings.animations);
+        unsafe {
+            let _ = InvalidateRect(self.statusbar.toolbar_hwnd(), None, TRUE);
+            // The separator above the status bar
+            let _ = InvalidateRect(self.window.hwnd(), None, FALSE);
+        }
+        self.invalidate();
+    }
+
+    // --- Command Handlers ---
+
+    fn cmd_info(&self) {
+        let hwnd = self.window.hwnd();
+        let version = crate::utils::app_version();
+        let build_date = env!("SIMPLIVIEW_BUILD_DATE");
+        let architecture = std::env::consts::ARCH;
+        let message = format!(
+            "SimpliView - Release {}\n\nErstellt am {} ({})\n\n© 2026 SimpliMed GmbH\n\nwww.simplimed.de",
+            version, build_date, architecture
+        );
+        let version_info = format!("SimpliView {}\nBuild: {}\nArchitektur: {}", version, build_date, architecture);
+
+        // Not in restricted deployments: those machines are managed centrally
+        let manifest_url = self.dialogs.restricted_path.is_none().then(crate::settings::update_manifest_url).flatten();
+        let check = crate::dialogs::show_about(hwnd, &message, &version_info, manifest_url.is_some());
+        if let (true, Some(url)) = (check, manifest_url) {
+            if !crate::update_check::start(hwnd, url, version) {
+                crate::dialogs::show_error(hwnd, "Die Suche nach Updates konnte nicht gestartet werden.");
+            }
+        }
+    }
+
+    fn on_update_check(&self, status: UpdateStatus) {
+        let hwnd = self.window.hwnd();
+        match status {
+            UpdateStatus::Current => crate::dialogs::show_info(hwnd, "SimpliView", "SimpliView ist auf dem neuesten Stand."),
+            UpdateStatus::Available { version, url } => {
+                let mut message = format!("Eine neuere Version ist verfügbar: {}", version);
+                if let Some(url) = url {
+                    message.push_str(&format!("\n\nVersionshinweise: {}", url));
+                }
+                crate::dialogs::show_info(hwnd, "SimpliView", &message);
+            }
+            UpdateStatus::Failed(reason) => crate::dialogs::show_error(
+                hwnd,
+                &format!("Die Suche nach Updates ist fehlgeschlagen.\n\n{}", reason),
+            ),
+        }
+    }
+
+    /// Runs a remote-control command from the pipe (see ipc.rs) and replies to it
+    fn handle_ipc_command(&mut self, request: &IpcRequest) {
+        use crate::ipc::{error_response, json_string, ok_response};
+
+        let has_document = self.state.lock().document.is_some();
+        let response = match request.command {
+            IpcCommand::Open(ref path) => {
+                // Same rules as deep links: nothing outside the restricted root
+                let outside_root = self
+                    .dialogs
+                    .restricted_path
+                    .as_deref()
+                    .is_some_and(|root| !crate::deeplink::is_within_root(path, root));
+                if outside_root {
+                    error_response("Die Datei liegt außerhalb des freigegebenen Verzeichnisses.")
+                } else {
+                    self.operation_log.record("Fernsteuerung: open");
+                    let previous = self.state.lock().file_path.clone();
+                    self.open_document(path);
+                    let state = self.state.lock();
+                    let loaded = state.load_error.is_none()
+                        && state.file_path.is_some()
+                        && (state.file_path.as_deref() == Some(path.as_str()) || state.file_path != previous);
+                    if loaded { ok_response() } else { error_response("Die Datei konnte nicht geöffnet werden.") }
+                }
+            }
+            _ if !has_document && !matches!(request.command, IpcCommand::Close | IpcCommand::Status) => {
+                error_response("Es ist kein Dokument geöffnet.")
+            }
+            IpcCommand::GotoPage(page) => {
+                let total = self.state.lock().total_pages;
+                if page > total {
+                    error_response(&format!("Das Dokument hat nur {} Seiten.", total))
+                } else {
+                    self.go_to_page(page - 1);
+                    ok_response()
+                }
+            }
+            IpcCommand::Zoom(Some(percent)) => {
+                self.set_zoom(percent / 100.0);
+                ok_response()
+            }
+            IpcCommand::Zoom(None) => {
+                self.cmd_fit_to_page();
+                ok_response()
+            }
+            IpcCommand::Close => {
+                self.ipc_close = true;
+                self.cmd_close();
+                ok_response()
+            }
+            IpcCommand::Status => {
+                let state = self.state.lock();
+                format!(
+                    "{{\"ok\":true,\"file\":{},\"page\":{},\"pages\":{},\"zoom\":{:.0},\"restricted\":{},\"viewOnly\":{}}}",
+                    state.file_path.as_deref().map_or("null".to_string(), json_string),
+                    if has_document { state.current_page + 1 } else { 0 },
+                    if has_document { state.total_pages } else { 0 },
+                    state.zoom * 100.0,
+                    self.dialogs.restricted_path.is_some(),
+                    self.capabilities == Capabilities::view_only(),
+                )
+            }
+        };
+        request.reply(response);
+    }
+
+    fn cmd_toggle_keep_view(&mut self) {
+        let enabled = !crate::settings::keep_view();
+        crate::settings::set_keep_view(enabled);
+        self.context_menu.set_checked(crate::menu::IDM_KEEP_VIEW, enabled);
+        if !enabled {
+            self.kept_view = None;
+        }
+    }
+
+    /// "Eingangsordner überwachen...": picks a folder to watch, or stops watching
+    fn cmd_toggle_inbox(&mut self) {
+        if self.inbox.take().is_some() {
+            self.inbox_paused = false;
+            self.inbox_pending = None;
+            self.statusbar.set_inbox_paused(false);
+            self.context_menu.set_checked(crate::menu::IDM_INBOX, false);
+            return;
+        }
+        if let Some(folder) = self.dialogs.pick_folder(self.window.hwnd(), "Eingangsordner auswählen") {
+            self.start_inbox(&folder, true);
+        }
+    }
+
+    /// "Unterordner einbeziehen": Back/Next also walk sibling and subfolders
+    fn cmd_toggle_include_subfolders(&mut self) {
+        let enabled = !crate::settings::include_subfolders();
+        crate::settings::set_include_subfolders(enabled);
+        self.context_menu.set_checked(crate::menu::IDM_INCLUDE_SUBFOLDERS, enabled);
+        {
+            let mut state = self.state.lock();
+            state.folder_recursive = enabled;
+            // Rescanned lazily on the next Back/Next
+            if state.navigation_context == NavigationContext::FolderBrowsing {
+                state.folder_cache_valid = false;
+                state.folder_root = None;
+            }
+        }
+        self.pending_folder_step = None;
+        self.update_navigation_buttons();
+        self.update_page_display_and_repaint();
+    }
+
+    fn cmd_toggle_escape_exits(&mut self) {
+        let enabled = !crate::settings::escape_exits();
+        crate::settings::set_escape_exits(enabled);
+        self.context_menu.set_checked(crate::menu::IDM_ESCAPE_EXITS, enabled);
+    }
+
+    fn cmd_toggle_inbox_sound(&mut self) {
+        let enabled = !crate::settings::inbox_sound();
+        crate::settings::set_inbox_sound(enabled);
+        self.context_menu.set_checked(crate::menu::IDM_INBOX_SOUND, enabled);
+    }
+
+    /// Starts the scan inbox on `folder`, showing its newest file if `open_newest`
+    fn start_inbox(&mut self, folder: &str, open_newest: bool) {
+        match InboxWatcher::start(self.window.hwnd(), folder) {
+            Ok(watcher) => {
+                self.operation_log.record(&format!("Eingangsordner überwachen: {}", watcher.folder()));
+                self.inbox = Some(watcher);
+                self.context_menu.set_checked(crate::menu::IDM_INBOX, true);
+                if let Some(newest) = crate::inbox::newest_file(folder).filter(|_| open_newest) {
+                    self.open_document(&newest);
+                }
+            }
+            Err(e) => self.show_error_details(
+                &format!("Der Eingangsordner kann nicht überwacht werden:\n{}", e.message()),
+                Some(&e),
+                Some(folder),
+            ),
+        }
+    }
+
+    /// A new file in the inbox finished being written
+    fn on_inbox_file(&mut self, path: String) {
+        if self.inbox.is_none() {
+            return;
+        }
+        if self.inbox_paused {
+            // Shown on "Fortsetzen"
+            self.inbox_pending = Some(path);
+            return;
+        }
+        self.open_inbox_file(&path);
+    }
+
+    fn open_inbox_file(&mut self, path: &str) {
+        self.unattended_open = true;
+        self.open_document(path);
+        self.unattended_open = false;
+        // A file that still failed after the retries was skipped
+        if self.state.lock().file_path.as_deref() != Some(path) {
+            return;
+        }
+        if crate::settings::inbox_sound() {
+            unsafe {
+                let _ = windows::Win32::System::Diagnostics::Debug::MessageBeep(MB_ICONASTERISK);
+            }
+        }
+        self.show_osd("Neues Dokument");
+    }
+
+    /// Manual navigation stops the inbox from switching documents until "Fortsetzen"
+    fn pause_inbox(&mut self) {
+        if self.inbox.is_some() && !self.inbox_paused {
+            self.inbox_paused = true;
+            self.statusbar.set_inbox_paused(true);
+        }
+    }
+
+    fn cmd_resume_inbox(&mut self) {
+        self.inbox_paused = false;
+        self.statusbar.set_inbox_paused(false);
+        if let Some(path) = self.inbox_pending.take() {
+            self.open_inbox_file(&path);
+        }
+    }
+
+    /// Safe mode: deletes the saved settings; this session keeps running on the defaults
+    fn cmd_reset_settings(&mut self) {
+        let hwnd = self.window.hwnd();
+        if !crate::dialogs::confirm_reset_settings(hwnd) {
+            return;
+        }
+        match crate::settings::reset() {
+            Ok(()) => {
+                self.statusbar.set_safe_mode(false);
+                self.show_osd("Einstellungen zurückgesetzt");
+            }
+            Err(e) => crate::dialogs::show_error(hwnd, &format!("Die Einstellungen konnten nicht zurückgesetzt werden.\n\n{}", e.message())),
+        }
+    }
+
+    fn show_osd(&mut self, message: &str) {
+        self.osd_message = Some(message.to_string());
+        unsafe {
+            SetTimer(self.window.hwnd(), OSD_TIMER, OSD_DURATION_MS, None);
+        }
+        self.invalidate();
+    }
+
+    fn hide_osd(&mut self) {
+        unsafe {
+            let _ = KillTimer(self.window.hwnd(), OSD_TIMER);
+        }
+        self.osd_message = None;
+        self.invalidate();
+    }
+
+    fn cmd_toggle_hover_toolbar(&mut self) {
+        let enabled = !self.hover_toolbar.is_enabled();
+        crate::settings::set_hover_toolbar(enabled);
+        self.hover_toolbar.set_enabled(enabled);
+        self.context_menu.set_checked(crate::menu::IDM_HOVER_TOOLBAR, enabled);
+        self.update_hover_toolbar_timer();
+        self.invalidate();
+    }
+
+    fn cmd_toggle_reopen_last_file(&mut self) {
+        let enabled = !crate::settings::reopen_last_file();
+        crate::settings::set_reopen_last_file(enabled);
+        self.context_menu.set_checked(crate::menu::IDM_REOPEN_LAST_FILE, enabled);
+    }
+
+    /// Last file for "Letzte Datei beim Start öffnen". Never in restricted mode; a file on a
+    /// removed drive or unreachable share is skipped quietly and the app starts empty.
+    fn last_file_to_reopen(&self) -> Option<String> {
+        if self.dialogs.restricted_path.is_some() || !crate::settings::reopen_last_file() {
+            return None;
+        }
+        crate::settings::last_file().filter(|path| {
+            crate::utils::unreachable_unc_share(path).is_none() && std::path::Path::new(path).is_file()
+        })
+    }
+
+    fn cmd_close(&self) { unsafe { let _ = PostMessageW(self.window.hwnd(), WM_CLOSE, WPARAM(0), LPARAM(0)); } }
+
+    fn cmd_print(&mut self) {
+        let (doc, current_page, transforms, file_path, total_pages, selection) = {
+            let state = self.state.lock();
+            if let Some(ref doc) = state.document {
+                let transforms: Vec<PageTransform> = (0..state.total_pages).map(|page| self.page_transform(&state, page)).collect();
+                (doc.clone(), state.current_page, transforms, state.file_path.clone(), state.total_pages, state.selection)
+            } else {
+                return;
+            }
+        };
+        let rotations: Vec<i32> = transforms.iter().map(|t| t.rotation).collect();
+        self.operation_log.record("Drucken");
+
+        unsafe {
+            // Prepare PRINTDLGW structure
+            let mut pd: PRINTDLGW = std::mem::zeroed();
+            pd.lStructSize = std::mem::size_of::<PRINTDLGW>() as u32;
+            pd.hwndOwner = crate::dialogs::dialog_owner(self.window.hwnd());
+            // Enable page numbers; "Nur Auswahl drucken" only with a Shift+drag selection.
+            // Without PD_USEDEVMODECOPIESANDCOLLATE the copies field stays usable for drivers
+            // that cannot make copies; those are then printed here (see below).
+            pd.Flags = PD_RETURNDC | PD_ENABLEPRINTHOOK;
+            if selection.is_none() {
+                pd.Flags |= PD_NOSELECTION;
+            }
+            pd.lpfnPrintHook = Some(print_dialog_hook);
+            // Thumbnail strip to pick single pages (see page_picker.rs)
+            let mut picker = (total_pages > 1).then(|| PagePicker::new(doc.clone(), rotations.clone(), current_page, &self.wic_loader, "drucken"));
+            if let Some(ref mut picker) = picker {
+                pd.lCustData = LPARAM(picker as *mut PagePicker as isize);
+            }
+            pd.nCopies = 1;
+            pd.nMinPage = 1;
+            pd.nMaxPage = total_pages as u16;
+            pd.nFromPage = (current_page + 1) as u16;
+            pd.nToPage = (current_page + 1) as u16;
+
+            // Show print dialog
+            if !PrintDlgW(&mut pd).as_bool() {
+                // User cancelled or error
+                return;
+            }
+            // Large jobs take minutes; don't let the PC fall asleep while spooling
+            let _keep_awake = KeepAwake::new();
+
+            // Get the printer DC
+            let hdc = pd.hDC;
+            if hdc.is_invalid() {
+                self.show_error("Drucker-Gerätekontext konnte nicht abgerufen werden");
+                return;
+            }
+
+            // Only the selected part when printing the selection
+            let selection = selection.filter(|_| (pd.Flags & PD_SELECTION) == PD_SELECTION);
+
+            // Determine page range
+            let pages: Vec<usize> = if let Some(sel) = selection {
+                vec![sel.page]
+            } else if let Some(picked) = picker.as_ref().and_then(|p| p.selected_pages()) {
+                // Pages ticked in the thumbnail strip override the range
+                picked
+            } else if (pd.Flags & PD_PAGENUMS) == PD_PAGENUMS {
+                // User selected range (1-based to 0-based)
+                let from = pd.nFromPage.max(1).min(total_pages as u16) as usize;
+                let to = pd.nToPage.max(from as u16).min(total_pages as u16) as usize;
+                (from - 1..to).collect()
+            } else {
+                // All pages
+                (0..total_pages).collect()
+            };
+
+            // Show wait cursor as printing might take time
+            let _wait = WaitCursorGuard::new();
+
+            // Prepare document name
+            let doc_name = file_path
+                .as_ref()
+                .and_then(|p| std::path::Path::new(p).file_name())
+                .and_then(|n| n.to_str())
+                .unwrap_or("SimpliView Document");
+
+            let job = match PrintJob::start(hdc, doc_name, None) {
+                Some(job) => job,
+                None => {
+                    self.show_error("Druckauftrag konnte nicht gestartet werden");
+                    // Free memory allocated by PrintDlgW
+                    if !pd.hDevMode.is_invalid() {
+                        let _ = GlobalFree(pd.hDevMode);
+                    }
+                    if !pd.hDevNames.is_invalid() {
+                        let _ = GlobalFree(pd.hDevNames);
+                    }
+                    return;
+                }
+            };
+
+            let mut success = true;
+
+            // Copies the driver makes are in the DEVMODE and nCopies is 1; otherwise nCopies
+            // and PD_COLLATE tell how to print them. A driver that reports its own copies
+            // is not sent the pages again.
+            let copies = if crate::print_job::driver_copies(pd.hDevMode) > 1 { 1 } else { pd.nCopies.max(1) as u32 };
+            let collate = (pd.Flags & PD_COLLATE) == PD_COLLATE;
+            if copies > 1 {
+                self.statusbar.set_printing_copies(copies, collate);
+            }
+
+            // Loop through pages; uncollated copies of a page reuse its bitmap
+            let pages = crate::print_job::copy_order(&pages, copies, collate);
+            let page_total = pages.len();
+            let mut prepared: Option<(usize, crate::wic::ClipboardBitmapData)> = None;
+            for (printed, page_idx) in pages.into_iter().enumerate() {
+                self.statusbar.set_progress(printed, page_total);
+                // Get bitmap data for printing
+                let bitmap_data = match prepared.take().filter(|&(page, _)| page == page_idx) {
+                    Some((_, data)) => Ok(data),
+                    None => {
+                        let shown = transforms.get(page_idx).copied().unwrap_or_default();
+                        let transform = match selection {
+                            Some(ref sel) => Self::selection_transform(&shown, sel),
+                            None => shown,
+                        };
+                        self.wic_loader.get_bitmap_for_clipboard(&doc, page_idx, &transform, None)
+                    }
+                };
+                let bitmap_data = match bitmap_data {
+                    Ok(data) => data,
+                    Err(e) => {
+                        self.show_error_details(
+                            &format!("Seite {} konnte nicht zum Drucken vorbereitet werden:\n{}", page_idx + 1, crate::load_error::describe(&e)),
+                            Some(&e),
+                            file_path.as_deref(),
+                        );
+                        success = false;
+                        break;
+                    }
+                };
+
+                if !job.print_page(&bitmap_data, self.watermark.as_ref(), page_idx, total_pages) {
+                    success = false;
+                    break;
+                }
+                prepared = Some((page_idx, bitmap_data));
+            }
+
+            job.finish();
+            self.statusbar.clear_progress();
+            if copies > 1 {
+                self.update_page_display(current_page, total_pages, file_path.as_deref());
+            }
+            if !success {
+                self.show_error("Drucken fehlgeschlagen oder abgebrochen");
+            }
+
+            // Free memory allocated by PrintDlgW
+            if !pd.hDevMode.is_invalid() {
+                let _ = GlobalFree(pd.hDevMode);
+            }
+            if !pd.hDevNames.is_invalid() {
+                let _ = GlobalFree(pd.hDevNames);
+            }
+        }
+    }
+
+    /// "Als PDF drucken": all pages through the "Microsoft Print to PDF" printer into a file
+    /// chosen in the save dialog, without the print dialog. Pages are fitted to the paper
+    /// like when printing; the paper is turned like the first page.
+    fn cmd_print_to_pdf(&mut self) {
+        let (doc, transforms, file_path, total_pages, landscape) = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            let transforms: Vec<PageTransform> = (0..state.total_pages).map(|page| self.page_transform(&state, page)).collect();
+            let [left, top, right, bottom] = transforms[0].source_rect(doc, 0);
+            let (width, height) = transforms[0].output_size(right - left, bottom - top);
+            (doc.clone(), transforms, state.file_path.clone(), state.total_pages, width > height)
+        };
+        let name = file_path
+            .as_ref()
+            .and_then(|p| std::path::Path::new(p).file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("SimpliView Document")
+            .to_string();
+        let stem = file_path.as_deref().and_then(|p| std::path::Path::new(p).file_stem()?.to_str()).unwrap_or("Dokument");
+        let Some(path) = self.dialogs.save_pdf(self.window.hwnd(), &format!("{}.pdf", stem)) else {
+            return;
+        };
+        if file_path.as_deref().is_some_and(|open| path.eq_ignore_ascii_case(open)) {
+            self.show_error("Das PDF kann nicht in die geöffnete Datei gedruckt werden. Bitte wählen Sie einen anderen Namen.");
+            return;
+        }
+        let printer = match PdfPrinter::open(landscape) {
+            Ok(printer) => printer,
+            Err(e) => {
+                self.show_error(&e.message().to_string());
+                return;
+            }
+        };
+        self.operation_log.record(&format!("Als PDF drucken: {}", path));
+
+        let _keep_awake = KeepAwake::new();
+        let _wait = WaitCursorGuard::new();
+        let Some(job) = PrintJob::start(printer.hdc, &name, Some(&path)) else {
+            self.show_error("Druckauftrag konnte nicht gestartet werden");
+            return;
+        };
+        let mut success = true;
+        for (page_idx, transform) in transforms.iter().enumerate() {
+            self.statusbar.set_progress(page_idx, total_pages);
+            let bitmap_data = match self.wic_loader.get_bitmap_for_clipboard(&doc, page_idx, transform, None) {
+                Ok(data) => data,
+                Err(e) => {
+                    self.show_error_details(
+                        &format!("Seite {} konnte nicht zum Drucken vorbereitet werden:\n{}", page_idx + 1, crate::load_error::describe(&e)),
+                        Some(&e),
+                        file_path.as_deref(),
+                    );
+                    success = false;
+                    break;
+                }
+            };
+            if !job.print_page(&bitmap_data, self.watermark.as_ref(), page_idx, total_pages) {
+                success = false;
+                break;
+            }
+        }
+        job.finish();
+        self.statusbar.clear_progress();
+        if success {
+            self.show_osd("Als PDF gedruckt");
+        } else {
+            self.show_error("Drucken als PDF fehlgeschlagen oder abgebrochen");
+        }
+    }
+
+    fn cmd_open(&mut self) {
+        // Ignore if open is disabled (file was passed via command line)
+        if self.open_disabled {
+            return;
+        }
+        let selection = self.dialogs.open_file(self.window.hwnd());
+        let files = selection.paths;
+        if files.is_empty() {
+            return;
+        }
+        self.pause_inbox();
+        if files.len() == 1 {
+            // A file outside an earlier selection goes back to browsing its whole folder
+            {
+                let mut state = self.state.lock();
+                if state.folder_recursive != selection.include_subfolders
+                    || !state.folder_files.iter().any(|f| f.eq_ignore_ascii_case(&files[0]))
+                {
+                    state.folder_cache_valid = false;
+                }
+                state.folder_recursive = selection.include_subfolders;
+            }
+            self.context_menu.set_checked(crate::menu::IDM_INCLUDE_SUBFOLDERS, selection.include_subfolders);
+            self.open_document(&files[0]);
+        } else {
+            self.open_selection(files);
+        }
+    }
+
+    /// Opens the first of several files chosen together; Back/Next then step through
+    /// exactly this selection instead of the whole folder
+    fn open_selection(&mut self, mut files: Vec<String>) {
+        files.sort_by_cached_key(|f| natural_sort_key(f));
+        let first = files[0].clone();
+        self.open_document(&first);
+
+        {
+            let mut state = self.state.lock();
+            // Not opened (e.g. unapplied changes kept)
+            if state.file_path.as_deref() != Some(first.as_str()) {
+                return;
+            }
+            state.folder_files = files;
+            state.folder_file_index = 0;
+            state.folder_cache_valid = true;
+            state.folder_recursive = false;
+            state.folder_navigation_mode = true;
+            state.navigation_context = NavigationContext::FolderBrowsing;
+        }
+        self.update_navigation_buttons();
+    }
+
+    /// Returns true if the page was exported
+    fn cmd_export(&mut self) -> bool {
+        let state = self.state.lock();
+        if state.document.is_some() {
+            let file_path = state.file_path.clone();
+            // Shown without white margins or straightened: offered to export it like that
+            let shown = self.page_transform(&state, state.current_page);
+            drop(state);
+
+            let (current_filename, extension) = if let Some(ref p) = file_path {
+                let path = std::path::Path::new(p);
+                let filename = path.file_name().and_then(|n| n.to_str()).map(|s| s.to_string());
+                let ext = path.extension().and_then(|e| e.to_str()).map(|s| s.to_string());
+                (filename, ext)
+            } else { (None, None) };
+
+            if let Some(target) = self.dialogs.save_file(
+                self.window.hwnd(),
+                current_filename.as_deref(),
+                extension.as_deref(),
+                shown.rotation != 0,
+                shown.crop.is_some(),
+                shown.skew != 0.0,
+            ) {
+                self.operation_log.record(&format!("Exportieren: {}", target.path));
+                let _keep_awake = KeepAwake::new();
+                // Saving over the open file is not a change by another program
+                let own_file = self.state.lock().file_path.as_deref().is_some_and(|path| path.eq_ignore_ascii_case(&target.path));
+                let watcher = self.file_watch.as_ref().filter(|_| own_file);
+                watcher.inspect(|watcher| watcher.expect_own_write());
+                let transform = PageTransform {
+                    crop: shown.crop.filter(|_| target.cropped),
+                    rotation: if target.apply_rotation { shown.rotation } else { 0 },
+                    skew: if target.deskewed { shown.skew } else { 0.0 },
+                };
+                let exported = self.export_document(&target.path, &transform, target.webp_lossless);
+                watcher.inspect(|watcher| watcher.expect_own_write());
+                // Exporting with the rotation keeps it, so it's no longer at risk
+                if exported && target.apply_rotation {
+                    self.set_unapplied_changes(false);
+                }
+                return exported;
+            }
+        }
+        false
+    }
+
+    /// "Übersichtsbild exportieren": thumbnails of all pages in one image (see contact_sheet.rs)
+    fn cmd_contact_sheet(&mut self) {
+        let (doc, rotations, file_path) = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            let rotations: Vec<i32> = (0..state.total_pages).map(|page| state.page_rotation(page)).collect();
+            (doc.clone(), rotations, state.file_path.clone())
+        };
+        let stem = file_path.as_deref().and_then(|p| std::path::Path::new(p).file_stem()?.to_str()).unwrap_or("Dokument");
+        let default_filename = format!("{} - Übersicht.png", stem);
+        let options = crate::settings::contact_sheet_options();
+        let Some((path, options)) = self.dialogs.save_contact_sheet(self.window.hwnd(), &default_filename, &options) else {
+            return;
+        };
+        crate::settings::set_contact_sheet_options(&options);
+        if path.to_lowercase().ends_with(".pdf") {
+            self.show_error("Das Übersichtsbild kann nur als Bild gespeichert werden. Bitte wählen Sie ein Bildformat.");
+            return;
+        }
+
+        let grid = ContactSheetGrid::new(rotations.len(), &options);
+        if grid.pages < rotations.len()
+            && !crate::dialogs::confirm_warning(
+                self.window.hwnd(),
+                &format!(
+                    "Das Übersichtsbild zeigt nur die ersten {} von {} Seiten.\nEs ist auf {} × {} Pixel begrenzt.",
+                    grid.pages,
+                    rotations.len(),
+                    grid.width,
+                    grid.height
+                ),
+            )
+        {
+            return;
+        }
+
+        self.operation_log.record(&format!("Übersichtsbild exportieren: {}", path));
+        let _keep_awake = KeepAwake::new();
+        let result = {
+            let _wait_cursor = WaitCursorGuard::new();
+            self.write_contact_sheet(&doc, &rotations, grid, &path)
+        };
+        self.statusbar.clear_progress();
+        if let Err(e) = result {
+            self.show_error_details(&format!("Export fehlgeschlagen:\n{}", crate::load_error::describe(&e)), Some(&e), Some(&path));
+        }
+    }
+
+    /// "Seiten extrahieren": the chosen pages of the PDF as a new PDF, copied without
+    /// rendering (see pdf_extract.rs)
+    fn cmd_extract_pages(&mut self) {
+        // The copied pages would not carry the compliance watermark
+        if self.watermark.is_some() {
+            self.show_error("Seiten können nicht extrahiert werden, solange ein Wasserzeichen vorgegeben ist.");
+            return;
+        }
+        let (doc, rotations, file_path, current_page) = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            let Some(ref file_path) = state.file_path else { return };
+            if doc.doc_type() != crate::document::DocumentType::Pdf {
+                return;
+            }
+            let rotations: Vec<i32> = (0..state.total_pages).map(|page| state.page_rotation(page)).collect();
+            (doc.clone(), rotations, file_path.clone(), state.current_page)
+        };
+        let page_count = rotations.len();
+        let pages = {
+            let mut picker = PagePicker::new(doc, rotations, current_page, &self.wic_loader, "extrahieren");
+            crate::dialogs::extract_pages_dialog(self.window.hwnd(), "Seiten extrahieren", &mut picker, page_count, current_page)
+        };
+        let Some(pages) = pages else { return };
+
+        let stem = std::path::Path::new(&file_path).file_stem().and_then(|s| s.to_str()).unwrap_or("Dokument");
+        let range = crate::page_picker::format_pages(&pages);
+        let default_filename = match pages.len() {
+            1 => format!("{} - Seite {}.pdf", stem, range),
+            // Long lists make unwieldy file names
+            _ if range.len() > 30 => format!("{} - Auszug.pdf", stem),
+            _ => format!("{} - Seiten {}.pdf", stem, range),
+        };
+        let Some(path) = self.dialogs.save_pdf(self.window.hwnd(), &default_filename) else {
+            return;
+        };
+        if path.eq_ignore_ascii_case(&file_path) {
+            self.show_error("Die Seiten können nicht in die geöffnete Datei gespeichert werden. Bitte wählen Sie einen anderen Namen.");
+            return;
+        }
+
+        self.operation_log.record(&format!("Seiten extrahieren ({}): {}", range, path));
+        let result = {
+            let _wait_cursor = WaitCursorGuard::new();
+            crate::pdf_extract::extract_pages(&file_path, &pages, &path)
+        };
+        match result {
+            Ok(()) if pages.len() == 1 => self.show_osd("1 Seite extrahiert"),
+            Ok(()) => self.show_osd(&format!("{} Seiten extrahiert", pages.len())),
+            Err(e) => self.show_error_details(&format!("Extrahieren fehlgeschlagen:\n{}", crate::load_error::describe(&e)), Some(&e), Some(&path)),
+        }
+    }
+
+    /// "Seiten als Dateien kopieren": the chosen pages as PNG files on the clipboard, e.g. to
+    /// attach them to an e-mail (see page_files.rs)
+    fn cmd_copy_page_files(&mut self) {
+        let (doc, rotations, file_path, current_page) = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            let rotations: Vec<i32> = (0..state.total_pages).map(|page| state.page_rotation(page)).collect();
+            (doc.clone(), rotations, state.file_path.clone(), state.current_page)
+        };
+        let page_count = rotations.len();
+        let pages = {
+            let mut picker = PagePicker::new(doc.clone(), rotations.clone(), current_page, &self.wic_loader, "kopieren");
+            crate::dialogs::extract_pages_dialog(self.window.hwnd(), "Seiten als Dateien kopieren", &mut picker, page_count, current_page)
+        };
+        let Some(pages) = pages else { return };
+
+        let stem = file_path.as_deref().and_then(|p| std::path::Path::new(p).file_stem()?.to_str()).unwrap_or("Dokument");
+        self.operation_log.record(&format!("Seiten als Dateien kopieren ({})", crate::page_picker::format_pages(&pages)));
+        // The files of the previous copy go first: the new ones take the same folder
+        self.page_files = None;
+        let result = {
+            let _wait_cursor = WaitCursorGuard::new();
+            crate::page_files::PageFiles::write(&self.wic_loader, &doc, &pages, &rotations, stem, self.watermark.as_ref())
+        };
+        let copied = result.and_then(|page_files| {
+            page_files.copy_to_clipboard(self.window.hwnd())?;
+            Ok(page_files)
+        });
+        match copied {
+            Ok(page_files) => {
+                self.page_files = Some(page_files);
+                match pages.len() {
+                    1 => self.show_osd("1 Seite als Datei kopiert"),
+                    count => self.show_osd(&format!("{} Seiten als Dateien kopiert", count)),
+                }
+            }
+            Err(e) => self.show_error_details(&format!("Kopieren fehlgeschlagen:\n{}", crate::load_error::describe(&e)), Some(&e), None),
+        }
+    }
+
+    /// "Anmerkungen": reads the annotations of the open PDF on a worker thread; they are
+    /// listed when they arrive (`on_annotations_read`)
+    fn cmd_annotations(&mut self) {
+        let file_path = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            if doc.doc_type() != crate::document::DocumentType::Pdf {
+                return;
+            }
+            let Some(ref file_path) = state.file_path else { return };
+            file_path.clone()
+        };
+        self.annotations_request += 1;
+        if !crate::annotations::start_read(self.window.hwnd(), file_path, self.annotations_request) {
+            crate::dialogs::show_error(self.window.hwnd(), "Die Anmerkungen konnten nicht gelesen werden.");
+        }
+    }
+
+    /// The annotations of the open PDF arrived: lists them in the "Anmerkungen" window
+    fn on_annotations_read(&mut self, result: crate::annotations::ReadResult) {
+        if result.request != self.annotations_request || self.state.lock().file_path.as_deref() != Some(result.path.as_str()) {
+            return;
+        }
+        let annotations = match result.annotations {
+            Ok(annotations) => annotations,
+            Err((code, message)) => {
+                let e = Error::new(code, HSTRING::from(message));
+                self.show_error_details(&format!("Anmerkungen konnten nicht gelesen werden:\n{}", crate::load_error::describe(&e)), Some(&e), Some(&result.path));
+                return;
+            }
+        };
+        self.annotation_marker = None;
+        if annotations.is_empty() {
+            self.close_annotations();
+            self.invalidate();
+            self.show_osd("Das Dokument enthält keine Anmerkungen");
+            return;
+        }
+        if self.annotation_panel.is_none() {
+            match AnnotationPanel::new(self.window.hwnd(), self.window.instance()) {
+                Ok(panel) => self.annotation_panel = Some(panel),
+                Err(e) => {
+                    self.show_error_details("Die Anmerkungen können nicht angezeigt werden.", Some(&e), None);
+                    return;
+                }
+            }
+        }
+        if let Some(ref panel) = self.annotation_panel {
+            panel.show(&annotations);
+        }
+        self.annotations = annotations;
+        self.invalidate();
+    }
+
+    /// An entry of the "Anmerkungen" list was chosen: shows its page and marks it
+    fn on_annotation_selected(&mut self, index: usize) {
+        let Some(annotation) = self.annotations.get(index) else { return };
+        let (page, rect) = (annotation.page, annotation.rect);
+        if page >= self.state.lock().total_pages {
+            return;
+        }
+        self.go_to_page(page);
+
+        let state = self.state.lock();
+        let Some(ref doc) = state.document else { return };
+        let (width, height) = doc.page_dimensions(page);
+        self.annotation_marker = rect.map(|[left, top, right, bottom]| Selection {
+            page,
+            left: left * width,
+            top: top * height,
+            right: right * width,
+            bottom: bottom * height,
+        });
+        // Scroll annotations low on a long page into view
+        let view_rect = self.annotation_marker.and_then(|marker| self.selection_view_rect(&state, &marker));
+        let (_, viewport_height) = self.renderer.size();
+        let max_y = (state.content_height - viewport_height as i32).max(0);
+        let scroll_y = state.scroll_y;
+        drop(state);
+        if let Some(rect) = view_rect.filter(|r| r.top < 0.0 || r.bottom > viewport_height as f32) {
+            let margin = viewport_height as f32 / 4.0;
+            let new_y = (scroll_y + (rect.top - margin) as i32).clamp(0, max_y);
+            self.state.lock().scroll_y = new_y;
+            self.scroll_manager.set_pos(SB_VERT, new_y);
+        }
+        self.invalidate();
+    }
+
+    /// "Ausschnitt anheften": the selection as displayed, in a window of its own on top
+    fn cmd_pin_selection(&mut self) {
+        self.pins.retain(Pin::is_open);
+        if self.pins.len() >= crate::pins::MAX_PINS {
+            self.show_osd(&format!("Es können höchstens {} Ausschnitte angeheftet werden", crate::pins::MAX_PINS));
+            return;
+        }
+        let (doc, sel, transform, view_rect) = {
+            let state = self.state.lock();
+            let (Some(doc), Some(sel)) = (state.document.clone(), state.selection) else { return };
+            let Some(view_rect) = self.selection_view_rect(&state, &sel) else { return };
+            (doc, sel, Self::selection_transform(&self.page_transform(&state, sel.page), &sel), view_rect)
+        };
+        let pixels = match self.wic_loader.get_bitmap_for_clipboard(&doc, sel.page, &transform, self.watermark.as_ref()) {
+            Ok(pixels) => pixels,
+            Err(e) => {
+                self.show_error_details("Der Ausschnitt konnte nicht gelesen werden.", Some(&e), None);
+                return;
+            }
+        };
+        // Over the selection, at the size it is shown now
+        let mut position = POINT { x: view_rect.left.round() as i32, y: view_rect.top.round() as i32 };
         unsafe {
-            // Prepare PRINTDLGW structure
-            let mut pd: PRINTDLGW = std::mem::zeroed();
-            pd.lStructSize = std::mem::size_of::<PRINTDLGW>() as u32;
-            pd.hwndOwner = self.window.hwnd();
-            // Enable page numbers, disable selection (we don't support text selection)
-            pd.Flags = PD_RETURNDC | PD_NOSELECTION | PD_USEDEVMODECOPIESANDCOLLATE;
-            pd.nCopies = 1;
-            pd.nMinPage = 1;
-            pd.nMaxPage = total_pages as u16;
-            pd.nFromPage = (current_page + 1) as u16;
-            pd.nToPage = (current_page + 1) as u16;
+            let _ = ClientToScreen(self.view_window.hwnd(), &mut position);
+        }
+        let size = ((view_rect.right - view_rect.left).round() as i32, (view_rect.bottom - view_rect.top).round() as i32);
+        let title = format!("Seite {}", sel.page + 1);
+        match Pin::new(self.window.hwnd(), self.window.instance(), &title, pixels, position, size) {
+            Ok(pin) => self.pins.push(pin),
+            Err(e) => self.show_error_details("Der Ausschnitt konnte nicht angeheftet werden.", Some(&e), None),
+        }
+    }
+
+    /// Closes the "Ausschnitt anheften" windows, e.g. when another document opens
+    fn close_pins(&mut self) {
+        for pin in self.pins.drain(..) {
+            pin.close();
+        }
+    }
+
+    /// Hides the "Anmerkungen" window and the marker, e.g. when another document opens
+    fn close_annotations(&mut self) {
+        // A read still running is for the document that is closed
+        self.annotations_request += 1;
+        self.annotations.clear();
+        self.annotation_marker = None;
+        if let Some(ref panel) = self.annotation_panel {
+            panel.hide();
+        }
+    }
+
+    /// Lays out the thumbnails and saves the sheet; pages that fail to render stay empty
+    fn write_contact_sheet(&self, doc: &Document, rotations: &[i32], grid: ContactSheetGrid, path: &str) -> Result<()> {
+        let mut progress = Self::load_progress(&self.statusbar);
+        let mut sheet = crate::contact_sheet::Sheet::new(grid);
+        for (page, &rotation) in rotations.iter().enumerate().take(grid.pages) {
+            progress(page, grid.pages);
+            match self.wic_loader.get_thumbnail(doc, page, rotation, grid.cell) {
+                Ok(thumbnail) => sheet.place(page, &thumbnail),
+                Err(_) if doc.page_state(page) == PageState::Failed => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let sheet = Document::new_image(sheet.finish()?, grid.width, grid.height, None, None);
+        // The watermark goes across the whole sheet; lossless WebP keeps the page numbers sharp
+        self.wic_loader.save(&sheet, path, 0, &PageTransform::default(), self.watermark.as_ref(), true)
+    }
+
+    fn set_unapplied_changes(&mut self, changed: bool) {
+        self.state.lock().has_unapplied_changes = changed;
+        self.statusbar.set_modified(changed);
+    }
+
+    /// "SimpliView beenden?" if enabled. Skipped when the unapplied-changes prompt asks anyway.
+    /// Returns false if the user wants to stay.
+    fn confirm_close(&mut self) -> bool {
+        let changes_prompt = self.confirm_unapplied_changes && self.state.lock().has_unapplied_changes;
+        if !self.confirm_exit || changes_prompt {
+            return true;
+        }
+        let answer = crate::dialogs::confirm_exit(self.window.hwnd());
+        if answer.dont_ask_again {
+            self.confirm_exit = false;
+            crate::settings::set_confirm_exit(false);
+        }
+        answer.exit
+    }
+
+    /// Offers to export unapplied changes before the document is closed or replaced.
+    /// Returns false if the user cancels.
+    fn confirm_discard_changes(&mut self) -> bool {
+        if !self.confirm_unapplied_changes || !self.state.lock().has_unapplied_changes {
+            return true;
+        }
+        match crate::dialogs::confirm_unapplied_changes(self.window.hwnd(), self.capabilities.export) {
+            // Canceling the export dialog keeps the document open
+            UnappliedChangesChoice::Export => self.cmd_export(),
+            UnappliedChangesChoice::Discard => true,
+            UnappliedChangesChoice::Cancel => false,
+        }
+    }
+
+    fn cmd_rotate_left(&mut self) {
+        self.rotate_document(270);
+    }
+
+    fn cmd_rotate_right(&mut self) {
+        self.rotate_document(90);
+    }
+
+    fn cmd_rotate_180(&mut self) {
+        self.rotate_document(180);
+    }
+
+    /// Rotates all pages clockwise by `delta` degrees (90, 180 or 270)
+    fn rotate_document(&mut self, delta: i32) {
+        self.record_view();
+        {
+            let mut state = self.state.lock();
+            state.rotation = (state.rotation + delta) % 360;
+            state.scroll_x = 0;
+            state.scroll_y = 0;
+        }
+        self.reapply_zoom_mode();
+        let rotated = self.state.lock().is_rotated();
+        self.set_unapplied_changes(rotated);
+        self.update_content_size();
+        self.invalidate();
+    }
+
+    /// Rotates a single page on top of the document rotation (hover toolbar).
+    /// `delta` is 90 (right) or 270 (left).
+    fn rotate_page(&mut self, page: usize, delta: i32) {
+        self.record_view();
+        {
+            let mut state = self.state.lock();
+            let rotation = (state.page_rotations.get(&page).copied().unwrap_or(0) + delta) % 360;
+            if rotation == 0 {
+                state.page_rotations.remove(&page);
+            } else {
+                state.page_rotations.insert(page, rotation);
+            }
+        }
+        self.reapply_zoom_mode();
+        let rotated = self.state.lock().is_rotated();
+        self.set_unapplied_changes(rotated);
+        self.update_content_size();
+        self.invalidate();
+    }
+
+    /// Ensure folder cache is populated (lazy enumeration).
+    /// Returns false while a recursive scan is still running (see folder_scan.rs).
+    fn ensure_folder_cache(&mut self) -> bool {
+        let state = self.state.lock();
+        let cache_valid = state.folder_cache_valid;
+        let file_path = state.file_path.clone();
+        let recursive = state.folder_recursive;
+        drop(state);
+
+        if cache_valid {
+            return true; // Cache already valid
+        }
+
+        // Perform lazy folder scan on a worker thread
+        let Some(path) = file_path else { return true };
+        if self.folder_scanning {
+            return false;
+        }
+        let restricted_root = self.dialogs.restricted_path.as_deref();
+        if crate::folder_scan::start_scan(self.window.hwnd(), &path, recursive, restricted_root) {
+            self.folder_scanning = true;
+            if recursive {
+                self.show_osd("Unterordner werden durchsucht...");
+            }
+            return false;
+        }
+        self.ensure_folder_cache_flat();
+        true
+    }
+
+    /// A folder scan finished; takes over the list and performs a Back/Next clicked meanwhile
+    fn on_folder_scanned(&mut self, scan: FolderScan) {
+        self.folder_scanning = false;
+        self.statusbar.clear_progress();
+        let step = self.pending_folder_step.take();
+        {
+            let mut state = self.state.lock();
+            // Another file was opened or the option was switched off meanwhile
+            let current = state.file_path.as_deref().is_some_and(|p| p.eq_ignore_ascii_case(&scan.path));
+            if !current || state.folder_recursive != scan.recursive || state.folder_cache_valid {
+                return;
+            }
+            let Some(idx) = scan.files.iter().position(|f| f.eq_ignore_ascii_case(&scan.path)) else {
+                // Beyond the cap of a recursive walk: only the file's own folder
+                drop(state);
+                self.ensure_folder_cache_flat();
+                return;
+            };
+            state.folder_files = scan.files;
+            state.folder_file_index = idx;
+            state.folder_cache_valid = true;
+            state.folder_root = scan.recursive.then_some(scan.root);
+        }
+        self.update_navigation_buttons();
+        self.update_page_display_and_repaint();
+        if scan.truncated {
+            self.show_osd(&format!("Nur die ersten {} Dateien werden durchlaufen", crate::folder_scan::max_files()));
+        }
+        match step {
+            Some(true) => self.cmd_next_page(),
+            Some(false) => self.cmd_prev_page(),
+            None => {}
+        }
+    }
+
+    /// Lists only the current file's folder (also the fallback for "Unterordner einbeziehen")
+    fn ensure_folder_cache_flat(&mut self) {
+        let Some(path) = self.state.lock().file_path.clone() else { return };
+        let (files, idx) = crate::folder_scan::scan_folder(&path);
+        {
+            let mut state = self.state.lock();
+            state.folder_files = files;
+            state.folder_file_index = idx;
+            state.folder_cache_valid = true;
+            state.folder_root = None;
+        }
+        // Update button states now that we know the actual folder size
+        self.update_navigation_buttons();
+    }
+
+    /// Update Back/Next button states based on current navigation context and position.
+    /// Single source of truth for navigation button enable/disable logic.
+    fn update_navigation_buttons(&mut self) {
+        let (back_enabled, next_enabled) = navigation_targets(&self.state.lock());
+        self.top_toolbar.set_navigation_buttons(back_enabled, next_enabled);
+    }
+
+    fn cmd_prev_page(&mut self) {
+        self.pause_inbox();
+        let state = self.state.lock();
+        let nav_context = state.navigation_context;
+        let current_page = state.current_page;
+        let is_multipage = state.multi_page_view && state.total_pages > 1;
+        drop(state);
+
+        match nav_context {
+            NavigationContext::Disabled => {
+                // Navigation disabled, do nothing
+            }
+            NavigationContext::FolderBrowsing => {
+                // Ensure folder cache is populated (lazy enumeration)
+                if !self.ensure_folder_cache() {
+                    self.pending_folder_step = Some(false);
+                    return;
+                }
 
-            // Show print dialog
-            if !PrintDlgW(&mut pd).as_bool() {
-                // User cancelled or error
+                let state = self.state.lock();
+                let folder_files = state.folder_files.clone();
+                let folder_index = state.folder_file_index;
+                let failed_files = state.failed_files.clone();
+                drop(state);
+
+                // Skip files that already failed to load
+                let prev_file = folder_files[..folder_index.min(folder_files.len())]
+                    .iter()
+                    .rev()
+                    .find(|f| !failed_files.contains(*f))
+                    .cloned();
+                if let Some(prev_file) = prev_file {
+                    self.open_document_with_mode(&prev_file, true);
+                    // Note: open_document_with_mode calls update_navigation_buttons
+                }
+            }
+            NavigationContext::DocumentPaging => {
+                if current_page > 0 {
+                    if is_multipage {
+                        // In multi-page mode, scroll to previous page
+                        self.scroll_to_page(current_page - 1);
+                    } else {
+                        self.show_single_page(current_page - 1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn cmd_next_page(&mut self) {
+        self.pause_inbox();
+        let state = self.state.lock();
+        let nav_context = state.navigation_context;
+        let current_page = state.current_page;
+        let total_pages = state.total_pages;
+        let is_multipage = state.multi_page_view && state.total_pages > 1;
+        drop(state);
+
+        match nav_context {
+            NavigationContext::Disabled => {
+                // Navigation disabled, do nothing
+            }
+            NavigationContext::FolderBrowsing => {
+                // Ensure folder cache is populated (lazy enumeration)
+                if !self.ensure_folder_cache() {
+                    self.pending_folder_step = Some(true);
+                    return;
+                }
+
+                let state = self.state.lock();
+                let folder_files = state.folder_files.clone();
+                let folder_index = state.folder_file_index;
+                let failed_files = state.failed_files.clone();
+                drop(state);
+
+                // Skip files that already failed to load
+                let next_file = folder_files
+                    .iter()
+                    .skip(folder_index + 1)
+                    .find(|f| !failed_files.contains(*f))
+                    .cloned();
+                if let Some(next_file) = next_file {
+                    self.open_document_with_mode(&next_file, true);
+                    // Note: open_document_with_mode calls update_navigation_buttons
+                }
+            }
+            NavigationContext::DocumentPaging => {
+                if current_page < total_pages - 1 {
+                    if is_multipage {
+                        // In multi-page mode, scroll to next page
+                        self.scroll_to_page(current_page + 1);
+                    } else {
+                        self.show_single_page(current_page + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn cmd_first_page(&mut self) {
+        self.go_to_page(0);
+    }
+
+    fn cmd_last_page(&mut self) {
+        let last = self.state.lock().total_pages.saturating_sub(1);
+        self.go_to_page(last);
+    }
+
+    /// "Gehe zu Seite": asks for a page number or, in PDFs with page labels, a label
+    fn cmd_go_to_page(&mut self) {
+        let (doc, current_page) = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            if state.total_pages < 2 {
                 return;
             }
+            (doc.clone(), state.current_page)
+        };
+        if let Some(page) = crate::dialogs::go_to_page_dialog(self.window.hwnd(), &doc, current_page) {
+            self.record_view();
+            self.go_to_page(page);
+        }
+    }
+
+    /// Jumps to a page (0-based, clamped to the document)
+    fn go_to_page(&mut self, page: usize) {
+        let state = self.state.lock();
+        if state.total_pages == 0 { return; }
+        let page = page.min(state.total_pages - 1);
+        let is_multipage = state.multi_page_view && state.total_pages > 1;
+        let current_page = state.current_page;
+        drop(state);
+
+        if current_page == page { return; }
+
+        if is_multipage {
+            self.scroll_to_page(page);
+            // Note: scroll_to_page calls update_navigation_buttons
+        } else {
+            self.show_single_page(page);
+        }
+    }
+
+    /// Single page mode: switches to `page`, shown from its top left. A fit mode stays on,
+    /// so a page of another size is fitted again instead of keeping the previous zoom.
+    fn show_single_page(&mut self, page: usize) {
+        {
+            let mut state = self.state.lock();
+            state.current_page = page;
+            state.scroll_x = 0;
+            state.scroll_y = 0;
+        }
+        self.reapply_zoom_mode();
+        self.update_page_display_and_repaint();
+        self.update_navigation_buttons();
+    }
+
+    /// Scroll to bring a specific page into view (for multi-page mode)
+    fn scroll_to_page(&mut self, page: usize) {
+        let mut state = self.state.lock();
+
+        if let Some(ref layout) = state.page_layout {
+            if page < layout.page_tops.len() {
+                // Scroll to the top of the requested page
+                let target_y = layout.scroll_target(page);
+
+                // Clamp to valid range
+                let (_, render_h) = self.renderer.size();
+                let viewport_height = render_h as i32;
+                let max_y = (state.content_height - viewport_height).max(0);
+                let new_y = target_y.clamp(0, max_y);
+
+                state.scroll_y = new_y;
+                state.current_page = page;
+                let total = state.total_pages;
+                let path = state.file_path.clone();
+                drop(state);
+
+                self.scroll_manager.set_pos(SB_VERT, new_y);
+                self.update_page_display(page, total, path.as_deref());
+                self.update_navigation_buttons();
+                self.invalidate();
 
-            // Get the printer DC
-            let hdc = pd.hDC;
-            if hdc.is_invalid() {
-                self.show_error("Drucker-Gerätekontext konnte nicht abgerufen werden");
                 return;
             }
+        }
 
-            // Determine page range
-            let (start_page, end_page) = if (pd.Flags & PD_PAGENUMS) == PD_PAGENUMS {
-                // User selected range (1-based to 0-based)
-                let from = pd.nFromPage.max(1).min(total_pages as u16) as usize;
-                let to = pd.nToPage.max(from as u16).min(total_pages as u16) as usize;
-                (from - 1, to - 1)
-            } else {
-                // All pages
-                (0, total_pages - 1)
-            };
+        // Fallback: just update current page
+        state.current_page = page;
+        drop(state);
+        self.update_page_display_and_repaint();
+        self.update_navigation_buttons();
+    }
 
-            // Show wait cursor as printing might take time
-            let _wait = WaitCursorGuard::new();
+    fn update_page_display_and_repaint(&mut self) {
+        let state = self.state.lock();
+        let page = state.current_page;
+        let total = state.total_pages;
+        let path = state.file_path.clone();
+        drop(state);
+        self.update_page_display(page, total, path.as_deref());
+        self.update_content_size();
+        self.invalidate();
+    }
 
-            // Prepare document name
-            let doc_name = file_path
-                .as_ref()
-                .and_then(|p| std::path::Path::new(p).file_name())
-                .and_then(|n| n.to_str())
-                .unwrap_or("SimpliView Document");
-            let doc_name_wide: Vec<u16> = doc_name.encode_utf16().chain(std::iter::once(0)).collect();
-
-            // Start document
-            let doc_info = DOCINFOW {
-                cb_size: std::mem::size_of::<DOCINFOW>() as i32,
-                lpsz_doc_name: PCWSTR(doc_name_wide.as_ptr()),
-                lpsz_output: PCWSTR::null(),
-                lpsz_datatype: PCWSTR::null(),
-                fw_type: 0,
-            };
+    /// Notes the view before a view command changes it, for Strg+Z
+    fn record_view(&mut self) {
+        let state = self.state.lock();
+        if state.document.is_some() {
+            self.view_history.record(ViewSnapshot::capture(&state));
+        }
+    }
+
+    /// Strg+Z / Strg+Y: back to the view before the last view command, or forward again
+    fn restore_view(&mut self, redo: bool) {
+        let view = {
+            let state = self.state.lock();
+            if state.document.is_none() {
+                return;
+            }
+            let current = ViewSnapshot::capture(&state);
+            if redo { self.view_history.redo(current) } else { self.view_history.undo(current) }
+        };
+        let Some(view) = view else { return };
+
+        view.restore(&mut self.state.lock());
+        self.reapply_zoom_mode();
+        let rotated = self.state.lock().is_rotated();
+        self.set_unapplied_changes(rotated);
+        self.update_content_size();
+        // The scroll position was clamped to the old layout; set it again on the restored one
+        {
+            let mut state = self.state.lock();
+            state.scroll_x = view.scroll_x;
+            state.scroll_y = view.scroll_y;
+        }
+        self.update_content_size();
+
+        if self.state.lock().page_layout.is_some() {
+            self.update_current_page_from_scroll();
+        }
+        self.update_page_display_and_repaint();
+        self.update_navigation_buttons();
+    }
+
+    // Zoom levels - finer increments for smoother Ctrl+Wheel zooming
+    const ZOOM_LEVELS: &'static [f32] = &[
+        0.10, 0.125, 0.15, 0.175, 0.20, 0.25, 0.33, 0.40, 0.50, 0.60, 0.67, 0.75, 0.85,
+        1.00, 1.10, 1.25, 1.40, 1.50, 1.75, 2.00, 2.50, 3.00, 4.00, 5.00, 6.00, 8.00, 10.00
+    ];
+
+    /// Smallest step that counts as a change; far below the closest pair of levels, so
+    /// no level is skipped from a zoom between them (e.g. after a fit)
+    const ZOOM_STEP_TOLERANCE: f32 = 1.005;
+
+    /// Next zoom level above `current`, the largest one at the top
+    fn next_zoom_level(current: f32) -> f32 {
+        let levels = Self::ZOOM_LEVELS;
+        levels.iter().copied().find(|&z| z > current * Self::ZOOM_STEP_TOLERANCE).unwrap_or(levels[levels.len() - 1])
+    }
+
+    /// Next zoom level below `current`, the smallest one at the bottom
+    fn previous_zoom_level(current: f32) -> f32 {
+        let levels = Self::ZOOM_LEVELS;
+        levels.iter().rev().copied().find(|&z| z * Self::ZOOM_STEP_TOLERANCE < current).unwrap_or(levels[0])
+    }
+
+    fn cmd_zoom_in(&mut self) {
+        self.record_view();
+        let new_zoom = Self::next_zoom_level(self.state.lock().zoom);
+        self.set_zoom_mode(ZoomMode::Fixed(new_zoom));
+        self.update_content_size();
+        self.invalidate();
+    }
+
+    fn cmd_zoom_out(&mut self) {
+        self.record_view();
+        let new_zoom = Self::previous_zoom_level(self.state.lock().zoom);
+        self.set_zoom_mode(ZoomMode::Fixed(new_zoom));
+        self.update_content_size();
+        self.invalidate();
+    }
+
+    fn cmd_zoom_reset(&mut self) {
+        self.set_zoom(1.0);
+    }
+
+    fn set_zoom(&mut self, zoom: f32) {
+        self.record_view();
+        self.set_zoom_mode(ZoomMode::Fixed(zoom));
+        self.update_content_size();
+        self.invalidate();
+    }
+
+    /// "Anpassen..." in the Zoom submenu
+    fn cmd_zoom_custom(&mut self) {
+        let current = (self.state.lock().zoom * 100.0).round() as u32;
+        if let Some(percent) = crate::dialogs::zoom_dialog(self.window.hwnd(), current) {
+            self.set_zoom(percent as f32 / 100.0);
+        }
+    }
+
+    /// "Fortlaufend" / "Einzelseite" for multi-page documents
+    fn set_continuous_view(&mut self, continuous: bool) {
+        self.record_view();
+        let page = self.get_most_visible_page();
+        {
+            let mut state = self.state.lock();
+            if state.multi_page_view == continuous {
+                return;
+            }
+            state.multi_page_view = continuous;
+            state.current_page = page;
+            state.scroll_x = 0;
+            state.scroll_y = 0;
+        }
+        self.reapply_zoom_mode();
+        self.update_content_size();
+        if continuous {
+            self.scroll_to_page(page);
+        }
+        self.update_page_display_and_repaint();
+        self.update_navigation_buttons();
+    }
+
+    /// "Vollbild" (F11): the document fills the monitor without toolbar and status bar
+    fn cmd_toggle_fullscreen(&mut self) {
+        let fullscreen = !self.window.is_fullscreen();
+        self.window.set_fullscreen(fullscreen);
+        self.show_bars();
+    }
+
+    /// "Kompaktmodus" (F9): only a thin border around the document, for a small window next
+    /// to other programs. The toolbar slides in at the top edge; otherwise the keyboard
+    /// shortcuts and the context menu are the controls.
+    fn cmd_toggle_compact(&mut self) {
+        let compact = !self.window.is_compact();
+        crate::settings::set_compact_mode(compact);
+        self.window.set_compact(compact);
+        self.set_compact_toolbar(false);
+        self.show_bars();
+        self.show_osd(if compact { "Kompaktmodus: ein (F9 beendet)" } else { "Kompaktmodus: aus" });
+    }
+
+    /// "Ränder automatisch zuschneiden": scans are shown without their white margins, so
+    /// fitting to the window shows the text larger. Export and print keep the whole page.
+    fn cmd_toggle_auto_crop(&mut self) {
+        let auto_crop = {
+            let mut state = self.state.lock();
+            state.auto_crop = !state.auto_crop;
+            state.auto_crop
+        };
+        crate::settings::set_auto_crop(auto_crop);
+        self.refit();
+        self.show_osd(if auto_crop { "Ränder zuschneiden: ein" } else { "Ränder zuschneiden: aus" });
+    }
+
+    /// "Schräglage automatisch korrigieren": crooked scans are shown straight. Each page is
+    /// measured once when it comes into view (see deskew.rs).
+    fn cmd_toggle_deskew(&mut self) {
+        self.deskew = !self.deskew;
+        crate::settings::set_deskew(self.deskew);
+        self.update_skew_display();
+        self.invalidate();
+        self.show_osd(if self.deskew { "Schräglage korrigieren: ein" } else { "Schräglage korrigieren: aus" });
+    }
+
+    /// "Schräglage dieser Seite korrigieren": shows the current page as scanned, or
+    /// straight again
+    fn cmd_toggle_page_deskew(&mut self) {
+        let page = self.state.lock().current_page;
+        if !self.skew_off.remove(&page) {
+            self.skew_off.insert(page);
+        }
+        self.update_skew_display();
+        self.invalidate();
+    }
+
+    /// Extra rotation of a page in clockwise degrees that straightens its measured tilt
+    fn page_skew(&self, page: usize) -> f32 {
+        match self.skews.get(&page) {
+            Some(Some(angle)) if self.deskew && !self.skew_off.contains(&page) => -angle,
+            _ => 0.0,
+        }
+    }
+
+    /// How a page is shown: cropped with "Ränder automatisch zuschneiden", rotated and
+    /// straightened. Export, clipboard and print take it through the same transform.
+    fn page_transform(&self, state: &AppState, page: usize) -> PageTransform {
+        match state.document {
+            Some(ref doc) => PageTransform::displayed(doc, page, state.page_rotation(page), state.auto_crop, self.page_skew(page)),
+            None => PageTransform::rotated(state.page_rotation(page)),
+        }
+    }
+
+    /// The selected part of a page in the orientation it is shown in. The selection is
+    /// marked on the page as shown, tilt included, so its part is not straightened.
+    fn selection_transform(shown: &PageTransform, sel: &Selection) -> PageTransform {
+        PageTransform { crop: Some([sel.left, sel.top, sel.right, sel.bottom]), rotation: shown.rotation, skew: 0.0 }
+    }
+
+    fn update_skew_display(&mut self) {
+        let page = self.state.lock().current_page;
+        let skew = self.skews.get(&page).copied().flatten().filter(|_| self.deskew);
+        self.statusbar.set_skew(skew.map(|angle| (angle, !self.skew_off.contains(&page))));
+    }
+
+    /// Starts measuring the tilt of the first visible page not measured yet
+    fn measure_skew(&mut self, state: &AppState) {
+        let Some(ref doc) = state.document else { return };
+        let Some(page) = Self::visible_pages(state, self.renderer.size().1 as i32)
+            .find(|page| !self.skews.contains_key(page) && doc.page_state(*page) == PageState::Ready)
+        else {
+            return;
+        };
+        let pixels = self.wic_loader.get_page_pixels(doc, page, state.page_rotation(page), crate::deskew::MAX_SIZE);
+        let hwnd = self.window.hwnd();
+        if pixels.is_ok_and(|pixels| crate::deskew::start(hwnd, state.file_path.clone(), page, pixels)) {
+            self.skew_pending = Some(page);
+        } else {
+            // Shown as it is
+            self.skews.insert(page, None);
+        }
+    }
 
-            if StartDocW(hdc, &doc_info) <= 0 {
-                self.show_error("Druckauftrag konnte nicht gestartet werden");
-                // Free memory allocated by PrintDlgW
-                if !pd.hDevMode.is_invalid() {
-                    let _ = GlobalFree(pd.hDevMode);
-                }
-                if !pd.hDevNames.is_invalid() {
-                    let _ = GlobalFree(pd.hDevNames);
-                }
+    fn on_skew(&mut self, skew: crate::deskew::Skew) {
+        if self.skew_pending != Some(skew.page) || self.state.lock().file_path != skew.path {
+            return;
+        }
+        self.skew_pending = None;
+        self.skews.insert(skew.page, skew.angle);
+        self.update_skew_display();
+        // Also measures the next visible page
+        self.invalidate();
+    }
+
+    /// Links and labels of the open PDF arrived: the status bar shows the label
+    fn on_pdf_structure(&mut self, result: crate::pdf::StructureResult) {
+        if result.request != self.structure_request {
+            return;
+        }
+        let (page, total, path) = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            if state.file_path.as_deref() != Some(result.path.as_str()) || doc.doc_type() != crate::document::DocumentType::Pdf {
                 return;
             }
+            doc.set_structure(result.structure);
+            (state.current_page, state.total_pages, state.file_path.clone())
+        };
+        self.update_page_display(page, total, path.as_deref());
+    }
 
-            // Get printable area dimensions (in pixels at printer resolution)
-            let page_width = GetDeviceCaps(hdc, HORZRES);
-            let page_height = GetDeviceCaps(hdc, VERTRES);
+    /// A new document: the tilts measured belong to the previous one
+    fn forget_skew(&mut self) {
+        self.skews.clear();
+        self.skew_pending = None;
+        self.skew_off.clear();
+        self.update_skew_display();
+    }
 
-            let mut success = true;
+    /// "Doppelseiten teilen": scans of two facing pages (wider than tall) are shown, paged
+    /// and exported as two pages. Only image documents are split.
+    fn cmd_toggle_split_spreads(&mut self) {
+        self.split_spreads = !self.split_spreads;
+        crate::settings::set_split_spreads(self.split_spreads);
+        self.apply_split();
+        self.show_osd(if self.split_spreads { "Doppelseiten teilen: ein" } else { "Doppelseiten teilen: aus" });
+    }
 
-            // Loop through pages
-            for page_idx in start_page..=end_page {
-                // Get bitmap data for printing
-                let bitmap_data = match self.wic_loader.get_bitmap_for_clipboard(&doc, page_idx, rotation) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        self.show_error(&format!("Seite {} konnte nicht zum Drucken vorbereitet werden: {:?}", page_idx + 1, e));
-                        success = false;
-                        break;
-                    }
-                };
+    /// "Seitenabstand": gap and margin of the continuous view. The current page stays at
+    /// the top of the view.
+    fn set_page_spacing(&mut self, spacing: PageSpacing) {
+        self.page_spacing = spacing;
+        crate::settings::set_page_spacing(spacing);
+        let page = self.state.lock().current_page;
+        self.update_content_size();
+        self.scroll_to_page(page);
+        self.invalidate();
+    }
 
-                // Start page
-                if StartPage(hdc) <= 0 {
-                    success = false;
-                    break;
+    /// "Teilung anpassen...": moves the split of the double pages of this document, e.g.
+    /// for scans not centered on the gutter
+    fn cmd_adjust_split(&mut self) {
+        let current = (self.split_position * 100.0).round() as u32;
+        if let Some(percent) = crate::dialogs::split_dialog(self.window.hwnd(), current) {
+            self.split_position = percent as f32 / 100.0;
+            self.apply_split();
+        }
+    }
+
+    /// Splits the open document again after the setting or the position changed. Page
+    /// numbers change, so everything kept per page is moved along or dropped.
+    fn apply_split(&mut self) {
+        let split = self.split_spreads.then_some(self.split_position);
+        let (page, continuous) = {
+            let mut state = self.state.lock();
+            let Some(old) = state.document.take() else { return };
+            let doc = old.with_split(split);
+            // Each page of an image page takes its rotation
+            let page_rotations = std::mem::take(&mut state.page_rotations);
+            state.page_rotations = (0..doc.page_count())
+                .filter_map(|page| Some((page, *page_rotations.get(&old.page_of_source(doc.source_page(page)))?)))
+                .collect();
+            if let Some((ref path, ref mut mark)) = self.blink_mark {
+                if *path == state.file_path {
+                    *mark = doc.page_of_source(old.source_page(*mark));
                 }
+            }
+            state.current_page = doc.page_of_source(old.source_page(state.current_page));
+            state.total_pages = doc.page_count();
+            state.selection = None;
+            state.document = Some(doc);
+            (state.current_page, state.multi_page_view && state.total_pages > 1)
+        };
+        self.ocr_words.clear();
+        self.forget_skew();
+        self.forget_difference();
+        self.view_history.clear();
+        self.refit();
+        self.update_page_display_and_repaint();
+        self.update_navigation_buttons();
+        if continuous {
+            self.scroll_to_page(page);
+        }
+    }
 
-                // Calculate scaling to fit image on page while preserving aspect ratio
-                let img_width = bitmap_data.width as i32;
-                let img_height = bitmap_data.height as i32;
-
-                let scale_x = page_width as f64 / img_width as f64;
-                let scale_y = page_height as f64 / img_height as f64;
-                let scale = scale_x.min(scale_y);
-
-                let dest_width = (img_width as f64 * scale) as i32;
-                let dest_height = (img_height as f64 * scale) as i32;
-
-                // Center on page
-                let dest_x = (page_width - dest_width) / 2;
-                let dest_y = (page_height - dest_height) / 2;
-
-                // Prepare bitmap info header for StretchDIBits
-                let bmi = BITMAPINFO {
-                    bmiHeader: BITMAPINFOHEADER {
-                        biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
-                        biWidth: img_width,
-                        biHeight: img_height, // Positive for bottom-up DIB
-                        biPlanes: 1,
-                        biBitCount: 32,
-                        biCompression: BI_RGB.0 as u32,
-                        biSizeImage: bitmap_data.data.len() as u32,
-                        biXPelsPerMeter: 0,
-                        biYPelsPerMeter: 0,
-                        biClrUsed: 0,
-                        biClrImportant: 0,
-                    },
-                    bmiColors: [RGBQUAD::default()],
-                };
+    /// Layout again after page sizes changed, keeping the fit mode
+    fn refit(&mut self) {
+        self.reapply_zoom_mode();
+        self.update_content_size();
+        self.invalidate();
+    }
 
-                // Set stretch mode for better quality
-                SetStretchBltMode(hdc, HALFTONE);
-                SetBrushOrgEx(hdc, 0, 0, None);
-
-                // Draw the bitmap
-                let result = StretchDIBits(
-                    hdc,
-                    dest_x,
-                    dest_y,
-                    dest_width,
-                    dest_height,
-                    0,
-                    0,
-                    img_width,
-                    img_height,
-                    Some(bitmap_data.data.as_ptr() as *const _),
-                    &bmi,
-                    DIB_RGB_COLORS,
-                    SRCCOPY,
-                );
+    fn toolbar_shown(&self) -> bool {
+        !self.window.is_fullscreen() && (!self.window.is_compact() || self.compact_toolbar)
+    }
 
-                if result == 0 {
-                    success = false;
-                    EndPage(hdc);
-                    break;
-                }
+    fn statusbar_shown(&self) -> bool {
+        !self.window.is_fullscreen() && !self.window.is_compact()
+    }
 
-                if EndPage(hdc) <= 0 {
-                    success = false;
-                    break;
-                }
-            }
+    /// Shows or hides toolbar, status bar and menu bar for fullscreen and Kompaktmodus
+    fn show_bars(&mut self) {
+        unsafe {
+            ShowWindow(self.top_toolbar.hwnd(), if self.toolbar_shown() { SW_SHOW } else { SW_HIDE });
+            ShowWindow(self.statusbar.hwnd(), if self.statusbar_shown() { SW_SHOW } else { SW_HIDE });
+        }
+        self.menu_bar.set_visible(self.window.hwnd(), self.statusbar_shown() && crate::settings::menu_bar());
+        self.relayout();
+    }
 
-            // End document
-            if success {
-                EndDoc(hdc);
+    fn set_compact_toolbar(&mut self, shown: bool) {
+        self.compact_toolbar = shown;
+        unsafe {
+            if shown {
+                SetTimer(self.window.hwnd(), COMPACT_TOOLBAR_TIMER, COMPACT_TOOLBAR_CHECK_MS, None);
             } else {
-                // AbortDoc(hdc) would be better here if available, but EndDoc is safe enough
-                EndDoc(hdc);
-                self.show_error("Drucken fehlgeschlagen oder abgebrochen");
-            }
-
-            // Free memory allocated by PrintDlgW
-            if !pd.hDevMode.is_invalid() {
-                let _ = GlobalFree(pd.hDevMode);
-            }
-            if !pd.hDevNames.is_invalid() {
-                let _ = GlobalFree(pd.hDevNames);
+                let _ = KillTimer(self.window.hwnd(), COMPACT_TOOLBAR_TIMER);
             }
         }
     }
 
-    fn cmd_open(&mut self) {
-        // Ignore if open is disabled (file was passed via command line)
-        if self.open_disabled {
+    /// Slides the toolbar in when the mouse reaches the top edge in Kompaktmodus
+    fn reveal_compact_toolbar(&mut self, y: i32) {
+        if !self.window.is_compact() || self.window.is_fullscreen() || self.compact_toolbar {
             return;
         }
-        if let Some(path) = self.dialogs.open_file(self.window.hwnd()) {
-            self.open_document(&path);
+        let dpi = unsafe { GetDpiForWindow(self.window.hwnd()) }.max(96);
+        if y < COMPACT_REVEAL_ZONE * dpi as i32 / 96 {
+            self.set_compact_toolbar(true);
+            self.show_bars();
         }
     }
 
-    fn cmd_export(&mut self) {
-        let state = self.state.lock();
-        if state.document.is_some() {
-            let file_path = state.file_path.clone();
-            drop(state);
+    /// COMPACT_TOOLBAR_TIMER: hides the slid-in toolbar once the mouse is below it, unless
+    /// one of its menus is open or it has the keyboard focus
+    fn check_compact_toolbar(&mut self) {
+        let hwnd = self.window.hwnd();
+        let mut cursor = POINT::default();
+        let mut gui = GUITHREADINFO { cbSize: std::mem::size_of::<GUITHREADINFO>() as u32, ..Default::default() };
+        let (in_menu, focused) = unsafe {
+            let _ = GetCursorPos(&mut cursor);
+            let _ = ScreenToClient(hwnd, &mut cursor);
+            let in_menu = GetGUIThreadInfo(0, &mut gui).as_bool() && (gui.flags & GUI_INMENUMODE).0 != 0;
+            (in_menu, GetFocus() == self.top_toolbar.toolbar_hwnd())
+        };
+        let mut client = RECT::default();
+        unsafe {
+            let _ = GetClientRect(hwnd, &mut client);
+        }
+        let over = cursor.x >= client.left && cursor.x < client.right && cursor.y < self.top_toolbar.height();
+        if !self.compact_toolbar || over || in_menu || focused {
+            return;
+        }
+        self.set_compact_toolbar(false);
+        self.show_bars();
+    }
 
-            let (current_filename, extension) = if let Some(ref p) = file_path {
-                let path = std::path::Path::new(p);
-                let filename = path.file_name().and_then(|n| n.to_str()).map(|s| s.to_string());
-                let ext = path.extension().and_then(|e| e.to_str()).map(|s| s.to_string());
-                (filename, ext)
-            } else { (None, None) };
+    /// "Immer im Vordergrund", e.g. above an editor while transcribing
+    fn cmd_toggle_topmost(&mut self) {
+        let topmost = !self.window.is_topmost();
+        crate::settings::set_topmost(topmost);
+        self.window.set_topmost(topmost);
+        self.show_osd(if topmost { "Immer im Vordergrund: ein" } else { "Immer im Vordergrund: aus" });
+    }
 
-            if let Some(path) = self.dialogs.save_file(
-                self.window.hwnd(),
-                current_filename.as_deref(),
-                extension.as_deref(),
-            ) {
-                self.export_document(&path);
-            }
-        }
+    /// "16-Bit-Graustufen erhalten"; the open document keeps how it was loaded
+    fn cmd_toggle_high_bit_depth(&mut self) {
+        let enabled = !crate::settings::high_bit_depth();
+        crate::settings::set_high_bit_depth(enabled);
+        self.wic_loader.set_keep_high_bit_depth(enabled);
+        self.show_osd("Gilt ab dem nächsten Öffnen eines Bildes");
     }
 
-    fn cmd_rotate_left(&mut self) {
-        {
-            let mut state = self.state.lock();
-            state.rotation = (state.rotation + 270) % 360;
-            state.scroll_x = 0;
-            state.scroll_y = 0;
+    /// "Fensterung zurücksetzen": back to the value range of each page
+    fn cmd_reset_window_level(&mut self) {
+        if let Some(ref doc) = self.state.lock().document {
+            doc.set_window_level(None);
         }
-        if self.state.lock().fit_to_page {
-            self.calculate_fit_zoom();
-        }
-        self.update_content_size();
         self.invalidate();
     }
 
-    fn cmd_rotate_right(&mut self) {
-        {
-            let mut state = self.state.lock();
-            state.rotation = (state.rotation + 90) % 360;
-            state.scroll_x = 0;
-            state.scroll_y = 0;
+    /// Pages of the Blinkvergleich with their displayed rotations
+    fn blink_pair(state: &AppState, partner: usize) -> crate::blink::Pair {
+        crate::blink::Pair {
+            path: state.file_path.clone(),
+            page: state.current_page,
+            partner,
+            rotations: (state.page_rotation(state.current_page), state.page_rotation(partner)),
         }
-        if self.state.lock().fit_to_page {
-            self.calculate_fit_zoom();
+    }
+
+    /// B pressed: shows the comparison page until B is released (see blink.rs)
+    fn start_blink(&mut self) {
+        let (doc, pair) = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            let marked = self.blink_mark.as_ref().filter(|(path, _)| *path == state.file_path).map(|&(_, page)| page);
+            let partner = crate::blink::partner_page(state.current_page, marked, state.total_pages);
+            (doc.clone(), partner.map(|partner| Self::blink_pair(&state, partner)))
+        };
+        let Some(pair) = pair else {
+            self.show_osd("Für den Blinkvergleich sind zwei Seiten nötig");
+            return;
+        };
+
+        doc.render_page(pair.partner);
+        self.blink = Some(pair.partner);
+        if self.blink_difference
+            && self.difference.as_ref().map(|(computed, _)| computed) != Some(&pair)
+            && self.difference_pending.as_ref() != Some(&pair)
+        {
+            self.start_difference(&doc, pair);
         }
-        self.update_content_size();
         self.invalidate();
     }
 
-    /// Ensure folder cache is populated (lazy enumeration)
-    fn ensure_folder_cache(&mut self) {
-        let state = self.state.lock();
-        let cache_valid = state.folder_cache_valid;
-        let file_path = state.file_path.clone();
-        drop(state);
+    fn start_difference(&mut self, doc: &Document, pair: crate::blink::Pair) {
+        let thumbnails = {
+            let _wait_cursor = WaitCursorGuard::new();
+            let max_size = crate::blink::MAX_SIZE;
+            self.wic_loader.get_thumbnail(doc, pair.page, pair.rotations.0, max_size).and_then(|page| {
+                Ok((page, self.wic_loader.get_thumbnail(doc, pair.partner, pair.rotations.1, max_size)?))
+            })
+        };
+        let hwnd = self.window.hwnd();
+        if thumbnails.is_ok_and(|(page, partner)| crate::blink::start(hwnd, pair.clone(), page, partner)) {
+            self.difference_pending = Some(pair);
+            self.show_osd("Differenz wird berechnet...");
+        } else {
+            self.show_osd("Die Differenz konnte nicht berechnet werden");
+        }
+    }
 
-        if cache_valid {
-            return; // Cache already valid
+    fn end_blink(&mut self) {
+        if self.blink.take().is_some() {
+            self.invalidate();
         }
+    }
 
-        // Perform lazy folder scan
-        if let Some(path) = file_path {
-            let (files, idx) = Self::scan_folder_files(&path);
-            let mut state = self.state.lock();
-            state.folder_files = files;
-            state.folder_file_index = idx;
-            state.folder_cache_valid = true;
-            drop(state);
-            // Update button states now that we know the actual folder size
-            self.update_navigation_buttons();
+    fn on_difference(&mut self, difference: crate::blink::Difference) {
+        if self.difference_pending.as_ref() != Some(&difference.pair) {
+            return;
+        }
+        self.difference_pending = None;
+        match self.wic_loader.create_bitmap_from_data(difference.width, difference.height, &difference.pixels) {
+            Ok(bitmap) => {
+                let doc = Document::new_image(bitmap, difference.width, difference.height, None, None);
+                self.difference = Some((difference.pair, doc));
+                self.hide_osd();
+            }
+            Err(_) => self.show_osd("Die Differenz konnte nicht berechnet werden"),
         }
     }
 
-    /// Update Back/Next button states based on current navigation context and position.
-    /// Single source of truth for navigation button enable/disable logic.
-    fn update_navigation_buttons(&mut self) {
-        let state = self.state.lock();
-        let nav_context = state.navigation_context;
+    /// A new document: an old difference image may belong to the previous content of the file
+    fn forget_difference(&mut self) {
+        self.blink = None;
+        self.difference = None;
+        self.difference_pending = None;
+    }
 
-        let (back_enabled, next_enabled) = match nav_context {
-            NavigationContext::Disabled => (false, false),
-            NavigationContext::FolderBrowsing => {
-                if !state.folder_cache_valid {
-                    // Lazy enumeration not done yet - enable both to allow triggering it
-                    (true, true)
-                } else {
-                    let count = state.folder_files.len();
-                    let idx = state.folder_file_index;
-                    // At first file: Back disabled. At last file: Next disabled.
-                    (idx > 0, count > 1 && idx < count - 1)
-                }
-            }
-            NavigationContext::DocumentPaging => {
-                let page = state.current_page;
-                let total = state.total_pages;
-                // At first page: Back disabled. At last page: Next disabled.
-                (page > 0, total > 1 && page < total - 1)
-            }
+    /// Umschalt+B: the current page becomes the comparison page of this file
+    fn cmd_mark_blink_page(&mut self) {
+        let mark = {
+            let state = self.state.lock();
+            state.document.as_ref().map(|_| (state.file_path.clone(), state.current_page))
         };
-        drop(state);
+        if let Some((path, page)) = mark {
+            self.blink_mark = Some((path, page));
+            self.show_osd(&format!("Seite {} ist die Vergleichsseite", page + 1));
+        }
+    }
 
-        self.top_toolbar.set_navigation_buttons(back_enabled, next_enabled);
+    /// Blinkvergleich: the difference, or the comparison page at the zoom of the current
+    /// page and centered on it; it covers the current page completely
+    fn draw_blink(&self, state: &AppState, doc: &Document, partner: usize, background: D2D1_COLOR_F) {
+        let Some(cover) = self.page_view_rect(state, state.current_page).filter(|_| partner < state.total_pages) else {
+            return;
+        };
+        let pair = Self::blink_pair(state, partner);
+        if let Some((_, difference)) = self.difference.as_ref().filter(|(computed, _)| self.blink_difference && *computed == pair) {
+            // The difference shows the whole page as displayed; only the shown part is drawn
+            let (width, height) = doc.page_dimensions(state.current_page);
+            let [left, top, right, bottom] = doc.display_rect(state.current_page, state.auto_crop);
+            let rotate = |u: f32, v: f32| match state.page_rotation(state.current_page) {
+                90 => (1.0 - v, u),
+                180 => (1.0 - u, 1.0 - v),
+                270 => (v, 1.0 - u),
+                _ => (u, v),
+            };
+            let (u0, v0) = rotate(left / width, top / height);
+            let (u1, v1) = rotate(right / width, bottom / height);
+            let (difference_w, difference_h) = difference.page_dimensions(0);
+            let source = [u0.min(u1) * difference_w, v0.min(v1) * difference_h, u0.max(u1) * difference_w, v0.max(v1) * difference_h];
+            let _ = self.renderer.draw_page_over(difference, 0, 0, source, &cover, &cover, background);
+            return;
+        }
+
+        let rotation = pair.rotations.1;
+        let source = doc.display_rect(partner, state.auto_crop);
+        let (w, h) = (source[2] - source[0], source[3] - source[1]);
+        let (w, h) = if rotation == 90 || rotation == 270 { (h, w) } else { (w, h) };
+        let (w, h) = (w * state.zoom, h * state.zoom);
+        let center_x = (cover.left + cover.right) / 2.0;
+        let center_y = (cover.top + cover.bottom) / 2.0;
+        let dest = D2D_RECT_F { left: center_x - w / 2.0, top: center_y - h / 2.0, right: center_x + w / 2.0, bottom: center_y + h / 2.0 };
+        let _ = self.renderer.draw_page_over(doc, partner, rotation, source, &cover, &dest, background);
     }
 
-    fn cmd_prev_page(&mut self) {
-        let state = self.state.lock();
-        let nav_context = state.navigation_context;
-        let current_page = state.current_page;
-        let is_multipage = state.multi_page_view && state.total_pages > 1;
-        drop(state);
+    /// "Menüleiste anzeigen"; in fullscreen the menu bar comes back when leaving it
+    fn cmd_toggle_menu_bar(&mut self) {
+        let enabled = !crate::settings::menu_bar();
+        crate::settings::set_menu_bar(enabled);
+        self.context_menu.set_checked(crate::menu::IDM_MENU_BAR, enabled);
+        self.show_bars();
+    }
 
-        match nav_context {
-            NavigationContext::Disabled => {
-                // Navigation disabled, do nothing
-            }
-            NavigationContext::FolderBrowsing => {
-                // Ensure folder cache is populated (lazy enumeration)
-                self.ensure_folder_cache();
+    /// Lays out the bars and the view again after the client area changed
+    fn relayout(&mut self) {
+        let mut rect = RECT::default();
+        unsafe {
+            let _ = GetClientRect(self.window.hwnd(), &mut rect);
+        }
+        self.on_resize(rect.right, rect.bottom);
+    }
 
-                let state = self.state.lock();
-                let folder_files = state.folder_files.clone();
-                let folder_index = state.folder_file_index;
-                drop(state);
+    /// Enabled and checked states of the menu bar, before one of its menus opens
+    fn refresh_menu_bar(&self) {
+        let document_loaded = self.state.lock().document.is_some();
+        let recent = self.recent_files();
+        self.menu_bar.refresh(&self.menu_state(), document_loaded, !self.open_disabled, &recent);
+    }
 
-                if folder_files.len() > 1 && folder_index > 0 {
-                    let prev_file = folder_files[folder_index - 1].clone();
-                    self.open_document_with_mode(&prev_file, true);
-                    // Note: open_document_with_mode calls update_navigation_buttons
-                }
-            }
-            NavigationContext::DocumentPaging => {
-                if current_page > 0 {
-                    if is_multipage {
-                        // In multi-page mode, scroll to previous page
-                        self.scroll_to_page(current_page - 1);
-                    } else {
-                        // Single page mode - switch page
-                        {
-                            let mut state = self.state.lock();
-                            state.current_page -= 1;
-                            state.scroll_x = 0;
-                            state.scroll_y = 0;
-                        }
-                        self.update_page_display_and_repaint();
-                        self.update_navigation_buttons();
-                    }
-                }
+    /// "Zuletzt geöffnet" (nothing in restricted mode, which does not record files)
+    fn recent_files(&self) -> Vec<String> {
+        if self.dialogs.restricted_path.is_some() {
+            return Vec::new();
+        }
+        crate::settings::recent_files()
+    }
+
+    fn cmd_open_recent(&mut self, index: usize) {
+        if self.open_disabled {
+            return;
+        }
+        let Some(path) = self.recent_files().into_iter().nth(index) else {
+            return;
+        };
+        self.pause_inbox();
+        {
+            let mut state = self.state.lock();
+            if !state.folder_files.iter().any(|f| f.eq_ignore_ascii_case(&path)) {
+                state.folder_cache_valid = false;
             }
         }
+        self.open_document(&path);
     }
 
-    fn cmd_next_page(&mut self) {
+    /// "Tastenkürzel" overview over the view (F1, Hilfe menu)
+    fn cmd_shortcuts(&mut self) {
+        self.shortcut_overlay = true;
+        self.invalidate();
+    }
+
+    /// "Eigenschaften": file and page details of the current document
+    fn cmd_properties(&self) {
         let state = self.state.lock();
-        let nav_context = state.navigation_context;
-        let current_page = state.current_page;
-        let total_pages = state.total_pages;
-        let is_multipage = state.multi_page_view && state.total_pages > 1;
+        let (Some(doc), Some(path)) = (state.document.as_ref(), state.file_path.as_deref()) else {
+            return;
+        };
+        let file = std::path::Path::new(path);
+        let name = file.file_name().and_then(|n| n.to_str()).unwrap_or(path);
+        let folder = file.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+        // Pixel format and resolution of the current page where the image has them
+        let format = doc
+            .format_text(state.current_page)
+            .unwrap_or_else(|| file.extension().and_then(|e| e.to_str()).unwrap_or("").to_uppercase());
+        let mut message = format!(
+            "{}\n\nOrdner: {}\nFormat: {}\nDateigröße: {}\nSeiten: {}\nSeitengröße (Seite {}): {}",
+            name,
+            folder,
+            format,
+            crate::utils::format_file_size(Self::get_file_size(path)),
+            doc.page_count(),
+            state.current_page + 1,
+            doc.page_size_text(state.current_page),
+        );
+        for (name, value) in doc.tags(state.current_page) {
+            message.push_str(&format!("\n{}: {}", name, value));
+        }
         drop(state);
+        crate::dialogs::show_info(self.window.hwnd(), "Eigenschaften", &message);
+    }
 
-        match nav_context {
-            NavigationContext::Disabled => {
-                // Navigation disabled, do nothing
-            }
-            NavigationContext::FolderBrowsing => {
-                // Ensure folder cache is populated (lazy enumeration)
-                self.ensure_folder_cache();
+    /// "Ordnerstatistik": walks the Back/Next list on a worker thread (see folder_stats.rs)
+    fn cmd_folder_stats(&mut self) {
+        if self.folder_stats.is_some() {
+            self.show_osd("Die Ordnerstatistik wird bereits erstellt (Esc bricht ab)");
+            return;
+        }
+        let Some((folder, files)) = self.batch_files() else {
+            return;
+        };
 
-                let state = self.state.lock();
-                let folder_files = state.folder_files.clone();
-                let folder_index = state.folder_file_index;
-                drop(state);
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        if crate::folder_stats::start(self.window.hwnd(), folder, files, cancel.clone()) {
+            self.folder_stats = Some(cancel);
+            self.show_osd("Ordnerstatistik wird erstellt (Esc bricht ab)");
+        } else {
+            crate::dialogs::show_error(self.window.hwnd(), "Die Ordnerstatistik konnte nicht gestartet werden.");
+        }
+    }
 
-                if folder_files.len() > 1 && folder_index < folder_files.len() - 1 {
-                    let next_file = folder_files[folder_index + 1].clone();
-                    self.open_document_with_mode(&next_file, true);
-                    // Note: open_document_with_mode calls update_navigation_buttons
-                }
-            }
-            NavigationContext::DocumentPaging => {
-                if current_page < total_pages - 1 {
-                    if is_multipage {
-                        // In multi-page mode, scroll to next page
-                        self.scroll_to_page(current_page + 1);
-                    } else {
-                        // Single page mode - switch page
-                        {
-                            let mut state = self.state.lock();
-                            state.current_page += 1;
-                            state.scroll_x = 0;
-                            state.scroll_y = 0;
-                        }
-                        self.update_page_display_and_repaint();
-                        self.update_navigation_buttons();
-                    }
+    fn on_folder_stats(&mut self, stats: FolderStats) {
+        self.folder_stats = None;
+        self.statusbar.clear_progress();
+        self.hide_osd();
+        crate::dialogs::show_copyable_info(self.window.hwnd(), "Ordnerstatistik", &stats.report());
+    }
+
+    /// "Text erkennen (OCR)": recognizes the current page on a worker thread (see ocr.rs)
+    fn cmd_ocr(&mut self) {
+        if self.ocr_running {
+            self.show_osd("Der Text wird bereits erkannt...");
+            return;
+        }
+        let page = {
+            let state = self.state.lock();
+            let Some(ref doc) = state.document else { return };
+            let page = state.current_page;
+            let rotation = state.page_rotation(page);
+            let pixels = self.wic_loader.get_page_pixels(doc, page, rotation, crate::ocr::max_dimension());
+            match pixels {
+                Ok(pixels) => OcrPage { page, pixels, rotation, source_size: doc.page_dimensions(page) },
+                Err(e) => {
+                    drop(state);
+                    self.show_error_details("Die Seite konnte nicht für die Texterkennung gelesen werden.", Some(&e), None);
+                    return;
                 }
             }
+        };
+        if crate::ocr::start(self.window.hwnd(), page) {
+            self.ocr_running = true;
+            self.show_osd("Text wird erkannt...");
+        } else {
+            crate::dialogs::show_error(self.window.hwnd(), "Die Texterkennung konnte nicht gestartet werden.");
         }
     }
 
-    fn cmd_first_page(&mut self) {
-        let state = self.state.lock();
-        let is_multipage = state.multi_page_view && state.total_pages > 1;
-        let current_page = state.current_page;
-        drop(state);
-
-        if current_page == 0 { return; }
-
-        if is_multipage {
-            self.scroll_to_page(0);
-            // Note: scroll_to_page calls update_navigation_buttons
-        } else {
-            {
-                let mut state = self.state.lock();
-                state.current_page = 0;
-                state.scroll_x = 0;
-                state.scroll_y = 0;
+    fn on_ocr(&mut self, output: OcrOutput) {
+        self.ocr_running = false;
+        self.hide_osd();
+        let recognized = match output.result {
+            Ok(recognized) => recognized,
+            Err(message) => {
+                crate::dialogs::show_error(self.window.hwnd(), &message);
+                return;
             }
-            self.update_page_display_and_repaint();
-            self.update_navigation_buttons();
+        };
+        // Another document was opened meanwhile
+        let file_path = {
+            let state = self.state.lock();
+            if state.document.is_none() || output.page >= state.total_pages {
+                return;
+            }
+            state.file_path.clone()
+        };
+        if recognized.text.trim().is_empty() {
+            self.show_osd("Auf dieser Seite wurde kein Text erkannt");
+            return;
         }
-    }
 
-    fn cmd_last_page(&mut self) {
-        let state = self.state.lock();
-        let last = state.total_pages - 1;
-        let is_multipage = state.multi_page_view && state.total_pages > 1;
-        let current_page = state.current_page;
-        drop(state);
+        self.ocr_words = recognized
+            .words
+            .iter()
+            .map(|&[left, top, right, bottom]| Selection { page: output.page, left, top, right, bottom })
+            .collect();
+        self.invalidate();
 
-        if current_page == last { return; }
+        let mut info = format!("Seite {} – erkannt mit {} (Esc entfernt die Wortrahmen)", output.page + 1, recognized.language);
+        if let Some(note) = recognized.note {
+            info = format!("{}\n{}", note, info);
+        }
+        let stem = file_path.as_deref().and_then(|p| std::path::Path::new(p).file_stem()?.to_str()).unwrap_or("Dokument");
+        let default_filename = format!("{} - Seite {}.txt", stem, output.page + 1);
+        crate::dialogs::ocr_text_dialog(self.window.hwnd(), &self.dialogs, &info, &recognized.text, &default_filename);
+    }
 
-        if is_multipage {
-            self.scroll_to_page(last);
-            // Note: scroll_to_page calls update_navigation_buttons
-        } else {
-            {
-                let mut state = self.state.lock();
-                state.current_page = last;
-                state.scroll_x = 0;
-                state.scroll_y = 0;
+    /// "Ordner durchsuchen": shows the search window for the Back/Next list; the search
+    /// starts from the window (see folder_search.rs)
+    fn cmd_folder_search(&mut self) {
+        let Some((folder, _)) = self.batch_files() else {
+            return;
+        };
+        if self.search_panel.is_none() {
+            match SearchPanel::new(self.window.hwnd(), self.window.instance()) {
+                Ok(panel) => self.search_panel = Some(panel),
+                Err(e) => {
+                    self.show_error_details("Die Ordnersuche kann nicht angezeigt werden.", Some(&e), None);
+                    return;
+                }
             }
-            self.update_page_display_and_repaint();
-            self.update_navigation_buttons();
+        }
+        if let Some(ref panel) = self.search_panel {
+            panel.show(&folder);
         }
     }
 
-    /// Scroll to bring a specific page into view (for multi-page mode)
-    fn scroll_to_page(&mut self, page: usize) {
-        let mut state = self.state.lock();
-
-        if let Some(ref layout) = state.page_layout {
-            if page < layout.page_tops.len() {
-                // Scroll to the top of the requested page
-                let target_y = layout.page_tops[page];
+    /// "Suchindex löschen": deletes the text kept by the folder search (see text_index.rs);
+    /// a running search is stopped first so it does not write new entries
+    fn cmd_clear_text_index(&mut self) {
+        if let Some((_, ref cancel)) = self.search {
+            cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+        match crate::text_index::clear() {
+            Ok(()) => self.show_osd("Suchindex gelöscht"),
+            Err(e) => crate::dialogs::show_error(self.window.hwnd(), &format!("Der Suchindex konnte nicht gelöscht werden.\n\n{}", e)),
+        }
+    }
 
-                // Clamp to valid range
-                let (_, render_h) = self.renderer.size();
-                let viewport_height = render_h as i32;
-                let max_y = (state.content_height - viewport_height).max(0);
-                let new_y = target_y.clamp(0, max_y);
+    fn on_search_request(&mut self, request: SearchRequest, index: usize) {
+        match request {
+            SearchRequest::Search => self.start_folder_search(),
+            SearchRequest::Open => self.open_search_hit(index),
+            SearchRequest::Stop => {
+                if let Some((_, ref cancel)) = self.search {
+                    cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
+        }
+    }
 
-                state.scroll_y = new_y;
-                state.current_page = page;
-                let total = state.total_pages;
-                let path = state.file_path.clone();
-                drop(state);
+    /// Searches the current Back/Next list for the text of the search window; a search
+    /// still running is stopped
+    fn start_folder_search(&mut self) {
+        let Some(term) = self.search_panel.as_ref().map(SearchPanel::term) else {
+            return;
+        };
+        if term.is_empty() {
+            if let Some(ref panel) = self.search_panel {
+                panel.set_status("Bitte geben Sie einen Suchtext ein.");
+            }
+            return;
+        }
+        let Some((folder, files)) = self.batch_files() else {
+            return;
+        };
+        if let Some((_, cancel)) = self.search.take() {
+            cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+        self.search_hits.clear();
+        self.search_id = self.search_id.wrapping_add(1);
+        self.search_progress = (0, files.len());
+        let Some(ref panel) = self.search_panel else { return };
+        panel.clear();
+        panel.show(&folder);
+
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let use_index = self.dialogs.restricted_path.is_none() && !crate::safe_mode::is_active();
+        let job = SearchJob { id: self.search_id, term, files, use_index };
+        if crate::folder_search::start(self.window.hwnd(), job, cancel.clone()) {
+            self.search = Some((self.search_id, cancel));
+            panel.set_status(&format!("Suche läuft: 0 von {} Dateien (Esc bricht ab)", self.search_progress.1));
+        } else {
+            panel.set_status("Die Suche konnte nicht gestartet werden.");
+        }
+    }
 
-                self.scroll_manager.set_pos(SB_VERT, new_y);
-                self.update_page_display(page, total, path.as_deref());
-                self.update_navigation_buttons();
-                self.invalidate();
+    fn on_search_message(&mut self, message: SearchMessage) {
+        // Messages of a search replaced by a newer one
+        if self.search.as_ref().map(|(id, _)| *id) != Some(message.id) {
+            return;
+        }
+        match message.event {
+            SearchEvent::File(hits) => {
+                let Some(ref panel) = self.search_panel else { return };
+                panel.add_hits(&hits);
+                self.search_hits.extend(hits);
+                self.search_progress.0 += 1;
+                let (searched, total) = self.search_progress;
+                panel.set_status(&format!("Suche läuft: {} von {} Dateien, {} Treffer (Esc bricht ab)", searched, total, self.search_hits.len()));
+            }
+            SearchEvent::Done(summary) => {
+                self.search = None;
+                self.statusbar.clear_progress();
+                self.hide_osd();
+                let mut files: Vec<&str> = self.search_hits.iter().map(|hit| hit.path.as_str()).collect();
+                files.dedup();
+                let mut status = match self.search_hits.len() {
+                    0 => "Keine Treffer".to_string(),
+                    hits => format!("{} Treffer in {} Dateien", hits, files.len()),
+                };
+                if summary.cancelled {
+                    status.push_str(&format!(" (abgebrochen nach {} von {} Dateien)", self.search_progress.0, self.search_progress.1));
+                }
+                if summary.skipped > 0 {
+                    status.push_str(&format!("; {} Dateien nicht durchsuchbar", summary.skipped));
+                }
+                if !summary.failed.is_empty() {
+                    status.push_str(&format!("; {} Dateien nicht lesbar (siehe Protokoll)", summary.failed.len()));
+                }
+                if let Some(ref panel) = self.search_panel {
+                    panel.set_status(&status);
+                }
+                for (path, error) in &summary.failed {
+                    self.operation_log.record(&format!("Ordner durchsuchen: {}: {}", path, error.replace('\n', " ")));
+                }
+                if let Some(message) = summary.ocr_unavailable {
+                    crate::dialogs::show_info(self.window.hwnd(), "Ordner durchsuchen", &format!("Bilder wurden nicht durchsucht.\n\n{}", message));
+                }
+            }
+        }
+    }
 
+    /// A match of "Ordner durchsuchen" was chosen: opens its file at the page
+    fn open_search_hit(&mut self, index: usize) {
+        let Some(hit) = self.search_hits.get(index).cloned() else { return };
+        let is_open = |app: &Self| app.state.lock().file_path.as_deref().is_some_and(|path| path.eq_ignore_ascii_case(&hit.path));
+        if !is_open(self) {
+            self.open_document(&hit.path);
+            if !is_open(self) {
                 return;
             }
         }
-
-        // Fallback: just update current page
-        state.current_page = page;
-        drop(state);
-        self.update_page_display_and_repaint();
-        self.update_navigation_buttons();
+        self.go_to_page(hit.page);
     }
 
-    fn update_page_display_and_repaint(&mut self) {
+    /// Folder and files of the Back/Next list for folder-wide commands, just the open file
+    /// outside a folder. `None` without a file or while the folder is still scanned.
+    fn batch_files(&mut self) -> Option<(String, Vec<String>)> {
+        if !self.ensure_folder_cache() {
+            self.show_osd("Der Ordner wird noch durchsucht...");
+            return None;
+        }
         let state = self.state.lock();
-        let page = state.current_page;
-        let total = state.total_pages;
-        let path = state.file_path.clone();
-        drop(state);
-        self.update_page_display(page, total, path.as_deref());
-        self.update_content_size();
-        self.invalidate();
+        let path = state.file_path.clone()?;
+        let folder = state.folder_root.clone().unwrap_or_else(|| {
+            std::path::Path::new(&path).parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default()
+        });
+        let files = if state.folder_files.is_empty() { vec![path] } else { state.folder_files.clone() };
+        Some((folder, files))
     }
 
-    // Zoom levels - finer increments for smoother Ctrl+Wheel zooming
-    const ZOOM_LEVELS: &'static [f32] = &[
-        0.10, 0.125, 0.15, 0.175, 0.20, 0.25, 0.33, 0.40, 0.50, 0.60, 0.67, 0.75, 0.85,
-        1.00, 1.10, 1.25, 1.40, 1.50, 1.75, 2.00, 2.50, 3.00, 4.00, 5.00, 6.00, 8.00, 10.00
-    ];
+    /// "Ordner-Stapelverarbeitung > Alle drehen": rotates the images of the Back/Next list
+    /// on a worker thread (see batch_rotate.rs)
+    fn cmd_batch_rotate(&mut self) {
+        if self.batch_rotate.is_some() {
+            self.show_osd("Die Bilder werden bereits gedreht (Esc bricht ab)");
+            return;
+        }
+        let Some((folder, files)) = self.batch_files() else {
+            return;
+        };
+        let Some(choice) = crate::dialogs::batch_rotate_dialog(self.window.hwnd()) else {
+            return;
+        };
+        let files: Vec<String> = files
+            .into_iter()
+            .filter(|path| {
+                let name = std::path::Path::new(path).file_name().unwrap_or_default().to_string_lossy();
+                crate::batch_rotate::matches_filter(&choice.filter, &name)
+            })
+            .collect();
+        if files.is_empty() {
+            self.show_osd("Keine Dateien entsprechen dem Filter");
+            return;
+        }
+        let target = if choice.to_folder {
+            match self.dialogs.pick_folder(self.window.hwnd(), "Zielordner für die gedrehten Bilder") {
+                Some(target) => Some(target),
+                None => return,
+            }
+        } else {
+            let message = format!(
+                "{} Dateien werden gedreht und überschrieben.\nJPEG-Bilder werden dabei neu komprimiert, Metadaten gehen verloren.",
+                files.len()
+            );
+            if !crate::dialogs::confirm_warning(self.window.hwnd(), &message) {
+                return;
+            }
+            None
+        };
 
-    fn cmd_zoom_in(&mut self) {
-        let mut state = self.state.lock();
-        let current = state.zoom;
-        let min_zoom = current * 1.10;
-        let new_zoom = Self::ZOOM_LEVELS.iter().find(|&&z| z >= min_zoom).copied().unwrap_or(10.0);
-        state.zoom = new_zoom;
-        state.fit_to_page = false;
-        drop(state);
-        self.statusbar.set_zoom(new_zoom);
-        self.update_content_size();
-        self.invalidate();
+        // Overwriting the open file is not a change by another program; it is reloaded afterwards
+        let file_path = self.state.lock().file_path.clone();
+        if target.is_none() && file_path.is_some_and(|path| files.iter().any(|f| f.eq_ignore_ascii_case(&path))) {
+            self.file_watch = None;
+        }
+        self.operation_log.record(&format!("Alle drehen ({}°): {} Dateien in {}", choice.rotation, files.len(), folder));
+        let job = RotateJob { folder, files, rotation: choice.rotation, target };
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        if crate::batch_rotate::start(self.window.hwnd(), job, cancel.clone()) {
+            self.batch_rotate = Some(cancel);
+            self.show_osd("Bilder werden gedreht (Esc bricht ab)");
+        } else {
+            crate::dialogs::show_error(self.window.hwnd(), "Das Drehen konnte nicht gestartet werden.");
+        }
     }
 
-    fn cmd_zoom_out(&mut self) {
-        let mut state = self.state.lock();
-        let current = state.zoom;
-        let max_zoom = current / 1.10;
-        let new_zoom = Self::ZOOM_LEVELS.iter().rev().find(|&&z| z <= max_zoom).copied().unwrap_or(0.10);
-        state.zoom = new_zoom;
-        state.fit_to_page = false;
-        drop(state);
-        self.statusbar.set_zoom(new_zoom);
-        self.update_content_size();
-        self.invalidate();
+    fn on_batch_rotate(&mut self, report: RotateReport) {
+        self.batch_rotate = None;
+        self.statusbar.clear_progress();
+        self.hide_osd();
+        let file_path = self.state.lock().file_path.clone();
+        if let Some(path) = file_path.filter(|_| report.target.is_none()) {
+            if report.rotated.iter().any(|rotated| rotated.eq_ignore_ascii_case(&path)) {
+                self.reload_document();
+            } else if self.file_watch.is_none() {
+                self.file_watch = FileWatcher::start(self.window.hwnd(), &path).ok();
+            }
+        }
+        crate::dialogs::show_copyable_info(self.window.hwnd(), "Alle drehen", &report.report());
     }
 
-    fn cmd_zoom_reset(&mut self) {
-        {
-            let mut state = self.state.lock();
-            state.zoom = 1.0;
-            state.fit_to_page = false;
+    /// Check marks of the context menu from the current view
+    fn menu_state(&self) -> MenuState {
+        let state = self.state.lock();
+        let zoom_item = state.document.as_ref().map(|_| {
+            match state.zoom_mode {
+                ZoomMode::FitPage => crate::menu::IDM_FIT_TO_PAGE,
+                ZoomMode::FitWidth => crate::menu::IDM_FIT_WIDTH,
+                ZoomMode::FitHeight => crate::menu::IDM_FIT_HEIGHT,
+                ZoomMode::Fixed(zoom) => crate::menu::zoom_preset_item(zoom).unwrap_or(crate::menu::IDM_ZOOM_CUSTOM),
+            }
+        });
+        MenuState {
+            zoom_item,
+            continuous: (state.document.is_some() && state.total_pages > 1).then_some(state.multi_page_view),
+            fullscreen: self.window.is_fullscreen(),
+            topmost: self.window.is_topmost(),
+            compact: self.window.is_compact(),
+            auto_crop: state.auto_crop,
+            deskew: self.deskew,
+            split_spreads: self.split_spreads,
+            minimap: self.minimap,
+            page_spacing_item: match self.page_spacing {
+                PageSpacing::NARROW => Some(crate::menu::IDM_PAGE_SPACING_NARROW),
+                PageSpacing::NORMAL => Some(crate::menu::IDM_PAGE_SPACING_NORMAL),
+                PageSpacing::WIDE => Some(crate::menu::IDM_PAGE_SPACING_WIDE),
+                _ => None,
+            },
+            split_position: state.document.as_ref().is_some_and(|doc| doc.is_split()),
+            deskew_page: self
+                .skews
+                .get(&state.current_page)
+                .is_some_and(|angle| self.deskew && angle.is_some())
+                .then(|| !self.skew_off.contains(&state.current_page)),
+            keep_high_bit_depth: crate::settings::high_bit_depth(),
+            window_level: state.document.as_ref().is_some_and(|doc| doc.has_high_bit_depth()),
+            wheel_item: match crate::settings::wheel_mode() {
+                WheelMode::Scroll => crate::menu::IDM_WHEEL_SCROLL,
+                WheelMode::Zoom => crate::menu::IDM_WHEEL_ZOOM,
+                WheelMode::PageChange => crate::menu::IDM_WHEEL_PAGE,
+            },
+            invert_wheel: crate::settings::invert_wheel(),
+            render_quality_item: self.pdf_dpi.is_none().then(|| match crate::settings::render_quality() {
+                RenderQuality::Fast => crate::menu::IDM_RENDER_FAST,
+                RenderQuality::Standard => crate::menu::IDM_RENDER_STANDARD,
+                RenderQuality::High => crate::menu::IDM_RENDER_HIGH,
+                RenderQuality::Screen => crate::menu::IDM_RENDER_SCREEN,
+            }),
+            default_rotation: (crate::settings::default_rotation(false), crate::settings::default_rotation(true)),
+            blink_difference: self.blink_difference,
+            undo_view: self.view_history.can_undo(),
+            redo_view: self.view_history.can_redo(),
+            extract_pages: self.watermark.is_none()
+                && state.document.as_ref().is_some_and(|doc| doc.doc_type() == crate::document::DocumentType::Pdf),
+            annotations: state.document.as_ref().is_some_and(|doc| doc.doc_type() == crate::document::DocumentType::Pdf),
+            pin_selection: state.document.is_some() && state.selection.is_some(),
         }
-        self.statusbar.set_zoom(1.0);
-        self.update_content_size();
-        self.invalidate();
     }
 
     fn cmd_fit_to_page(&mut self) {
+        self.record_view();
         let is_multipage = {
             let state = self.state.lock();
             state.multi_page_view && state.total_pages > 1
@@ -1469,17 +4935,17 @@ impl App {
             // Single-page: original fit-to-page behavior
             {
                 let mut state = self.state.lock();
-                state.fit_to_page = true;
                 state.scroll_x = 0;
                 state.scroll_y = 0;
             }
-            self.calculate_fit_zoom();
+            self.set_zoom_mode(ZoomMode::FitPage);
             self.update_content_size();
             self.invalidate();
         }
     }
 
     fn cmd_fit_to_height(&mut self) {
+        self.record_view();
         let is_multipage = {
             let state = self.state.lock();
             state.multi_page_view && state.total_pages > 1
@@ -1491,34 +4957,14 @@ impl App {
             return;
         }
 
-        // Single-page: original fit-to-height behavior
-        let state = self.state.lock();
-        if let Some(ref doc) = state.document {
-            let (doc_width, doc_height) = doc.dimensions();
-            let (_, h) = if state.rotation == 90 || state.rotation == 270 {
-                (doc_height, doc_width)
-            } else {
-                (doc_width, doc_height)
-            };
-            drop(state);
-
-            let (_, render_h) = self.renderer.size();
-            if render_h > 0 && h > 0.0 {
-                let zoom = (render_h as f32 / h).clamp(0.1, 10.0);
-                {
-                    let mut state = self.state.lock();
-                    state.zoom = zoom;
-                    state.fit_to_page = false;
-                    state.scroll_x = 0;
-                }
-                self.statusbar.set_zoom(zoom);
-                self.update_content_size();
-                self.invalidate();
-            }
-        }
+        self.state.lock().scroll_x = 0;
+        self.set_zoom_mode(ZoomMode::FitHeight);
+        self.update_content_size();
+        self.invalidate();
     }
 
     fn cmd_fit_to_width(&mut self) {
+        self.record_view();
         let is_multipage = {
             let state = self.state.lock();
             state.multi_page_view && state.total_pages > 1
@@ -1530,81 +4976,62 @@ impl App {
             return;
         }
 
-        // Single-page: original fit-to-width behavior
-        let state = self.state.lock();
-        if let Some(ref doc) = state.document {
-            let (doc_width, doc_height) = doc.dimensions();
-            let (w, _) = if state.rotation == 90 || state.rotation == 270 {
-                (doc_height, doc_width)
-            } else {
-                (doc_width, doc_height)
-            };
-            drop(state);
+        self.state.lock().scroll_y = 0;
+        self.set_zoom_mode(ZoomMode::FitWidth);
+        self.update_content_size();
+        self.invalidate();
+    }
 
-            let (render_w, _) = self.renderer.size();
-            if render_w > 0 && w > 0.0 {
-                let zoom = (render_w as f32 / w).clamp(0.1, 10.0);
-                {
-                    let mut state = self.state.lock();
-                    state.zoom = zoom;
-                    state.fit_to_page = false;
-                    state.scroll_y = 0;
-                }
-                self.statusbar.set_zoom(zoom);
-                self.update_content_size();
-                self.invalidate();
+    /// Sets how the zoom is chosen and applies it. Every change of zoom or fit mode goes
+    /// through here, so the status bar and the fit buttons always show the active mode.
+    fn set_zoom_mode(&mut self, mode: ZoomMode) {
+        let zoom = match mode {
+            ZoomMode::Fixed(zoom) => Some(zoom),
+            _ => self.fitted_zoom(mode),
+        };
+        let zoom = {
+            let mut state = self.state.lock();
+            state.zoom_mode = mode;
+            if let Some(zoom) = zoom {
+                state.zoom = zoom;
             }
-        }
+            state.zoom
+        };
+        let past_source = past_source_resolution(zoom, self.renderer.dpi_scale());
+        self.statusbar.set_zoom(zoom, mode, past_source);
     }
 
-    fn calculate_fit_zoom(&mut self) {
-        let state = self.state.lock();
-        if let Some(ref doc) = state.document {
-            let use_multipage = state.multi_page_view && state.total_pages > 1;
+    /// Fits again after the window, the page or its rotation changed; a fixed zoom stays
+    fn reapply_zoom_mode(&mut self) {
+        let mode = self.state.lock().zoom_mode;
+        self.set_zoom_mode(mode);
+    }
 
-            if use_multipage {
+    /// Zoom of a fit mode for the window and the current page, `None` for `Fixed`, without
+    /// a document or while the window has no size
+    fn fitted_zoom(&self, mode: ZoomMode) -> Option<f32> {
+        let state = self.state.lock();
+        let doc = state.document.as_ref()?;
+        let (render_w, render_h) = self.renderer.size();
+        // Page size as shown, width and height swapped when turned by a quarter
+        let shown_size = |page: usize| {
+            let (w, h) = doc.display_dimensions(page, state.auto_crop);
+            if matches!(state.page_rotation(page), 90 | 270) { (h, w) } else { (w, h) }
+        };
+        let current = state.current_page;
+        match mode {
+            ZoomMode::Fixed(_) => None,
+            ZoomMode::FitPage | ZoomMode::FitWidth if state.multi_page_view && state.total_pages > 1 => {
                 // For multi-page view, fit the widest page to viewport width
                 // This allows vertical scrolling through the document
-                let (render_w, _render_h) = self.renderer.size();
-
-                // Find the widest page (accounting for rotation)
-                let mut max_width: f32 = 0.0;
-                for i in 0..doc.page_count() {
-                    let (pw, ph) = doc.page_dimensions(i);
-                    let w = if state.rotation == 90 || state.rotation == 270 { ph } else { pw };
-                    max_width = max_width.max(w);
-                }
-                drop(state);
-
-                if render_w > 0 && max_width > 0.0 {
-                    let zoom = (render_w as f32 / max_width).clamp(0.1, 10.0);
-
-                    let mut state = self.state.lock();
-                    state.zoom = zoom;
-                    drop(state);
-                    self.statusbar.set_zoom(zoom);
-                }
-            } else {
-                // Single page view - fit both width and height
-                let (doc_width, doc_height) = doc.dimensions();
-                let (w, h) = if state.rotation == 90 || state.rotation == 270 {
-                    (doc_height, doc_width)
-                } else {
-                    (doc_width, doc_height)
-                };
-                drop(state);
-
-                let (render_w, render_h) = self.renderer.size();
-                if render_w > 0 && render_h > 0 && w > 0.0 && h > 0.0 {
-                    let zoom_w = render_w as f32 / w;
-                    let zoom_h = render_h as f32 / h;
-                    let zoom = zoom_w.min(zoom_h).clamp(0.1, 10.0);
-
-                    let mut state = self.state.lock();
-                    state.zoom = zoom;
-                    drop(state);
-                    self.statusbar.set_zoom(zoom);
-                }
+                let max_width = (0..doc.page_count()).map(|i| shown_size(i).0).fold(0.0, f32::max);
+                (render_w > 0 && max_width > 0.0).then(|| (render_w as f32 / max_width).clamp(0.1, 10.0))
+            }
+            ZoomMode::FitPage => fit_zoom((render_w, render_h), doc.display_dimensions(current, state.auto_crop), state.page_rotation(current)),
+            ZoomMode::FitWidth => fit_width_zoom(render_w, doc.display_dimensions(current, state.auto_crop), state.page_rotation(current)),
+            ZoomMode::FitHeight => {
+                let (_, h) = shown_size(current);
+                (render_h > 0 && h > 0.0).then(|| (render_h as f32 / h).clamp(0.1, 10.0))
             }
         }
     }
@@ -1643,141 +5070,216 @@ impl App {
         }
     }
 
-    fn cmd_copy_to_clipboard(&mut self) {
+    fn cmd_copy_to_clipboard(&mut self) {
+        let current_page = self.state.lock().current_page;
+        self.copy_page_to_clipboard(current_page);
+    }
+
+    /// Copies a page as displayed (rotation, watermark) as an image (see clipboard.rs)
+    fn copy_page_to_clipboard(&mut self, page: usize) {
+        let (doc, transform) = {
+            let state = self.state.lock();
+            if let Some(ref doc) = state.document {
+                (doc.clone(), self.page_transform(&state, page))
+            } else { return; }
+        };
+
+        let copied = self
+            .wic_loader
+            .get_bitmap_for_clipboard(&doc, page, &transform, self.watermark.as_ref())
+            .and_then(|bitmap_data| crate::clipboard::write_image(self.window.hwnd(), &bitmap_data));
+        if let Err(e) = copied {
+            self.show_error_details(&format!("Kopieren fehlgeschlagen:\n{}", crate::load_error::describe(&e)), Some(&e), None);
+        }
+    }
+
+    /// Ctrl+V: opens a copied file or shows the clipboard image as an untitled document
+    fn cmd_paste(&mut self) {
+        self.operation_log.record("Einfügen aus der Zwischenablage");
+        let content = match crate::clipboard::read(self.window.hwnd()) {
+            Ok(Some(c)) => c,
+            Ok(None) => return,
+            Err(e) => {
+                self.show_error_details(&format!("Einfügen fehlgeschlagen:\n{}", crate::load_error::describe(&e)), Some(&e), None);
+                return;
+            }
+        };
+
+        let result = match content {
+            ClipboardContent::File(path) => {
+                let allowed = self.dialogs.restricted_path.as_deref()
+                    .is_none_or(|root| crate::deeplink::is_within_root(&path, root));
+                if allowed {
+                    self.open_document(&path);
+                } else {
+                    self.show_error("Die Datei in der Zwischenablage liegt außerhalb des freigegebenen Ordners.");
+                }
+                return;
+            }
+            ClipboardContent::Png(data) => crate::wic::decode_from_memory(&data).and_then(|bitmap| {
+                let (mut width, mut height) = (0u32, 0u32);
+                unsafe { bitmap.GetSize(&mut width, &mut height)? };
+                Ok(Document::new_image(bitmap, width, height, None, None))
+            }),
+            ClipboardContent::Pixels { width, height, data } => self
+                .wic_loader
+                .create_bitmap_from_data(width, height, &data)
+                .map(|bitmap| Document::new_image(bitmap, width, height, None, None)),
+        };
+
+        let doc = match result {
+            Ok(doc) => doc,
+            Err(e) => {
+                self.show_error_details(&format!("Einfügen fehlgeschlagen:\n{}", crate::load_error::describe(&e)), Some(&e), None);
+                return;
+            }
+        };
+
+        if !self.confirm_discard_changes() {
+            return;
+        }
+
+        let doc_size = doc.page_size_text(0);
+        self.temp_download = None;
+        {
+            let mut state = self.state.lock();
+            state.document = Some(doc);
+            state.current_page = 0;
+            state.total_pages = 1;
+            state.rotation = 0;
+            state.default_rotation = 0;
+            state.page_rotations.clear();
+            state.file_path = None;
+            state.zoom_mode = ZoomMode::FitPage;
+            state.zoom = 1.0;
+            state.folder_files = Vec::new();
+            state.folder_file_index = 0;
+            state.folder_navigation_mode = false;
+            state.folder_cache_valid = false;
+            state.navigation_context = NavigationContext::Disabled;
+            state.scroll_x = 0;
+            state.scroll_y = 0;
+            state.load_error = None;
+            state.selection = None;
+        }
+        self.close_annotations();
+        self.close_pins();
+        self.forget_skew();
+        self.ocr_words.clear();
+        self.watch_file(None);
+
+        self.window.set_title("SimpliView");
+        self.statusbar.set_file_info("Zwischenablage", &doc_size, None, 0, 0, 1, None);
+        self.set_unapplied_changes(false);
+        self.forget_difference();
+        self.view_history.clear();
+        self.top_toolbar.set_document_loaded(true);
+        self.update_navigation_buttons();
+        self.statusbar.set_document_loaded(true);
+        self.context_menu.set_document_loaded(true);
+
+        self.set_zoom_mode(ZoomMode::FitPage);
+        self.update_content_size();
+        self.invalidate();
+    }
+
+    /// Saves the current page as displayed (rotation, watermark) and sets it as desktop wallpaper
+    fn cmd_set_wallpaper(&mut self, monitor: Option<usize>, position: WallpaperPosition) {
         let (doc, current_page, rotation) = {
             let state = self.state.lock();
-            if let Some(ref doc) = state.document {
-                (doc.clone(), state.current_page, state.rotation)
-            } else { return; }
+            match state.document {
+                Some(ref doc) => (doc.clone(), state.current_page, state.page_rotation(state.current_page)),
+                None => return,
+            }
         };
 
-        if let Ok(bitmap_data) = self.wic_loader.get_bitmap_for_clipboard(&doc, current_page, rotation) {
-            unsafe {
-                if OpenClipboard(self.window.hwnd()).as_bool() {
-                    let _ = EmptyClipboard();
-                    let header_size = std::mem::size_of::<BITMAPINFOHEADER>();
-                    let total_size = header_size + bitmap_data.data.len();
-
-                    if let Ok(hglobal) = GlobalAlloc(GMEM_MOVEABLE, total_size) {
-                        let ptr = GlobalLock(hglobal);
-                        if !ptr.is_null() {
-                            let header = BITMAPINFOHEADER {
-                                biSize: header_size as u32,
-                                biWidth: bitmap_data.width as i32,
-                                biHeight: bitmap_data.height as i32,
-                                biPlanes: 1,
-                                biBitCount: 32,
-                                biCompression: BI_RGB.0 as u32,
-                                biSizeImage: bitmap_data.data.len() as u32,
-                                ..Default::default()
-                            };
-                            std::ptr::copy_nonoverlapping(&header as *const _ as *const u8, ptr as *mut u8, header_size);
-                            std::ptr::copy_nonoverlapping(bitmap_data.data.as_ptr(), (ptr as *mut u8).add(header_size), bitmap_data.data.len());
-                            let _ = GlobalUnlock(hglobal);
-                            let _ = SetClipboardData(CF_DIB.0 as u32, HANDLE(hglobal.0));
-                        }
-                    }
-                    let _ = CloseClipboard();
-                }
-            }
+        let _wait_cursor = WaitCursorGuard::new();
+        self.operation_log.record("Als Hintergrundbild festlegen");
+        let path = crate::wallpaper::image_path(monitor);
+        let result = self
+            .wic_loader
+            .save(&doc, &path, current_page, &PageTransform::rotated(rotation), self.watermark.as_ref(), false)
+            .and_then(|_| crate::wallpaper::apply(&path, monitor, position));
+
+        if let Err(e) = result {
+            self.show_error_details(
+                &format!("Das Hintergrundbild konnte nicht festgelegt werden:\n{}", crate::load_error::describe(&e)),
+                Some(&e),
+                Some(&path),
+            );
         }
     }
 
     // --- File Loading Helpers ---
 
     fn get_file_size(path: &str) -> u64 {
-        unsafe {
-            let path_wide: Vec<u16> = path.encode_utf16().chain(std::iter::once(0)).collect();
-            let mut file_data = WIN32_FILE_ATTRIBUTE_DATA::default();
-            if GetFileAttributesExW(PCWSTR(path_wide.as_ptr()), GetFileExInfoStandard, &mut file_data as *mut _ as *mut _).as_bool() {
-                ((file_data.nFileSizeHigh as u64) << 32) | (file_data.nFileSizeLow as u64)
-            } else { 0 }
-        }
+        crate::cloud_file::file_info(path).map(|info| info.size).unwrap_or(0)
     }
 
-    /// Natural sort key for filenames: splits into text and numeric segments
-    /// Example: "file2.txt" < "file10.txt" (unlike lexicographic sort)
-    fn natural_sort_key(path: &str) -> Vec<(String, u64)> {
-        let filename = std::path::Path::new(path)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or(path)
-            .to_lowercase();
-
-        let mut result = Vec::new();
-        let mut current_text = String::new();
-        let mut current_num = String::new();
-        let mut in_number = false;
-
-        for ch in filename.chars() {
-            if ch.is_ascii_digit() {
-                if !in_number && !current_text.is_empty() {
-                    result.push((current_text.clone(), 0));
-                    current_text.clear();
-                }
-                in_number = true;
-                current_num.push(ch);
-            } else {
-                if in_number && !current_num.is_empty() {
-                    let num = current_num.parse::<u64>().unwrap_or(0);
-                    result.push((String::new(), num));
-                    current_num.clear();
+    /// Fails fast for an empty file and downloads an online-only cloud file before it is
+    /// decoded, with the progress in the statusbar. A missing file is left to the loader.
+    fn prepare_file(&mut self, path: &str, cancel: &CancelToken) -> Result<()> {
+        let Ok(info) = crate::cloud_file::file_info(path) else {
+            return Ok(());
+        };
+        if info.size == 0 {
+            return Err(crate::cloud_file::empty_file());
+        }
+        if info.cloud_placeholder {
+            self.operation_log.record(&format!("Aus der Cloud laden: {}", path));
+            let statusbar_hwnd = self.statusbar.toolbar_hwnd();
+            let statusbar = &mut self.statusbar;
+            statusbar.set_cloud_progress(0, info.size);
+            crate::cloud_file::hydrate(path, info.size, cancel, |received, total| {
+                statusbar.set_cloud_progress(received, total);
+                unsafe {
+                    UpdateWindow(statusbar_hwnd);
                 }
-                in_number = false;
-                current_text.push(ch);
-            }
+            })?;
         }
+        Ok(())
+    }
 
-        // Push remaining
-        if !current_text.is_empty() {
-            result.push((current_text, 0));
+    /// Estimates the memory of a PDF or image before it is loaded and asks first if it is
+    /// above the limit (see `settings::large_document_limit`). Returns whether to load in
+    /// reduced quality; cancelling returns E_CANCELLED. The inbox opens such files in reduced
+    /// quality without asking.
+    fn check_document_size(&mut self, path: &str, ext: &str) -> Result<bool> {
+        let Some(limit) = crate::settings::large_document_limit() else {
+            return Ok(false);
+        };
+        let estimate = match ext {
+            "pdf" => self.pdf_loader.estimate_memory(path),
+            "jpg" | "jpeg" | "png" | "bmp" | "tif" | "tiff" | "webp" | "gif" | "heic" | "avif" => WicLoader::estimate_memory(path),
+            _ => return Ok(false),
+        };
+        // Files that cannot be estimated (e.g. encrypted PDFs) load as before and report
+        // their errors there
+        let Ok(estimate) = estimate.map_err(|_| ()).and_then(|bytes| if bytes > limit { Ok(bytes) } else { Err(()) }) else {
+            return Ok(false);
+        };
+        self.operation_log.record(&format!("Großes Dokument (geschätzt {}): {}", crate::utils::format_file_size(estimate), path));
+        if self.unattended_open {
+            return Ok(true);
         }
-        if !current_num.is_empty() {
-            let num = current_num.parse::<u64>().unwrap_or(0);
-            result.push((String::new(), num));
+        let filename = std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or("Datei");
+        match crate::dialogs::confirm_large_document(self.window.hwnd(), filename, estimate) {
+            crate::dialogs::LargeDocumentChoice::Open => Ok(false),
+            crate::dialogs::LargeDocumentChoice::Reduced => Ok(true),
+            crate::dialogs::LargeDocumentChoice::Cancel => Err(Error::from(E_CANCELLED)),
         }
-
-        result
     }
 
-    fn scan_folder_files(path: &str) -> (Vec<String>, usize) {
-        let path_obj = std::path::Path::new(path);
-        let folder = match path_obj.parent() { Some(f) => f, None => return (vec![path.to_string()], 0) };
-        let folder_str = match folder.to_str() { Some(s) => s, None => return (vec![path.to_string()], 0) };
-        let supported_extensions = ["pdf", "jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp"];
-        let mut files: Vec<String> = Vec::new();
-
-        unsafe {
-            let search_pattern = format!("{}\\*", folder_str);
-            let pattern_wide: Vec<u16> = search_pattern.encode_utf16().chain(std::iter::once(0)).collect();
-            let mut find_data = WIN32_FIND_DATAW::default();
-            let handle = match FindFirstFileW(PCWSTR(pattern_wide.as_ptr()), &mut find_data) {
-                Ok(h) => h, Err(_) => return (vec![path.to_string()], 0),
-            };
-
-            loop {
-                if (find_data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY.0) == 0 {
-                    let filename_len = find_data.cFileName.iter().position(|&c| c == 0).unwrap_or(find_data.cFileName.len());
-                    let filename = String::from_utf16_lossy(&find_data.cFileName[..filename_len]);
-                    if let Some(dot_pos) = filename.rfind('.') {
-                        let ext = &filename[dot_pos + 1..].to_lowercase();
-                        if supported_extensions.contains(&ext.as_str()) {
-                            files.push(format!("{}\\{}", folder_str, filename));
-                        }
-                    }
-                }
-                if !FindNextFileW(handle, &mut find_data).as_bool() { break; }
-            }
-            let _ = FindClose(handle);
+    fn open_document(&mut self, path: &str) {
+        if !self.confirm_discard_changes() {
+            return;
+        }
+        if crate::download::is_url(path) {
+            self.open_url(path);
+            self.opened_from_cmdline = false;
+            return;
         }
 
-        // Use natural sort (file2 before file10)
-        files.sort_by(|a, b| Self::natural_sort_key(a).cmp(&Self::natural_sort_key(b)));
-        let current_index = files.iter().position(|f| f.eq_ignore_ascii_case(path)).unwrap_or(0);
-        (files, current_index)
-    }
-
-    fn open_document(&mut self, path: &str) {
         let skip_scan = self.opened_from_cmdline;
         // If already in folder navigation mode, preserve it even when using Open dialog
         let keep_folder_mode = if !skip_scan {
@@ -1790,12 +5292,135 @@ impl App {
         self.opened_from_cmdline = false;
     }
 
+    /// Downloads a http(s) document to a temp file and opens the temp copy.
+    /// The copy is treated like a command-line file (no folder navigation).
+    fn open_url(&mut self, url: &str) {
+        let _wait_cursor = WaitCursorGuard::new();
+        self.operation_log.record(&format!("Herunterladen: {}", url));
+
+        let name = url.split(['?', '#']).next().unwrap_or(url);
+        let name = crate::utils::percent_decode(name.rsplit('/').next().unwrap_or(""));
+        self.statusbar.set_loading_file(if name.is_empty() { url } else { &name });
+
+        // Esc, Ctrl+O or closing the window aborts the download like a load (see cancel.rs)
+        let cancel = CancelToken::new(self.window.hwnd());
+        let statusbar_hwnd = self.statusbar.toolbar_hwnd();
+        let statusbar = &mut self.statusbar;
+        let result = crate::download::download_to_temp(url, &cancel, |received, total| {
+            statusbar.set_download_progress(received, total);
+            unsafe {
+                UpdateWindow(statusbar_hwnd);
+            }
+        });
+
+        let download = match result {
+            Ok(d) => d,
+            Err(e) if e.code() == E_CANCELLED => {
+                self.operation_log.record(&format!("Herunterladen abgebrochen: {}", url));
+                self.restore_statusbar();
+                return;
+            }
+            Err(e) => {
+                self.statusbar.set_loading_file("");
+                self.show_error_details(&format!("Datei konnte nicht geladen werden:\n{}\n\n{}", url, e.message()), Some(&e), Some(url));
+                return;
+            }
+        };
+
+        // Keep the previous temp file until the new document is actually shown
+        let temp_path = download.path().to_string();
+        let previous = self.temp_download.replace(download);
+        self.open_document_internal(&temp_path, false, true);
+
+        let loaded = self.state.lock().file_path.as_deref() == Some(temp_path.as_str());
+        if !loaded {
+            self.temp_download = previous;
+        }
+    }
+
     fn open_document_with_mode(&mut self, path: &str, keep_folder_mode: bool) {
+        let keep_view = keep_folder_mode && crate::settings::keep_view();
+        // A kept rotation moves on to the next file, so only page rotations would be lost
+        let carries_changes = keep_view && self.state.lock().page_rotations.is_empty();
+        if !carries_changes && !self.confirm_discard_changes() {
+            return;
+        }
+        if keep_view {
+            if let Some(view) = self.capture_view() {
+                self.kept_view = Some(view);
+            }
+        }
         self.open_document_internal(path, keep_folder_mode, false);
+        if keep_view && self.state.lock().load_error.is_none() {
+            if let Some(view) = self.kept_view {
+                self.apply_kept_view(view);
+            }
+        }
+    }
+
+    /// Current zoom, rotation and viewport position (None without a document)
+    fn capture_view(&self) -> Option<KeptView> {
+        let state = self.state.lock();
+        state.document.as_ref()?;
+        let (viewport_w, viewport_h) = self.renderer.size();
+        let (left, top, width, height) = Self::page_content_rect(&state, state.current_page)?;
+        let center_x = (state.scroll_x + viewport_w as i32 / 2) as f32;
+        let center_y = (state.scroll_y + viewport_h as i32 / 2) as f32;
+        Some(KeptView {
+            zoom_mode: state.zoom_mode,
+            rotation: state.rotation,
+            page: state.current_page,
+            center_u: ((center_x - left) / width.max(1.0)).clamp(0.0, 1.0),
+            center_v: ((center_y - top) / height.max(1.0)).clamp(0.0, 1.0),
+        })
+    }
+
+    /// Applies a kept view to the freshly loaded document: the page is clamped to the new
+    /// page count and the viewport centered on the same relative spot of that page.
+    fn apply_kept_view(&mut self, view: KeptView) {
+        {
+            let mut state = self.state.lock();
+            state.rotation = view.rotation;
+            state.current_page = view.page.min(state.total_pages.saturating_sub(1));
+        }
+        self.set_zoom_mode(view.zoom_mode);
+        let rotated = self.state.lock().is_rotated();
+        self.set_unapplied_changes(rotated);
+        self.update_content_size();
+
+        let (viewport_w, viewport_h) = self.renderer.size();
+        {
+            let mut state = self.state.lock();
+            if let Some((left, top, width, height)) = Self::page_content_rect(&state, state.current_page) {
+                state.scroll_x = (left + view.center_u * width) as i32 - viewport_w as i32 / 2;
+                state.scroll_y = (top + view.center_v * height) as i32 - viewport_h as i32 / 2;
+            }
+        }
+        // Clamps the scroll position to the new content and updates the scrollbars
+        self.update_content_size();
+
+        if self.state.lock().page_layout.is_some() {
+            self.update_current_page_from_scroll();
+        }
+        self.update_page_display_and_repaint();
+        self.update_navigation_buttons();
+    }
+
+    /// Page rectangle in content coordinates (left, top, width, height)
+    fn page_content_rect(state: &AppState, page: usize) -> Option<(f32, f32, f32, f32)> {
+        match state.page_layout {
+            Some(ref layout) => {
+                let (w, h) = *layout.page_sizes.get(page)?;
+                let left = (layout.max_width - w) / 2;
+                Some((left as f32, layout.page_tops[page] as f32, w as f32, h as f32))
+            }
+            None => Some((0.0, 0.0, state.content_width as f32, state.content_height as f32)),
+        }
     }
 
     fn open_document_internal(&mut self, path: &str, keep_folder_mode: bool, skip_folder_scan: bool) {
         let _wait_cursor = WaitCursorGuard::new();
+        self.operation_log.record(&format!("Öffnen: {}", path));
 
         // Show filename in statusbar immediately before loading
         let filename = std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or("Datei");
@@ -1806,17 +5431,55 @@ impl App {
         }
 
         let ext = std::path::Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
-        let result = match ext.as_str() {
-            "pdf" => self.load_pdf(path),
-            "jpg" | "jpeg" | "png" | "bmp" | "tif" | "tiff" | "webp" | "ico" | "icon" => self.load_image(path),
-            _ => Err(Error::from_win32()),
+        // Esc, Ctrl+O or closing the window aborts the load (see cancel.rs)
+        let cancel = CancelToken::new(self.window.hwnd());
+        let load_start = Instant::now();
+        let result = match self.prepare_file(path, &cancel).and_then(|()| self.check_document_size(path, &ext)) {
+            Err(e) => Err(e),
+            Ok(reduced) => {
+                self.pdf_loader.set_reduced_quality(reduced);
+                self.wic_loader.set_reduced_quality(reduced);
+                let result = match ext.as_str() {
+                    "pdf" => self.load_pdf(path, &cancel),
+                    // HEIC and AVIF need the codecs from the Microsoft Store; without them
+                    // loading fails with the hint to install the codec (load_error.rs)
+                    "jpg" | "jpeg" | "png" | "bmp" | "tif" | "tiff" | "webp" | "gif" | "heic" | "avif" | "ico" | "icon" => {
+                        self.load_image(path, &cancel)
+                    }
+                    "zip" | "cbz" => self.load_archive(path, &cancel),
+                    "dcm" => crate::dicom::load(path, &cancel),
+                    _ => Err(crate::load_error::unsupported_format()),
+                };
+                self.pdf_loader.set_reduced_quality(false);
+                self.wic_loader.set_reduced_quality(false);
+                result
+            }
         };
+        self.statusbar.clear_progress();
 
         match result {
             Ok(doc) => {
+                self.debug_overlay.set_load_time(load_start.elapsed());
+                // The split is adjusted per document; reloading the same file keeps it
+                if self.state.lock().file_path.as_deref() != Some(path) {
+                    self.split_position = 0.5;
+                }
+                let doc = doc.with_split(self.split_spreads.then_some(self.split_position));
+
+                // Another document replaces a downloaded temp copy - remove it
+                if self.temp_download.as_ref().is_some_and(|t| t.path() != path) {
+                    self.temp_download = None;
+                }
+
+                // Remembered for "Letzte Datei beim Start öffnen" (not for downloads or in restricted mode)
+                if self.temp_download.is_none() && self.dialogs.restricted_path.is_none() {
+                    crate::settings::set_last_file(path);
+                    crate::settings::add_recent_file(path, crate::menu_bar::MAX_RECENT);
+                }
+
                 let total_pages = doc.page_count();
-                let (width, height) = doc.dimensions();
                 let file_size = Self::get_file_size(path);
+                self.record_retries();
                 let is_multipage = total_pages > 1;
 
                 // Determine navigation context and whether to scan folder
@@ -1836,6 +5499,7 @@ impl App {
                     let state = self.state.lock();
                     let files = state.folder_files.clone();
                     let cache_valid = state.folder_cache_valid;
+                    let recursive = state.folder_recursive;
                     let old_file_path = state.file_path.clone();
                     drop(state);
 
@@ -1849,7 +5513,9 @@ impl App {
                         _ => false,
                     };
 
-                    if same_folder && cache_valid {
+                    // With subfolders the next file may live in another folder of the list
+                    let in_list = recursive && files.iter().any(|f| f.eq_ignore_ascii_case(path));
+                    if (same_folder || in_list) && cache_valid {
                         // Same folder, keep cache
                         let idx = files.iter().position(|f| f.eq_ignore_ascii_case(path)).unwrap_or(0);
                         (NavigationContext::FolderBrowsing, files, idx, cache_valid)
@@ -1872,17 +5538,20 @@ impl App {
                 let nav_mode = nav_context == NavigationContext::FolderBrowsing;
 
                 // Multi-page documents use 100% zoom, single-page uses fit-to-page
-                let initial_zoom = 1.0; // Will be recalculated for single-page
 
                 {
                     let mut state = self.state.lock();
                     state.document = Some(doc.clone());
                     state.current_page = 0;
                     state.total_pages = total_pages;
-                    state.rotation = 0;
+                    // Applied before the fit and content size below, so the first paint is rotated
+                    state.default_rotation = crate::settings::default_rotation(ext == "pdf");
+                    state.rotation = state.default_rotation;
+                    state.page_rotations.clear();
                     state.file_path = Some(path.to_string());
-                    state.fit_to_page = !is_multipage; // Fit only for single-page documents
-                    state.zoom = initial_zoom;
+                    // Fit only for single-page documents
+                    state.zoom_mode = if is_multipage { ZoomMode::Fixed(1.0) } else { ZoomMode::FitPage };
+                    state.zoom = 1.0;
                     state.folder_files = folder_files;
                     state.folder_file_index = folder_index;
                     state.folder_navigation_mode = nav_mode;
@@ -1890,58 +5559,166 @@ impl App {
                     state.navigation_context = nav_context;
                     state.scroll_x = 0;
                     state.scroll_y = 0;
+                    state.load_error = None;
+                    state.failed_files.remove(path);
+                    state.selection = None;
+                }
+                self.close_annotations();
+                self.close_pins();
+                self.forget_skew();
+                self.ocr_words.clear();
+                self.watch_file(Some(path));
+                if doc.doc_type() == crate::document::DocumentType::Pdf && doc.pdf_password().is_none() {
+                    self.structure_request += 1;
+                    crate::pdf::read_structure(self.window.hwnd(), path.to_string(), self.structure_request, total_pages);
                 }
 
-                let filename = std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or("SimpliView");
+                let filename = Self::status_file_name(&self.state.lock(), path);
                 self.window.set_title("SimpliView");
 
-                let dim_str = if doc.doc_type() == crate::document::DocumentType::Pdf {
-                   format!("{:.0}x{:.0} mm", width * 25.4 / 72.0, height * 25.4 / 72.0)
-                } else {
-                   format!("{}x{} px", width as u32, height as u32)
-                };
-
-                self.statusbar.set_file_info(filename, &dim_str, file_size, 0, total_pages);
+                self.statusbar.set_file_info(
+                    &filename,
+                    &doc.page_size_text(0),
+                    doc.format_text(0).as_deref(),
+                    file_size,
+                    0,
+                    total_pages,
+                    doc.page_label(0),
+                );
+                self.set_unapplied_changes(false);
+                self.forget_difference();
+                self.view_history.clear();
                 self.top_toolbar.set_document_loaded(true);
                 // Update navigation buttons based on context and position
                 self.update_navigation_buttons();
                 self.statusbar.set_document_loaded(true);
                 self.context_menu.set_document_loaded(true);
 
-                // Initial layout calculation - only fit for single-page documents
-                if !is_multipage {
-                    self.calculate_fit_zoom();
-                } else {
-                    self.statusbar.set_zoom(1.0);
-                }
+                // Initial layout calculation
+                self.reapply_zoom_mode();
                 self.update_content_size();
                 self.invalidate();
             }
             Err(e) => {
-                // Don't show error for user cancellation (e.g., cancelled password dialog)
-                const ERROR_CANCELLED: u32 = 0x800704C7;
-                if e.code().0 as u32 != ERROR_CANCELLED {
-                    self.show_error(&format!("Datei konnte nicht geöffnet werden: {:?}", e));
+                self.record_retries();
+                // Don't show error for user cancellation (e.g., cancelled password dialog, Esc).
+                // The state was not touched - only the statusbar needs to show the previous document again.
+                if e.code() == E_CANCELLED {
+                    self.restore_statusbar();
+                    return;
+                }
+                // The inbox skips files it cannot open instead of interrupting with an error
+                if self.unattended_open {
+                    self.operation_log.record(&format!("Übersprungen (0x{:08X}): {}", e.code().0 as u32, path));
+                    self.state.lock().failed_files.insert(path.to_string());
+                    self.restore_statusbar();
+                    self.update_navigation_buttons();
+                    return;
+                }
+
+                let message = match crate::utils::unreachable_unc_share(path) {
+                    Some(share) => format!(
+                        "Die Netzwerkfreigabe {} ist nicht erreichbar.\n\nBitte Netzwerkverbindung und Berechtigungen prüfen.",
+                        share
+                    ),
+                    None => crate::load_error::describe(&e),
+                };
+
+                if keep_folder_mode {
+                    // Folder navigation: show the error in the canvas and keep Back/Next usable
+                    self.show_load_failure(path, message);
+                } else {
+                    self.show_error_details(
+                        &format!("Datei konnte nicht geöffnet werden:\n{}\n\n{}", filename, message),
+                        Some(&e),
+                        Some(path),
+                    );
                 }
             }
         }
     }
 
+    /// Moves the retry notes of the last file access into the operation log
+    fn record_retries(&mut self) {
+        for entry in crate::retry::take_log() {
+            self.operation_log.record(&entry);
+        }
+    }
+
+    /// Shows the current document (or the load failure) in the statusbar again after a canceled load
+    fn restore_statusbar(&mut self) {
+        let state = self.state.lock();
+        let page = state.current_page;
+        let total = state.total_pages;
+        let path = state.file_path.clone();
+        let has_document = state.document.is_some();
+        drop(state);
+
+        match path {
+            Some(ref path) if has_document => self.update_page_display(page, total, Some(path)),
+            Some(ref path) => {
+                let filename = std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or("Datei");
+                self.statusbar.set_load_failed(filename);
+            }
+            None => self.statusbar.clear_file_info(),
+        }
+    }
+
+    /// Replaces the current document with the error panel for `path`.
+    /// The file is marked as failed so Back/Next skip it from now on.
+    fn show_load_failure(&mut self, path: &str, message: String) {
+        {
+            let mut state = self.state.lock();
+            if let Some(idx) = state.folder_files.iter().position(|f| f.eq_ignore_ascii_case(path)) {
+                state.folder_file_index = idx;
+            }
+            state.document = None;
+            state.page_layout = None;
+            state.current_page = 0;
+            state.total_pages = 1;
+            state.file_path = Some(path.to_string());
+            state.navigation_context = NavigationContext::FolderBrowsing;
+            state.folder_navigation_mode = true;
+            state.scroll_x = 0;
+            state.scroll_y = 0;
+            state.failed_files.insert(path.to_string());
+            state.selection = None;
+            state.load_error = Some(LoadFailure { path: path.to_string(), message });
+        }
+        self.close_annotations();
+        self.close_pins();
+        self.forget_skew();
+        self.ocr_words.clear();
+        self.watch_file(None);
+
+        let filename = std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or("Datei");
+        self.statusbar.set_load_failed(filename);
+        self.set_unapplied_changes(false);
+        self.top_toolbar.set_document_loaded(false);
+        self.statusbar.set_document_loaded(false);
+        self.context_menu.set_document_loaded(false);
+        self.update_navigation_buttons();
+        self.update_content_size();
+        self.invalidate();
+    }
+
     /// Loads a PDF document, handling password-protected files with user prompts.
     ///
     /// Flow:
-    /// 1. Try loading without password
+    /// 1. Try loading without password, or with a known one (reload, --password)
     /// 2. If password required, prompt user (up to MAX_PASSWORD_ATTEMPTS times)
     /// 3. User can cancel at any time to abort loading gracefully
     ///
     /// Returns ERROR_CANCELLED (0x800704C7) when user cancels to distinguish from real errors.
-    fn load_pdf(&mut self, path: &str) -> Result<Document> {
+    fn load_pdf(&mut self, path: &str, cancel: &CancelToken) -> Result<Document> {
         const MAX_PASSWORD_ATTEMPTS: u32 = 3;
         // ERROR_CANCELLED - used to signal user cancellation (no error message should be shown)
         const ERROR_CANCELLED: i32 = 0x800704C7u32 as i32;
 
-        // First attempt: try without password
-        match self.pdf_loader.load(path, None) {
+        // First attempt: try without password (or with the known one when reloading or
+        // handed over on the command line)
+        let known_password = self.known_password.take();
+        match self.pdf_loader.load(path, known_password.as_ref().map(Password::as_str), cancel, Self::load_progress(&self.statusbar)) {
             Ok(doc) => return Ok(doc),
             Err(e) => {
                 // Check if this is a password-protected PDF
@@ -1970,8 +5747,9 @@ impl App {
             attempts += 1;
 
             // Try loading with provided password
-            match self.pdf_loader.load(path, Some(&password)) {
+            match self.pdf_loader.load(path, Some(&password), cancel, Self::load_progress(&self.statusbar)) {
                 Ok(doc) => return Ok(doc),  // Success!
+                Err(e) if e.code() == E_CANCELLED => return Err(e),
                 Err(_) => {
                     // Wrong password
                     if attempts >= MAX_PASSWORD_ATTEMPTS {
@@ -1992,57 +5770,486 @@ impl App {
         }
     }
 
-    fn load_image(&mut self, path: &str) -> Result<Document> {
-        self.wic_loader.load(path)
+    fn load_image(&mut self, path: &str, cancel: &CancelToken) -> Result<Document> {
+        self.wic_loader.load(path, cancel)
+    }
+
+    fn load_archive(&mut self, path: &str, cancel: &CancelToken) -> Result<Document> {
+        self.archive_loader.load(path, cancel, Self::load_progress(&self.statusbar))
+    }
+
+    /// Progress callback for the loaders; the status bar shows it only once loading takes
+    /// longer than PROGRESS_DELAY, so quick loads don't flash the bar
+    fn load_progress(statusbar: &StatusBar) -> impl FnMut(usize, usize) + '_ {
+        let start = Instant::now();
+        move |current, total| {
+            if start.elapsed() >= PROGRESS_DELAY {
+                statusbar.set_progress(current, total);
+            }
+        }
     }
 
-    fn export_document(&self, path: &str) {
+    /// Exports the current page. `rotation` is applied to image output; a PDF exported
+    /// as PDF is copied byte for byte and keeps its original orientation. Once the file was
+    /// changed or deleted by another program, images are encoded from the loaded pages
+    /// instead of copied. Returns true if the file was written.
+    /// Writes the current page to `path`, with `crop` (source pixels) only that part of it,
+    /// turned clockwise by `skew` degrees on top of `rotation`
+    fn export_document(&self, path: &str, transform: &PageTransform, webp_lossless: bool) -> bool {
         let (doc, current_page, source_path) = {
             let state = self.state.lock();
-            if let Some(ref doc) = state.document {
-                (Some(doc.clone()), state.current_page, state.file_path.clone())
-            } else { (None, 0, None) }
-        };
-
-        if let Some(doc) = doc {
-            // Check if user chose PDF export
-            if path.to_lowercase().ends_with(".pdf") {
-                if doc.doc_type() == crate::document::DocumentType::Pdf {
-                    if let Some(src) = source_path {
-                        if let Err(e) = std::fs::copy(&src, path) {
-                            self.show_error(&format!("PDF-Export fehlgeschlagen: {}", e));
-                        }
-                        return;
+            match state.document {
+                Some(ref doc) => (doc.clone(), state.current_page, state.file_path.clone()),
+                None => return false,
+            }
+        };
+
+        // Changed or deleted since it was loaded: a copy of the file would not be what is shown
+        let source_current = self.source_change.is_none();
+
+        // Check if user chose PDF export
+        if path.to_lowercase().ends_with(".pdf") {
+            if self.watermark.is_some() {
+                self.show_error("Mit Wasserzeichen ist nur der Export als Bild möglich. Bitte wählen Sie ein Bildformat.");
+                return false;
+            }
+            if transform.crop.is_some() {
+                self.show_error("Zugeschnitten ist nur der Export als Bild möglich. Bitte wählen Sie ein Bildformat.");
+                return false;
+            }
+            if transform.skew != 0.0 {
+                self.show_error("Mit korrigierter Schräglage ist nur der Export als Bild möglich. Bitte wählen Sie ein Bildformat.");
+                return false;
+            }
+            if doc.doc_type() == crate::document::DocumentType::Pdf && !source_current {
+                self.show_error("Die PDF-Datei wurde seit dem Öffnen geändert oder gelöscht. Bitte laden Sie sie neu oder exportieren Sie die Seite als Bild.");
+                return false;
+            }
+            if doc.doc_type() == crate::document::DocumentType::Pdf {
+                if let Some(src) = source_path {
+                    if let Err(e) = copy_atomically(&src, path) {
+                        self.show_error_details(&format!("PDF-Export fehlgeschlagen:\n{}", e.message()), Some(&e), Some(path));
+                        return false;
                     }
-                } else {
-                    self.show_error("Bild kann nicht als PDF exportiert werden. Bitte wählen Sie ein Bildformat.");
-                    return;
+                    return true;
                 }
+            } else {
+                self.show_error("Bild kann nicht als PDF exportiert werden. Bitte wählen Sie ein Bildformat.");
+                return false;
             }
+        }
 
-            if let Err(e) = self.wic_loader.save(&doc, path, current_page) {
-                self.show_error(&format!("Export fehlgeschlagen: {:?}", e));
+        let single_image = doc.doc_type() == crate::document::DocumentType::Image && doc.page_count() == 1;
+        let transformed = *transform != PageTransform::default() || self.watermark.is_some();
+        let method = export_method(source_path.as_deref(), path, single_image, transformed, source_current);
+        if method == ExportMethod::Copy {
+            if let Some(src) = source_path {
+                if let Err(e) = copy_atomically(&src, path) {
+                    self.show_error_details(&format!("Export fehlgeschlagen:\n{}", e.message()), Some(&e), Some(path));
+                    return false;
+                }
+                return true;
             }
         }
+        if method == ExportMethod::ReencodeSameFormat
+            && !crate::dialogs::confirm_warning(
+                self.window.hwnd(),
+                "Das Bild wird neu kodiert.\nWegen der Drehung, des Zuschnitts, der Schräglage bzw. des Wasserzeichens wird es nicht nur kopiert. Dabei können Qualität und Metadaten verloren gehen.",
+            )
+        {
+            return false;
+        }
+
+        let _wait_cursor = WaitCursorGuard::new();
+        if let Err(e) = self.wic_loader.save(&doc, path, current_page, transform, self.watermark.as_ref(), webp_lossless) {
+            self.show_error_details(&format!("Export fehlgeschlagen:\n{}", crate::load_error::describe(&e)), Some(&e), Some(path));
+            return false;
+        }
+        true
     }
 
     fn prompt_password(&self) -> Option<String> { crate::dialogs::password_dialog(self.window.hwnd()) }
     fn retry_password(&self) -> bool { crate::dialogs::retry_password_dialog(self.window.hwnd()) }
     fn show_error(&self, message: &str) { crate::dialogs::show_error(self.window.hwnd(), message); }
+
+    /// Error box with error code, file and the recent operations in its "Details" section
+    fn show_error_details(&self, message: &str, error: Option<&Error>, path: Option<&str>) {
+        let details = self.operation_log.details(error, path);
+        crate::dialogs::show_error_details(self.window.hwnd(), message, Some(&details));
+    }
     
     fn update_page_display(&mut self, page: usize, total: usize, path: Option<&str>) {
         let state = self.state.lock();
         if let Some(ref doc) = state.document {
-            let (width, height) = doc.page_dimensions(page);
+            let dim_str = doc.page_size_text(page);
+            let format = doc.format_text(page);
+            let label = doc.page_label(page).map(str::to_string);
             let file_size = path.map(Self::get_file_size).unwrap_or(0);
-            let filename = path.and_then(|p| std::path::Path::new(p).file_name()).and_then(|n| n.to_str()).unwrap_or("");
-            let dim_str = if doc.doc_type() == crate::document::DocumentType::Pdf {
-                format!("{:.0}x{:.0} mm", width * 25.4 / 72.0, height * 25.4 / 72.0)
-            } else {
-                format!("{}x{} px", width as u32, height as u32)
-            };
+            let filename = path.map(|p| Self::status_file_name(&state, p)).unwrap_or_default();
             drop(state);
-            self.statusbar.set_file_info(filename, &dim_str, file_size, page, total);
+            self.statusbar.set_file_info(&filename, &dim_str, format.as_deref(), file_size, page, total, label.as_deref());
+            self.update_skew_display();
+        }
+    }
+
+    /// File name for the statusbar; relative to the walk root for files from subfolders
+    fn status_file_name(state: &AppState, path: &str) -> String {
+        let relative = match state.folder_root {
+            Some(ref root) if state.folder_recursive && state.folder_cache_valid => crate::folder_scan::relative_name(path, root),
+            _ => None,
+        };
+        relative.unwrap_or_else(|| {
+            std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or("SimpliView").to_string()
+        })
+    }
+}
+
+/// Whether Back and Next have a target: a previous/next page, or a previous/next file of
+/// the folder that has not failed to load (Back/Next skip those)
+fn navigation_targets(state: &AppState) -> (bool, bool) {
+    match state.navigation_context {
+        NavigationContext::Disabled => (false, false),
+        // Lazy enumeration not done yet - enable both to allow triggering it
+        NavigationContext::FolderBrowsing if !state.folder_cache_valid => (true, true),
+        NavigationContext::FolderBrowsing => {
+            let idx = state.folder_file_index.min(state.folder_files.len());
+            let loadable = |file: &String| !state.failed_files.contains(file);
+            let back = state.folder_files[..idx].iter().any(loadable);
+            let next = state.folder_files.iter().skip(idx + 1).any(loadable);
+            (back, next)
+        }
+        // At first page: Back disabled. At last page: Next disabled.
+        NavigationContext::DocumentPaging => (state.current_page > 0, state.current_page + 1 < state.total_pages),
+    }
+}
+
+/// "An Seite": zoom that shows a `page_size` page turned by `rotation` whole in a
+/// `viewport` (both in pixels), `None` while either is empty
+fn fit_zoom(viewport: (u32, u32), page_size: (f32, f32), rotation: i32) -> Option<f32> {
+    let (width, height) = if rotation == 90 || rotation == 270 { (page_size.1, page_size.0) } else { page_size };
+    if viewport.0 == 0 || viewport.1 == 0 || width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+    Some((viewport.0 as f32 / width).min(viewport.1 as f32 / height).clamp(0.1, 10.0))
+}
+
+/// "Breite": zoom that fills a viewport `viewport_width` pixels wide with a `page_size`
+/// page turned by `rotation`, `None` while either is empty
+fn fit_width_zoom(viewport_width: u32, page_size: (f32, f32), rotation: i32) -> Option<f32> {
+    let width = if rotation == 90 || rotation == 270 { page_size.1 } else { page_size.0 };
+    (viewport_width > 0 && width > 0.0).then(|| (viewport_width as f32 / width).clamp(0.1, 10.0))
+}
+
+/// True if `zoom` spreads one source pixel over more than one screen pixel, so the page
+/// only gets larger, not sharper. Zoom 1.0 shows one source pixel per DIP: an image pixel,
+/// or for PDFs a pixel of the page as rendered for the "Renderqualität" (or --pdf-dpi)
+/// setting. `dpi_scale` is screen pixels per DIP. Fit modes are flagged alike; the zoom
+/// itself is not limited.
+///
+/// With display scaling above 100 %, zoom 100 % is flagged too: at 150 % each source pixel
+/// covers 1.5 screen pixels, the blur the hint is about. The source shows 1:1 there at 67 %.
+fn past_source_resolution(zoom: f32, dpi_scale: f32) -> bool {
+    // Rounding of fitted zooms is not worth a hint
+    const TOLERANCE: f32 = 1.005;
+    zoom * dpi_scale.max(0.1) > TOLERANCE
+}
+
+/// A view point turned clockwise by `degrees` about the center of `rect` (a page's
+/// bounding box), as the renderer turns a page for "Schräglage automatisch korrigieren"
+fn rotate_about_center(rect: &D2D_RECT_F, x: f32, y: f32, degrees: f32) -> (f32, f32) {
+    if degrees == 0.0 {
+        return (x, y);
+    }
+    let (center_x, center_y) = ((rect.left + rect.right) / 2.0, (rect.top + rect.bottom) / 2.0);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (dx, dy) = (x - center_x, y - center_y);
+    (center_x + dx * cos - dy * sin, center_y + dx * sin + dy * cos)
+}
+
+/// Export by copying the source file, through a temporary file (see `write_atomically`)
+fn copy_atomically(source: &str, target: &str) -> Result<()> {
+    crate::utils::write_atomically(target, |temp| {
+        std::fs::copy(crate::utils::long_path(source), crate::utils::long_path(temp))
+            .map(|_| ())
+            .map_err(crate::pdf_structure::io_error)
+    })
+}
+
+/// How an image export is written (see `export_method`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportMethod {
+    /// The source file is copied
+    Copy,
+    /// Encoded again in the format of the source; asked first, as quality and metadata
+    /// may get lost
+    ReencodeSameFormat,
+    /// Encoded in another format
+    Encode,
+}
+
+/// A single image exported in its own format is copied unless something has to be burned
+/// in (`transformed`), so JPEGs don't lose quality and metadata by re-encoding. A source
+/// changed since it was loaded is not what is shown, so that is encoded from the display.
+fn export_method(source: Option<&str>, target: &str, single_image: bool, transformed: bool, source_current: bool) -> ExportMethod {
+    let same_format = single_image && source_current && source.is_some_and(|src| same_image_format(src, target));
+    match (same_format, transformed) {
+        (false, _) => ExportMethod::Encode,
+        (true, false) => ExportMethod::Copy,
+        (true, true) => ExportMethod::ReencodeSameFormat,
+    }
+}
+
+/// Whether two paths have the same image format by extension (jpg = jpeg, tif = tiff)
+fn same_image_format(source: &str, target: &str) -> bool {
+    let format = |path: &str| {
+        let ext = std::path::Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        match ext.as_str() {
+            "jpeg" => "jpg".to_string(),
+            "tiff" => "tif".to_string(),
+            _ => ext,
+        }
+    };
+    let source = format(source);
+    !source.is_empty() && source == format(target)
+}
+
+/// Print dialog hook: adds the page picker and the header/footer options, centers the dialog and labels the
+/// standard "Selection" radio button
+unsafe extern "system" fn print_dialog_hook(hdlg: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> usize {
+    if let Some(result) = crate::page_picker::handle_message(hdlg, msg, wparam, lparam) {
+        return result;
+    }
+    match msg {
+        WM_INITDIALOG => {
+            crate::page_picker::attach(hdlg, lparam);
+            crate::print_stamp::add_dialog_controls(hdlg);
+            crate::dialogs::center_on_owner(hdlg);
+            let _ = SetDlgItemTextW(hdlg, windows::Win32::UI::Controls::rad2 as i32, w!("Nur Auswahl drucken"));
+            1
+        }
+        WM_COMMAND if (wparam.0 & 0xFFFF) as i32 == IDOK.0 => {
+            crate::print_stamp::save_dialog_controls(hdlg);
+            0
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_steps_walk_the_whole_table() {
+        let levels = App::ZOOM_LEVELS;
+        for (i, &level) in levels.iter().enumerate() {
+            let next = levels.get(i + 1).copied().unwrap_or(level);
+            let previous = if i == 0 { level } else { levels[i - 1] };
+            assert_eq!(App::next_zoom_level(level), next, "in from {level}");
+            assert_eq!(App::previous_zoom_level(level), previous, "out from {level}");
+        }
+    }
+
+    #[test]
+    fn zoom_steps_stop_at_the_ends() {
+        assert_eq!(App::ZOOM_LEVELS[0], 0.10);
+        assert_eq!(App::ZOOM_LEVELS[App::ZOOM_LEVELS.len() - 1], 10.0);
+        assert_eq!(App::previous_zoom_level(0.10), 0.10);
+        assert_eq!(App::next_zoom_level(10.0), 10.0);
+        // Fitted zooms beyond the table come back in one step
+        assert_eq!(App::previous_zoom_level(0.05), 0.10);
+        assert_eq!(App::next_zoom_level(0.05), 0.10);
+        assert_eq!(App::next_zoom_level(12.0), 10.0);
+        assert_eq!(App::previous_zoom_level(12.0), 10.0);
+    }
+
+    #[test]
+    fn zoom_steps_from_between_levels() {
+        // After a fit: the neighbouring levels, none skipped
+        assert_eq!(App::next_zoom_level(0.93), 1.0);
+        assert_eq!(App::previous_zoom_level(0.93), 0.85);
+        // Rounding off a level is not a level of its own
+        assert_eq!(App::next_zoom_level(1.003), 1.10);
+        assert_eq!(App::previous_zoom_level(0.997), 0.85);
+    }
+
+    const A4: (f32, f32) = (595.0, 842.0);
+
+    #[test]
+    fn fit_to_page_uses_the_tighter_side() {
+        // Portrait page in a landscape window: the height decides
+        assert_eq!(fit_zoom((1000, 421), A4, 0), Some(0.5));
+        // Portrait page in a narrow window: the width decides
+        assert_eq!(fit_zoom((595, 2000), A4, 0), Some(1.0));
+        assert_eq!(fit_zoom((1190, 1684), A4, 180), Some(2.0));
+    }
+
+    #[test]
+    fn fit_to_page_turns_the_page_by_a_quarter() {
+        // Turned, the A4 page is 842 wide and 595 high
+        assert_eq!(fit_zoom((842, 1000), A4, 90), Some(1.0));
+        assert_eq!(fit_zoom((1684, 2000), A4, 270), Some(2.0));
+        assert_eq!(fit_zoom((1000, 595), A4, 90), fit_zoom((595, 1000), A4, 0));
+    }
+
+    #[test]
+    fn fit_to_page_in_tiny_and_empty_windows() {
+        // Minimized or not yet laid out
+        assert_eq!(fit_zoom((0, 500), A4, 0), None);
+        assert_eq!(fit_zoom((500, 0), A4, 90), None);
+        assert_eq!(fit_zoom((500, 500), (0.0, 842.0), 0), None);
+        // A few pixels: the smallest zoom, not a page scaled to nothing
+        assert_eq!(fit_zoom((1, 1), A4, 0), Some(0.1));
+        assert_eq!(fit_zoom((10, 10), A4, 90), Some(0.1));
+        // A tiny page in a large window stops at the largest zoom
+        assert_eq!(fit_zoom((2000, 2000), (16.0, 16.0), 0), Some(10.0));
+    }
+
+    #[test]
+    fn fit_to_width_ignores_the_height() {
+        assert_eq!(fit_width_zoom(1190, A4, 0), Some(2.0));
+        assert_eq!(fit_width_zoom(595, A4, 180), Some(1.0));
+        // Turned by a quarter, the page height is its width
+        assert_eq!(fit_width_zoom(842, A4, 90), Some(1.0));
+        assert_eq!(fit_width_zoom(421, A4, 270), Some(0.5));
+    }
+
+    #[test]
+    fn fit_to_width_in_tiny_and_empty_windows() {
+        assert_eq!(fit_width_zoom(0, A4, 0), None);
+        assert_eq!(fit_width_zoom(800, (0.0, 842.0), 0), None);
+        assert_eq!(fit_width_zoom(800, (595.0, 0.0), 90), None);
+        assert_eq!(fit_width_zoom(1, A4, 0), Some(0.1));
+        assert_eq!(fit_width_zoom(3000, (100.0, 5.0), 90), Some(10.0));
+    }
+
+    #[test]
+    fn zoom_text_of_every_level() {
+        let texts: Vec<String> = App::ZOOM_LEVELS.iter().map(|&z| crate::statusbar::zoom_text(z)).collect();
+        assert_eq!(texts[0], "10 %");
+        assert_eq!(texts[1], "12,5 %");
+        assert_eq!(texts[3], "17,5 %");
+        assert_eq!(texts[6], "33 %");
+        assert_eq!(texts[13], "100 %");
+        assert_eq!(texts[texts.len() - 1], "1000 %");
+        // Each step shows in the status bar
+        let distinct: HashSet<&String> = texts.iter().collect();
+        assert_eq!(distinct.len(), texts.len());
+        assert_eq!(crate::statusbar::zoom_text(0.873), "87,3 %");
+        assert_eq!(crate::statusbar::zoom_text(1.234), "123 %");
+    }
+
+    fn folder_state(files: &[&str], index: usize) -> AppState {
+        AppState {
+            navigation_context: NavigationContext::FolderBrowsing,
+            folder_files: files.iter().map(|f| f.to_string()).collect(),
+            folder_file_index: index,
+            folder_cache_valid: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn navigation_in_a_folder() {
+        let files = ["a.jpg", "b.jpg", "c.jpg"];
+        assert_eq!(navigation_targets(&folder_state(&files, 0)), (false, true));
+        assert_eq!(navigation_targets(&folder_state(&files, 1)), (true, true));
+        assert_eq!(navigation_targets(&folder_state(&files, 2)), (true, false));
+        assert_eq!(navigation_targets(&folder_state(&["a.jpg"], 0)), (false, false));
+    }
+
+    #[test]
+    fn navigation_skips_failed_files() {
+        let mut state = folder_state(&["a.jpg", "b.jpg", "c.jpg", "d.jpg"], 1);
+        state.failed_files.insert("a.jpg".to_string());
+        assert_eq!(navigation_targets(&state), (false, true));
+        state.failed_files.insert("c.jpg".to_string());
+        assert_eq!(navigation_targets(&state), (false, true));
+        state.failed_files.insert("d.jpg".to_string());
+        assert_eq!(navigation_targets(&state), (false, false));
+    }
+
+    #[test]
+    fn navigation_before_the_folder_is_listed() {
+        // The first Back/Next lists the folder, so both are offered
+        let state = AppState { folder_cache_valid: false, ..folder_state(&["a.jpg"], 0) };
+        assert_eq!(navigation_targets(&state), (true, true));
+        // An index past a list that shrank keeps Back to the files left
+        assert_eq!(navigation_targets(&folder_state(&["a.jpg", "b.jpg"], 5)), (true, false));
+    }
+
+    #[test]
+    fn navigation_in_a_document() {
+        let paging = |current_page, total_pages| AppState {
+            navigation_context: NavigationContext::DocumentPaging,
+            current_page,
+            total_pages,
+            ..Default::default()
+        };
+        assert_eq!(navigation_targets(&paging(0, 3)), (false, true));
+        assert_eq!(navigation_targets(&paging(1, 3)), (true, true));
+        assert_eq!(navigation_targets(&paging(2, 3)), (true, false));
+        assert_eq!(navigation_targets(&paging(0, 1)), (false, false));
+        let disabled = AppState { navigation_context: NavigationContext::Disabled, ..paging(1, 3) };
+        assert_eq!(navigation_targets(&disabled), (false, false));
+    }
+
+    #[test]
+    fn same_image_format_by_extension() {
+        assert!(same_image_format("a.jpg", "b.JPEG"));
+        assert!(same_image_format("a.TIF", "b.tiff"));
+        assert!(same_image_format("a.png", "b.png"));
+        assert!(!same_image_format("a.png", "b.jpg"));
+        assert!(!same_image_format("a.webp", "b.png"));
+        // Without an extension there is no format to match
+        assert!(!same_image_format("a", "b"));
+    }
+
+    #[test]
+    fn export_copies_only_untransformed_images_of_the_same_format() {
+        use ExportMethod::*;
+        // (source, target, single image, transformed, source current) -> method
+        let cases = [
+            (Some("a.jpg"), "b.jpeg", true, false, true, Copy),
+            (Some("a.jpg"), "b.jpg", true, true, true, ReencodeSameFormat),
+            (Some("a.jpg"), "b.png", true, false, true, Encode),
+            (Some("a.jpg"), "b.png", true, true, true, Encode),
+            // Multi-page documents and PDFs are never copied as an image
+            (Some("a.tif"), "b.tif", false, false, true, Encode),
+            (Some("a.tif"), "b.tif", false, true, true, Encode),
+            // Changed since loading: the copy would not be what is shown
+            (Some("a.png"), "b.png", true, false, false, Encode),
+            (Some("a.png"), "b.png", true, true, false, Encode),
+            // Pasted images have no source file
+            (None, "b.png", true, false, true, Encode),
+        ];
+        for (source, target, single_image, transformed, source_current, method) in cases {
+            assert_eq!(
+                export_method(source, target, single_image, transformed, source_current),
+                method,
+                "{source:?} -> {target}, single {single_image}, transformed {transformed}, current {source_current}"
+            );
         }
     }
+
+    #[test]
+    fn past_source_at_100_percent_scaling() {
+        assert!(!past_source_resolution(0.5, 1.0));
+        assert!(!past_source_resolution(1.0, 1.0));
+        // Rounding of a fitted zoom
+        assert!(!past_source_resolution(1.004, 1.0));
+        assert!(past_source_resolution(1.1, 1.0));
+        assert!(past_source_resolution(10.0, 1.0));
+    }
+
+    #[test]
+    fn past_source_with_display_scaling() {
+        // 150 %: 100 % zoom already spreads a source pixel over 1.5 screen pixels
+        assert!(past_source_resolution(1.0, 1.5));
+        assert!(!past_source_resolution(0.67, 1.5));
+        assert!(past_source_resolution(0.7, 1.5));
+        // 200 %: 1:1 at 50 %
+        assert!(!past_source_resolution(0.5, 2.0));
+        assert!(past_source_resolution(0.6, 2.0));
+    }
 }
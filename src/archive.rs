@@ -0,0 +1,236 @@
+//! ZIP/CBZ archive loading
+//!
+//! Scanned case files sometimes arrive as a ZIP of numbered images. Every image entry
+//! becomes one page of a multi-page image document. Opening decompresses only the start
+//! of each entry to read the image size; the archive stays open and an entry is
+//! decompressed and decoded when its page is first shown (see `Document::get_wic_bitmap`),
+//! so opening stays fast and small even for large archives.
+
+use crate::cancel::CancelToken;
+use crate::document::{Document, PageData};
+use crate::utils::natural_sort_key;
+use std::cell::RefCell;
+use std::io::Read;
+use std::rc::Rc;
+use windows::core::*;
+
+/// Image extensions accepted inside an archive
+const ARCHIVE_IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp"];
+
+/// Largest uncompressed image entry. The sizes in the ZIP headers are not trusted: a
+/// crafted archive could claim or inflate to any size.
+const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+/// Start of an entry decompressed on opening, enough for the header of most images
+const HEADER_PREFIX: u64 = 256 * 1024;
+
+// HRESULTs used to report archive problems
+const E_INVALID_DATA: i32 = 0x8007000Du32 as i32;
+const E_ACCESSDENIED: i32 = 0x80070005u32 as i32;
+
+type Archive = zip::ZipArchive<std::fs::File>;
+
+pub struct ArchiveLoader;
+
+/// An image entry of an open archive, decompressed when its page is first shown
+pub struct ArchiveEntry {
+    archive: Rc<RefCell<Archive>>,
+    index: usize,
+    name: String,
+}
+
+impl ArchiveEntry {
+    /// The whole entry, at most MAX_ENTRY_SIZE bytes
+    pub fn read(&self) -> Result<Vec<u8>> {
+        decompress(&mut self.archive.borrow_mut(), self.index, &self.name, None)
+    }
+}
+
+impl ArchiveLoader {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Opens a ZIP/CBZ file and returns a document with one page per image entry.
+    /// `cancel` is checked and `progress` gets (entries done, entries) before the header
+    /// of every entry is read.
+    pub fn load(&self, path: &str, cancel: &CancelToken, mut progress: impl FnMut(usize, usize)) -> Result<Document> {
+        let file = std::fs::File::open(path).map_err(|e| archive_error(E_INVALID_DATA, &e.to_string()))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| archive_error(E_INVALID_DATA, &format!("Ungültiges ZIP-Archiv: {}", e)))?;
+
+        // Collect image entries (skip directories and macOS metadata)
+        let mut entries: Vec<(usize, String)> = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive
+                .by_index_raw(i)
+                .map_err(|e| archive_error(E_INVALID_DATA, &e.to_string()))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_string();
+            if name.starts_with("__MACOSX/") {
+                continue;
+            }
+            if is_archive_image(&name) {
+                entries.push((i, name));
+            }
+        }
+
+        if entries.is_empty() {
+            return Err(archive_error(E_INVALID_DATA, "Das Archiv enthält keine unterstützten Bilddateien."));
+        }
+
+        // Natural order (scan_2 before scan_10), folder name breaks ties
+        entries.sort_by_cached_key(|(_, name)| (natural_sort_key(name), name.clone()));
+
+        let total = entries.len();
+        let mut pages = Vec::with_capacity(total);
+        let archive = Rc::new(RefCell::new(archive));
+        for (index, name) in entries {
+            cancel.check()?;
+            progress(pages.len(), total);
+
+            // Only the header is parsed here - the entry is decompressed again on first view
+            let start = decompress(&mut archive.borrow_mut(), index, &name, Some(HEADER_PREFIX))?;
+            let header = match crate::wic::read_header_from_memory(&start) {
+                Ok(header) => header,
+                // A TIFF may keep its directory at the end
+                Err(_) if start.len() as u64 == HEADER_PREFIX => {
+                    crate::wic::read_header_from_memory(&decompress(&mut archive.borrow_mut(), index, &name, None)?)?
+                }
+                Err(e) => return Err(e),
+            };
+            let (width, height, source_format) = header;
+
+            pages.push(PageData {
+                width: width as f32,
+                height: height as f32,
+                source_format,
+                archive_entry: Some(ArchiveEntry { archive: archive.clone(), index, name }),
+                ..Default::default()
+            });
+        }
+
+        Ok(Document::new_multiframe_image(pages))
+    }
+}
+
+/// Decompresses entry `index`: only its first `prefix` bytes, or all of it up to
+/// MAX_ENTRY_SIZE
+fn decompress(archive: &mut Archive, index: usize, name: &str, prefix: Option<u64>) -> Result<Vec<u8>> {
+    let mut entry = match archive.by_index(index) {
+        Ok(e) => e,
+        Err(zip::result::ZipError::UnsupportedArchive(zip::result::ZipError::PASSWORD_REQUIRED)) => {
+            return Err(archive_error(
+                E_ACCESSDENIED,
+                "Kennwortgeschützte Archive werden nicht unterstützt.",
+            ));
+        }
+        Err(e) => return Err(archive_error(E_INVALID_DATA, &format!("{}: {}", name, e))),
+    };
+    let data = match prefix {
+        Some(len) => {
+            let mut data = Vec::new();
+            (&mut entry).take(len).read_to_end(&mut data).map(|_| data)
+        }
+        None => read_entry(&mut entry, MAX_ENTRY_SIZE),
+    };
+    data.map_err(|e| archive_error(E_INVALID_DATA, &format!("{}: {}", name, e)))
+}
+
+/// Decompresses one entry, failing once it grows beyond `limit` bytes
+fn read_entry(entry: &mut impl Read, limit: u64) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    entry.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Der Eintrag ist zu groß (Bilder in Archiven werden höchstens bis 256 MB geöffnet).",
+        ));
+    }
+    Ok(data)
+}
+
+fn is_archive_image(name: &str) -> bool {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| ARCHIVE_IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn archive_error(code: i32, message: &str) -> Error {
+    Error::new(HRESULT(code), HSTRING::from(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_entry_accepts_up_to_the_limit() {
+        let data = vec![7u8; 100];
+        assert_eq!(read_entry(&mut data.as_slice(), 100).unwrap(), data);
+    }
+
+    #[test]
+    fn read_entry_rejects_more_than_the_limit() {
+        let data = vec![7u8; 101];
+        let error = read_entry(&mut data.as_slice(), 100).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_entry_stops_reading_past_the_limit() {
+        // An endless stream, like a ZIP bomb, ends after limit + 1 bytes
+        let error = read_entry(&mut std::io::repeat(0), 1000).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    /// A ZIP file in the temp folder with deflated entries
+    fn zip_file(name: &str, entries: &[(&str, &[u8])]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("SimpliView-{}-{}.zip", name, std::process::id()));
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            std::io::Write::write_all(&mut writer, data).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn opening_decompresses_only_the_start_of_an_entry() {
+        let data: Vec<u8> = (0..HEADER_PREFIX as usize * 3).map(|i| (i % 251) as u8).collect();
+        let path = zip_file("ArchivePrefix", &[("scan_1.png", &data)]);
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let start = decompress(&mut archive, 0, "scan_1.png", Some(HEADER_PREFIX)).unwrap();
+        assert_eq!(start, data[..HEADER_PREFIX as usize]);
+        assert_eq!(decompress(&mut archive, 0, "scan_1.png", None).unwrap(), data);
+        drop(archive);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn entries_are_read_when_asked_for() {
+        let path = zip_file("ArchiveEntries", &[("a.jpg", b"first"), ("b.jpg", b"second")]);
+        let archive = Rc::new(RefCell::new(zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap()));
+        let second = ArchiveEntry { archive: archive.clone(), index: 1, name: "b.jpg".to_string() };
+        let first = ArchiveEntry { archive, index: 0, name: "a.jpg".to_string() };
+        // In any order, and again
+        assert_eq!(second.read().unwrap(), b"second");
+        assert_eq!(first.read().unwrap(), b"first");
+        assert_eq!(second.read().unwrap(), b"second");
+        drop((first, second));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn archive_images_by_extension() {
+        assert!(is_archive_image("Akte/scan_01.JPG"));
+        assert!(is_archive_image("seite.tiff"));
+        assert!(!is_archive_image("notizen.txt"));
+        assert!(!is_archive_image("ohne_endung"));
+    }
+}
@@ -0,0 +1,51 @@
+//! "Ränder automatisch zuschneiden": the content rectangle of a page with white margins
+//!
+//! Rows and columns are taken as margin from the edges inward while nearly all of their
+//! pixels are near-white, so specks of dust and scanner noise do not stop the search. The
+//! rectangle found is widened by a small safety margin, so text touching the edge of the
+//! content is not cut. Blank pages and pages without worth-while margins are not cropped.
+
+/// A pixel whose darkest channel is below this counts as content
+const WHITE_THRESHOLD: u8 = 220;
+/// Content pixels a row or column may have and still be margin, per thousand of its length
+const NOISE_PER_MILLE: usize = 3;
+/// Added around the content on each side, per thousand of the longer page side, at least
+/// `MIN_SAFETY_MARGIN` pixels
+const SAFETY_MARGIN_PER_MILLE: u32 = 10;
+const MIN_SAFETY_MARGIN: u32 = 4;
+/// Pages are cropped only where the margins take at least this share of width or height
+const MIN_CROPPED_SHARE: f32 = 0.05;
+
+/// Content rectangle [left, top, right, bottom] of 32bpp BGRA `pixels` (`stride` bytes per
+/// row), or `None` if the page is blank or has no margins worth cropping
+pub fn content_rect(pixels: &[u8], width: u32, height: u32, stride: u32) -> Option<[u32; 4]> {
+    let (w, h, stride) = (width as usize, height as usize, stride as usize);
+    if w == 0 || h == 0 || stride < w * 4 || pixels.len() < stride * (h - 1) + w * 4 {
+        return None;
+    }
+    let is_content = |x: usize, y: usize| {
+        let i = y * stride + x * 4;
+        let [b, g, r, a] = [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]];
+        // Transparent pixels are background, whatever their color
+        a >= 128 && b.min(g).min(r) < WHITE_THRESHOLD
+    };
+    let row_is_margin = |y: usize| (0..w).filter(|&x| is_content(x, y)).count() <= w * NOISE_PER_MILLE / 1000;
+
+    let top = (0..h).find(|&y| !row_is_margin(y))?;
+    let bottom = (top..h).rev().find(|&y| !row_is_margin(y))? + 1;
+    let rows = bottom - top;
+    let column_is_margin = |x: usize| (top..bottom).filter(|&y| is_content(x, y)).count() <= rows * NOISE_PER_MILLE / 1000;
+    let left = (0..w).find(|&x| !column_is_margin(x))?;
+    let right = (left..w).rev().find(|&x| !column_is_margin(x))? + 1;
+
+    let margin = (width.max(height) * SAFETY_MARGIN_PER_MILLE / 1000).max(MIN_SAFETY_MARGIN);
+    let rect = [
+        (left as u32).saturating_sub(margin),
+        (top as u32).saturating_sub(margin),
+        (right as u32 + margin).min(width),
+        (bottom as u32 + margin).min(height),
+    ];
+    let cropped_x = 1.0 - (rect[2] - rect[0]) as f32 / width as f32;
+    let cropped_y = 1.0 - (rect[3] - rect[1]) as f32 / height as f32;
+    (cropped_x >= MIN_CROPPED_SHARE || cropped_y >= MIN_CROPPED_SHARE).then_some(rect)
+}
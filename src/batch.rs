@@ -0,0 +1,439 @@
+//! Headless batch export and print of a folder
+//!
+//! `--export-folder <dir> --to <out> [--format png]` writes every page of every supported
+//! file as an image, `--print-folder <dir>` prints each file to the default printer as its
+//! own job. `--recursive` includes subfolders; export mirrors them below the output folder.
+//! No window is created: each file gets one line on stdout, and the exit code is 0 if all
+//! files succeeded, 1 if some failed and 2 for invalid arguments.
+//!
+//! Files are processed one at a time and each document is dropped before the next one is
+//! loaded. A file that cannot be loaded or written is reported and the batch goes on.
+//!
+//! Batches of the same kind run one after another: each takes a named mutex for its mode
+//! (`RunLock`), and a batch started while another one runs, e.g. by a second scheduled task,
+//! reports that it is queued and waits, so print jobs of two batches never interleave.
+//! Interactive starts take no lock and are never held up by a batch.
+
+use crate::{
+    archive::ArchiveLoader,
+    cancel::CancelToken,
+    capabilities::Capabilities,
+    document::Document,
+    page_transform::PageTransform,
+    pdf::PdfLoader,
+    print_job::PrintJob,
+    utils::natural_sort_key,
+    watermark::Watermark,
+    wic::WicLoader,
+};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Graphics::Gdi::{CreatedHDC, DeleteDC, HDC},
+        System::{
+            Console::{AttachConsole, ATTACH_PARENT_PROCESS},
+            Memory::GlobalFree,
+            Threading::{CreateMutexW, ReleaseMutex, WaitForSingleObject, INFINITE},
+        },
+        UI::Controls::Dialogs::*,
+    },
+};
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED_FILES: i32 = 1;
+const EXIT_INVALID_ARGUMENTS: i32 = 2;
+
+const EXPORT_FORMATS: [&str; 7] = ["png", "jpg", "jpeg", "bmp", "tif", "tiff", "webp"];
+
+enum Target {
+    Export { output: PathBuf, format: String },
+    Print,
+}
+
+struct Batch {
+    source: PathBuf,
+    target: Target,
+    recursive: bool,
+    watermark: Option<Watermark>,
+    wic_loader: WicLoader,
+    pdf_loader: PdfLoader,
+    archive_loader: ArchiveLoader,
+    /// Output names already written in this run (lowercase), to keep same-named sources apart
+    written: HashSet<PathBuf>,
+}
+
+/// Runs `--export-folder` / `--print-folder` if present and returns the exit code
+pub fn run(args: &[String]) -> Option<i32> {
+    if !args.iter().any(|a| a == "--export-folder" || a == "--print-folder") {
+        return None;
+    }
+    let value = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+    let export_folder = value("--export-folder");
+    let print_folder = value("--print-folder");
+
+    // Built as a GUI program: write to the console of a calling cmd/PowerShell, if any
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+
+    match prepare(export_folder, print_folder, value("--to"), value("--format"), args.iter().any(|a| a == "--recursive")) {
+        Ok(mut batch) => {
+            // Held until the batch is done; without it the batch runs unserialized
+            let _lock = RunLock::acquire(&batch.target);
+            Some(batch.run())
+        }
+        Err(message) => {
+            eprintln!("Fehler: {}", message);
+            Some(EXIT_INVALID_ARGUMENTS)
+        }
+    }
+}
+
+fn prepare(
+    export_folder: Option<String>,
+    print_folder: Option<String>,
+    output: Option<String>,
+    format: Option<String>,
+    recursive: bool,
+) -> std::result::Result<Batch, String> {
+    let capabilities = Capabilities::from_startup(false);
+    let (source, target) = match (export_folder, print_folder) {
+        (Some(_), Some(_)) => return Err("--export-folder und --print-folder können nicht kombiniert werden.".to_string()),
+        (Some(folder), None) => {
+            if !capabilities.export {
+                return Err("Export ist durch eine Richtlinie deaktiviert.".to_string());
+            }
+            let output = output.ok_or("--export-folder erwartet --to <Zielordner>.")?;
+            let format = format.unwrap_or_else(|| "png".to_string()).trim_start_matches('.').to_lowercase();
+            if !EXPORT_FORMATS.contains(&format.as_str()) {
+                return Err(format!("Unbekanntes Format \"{}\". Möglich: {}", format, EXPORT_FORMATS.join(", ")));
+            }
+            (folder, Target::Export { output: PathBuf::from(output), format })
+        }
+        (None, Some(folder)) => {
+            if !capabilities.print {
+                return Err("Drucken ist durch eine Richtlinie deaktiviert.".to_string());
+            }
+            (folder, Target::Print)
+        }
+        (None, None) => return Err("--export-folder bzw. --print-folder erwartet einen Ordner.".to_string()),
+    };
+
+    let source = PathBuf::from(source);
+    if !source.is_dir() {
+        return Err(format!("Der Ordner existiert nicht: {}", source.display()));
+    }
+    if let Target::Export { ref output, .. } = target {
+        std::fs::create_dir_all(output)
+            .map_err(|e| format!("Der Zielordner konnte nicht angelegt werden: {}\n{}", output.display(), e))?;
+    }
+
+    Ok(Batch {
+        source,
+        target,
+        recursive,
+        // A watermark policy applies to batch output as well
+        watermark: Watermark::from_startup(None, None, None),
+        wic_loader: WicLoader::new().map_err(|e| e.message().to_string())?,
+        pdf_loader: PdfLoader::new(),
+        archive_loader: ArchiveLoader::new(),
+        written: HashSet::new(),
+    })
+}
+
+impl Batch {
+    fn run(&mut self) -> i32 {
+        let mut files = Vec::new();
+        self.collect_files(&self.source.clone(), &mut files);
+        if files.is_empty() {
+            println!("Keine unterstützten Dateien in {}", self.source.display());
+            return EXIT_OK;
+        }
+
+        // Without a printer every file would fail the same way
+        let printer = match self.target {
+            Target::Print => match DefaultPrinter::open() {
+                Some(printer) => Some(printer),
+                None => {
+                    eprintln!("Fehler: Kein Standarddrucker verfügbar.");
+                    return EXIT_FAILED_FILES;
+                }
+            },
+            Target::Export { .. } => None,
+        };
+
+        let mut failed = 0;
+        for file in &files {
+            let path = file.to_string_lossy();
+            let result = match printer {
+                Some(ref printer) => self.print_file(&path, printer),
+                None => self.export_file(file),
+            };
+            match result {
+                Ok(pages) => println!("OK      {} ({} {})", path, pages, if pages == 1 { "Seite" } else { "Seiten" }),
+                Err(e) => {
+                    failed += 1;
+                    println!("FEHLER  {}: {}", path, crate::load_error::describe(&e));
+                }
+            }
+        }
+
+        println!(
+            "{} von {} Dateien erfolgreich, {} fehlgeschlagen",
+            files.len() - failed,
+            files.len(),
+            failed
+        );
+        if failed == 0 { EXIT_OK } else { EXIT_FAILED_FILES }
+    }
+
+    /// Supported files in natural order, subfolders after the files of a folder
+    fn collect_files(&self, folder: &Path, files: &mut Vec<PathBuf>) {
+        let entries = match std::fs::read_dir(folder) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("FEHLER  {}: {}", folder.display(), e);
+                return;
+            }
+        };
+        let mut found = Vec::new();
+        let mut subfolders = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                // Never walk into our own output
+                let is_output = matches!(self.target, Target::Export { ref output, .. } if same_folder(&path, output));
+                if self.recursive && !is_output {
+                    subfolders.push(path);
+                }
+            } else if is_supported(&path) {
+                found.push(path);
+            }
+        }
+        found.sort_by_cached_key(|p| natural_sort_key(&p.to_string_lossy()));
+        subfolders.sort_by_cached_key(|p| natural_sort_key(&p.to_string_lossy()));
+        files.extend(found);
+        for subfolder in subfolders {
+            self.collect_files(&subfolder, files);
+        }
+    }
+
+    fn load(&self, path: &str) -> Result<Document> {
+        load(&self.wic_loader, &self.pdf_loader, &self.archive_loader, path)
+    }
+
+    /// Writes every page of `file`, returns the page count
+    fn export_file(&mut self, file: &Path) -> Result<usize> {
+        let Target::Export { ref output, ref format } = self.target else {
+            unreachable!()
+        };
+        let doc = self.load(&file.to_string_lossy())?;
+
+        // Mirror subfolders of the source below the output folder
+        let relative_dir = file.parent().and_then(|p| p.strip_prefix(&self.source).ok()).unwrap_or(Path::new(""));
+        let folder = output.join(relative_dir);
+        std::fs::create_dir_all(&folder).map_err(io_error)?;
+
+        // scan.pdf and scan.tif both become scan_001.png; the second one keeps its extension
+        let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let mut base = folder.join(&stem);
+        if !self.written.insert(key(&base)) {
+            base = folder.join(format!("{}_{}", stem, extension(file)));
+            self.written.insert(key(&base));
+        }
+
+        for page in 0..doc.page_count() {
+            let target = format!("{}_{:03}.{}", base.to_string_lossy(), page + 1, format);
+            self.wic_loader.save(&doc, &target, page, &PageTransform::default(), self.watermark.as_ref(), false)?;
+        }
+        Ok(doc.page_count())
+    }
+
+    /// Prints `path` as one job, returns the page count
+    fn print_file(&self, path: &str, printer: &DefaultPrinter) -> Result<usize> {
+        let doc = self.load(path)?;
+        let name = Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or(path);
+        let job = PrintJob::start(printer.hdc, name, None)
+            .ok_or_else(|| Error::new(E_FAIL, HSTRING::from("Druckauftrag konnte nicht gestartet werden.")))?;
+
+        let pages = doc.page_count();
+        for page in 0..pages {
+            let printed = self
+                .wic_loader
+                .get_bitmap_for_clipboard(&doc, page, &PageTransform::default(), None)
+                .and_then(|bitmap| {
+                    if job.print_page(&bitmap, self.watermark.as_ref(), page, pages) {
+                        Ok(())
+                    } else {
+                        Err(Error::new(E_FAIL, HSTRING::from(format!("Seite {} konnte nicht gedruckt werden.", page + 1))))
+                    }
+                });
+            if let Err(e) = printed {
+                job.finish();
+                return Err(e);
+            }
+        }
+        job.finish();
+        Ok(pages)
+    }
+}
+
+/// Named mutex of a batch mode, owned while a batch of that mode runs
+struct RunLock(HANDLE);
+
+impl RunLock {
+    /// Waits until no other batch of the same mode runs, then owns the mutex. `None` if
+    /// the mutex could not be created.
+    fn acquire(target: &Target) -> Option<Self> {
+        let (name, kind) = Self::mode(target);
+        Self::acquire_named(name, INFINITE, || println!("In der Warteschlange: ein anderer {} läuft noch ...", kind))
+    }
+
+    /// Mutex name and description of the mode of `target`
+    fn mode(target: &Target) -> (PCWSTR, &'static str) {
+        match target {
+            Target::Print => (w!("Local\\SimpliView-PrintFolder"), "Druckauftrag (--print-folder)"),
+            Target::Export { .. } => (w!("Local\\SimpliView-ExportFolder"), "Export (--export-folder)"),
+        }
+    }
+
+    /// Owns the mutex `name`, waiting up to `timeout` ms while another batch holds it;
+    /// `queued` is called before that wait. `None` if the mutex could not be created or
+    /// the wait timed out.
+    fn acquire_named(name: PCWSTR, timeout: u32, queued: impl FnOnce()) -> Option<Self> {
+        unsafe {
+            let mutex = CreateMutexW(None, false, name).ok()?;
+            let mut result = WaitForSingleObject(mutex, 0);
+            if result == WAIT_TIMEOUT {
+                queued();
+                result = WaitForSingleObject(mutex, timeout);
+            }
+            // WAIT_ABANDONED: the previous batch ended without releasing it, the mutex is ours
+            if result == WAIT_OBJECT_0 || result == WAIT_ABANDONED {
+                Some(Self(mutex))
+            } else {
+                let _ = CloseHandle(mutex);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ReleaseMutex(self.0);
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+/// DC of the default printer, without showing the print dialog
+struct DefaultPrinter {
+    hdc: HDC,
+}
+
+impl DefaultPrinter {
+    fn open() -> Option<Self> {
+        unsafe {
+            let mut pd: PRINTDLGW = std::mem::zeroed();
+            pd.lStructSize = std::mem::size_of::<PRINTDLGW>() as u32;
+            pd.Flags = PD_RETURNDEFAULT | PD_RETURNDC;
+            let ok = PrintDlgW(&mut pd).as_bool();
+            if !pd.hDevMode.is_invalid() {
+                let _ = GlobalFree(pd.hDevMode);
+            }
+            if !pd.hDevNames.is_invalid() {
+                let _ = GlobalFree(pd.hDevNames);
+            }
+            (ok && !pd.hDC.is_invalid()).then_some(Self { hdc: pd.hDC })
+        }
+    }
+}
+
+impl Drop for DefaultPrinter {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteDC(CreatedHDC(self.hdc.0));
+        }
+    }
+}
+
+/// Loads `path` without a window: no progress, no password prompt (also `--render-test`)
+pub fn load(wic_loader: &WicLoader, pdf_loader: &PdfLoader, archive_loader: &ArchiveLoader, path: &str) -> Result<Document> {
+    let cancel = CancelToken::new(HWND(0));
+    let ext = extension(Path::new(path));
+    match ext.as_str() {
+        "pdf" => pdf_loader.load(path, None, &cancel, |_, _| {}).map_err(|e| {
+            if pdf_loader.needs_password() {
+                Error::new(e.code(), HSTRING::from("Die PDF-Datei ist passwortgeschützt."))
+            } else {
+                e
+            }
+        }),
+        "zip" | "cbz" => archive_loader.load(path, &cancel, |_, _| {}),
+        "dcm" => crate::dicom::load(path, &cancel),
+        _ => wic_loader.load(path, &cancel),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase()
+}
+
+fn is_supported(path: &Path) -> bool {
+    crate::app::FOLDER_EXTENSIONS.contains(&extension(path).as_str())
+}
+
+fn same_folder(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn key(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().to_lowercase())
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::new(E_FAIL, HSTRING::from(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tries the lock of `target`'s mode on another thread (a mutex is reentrant for the
+    /// thread that owns it) without waiting: whether it was queued and whether it got it
+    fn try_on_other_thread(print: bool) -> (bool, bool) {
+        std::thread::spawn(move || {
+            let target = if print { Target::Print } else { Target::Export { output: PathBuf::new(), format: String::new() } };
+            let (name, _) = RunLock::mode(&target);
+            let mut queued = false;
+            let lock = RunLock::acquire_named(name, 0, || queued = true);
+            (queued, lock.is_some())
+        })
+        .join()
+        .unwrap()
+    }
+
+    #[test]
+    fn second_run_of_a_mode_is_refused_while_one_is_active() {
+        let print = RunLock::acquire(&Target::Print).expect("print lock");
+        assert_eq!(try_on_other_thread(true), (true, false));
+        // The other mode is not held up
+        assert_eq!(try_on_other_thread(false), (false, true));
+        drop(print);
+        assert_eq!(try_on_other_thread(true), (false, true));
+
+        let export = RunLock::acquire(&Target::Export { output: PathBuf::new(), format: String::new() }).expect("export lock");
+        assert_eq!(try_on_other_thread(false), (true, false));
+        assert_eq!(try_on_other_thread(true), (false, true));
+        drop(export);
+        assert_eq!(try_on_other_thread(false), (false, true));
+    }
+}
@@ -0,0 +1,231 @@
+//! "Ordner-Stapelverarbeitung > Alle drehen": rotates every image of the current folder
+//!
+//! For days of scans that a misconfigured scanner turned by the same angle. The files of
+//! the Back/Next list matching the filter are loaded one at a time on a worker thread,
+//! rotated and written back in their own format through a temporary file
+//! (`WicLoader::save`), over the original or into a target folder. The decoded pixels are
+//! re-encoded, so JPEGs are compressed once more and metadata is not carried over; true
+//! lossless JPEG rotation is not done yet. PDFs, multi-page files and formats Windows
+//! cannot write are skipped with a note, and a file that fails does not stop the rest.
+//! The status bar shows the progress and Esc stops after the current file. The summary
+//! comes back with WM_APP_BATCH_ROTATE.
+
+use crate::cancel::CancelToken;
+use crate::page_transform::PageTransform;
+use crate::wic::WicLoader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED},
+        UI::WindowsAndMessaging::*,
+    },
+};
+
+/// Posted to the main window; lparam owns a `Box<RotateReport>` (see `take_result`)
+pub const WM_APP_BATCH_ROTATE: u32 = WM_APP + 13;
+
+/// Formats written back as they are (see `WicLoader::save`)
+const WRITABLE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp"];
+/// Files listed per section of the summary
+const MAX_LISTED: usize = 20;
+
+pub struct RotateJob {
+    /// Folder of the Back/Next list, subfolders are mirrored below `target`
+    pub folder: String,
+    /// Files matching the filter (see `matches_filter`)
+    pub files: Vec<String>,
+    /// Clockwise degrees
+    pub rotation: i32,
+    /// `None` overwrites the originals
+    pub target: Option<String>,
+}
+
+pub struct RotateReport {
+    pub folder: String,
+    pub target: Option<String>,
+    /// Files of the job
+    pub total: usize,
+    /// Source paths that were rotated
+    pub rotated: Vec<String>,
+    /// (path, reason)
+    pub skipped: Vec<(String, String)>,
+    /// (path, error message)
+    pub failed: Vec<(String, String)>,
+    pub cancelled: bool,
+}
+
+impl RotateReport {
+    /// Text for the summary dialog and the clipboard
+    pub fn report(&self) -> String {
+        let mut text = format!("Alle drehen\n\nOrdner: {}", self.folder);
+        if let Some(ref target) = self.target {
+            text.push_str(&format!("\nZielordner: {}", target));
+        }
+        text.push_str(&format!("\n{} von {} Dateien gedreht.", self.rotated.len(), self.total));
+        for (title, entries) in [("Übersprungen", &self.skipped), ("Fehler", &self.failed)] {
+            if entries.is_empty() {
+                continue;
+            }
+            text.push_str(&format!("\n\n{} ({}):", title, entries.len()));
+            for (path, reason) in entries.iter().take(MAX_LISTED) {
+                let name = Path::new(path).file_name().map_or(path.clone(), |n| n.to_string_lossy().to_string());
+                text.push_str(&format!("\n{}: {}", name, reason));
+            }
+            if entries.len() > MAX_LISTED {
+                text.push_str(&format!("\n... und {} weitere", entries.len() - MAX_LISTED));
+            }
+        }
+        if self.cancelled {
+            let done = self.rotated.len() + self.skipped.len() + self.failed.len();
+            text.push_str(&format!("\n\nAbgebrochen nach {} von {} Dateien.", done, self.total));
+        }
+        text
+    }
+}
+
+/// Whether the file name `name` matches `filter`: patterns separated by ";" or ",", with
+/// `*` and `?` as wildcards. A pattern without wildcards matches names containing it, an
+/// empty filter matches everything. Case is ignored.
+pub fn matches_filter(filter: &str, name: &str) -> bool {
+    let name = name.to_lowercase();
+    let mut patterns = filter.split([';', ',']).map(str::trim).filter(|p| !p.is_empty()).peekable();
+    if patterns.peek().is_none() {
+        return true;
+    }
+    patterns.any(|pattern| {
+        let pattern = pattern.to_lowercase();
+        if pattern.contains(['*', '?']) {
+            wildcard_match(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+        } else {
+            name.contains(&pattern)
+        }
+    })
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name position it currently stands for
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the `*` swallow one more character
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Rotates the files of `job` on a worker thread until done or `cancel` is set and posts
+/// the report to `hwnd`. False if the thread could not be started.
+pub fn start(hwnd: HWND, job: RotateJob, cancel: Arc<AtomicBool>) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView Batch Rotate".to_string())
+        .spawn(move || {
+            let hwnd = HWND(hwnd_raw);
+            // WIC needs COM on this thread
+            let com = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+            let report = run(hwnd, job, &cancel);
+            if com {
+                unsafe { CoUninitialize() };
+            }
+            crate::statusbar::post_progress(hwnd, None);
+            let result = Box::into_raw(Box::new(report));
+            if !unsafe { PostMessageW(hwnd, WM_APP_BATCH_ROTATE, WPARAM(0), LPARAM(result as isize)) }.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
+            }
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the report posted with WM_APP_BATCH_ROTATE
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_BATCH_ROTATE message and be taken only once.
+pub unsafe fn take_result(lparam: LPARAM) -> RotateReport {
+    *Box::from_raw(lparam.0 as *mut RotateReport)
+}
+
+enum Outcome {
+    Rotated,
+    Skipped(&'static str),
+}
+
+fn run(hwnd: HWND, job: RotateJob, cancel: &AtomicBool) -> RotateReport {
+    let mut report = RotateReport {
+        folder: job.folder.clone(),
+        target: job.target.clone(),
+        total: job.files.len(),
+        rotated: Vec::new(),
+        skipped: Vec::new(),
+        failed: Vec::new(),
+        cancelled: false,
+    };
+
+    let loader = match WicLoader::new() {
+        Ok(loader) => loader,
+        Err(e) => {
+            report.failed.push((job.folder.clone(), e.message().to_string()));
+            return report;
+        }
+    };
+    for (i, path) in job.files.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+        crate::statusbar::post_progress(hwnd, Some((i, job.files.len())));
+        match rotate_file(&loader, path, &job) {
+            Ok(Outcome::Rotated) => report.rotated.push(path.clone()),
+            Ok(Outcome::Skipped(reason)) => report.skipped.push((path.clone(), reason.to_string())),
+            Err(e) => report.failed.push((path.clone(), crate::load_error::describe(&e))),
+        }
+    }
+    report
+}
+
+fn rotate_file(loader: &WicLoader, path: &str, job: &RotateJob) -> Result<Outcome> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    if extension == "pdf" {
+        return Ok(Outcome::Skipped("PDF-Dateien werden nicht gedreht"));
+    }
+    if !WRITABLE_EXTENSIONS.contains(&extension.as_str()) {
+        return Ok(Outcome::Skipped("Dieses Format kann nicht gespeichert werden"));
+    }
+
+    let doc = loader.load(path, &CancelToken::new(HWND(0)))?;
+    if doc.page_count() > 1 {
+        return Ok(Outcome::Skipped("Mehrseitige Dateien werden nicht gedreht"));
+    }
+    let target = match job.target {
+        Some(ref folder) => target_path(Path::new(folder), Path::new(&job.folder), Path::new(path))?,
+        None => path.to_string(),
+    };
+    // WebP is written lossless so the rotation adds no further loss
+    loader.save(&doc, &target, 0, &PageTransform::rotated(job.rotation), None, true)?;
+    Ok(Outcome::Rotated)
+}
+
+/// Path below `target` for `file`, mirroring its subfolder of `folder`
+fn target_path(target: &Path, folder: &Path, file: &Path) -> Result<String> {
+    let relative = file.strip_prefix(folder).map(Path::to_path_buf).unwrap_or_else(|_| PathBuf::from(file.file_name().unwrap_or_default()));
+    let path = target.join(relative);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| Error::new(E_FAIL, HSTRING::from(e.to_string())))?;
+    }
+    Ok(path.to_string_lossy().to_string())
+}
@@ -0,0 +1,98 @@
+//! Blinkvergleich: holding B shows a comparison page in place of the current page
+//!
+//! QA flips between an original and a corrected scan to spot what changed. While B is held
+//! the view shows the comparison page with the zoom and position of the current page, so
+//! changes stand out as movement. Umschalt+B marks the comparison page; without a mark the
+//! next page is used, on the last page the previous one.
+//!
+//! With "Differenz beim Blinkvergleich" the view shows the per-pixel difference instead:
+//! unchanged areas turn black, changes light up. Both pages are scaled to at most
+//! `MAX_SIZE` pixels and compared on a worker thread; the result comes back with
+//! WM_APP_DIFFERENCE and is kept until another pair is compared.
+
+use crate::wic::ClipboardBitmapData;
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+pub const WM_APP_DIFFERENCE: u32 = WM_APP + 8;
+/// Longest side of the pages compared for the difference
+pub const MAX_SIZE: u32 = 2048;
+
+/// The two pages of a comparison, each with its rotation
+#[derive(Clone, PartialEq, Eq)]
+pub struct Pair {
+    pub path: Option<String>,
+    pub page: usize,
+    pub partner: usize,
+    pub rotations: (i32, i32),
+}
+
+/// Difference image of a pair: top-down PBGRA, opaque
+pub struct Difference {
+    pub pair: Pair,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Comparison page for `page`: the marked page if there is one, else a neighbor
+pub fn partner_page(page: usize, marked: Option<usize>, total_pages: usize) -> Option<usize> {
+    match marked {
+        Some(marked) if marked != page && marked < total_pages => Some(marked),
+        _ if page + 1 < total_pages => Some(page + 1),
+        _ => page.checked_sub(1),
+    }
+}
+
+/// Computes the difference of two thumbnails (see `WicLoader::get_thumbnail`) on a worker
+/// thread and posts it to `hwnd`. False if the thread could not be started.
+pub fn start(hwnd: HWND, pair: Pair, page: ClipboardBitmapData, partner: ClipboardBitmapData) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView Difference".to_string())
+        .spawn(move || {
+            let result = Box::into_raw(Box::new(Difference {
+                width: page.width,
+                height: page.height,
+                pixels: difference(&page, &partner),
+                pair,
+            }));
+            let posted = unsafe { PostMessageW(HWND(hwnd_raw), WM_APP_DIFFERENCE, WPARAM(0), LPARAM(result as isize)) };
+            if !posted.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
+            }
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the result posted with WM_APP_DIFFERENCE
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_DIFFERENCE message and be taken only once.
+pub unsafe fn take_result(lparam: LPARAM) -> Difference {
+    *Box::from_raw(lparam.0 as *mut Difference)
+}
+
+/// |a - b| per color channel on the pixel grid of `a`; `b` is sampled to the same size.
+/// The thumbnails are bottom-up DIB rows, the result is top-down.
+fn difference(a: &ClipboardBitmapData, b: &ClipboardBitmapData) -> Vec<u8> {
+    let (width, height) = (a.width as usize, a.height as usize);
+    let (b_width, b_height) = (b.width as usize, b.height as usize);
+    let mut pixels = vec![0u8; width * height * 4];
+    if b_width == 0 || b_height == 0 {
+        return pixels;
+    }
+    for y in 0..height {
+        let a_row = (height - 1 - y) * width * 4;
+        let b_row = (b_height - 1 - y * b_height / height) * b_width * 4;
+        for x in 0..width {
+            let a_pixel = a_row + x * 4;
+            let b_pixel = b_row + x * b_width / width * 4;
+            let out = (y * width + x) * 4;
+            for channel in 0..3 {
+                pixels[out + channel] = a.data[a_pixel + channel].abs_diff(b.data[b_pixel + channel]);
+            }
+            pixels[out + 3] = 255;
+        }
+    }
+    pixels
+}
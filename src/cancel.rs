@@ -0,0 +1,95 @@
+//! Cancelable document loading
+//!
+//! Loaders call `CancelToken::check` between expensive steps (TIFF frames, PDF pages,
+//! archive entries) and return `ERROR_CANCELLED` once the user aborted, dropping the
+//! pages decoded so far. Loading runs on the UI thread, so `check` also looks at the
+//! message queue: Esc aborts the load, and a pending Ctrl+O or window close (WM_CLOSE,
+//! the close button or "Fenster schließen" on the taskbar) aborts it as well (those
+//! messages stay queued and run once the load has returned).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::HWND,
+        UI::{
+            Input::KeyboardAndMouse::{GetAsyncKeyState, VIRTUAL_KEY, VK_CONTROL, VK_ESCAPE, VK_O},
+            WindowsAndMessaging::*,
+        },
+    },
+};
+
+/// HRESULT_FROM_WIN32(ERROR_CANCELLED) - no error message is shown for it
+pub const E_CANCELLED: HRESULT = HRESULT(0x800704C7u32 as i32);
+
+#[derive(Clone)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    hwnd: HWND,
+}
+
+impl CancelToken {
+    /// Token for a load started from the window `hwnd`
+    pub fn new(hwnd: HWND) -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            hwnd,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns `Err(E_CANCELLED)` if the load was aborted
+    pub fn check(&self) -> Result<()> {
+        if !self.is_cancelled() && cancel_requested(self.hwnd) {
+            self.cancel();
+        }
+        if self.is_cancelled() {
+            Err(Error::from(E_CANCELLED))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Looks for Esc, Ctrl+O or a close request
+fn cancel_requested(hwnd: HWND) -> bool {
+    unsafe {
+        let mut msg = MSG::default();
+        // Only the first queued key can be looked at without taking the keys typed ahead
+        // out of the queue. An Esc behind them is seen as held down, as long as the window
+        // is in front (the key state is that of the whole desktop).
+        if PeekMessageW(&mut msg, None, WM_KEYDOWN, WM_KEYDOWN, PM_NOREMOVE).as_bool() {
+            match VIRTUAL_KEY(msg.wParam.0 as u16) {
+                // Esc only cancels, so it is taken out
+                VK_ESCAPE => {
+                    let _ = PeekMessageW(&mut msg, None, WM_KEYDOWN, WM_KEYDOWN, PM_REMOVE);
+                    return true;
+                }
+                // Ctrl+O stays queued and opens the file dialog after the load
+                VK_O if GetAsyncKeyState(VK_CONTROL.0 as i32) < 0 => return true,
+                _ => {}
+            }
+        }
+        if GetForegroundWindow() == GetAncestor(hwnd, GA_ROOT) && GetAsyncKeyState(VK_ESCAPE.0 as i32) < 0 {
+            return true;
+        }
+
+        if PeekMessageW(&mut msg, hwnd, WM_CLOSE, WM_CLOSE, PM_NOREMOVE).as_bool() {
+            return true;
+        }
+        // "Fenster schließen" on the taskbar posts SC_CLOSE, the close button is a
+        // non-client click that DefWindowProc turns into SC_CLOSE once it is handled
+        if PeekMessageW(&mut msg, hwnd, WM_SYSCOMMAND, WM_SYSCOMMAND, PM_NOREMOVE).as_bool() && (msg.wParam.0 & 0xFFF0) as u32 == SC_CLOSE {
+            return true;
+        }
+        PeekMessageW(&mut msg, hwnd, WM_NCLBUTTONDOWN, WM_NCLBUTTONDOWN, PM_NOREMOVE).as_bool() && msg.wParam.0 as u32 == HTCLOSE
+    }
+}
@@ -0,0 +1,151 @@
+//! View-only mode
+//!
+//! Some deployments must prevent documents from leaving the viewer. In view-only mode
+//! (`--viewonly` or the `ViewOnly` policy value) Export, Print and Copy to clipboard
+//! are hard-disabled, and so are OCR and the folder search, which keep document text:
+//! the buttons stay greyed out, the menu items are removed and the commands are dropped
+//! in `App::handle_command` / `App::handle_accelerator`.
+
+use crate::menu::*;
+use crate::toolbar::{ToolbarCommand, ID_EXPORT, ID_PRINT};
+use windows::core::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+const POLICY_VIEW_ONLY: PCWSTR = w!("ViewOnly");
+/// Policy value; non-zero rejects --password / --password-stdin in view-only sessions
+const POLICY_NO_PASSWORD_HANDOFF: PCWSTR = w!("NoPasswordHandoff");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub export: bool,
+    pub print: bool,
+    pub clipboard: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            export: true,
+            print: true,
+            clipboard: true,
+        }
+    }
+}
+
+impl Capabilities {
+    pub fn view_only() -> Self {
+        Self {
+            export: false,
+            print: false,
+            clipboard: false,
+        }
+    }
+
+    /// Capabilities for this session from the command-line flag and the registry policy
+    pub fn from_startup(view_only_flag: bool) -> Self {
+        if view_only_flag || view_only_policy() {
+            Self::view_only()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Whether a PDF password may be handed over on the command line
+    pub fn allows_password_handoff(&self) -> bool {
+        *self != Self::view_only() || crate::policy::read_dword(POLICY_NO_PASSWORD_HANDOFF).unwrap_or(0) == 0
+    }
+
+    /// Whether a toolbar command may run
+    pub fn allows(&self, cmd: ToolbarCommand) -> bool {
+        match cmd {
+            ToolbarCommand::Export => self.export,
+            ToolbarCommand::Print => self.print,
+            _ => true,
+        }
+    }
+
+    /// Whether a menu or toolbar command (WM_COMMAND id) may run
+    pub fn allows_command(&self, id: u16) -> bool {
+        match u32::from(id) {
+            IDM_EXPORT | IDM_CONTACT_SHEET | IDM_EXTRACT_PAGES | IDM_BATCH_ROTATE | IDM_OCR | IDM_FOLDER_SEARCH => self.export,
+            IDM_PRINT => self.print,
+            IDM_COPY_PAGE_FILES => self.export && self.clipboard,
+            IDM_PRINT_PDF => self.print && self.export,
+            _ if id == ID_EXPORT => self.export,
+            _ if id == ID_PRINT => self.print,
+            _ => true,
+        }
+    }
+
+    /// Whether a Ctrl+`key` shortcut may run
+    pub fn allows_shortcut(&self, key: VIRTUAL_KEY) -> bool {
+        match key {
+            VK_E => self.export,
+            VK_C => self.clipboard,
+            VK_P => self.print,
+            _ => true,
+        }
+    }
+}
+
+fn view_only_policy() -> bool {
+    crate::policy::read_dword(POLICY_VIEW_ONLY).is_some_and(|v| v != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT_COMMANDS: [u32; 6] = [IDM_EXPORT, IDM_CONTACT_SHEET, IDM_EXTRACT_PAGES, IDM_BATCH_ROTATE, IDM_OCR, IDM_FOLDER_SEARCH];
+
+    fn allows(capabilities: Capabilities, id: u32) -> bool {
+        capabilities.allows_command(id as u16)
+    }
+
+    #[test]
+    fn everything_runs_by_default() {
+        let all = Capabilities::default();
+        for id in EXPORT_COMMANDS.into_iter().chain([IDM_PRINT, IDM_COPY_PAGE_FILES, IDM_PRINT_PDF]) {
+            assert!(allows(all, id), "{id}");
+        }
+        assert!(all.allows_command(ID_EXPORT) && all.allows_command(ID_PRINT));
+        assert!([VK_E, VK_C, VK_P].into_iter().all(|key| all.allows_shortcut(key)));
+        assert!(all.allows(ToolbarCommand::Export) && all.allows(ToolbarCommand::Print));
+    }
+
+    #[test]
+    fn view_only_drops_every_way_out() {
+        let view_only = Capabilities::view_only();
+        for id in EXPORT_COMMANDS.into_iter().chain([IDM_PRINT, IDM_COPY_PAGE_FILES, IDM_PRINT_PDF]) {
+            assert!(!allows(view_only, id), "{id}");
+        }
+        assert!(!view_only.allows_command(ID_EXPORT) && !view_only.allows_command(ID_PRINT));
+        assert!([VK_E, VK_C, VK_P].into_iter().all(|key| !view_only.allows_shortcut(key)));
+        assert!(!view_only.allows(ToolbarCommand::Export) && !view_only.allows(ToolbarCommand::Print));
+    }
+
+    #[test]
+    fn view_commands_always_run() {
+        let view_only = Capabilities::view_only();
+        for id in [IDM_ROTATE_LEFT, IDM_FIT_TO_PAGE, IDM_GO_TO_PAGE, IDM_PROPERTIES, IDM_UNDO_VIEW] {
+            assert!(allows(view_only, id), "{id}");
+        }
+        assert!([VK_O, VK_G, VK_Z].into_iter().all(|key| view_only.allows_shortcut(key)));
+        assert!(view_only.allows(ToolbarCommand::Open) && view_only.allows(ToolbarCommand::RotateLeft));
+    }
+
+    #[test]
+    fn commands_needing_two_capabilities_need_both() {
+        let no_clipboard = Capabilities { clipboard: false, ..Default::default() };
+        assert!(!allows(no_clipboard, IDM_COPY_PAGE_FILES));
+        assert!(allows(no_clipboard, IDM_EXPORT) && !no_clipboard.allows_shortcut(VK_C));
+
+        let no_export = Capabilities { export: false, ..Default::default() };
+        assert!(!allows(no_export, IDM_PRINT_PDF) && !allows(no_export, IDM_COPY_PAGE_FILES));
+        assert!(allows(no_export, IDM_PRINT) && no_export.allows_shortcut(VK_P));
+
+        let no_print = Capabilities { print: false, ..Default::default() };
+        assert!(!allows(no_print, IDM_PRINT_PDF) && !no_print.allows_command(ID_PRINT));
+        assert!(allows(no_print, IDM_EXPORT) && no_print.allows_shortcut(VK_E));
+    }
+}
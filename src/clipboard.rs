@@ -0,0 +1,480 @@
+//! Paste from the clipboard (Ctrl+V), plus plain text for error details, a file list
+//! for "Seiten als Dateien kopieren" and the page image for Ctrl+C
+//!
+//! Screenshots can be checked without saving them first. A copied file (CF_HDROP) is
+//! opened like any other file; image data (PNG, CF_DIBV5, CF_DIB) becomes an untitled
+//! document. DIBs are converted to top-down premultiplied BGRA here because WIC's BMP
+//! decoder does not understand clipboard DIBs (no file header, BITMAPV5 alpha masks).
+//!
+//! A copied page is put on the clipboard in both DIB formats, so Windows does not
+//! synthesize one from the other (which turned pages upside down or black in some older
+//! programs): CF_DIBV5 with explicit BGRA masks keeps the alpha, CF_DIB is opaque with
+//! transparent parts on white. Both are bottom-up; 32bpp rows need no padding.
+
+use crate::wic::ClipboardBitmapData;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        System::{DataExchange::*, Memory::*, Ole::{CF_DIB, CF_DIBV5, CF_HDROP, CF_UNICODETEXT}},
+        UI::Shell::{DragQueryFileW, DROPFILES, HDROP},
+    },
+};
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BITMAPINFOHEADER_SIZE: usize = 40;
+const BITMAPV5HEADER_SIZE: usize = 124;
+/// bV5CSType 'sRGB'
+const LCS_SRGB: u32 = 0x7352_4742;
+/// bV5Intent LCS_GM_IMAGES
+const LCS_GM_IMAGES: u32 = 4;
+
+pub enum ClipboardContent {
+    /// First file of a copied file list
+    File(String),
+    /// Encoded PNG
+    Png(Vec<u8>),
+    /// Top-down 32bpp premultiplied BGRA
+    Pixels { width: u32, height: u32, data: Vec<u8> },
+}
+
+/// Reads the best supported format from the clipboard
+pub fn read(hwnd: HWND) -> Result<Option<ClipboardContent>> {
+    unsafe {
+        if !OpenClipboard(hwnd).as_bool() {
+            return Err(Error::from_win32());
+        }
+        let content = read_open_clipboard();
+        let _ = CloseClipboard();
+        content
+    }
+}
+
+/// Replaces the clipboard content with text (CF_UNICODETEXT)
+pub fn write_text(hwnd: HWND, text: &str) -> Result<()> {
+    let wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    let size = wide.len() * std::mem::size_of::<u16>();
+    unsafe {
+        if !OpenClipboard(hwnd).as_bool() {
+            return Err(Error::from_win32());
+        }
+        let result = (|| {
+            EmptyClipboard().ok()?;
+            let hglobal = GlobalAlloc(GMEM_MOVEABLE, size)?;
+            let ptr = GlobalLock(hglobal) as *mut u16;
+            if ptr.is_null() {
+                let _ = GlobalFree(hglobal);
+                return Err(Error::from_win32());
+            }
+            std::ptr::copy_nonoverlapping(wide.as_ptr(), ptr, wide.len());
+            let _ = GlobalUnlock(hglobal);
+            // The clipboard owns the memory once SetClipboardData succeeds
+            if let Err(e) = SetClipboardData(CF_UNICODETEXT.0 as u32, HANDLE(hglobal.0)) {
+                let _ = GlobalFree(hglobal);
+                return Err(e);
+            }
+            Ok(())
+        })();
+        let _ = CloseClipboard();
+        result
+    }
+}
+
+/// Replaces the clipboard content with a list of files (CF_HDROP), as Explorer copies them
+pub fn write_files(hwnd: HWND, files: &[std::path::PathBuf]) -> Result<()> {
+    // DROPFILES, then the wide paths, each null-terminated, and a final null
+    let mut paths: Vec<u16> = Vec::new();
+    for file in files {
+        paths.extend(file.as_os_str().to_string_lossy().encode_utf16());
+        paths.push(0);
+    }
+    paths.push(0);
+    let header = std::mem::size_of::<DROPFILES>();
+    let size = header + paths.len() * std::mem::size_of::<u16>();
+    unsafe {
+        if !OpenClipboard(hwnd).as_bool() {
+            return Err(Error::from_win32());
+        }
+        let result = (|| {
+            EmptyClipboard().ok()?;
+            let hglobal = GlobalAlloc(GMEM_MOVEABLE | GMEM_ZEROINIT, size)?;
+            let ptr = GlobalLock(hglobal) as *mut u8;
+            if ptr.is_null() {
+                let _ = GlobalFree(hglobal);
+                return Err(Error::from_win32());
+            }
+            let dropfiles = DROPFILES { pFiles: header as u32, fWide: TRUE, ..Default::default() };
+            std::ptr::write_unaligned(ptr as *mut DROPFILES, dropfiles);
+            std::ptr::copy_nonoverlapping(paths.as_ptr() as *const u8, ptr.add(header), paths.len() * std::mem::size_of::<u16>());
+            let _ = GlobalUnlock(hglobal);
+            // The clipboard owns the memory once SetClipboardData succeeds
+            if let Err(e) = SetClipboardData(CF_HDROP.0 as u32, HANDLE(hglobal.0)) {
+                let _ = GlobalFree(hglobal);
+                return Err(e);
+            }
+            Ok(())
+        })();
+        let _ = CloseClipboard();
+        result
+    }
+}
+
+/// Replaces the clipboard content with an image (bottom-up, non-premultiplied 32bpp BGRA
+/// as from `WicLoader::get_bitmap_for_clipboard`) as CF_DIBV5 and CF_DIB
+pub fn write_image(hwnd: HWND, bitmap: &ClipboardBitmapData) -> Result<()> {
+    unsafe {
+        if !OpenClipboard(hwnd).as_bool() {
+            return Err(Error::from_win32());
+        }
+        let result = (|| {
+            EmptyClipboard().ok()?;
+            set_global(CF_DIBV5.0 as u32, &pack_dibv5(bitmap))?;
+            set_global(CF_DIB.0 as u32, &pack_dib(bitmap))
+        })();
+        let _ = CloseClipboard();
+        result
+    }
+}
+
+/// Puts `data` on the open clipboard as `format`
+unsafe fn set_global(format: u32, data: &[u8]) -> Result<()> {
+    let hglobal = GlobalAlloc(GMEM_MOVEABLE, data.len())?;
+    let ptr = GlobalLock(hglobal) as *mut u8;
+    if ptr.is_null() {
+        let _ = GlobalFree(hglobal);
+        return Err(Error::from_win32());
+    }
+    std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+    let _ = GlobalUnlock(hglobal);
+    // The clipboard owns the memory once SetClipboardData succeeds
+    if let Err(e) = SetClipboardData(format, HANDLE(hglobal.0)) {
+        let _ = GlobalFree(hglobal);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Packed DIB with a BITMAPV5HEADER: BI_BITFIELDS with the masks of BGRA, sRGB, the
+/// pixels as they are
+fn pack_dibv5(bitmap: &ClipboardBitmapData) -> Vec<u8> {
+    let mut dib = Vec::with_capacity(BITMAPV5HEADER_SIZE + bitmap.data.len());
+    push_info_header(&mut dib, BITMAPV5HEADER_SIZE, bitmap, BI_BITFIELDS);
+    // Red, green, blue and alpha masks
+    for mask in [0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+        dib.extend_from_slice(&mask.to_le_bytes());
+    }
+    dib.extend_from_slice(&LCS_SRGB.to_le_bytes());
+    // Endpoints and gamma are unused with sRGB
+    dib.resize(dib.len() + 36 + 12, 0);
+    dib.extend_from_slice(&LCS_GM_IMAGES.to_le_bytes());
+    // No profile; reserved
+    dib.resize(BITMAPV5HEADER_SIZE, 0);
+    dib.extend_from_slice(&bitmap.data);
+    dib
+}
+
+/// Packed DIB with a BITMAPINFOHEADER: BI_RGB, opaque, transparent parts on white.
+/// Programs reading CF_DIB either ignore the fourth byte or take it as alpha.
+fn pack_dib(bitmap: &ClipboardBitmapData) -> Vec<u8> {
+    let mut dib = Vec::with_capacity(BITMAPINFOHEADER_SIZE + bitmap.data.len());
+    push_info_header(&mut dib, BITMAPINFOHEADER_SIZE, bitmap, BI_RGB);
+    dib.extend(bitmap.data.chunks_exact(4).flat_map(|px| {
+        let a = px[3] as u32;
+        let on_white = |c: u8| ((c as u32 * a + 255 * (255 - a)) / 255) as u8;
+        [on_white(px[0]), on_white(px[1]), on_white(px[2]), 255]
+    }));
+    dib
+}
+
+/// The BITMAPINFOHEADER part shared by both headers, bottom-up 32bpp
+fn push_info_header(dib: &mut Vec<u8>, header_size: usize, bitmap: &ClipboardBitmapData, compression: u32) {
+    dib.extend_from_slice(&(header_size as u32).to_le_bytes());
+    dib.extend_from_slice(&(bitmap.width as i32).to_le_bytes());
+    // Positive height = bottom-up rows
+    dib.extend_from_slice(&(bitmap.height as i32).to_le_bytes());
+    dib.extend_from_slice(&1u16.to_le_bytes());
+    dib.extend_from_slice(&32u16.to_le_bytes());
+    dib.extend_from_slice(&compression.to_le_bytes());
+    dib.extend_from_slice(&(bitmap.data.len() as u32).to_le_bytes());
+    // Resolution, colors used and important
+    dib.resize(dib.len() + 16, 0);
+}
+
+unsafe fn read_open_clipboard() -> Result<Option<ClipboardContent>> {
+    if IsClipboardFormatAvailable(CF_HDROP.0 as u32).as_bool() {
+        let handle = GetClipboardData(CF_HDROP.0 as u32)?;
+        let hdrop = HDROP(handle.0);
+        let len = DragQueryFileW(hdrop, 0, None) as usize;
+        if len > 0 {
+            let mut buffer = vec![0u16; len + 1];
+            DragQueryFileW(hdrop, 0, Some(&mut buffer));
+            return Ok(Some(ClipboardContent::File(String::from_utf16_lossy(&buffer[..len]))));
+        }
+    }
+
+    // PNG keeps transparency best, so it is preferred over the DIB formats
+    let png_format = RegisterClipboardFormatW(w!("PNG"));
+    if png_format != 0 && IsClipboardFormatAvailable(png_format).as_bool() {
+        if let Some(data) = global_bytes(png_format)? {
+            return Ok(Some(ClipboardContent::Png(data)));
+        }
+    }
+
+    for format in [CF_DIBV5, CF_DIB] {
+        if IsClipboardFormatAvailable(format.0 as u32).as_bool() {
+            if let Some(data) = global_bytes(format.0 as u32)? {
+                let (width, height, pixels) = dib_to_pbgra(&data)?;
+                return Ok(Some(ClipboardContent::Pixels { width, height, data: pixels }));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Copies the global memory block of a clipboard format
+unsafe fn global_bytes(format: u32) -> Result<Option<Vec<u8>>> {
+    let handle = GetClipboardData(format)?;
+    let hglobal = HGLOBAL(handle.0);
+    let size = GlobalSize(hglobal);
+    let ptr = GlobalLock(hglobal) as *const u8;
+    if ptr.is_null() || size == 0 {
+        return Ok(None);
+    }
+    let data = std::slice::from_raw_parts(ptr, size).to_vec();
+    let _ = GlobalUnlock(hglobal);
+    Ok(Some(data))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .unwrap_or(0)
+}
+
+fn invalid_dib() -> Error {
+    Error::new(
+        HRESULT(0x88982F60u32 as i32), // WINCODEC_ERR_BADIMAGE
+        HSTRING::from("Das Bild in der Zwischenablage hat ein nicht unterstütztes Format."),
+    )
+}
+
+/// Converts a packed DIB (BITMAPINFOHEADER/V4/V5 + pixels) with 24 or 32 bits per pixel
+/// to top-down premultiplied BGRA.
+fn dib_to_pbgra(dib: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+    let header_size = read_u32(dib, 0) as usize;
+    if header_size < BITMAPINFOHEADER_SIZE || dib.len() < header_size {
+        return Err(invalid_dib());
+    }
+    let width = read_u32(dib, 4) as i32;
+    let raw_height = read_u32(dib, 8) as i32;
+    let bit_count = u16::from_le_bytes([dib[14], dib[15]]);
+    let compression = read_u32(dib, 16);
+    let colors_used = read_u32(dib, 32) as usize;
+
+    // Positive height = bottom-up rows, negative = top-down
+    let top_down = raw_height < 0;
+    let height = raw_height.unsigned_abs();
+    if width <= 0 || height == 0 || !(bit_count == 24 || bit_count == 32) {
+        return Err(invalid_dib());
+    }
+    let width = width as u32;
+
+    // Channel masks: inside V4/V5 headers, or three DWORDs after a plain header
+    let (masks, masks_after_header) = match compression {
+        BI_BITFIELDS if header_size == BITMAPINFOHEADER_SIZE => (
+            [read_u32(dib, 40), read_u32(dib, 44), read_u32(dib, 48), 0],
+            12,
+        ),
+        BI_BITFIELDS => (
+            [read_u32(dib, 40), read_u32(dib, 44), read_u32(dib, 48), read_u32(dib, 52)],
+            0,
+        ),
+        BI_RGB => (
+            [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, if header_size >= 56 { read_u32(dib, 52) } else { 0 }],
+            0,
+        ),
+        _ => return Err(invalid_dib()),
+    };
+
+    let pixel_offset = header_size + masks_after_header + colors_used * 4;
+    let src_stride = (width as usize * bit_count as usize).div_ceil(32) * 4;
+    if dib.len() < pixel_offset + src_stride * height as usize {
+        return Err(invalid_dib());
+    }
+
+    let channel = |pixel: u32, mask: u32| -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+        let value = (pixel & mask) >> mask.trailing_zeros();
+        let max = mask >> mask.trailing_zeros();
+        (value * 255 / max) as u8
+    };
+
+    let mut data = vec![0u8; width as usize * height as usize * 4];
+    let mut any_alpha = false;
+    for y in 0..height as usize {
+        let src_row = if top_down { y } else { height as usize - 1 - y };
+        let src = &dib[pixel_offset + src_row * src_stride..];
+        let dst = &mut data[y * width as usize * 4..(y + 1) * width as usize * 4];
+        for x in 0..width as usize {
+            let (b, g, r, a) = if bit_count == 24 {
+                (src[x * 3], src[x * 3 + 1], src[x * 3 + 2], 255)
+            } else {
+                let pixel = read_u32(src, x * 4);
+                (
+                    channel(pixel, masks[2]),
+                    channel(pixel, masks[1]),
+                    channel(pixel, masks[0]),
+                    channel(pixel, masks[3]),
+                )
+            };
+            any_alpha |= bit_count == 32 && a != 0;
+            dst[x * 4..x * 4 + 4].copy_from_slice(&[b, g, r, a]);
+        }
+    }
+
+    // 32bpp without alpha information (all zero) is opaque; otherwise premultiply
+    for px in data.chunks_exact_mut(4) {
+        if bit_count == 32 && !any_alpha {
+            px[3] = 255;
+        } else if px[3] != 255 {
+            let a = px[3] as u32;
+            px[0] = (px[0] as u32 * a / 255) as u8;
+            px[1] = (px[1] as u32 * a / 255) as u8;
+            px[2] = (px[2] as u32 * a / 255) as u8;
+        }
+    }
+
+    Ok((width, height, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BITMAPINFOHEADER; a negative `height` is top-down
+    fn info_header(width: i32, height: i32, bit_count: u16, compression: u32) -> Vec<u8> {
+        let mut dib = Vec::new();
+        dib.extend_from_slice(&(BITMAPINFOHEADER_SIZE as u32).to_le_bytes());
+        dib.extend_from_slice(&width.to_le_bytes());
+        dib.extend_from_slice(&height.to_le_bytes());
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&bit_count.to_le_bytes());
+        dib.extend_from_slice(&compression.to_le_bytes());
+        dib.resize(BITMAPINFOHEADER_SIZE, 0);
+        dib
+    }
+
+    /// Top-down 24bpp BGR rows, written bottom-up or top-down with each row padded to
+    /// a DWORD
+    fn dib24(width: usize, rows: &[Vec<u8>], top_down: bool) -> Vec<u8> {
+        let height = rows.len() as i32;
+        let mut dib = info_header(width as i32, if top_down { -height } else { height }, 24, BI_RGB);
+        let stride = (width * 3).div_ceil(4) * 4;
+        let ordered: Vec<&Vec<u8>> = if top_down { rows.iter().collect() } else { rows.iter().rev().collect() };
+        for row in ordered {
+            dib.extend_from_slice(row);
+            // Padding bytes that must not be read as pixels
+            dib.extend(std::iter::repeat_n(0xAA, stride - row.len()));
+        }
+        dib
+    }
+
+    /// Top-down BGRA of top-down BGR rows
+    fn opaque(rows: &[Vec<u8>]) -> Vec<u8> {
+        rows.iter().flat_map(|row| row.chunks_exact(3).flat_map(|px| [px[0], px[1], px[2], 255])).collect()
+    }
+
+    fn rows24(width: usize, height: usize) -> Vec<Vec<u8>> {
+        (0..height).map(|y| (0..width * 3).map(|i| (y * 40 + i * 7 + 1) as u8).collect()).collect()
+    }
+
+    #[test]
+    fn reads_24_bit_bottom_up_with_row_padding() {
+        for width in [1, 2, 3, 5] {
+            let rows = rows24(width, 3);
+            let (w, h, data) = dib_to_pbgra(&dib24(width, &rows, false)).unwrap();
+            assert_eq!((w, h), (width as u32, 3));
+            assert_eq!(data, opaque(&rows), "width {}", width);
+        }
+    }
+
+    #[test]
+    fn reads_24_bit_top_down_with_row_padding() {
+        for width in [1, 3, 7] {
+            let rows = rows24(width, 2);
+            let (_, _, data) = dib_to_pbgra(&dib24(width, &rows, true)).unwrap();
+            assert_eq!(data, opaque(&rows), "width {}", width);
+        }
+    }
+
+    /// Bottom-up straight BGRA as `get_bitmap_for_clipboard` hands it over, three pixels
+    /// wide: opaque, half transparent, fully transparent, and the same again upside down
+    fn clipboard_bitmap() -> ClipboardBitmapData {
+        let bottom = [10, 20, 30, 255, 200, 100, 50, 128, 90, 90, 90, 0];
+        let top = [1, 2, 3, 255, 255, 255, 255, 128, 0, 0, 255, 64];
+        ClipboardBitmapData { width: 3, height: 2, data: [bottom, top].concat() }
+    }
+
+    fn premultiplied(px: &[u8]) -> [u8; 4] {
+        let a = px[3] as u32;
+        [(px[0] as u32 * a / 255) as u8, (px[1] as u32 * a / 255) as u8, (px[2] as u32 * a / 255) as u8, px[3]]
+    }
+
+    #[test]
+    fn dibv5_round_trip_keeps_alpha() {
+        let bitmap = clipboard_bitmap();
+        let dib = pack_dibv5(&bitmap);
+        assert_eq!(dib.len(), BITMAPV5HEADER_SIZE + 3 * 2 * 4);
+        let (w, h, data) = dib_to_pbgra(&dib).unwrap();
+        assert_eq!((w, h), (3, 2));
+        // Read back top-down and premultiplied
+        let expected: Vec<u8> = bitmap.data[12..].chunks_exact(4).chain(bitmap.data[..12].chunks_exact(4)).flat_map(premultiplied).collect();
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn dib_round_trip_is_opaque_on_white() {
+        let (w, h, data) = dib_to_pbgra(&pack_dib(&clipboard_bitmap())).unwrap();
+        assert_eq!((w, h), (3, 2));
+        assert_eq!(
+            data,
+            [
+                [1, 2, 3, 255], [255, 255, 255, 255], [191, 191, 255, 255],
+                [10, 20, 30, 255], [227, 177, 152, 255], [255, 255, 255, 255],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn reads_32_bit_without_alpha_as_opaque() {
+        let mut dib = info_header(1, -2, 32, BI_RGB);
+        dib.extend_from_slice(&[10, 20, 30, 0, 40, 50, 60, 0]);
+        assert_eq!(dib_to_pbgra(&dib).unwrap().2, [10, 20, 30, 255, 40, 50, 60, 255]);
+    }
+
+    #[test]
+    fn reads_bitfield_masks_after_a_plain_header() {
+        // RGB masks in the opposite byte order of BGRA
+        let mut dib = info_header(2, 1, 32, BI_BITFIELDS);
+        for mask in [0x0000_00FFu32, 0x0000_FF00, 0x00FF_0000] {
+            dib.extend_from_slice(&mask.to_le_bytes());
+        }
+        dib.extend_from_slice(&[1, 2, 3, 0, 4, 5, 6, 0]);
+        assert_eq!(dib_to_pbgra(&dib).unwrap().2, [3, 2, 1, 255, 6, 5, 4, 255]);
+    }
+
+    #[test]
+    fn refuses_short_and_unsupported_dibs() {
+        let mut dib = dib24(3, &rows24(3, 2), false);
+        dib.pop();
+        assert!(dib_to_pbgra(&dib).is_err());
+        assert!(dib_to_pbgra(&info_header(2, 2, 8, BI_RGB)).is_err());
+        assert!(dib_to_pbgra(&info_header(0, 2, 32, BI_RGB)).is_err());
+        assert!(dib_to_pbgra(&[0; 12]).is_err());
+    }
+}
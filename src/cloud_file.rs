@@ -0,0 +1,111 @@
+//! Online-only cloud files (OneDrive placeholders) and empty files
+//!
+//! A placeholder has only its metadata on disk; the first read makes the sync client
+//! download ("hydrate") the content, which takes a while on a slow connection. WIC and
+//! Windows.Data.Pdf would block the UI thread meanwhile without any feedback, so such a
+//! file is read once on a worker thread first while the status bar shows "Wird aus der
+//! Cloud geladen…" and Esc can abort; the loaders then find the content local.
+//!
+//! An empty file fails right away with a clear message instead of a WIC error code.
+
+use crate::cancel::CancelToken;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::WIN32_ERROR,
+        Storage::FileSystem::*,
+    },
+};
+
+/// HRESULT_FROM_WIN32(ERROR_INVALID_DATA)
+const E_INVALID_DATA: HRESULT = HRESULT(0x8007000Du32 as i32);
+/// Attributes of content that is not on the local disk
+const PLACEHOLDER_ATTRIBUTES: u32 =
+    FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS.0 | FILE_ATTRIBUTE_RECALL_ON_OPEN.0 | FILE_ATTRIBUTE_OFFLINE.0;
+/// Status bar update interval while a placeholder downloads
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const READ_CHUNK: usize = 1024 * 1024;
+
+pub struct FileInfo {
+    pub size: u64,
+    /// Online-only: reading the content downloads it first
+    pub cloud_placeholder: bool,
+}
+
+/// Size and placeholder state of a file without touching its content
+pub fn file_info(path: &str) -> Result<FileInfo> {
+    let path_wide = crate::utils::to_wide_path(path);
+    crate::retry::with_retry("Dateiattribute", path, || unsafe {
+        let mut file_data = WIN32_FILE_ATTRIBUTE_DATA::default();
+        if GetFileAttributesExW(PCWSTR(path_wide.as_ptr()), GetFileExInfoStandard, &mut file_data as *mut _ as *mut _).as_bool() {
+            Ok(FileInfo {
+                size: ((file_data.nFileSizeHigh as u64) << 32) | (file_data.nFileSizeLow as u64),
+                cloud_placeholder: is_placeholder(file_data.dwFileAttributes),
+            })
+        } else {
+            Err(Error::from_win32())
+        }
+    })
+}
+
+/// True if `attributes` mark online-only content
+fn is_placeholder(attributes: u32) -> bool {
+    attributes & PLACEHOLDER_ATTRIBUTES != 0
+}
+
+/// Error for a file of 0 bytes
+pub fn empty_file() -> Error {
+    Error::new(E_INVALID_DATA, HSTRING::from("Die Datei ist leer (0 Byte)."))
+}
+
+/// Reads the whole file on a worker thread so the sync client downloads it. `progress` is
+/// called on the calling thread with the bytes read so far, `cancel` aborts the wait; the
+/// worker then stops after its current chunk.
+pub fn hydrate(path: &str, size: u64, cancel: &CancelToken, mut progress: impl FnMut(u64, u64)) -> Result<()> {
+    let received = Arc::new(AtomicU64::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let worker_path = path.to_string();
+    let worker_received = received.clone();
+    let worker_cancel = cancel.clone();
+    std::thread::Builder::new()
+        .name("SimpliView Cloud Download".to_string())
+        .spawn(move || {
+            let _ = sender.send(read_all(&worker_path, &worker_received, &worker_cancel));
+        })
+        .map_err(io_error)?;
+
+    loop {
+        match receiver.recv_timeout(PROGRESS_INTERVAL) {
+            Ok(result) => return result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                cancel.check()?;
+                progress(received.load(Ordering::Relaxed), size);
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Err(Error::from(E_INVALID_DATA)),
+        }
+    }
+}
+
+fn read_all(path: &str, received: &AtomicU64, cancel: &CancelToken) -> Result<()> {
+    let mut file = std::fs::File::open(crate::utils::long_path(path)).map_err(io_error)?;
+    let mut buffer = vec![0u8; READ_CHUNK];
+    while !cancel.is_cancelled() {
+        let read = file.read(&mut buffer).map_err(io_error)?;
+        if read == 0 {
+            break;
+        }
+        received.fetch_add(read as u64, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    match e.raw_os_error() {
+        Some(code) => Error::from(WIN32_ERROR(code as u32).to_hresult()),
+        None => Error::new(E_INVALID_DATA, HSTRING::from(e.to_string())),
+    }
+}
@@ -0,0 +1,274 @@
+//! "Übersichtsbild exportieren": all pages as thumbnails in one image
+//!
+//! A quick visual index of a scanned file. The pages are laid out in a grid of square
+//! cells, each thumbnail centered in its cell, optionally with the page number below.
+//! Cells are `CELL_SIZE` pixels; for long documents they shrink so the sheet stays within
+//! `MAX_SIDE`, and below `MIN_CELL_SIZE` the sheet ends early (the caller warns).
+//!
+//! The thumbnails are copied into one pixel buffer, the page numbers are drawn with
+//! DirectWrite afterwards and the result is saved like any exported page.
+
+use crate::d2d::{get_d2d_factory, get_dwrite_factory};
+use crate::wic::ClipboardBitmapData;
+use windows::{
+    core::*,
+    Win32::Graphics::{
+        Direct2D::{Common::*, *},
+        DirectWrite::*,
+        Dxgi::Common::*,
+        Imaging::IWICBitmap,
+    },
+};
+
+/// Longest side of a thumbnail
+pub const CELL_SIZE: u32 = 256;
+const MIN_CELL_SIZE: u32 = 64;
+/// Longest side of the sheet (about 144 MB of pixels at most)
+const MAX_SIDE: u32 = 6000;
+/// Strip below each cell for the page number
+const LABEL_HEIGHT: u32 = 24;
+const LABEL_FONT_SIZE: f32 = 14.0;
+const MAX_COLUMNS: u32 = 20;
+const MAX_MARGIN: u32 = 64;
+/// Light gray around the pages, so white pages keep their outline
+const BACKGROUND: u8 = 0xE6;
+
+/// Grid options, chosen in the export dialog and kept in the settings
+#[derive(Clone)]
+pub struct Options {
+    pub columns: u32,
+    /// Space between the cells and around the grid, in pixels
+    pub margin: u32,
+    pub page_numbers: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { columns: 4, margin: 16, page_numbers: true }
+    }
+}
+
+/// Layout of a contact sheet in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+    /// Side of the square thumbnail area of a cell
+    pub cell: u32,
+    pub margin: u32,
+    pub label_height: u32,
+    /// Pages on the sheet, fewer than the document has if it did not fit
+    pub pages: usize,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Grid {
+    /// Lays out `page_count` pages. Cells shrink from `CELL_SIZE` to `MIN_CELL_SIZE` to keep
+    /// the sheet within `MAX_SIDE`; if even that is too large, the grid ends after the
+    /// rows that fit.
+    pub fn new(page_count: usize, options: &Options) -> Self {
+        let page_count = page_count.max(1);
+        let columns = options.columns.clamp(1, MAX_COLUMNS).min(page_count as u32);
+        let margin = options.margin.min(MAX_MARGIN);
+        let label_height = if options.page_numbers { LABEL_HEIGHT } else { 0 };
+        let rows = page_count.div_ceil(columns as usize) as u32;
+
+        let fit_width = (MAX_SIDE - margin) / columns;
+        let fit_height = (MAX_SIDE - margin) / rows;
+        let cell = CELL_SIZE
+            .min(fit_width.saturating_sub(margin))
+            .min(fit_height.saturating_sub(margin + label_height));
+        // MAX_COLUMNS and MAX_MARGIN leave room for MIN_CELL_SIZE across
+        let (cell, rows) = if cell >= MIN_CELL_SIZE {
+            (cell, rows)
+        } else {
+            (MIN_CELL_SIZE, (MAX_SIDE - margin) / (MIN_CELL_SIZE + margin + label_height))
+        };
+        let pages = page_count.min((rows * columns) as usize);
+        let rows = pages.div_ceil(columns as usize) as u32;
+
+        Self {
+            columns,
+            rows,
+            cell,
+            margin,
+            label_height,
+            pages,
+            width: margin + columns * (cell + margin),
+            height: margin + rows * (cell + label_height + margin),
+        }
+    }
+
+    /// Top-left corner of the thumbnail area of cell `index`
+    pub fn cell_origin(&self, index: usize) -> (u32, u32) {
+        let column = index as u32 % self.columns;
+        let row = index as u32 / self.columns;
+        (
+            self.margin + column * (self.cell + self.margin),
+            self.margin + row * (self.cell + self.label_height + self.margin),
+        )
+    }
+}
+
+/// The sheet's pixels (top-down PBGRA) while the thumbnails are placed
+pub struct Sheet {
+    grid: Grid,
+    pixels: Vec<u8>,
+}
+
+impl Sheet {
+    pub fn new(grid: Grid) -> Self {
+        let pixels = vec![BACKGROUND; grid.width as usize * grid.height as usize * 4];
+        Self { grid, pixels }
+    }
+
+    /// Copies a thumbnail (see `WicLoader::get_thumbnail`, bottom-up BGRA) centered into
+    /// cell `index`, composited over white like paper
+    pub fn place(&mut self, index: usize, thumbnail: &ClipboardBitmapData) {
+        let cell = self.grid.cell;
+        let (width, height) = (thumbnail.width.min(cell), thumbnail.height.min(cell));
+        let (cell_x, cell_y) = self.grid.cell_origin(index);
+        let left = (cell_x + (cell - width) / 2) as usize;
+        let top = (cell_y + (cell - height) / 2) as usize;
+        let sheet_stride = self.grid.width as usize * 4;
+        let thumbnail_stride = thumbnail.width as usize * 4;
+
+        for y in 0..height as usize {
+            let source = (thumbnail.height as usize - 1 - y) * thumbnail_stride;
+            let target = (top + y) * sheet_stride + left * 4;
+            for x in 0..width as usize {
+                let pixel = &thumbnail.data[source + x * 4..source + x * 4 + 4];
+                let alpha = pixel[3] as u32;
+                for (channel, &value) in pixel[..3].iter().enumerate() {
+                    self.pixels[target + x * 4 + channel] = ((value as u32 * alpha) / 255 + 255 - alpha) as u8;
+                }
+                self.pixels[target + x * 4 + 3] = 255;
+            }
+        }
+    }
+
+    /// The finished sheet, with "1", "2", ... below the cells if the grid has labels
+    pub fn finish(self) -> Result<IWICBitmap> {
+        let bitmap = crate::wic::bitmap_from_pbgra(self.grid.width, self.grid.height, &self.pixels)?;
+        drop(self.pixels);
+        if self.grid.label_height > 0 {
+            draw_page_numbers(&bitmap, &self.grid)?;
+        }
+        Ok(bitmap)
+    }
+}
+
+fn draw_page_numbers(bitmap: &IWICBitmap, grid: &Grid) -> Result<()> {
+    // 96 DPI so one DIP equals one pixel of the bitmap
+    let props = D2D1_RENDER_TARGET_PROPERTIES {
+        r#type: D2D1_RENDER_TARGET_TYPE_SOFTWARE,
+        pixelFormat: D2D1_PIXEL_FORMAT {
+            format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            alphaMode: D2D1_ALPHA_MODE_PREMULTIPLIED,
+        },
+        dpiX: 96.0,
+        dpiY: 96.0,
+        usage: D2D1_RENDER_TARGET_USAGE_NONE,
+        minLevel: D2D1_FEATURE_LEVEL_DEFAULT,
+    };
+
+    unsafe {
+        let rt = get_d2d_factory()?.CreateWicBitmapRenderTarget(bitmap, &props)?;
+        let format = get_dwrite_factory()?.CreateTextFormat(
+            w!("Segoe UI"),
+            None,
+            DWRITE_FONT_WEIGHT_NORMAL,
+            DWRITE_FONT_STYLE_NORMAL,
+            DWRITE_FONT_STRETCH_NORMAL,
+            LABEL_FONT_SIZE,
+            w!("de-de"),
+        )?;
+        format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+        format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+        let brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.2, g: 0.2, b: 0.2, a: 1.0 }, None)?;
+
+        rt.BeginDraw();
+        for index in 0..grid.pages {
+            let (left, top) = grid.cell_origin(index);
+            let top = (top + grid.cell) as f32;
+            let layout = D2D_RECT_F {
+                left: left as f32,
+                top,
+                right: (left + grid.cell) as f32,
+                bottom: top + grid.label_height as f32,
+            };
+            let text: Vec<u16> = (index + 1).to_string().encode_utf16().collect();
+            rt.DrawText(&text, &format, &layout, &brush, D2D1_DRAW_TEXT_OPTIONS_NONE, DWRITE_MEASURING_MODE_NATURAL);
+        }
+        rt.EndDraw(None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(columns: u32, margin: u32, page_numbers: bool) -> Options {
+        Options { columns, margin, page_numbers }
+    }
+
+    #[test]
+    fn short_documents_take_full_cells() {
+        let grid = Grid::new(3, &Options::default());
+        assert_eq!((grid.columns, grid.rows, grid.cell, grid.pages), (3, 1, CELL_SIZE, 3));
+        assert_eq!((grid.width, grid.height), (16 + 3 * (256 + 16), 16 + 256 + 24 + 16));
+
+        let grid = Grid::new(10, &options(4, 16, false));
+        assert_eq!((grid.columns, grid.rows, grid.label_height, grid.pages), (4, 3, 0, 10));
+        assert_eq!(grid.height, 16 + 3 * (256 + 16));
+    }
+
+    #[test]
+    fn cells_shrink_for_long_documents() {
+        let grid = Grid::new(200, &Options::default());
+        assert_eq!((grid.columns, grid.rows, grid.cell, grid.pages), (4, 50, 79, 200));
+        assert!(grid.height <= MAX_SIDE);
+    }
+
+    #[test]
+    fn sheet_ends_early_below_the_smallest_cell() {
+        let grid = Grid::new(1000, &Options::default());
+        assert_eq!((grid.cell, grid.rows, grid.pages), (MIN_CELL_SIZE, 57, 228));
+        assert!(grid.height <= MAX_SIDE);
+    }
+
+    #[test]
+    fn options_are_clamped() {
+        assert_eq!(Grid::new(5, &options(0, 16, true)).columns, 1);
+        assert_eq!(Grid::new(100, &options(100, 16, true)).columns, MAX_COLUMNS);
+        assert_eq!(Grid::new(5, &options(4, 1000, true)).margin, MAX_MARGIN);
+        assert_eq!(Grid::new(0, &Options::default()).pages, 1);
+    }
+
+    #[test]
+    fn sheets_stay_within_the_limit() {
+        for page_count in [1, 7, 64, 500, 5000] {
+            for columns in [1, 4, 20] {
+                for margin in [0, 16, 64] {
+                    for page_numbers in [false, true] {
+                        let grid = Grid::new(page_count, &options(columns, margin, page_numbers));
+                        let case = format!("{page_count} pages, {columns} columns, margin {margin}, numbers {page_numbers}");
+                        assert!(grid.width <= MAX_SIDE && grid.height <= MAX_SIDE, "{case}: {grid:?}");
+                        assert!(grid.cell >= MIN_CELL_SIZE && grid.cell <= CELL_SIZE, "{case}: {grid:?}");
+                        assert!(grid.pages >= 1 && grid.pages <= page_count, "{case}: {grid:?}");
+                        assert!(grid.pages <= (grid.rows * grid.columns) as usize, "{case}: {grid:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cells_are_placed_row_by_row() {
+        let grid = Grid::new(6, &options(2, 10, true));
+        assert_eq!(grid.cell_origin(0), (10, 10));
+        assert_eq!(grid.cell_origin(1), (10 + 256 + 10, 10));
+        assert_eq!(grid.cell_origin(2), (10, 10 + 256 + 24 + 10));
+    }
+}
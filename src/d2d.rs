@@ -1,5 +1,10 @@
-use crate::document::{Document, PageLayout};
-use std::cell::RefCell;
+use crate::document::{Document, PageLayout, PageState};
+use crate::hover_toolbar::HoverAction;
+use crate::minimap::Minimap;
+use crate::page_transform::PageTransform;
+use crate::shortcuts::ShortcutGroup;
+use crate::watermark::Watermark;
+use std::cell::{Cell, RefCell};
 use windows::{
     core::*,
     Foundation::Numerics::Matrix3x2,
@@ -7,7 +12,9 @@ use windows::{
         Foundation::*,
         Graphics::{
             Direct2D::{Common::*, *},
+            DirectWrite::*,
             Dxgi::Common::*,
+            Imaging::IWICBitmap,
         },
         UI::WindowsAndMessaging::GetClientRect,
     },
@@ -15,9 +22,10 @@ use windows::{
 
 thread_local! {
     static D2D_FACTORY: RefCell<Option<ID2D1Factory1>> = const { RefCell::new(None) };
+    static DWRITE_FACTORY: RefCell<Option<IDWriteFactory>> = const { RefCell::new(None) };
 }
 
-fn get_d2d_factory() -> Result<ID2D1Factory1> {
+pub fn get_d2d_factory() -> Result<ID2D1Factory1> {
     D2D_FACTORY.with(|cell| {
         let mut opt = cell.borrow_mut();
         if opt.is_none() {
@@ -30,32 +38,82 @@ fn get_d2d_factory() -> Result<ID2D1Factory1> {
     })
 }
 
+pub fn get_dwrite_factory() -> Result<IDWriteFactory> {
+    DWRITE_FACTORY.with(|cell| {
+        let mut opt = cell.borrow_mut();
+        if opt.is_none() {
+            let factory: IDWriteFactory = unsafe { DWriteCreateFactory(DWRITE_FACTORY_TYPE_SHARED)? };
+            *opt = Some(factory);
+        }
+        Ok(opt.as_ref().unwrap().clone())
+    })
+}
+
+/// Canvas color behind the pages
+pub const BACKGROUND_COLOR: D2D1_COLOR_F = D2D1_COLOR_F { r: 0.22, g: 0.23, b: 0.25, a: 1.0 };
+
 pub struct D2DRenderer {
-    hwnd: HWND,
-    render_target: Option<ID2D1HwndRenderTarget>,
+    /// Window drawn into, `None` for an offscreen renderer (see `offscreen`)
+    hwnd: Option<HWND>,
+    render_target: Option<ID2D1RenderTarget>,
     width: u32,
     height: u32,
+    watermark: Option<Watermark>,
+    // Pages drawn since the last begin_draw (debug overlay)
+    pages_drawn: Cell<usize>,
 }
 
 impl D2DRenderer {
     pub fn new(hwnd: HWND) -> Result<Self> {
         let mut renderer = Self {
-            hwnd,
+            hwnd: Some(hwnd),
             render_target: None,
             width: 0,
             height: 0,
+            watermark: None,
+            pages_drawn: Cell::new(0),
         };
         renderer.create_render_target()?;
         Ok(renderer)
     }
 
+    /// Renderer drawing into `bitmap` (32bpp PBGRA) instead of a window, with the same
+    /// drawing code (`--render-test`, see render_test.rs). One pixel is one DIP.
+    pub fn offscreen(bitmap: &IWICBitmap) -> Result<Self> {
+        unsafe {
+            let (mut width, mut height) = (0u32, 0u32);
+            bitmap.GetSize(&mut width, &mut height)?;
+            let props = D2D1_RENDER_TARGET_PROPERTIES {
+                r#type: D2D1_RENDER_TARGET_TYPE_DEFAULT,
+                pixelFormat: D2D1_PIXEL_FORMAT {
+                    format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                    alphaMode: D2D1_ALPHA_MODE_PREMULTIPLIED,
+                },
+                dpiX: 96.0,
+                dpiY: 96.0,
+                usage: D2D1_RENDER_TARGET_USAGE_NONE,
+                minLevel: D2D1_FEATURE_LEVEL_DEFAULT,
+            };
+            Ok(Self {
+                hwnd: None,
+                render_target: Some(get_d2d_factory()?.CreateWicBitmapRenderTarget(bitmap, &props)?),
+                width,
+                height,
+                watermark: None,
+                pages_drawn: Cell::new(0),
+            })
+        }
+    }
+
     fn create_render_target(&mut self) -> Result<()> {
+        // An offscreen target cannot be recreated
+        let Some(hwnd) = self.hwnd else { return Ok(()) };
         unsafe {
             let factory = get_d2d_factory()?;
 
             // Get client rect
             let mut rect = RECT::default();
-            let _ = GetClientRect(self.hwnd, &mut rect);
+            let _ = GetClientRect(hwnd, &mut rect);
 
             self.width = (rect.right - rect.left) as u32;
             self.height = (rect.bottom - rect.top) as u32;
@@ -78,12 +136,12 @@ impl D2DRenderer {
             };
 
             let hwnd_props = D2D1_HWND_RENDER_TARGET_PROPERTIES {
-                hwnd: self.hwnd,
+                hwnd,
                 pixelSize: size,
                 presentOptions: D2D1_PRESENT_OPTIONS_NONE,
             };
 
-            self.render_target = Some(factory.CreateHwndRenderTarget(&props, &hwnd_props)?);
+            self.render_target = Some(factory.CreateHwndRenderTarget(&props, &hwnd_props)?.cast()?);
         }
         Ok(())
     }
@@ -92,7 +150,7 @@ impl D2DRenderer {
         self.width = width;
         self.height = height;
 
-        if let Some(ref rt) = self.render_target {
+        if let Some(rt) = self.render_target.as_ref().and_then(|rt| rt.cast::<ID2D1HwndRenderTarget>().ok()) {
             unsafe {
                 let size = D2D_SIZE_U { width, height };
                 rt.Resize(&size)?;
@@ -101,11 +159,32 @@ impl D2DRenderer {
         Ok(())
     }
 
+    pub fn set_watermark(&mut self, watermark: Option<Watermark>) {
+        self.watermark = watermark;
+    }
+
+    /// Draws the watermark (if configured) over a page's on-screen bounding box
+    fn draw_watermark(&self, rt: &ID2D1RenderTarget, page_rect: &D2D_RECT_F) -> Result<()> {
+        if let Some(ref watermark) = self.watermark {
+            watermark.draw_d2d(&rt.cast()?, page_rect)?;
+        }
+        Ok(())
+    }
+
     pub fn size(&self) -> (u32, u32) {
         (self.width, self.height)
     }
 
+    /// Screen pixels per DIP of the render target, 1.0 without one
+    pub fn dpi_scale(&self) -> f32 {
+        let Some(ref rt) = self.render_target else { return 1.0 };
+        let (mut dpi_x, mut dpi_y) = (0.0, 0.0);
+        unsafe { rt.GetDpi(&mut dpi_x, &mut dpi_y) };
+        if dpi_x > 0.0 { dpi_x / 96.0 } else { 1.0 }
+    }
+
     pub fn begin_draw(&mut self) -> Result<()> {
+        self.pages_drawn.set(0);
         if self.render_target.is_none() {
             self.create_render_target()?;
         }
@@ -119,6 +198,11 @@ impl D2DRenderer {
         Ok(())
     }
 
+    /// Pages drawn in the current frame
+    pub fn pages_drawn(&self) -> usize {
+        self.pages_drawn.get()
+    }
+
     pub fn end_draw(&mut self) -> Result<()> {
         if let Some(ref rt) = self.render_target {
             unsafe {
@@ -145,11 +229,14 @@ impl D2DRenderer {
         }
     }
 
+    /// Draws one page; with `auto_crop` only its content (see `Document::display_rect`).
+    /// `skew` (clockwise degrees) turns the page a little further, within its bounding box.
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_document(
         &self,
         doc: &Document,
         zoom: f32,
-        rotation: i32,
+        transform: &PageTransform,
         page: usize,
         scroll_x: i32,
         scroll_y: i32,
@@ -159,20 +246,27 @@ impl D2DRenderer {
             None => return Ok(()),
         };
 
+        // Pending PDF page: placeholder of the page size, centered
+        let page_state = doc.page_state(page);
+        let source = transform.source_rect(doc, page);
+        if page_state != PageState::Ready {
+            let (w, h) = transform.output_size((source[2] - source[0]) * zoom, (source[3] - source[1]) * zoom);
+            let (viewport_width, viewport_height) = (self.width as f32, self.height as f32);
+            let left = if w <= viewport_width { (viewport_width - w) / 2.0 } else { -(scroll_x as f32) };
+            let top = if h <= viewport_height { (viewport_height - h) / 2.0 } else { -(scroll_y as f32) };
+            let rect = D2D_RECT_F { left, top, right: left + w, bottom: top + h };
+            return Self::draw_page_placeholder(rt, &rect, page_state);
+        }
+
         // Get the bitmap for the current page
         let bitmap = doc.get_page_bitmap(rt, page)?;
 
         unsafe {
-            let bitmap_size = bitmap.GetSize();
-            let unrotated_w = bitmap_size.width * zoom;
-            let unrotated_h = bitmap_size.height * zoom;
+            let unrotated_w = (source[2] - source[0]) * zoom;
+            let unrotated_h = (source[3] - source[1]) * zoom;
 
             // Determine dimensions of the bounding box after rotation
-            let (layout_w, layout_h) = if rotation == 90 || rotation == 270 {
-                (unrotated_h, unrotated_w)
-            } else {
-                (unrotated_w, unrotated_h)
-            };
+            let (layout_w, layout_h) = transform.output_size(unrotated_w, unrotated_h);
 
             let viewport_width = self.width as f32;
             let viewport_height = self.height as f32;
@@ -196,8 +290,7 @@ impl D2DRenderer {
             let center_y = bbox_top + layout_h / 2.0;
 
             // Calculate rotation transform around this center
-            let angle = rotation as f32;
-            let rotation_transform = make_rotation_matrix(angle, center_x, center_y);
+            let rotation_transform = transform.matrix(center_x, center_y);
 
             // The destination rectangle is the unrotated image centered at the same point
             let dest_rect = D2D_RECT_F {
@@ -216,33 +309,43 @@ impl D2DRenderer {
                 Some(&dest_rect),
                 1.0,
                 D2D1_BITMAP_INTERPOLATION_MODE_LINEAR,
-                None,
+                Some(&bitmap_source_rect(doc, page, &bitmap, source)),
             );
 
             // Reset transform
             rt.SetTransform(&make_identity_matrix());
+            self.pages_drawn.set(self.pages_drawn.get() + 1);
+
+            self.draw_watermark(rt, &D2D_RECT_F {
+                left: bbox_left,
+                top: bbox_top,
+                right: bbox_left + layout_w,
+                bottom: bbox_top + layout_h,
+            })?;
         }
 
         Ok(())
     }
 
     #[allow(dead_code)]
-    pub fn render_target(&self) -> Option<&ID2D1HwndRenderTarget> {
+    pub fn render_target(&self) -> Option<&ID2D1RenderTarget> {
         self.render_target.as_ref()
     }
 
     /// Draw all visible pages of a multi-page document
     ///
-    /// Pages are stacked vertically with gaps between them.
-    /// Only pages intersecting the viewport are rendered.
+    /// Pages are stacked vertically with gaps between them, each with its own
+    /// rotation from the layout and the extra rotation from `skew_of` (see `draw_document`).
+    /// Only pages intersecting the viewport are rendered. Every frame draws them whole from
+    /// the bitmap cache; a scroll does not reuse the previous frame (no retained content).
     pub fn draw_document_multipage(
         &self,
         doc: &Document,
         layout: &PageLayout,
         zoom: f32,
-        rotation: i32,
         scroll_x: i32,
         scroll_y: i32,
+        skew_of: impl Fn(usize) -> f32,
     ) -> Result<()> {
         let rt = match &self.render_target {
             Some(rt) => rt,
@@ -257,12 +360,32 @@ impl D2DRenderer {
 
         // Draw each visible page
         for page_idx in first_page..last_page {
+            let page_state = doc.page_state(page_idx);
+            if page_state != PageState::Ready {
+                let (page_w, page_h) = layout.page_sizes[page_idx];
+                let draw_x = if layout.max_width <= viewport_width as i32 {
+                    (viewport_width - page_w as f32) / 2.0
+                } else {
+                    -(scroll_x as f32) + ((layout.max_width - page_w) / 2) as f32
+                };
+                let draw_y = (layout.page_tops[page_idx] - scroll_y) as f32;
+                let rect = D2D_RECT_F {
+                    left: draw_x,
+                    top: draw_y,
+                    right: draw_x + page_w as f32,
+                    bottom: draw_y + page_h as f32,
+                };
+                Self::draw_page_placeholder(rt, &rect, page_state)?;
+                continue;
+            }
+
             let bitmap = doc.get_page_bitmap(rt, page_idx)?;
+            let transform = PageTransform { skew: skew_of(page_idx), ..layout.transforms[page_idx] };
+            let source = transform.source_rect(doc, page_idx);
 
             unsafe {
-                let bitmap_size = bitmap.GetSize();
-                let unrotated_w = bitmap_size.width * zoom;
-                let unrotated_h = bitmap_size.height * zoom;
+                let unrotated_w = (source[2] - source[0]) * zoom;
+                let unrotated_h = (source[3] - source[1]) * zoom;
 
                 // Get page position from layout
                 let page_top = layout.page_tops[page_idx];
@@ -290,8 +413,7 @@ impl D2DRenderer {
                 let center_y = draw_y + page_h as f32 / 2.0;
 
                 // Calculate rotation transform around this center
-                let angle = rotation as f32;
-                let rotation_transform = make_rotation_matrix(angle, center_x, center_y);
+                let rotation_transform = transform.matrix(center_x, center_y);
 
                 // The destination rectangle is the unrotated image centered at the same point
                 let dest_rect = D2D_RECT_F {
@@ -310,11 +432,19 @@ impl D2DRenderer {
                     Some(&dest_rect),
                     1.0,
                     D2D1_BITMAP_INTERPOLATION_MODE_LINEAR,
-                    None,
+                    Some(&bitmap_source_rect(doc, page_idx, &bitmap, source)),
                 );
 
                 // Reset transform for next page
                 rt.SetTransform(&make_identity_matrix());
+                self.pages_drawn.set(self.pages_drawn.get() + 1);
+
+                self.draw_watermark(rt, &D2D_RECT_F {
+                    left: draw_x,
+                    top: draw_y,
+                    right: draw_x + page_w as f32,
+                    bottom: draw_y + page_h as f32,
+                })?;
             }
         }
 
@@ -327,22 +457,592 @@ impl D2DRenderer {
         Ok(())
     }
 
-    /// Draw a 1px horizontal separator line at the bottom of the viewport
-    pub fn draw_bottom_separator(&self, color: D2D1_COLOR_F) {
-        if let Some(ref rt) = self.render_target {
-            unsafe {
-                if let Ok(brush) = rt.CreateSolidColorBrush(&color, None) {
-                    let y = self.height as f32 - 0.5; // Center of the bottom pixel row
-                    rt.DrawLine(
-                        D2D_POINT_2F { x: 0.0, y },
-                        D2D_POINT_2F { x: self.width as f32, y },
-                        &brush,
-                        1.0,
-                        None,
-                    );
+    /// Draws `page` over what is already on screen (Blinkvergleich): `cover` is filled with
+    /// `background` first, then the `source` part of the page (source pixels, see
+    /// `Document::display_rect`) goes into `dest`, the bounding box after rotation.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_page_over(
+        &self,
+        doc: &Document,
+        page: usize,
+        rotation: i32,
+        source: [f32; 4],
+        cover: &D2D_RECT_F,
+        dest: &D2D_RECT_F,
+        background: D2D1_COLOR_F,
+    ) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+        unsafe {
+            let brush = rt.CreateSolidColorBrush(&background, None)?;
+            rt.FillRectangle(cover, &brush);
+        }
+        if doc.page_state(page) != PageState::Ready {
+            return Self::draw_page_placeholder(rt, dest, doc.page_state(page));
+        }
+
+        let bitmap = doc.get_page_bitmap(rt, page)?;
+        unsafe {
+            let (layout_w, layout_h) = (dest.right - dest.left, dest.bottom - dest.top);
+            let (unrotated_w, unrotated_h) =
+                if rotation == 90 || rotation == 270 { (layout_h, layout_w) } else { (layout_w, layout_h) };
+            let center_x = dest.left + layout_w / 2.0;
+            let center_y = dest.top + layout_h / 2.0;
+            let dest_rect = D2D_RECT_F {
+                left: center_x - unrotated_w / 2.0,
+                top: center_y - unrotated_h / 2.0,
+                right: center_x + unrotated_w / 2.0,
+                bottom: center_y + unrotated_h / 2.0,
+            };
+            rt.SetTransform(&make_rotation_matrix(rotation as f32, center_x, center_y));
+            let source = bitmap_source_rect(doc, page, &bitmap, source);
+            rt.DrawBitmap(&bitmap, Some(&dest_rect), 1.0, D2D1_BITMAP_INTERPOLATION_MODE_LINEAR, Some(&source));
+            rt.SetTransform(&make_identity_matrix());
+        }
+        self.pages_drawn.set(self.pages_drawn.get() + 1);
+        self.draw_watermark(rt, dest)
+    }
+
+    /// Light page rectangle with a status text for PDF pages that are not rendered yet
+    fn draw_page_placeholder(rt: &ID2D1RenderTarget, rect: &D2D_RECT_F, state: PageState) -> Result<()> {
+        let text = if state == PageState::Failed {
+            "Seite konnte nicht geladen werden"
+        } else {
+            "Seite wird geladen…"
+        };
+        let text: Vec<u16> = text.encode_utf16().collect();
+
+        unsafe {
+            let format = get_dwrite_factory()?.CreateTextFormat(
+                w!("Segoe UI"),
+                None,
+                DWRITE_FONT_WEIGHT_NORMAL,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                14.0,
+                w!("de-de"),
+            )?;
+            format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+            format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+
+            let page_brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.92, g: 0.92, b: 0.92, a: 1.0 }, None)?;
+            let text_brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.45, g: 0.45, b: 0.45, a: 1.0 }, None)?;
+
+            rt.FillRectangle(rect, &page_brush);
+            rt.DrawText(&text, &format, rect, &text_brush, D2D1_DRAW_TEXT_OPTIONS_CLIP, DWRITE_MEASURING_MODE_NATURAL);
+        }
+
+        Ok(())
+    }
+
+    /// Draws the Shift+drag selection rectangle (view coordinates)
+    pub fn draw_selection(&self, rect: &D2D_RECT_F) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            let fill = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.47, b: 0.84, a: 0.15 }, None)?;
+            let border = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.47, b: 0.84, a: 1.0 }, None)?;
+            rt.FillRectangle(rect, &fill);
+            rt.DrawRectangle(rect, &border, 1.0, None);
+        }
+
+        Ok(())
+    }
+
+    /// Page border or center line a dragged rectangle snapped to, in the selection color
+    pub fn draw_snap_guides(&self, guides: &[(D2D_POINT_2F, D2D_POINT_2F)]) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) if !guides.is_empty() => rt,
+            _ => return Ok(()),
+        };
+
+        unsafe {
+            let brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.47, b: 0.84, a: 0.6 }, None)?;
+            for (from, to) in guides {
+                rt.DrawLine(*from, *to, &brush, 1.0, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Frame around the annotation chosen in the "Anmerkungen" list, orange to stand apart
+    /// from the selection
+    pub fn draw_annotation_marker(&self, rect: &D2D_RECT_F) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            let fill = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 1.0, g: 0.55, b: 0.0, a: 0.12 }, None)?;
+            let border = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 1.0, g: 0.55, b: 0.0, a: 1.0 }, None)?;
+            let frame = D2D_RECT_F { left: rect.left - 3.0, top: rect.top - 3.0, right: rect.right + 3.0, bottom: rect.bottom + 3.0 };
+            rt.FillRectangle(&frame, &fill);
+            rt.DrawRectangle(&frame, &border, 2.0, None);
+        }
+
+        Ok(())
+    }
+
+    /// Frames the words found by "Text erkennen (OCR)", green to stand apart from the
+    /// selection and the annotation marker
+    pub fn draw_ocr_words(&self, rects: &[D2D_RECT_F]) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            let fill = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.1, g: 0.65, b: 0.25, a: 0.12 }, None)?;
+            let border = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.1, g: 0.65, b: 0.25, a: 0.9 }, None)?;
+            for rect in rects {
+                rt.FillRectangle(rect, &fill);
+                rt.DrawRectangle(rect, &border, 1.0, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// "Übersichtsleiste": the pages of `layout` as light blocks in a dark strip, the part
+    /// in view at `scroll_y` as a window in the selection color
+    pub fn draw_minimap(&self, minimap: &Minimap, layout: &PageLayout, scroll_y: i32) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            let background = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.0, b: 0.0, a: 0.45 }, None)?;
+            let page = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.85, g: 0.85, b: 0.85, a: 0.9 }, None)?;
+            let fill = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.47, b: 0.84, a: 0.3 }, None)?;
+            let border = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.47, b: 0.84, a: 1.0 }, None)?;
+            let strip = D2D1_ROUNDED_RECT { rect: minimap.rect, radiusX: 4.0, radiusY: 4.0 };
+            rt.FillRoundedRectangle(&strip, &background);
+            for rect in minimap.page_rects(layout) {
+                rt.FillRectangle(&rect, &page);
+            }
+            let window = minimap.viewport_rect(scroll_y, self.height as i32);
+            rt.FillRectangle(&window, &fill);
+            rt.DrawRectangle(&window, &border, 1.0, None);
+        }
+
+        Ok(())
+    }
+
+    /// Draws the "could not open" panel centered in the viewport.
+    /// Returns the rectangle of the "Erneut versuchen" button for hit testing.
+    pub fn draw_error_panel(&self, filename: &str, message: &str) -> Result<Option<D2D_RECT_F>> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(None),
+        };
+
+        const PANEL_WIDTH: f32 = 460.0;
+        const PANEL_HEIGHT: f32 = 220.0;
+        const PADDING: f32 = 20.0;
+        const BUTTON_WIDTH: f32 = 150.0;
+        const BUTTON_HEIGHT: f32 = 32.0;
+
+        let left = ((self.width as f32 - PANEL_WIDTH) / 2.0).max(0.0);
+        let top = ((self.height as f32 - PANEL_HEIGHT) / 2.0).max(0.0);
+        let panel = D2D_RECT_F { left, top, right: left + PANEL_WIDTH, bottom: top + PANEL_HEIGHT };
+        let button = D2D_RECT_F {
+            left: left + (PANEL_WIDTH - BUTTON_WIDTH) / 2.0,
+            top: panel.bottom - PADDING - BUTTON_HEIGHT,
+            right: left + (PANEL_WIDTH + BUTTON_WIDTH) / 2.0,
+            bottom: panel.bottom - PADDING,
+        };
+
+        unsafe {
+            let dwrite = get_dwrite_factory()?;
+            let title_format = dwrite.CreateTextFormat(
+                w!("Segoe UI"),
+                None,
+                DWRITE_FONT_WEIGHT_SEMI_BOLD,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                16.0,
+                w!("de-de"),
+            )?;
+            let text_format = dwrite.CreateTextFormat(
+                w!("Segoe UI"),
+                None,
+                DWRITE_FONT_WEIGHT_NORMAL,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                13.0,
+                w!("de-de"),
+            )?;
+            text_format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+            title_format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+
+            let panel_brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.96, g: 0.96, b: 0.96, a: 1.0 }, None)?;
+            let text_brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.15, g: 0.15, b: 0.15, a: 1.0 }, None)?;
+            let error_brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.75, g: 0.1, b: 0.1, a: 1.0 }, None)?;
+            let button_brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.47, b: 0.84, a: 1.0 }, None)?;
+            let button_text_brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 1.0, g: 1.0, b: 1.0, a: 1.0 }, None)?;
+
+            let rounded = |rect: D2D_RECT_F, radius: f32| D2D1_ROUNDED_RECT { rect, radiusX: radius, radiusY: radius };
+            rt.FillRoundedRectangle(&rounded(panel, 6.0), &panel_brush);
+
+            let title: Vec<u16> = "Datei konnte nicht geöffnet werden".encode_utf16().collect();
+            let title_rect = D2D_RECT_F { left: left + PADDING, top: top + PADDING, right: panel.right - PADDING, bottom: top + PADDING + 24.0 };
+            rt.DrawText(&title, &title_format, &title_rect, &error_brush, D2D1_DRAW_TEXT_OPTIONS_NONE, DWRITE_MEASURING_MODE_NATURAL);
+
+            let body: Vec<u16> = format!("{}\n\n{}", filename, message).encode_utf16().collect();
+            let body_rect = D2D_RECT_F { left: left + PADDING, top: title_rect.bottom + 8.0, right: panel.right - PADDING, bottom: button.top - 8.0 };
+            rt.DrawText(&body, &text_format, &body_rect, &text_brush, D2D1_DRAW_TEXT_OPTIONS_CLIP, DWRITE_MEASURING_MODE_NATURAL);
+
+            rt.FillRoundedRectangle(&rounded(button, 4.0), &button_brush);
+            text_format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+            let label: Vec<u16> = "Erneut versuchen".encode_utf16().collect();
+            rt.DrawText(&label, &text_format, &button, &button_text_brush, D2D1_DRAW_TEXT_OPTIONS_NONE, DWRITE_MEASURING_MODE_NATURAL);
+        }
+
+        Ok(Some(button))
+    }
+
+    /// Floating quick-action bar in the top-right corner of a page (continuous mode).
+    /// Returns the button rectangles for hit testing.
+    pub fn draw_hover_toolbar(
+        &self,
+        page_rect: &D2D_RECT_F,
+        actions: &[HoverAction],
+        opacity: f32,
+    ) -> Result<Vec<(HoverAction, D2D_RECT_F)>> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(Vec::new()),
+        };
+
+        const MARGIN: f32 = 8.0;
+        const PADDING: f32 = 4.0;
+        const BUTTON_SIZE: f32 = 32.0;
+
+        let width = PADDING * 2.0 + BUTTON_SIZE * actions.len() as f32;
+        let right = page_rect.right - MARGIN;
+        let top = page_rect.top + MARGIN;
+        let bar = D2D_RECT_F { left: right - width, top, right, bottom: top + BUTTON_SIZE + PADDING * 2.0 };
+
+        let buttons: Vec<(HoverAction, D2D_RECT_F)> = actions
+            .iter()
+            .enumerate()
+            .map(|(i, &action)| {
+                let left = bar.left + PADDING + BUTTON_SIZE * i as f32;
+                (action, D2D_RECT_F { left, top: top + PADDING, right: left + BUTTON_SIZE, bottom: top + PADDING + BUTTON_SIZE })
+            })
+            .collect();
+
+        unsafe {
+            // Segoe MDL2 Assets: E7AD = Rotate (clockwise), E8C8 = Copy
+            let format = get_dwrite_factory()?.CreateTextFormat(
+                w!("Segoe MDL2 Assets"),
+                None,
+                DWRITE_FONT_WEIGHT_NORMAL,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                16.0,
+                w!("de-de"),
+            )?;
+            format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+            format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+
+            let background = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.15, g: 0.15, b: 0.15, a: 0.85 * opacity }, None)?;
+            let foreground = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 1.0, g: 1.0, b: 1.0, a: opacity }, None)?;
+            rt.FillRoundedRectangle(&D2D1_ROUNDED_RECT { rect: bar, radiusX: 4.0, radiusY: 4.0 }, &background);
+
+            for (action, rect) in &buttons {
+                let glyph: &[u16] = match action {
+                    HoverAction::RotateLeft | HoverAction::RotateRight => &[0xE7AD],
+                    HoverAction::Copy => &[0xE8C8],
+                };
+                // There is no counter-clockwise rotate glyph, so mirror the clockwise one
+                if *action == HoverAction::RotateLeft {
+                    let center_x = (rect.left + rect.right) / 2.0;
+                    rt.SetTransform(&Matrix3x2 { M11: -1.0, M12: 0.0, M21: 0.0, M22: 1.0, M31: 2.0 * center_x, M32: 0.0 });
                 }
+                rt.DrawText(glyph, &format, rect, &foreground, D2D1_DRAW_TEXT_OPTIONS_NONE, DWRITE_MEASURING_MODE_NATURAL);
+                rt.SetTransform(&make_identity_matrix());
             }
         }
+
+        Ok(buttons)
+    }
+
+    /// Keyboard focus indicator: 2px inset border around the view
+    pub fn draw_focus_border(&self) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            let brush = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.47, b: 0.84, a: 1.0 }, None)?;
+            let rect = D2D_RECT_F {
+                left: 1.0,
+                top: 1.0,
+                right: self.width as f32 - 1.0,
+                bottom: self.height as f32 - 1.0,
+            };
+            rt.DrawRectangle(&rect, &brush, 2.0, None);
+        }
+
+        Ok(())
+    }
+
+    /// Short notice ("Neues Dokument") centered at the top of the view
+    pub fn draw_osd(&self, text: &str) -> Result<()> {
+        const TOP: f32 = 24.0;
+        self.draw_text_box(text, 18.0, (18.0, 10.0), 8.0, |width, _| ((self.width as f32 - width) / 2.0, TOP))
+    }
+
+    /// Size chip next to the cursor while a rectangle is dragged, on the other side of the
+    /// cursor where it would leave the view
+    pub fn draw_info_chip(&self, text: &str, x: f32, y: f32) -> Result<()> {
+        const OFFSET: f32 = 16.0;
+        self.draw_text_box(text, 12.0, (8.0, 4.0), 4.0, |width, height| {
+            let left = if x + OFFSET + width > self.width as f32 { x - OFFSET - width } else { x + OFFSET };
+            let top = if y + OFFSET + height > self.height as f32 { y - OFFSET - height } else { y + OFFSET };
+            (left.max(0.0), top.max(0.0))
+        })
+    }
+
+    /// White text on a translucent dark box. `place` gets the size of the box and returns
+    /// its top left corner.
+    fn draw_text_box(&self, text: &str, font_size: f32, padding: (f32, f32), radius: f32, place: impl FnOnce(f32, f32) -> (f32, f32)) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        let (padding_x, padding_y) = padding;
+        let text: Vec<u16> = text.encode_utf16().collect();
+
+        unsafe {
+            let format = get_dwrite_factory()?.CreateTextFormat(
+                w!("Segoe UI"),
+                None,
+                DWRITE_FONT_WEIGHT_SEMI_BOLD,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                font_size,
+                w!("de-de"),
+            )?;
+            let layout = get_dwrite_factory()?.CreateTextLayout(&text, &format, self.width as f32, self.height as f32)?;
+            let mut metrics = DWRITE_TEXT_METRICS::default();
+            layout.GetMetrics(&mut metrics)?;
+
+            let (width, height) = (metrics.width + padding_x * 2.0, metrics.height + padding_y * 2.0);
+            let (left, top) = place(width, height);
+            let box_rect = D2D_RECT_F { left, top, right: left + width, bottom: top + height };
+            let background = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.0, b: 0.0, a: 0.75 }, None)?;
+            let foreground = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 1.0, g: 1.0, b: 1.0, a: 1.0 }, None)?;
+            rt.FillRoundedRectangle(&D2D1_ROUNDED_RECT { rect: box_rect, radiusX: radius, radiusY: radius }, &background);
+            rt.DrawTextLayout(
+                D2D_POINT_2F { x: left + padding_x, y: top + padding_y },
+                &layout,
+                &foreground,
+                D2D1_DRAW_TEXT_OPTIONS_NONE,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Debug overlay: monospaced text on a translucent box in the top-left corner
+    pub fn draw_debug_overlay(&self, text: &str) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        const PADDING: f32 = 8.0;
+        let text: Vec<u16> = text.encode_utf16().collect();
+
+        unsafe {
+            let format = get_dwrite_factory()?.CreateTextFormat(
+                w!("Consolas"),
+                None,
+                DWRITE_FONT_WEIGHT_NORMAL,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                12.0,
+                w!("de-de"),
+            )?;
+            let layout = get_dwrite_factory()?.CreateTextLayout(&text, &format, self.width as f32, self.height as f32)?;
+            let mut metrics = DWRITE_TEXT_METRICS::default();
+            layout.GetMetrics(&mut metrics)?;
+
+            let background = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.0, b: 0.0, a: 0.7 }, None)?;
+            let foreground = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.4, g: 1.0, b: 0.4, a: 1.0 }, None)?;
+            rt.FillRectangle(
+                &D2D_RECT_F {
+                    left: PADDING,
+                    top: PADDING,
+                    right: PADDING * 3.0 + metrics.width,
+                    bottom: PADDING * 3.0 + metrics.height,
+                },
+                &background,
+            );
+            rt.DrawTextLayout(
+                D2D_POINT_2F { x: PADDING * 2.0, y: PADDING * 2.0 },
+                &layout,
+                &foreground,
+                D2D1_DRAW_TEXT_OPTIONS_NONE,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// "Tastenkürzel" overview (F1): translucent panel centered over the view, the groups
+    /// split into two columns of keys and actions
+    pub fn draw_shortcut_overlay(&self, groups: &[ShortcutGroup]) -> Result<()> {
+        let rt = match &self.render_target {
+            Some(rt) => rt,
+            None => return Ok(()),
+        };
+
+        const PADDING: f32 = 24.0;
+        const SPACING: f32 = 12.0;
+        const KEY_GAP: f32 = 16.0;
+        const COLUMN_GAP: f32 = 40.0;
+
+        // Split where the first column reaches half of the lines (title + rows + blank line each)
+        let total_lines: usize = groups.iter().map(|(_, rows)| rows.len() + 2).sum();
+        let mut split = 0;
+        let mut lines = 0;
+        while split < groups.len() && lines < total_lines / 2 {
+            lines += groups[split].1.len() + 2;
+            split += 1;
+        }
+
+        unsafe {
+            let factory = get_dwrite_factory()?;
+            let text_format = |weight: DWRITE_FONT_WEIGHT, size: f32| {
+                factory.CreateTextFormat(
+                    w!("Segoe UI"),
+                    None,
+                    weight,
+                    DWRITE_FONT_STYLE_NORMAL,
+                    DWRITE_FONT_STRETCH_NORMAL,
+                    size,
+                    w!("de-de"),
+                )
+            };
+            let title_format = text_format(DWRITE_FONT_WEIGHT_SEMI_BOLD, 18.0)?;
+            let format = text_format(DWRITE_FONT_WEIGHT_NORMAL, 14.0)?;
+            let text_layout = |text: &str, format: &IDWriteTextFormat| -> Result<(IDWriteTextLayout, DWRITE_TEXT_METRICS)> {
+                let text: Vec<u16> = text.encode_utf16().collect();
+                let layout = factory.CreateTextLayout(&text, format, self.width as f32, self.height as f32)?;
+                let mut metrics = DWRITE_TEXT_METRICS::default();
+                layout.GetMetrics(&mut metrics)?;
+                Ok((layout, metrics))
+            };
+
+            // Keys and actions are separate layouts with the same line structure, so the
+            // rows line up; the group titles are bold lines in the keys layout
+            let mut columns = Vec::new();
+            for column in [&groups[..split], &groups[split..]] {
+                let mut keys = String::new();
+                let mut actions = String::new();
+                let mut titles = Vec::new();
+                for (i, (title, rows)) in column.iter().enumerate() {
+                    if i > 0 {
+                        keys.push('\n');
+                        actions.push('\n');
+                    }
+                    titles.push(DWRITE_TEXT_RANGE {
+                        startPosition: keys.encode_utf16().count() as u32,
+                        length: title.encode_utf16().count() as u32,
+                    });
+                    keys.push_str(title);
+                    for (key, action) in rows {
+                        keys.push('\n');
+                        keys.push_str(key);
+                        actions.push('\n');
+                        actions.push_str(action);
+                    }
+                }
+                let (keys_layout, keys_metrics) = text_layout(&keys, &format)?;
+                for range in titles {
+                    keys_layout.SetFontWeight(DWRITE_FONT_WEIGHT_SEMI_BOLD, range)?;
+                }
+                let (actions_layout, actions_metrics) = text_layout(&actions, &format)?;
+                columns.push((keys_layout, keys_metrics, actions_layout, actions_metrics));
+            }
+            let (title_layout, title_metrics) = text_layout("Tastenkürzel", &title_format)?;
+            let (hint_layout, hint_metrics) = text_layout("Beliebige Taste oder Klick schließt die Übersicht", &format)?;
+
+            let column_width = |(_, keys, _, actions): &(IDWriteTextLayout, DWRITE_TEXT_METRICS, IDWriteTextLayout, DWRITE_TEXT_METRICS)| {
+                keys.width + KEY_GAP + actions.width
+            };
+            let columns_width = columns.iter().map(column_width).sum::<f32>() + COLUMN_GAP;
+            let columns_height = columns.iter().map(|(_, keys, _, _)| keys.height).fold(0.0, f32::max);
+            let width = (columns_width.max(title_metrics.width).max(hint_metrics.width)) + PADDING * 2.0;
+            let height = title_metrics.height + columns_height + hint_metrics.height + SPACING * 2.0 + PADDING * 2.0;
+            let left = ((self.width as f32 - width) / 2.0).max(0.0);
+            let top = ((self.height as f32 - height) / 2.0).max(0.0);
+
+            let dim = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.0, b: 0.0, a: 0.35 }, None)?;
+            let background = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.0, g: 0.0, b: 0.0, a: 0.85 }, None)?;
+            let foreground = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 1.0, g: 1.0, b: 1.0, a: 1.0 }, None)?;
+            let key_color = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.55, g: 0.8, b: 1.0, a: 1.0 }, None)?;
+            let hint_color = rt.CreateSolidColorBrush(&D2D1_COLOR_F { r: 0.7, g: 0.7, b: 0.7, a: 1.0 }, None)?;
+
+            rt.FillRectangle(
+                &D2D_RECT_F { left: 0.0, top: 0.0, right: self.width as f32, bottom: self.height as f32 },
+                &dim,
+            );
+            let panel = D2D_RECT_F { left, top, right: left + width, bottom: top + height };
+            rt.FillRoundedRectangle(&D2D1_ROUNDED_RECT { rect: panel, radiusX: 8.0, radiusY: 8.0 }, &background);
+
+            let mut y = top + PADDING;
+            rt.DrawTextLayout(D2D_POINT_2F { x: left + PADDING, y }, &title_layout, &foreground, D2D1_DRAW_TEXT_OPTIONS_NONE);
+            y += title_metrics.height + SPACING;
+
+            let mut x = left + PADDING;
+            for column in &columns {
+                let (keys_layout, keys_metrics, actions_layout, _) = column;
+                rt.DrawTextLayout(D2D_POINT_2F { x, y }, keys_layout, &key_color, D2D1_DRAW_TEXT_OPTIONS_NONE);
+                rt.DrawTextLayout(
+                    D2D_POINT_2F { x: x + keys_metrics.width + KEY_GAP, y },
+                    actions_layout,
+                    &foreground,
+                    D2D1_DRAW_TEXT_OPTIONS_NONE,
+                );
+                x += column_width(column) + COLUMN_GAP;
+            }
+            y += columns_height + SPACING;
+
+            rt.DrawTextLayout(D2D_POINT_2F { x: left + PADDING, y }, &hint_layout, &hint_color, D2D1_DRAW_TEXT_OPTIONS_NONE);
+        }
+
+        Ok(())
+    }
+}
+
+/// `source` (source pixels of `page`) as a rectangle of its bitmap, which may be rendered
+/// at another resolution than the page size
+fn bitmap_source_rect(doc: &Document, page: usize, bitmap: &ID2D1Bitmap, source: [f32; 4]) -> D2D_RECT_F {
+    // Halves of a split double page share the bitmap of the whole image page
+    let (width, height) = doc.image_dimensions(page);
+    let [offset_x, offset_y, _, _] = doc.page_clip(page).unwrap_or_default();
+    let size = unsafe { bitmap.GetSize() };
+    let scale_x = if width > 0.0 { size.width / width } else { 1.0 };
+    let scale_y = if height > 0.0 { size.height / height } else { 1.0 };
+    D2D_RECT_F {
+        left: (source[0] + offset_x) * scale_x,
+        top: (source[1] + offset_y) * scale_y,
+        right: (source[2] + offset_x) * scale_x,
+        bottom: (source[3] + offset_y) * scale_y,
     }
 }
 
@@ -358,7 +1058,7 @@ fn make_identity_matrix() -> Matrix3x2 {
     }
 }
 
-fn make_rotation_matrix(angle_degrees: f32, center_x: f32, center_y: f32) -> Matrix3x2 {
+pub fn make_rotation_matrix(angle_degrees: f32, center_x: f32, center_y: f32) -> Matrix3x2 {
     let angle_radians = angle_degrees * std::f32::consts::PI / 180.0;
     let cos = angle_radians.cos();
     let sin = angle_radians.sin();
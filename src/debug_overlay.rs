@@ -0,0 +1,75 @@
+//! Debug overlay (Ctrl+Shift+D)
+//!
+//! Shows load and frame timings, cache usage, zoom/scroll and DPI for diagnosing slow
+//! machines. The timers are plain `Instant`s and always run; the text is only rebuilt
+//! every `UPDATE_INTERVAL` so the overlay does not distort the frame times it reports.
+
+use std::time::{Duration, Instant};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Values collected for one frame
+pub struct FrameStats {
+    pub pages_drawn: usize,
+    pub cache_entries: usize,
+    pub cache_bytes: usize,
+    pub zoom: f32,
+    pub scroll_x: i32,
+    pub scroll_y: i32,
+    pub dpi: u32,
+}
+
+#[derive(Default)]
+pub struct DebugOverlay {
+    enabled: bool,
+    load_time: Option<Duration>,
+    last_frame_time: Option<Duration>,
+    text: String,
+    last_update: Option<Instant>,
+}
+
+impl DebugOverlay {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        // Show fresh values right away
+        self.last_update = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Time from opening the file to the finished `Document`
+    pub fn set_load_time(&mut self, time: Duration) {
+        self.load_time = Some(time);
+    }
+
+    /// Time from `BeginDraw` to `EndDraw` of the last frame
+    pub fn set_frame_time(&mut self, time: Duration) {
+        self.last_frame_time = Some(time);
+    }
+
+    /// Overlay text, rebuilt at most every `UPDATE_INTERVAL`
+    pub fn text(&mut self, stats: &FrameStats) -> &str {
+        let due = self.last_update.is_none_or(|t| t.elapsed() >= UPDATE_INTERVAL);
+        if due {
+            let ms = |d: Option<Duration>| {
+                d.map_or("–".to_string(), |d| format!("{:.1} ms", d.as_secs_f64() * 1000.0))
+            };
+            self.text = format!(
+                "Laden: {}\nLetzter Frame: {}\nSeiten im Frame: {}\nBitmap-Cache: {} ({:.1} MB)\nZoom: {:.0} %\nScroll: {}, {}\nDPI: {}",
+                ms(self.load_time),
+                ms(self.last_frame_time),
+                stats.pages_drawn,
+                stats.cache_entries,
+                stats.cache_bytes as f64 / (1024.0 * 1024.0),
+                stats.zoom * 100.0,
+                stats.scroll_x,
+                stats.scroll_y,
+                stats.dpi,
+            );
+            self.last_update = Some(Instant::now());
+        }
+        &self.text
+    }
+}
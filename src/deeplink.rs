@@ -0,0 +1,88 @@
+//! simpliview:// deep links
+//!
+//! The practice software opens documents with links like
+//! `simpliview://open?file=C%3A%5CAkten%5Cscan.pdf&page=5`. The link is only parsed
+//! into a file path and page number here; opening goes through the normal
+//! command-line path, so a link can never do more than a file argument could.
+
+use crate::utils::percent_decode;
+
+pub const PROTOCOL_SCHEME: &str = "simpliview";
+
+/// Parsed `simpliview://open?...` link
+pub struct DeepLink {
+    pub file: String,
+    /// 1-based page number
+    pub page: Option<usize>,
+}
+
+/// Returns true if the argument looks like a simpliview: URI
+pub fn is_deep_link(arg: &str) -> bool {
+    arg.get(..PROTOCOL_SCHEME.len() + 1)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("simpliview:"))
+}
+
+/// Parses a deep link. The error string is shown to the user as is.
+pub fn parse(uri: &str) -> std::result::Result<DeepLink, String> {
+    let rest = &uri[PROTOCOL_SCHEME.len() + 1..];
+    let rest = rest.trim_start_matches('/');
+
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    if !action.trim_end_matches('/').eq_ignore_ascii_case("open") {
+        return Err(format!("Unbekannte Aktion \"{}\".", action));
+    }
+
+    let mut file = None;
+    let mut page = None;
+    for param in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = percent_decode(value);
+        match key.to_ascii_lowercase().as_str() {
+            "file" if file.is_some() => {
+                return Err("Der Parameter \"file\" ist mehrfach angegeben.".to_string());
+            }
+            "file" => file = Some(value),
+            "page" => match value.parse::<usize>() {
+                Ok(n) if n >= 1 => page = Some(n),
+                _ => return Err(format!("Ungültige Seitenzahl \"{}\".", value)),
+            },
+            // Unknown parameters are ignored for forward compatibility
+            _ => {}
+        }
+    }
+
+    let file = file.ok_or_else(|| "Der Parameter \"file\" fehlt.".to_string())?;
+    validate_file_path(&file)?;
+
+    Ok(DeepLink { file, page })
+}
+
+/// Only absolute local or UNC paths are accepted - no URLs, relative paths or
+/// command-line fragments.
+fn validate_file_path(file: &str) -> std::result::Result<(), String> {
+    let invalid = || format!("Ungültiger Dateipfad \"{}\".", file);
+
+    if file.chars().any(|c| c.is_control() || c == '"') {
+        return Err(invalid());
+    }
+
+    let bytes = file.as_bytes();
+    let is_drive_path = bytes.len() > 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    let is_unc_path = file.starts_with(r"\\") && !file.starts_with(r"\\?\") && !file.starts_with(r"\\.\");
+    if !is_drive_path && !is_unc_path {
+        return Err(invalid());
+    }
+
+    Ok(())
+}
+
+/// Returns true if `file` lies inside the directory `root` (both resolved)
+pub fn is_within_root(file: &str, root: &str) -> bool {
+    match (std::fs::canonicalize(file), std::fs::canonicalize(root)) {
+        (Ok(file), Ok(root)) => file.starts_with(root),
+        _ => false,
+    }
+}
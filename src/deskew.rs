@@ -0,0 +1,239 @@
+//! "Schräglage automatisch korrigieren": pages scanned at a slight angle are shown straight
+//!
+//! The page is taken as displayed (rotation applied, so text lines run across), downscaled
+//! to `MAX_SIZE` and measured on a worker thread; the result comes back with
+//! WM_APP_SKEW. The angle is found with projection profiles: the dark pixels are summed
+//! along lines of each candidate angle, and at the skew of the text the sums are sharpest
+//! (full on the lines, empty between them). Pages without such lines - photos, blank pages
+//! - get no angle.
+//!
+//! The correction is a small extra rotation of the display; export can write it into the
+//! image (`straighten`).
+
+use crate::wic::ClipboardBitmapData;
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+/// Posted to the main window; lparam owns a `Box<Skew>` (see `take_result`)
+pub const WM_APP_SKEW: u32 = WM_APP + 17;
+/// Longest side of the page measured
+pub const MAX_SIZE: u32 = 1000;
+
+/// Largest skew looked for, in degrees either way; larger angles are not scan skew
+const MAX_ANGLE: f32 = 5.0;
+const COARSE_STEP: f32 = 0.5;
+const FINE_STEP: f32 = 0.05;
+/// Smaller angles are not worth correcting
+const MIN_ANGLE: f32 = 0.1;
+/// A gray value below this is ink
+const DARK_THRESHOLD: u8 = 128;
+/// Ink needed for a measurement, per thousand of the pixels
+const MIN_DARK_PER_MILLE: usize = 2;
+/// The sharpest profile must beat the flattest by this factor, else there are no lines
+const MIN_CONTRAST: f64 = 1.2;
+
+/// Measured skew of a page
+pub struct Skew {
+    /// Open file when the measurement started, so a late result of another file is dropped
+    pub path: Option<String>,
+    pub page: usize,
+    /// Clockwise tilt of the text lines in degrees, `None` if there is nothing to correct
+    pub angle: Option<f32>,
+}
+
+/// Measures `pixels` (top-down BGRA as from `WicLoader::get_page_pixels`) on a worker
+/// thread and posts the `Skew` to `hwnd`. False if the thread could not be started.
+pub fn start(hwnd: HWND, path: Option<String>, page: usize, pixels: ClipboardBitmapData) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView Deskew".to_string())
+        .spawn(move || {
+            let gray: Vec<u8> = pixels
+                .data
+                .chunks_exact(4)
+                // Transparent pixels are background
+                .map(|p| if p[3] < 128 { 255 } else { ((p[0] as u32 * 29 + p[1] as u32 * 150 + p[2] as u32 * 77) >> 8) as u8 })
+                .collect();
+            let angle = estimate_angle(&gray, pixels.width as usize, pixels.height as usize);
+            let result = Box::into_raw(Box::new(Skew { path, page, angle }));
+            if !unsafe { PostMessageW(HWND(hwnd_raw), WM_APP_SKEW, WPARAM(0), LPARAM(result as isize)) }.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
+            }
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the result posted with WM_APP_SKEW
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_SKEW message and be taken only once.
+pub unsafe fn take_result(lparam: LPARAM) -> Skew {
+    *Box::from_raw(lparam.0 as *mut Skew)
+}
+
+/// Clockwise tilt in degrees of the text lines in a `width` x `height` grayscale image, one
+/// byte per pixel. `None` for images without enough ink or without lines, and for tilts
+/// too small to correct.
+pub fn estimate_angle(gray: &[u8], width: usize, height: usize) -> Option<f32> {
+    if width == 0 || height == 0 || gray.len() < width * height {
+        return None;
+    }
+    let dark: Vec<(f32, f32)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| gray[y * width + x] < DARK_THRESHOLD)
+        .map(|(x, y)| (x as f32, y as f32))
+        .collect();
+    if dark.len() < (width * height * MIN_DARK_PER_MILLE / 1000).max(1) {
+        return None;
+    }
+
+    let steps = (MAX_ANGLE / COARSE_STEP).round() as i32;
+    let coarse: Vec<(f32, f64)> = (-steps..=steps)
+        .map(|i| i as f32 * COARSE_STEP)
+        .map(|angle| (angle, sharpness(&dark, width, height, angle)))
+        .collect();
+    let (coarse_best, best_score) = coarse.iter().copied().fold((0.0, f64::MIN), |best, c| if c.1 > best.1 { c } else { best });
+    let flattest = coarse.iter().map(|c| c.1).fold(f64::MAX, f64::min);
+    if best_score < flattest * MIN_CONTRAST {
+        return None;
+    }
+
+    let steps = (COARSE_STEP / FINE_STEP).round() as i32;
+    let (angle, _) = (-steps..=steps)
+        .map(|i| coarse_best + i as f32 * FINE_STEP)
+        .map(|angle| (angle, sharpness(&dark, width, height, angle)))
+        .fold((coarse_best, f64::MIN), |best, c| if c.1 > best.1 { c } else { best });
+    (angle.abs() >= MIN_ANGLE).then_some(angle)
+}
+
+/// Sum of squares of the projection profile along lines tilted clockwise by `angle`
+fn sharpness(dark: &[(f32, f32)], width: usize, height: usize, angle: f32) -> f64 {
+    let slope = angle.to_radians().tan();
+    // Rows of a tilted line through the whole width shift by up to this much
+    let shift = (width as f32 * slope.abs()).ceil() as usize + 1;
+    let mut profile = vec![0u32; height + 2 * shift];
+    for &(x, y) in dark {
+        // Row where the line through (x, y) meets the left edge
+        let row = (y - x * slope).round() as isize + shift as isize;
+        if let Some(count) = profile.get_mut(row.max(0) as usize) {
+            *count += 1;
+        }
+    }
+    profile.iter().map(|&count| count as f64 * count as f64).sum()
+}
+
+/// `pixels` (top-down BGRA) turned counterclockwise by `angle` degrees about the center,
+/// so lines tilted clockwise by `angle` come out straight. The size stays; the corners
+/// that come in are white. The result is premultiplied, as `create_bitmap_from_data`
+/// expects.
+pub fn straighten(pixels: &ClipboardBitmapData, angle: f32) -> Vec<u8> {
+    let (width, height) = (pixels.width as usize, pixels.height as usize);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+    let sample = |x: isize, y: isize, channel: usize| -> f32 {
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            return 255.0;
+        }
+        let i = (y as usize * width + x as usize) * 4;
+        let alpha = pixels.data[i + 3] as f32 / 255.0;
+        match channel {
+            3 => 255.0,
+            // Transparent parts become white like the corners
+            _ => pixels.data[i + channel] as f32 * alpha + 255.0 * (1.0 - alpha),
+        }
+    };
+
+    let mut out = vec![0u8; width * height * 4];
+    for y in 0..height {
+        for x in 0..width {
+            // Source point that lands on (x, y): turned back clockwise
+            let (dx, dy) = (x as f32 + 0.5 - center_x, y as f32 + 0.5 - center_y);
+            let sx = center_x + dx * cos - dy * sin - 0.5;
+            let sy = center_y + dx * sin + dy * cos - 0.5;
+            let (x0, y0) = (sx.floor() as isize, sy.floor() as isize);
+            let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+            let i = (y * width + x) * 4;
+            for channel in 0..4 {
+                let top = sample(x0, y0, channel) * (1.0 - fx) + sample(x0 + 1, y0, channel) * fx;
+                let bottom = sample(x0, y0 + 1, channel) * (1.0 - fx) + sample(x0 + 1, y0 + 1, channel) * fx;
+                out[i + channel] = (top * (1.0 - fy) + bottom * fy).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 400;
+    const HEIGHT: usize = 300;
+
+    /// White page with lines of "words" tilted clockwise by `angle`, like a skewed scan
+    fn text_page(angle: f32) -> Vec<u8> {
+        let slope = angle.to_radians().tan();
+        let mut gray = vec![255u8; WIDTH * HEIGHT];
+        for line in (30..HEIGHT - 30).step_by(20) {
+            for x in 20..WIDTH - 20 {
+                // Words of 30 pixels with gaps of 8
+                if x % 38 >= 30 {
+                    continue;
+                }
+                let y = line as f32 + x as f32 * slope;
+                for dy in 0..3 {
+                    let y = y.round() as isize + dy;
+                    if (0..HEIGHT as isize).contains(&y) {
+                        gray[y as usize * WIDTH + x] = 0;
+                    }
+                }
+            }
+        }
+        gray
+    }
+
+    fn assert_near(angle: Option<f32>, expected: f32) {
+        let angle = angle.unwrap_or_else(|| panic!("no angle, expected {expected}"));
+        assert!((angle - expected).abs() <= 2.0 * FINE_STEP, "{angle} vs {expected}");
+    }
+
+    #[test]
+    fn finds_the_tilt_of_text_lines() {
+        for expected in [2.0, -1.3, 0.35, 4.5] {
+            assert_near(estimate_angle(&text_page(expected), WIDTH, HEIGHT), expected);
+        }
+    }
+
+    #[test]
+    fn straight_pages_are_left_alone() {
+        assert_eq!(estimate_angle(&text_page(0.0), WIDTH, HEIGHT), None);
+        assert_eq!(estimate_angle(&text_page(0.04), WIDTH, HEIGHT), None);
+    }
+
+    #[test]
+    fn pages_without_lines_get_no_angle() {
+        assert_eq!(estimate_angle(&vec![255; WIDTH * HEIGHT], WIDTH, HEIGHT), None);
+        // Noise, as in a photo
+        let mut seed = 1u32;
+        let noise: Vec<u8> = (0..WIDTH * HEIGHT)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        assert_eq!(estimate_angle(&noise, WIDTH, HEIGHT), None);
+        assert_eq!(estimate_angle(&[], 0, 0), None);
+        assert_eq!(estimate_angle(&[0; 10], WIDTH, HEIGHT), None);
+    }
+
+    #[test]
+    fn straightened_page_measures_straight() {
+        let gray = text_page(2.0);
+        let pixels = ClipboardBitmapData {
+            width: WIDTH as u32,
+            height: HEIGHT as u32,
+            data: gray.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        };
+        let straight: Vec<u8> = straighten(&pixels, 2.0).chunks_exact(4).map(|p| p[0]).collect();
+        assert_eq!(estimate_angle(&straight, WIDTH, HEIGHT), None);
+    }
+}
@@ -1,10 +1,16 @@
+use crate::contact_sheet;
+use crate::document::Document;
+use crate::page_picker::PagePicker;
 use windows::{
     core::*,
     Win32::{
         Foundation::*,
+        Graphics::Gdi::{GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONEAREST},
         System::Com::*,
         System::LibraryLoader::*,
+        System::Threading::GetCurrentThreadId,
         UI::{
+            Controls::*,
             Input::KeyboardAndMouse::SetFocus,
             Shell::*,
             Shell::Common::*,
@@ -17,7 +23,7 @@ use windows::{
 const FILE_TYPES: &[COMDLG_FILTERSPEC] = &[
     COMDLG_FILTERSPEC {
         pszName: w!("All Supported Files"),
-        pszSpec: w!("*.pdf;*.jpg;*.jpeg;*.png;*.bmp;*.tif;*.tiff;*.webp"),
+        pszSpec: w!("*.pdf;*.jpg;*.jpeg;*.png;*.bmp;*.tif;*.tiff;*.webp;*.gif;*.heic;*.avif;*.ico;*.zip;*.cbz;*.dcm"),
     },
     COMDLG_FILTERSPEC {
         pszName: w!("PDF Documents"),
@@ -25,7 +31,15 @@ const FILE_TYPES: &[COMDLG_FILTERSPEC] = &[
     },
     COMDLG_FILTERSPEC {
         pszName: w!("Images"),
-        pszSpec: w!("*.jpg;*.jpeg;*.png;*.bmp;*.tif;*.tiff;*.webp"),
+        pszSpec: w!("*.jpg;*.jpeg;*.png;*.bmp;*.tif;*.tiff;*.webp;*.gif;*.heic;*.avif;*.ico"),
+    },
+    COMDLG_FILTERSPEC {
+        pszName: w!("Image Archives"),
+        pszSpec: w!("*.zip;*.cbz"),
+    },
+    COMDLG_FILTERSPEC {
+        pszName: w!("DICOM Images"),
+        pszSpec: w!("*.dcm"),
     },
     COMDLG_FILTERSPEC {
         pszName: w!("All Files"),
@@ -42,6 +56,8 @@ const SAVE_TYPES: &[COMDLG_FILTERSPEC] = &[
     COMDLG_FILTERSPEC { pszName: w!("PDF Document (*.pdf)"), pszSpec: w!("*.pdf") },
 ];
 
+const TEXT_TYPES: &[COMDLG_FILTERSPEC] = &[COMDLG_FILTERSPEC { pszName: w!("Text File (*.txt)"), pszSpec: w!("*.txt") }];
+
 fn get_save_type_index(ext: &str) -> (u32, PCWSTR) {
     match ext.to_lowercase().as_str() {
         "jpg" | "jpeg" => (2, w!("jpg")),
@@ -53,35 +69,200 @@ fn get_save_type_index(ext: &str) -> (u32, PCWSTR) {
     }
 }
 
+/// Index into FILE_TYPES for a --default-filter / OpenFilter value (1-based)
+fn get_open_type_index(filter: &str) -> u32 {
+    match filter.trim_start_matches("*.").trim_start_matches('.').to_lowercase().as_str() {
+        "pdf" => 2,
+        "images" | "bilder" | "jpg" | "jpeg" | "png" | "bmp" | "tif" | "tiff" | "webp" | "gif" | "heic" | "avif" | "ico" => 3,
+        "archives" | "archive" | "zip" | "cbz" => 4,
+        "dicom" | "dcm" => 5,
+        "all" | "alle" | "*" => 6,
+        _ => 1, // All supported files
+    }
+}
+
+// Custom controls of the export dialog
+const IDC_APPLY_ROTATION: u32 = 1;
+const IDC_WEBP_LOSSLESS: u32 = 2;
+const IDC_EXPORT_CROPPED: u32 = 10;
+const IDC_EXPORT_DESKEWED: u32 = 11;
+// Custom controls of the open dialog
+const IDC_INCLUDE_SUBFOLDERS: u32 = 3;
+const IDC_FILE_INFO: u32 = 9;
+// Custom controls of the contact sheet dialog
+const IDC_SHEET_COLUMNS_GROUP: u32 = 4;
+const IDC_SHEET_COLUMNS: u32 = 5;
+const IDC_SHEET_MARGIN_GROUP: u32 = 6;
+const IDC_SHEET_MARGIN: u32 = 7;
+const IDC_SHEET_PAGE_NUMBERS: u32 = 8;
+/// Column counts offered for the contact sheet; the item ID is the count
+const SHEET_COLUMNS: std::ops::RangeInclusive<u32> = 2..=10;
+/// Margins offered for the contact sheet; the item ID is the margin in pixels
+const SHEET_MARGINS: [(u32, PCWSTR); 3] = [(8, w!("Schmal")), (16, w!("Mittel")), (32, w!("Breit"))];
+
+/// Files and options chosen in the open dialog
+pub struct OpenSelection {
+    pub paths: Vec<String>,
+    /// "Unterordner in die Navigation einbeziehen"
+    pub include_subfolders: bool,
+}
+
+/// File and options chosen in the export dialog
+pub struct ExportTarget {
+    pub path: String,
+    /// "Drehung übernehmen": write the page as rotated on screen
+    pub apply_rotation: bool,
+    /// "WebP verlustfrei speichern"; only used when the target is a .webp file
+    pub webp_lossless: bool,
+    /// "Zugeschnitten exportieren": only the part shown with "Ränder automatisch zuschneiden"
+    pub cropped: bool,
+    /// "Schräglage korrigieren": write the page straightened as shown
+    pub deskewed: bool,
+}
+
+/// Open dialog events: shows the facts of the selected file below the file name
+#[implement(IFileDialogEvents)]
+struct OpenDialogEvents;
+
+impl IFileDialogEvents_Impl for OpenDialogEvents {
+    fn OnFileOk(&self, _: Option<&IFileDialog>) -> Result<()> {
+        Ok(())
+    }
+
+    fn OnFolderChanging(&self, _: Option<&IFileDialog>, _: Option<&IShellItem>) -> Result<()> {
+        Ok(())
+    }
+
+    fn OnFolderChange(&self, _: Option<&IFileDialog>) -> Result<()> {
+        Ok(())
+    }
+
+    fn OnSelectionChange(&self, dialog: Option<&IFileDialog>) -> Result<()> {
+        let Some(dialog) = dialog else { return Ok(()) };
+        let customize: IFileDialogCustomize = dialog.cast()?;
+        let text = unsafe { dialog.GetCurrentSelection() }
+            .ok()
+            .and_then(|item| unsafe { item.GetDisplayName(SIGDN_FILESYSPATH) }.ok())
+            .and_then(|path| {
+                let text = unsafe { path.to_string() }.ok();
+                unsafe { CoTaskMemFree(Some(path.0 as *const _)) };
+                text
+            })
+            .and_then(|path| crate::file_probe::describe(&path))
+            .unwrap_or_default();
+        unsafe { customize.SetControlLabel(IDC_FILE_INFO, &HSTRING::from(text)) }
+    }
+
+    fn OnShareViolation(&self, _: Option<&IFileDialog>, _: Option<&IShellItem>) -> Result<FDE_SHAREVIOLATION_RESPONSE> {
+        Ok(FDESVR_DEFAULT)
+    }
+
+    fn OnTypeChange(&self, _: Option<&IFileDialog>) -> Result<()> {
+        Ok(())
+    }
+
+    fn OnOverwrite(&self, _: Option<&IFileDialog>, _: Option<&IShellItem>) -> Result<FDE_OVERWRITE_RESPONSE> {
+        Ok(FDEOR_DEFAULT)
+    }
+}
+
 pub struct FileDialogs {
     pub restricted_path: Option<String>,
+    /// Preselected filter of the open dialog (--default-filter or OpenFilter setting)
+    default_filter: Option<String>,
+    /// Start folder of the open dialog (--start-folder or OpenFolder setting)
+    start_folder: Option<String>,
 }
 
 impl FileDialogs {
-    pub fn new(restricted_path: Option<String>) -> Self {
-        Self { restricted_path }
+    /// `default_filter` / `start_folder` come from the command line and fall back to the settings
+    pub fn new(restricted_path: Option<String>, default_filter: Option<String>, start_folder: Option<String>) -> Self {
+        Self {
+            restricted_path,
+            default_filter: default_filter.or_else(crate::settings::open_filter),
+            start_folder: start_folder.or_else(crate::settings::open_folder),
+        }
     }
 
-    pub fn open_file(&self, parent: HWND) -> Option<String> {
+    /// Open dialog; several files can be selected. No paths if cancelled.
+    pub fn open_file(&self, parent: HWND) -> OpenSelection {
+        let include_subfolders = crate::settings::include_subfolders();
+        let paths = unsafe { self.show_open_dialog(parent, include_subfolders) };
+        match paths {
+            Some((paths, include_subfolders)) => {
+                crate::settings::set_include_subfolders(include_subfolders);
+                OpenSelection { paths, include_subfolders }
+            }
+            None => OpenSelection { paths: Vec::new(), include_subfolders },
+        }
+    }
+
+    unsafe fn show_open_dialog(&self, parent: HWND, include_subfolders: bool) -> Option<(Vec<String>, bool)> {
+        // Create file open dialog
+        let dialog: IFileOpenDialog =
+            CoCreateInstance(&FileOpenDialog, None, CLSCTX_INPROC_SERVER).ok()?;
+
+        // Set file types
+        dialog.SetFileTypes(FILE_TYPES).ok()?;
+        dialog.SetFileTypeIndex(self.default_filter.as_deref().map_or(1, get_open_type_index)).ok()?;
+
+        // Set options
+        let options = dialog.GetOptions().ok()?;
+        dialog.SetOptions(options | FOS_FORCEFILESYSTEM | FOS_FILEMUSTEXIST | FOS_ALLOWMULTISELECT).ok()?;
+
+        // Configured start folder; a missing folder leaves the shell's default
+        if let Some(folder) = self.start_folder.as_deref().filter(|f| std::path::Path::new(f).is_dir()) {
+            self.apply_folder_restriction(&dialog, folder);
+        }
+
+        let customize = dialog.cast::<IFileDialogCustomize>().ok();
+        if let Some(ref customize) = customize {
+            let _ = customize.AddCheckButton(IDC_INCLUDE_SUBFOLDERS, w!("Unterordner in die Navigation einbeziehen"), include_subfolders);
+            let _ = customize.AddText(IDC_FILE_INFO, w!(""));
+        }
+        // Type, page count and size of the selected file (see file_probe.rs)
+        let events: IFileDialogEvents = OpenDialogEvents.into();
+        let cookie = customize.as_ref().and_then(|_| dialog.Advise(&events).ok());
+
+        // Show dialog
+        let shown = dialog.Show(dialog_owner(parent));
+        if let Some(cookie) = cookie {
+            let _ = dialog.Unadvise(cookie);
+        }
+        if shown.is_err() {
+            return None;
+        }
+
+        // Get results
+        let results = dialog.GetResults().ok()?;
+        let mut paths = Vec::new();
+        for i in 0..results.GetCount().ok()? {
+            let Ok(item) = results.GetItemAt(i) else { continue };
+            let Ok(path) = item.GetDisplayName(SIGDN_FILESYSPATH) else { continue };
+            if let Ok(path_str) = path.to_string() {
+                paths.push(path_str);
+            }
+            CoTaskMemFree(Some(path.0 as *const _));
+        }
+        let include_subfolders = customize
+            .and_then(|c| c.GetCheckButtonState(IDC_INCLUDE_SUBFOLDERS).ok())
+            .map_or(include_subfolders, |b| b.as_bool());
+        Some((paths, include_subfolders))
+    }
+
+    /// Folder picker, e.g. for the scan inbox
+    pub fn pick_folder(&self, parent: HWND, title: &str) -> Option<String> {
         unsafe {
-            // Create file open dialog
             let dialog: IFileOpenDialog =
                 CoCreateInstance(&FileOpenDialog, None, CLSCTX_INPROC_SERVER).ok()?;
-
-            // Set file types
-            dialog.SetFileTypes(FILE_TYPES).ok()?;
-            dialog.SetFileTypeIndex(1).ok()?;
-
-            // Set options
             let options = dialog.GetOptions().ok()?;
-            dialog.SetOptions(options | FOS_FORCEFILESYSTEM | FOS_FILEMUSTEXIST).ok()?;
+            dialog.SetOptions(options | FOS_PICKFOLDERS | FOS_FORCEFILESYSTEM | FOS_PATHMUSTEXIST).ok()?;
+            dialog.SetTitle(&HSTRING::from(title)).ok()?;
 
-            // Show dialog
-            if dialog.Show(parent).is_err() {
+            if dialog.Show(dialog_owner(parent)).is_err() {
                 return None;
             }
 
-            // Get result
             let result = dialog.GetResult().ok()?;
             let path = result.GetDisplayName(SIGDN_FILESYSPATH).ok()?;
             let path_str = path.to_string().ok()?;
@@ -91,7 +272,110 @@ impl FileDialogs {
         }
     }
 
-    pub fn save_file(&self, parent: HWND, default_filename: Option<&str>, original_extension: Option<&str>) -> Option<String> {
+    /// Export dialog. `rotated` adds the "Drehung übernehmen" checkbox (checked by default),
+    /// `cropped` the "Zugeschnitten exportieren" checkbox (unchecked by default), `deskewed`
+    /// the "Schräglage korrigieren" checkbox (checked by default);
+    /// "WebP verlustfrei speichern" is always offered and checked by default.
+    pub fn save_file(
+        &self,
+        parent: HWND,
+        default_filename: Option<&str>,
+        original_extension: Option<&str>,
+        rotated: bool,
+        cropped: bool,
+        deskewed: bool,
+    ) -> Option<ExportTarget> {
+        let ext = original_extension.unwrap_or("png");
+        let add_controls = |customize: &IFileDialogCustomize| unsafe {
+            if rotated {
+                let _ = customize.AddCheckButton(IDC_APPLY_ROTATION, w!("Drehung übernehmen"), TRUE);
+            }
+            if cropped {
+                let _ = customize.AddCheckButton(IDC_EXPORT_CROPPED, w!("Zugeschnitten exportieren"), FALSE);
+            }
+            if deskewed {
+                let _ = customize.AddCheckButton(IDC_EXPORT_DESKEWED, w!("Schräglage korrigieren"), TRUE);
+            }
+            let _ = customize.AddCheckButton(IDC_WEBP_LOSSLESS, w!("WebP verlustfrei speichern"), TRUE);
+        };
+        let read_controls = |customize: Option<&IFileDialogCustomize>| unsafe {
+            let apply_rotation = match (rotated, customize) {
+                (true, Some(customize)) => customize.GetCheckButtonState(IDC_APPLY_ROTATION).map_or(true, |b| b.as_bool()),
+                _ => rotated,
+            };
+            let webp_lossless = customize
+                .and_then(|c| c.GetCheckButtonState(IDC_WEBP_LOSSLESS).ok())
+                .is_none_or(|b| b.as_bool());
+            let export_cropped = cropped
+                && customize
+                    .and_then(|c| c.GetCheckButtonState(IDC_EXPORT_CROPPED).ok())
+                    .is_some_and(|b| b.as_bool());
+            let export_deskewed = match (deskewed, customize) {
+                (true, Some(customize)) => customize.GetCheckButtonState(IDC_EXPORT_DESKEWED).map_or(true, |b| b.as_bool()),
+                _ => deskewed,
+            };
+            (apply_rotation, webp_lossless, export_cropped, export_deskewed)
+        };
+        let (path, (apply_rotation, webp_lossless, cropped, deskewed)) =
+            self.show_save_dialog(parent, SAVE_TYPES, default_filename, ext, add_controls, read_controls)?;
+        Some(ExportTarget { path, apply_rotation, webp_lossless, cropped, deskewed })
+    }
+
+    /// "Übersichtsbild exportieren": image formats only, with the grid options preset from
+    /// `options` and returned as chosen
+    pub fn save_contact_sheet(&self, parent: HWND, default_filename: &str, options: &contact_sheet::Options) -> Option<(String, contact_sheet::Options)> {
+        let add_controls = |customize: &IFileDialogCustomize| unsafe {
+            let _ = customize.StartVisualGroup(IDC_SHEET_COLUMNS_GROUP, w!("Spalten:"));
+            let _ = customize.AddComboBox(IDC_SHEET_COLUMNS);
+            for columns in SHEET_COLUMNS {
+                let _ = customize.AddControlItem(IDC_SHEET_COLUMNS, columns, &HSTRING::from(columns.to_string()));
+            }
+            let _ = customize.SetSelectedControlItem(IDC_SHEET_COLUMNS, options.columns);
+            let _ = customize.EndVisualGroup();
+            let _ = customize.StartVisualGroup(IDC_SHEET_MARGIN_GROUP, w!("Abstand:"));
+            let _ = customize.AddComboBox(IDC_SHEET_MARGIN);
+            for (margin, label) in SHEET_MARGINS {
+                let _ = customize.AddControlItem(IDC_SHEET_MARGIN, margin, label);
+            }
+            let _ = customize.SetSelectedControlItem(IDC_SHEET_MARGIN, options.margin);
+            let _ = customize.EndVisualGroup();
+            let _ = customize.AddCheckButton(IDC_SHEET_PAGE_NUMBERS, w!("Seitenzahlen anzeigen"), options.page_numbers);
+        };
+        let read_controls = |customize: Option<&IFileDialogCustomize>| unsafe {
+            let Some(customize) = customize else { return options.clone() };
+            contact_sheet::Options {
+                columns: customize.GetSelectedControlItem(IDC_SHEET_COLUMNS).unwrap_or(options.columns),
+                margin: customize.GetSelectedControlItem(IDC_SHEET_MARGIN).unwrap_or(options.margin),
+                page_numbers: customize.GetCheckButtonState(IDC_SHEET_PAGE_NUMBERS).map_or(options.page_numbers, |b| b.as_bool()),
+            }
+        };
+        // Without the PDF entry at the end of SAVE_TYPES
+        let image_types = &SAVE_TYPES[..SAVE_TYPES.len() - 1];
+        self.show_save_dialog(parent, image_types, Some(default_filename), "png", add_controls, read_controls)
+    }
+
+    /// "Seiten extrahieren": PDF only, no options
+    pub fn save_pdf(&self, parent: HWND, default_filename: &str) -> Option<String> {
+        let pdf_types = &SAVE_TYPES[SAVE_TYPES.len() - 1..];
+        self.show_save_dialog(parent, pdf_types, Some(default_filename), "pdf", |_| {}, |_| ()).map(|(path, ())| path)
+    }
+
+    /// "Als Textdatei speichern" of the recognized text
+    pub fn save_text(&self, parent: HWND, default_filename: &str) -> Option<String> {
+        self.show_save_dialog(parent, TEXT_TYPES, Some(default_filename), "txt", |_| {}, |_| ()).map(|(path, ())| path)
+    }
+
+    /// Save dialog, shown again until the path is inside the restricted folder (if any).
+    /// `add_controls` adds custom controls, `read_controls` reads them after OK.
+    fn show_save_dialog<T>(
+        &self,
+        parent: HWND,
+        types: &[COMDLG_FILTERSPEC],
+        default_filename: Option<&str>,
+        ext: &str,
+        add_controls: impl Fn(&IFileDialogCustomize),
+        read_controls: impl Fn(Option<&IFileDialogCustomize>) -> T,
+    ) -> Option<(String, T)> {
         loop {
             unsafe {
                 // Create file save dialog
@@ -100,12 +384,13 @@ impl FileDialogs {
                     Err(_) => return None,
                 };
 
-                // Get default filter index and extension based on original file extension
-                let ext = original_extension.unwrap_or("png");
+                // Get default filter index and extension based on original file extension.
+                // A shortened list (PDF only) starts with the wanted type.
                 let (index, default_ext) = get_save_type_index(ext);
+                let index = if index as usize <= types.len() { index } else { 1 };
 
-                // Set file types - allow all supported save formats
-                if dialog.SetFileTypes(SAVE_TYPES).is_err() { return None; }
+                // Set file types
+                if dialog.SetFileTypes(types).is_err() { return None; }
                 if dialog.SetFileTypeIndex(index).is_err() { return None; }
                 if dialog.SetDefaultExtension(default_ext).is_err() { return None; }
 
@@ -125,8 +410,13 @@ impl FileDialogs {
                     self.apply_folder_restriction_save(&dialog, path);
                 }
 
+                let customize = dialog.cast::<IFileDialogCustomize>().ok();
+                if let Some(ref customize) = customize {
+                    add_controls(customize);
+                }
+
                 // Show dialog
-                if dialog.Show(parent).is_err() {
+                if dialog.Show(dialog_owner(parent)).is_err() {
                     return None;
                 }
 
@@ -167,7 +457,7 @@ impl FileDialogs {
                     }
                 }
 
-                return Some(path_str);
+                return Some((path_str, read_controls(customize.as_ref())));
             }
         }
     }
@@ -212,7 +502,7 @@ pub fn password_dialog(parent: HWND) -> Option<String> {
         let result = DialogBoxParamW(
             instance,
             PCWSTR(IDD_PASSWORD_DIALOG as *const u16),
-            parent,
+            dialog_owner(parent),
             Some(password_dialog_proc),
             LPARAM(&mut data as *mut _ as isize),
         );
@@ -246,24 +536,7 @@ extern "system" fn password_dialog_proc(
                 // Store the pointer to PasswordData in window's user data
                 SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam.0);
 
-                // Center the dialog relative to parent window
-                let parent = GetParent(hwnd);
-                if parent.0 != 0 {
-                    let mut rc_parent = RECT::default();
-                    let mut rc_dlg = RECT::default();
-                    GetWindowRect(parent, &mut rc_parent);
-                    GetWindowRect(hwnd, &mut rc_dlg);
-
-                    let dlg_w = rc_dlg.right - rc_dlg.left;
-                    let dlg_h = rc_dlg.bottom - rc_dlg.top;
-                    let parent_w = rc_parent.right - rc_parent.left;
-                    let parent_h = rc_parent.bottom - rc_parent.top;
-
-                    let x = rc_parent.left + (parent_w - dlg_w) / 2;
-                    let y = rc_parent.top + (parent_h - dlg_h) / 2;
-
-                    SetWindowPos(hwnd, None, x, y, 0, 0, SWP_NOSIZE | SWP_NOZORDER);
-                }
+                center_on_owner(hwnd);
 
                 // Set focus to the password input field
                 let edit = GetDlgItem(hwnd, IDC_PASSWORD_EDIT);
@@ -308,38 +581,1059 @@ extern "system" fn password_dialog_proc(
     }
 }
 
-pub fn retry_password_dialog(parent: HWND) -> bool {
+const IDD_ZOOM_DIALOG: isize = 202;
+const IDC_ZOOM_EDIT: i32 = 203;
+
+/// Zoom range of the "Anpassen..." dialog in percent
+const ZOOM_PERCENT_RANGE: std::ops::RangeInclusive<u32> = 10..=1000;
+
+/// "Zoom anpassen": asks for a zoom in percent, starting with `current`
+pub fn zoom_dialog(parent: HWND, current: u32) -> Option<u32> {
+    unsafe {
+        let instance = GetModuleHandleW(None).unwrap_or_default();
+        let mut percent = current;
+        let result = DialogBoxParamW(
+            instance,
+            PCWSTR(IDD_ZOOM_DIALOG as *const u16),
+            dialog_owner(parent),
+            Some(zoom_dialog_proc),
+            LPARAM(&mut percent as *mut u32 as isize),
+        );
+        (result == IDOK.0 as isize).then_some(percent)
+    }
+}
+
+extern "system" fn zoom_dialog_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> isize {
+    unsafe {
+        match msg {
+            WM_INITDIALOG => {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam.0);
+                center_on_owner(hwnd);
+
+                let edit = GetDlgItem(hwnd, IDC_ZOOM_EDIT);
+                let current = HSTRING::from((*(lparam.0 as *const u32)).to_string());
+                let _ = SetWindowTextW(edit, &current);
+                SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+                SetFocus(edit);
+                0
+            }
+            WM_COMMAND => match (wparam.0 & 0xFFFF) as i32 {
+                1 => {
+                    let edit = GetDlgItem(hwnd, IDC_ZOOM_EDIT);
+                    let mut buffer = [0u16; 16];
+                    let len = GetWindowTextW(edit, &mut buffer) as usize;
+                    let value = String::from_utf16_lossy(&buffer[..len]).trim().parse::<u32>().ok();
+                    match value.filter(|v| ZOOM_PERCENT_RANGE.contains(v)) {
+                        Some(value) => {
+                            *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut u32) = value;
+                            EndDialog(hwnd, IDOK.0 as isize);
+                        }
+                        None => {
+                            // Stay open with the invalid value selected
+                            show_error(
+                                hwnd,
+                                &format!("Bitte einen Wert von {} bis {} eingeben.", ZOOM_PERCENT_RANGE.start(), ZOOM_PERCENT_RANGE.end()),
+                            );
+                            SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+                            SetFocus(edit);
+                        }
+                    }
+                    1
+                }
+                2 => {
+                    EndDialog(hwnd, IDCANCEL.0 as isize);
+                    1
+                }
+                _ => 0,
+            },
+            WM_CLOSE => {
+                EndDialog(hwnd, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        }
+    }
+}
+
+const IDD_SPLIT_DIALOG: isize = 224;
+const IDC_SPLIT_SLIDER: i32 = 225;
+const IDC_SPLIT_VALUE: i32 = 226;
+// Not defined in windows 0.48
+const TBM_GETPOS: u32 = WM_USER;
+
+/// Split positions offered by "Teilung anpassen", in percent of the width
+const SPLIT_PERCENT_RANGE: std::ops::RangeInclusive<u32> = 30..=70;
+
+/// "Teilung anpassen": asks where double pages are split, in percent of their width,
+/// starting with `current`
+pub fn split_dialog(parent: HWND, current: u32) -> Option<u32> {
+    unsafe {
+        let instance = GetModuleHandleW(None).unwrap_or_default();
+        let mut percent = current.clamp(*SPLIT_PERCENT_RANGE.start(), *SPLIT_PERCENT_RANGE.end());
+        let result = DialogBoxParamW(
+            instance,
+            PCWSTR(IDD_SPLIT_DIALOG as *const u16),
+            dialog_owner(parent),
+            Some(split_dialog_proc),
+            LPARAM(&mut percent as *mut u32 as isize),
+        );
+        (result == IDOK.0 as isize).then_some(percent)
+    }
+}
+
+extern "system" fn split_dialog_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> isize {
+    unsafe {
+        let show_value = |hwnd: HWND, percent: usize| {
+            let _ = SetDlgItemTextW(hwnd, IDC_SPLIT_VALUE, &HSTRING::from(format!("{percent} %")));
+        };
+        match msg {
+            WM_INITDIALOG => {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam.0);
+                center_on_owner(hwnd);
+
+                let slider = GetDlgItem(hwnd, IDC_SPLIT_SLIDER);
+                let range = (*SPLIT_PERCENT_RANGE.end() << 16 | *SPLIT_PERCENT_RANGE.start()) as isize;
+                SendMessageW(slider, TBM_SETRANGE, WPARAM(0), LPARAM(range));
+                SendMessageW(slider, TBM_SETTICFREQ, WPARAM(5), LPARAM(0));
+                SendMessageW(slider, TBM_SETPAGESIZE, WPARAM(0), LPARAM(5));
+                let current = *(lparam.0 as *const u32) as isize;
+                SendMessageW(slider, TBM_SETPOS, WPARAM(1), LPARAM(current));
+                show_value(hwnd, current as usize);
+                SetFocus(slider);
+                0
+            }
+            WM_HSCROLL => {
+                let position = SendMessageW(GetDlgItem(hwnd, IDC_SPLIT_SLIDER), TBM_GETPOS, WPARAM(0), LPARAM(0));
+                show_value(hwnd, position.0 as usize);
+                0
+            }
+            WM_COMMAND => match (wparam.0 & 0xFFFF) as i32 {
+                1 => {
+                    let position = SendMessageW(GetDlgItem(hwnd, IDC_SPLIT_SLIDER), TBM_GETPOS, WPARAM(0), LPARAM(0));
+                    *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut u32) = position.0 as u32;
+                    EndDialog(hwnd, IDOK.0 as isize);
+                    1
+                }
+                2 => {
+                    EndDialog(hwnd, IDCANCEL.0 as isize);
+                    1
+                }
+                _ => 0,
+            },
+            WM_CLOSE => {
+                EndDialog(hwnd, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        }
+    }
+}
+
+const IDD_GO_TO_PAGE_DIALOG: isize = 206;
+const IDC_GO_TO_PAGE_EDIT: i32 = 207;
+
+struct GoToPageData<'d> {
+    doc: &'d Document,
+    page: usize,
+}
+
+/// "Gehe zu Seite": asks for a page number or page label, starting with `current_page`.
+/// Returns the 0-based page.
+pub fn go_to_page_dialog(parent: HWND, doc: &Document, current_page: usize) -> Option<usize> {
+    unsafe {
+        let instance = GetModuleHandleW(None).unwrap_or_default();
+        let mut data = GoToPageData { doc, page: current_page };
+        let result = DialogBoxParamW(
+            instance,
+            PCWSTR(IDD_GO_TO_PAGE_DIALOG as *const u16),
+            dialog_owner(parent),
+            Some(go_to_page_dialog_proc),
+            LPARAM(&mut data as *mut GoToPageData as isize),
+        );
+        (result == IDOK.0 as isize).then_some(data.page)
+    }
+}
+
+extern "system" fn go_to_page_dialog_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> isize {
+    unsafe {
+        match msg {
+            WM_INITDIALOG => {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam.0);
+                center_on_owner(hwnd);
+
+                let data = &*(lparam.0 as *const GoToPageData);
+                let edit = GetDlgItem(hwnd, IDC_GO_TO_PAGE_EDIT);
+                let current = match data.doc.page_label(data.page) {
+                    Some(label) => label.to_string(),
+                    None => (data.page + 1).to_string(),
+                };
+                let _ = SetWindowTextW(edit, &HSTRING::from(current));
+                SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+                SetFocus(edit);
+                0
+            }
+            WM_COMMAND => match (wparam.0 & 0xFFFF) as i32 {
+                1 => {
+                    let data = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut GoToPageData);
+                    let edit = GetDlgItem(hwnd, IDC_GO_TO_PAGE_EDIT);
+                    let mut buffer = [0u16; 64];
+                    let len = GetWindowTextW(edit, &mut buffer) as usize;
+                    let text = String::from_utf16_lossy(&buffer[..len]);
+                    match data.doc.find_page(&text) {
+                        Some(page) => {
+                            data.page = page;
+                            EndDialog(hwnd, IDOK.0 as isize);
+                        }
+                        None => {
+                            let hint = if data.doc.page_label(0).is_some() { " oder eine Seitenbeschriftung wie im Dokument" } else { "" };
+                            show_error(hwnd, &format!("Bitte eine Seitenzahl von 1 bis {}{} eingeben.", data.doc.page_count(), hint));
+                            SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+                            SetFocus(edit);
+                        }
+                    }
+                    1
+                }
+                2 => {
+                    EndDialog(hwnd, IDCANCEL.0 as isize);
+                    1
+                }
+                _ => 0,
+            },
+            WM_CLOSE => {
+                EndDialog(hwnd, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        }
+    }
+}
+
+const IDD_EXTRACT_DIALOG: isize = 204;
+const IDC_EXTRACT_PAGES: i32 = 205;
+
+struct ExtractData<'p, 'a> {
+    title: &'p str,
+    picker: &'p mut PagePicker<'a>,
+    page_count: usize,
+    current_page: usize,
+    pages: Vec<usize>,
+}
+
+/// "Seiten extrahieren" and "Seiten als Dateien kopieren" (dialog caption `title`): the
+/// pages typed (the current one to start with) or ticked in the thumbnail strip of `picker`,
+/// 0-based and sorted
+pub fn extract_pages_dialog(parent: HWND, title: &str, picker: &mut PagePicker, page_count: usize, current_page: usize) -> Option<Vec<usize>> {
+    unsafe {
+        let instance = GetModuleHandleW(None).unwrap_or_default();
+        let mut data = ExtractData { title, picker, page_count, current_page, pages: Vec::new() };
+        let result = DialogBoxParamW(
+            instance,
+            PCWSTR(IDD_EXTRACT_DIALOG as *const u16),
+            dialog_owner(parent),
+            Some(extract_dialog_proc),
+            LPARAM(&mut data as *mut ExtractData as isize),
+        );
+        (result == IDOK.0 as isize).then_some(data.pages)
+    }
+}
+
+extern "system" fn extract_dialog_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> isize {
+    unsafe {
+        // Thumbnail list notifications, and OK with no page ticked
+        if let Some(result) = crate::page_picker::handle_message(hwnd, msg, wparam, lparam) {
+            return result as isize;
+        }
+        match msg {
+            WM_INITDIALOG => {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam.0);
+                let data = &mut *(lparam.0 as *mut ExtractData);
+                let _ = SetWindowTextW(hwnd, &HSTRING::from(data.title));
+                crate::page_picker::attach_to(hwnd, &mut *data.picker);
+                center_on_owner(hwnd);
+
+                let edit = GetDlgItem(hwnd, IDC_EXTRACT_PAGES);
+                let _ = SetWindowTextW(edit, &HSTRING::from((data.current_page + 1).to_string()));
+                SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+                SetFocus(edit);
+                0
+            }
+            WM_COMMAND => match (wparam.0 & 0xFFFF) as i32 {
+                1 => {
+                    let data = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut ExtractData);
+                    let edit = GetDlgItem(hwnd, IDC_EXTRACT_PAGES);
+                    let mut buffer = [0u16; 512];
+                    let len = GetWindowTextW(edit, &mut buffer) as usize;
+                    // Ticked pages win over the typed ones, as in the print dialog
+                    let pages = data
+                        .picker
+                        .selected_pages()
+                        .or_else(|| crate::page_picker::parse_pages(&String::from_utf16_lossy(&buffer[..len]), data.page_count));
+                    match pages {
+                        Some(pages) => {
+                            data.pages = pages;
+                            EndDialog(hwnd, IDOK.0 as isize);
+                        }
+                        None => {
+                            show_error(hwnd, &format!("Bitte Seiten von 1 bis {} eingeben, z. B. 1, 3-5.", data.page_count));
+                            SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+                            SetFocus(edit);
+                        }
+                    }
+                    1
+                }
+                2 => {
+                    EndDialog(hwnd, IDCANCEL.0 as isize);
+                    1
+                }
+                _ => 0,
+            },
+            WM_CLOSE => {
+                EndDialog(hwnd, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        }
+    }
+}
+
+const IDD_BATCH_ROTATE_DIALOG: isize = 208;
+const IDC_BATCH_ROTATE_RIGHT: i32 = 209;
+const IDC_BATCH_ROTATE_180: i32 = 210;
+const IDC_BATCH_ROTATE_LEFT: i32 = 211;
+const IDC_BATCH_ROTATE_FILTER: i32 = 212;
+const IDC_BATCH_ROTATE_OVERWRITE: i32 = 213;
+const IDC_BATCH_ROTATE_TO_FOLDER: i32 = 214;
+
+/// Choices of the "Alle drehen" dialog
+pub struct BatchRotateChoice {
+    /// Clockwise degrees: 90, 180 or 270
+    pub rotation: i32,
+    /// File name patterns, empty for all files
+    pub filter: String,
+    /// Save into a folder picked next instead of overwriting the originals
+    pub to_folder: bool,
+}
+
+/// "Ordner-Stapelverarbeitung > Alle drehen": rotation, file filter and where to save
+pub fn batch_rotate_dialog(parent: HWND) -> Option<BatchRotateChoice> {
+    unsafe {
+        let instance = GetModuleHandleW(None).unwrap_or_default();
+        let mut choice = BatchRotateChoice { rotation: 90, filter: String::new(), to_folder: false };
+        let result = DialogBoxParamW(
+            instance,
+            PCWSTR(IDD_BATCH_ROTATE_DIALOG as *const u16),
+            dialog_owner(parent),
+            Some(batch_rotate_dialog_proc),
+            LPARAM(&mut choice as *mut BatchRotateChoice as isize),
+        );
+        (result == IDOK.0 as isize).then_some(choice)
+    }
+}
+
+extern "system" fn batch_rotate_dialog_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> isize {
+    unsafe {
+        match msg {
+            WM_INITDIALOG => {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam.0);
+                center_on_owner(hwnd);
+                let _ = CheckRadioButton(hwnd, IDC_BATCH_ROTATE_RIGHT, IDC_BATCH_ROTATE_LEFT, IDC_BATCH_ROTATE_RIGHT);
+                let _ = CheckRadioButton(hwnd, IDC_BATCH_ROTATE_OVERWRITE, IDC_BATCH_ROTATE_TO_FOLDER, IDC_BATCH_ROTATE_OVERWRITE);
+                1
+            }
+            WM_COMMAND => match (wparam.0 & 0xFFFF) as i32 {
+                1 => {
+                    let choice = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut BatchRotateChoice);
+                    let checked = |id: i32| IsDlgButtonChecked(hwnd, id) == BST_CHECKED.0;
+                    choice.rotation = if checked(IDC_BATCH_ROTATE_180) {
+                        180
+                    } else if checked(IDC_BATCH_ROTATE_LEFT) {
+                        270
+                    } else {
+                        90
+                    };
+                    let mut buffer = [0u16; 260];
+                    let len = GetWindowTextW(GetDlgItem(hwnd, IDC_BATCH_ROTATE_FILTER), &mut buffer) as usize;
+                    choice.filter = String::from_utf16_lossy(&buffer[..len]).trim().to_string();
+                    choice.to_folder = checked(IDC_BATCH_ROTATE_TO_FOLDER);
+                    EndDialog(hwnd, IDOK.0 as isize);
+                    1
+                }
+                2 => {
+                    EndDialog(hwnd, IDCANCEL.0 as isize);
+                    1
+                }
+                _ => 0,
+            },
+            WM_CLOSE => {
+                EndDialog(hwnd, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        }
+    }
+}
+
+const IDD_OCR_DIALOG: isize = 215;
+const IDC_OCR_INFO: i32 = 216;
+const IDC_OCR_TEXT: i32 = 217;
+const IDC_OCR_COPY: i32 = 218;
+const IDC_OCR_SAVE: i32 = 219;
+
+struct OcrDialogData<'a> {
+    dialogs: &'a FileDialogs,
+    info: &'a str,
+    text: &'a str,
+    default_filename: &'a str,
+}
+
+/// "Text erkennen (OCR)": shows the recognized `text` below `info`, with "Kopieren" and
+/// "Als Textdatei speichern"
+pub fn ocr_text_dialog(parent: HWND, dialogs: &FileDialogs, info: &str, text: &str, default_filename: &str) {
     unsafe {
-        let result = MessageBoxW(
-            parent,
+        let instance = GetModuleHandleW(None).unwrap_or_default();
+        let mut data = OcrDialogData { dialogs, info, text, default_filename };
+        DialogBoxParamW(
+            instance,
+            PCWSTR(IDD_OCR_DIALOG as *const u16),
+            dialog_owner(parent),
+            Some(ocr_dialog_proc),
+            LPARAM(&mut data as *mut OcrDialogData as isize),
+        );
+    }
+}
+
+extern "system" fn ocr_dialog_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> isize {
+    unsafe {
+        match msg {
+            WM_INITDIALOG => {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam.0);
+                center_on_owner(hwnd);
+
+                let data = &*(lparam.0 as *const OcrDialogData);
+                let _ = SetWindowTextW(GetDlgItem(hwnd, IDC_OCR_INFO), &HSTRING::from(data.info));
+                // The edit control needs CRLF line breaks
+                let _ = SetWindowTextW(GetDlgItem(hwnd, IDC_OCR_TEXT), &HSTRING::from(data.text.replace('\n', "\r\n")));
+                SetFocus(GetDlgItem(hwnd, IDCANCEL.0));
+                0
+            }
+            WM_COMMAND => match (wparam.0 & 0xFFFF) as i32 {
+                IDC_OCR_COPY => {
+                    let data = &*(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const OcrDialogData);
+                    match crate::clipboard::write_text(hwnd, data.text) {
+                        Ok(()) => {
+                            let _ = SetWindowTextW(GetDlgItem(hwnd, IDC_OCR_INFO), w!("Der Text wurde in die Zwischenablage kopiert."));
+                        }
+                        Err(e) => show_error(hwnd, &format!("Der Text konnte nicht kopiert werden:\n{}", e.message())),
+                    }
+                    1
+                }
+                IDC_OCR_SAVE => {
+                    let data = &*(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const OcrDialogData);
+                    if let Some(path) = data.dialogs.save_text(hwnd, data.default_filename) {
+                        // UTF-8 with BOM and CRLF, so Notepad and older editors read it right
+                        let contents = format!("\u{feff}{}\r\n", data.text.replace('\n', "\r\n"));
+                        let written = crate::utils::write_atomically(&path, |temp| {
+                            std::fs::write(crate::utils::long_path(temp), &contents).map_err(|e| Error::new(E_FAIL, HSTRING::from(e.to_string())))
+                        });
+                        match written {
+                            Ok(()) => {
+                                let _ = SetWindowTextW(GetDlgItem(hwnd, IDC_OCR_INFO), &HSTRING::from(format!("Gespeichert: {}", path)));
+                            }
+                            Err(e) => show_error(hwnd, &format!("Die Textdatei konnte nicht gespeichert werden:\n{}", e.message())),
+                        }
+                    }
+                    1
+                }
+                1 | 2 => {
+                    EndDialog(hwnd, IDCANCEL.0 as isize);
+                    1
+                }
+                _ => 0,
+            },
+            WM_CLOSE => {
+                EndDialog(hwnd, IDCANCEL.0 as isize);
+                1
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Top-level window that owns dialogs opened for `parent`, brought to the foreground first.
+/// Right after startup the main window may not be active yet, and dialogs owned by an
+/// inactive window can open behind it.
+pub fn dialog_owner(parent: HWND) -> HWND {
+    if parent.0 == 0 {
+        return parent;
+    }
+    unsafe {
+        let owner = GetAncestor(parent, GA_ROOT);
+        let _ = SetForegroundWindow(owner);
+        owner
+    }
+}
+
+/// Centers a dialog over its owner, kept inside the work area of the owner's monitor, and
+/// activates it. Every dialog window (resource dialogs, hooks, task dialogs, message boxes)
+/// calls this when it is created; new dialogs should too.
+pub fn center_on_owner(dialog: HWND) {
+    unsafe {
+        let owner = GetWindow(dialog, GW_OWNER);
+        let reference = if owner.0 != 0 { owner } else { dialog };
+        let mut monitor_info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if !GetMonitorInfoW(MonitorFromWindow(reference, MONITOR_DEFAULTTONEAREST), &mut monitor_info).as_bool() {
+            return;
+        }
+        let work = monitor_info.rcWork;
+
+        // Over the owner, or in the middle of the monitor without (visible) owner
+        let mut area = work;
+        if owner.0 != 0 && !IsIconic(owner).as_bool() {
+            GetWindowRect(owner, &mut area);
+        }
+
+        let mut rect = RECT::default();
+        GetWindowRect(dialog, &mut rect);
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
+        let x = (area.left + (area.right - area.left - width) / 2).clamp(work.left, (work.right - width).max(work.left));
+        let y = (area.top + (area.bottom - area.top - height) / 2).clamp(work.top, (work.bottom - height).max(work.top));
+
+        SetWindowPos(dialog, None, x, y, 0, 0, SWP_NOSIZE | SWP_NOZORDER);
+        let _ = SetForegroundWindow(dialog);
+    }
+}
+
+/// Common dialog hooks, WM_INITDIALOG: grows the dialog by `height` pixels and moves the
+/// OK/Cancel/Help row down. Returns the top of the freed space for extra controls.
+///
+/// # Safety
+/// `hdlg` must be a dialog window.
+pub unsafe fn make_room_above_buttons(hdlg: HWND, height: i32) -> i32 {
+    let mut client = RECT::default();
+    let _ = GetClientRect(hdlg, &mut client);
+    let ok = GetDlgItem(hdlg, IDOK.0);
+    let top = if ok.0 != 0 { dialog_item_rect(hdlg, ok).top } else { client.bottom };
+
+    let mut window = RECT::default();
+    let _ = GetWindowRect(hdlg, &mut window);
+    let _ = SetWindowPos(
+        hdlg,
+        None,
+        0,
+        0,
+        window.right - window.left,
+        window.bottom - window.top + height,
+        SWP_NOMOVE | SWP_NOZORDER,
+    );
+    for id in [IDOK.0, IDCANCEL.0, pshHelp as i32] {
+        let button = GetDlgItem(hdlg, id);
+        if button.0 != 0 {
+            let rect = dialog_item_rect(hdlg, button);
+            let _ = SetWindowPos(button, None, rect.left, rect.top + height, 0, 0, SWP_NOSIZE | SWP_NOZORDER);
+        }
+    }
+    top
+}
+
+/// Adds a child control with the dialog's font
+///
+/// # Safety
+/// `hdlg` must be a dialog window.
+pub unsafe fn add_dialog_control(
+    hdlg: HWND,
+    class: PCWSTR,
+    text: &str,
+    style: WINDOW_STYLE,
+    ex_style: WINDOW_EX_STYLE,
+    id: i32,
+    rect: RECT,
+) -> HWND {
+    let control = CreateWindowExW(
+        ex_style,
+        class,
+        &HSTRING::from(text),
+        WS_CHILD | WS_VISIBLE | style,
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+        hdlg,
+        HMENU(id as isize),
+        None,
+        None,
+    );
+    let font = SendMessageW(hdlg, WM_GETFONT, WPARAM(0), LPARAM(0));
+    SendMessageW(control, WM_SETFONT, WPARAM(font.0 as usize), LPARAM(1));
+    control
+}
+
+/// Window rectangle of a dialog control in dialog client coordinates
+///
+/// # Safety
+/// `control` must be a child of `hdlg`.
+pub unsafe fn dialog_item_rect(hdlg: HWND, control: HWND) -> RECT {
+    let mut rect = RECT::default();
+    let _ = GetWindowRect(control, &mut rect);
+    let mut points = [POINT { x: rect.left, y: rect.top }, POINT { x: rect.right, y: rect.bottom }];
+    windows::Win32::Graphics::Gdi::MapWindowPoints(None, hdlg, &mut points);
+    RECT { left: points[0].x, top: points[0].y, right: points[1].x, bottom: points[1].y }
+}
+
+thread_local! {
+    static MESSAGE_BOX_HOOK: std::cell::Cell<Option<HHOOK>> = const { std::cell::Cell::new(None) };
+}
+
+/// Runs a MessageBox call with a CBT hook that centers the box via `center_on_owner`
+/// (MessageBoxW itself centers on whatever monitor Windows picks)
+fn centered_message_box<T>(show: impl FnOnce() -> T) -> T {
+    let hook = unsafe { SetWindowsHookExW(WH_CBT, Some(message_box_hook), None, GetCurrentThreadId()) }.ok();
+    MESSAGE_BOX_HOOK.with(|h| h.set(hook));
+    let result = show();
+    if let Some(hook) = MESSAGE_BOX_HOOK.with(|h| h.take()) {
+        unsafe {
+            let _ = UnhookWindowsHookEx(hook);
+        }
+    }
+    result
+}
+
+unsafe extern "system" fn message_box_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let hook = MESSAGE_BOX_HOOK.with(|h| h.get());
+    if code == HCBT_ACTIVATE as i32 {
+        // Only the first activation is the message box; unhook right away
+        center_on_owner(HWND(wparam.0 as isize));
+        if let Some(hook) = MESSAGE_BOX_HOOK.with(|h| h.take()) {
+            let _ = UnhookWindowsHookEx(hook);
+        }
+    }
+    CallNextHookEx(hook.unwrap_or_default(), code, wparam, lparam)
+}
+
+// Custom task dialog buttons
+const ID_EXPORT_CHANGES: i32 = 100;
+const ID_DISCARD_CHANGES: i32 = 101;
+const ID_COPY_DETAILS: i32 = 102;
+const ID_CHECK_UPDATES: i32 = 103;
+const ID_OPEN_ANYWAY: i32 = 104;
+const ID_OPEN_REDUCED: i32 = 105;
+
+/// Contents of a task dialog. `icon` is a TD_*_ICON or an icon resource of this module.
+struct TaskDialogSpec<'a> {
+    title: &'a str,
+    instruction: &'a str,
+    content: &'a str,
+    icon: PCWSTR,
+    common_buttons: TASKDIALOG_COMMON_BUTTON_FLAGS,
+    buttons: &'a [TASKDIALOG_BUTTON],
+    default_button: i32,
+    /// Text of the expandable "Details" section, also offered as "Details kopieren"
+    details: Option<&'a str>,
+    /// Checkbox below the buttons, e.g. "Nicht mehr fragen"
+    verification: Option<&'a str>,
+    /// Button label and text it copies without closing the dialog; defaults to
+    /// "Details kopieren" for `details`
+    copy: Option<(&'a str, &'a str)>,
+}
+
+fn to_wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Shows a task dialog centered on `parent` and returns the pressed button.
+/// `None` means task dialogs are unavailable (comctl32 v5) and the caller falls back to MessageBoxW.
+fn show_task_dialog(parent: HWND, spec: &TaskDialogSpec) -> Option<i32> {
+    show_task_dialog_verified(parent, spec).map(|(pressed, _)| pressed)
+}
+
+/// Like `show_task_dialog`, also returns whether the verification checkbox was ticked
+fn show_task_dialog_verified(parent: HWND, spec: &TaskDialogSpec) -> Option<(i32, bool)> {
+    let title = to_wide(spec.title);
+    let instruction = to_wide(spec.instruction);
+    let content = to_wide(spec.content);
+    let details = spec.details.map(to_wide);
+    let verification = spec.verification.map(to_wide);
+    let copy = spec.copy.or(spec.details.map(|details| ("Details kopieren", details)));
+    let copy_label = copy.map(|(label, _)| to_wide(label));
+    let copy_text = copy.map(|(_, text)| to_wide(text));
+
+    let mut buttons = spec.buttons.to_vec();
+    let mut flags = TDF_ALLOW_DIALOG_CANCELLATION.0 | TDF_POSITION_RELATIVE_TO_WINDOW.0;
+    if let Some(label) = &copy_label {
+        buttons.push(TASKDIALOG_BUTTON { nButtonID: ID_COPY_DETAILS, pszButtonText: PCWSTR(label.as_ptr()) });
+        flags |= TDF_USE_COMMAND_LINKS.0;
+    }
+    let details_ptr = details.as_ref().map_or(PCWSTR::null(), |d| PCWSTR(d.as_ptr()));
+    let copy_ptr = copy_text.as_ref().map_or(PCWSTR::null(), |c| PCWSTR(c.as_ptr()));
+
+    let mut config = TASKDIALOGCONFIG {
+        cbSize: std::mem::size_of::<TASKDIALOGCONFIG>() as u32,
+        hwndParent: dialog_owner(parent),
+        hInstance: unsafe { GetModuleHandleW(None).unwrap_or_default() },
+        dwFlags: TASKDIALOG_FLAGS(flags),
+        dwCommonButtons: spec.common_buttons,
+        pszWindowTitle: PCWSTR(title.as_ptr()),
+        pszMainInstruction: PCWSTR(instruction.as_ptr()),
+        pszContent: if spec.content.is_empty() { PCWSTR::null() } else { PCWSTR(content.as_ptr()) },
+        cButtons: buttons.len() as u32,
+        pButtons: if buttons.is_empty() { std::ptr::null() } else { buttons.as_ptr() },
+        nDefaultButton: spec.default_button,
+        pszExpandedInformation: details_ptr,
+        pszCollapsedControlText: w!("Details anzeigen"),
+        pszExpandedControlText: w!("Details ausblenden"),
+        pszVerificationText: verification.as_ref().map_or(PCWSTR::null(), |v| PCWSTR(v.as_ptr())),
+        pfCallback: Some(task_dialog_callback),
+        lpCallbackData: copy_ptr.0 as isize,
+        ..Default::default()
+    };
+    config.Anonymous1.pszMainIcon = spec.icon;
+
+    let mut pressed = 0i32;
+    let mut verified = FALSE;
+    unsafe { TaskDialogIndirect(&config, Some(&mut pressed), None, Some(&mut verified)) }.ok()?;
+    Some((pressed, verified.as_bool()))
+}
+
+/// Centers the dialog; the copy button copies its text (`data`) and keeps the dialog open
+unsafe extern "system" fn task_dialog_callback(hwnd: HWND, msg: u32, wparam: WPARAM, _lparam: LPARAM, data: isize) -> HRESULT {
+    if msg == TDN_CREATED.0 as u32 {
+        center_on_owner(hwnd);
+    }
+    if msg == TDN_BUTTON_CLICKED.0 as u32 && wparam.0 as i32 == ID_COPY_DETAILS && data != 0 {
+        if let Ok(text) = PCWSTR(data as *const u16).to_string() {
+            let _ = crate::clipboard::write_text(hwnd, &text);
+        }
+        return S_FALSE;
+    }
+    S_OK
+}
+
+/// Splits a message into main instruction (first line) and content (the rest)
+fn split_message(message: &str) -> (&str, &str) {
+    match message.split_once('\n') {
+        Some((instruction, content)) => (instruction, content.trim_start_matches('\n')),
+        None => (message, ""),
+    }
+}
+
+pub fn retry_password_dialog(parent: HWND) -> bool {
+    let spec = TaskDialogSpec {
+        title: "Kennwortfehler",
+        instruction: "Falsches Kennwort.",
+        content: "Erneut versuchen?",
+        icon: TD_WARNING_ICON,
+        common_buttons: TASKDIALOG_COMMON_BUTTON_FLAGS(TDCBF_YES_BUTTON.0 | TDCBF_NO_BUTTON.0),
+        buttons: &[],
+        default_button: IDYES.0,
+        details: None,
+        verification: None,
+        copy: None,
+    };
+    if let Some(pressed) = show_task_dialog(parent, &spec) {
+        return pressed == IDYES.0;
+    }
+
+    let result = centered_message_box(|| unsafe {
+        MessageBoxW(
+            dialog_owner(parent),
             w!("Falsches Kennwort. Erneut versuchen?"),
             w!("Kennwortfehler"),
             MB_YESNO | MB_ICONWARNING,
-        );
-        result == IDYES
+        )
+    });
+    result == IDYES
+}
+
+/// Offered at startup when the previous startup did not finish
+pub fn ask_safe_mode() -> bool {
+    ask_yes_no(
+        HWND::default(),
+        "SimpliView wurde beim letzten Start unerwartet beendet.\nIm abgesicherten Modus starten? Gespeicherte Einstellungen werden dann nicht geladen, Eingangsordner und Fernsteuerung bleiben aus.",
+    )
+}
+
+/// Before a PDF link other than http, https or mailto is opened
+pub fn confirm_open_link(parent: HWND, uri: &str) -> bool {
+    ask_yes_no(
+        parent,
+        &format!("Diesen Link öffnen?\n{}\n\nDer Link wird mit dem Programm geöffnet, das Windows dafür vorsieht. Öffnen Sie nur Links aus vertrauenswürdigen Dokumenten.", uri),
+    )
+}
+
+/// After "Renderqualität" was changed while a PDF is open
+pub fn confirm_reload_pdf(parent: HWND) -> bool {
+    ask_yes_no(parent, "Geöffnetes PDF in der neuen Qualität neu laden?\nSeite, Zoom und Drehung bleiben erhalten.")
+}
+
+pub fn confirm_reset_settings(parent: HWND) -> bool {
+    ask_yes_no(
+        parent,
+        "Alle Einstellungen zurücksetzen?\nZuletzt geöffnete Dateien, Ansichts- und Druckeinstellungen werden gelöscht. SimpliView startet danach mit den Standardeinstellungen.",
+    )
+}
+
+/// Question with Ja/Nein; the first line of `message` is the main instruction
+fn ask_yes_no(parent: HWND, message: &str) -> bool {
+    let (instruction, content) = split_message(message);
+    let spec = TaskDialogSpec {
+        title: "SimpliView",
+        instruction,
+        content,
+        icon: TD_WARNING_ICON,
+        common_buttons: TASKDIALOG_COMMON_BUTTON_FLAGS(TDCBF_YES_BUTTON.0 | TDCBF_NO_BUTTON.0),
+        buttons: &[],
+        default_button: IDYES.0,
+        details: None,
+        verification: None,
+        copy: None,
+    };
+    if let Some(pressed) = show_task_dialog(parent, &spec) {
+        return pressed == IDYES.0;
     }
+
+    let message_wide = to_wide(message);
+    let result = centered_message_box(|| unsafe {
+        MessageBoxW(dialog_owner(parent), PCWSTR(message_wide.as_ptr()), w!("SimpliView"), MB_YESNO | MB_ICONWARNING)
+    });
+    result == IDYES
 }
 
 pub fn show_error(parent: HWND, message: &str) {
-    let message_wide: Vec<u16> = message.encode_utf16().chain(std::iter::once(0)).collect();
-    unsafe {
+    show_error_details(parent, message, None);
+}
+
+/// Error box with an optional expandable "Details" section (error code, file, recent actions)
+pub fn show_error_details(parent: HWND, message: &str, details: Option<&str>) {
+    let (instruction, content) = split_message(message);
+    let spec = TaskDialogSpec {
+        title: "Fehler",
+        instruction,
+        content,
+        icon: TD_ERROR_ICON,
+        common_buttons: TDCBF_OK_BUTTON,
+        buttons: &[],
+        default_button: IDOK.0,
+        details,
+        verification: None,
+        copy: None,
+    };
+    if show_task_dialog(parent, &spec).is_some() {
+        return;
+    }
+
+    let message = match details {
+        Some(details) => format!("{}\n\n{}", message, details),
+        None => message.to_string(),
+    };
+    let message_wide = to_wide(&message);
+    centered_message_box(|| unsafe {
         MessageBoxW(
-            parent,
+            dialog_owner(parent),
             PCWSTR(message_wide.as_ptr()),
             w!("Fehler"),
             MB_OK | MB_ICONERROR,
         );
+    });
+}
+
+/// Warning with OK/Cancel, returns true for OK; the first line of `message` is the main
+/// instruction
+pub fn confirm_warning(parent: HWND, message: &str) -> bool {
+    let (instruction, content) = split_message(message);
+    let spec = TaskDialogSpec {
+        title: "SimpliView",
+        instruction,
+        content,
+        icon: TD_WARNING_ICON,
+        common_buttons: TASKDIALOG_COMMON_BUTTON_FLAGS(TDCBF_OK_BUTTON.0 | TDCBF_CANCEL_BUTTON.0),
+        buttons: &[],
+        default_button: IDOK.0,
+        details: None,
+        verification: None,
+        copy: None,
+    };
+    if let Some(pressed) = show_task_dialog(parent, &spec) {
+        return pressed == IDOK.0;
     }
+
+    let message_wide = to_wide(message);
+    centered_message_box(|| unsafe {
+        MessageBoxW(
+            dialog_owner(parent),
+            PCWSTR(message_wide.as_ptr()),
+            w!("SimpliView"),
+            MB_OKCANCEL | MB_ICONWARNING,
+        )
+    }) == IDOK
+}
+
+/// Answer to the prompt about unapplied changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnappliedChangesChoice {
+    Export,
+    Discard,
+    Cancel,
+}
+
+/// "Exportieren / Verwerfen / Abbrechen" before rotated etc. pages are closed.
+/// Without `allow_export` (view-only mode) only Verwerfen and Abbrechen are offered.
+pub fn confirm_unapplied_changes(parent: HWND, allow_export: bool) -> UnappliedChangesChoice {
+    let buttons = [
+        TASKDIALOG_BUTTON { nButtonID: ID_EXPORT_CHANGES, pszButtonText: w!("Exportieren") },
+        TASKDIALOG_BUTTON { nButtonID: ID_DISCARD_CHANGES, pszButtonText: w!("Verwerfen") },
+    ];
+    let buttons = if allow_export { &buttons[..] } else { &buttons[1..] };
+
+    let spec = TaskDialogSpec {
+        title: "SimpliView",
+        instruction: "Änderungen an der Ansicht übernehmen?",
+        content: "Die Drehung wird nur angezeigt und geht beim Schließen verloren. Exportieren Sie die Seite, um sie zu behalten.",
+        icon: TD_WARNING_ICON,
+        common_buttons: TDCBF_CANCEL_BUTTON,
+        buttons,
+        default_button: buttons[0].nButtonID,
+        details: None,
+        verification: None,
+        copy: None,
+    };
+    match show_task_dialog(parent, &spec) {
+        Some(ID_EXPORT_CHANGES) => UnappliedChangesChoice::Export,
+        Some(ID_DISCARD_CHANGES) => UnappliedChangesChoice::Discard,
+        // A dialog that fails to show must not block closing
+        None => UnappliedChangesChoice::Discard,
+        _ => UnappliedChangesChoice::Cancel,
+    }
+}
+
+/// Answer to the warning before a very large document is opened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LargeDocumentChoice {
+    Open,
+    /// "In reduzierter Qualität öffnen"
+    Reduced,
+    Cancel,
+}
+
+/// "Trotzdem öffnen / In reduzierter Qualität öffnen / Abbrechen" for a document whose
+/// pages would take about `estimate` bytes of memory
+pub fn confirm_large_document(parent: HWND, filename: &str, estimate: u64) -> LargeDocumentChoice {
+    let buttons = [
+        TASKDIALOG_BUTTON { nButtonID: ID_OPEN_ANYWAY, pszButtonText: w!("Trotzdem öffnen") },
+        TASKDIALOG_BUTTON { nButtonID: ID_OPEN_REDUCED, pszButtonText: w!("In reduzierter Qualität öffnen") },
+    ];
+    let content = format!(
+        "Zum Anzeigen von \"{}\" werden voraussichtlich etwa {} Arbeitsspeicher gebraucht. In reduzierter Qualität werden Seiten und Bilder mit geringerer Auflösung geladen.",
+        filename,
+        crate::utils::format_file_size(estimate)
+    );
+    let spec = TaskDialogSpec {
+        title: "SimpliView",
+        instruction: "Dieses Dokument ist sehr groß.",
+        content: &content,
+        icon: TD_WARNING_ICON,
+        common_buttons: TDCBF_CANCEL_BUTTON,
+        buttons: &buttons,
+        default_button: ID_OPEN_REDUCED,
+        details: None,
+        verification: None,
+        copy: None,
+    };
+    match show_task_dialog(parent, &spec) {
+        Some(ID_OPEN_ANYWAY) => LargeDocumentChoice::Open,
+        Some(ID_OPEN_REDUCED) => LargeDocumentChoice::Reduced,
+        // Without task dialogs the document opens as before
+        None => LargeDocumentChoice::Open,
+        _ => LargeDocumentChoice::Cancel,
+    }
+}
+
+/// Answer to "SimpliView beenden?"
+pub struct ExitConfirmation {
+    pub exit: bool,
+    /// "Nicht mehr fragen" was ticked
+    pub dont_ask_again: bool,
+}
+
+/// "SimpliView beenden?" before the window closes (see --confirm-exit)
+pub fn confirm_exit(parent: HWND) -> ExitConfirmation {
+    let spec = TaskDialogSpec {
+        title: "SimpliView",
+        instruction: "SimpliView beenden?",
+        content: "",
+        icon: TD_INFORMATION_ICON,
+        common_buttons: TASKDIALOG_COMMON_BUTTON_FLAGS(TDCBF_YES_BUTTON.0 | TDCBF_NO_BUTTON.0),
+        buttons: &[],
+        default_button: IDYES.0,
+        details: None,
+        verification: Some("Nicht mehr fragen"),
+        copy: None,
+    };
+    if let Some((pressed, dont_ask_again)) = show_task_dialog_verified(parent, &spec) {
+        return ExitConfirmation { exit: pressed == IDYES.0, dont_ask_again };
+    }
+
+    let result = centered_message_box(|| unsafe {
+        MessageBoxW(dialog_owner(parent), w!("SimpliView beenden?"), w!("SimpliView"), MB_YESNO | MB_ICONQUESTION)
+    });
+    ExitConfirmation { exit: result == IDYES, dont_ask_again: false }
 }
 
 pub fn show_info(parent: HWND, title: &str, message: &str) {
-    let title_wide: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
-    let message_wide: Vec<u16> = message.encode_utf16().chain(std::iter::once(0)).collect();
-    unsafe {
+    show_info_dialog(parent, title, message, &[], None);
+}
+
+/// Information with a button that copies the whole message, e.g. for figures that go
+/// into a ticket
+pub fn show_copyable_info(parent: HWND, title: &str, message: &str) {
+    show_info_dialog(parent, title, message, &[], Some(("In die Zwischenablage kopieren", message)));
+}
+
+/// The Info dialog. "Versionsinfo kopieren" copies `version_info`; with `offer_update_check`
+/// there is also "Nach Updates suchen". Returns true if that was chosen.
+pub fn show_about(parent: HWND, message: &str, version_info: &str, offer_update_check: bool) -> bool {
+    let buttons = [TASKDIALOG_BUTTON { nButtonID: ID_CHECK_UPDATES, pszButtonText: w!("Nach Updates suchen") }];
+    let buttons = if offer_update_check { &buttons[..] } else { &[] };
+    let copy = Some(("Versionsinfo kopieren", version_info));
+    show_info_dialog(parent, "SimpliView", message, buttons, copy) == Some(ID_CHECK_UPDATES)
+}
+
+/// Information with the application icon; returns the pressed button, `None` for the
+/// MessageBoxW fallback (which has neither `buttons` nor `copy`)
+fn show_info_dialog(
+    parent: HWND,
+    title: &str,
+    message: &str,
+    buttons: &[TASKDIALOG_BUTTON],
+    copy: Option<(&str, &str)>,
+) -> Option<i32> {
+    let (instruction, content) = split_message(message);
+    let spec = TaskDialogSpec {
+        title,
+        instruction,
+        content,
+        #[allow(clippy::manual_dangling_ptr)] // MAKEINTRESOURCEW(1) - intentional
+        icon: PCWSTR(1 as *const u16),
+        common_buttons: TDCBF_OK_BUTTON,
+        buttons,
+        default_button: IDOK.0,
+        details: None,
+        verification: None,
+        copy,
+    };
+    if let Some(pressed) = show_task_dialog(parent, &spec) {
+        return Some(pressed);
+    }
+
+    let title_wide = to_wide(title);
+    let message_wide = to_wide(message);
+    centered_message_box(|| unsafe {
         let instance = GetModuleHandleW(None).unwrap_or_default();
         let params = MSGBOXPARAMSW {
             cbSize: std::mem::size_of::<MSGBOXPARAMSW>() as u32,
-            hwndOwner: parent,
+            hwndOwner: dialog_owner(parent),
             hInstance: instance,
             lpszText: PCWSTR(message_wide.as_ptr()),
             lpszCaption: PCWSTR(title_wide.as_ptr()),
@@ -351,5 +1645,6 @@ pub fn show_info(parent: HWND, title: &str, message: &str) {
             dwLanguageId: 0,
         };
         MessageBoxIndirectW(&params);
-    }
-}
\ No newline at end of file
+    });
+    None
+}
@@ -0,0 +1,624 @@
+//! DICOM (.dcm) single-frame images
+//!
+//! A minimal reader for the files the clinic receives from imaging devices: the header is
+//! walked element by element up to the pixel data, nothing else of the data set is kept.
+//! Supported transfer syntaxes are implicit and explicit VR little endian (uncompressed
+//! pixels) and JPEG baseline, which WIC decodes. Other syntaxes are refused with an error
+//! naming the UID, so the sender knows what to change.
+//!
+//! Monochrome images become 16-bit grayscale pages (see window_level.rs) with the window
+//! from the header as the initial one; Ctrl+drag works as for other 16-bit images.
+//! Of multi-frame files only the first frame is shown. Patient data is never read out;
+//! the properties dialog shows modality and study date only.
+
+use crate::cancel::CancelToken;
+use crate::document::{Document, PageData};
+use crate::window_level::{Gray16, WindowLevel};
+use windows::{
+    core::*,
+    Win32::Foundation::{WIN32_ERROR, WINCODEC_ERR_BADHEADER, WINCODEC_ERR_UNKNOWNIMAGEFORMAT},
+};
+
+const IMPLICIT_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+const EXPLICIT_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+const JPEG_BASELINE: &str = "1.2.840.10008.1.2.4.50";
+
+/// Names of transfer syntaxes we cannot read, for the error message
+const UNSUPPORTED_SYNTAX_NAMES: [(&str, &str); 10] = [
+    ("1.2.840.10008.1.2.1.99", "Deflated Explicit VR Little Endian"),
+    ("1.2.840.10008.1.2.2", "Explicit VR Big Endian"),
+    ("1.2.840.10008.1.2.4.51", "JPEG Extended"),
+    ("1.2.840.10008.1.2.4.57", "JPEG Lossless"),
+    ("1.2.840.10008.1.2.4.70", "JPEG Lossless SV1"),
+    ("1.2.840.10008.1.2.4.80", "JPEG-LS Lossless"),
+    ("1.2.840.10008.1.2.4.81", "JPEG-LS Near-Lossless"),
+    ("1.2.840.10008.1.2.4.90", "JPEG 2000 Lossless"),
+    ("1.2.840.10008.1.2.4.91", "JPEG 2000"),
+    ("1.2.840.10008.1.2.5", "RLE Lossless"),
+];
+
+type Tag = (u16, u16);
+
+const TRANSFER_SYNTAX: Tag = (0x0002, 0x0010);
+const STUDY_DATE: Tag = (0x0008, 0x0020);
+const MODALITY: Tag = (0x0008, 0x0060);
+const SAMPLES_PER_PIXEL: Tag = (0x0028, 0x0002);
+const PHOTOMETRIC: Tag = (0x0028, 0x0004);
+const PLANAR_CONFIGURATION: Tag = (0x0028, 0x0006);
+const ROWS: Tag = (0x0028, 0x0010);
+const COLUMNS: Tag = (0x0028, 0x0011);
+const BITS_ALLOCATED: Tag = (0x0028, 0x0100);
+const BITS_STORED: Tag = (0x0028, 0x0101);
+const PIXEL_REPRESENTATION: Tag = (0x0028, 0x0103);
+const WINDOW_CENTER: Tag = (0x0028, 0x1050);
+const WINDOW_WIDTH: Tag = (0x0028, 0x1051);
+const RESCALE_INTERCEPT: Tag = (0x0028, 0x1052);
+const RESCALE_SLOPE: Tag = (0x0028, 0x1053);
+const PIXEL_DATA: Tag = (0x7FE0, 0x0010);
+const ITEM: Tag = (0xFFFE, 0xE000);
+const ITEM_DELIMITER: Tag = (0xFFFE, 0xE00D);
+const SEQUENCE_DELIMITER: Tag = (0xFFFE, 0xE0DD);
+
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+/// Nesting of sequences and items of undefined length; real files stay in the single digits
+const MAX_NESTING: usize = 64;
+/// Explicit VRs with a 4-byte length field
+const LONG_VRS: [&[u8; 2]; 10] = [b"OB", b"OD", b"OF", b"OL", b"OW", b"SQ", b"UC", b"UR", b"UT", b"UN"];
+
+/// Opens a DICOM file as a one-page image document
+pub fn load(path: &str, cancel: &CancelToken) -> Result<Document> {
+    let data = crate::retry::with_retry("DICOM lesen", path, || {
+        std::fs::read(path).map_err(|e| match e.raw_os_error() {
+            Some(code) => Error::from(WIN32_ERROR(code as u32).to_hresult()),
+            None => dicom_error(WINCODEC_ERR_BADHEADER, &e.to_string()),
+        })
+    })?;
+    let header = Header::parse(&data)?;
+    cancel.check()?;
+    header.into_page().map(|page| Document::new_multiframe_image(vec![page]))
+}
+
+/// The elements of the data set this viewer uses
+#[derive(Default)]
+struct Header<'a> {
+    transfer_syntax: String,
+    modality: Option<String>,
+    study_date: Option<String>,
+    samples_per_pixel: u16,
+    photometric: String,
+    planar_configuration: u16,
+    rows: u16,
+    columns: u16,
+    bits_allocated: u16,
+    bits_stored: u16,
+    pixel_representation: u16,
+    window: Option<(f64, f64)>,
+    rescale: (f64, f64),
+    /// Native pixel data, or the fragments of encapsulated (compressed) pixel data
+    pixels: Option<PixelData<'a>>,
+}
+
+enum PixelData<'a> {
+    Native(&'a [u8]),
+    Encapsulated(Vec<&'a [u8]>),
+}
+
+impl<'a> Header<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        let mut header = Header { samples_per_pixel: 1, rescale: (1.0, 0.0), ..Default::default() };
+        let mut window_center = None;
+        let mut window_width = None;
+
+        // Part 10 files start with a 128-byte preamble and "DICM", followed by the file
+        // meta group in explicit VR; older files are a bare implicit VR data set
+        let mut reader = if data.get(128..132) == Some(b"DICM") {
+            let mut meta = Reader { data, pos: 132, explicit: true };
+            while meta.peek_group() == Some(0x0002) {
+                let element = meta.next_element()?;
+                if element.tag == TRANSFER_SYNTAX {
+                    header.transfer_syntax = text(element.value);
+                }
+            }
+            let explicit = match header.transfer_syntax.as_str() {
+                IMPLICIT_LITTLE_ENDIAN => false,
+                EXPLICIT_LITTLE_ENDIAN | JPEG_BASELINE => true,
+                uid => return Err(unsupported_syntax(uid)),
+            };
+            Reader { data, pos: meta.pos, explicit }
+        } else {
+            let reader = Reader { data, pos: 0, explicit: false };
+            if !matches!(reader.peek_group(), Some(0x0002 | 0x0008)) {
+                return Err(dicom_error(WINCODEC_ERR_UNKNOWNIMAGEFORMAT, "Die Datei ist keine DICOM-Datei."));
+            }
+            header.transfer_syntax = IMPLICIT_LITTLE_ENDIAN.to_string();
+            reader
+        };
+
+        while reader.pos < data.len() {
+            let element = reader.next_element()?;
+            match element.tag {
+                STUDY_DATE => header.study_date = Some(text(element.value)).filter(|s| !s.is_empty()),
+                MODALITY => header.modality = Some(text(element.value)).filter(|s| !s.is_empty()),
+                SAMPLES_PER_PIXEL => header.samples_per_pixel = us(element.value),
+                PHOTOMETRIC => header.photometric = text(element.value),
+                PLANAR_CONFIGURATION => header.planar_configuration = us(element.value),
+                ROWS => header.rows = us(element.value),
+                COLUMNS => header.columns = us(element.value),
+                BITS_ALLOCATED => header.bits_allocated = us(element.value),
+                BITS_STORED => header.bits_stored = us(element.value),
+                PIXEL_REPRESENTATION => header.pixel_representation = us(element.value),
+                WINDOW_CENTER => window_center = decimal(element.value),
+                WINDOW_WIDTH => window_width = decimal(element.value),
+                RESCALE_INTERCEPT => header.rescale.1 = decimal(element.value).unwrap_or(0.0),
+                RESCALE_SLOPE => header.rescale.0 = decimal(element.value).filter(|s| *s != 0.0).unwrap_or(1.0),
+                PIXEL_DATA => {
+                    header.pixels = Some(match element.fragments {
+                        Some(fragments) => PixelData::Encapsulated(fragments),
+                        None => PixelData::Native(element.value),
+                    });
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        header.window = window_center.zip(window_width).filter(|&(_, width)| width > 0.0);
+        Ok(header)
+    }
+
+    fn into_page(self) -> Result<PageData> {
+        let (width, height) = (self.columns as u32, self.rows as u32);
+        if width == 0 || height == 0 {
+            return Err(dicom_error(WINCODEC_ERR_BADHEADER, "Die DICOM-Datei enthält keine Bildgröße."));
+        }
+        let mut tags = Vec::new();
+        if let Some(ref modality) = self.modality {
+            tags.push(("Modalität", modality.clone()));
+        }
+        if let Some(ref date) = self.study_date {
+            tags.push(("Studiendatum", format_date(date)));
+        }
+        let mut page = PageData {
+            width: width as f32,
+            height: height as f32,
+            tags,
+            ..Default::default()
+        };
+
+        match self.pixels {
+            None => return Err(dicom_error(WINCODEC_ERR_BADHEADER, "Die DICOM-Datei enthält keine Bilddaten.")),
+            Some(PixelData::Encapsulated(ref fragments)) => {
+                // JPEG baseline: the fragments of the first frame form one JPEG stream
+                let jpeg: Vec<u8> = fragments.concat();
+                page.wic_bitmap = Some(crate::wic::decode_from_memory(&jpeg)?);
+                page.source_format = Some("DICOM, JPEG".to_string());
+            }
+            Some(PixelData::Native(pixels)) => match self.photometric.as_str() {
+                "MONOCHROME1" | "MONOCHROME2" => {
+                    page.gray16 = Some(self.gray16(pixels)?);
+                    page.source_format = Some(format!("DICOM, {}-Bit Graustufen", self.bits_stored));
+                }
+                "RGB" if self.samples_per_pixel == 3 && self.bits_allocated == 8 => {
+                    page.wic_bitmap = Some(self.rgb(pixels)?);
+                    page.source_format = Some("DICOM, 24-Bit RGB".to_string());
+                }
+                other => {
+                    return Err(dicom_error(
+                        WINCODEC_ERR_UNKNOWNIMAGEFORMAT,
+                        &format!("Die DICOM-Farbdarstellung wird nicht unterstützt: {}", other),
+                    ))
+                }
+            },
+        }
+        Ok(page)
+    }
+
+    /// Stored values through rescale slope/intercept, shifted so the smallest is 0.
+    /// The header window is shifted the same way; MONOCHROME1 (0 = white) is inverted.
+    fn gray16(&self, pixels: &[u8]) -> Result<Gray16> {
+        let count = self.columns as usize * self.rows as usize;
+        let bits_stored = self.bits_stored.clamp(1, 16) as u32;
+        let stored: Vec<i32> = match self.bits_allocated {
+            8 => pixels.get(..count).ok_or_else(truncated)?.iter().map(|&v| v as i32).collect(),
+            16 => {
+                let bytes = pixels.get(..count * 2).ok_or_else(truncated)?;
+                let mask = ((1u32 << bits_stored) - 1) as u16;
+                bytes
+                    .chunks_exact(2)
+                    .map(|b| {
+                        let value = u16::from_le_bytes([b[0], b[1]]) & mask;
+                        if self.pixel_representation == 1 {
+                            // Two's complement in `bits_stored` bits
+                            ((value as i32) << (32 - bits_stored)) >> (32 - bits_stored)
+                        } else {
+                            value as i32
+                        }
+                    })
+                    .collect()
+            }
+            bits => {
+                return Err(dicom_error(
+                    WINCODEC_ERR_UNKNOWNIMAGEFORMAT,
+                    &format!("Die DICOM-Bittiefe wird nicht unterstützt: {} Bit", bits),
+                ))
+            }
+        };
+
+        let (slope, intercept) = self.rescale;
+        let rescaled = |value: i32| value as f64 * slope + intercept;
+        let (min, max) = stored
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), &v| (min.min(rescaled(v)), max.max(rescaled(v))));
+        let offset = min.floor();
+        let span = (max - offset).round().clamp(0.0, u16::MAX as f64);
+        let invert = self.photometric == "MONOCHROME1";
+        let values = stored
+            .iter()
+            .map(|&v| {
+                let value = (rescaled(v) - offset).round().clamp(0.0, u16::MAX as f64);
+                (if invert { span - value } else { value }) as u16
+            })
+            .collect();
+
+        let gray = Gray16::new(self.columns as u32, self.rows as u32, values);
+        Ok(match self.window {
+            Some((center, width)) => {
+                let center = center - offset;
+                let center = if invert { span - center } else { center };
+                gray.with_default_window(WindowLevel {
+                    center: center.clamp(0.0, u16::MAX as f64) as f32,
+                    width: width as f32,
+                })
+            }
+            None => gray,
+        })
+    }
+
+    /// 8-bit RGB, interleaved or by plane, as a PBGRA bitmap
+    fn rgb(&self, pixels: &[u8]) -> Result<windows::Win32::Graphics::Imaging::IWICBitmap> {
+        let count = self.columns as usize * self.rows as usize;
+        let pixels = pixels.get(..count * 3).ok_or_else(truncated)?;
+        let mut bgra = Vec::with_capacity(count * 4);
+        for i in 0..count {
+            let (r, g, b) = if self.planar_configuration == 1 {
+                (pixels[i], pixels[count + i], pixels[2 * count + i])
+            } else {
+                (pixels[3 * i], pixels[3 * i + 1], pixels[3 * i + 2])
+            };
+            bgra.extend_from_slice(&[b, g, r, 255]);
+        }
+        crate::wic::bitmap_from_pbgra(self.columns as u32, self.rows as u32, &bgra)
+    }
+}
+
+struct Element<'a> {
+    tag: Tag,
+    value: &'a [u8],
+    /// Fragments of encapsulated pixel data (undefined length), without the offset table
+    fragments: Option<Vec<&'a [u8]>>,
+}
+
+/// Little endian data set reader
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    explicit: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn peek_group(&self) -> Option<u16> {
+        self.data.get(self.pos..self.pos + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.saturating_add(len)).ok_or_else(truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Tag and value length; items and delimiters have no VR even in explicit VR
+    fn element_header(&mut self) -> Result<(Tag, u32)> {
+        let tag = (self.u16()?, self.u16()?);
+        if !self.explicit || tag.0 == 0xFFFE {
+            return Ok((tag, self.u32()?));
+        }
+        let vr = self.take(2)?;
+        let len = if LONG_VRS.iter().any(|long| long[..] == *vr) {
+            self.take(2)?;
+            self.u32()?
+        } else {
+            self.u16()? as u32
+        };
+        Ok((tag, len))
+    }
+
+    fn next_element(&mut self) -> Result<Element<'a>> {
+        let (tag, len) = self.element_header()?;
+        if len != UNDEFINED_LENGTH {
+            return Ok(Element { tag, value: self.take(len as usize)?, fragments: None });
+        }
+        if tag == PIXEL_DATA {
+            return Ok(Element { tag, value: &[], fragments: Some(self.fragments()?) });
+        }
+        // Sequence (or UN) of undefined length; nothing in it is used
+        self.skip_undefined()?;
+        Ok(Element { tag, value: &[], fragments: None })
+    }
+
+    /// Skips elements up to and including the delimiter that ends an item or sequence.
+    /// Nested items and sequences of undefined length are counted, not recursed into.
+    fn skip_undefined(&mut self) -> Result<()> {
+        let mut depth = 1;
+        loop {
+            let (tag, len) = self.element_header()?;
+            if tag == ITEM_DELIMITER || tag == SEQUENCE_DELIMITER {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            } else if len == UNDEFINED_LENGTH {
+                depth += 1;
+                if depth > MAX_NESTING {
+                    return Err(dicom_error(WINCODEC_ERR_BADHEADER, "Die DICOM-Datei ist zu tief verschachtelt."));
+                }
+            } else {
+                self.take(len as usize)?;
+            }
+        }
+    }
+
+    /// Items of encapsulated pixel data; the first one is the basic offset table
+    fn fragments(&mut self) -> Result<Vec<&'a [u8]>> {
+        let mut items = Vec::new();
+        loop {
+            let (tag, len) = self.element_header()?;
+            match tag {
+                SEQUENCE_DELIMITER => break,
+                ITEM if len != UNDEFINED_LENGTH => items.push(self.take(len as usize)?),
+                _ => return Err(dicom_error(WINCODEC_ERR_BADHEADER, "Die DICOM-Bilddaten sind ungültig.")),
+            }
+        }
+        Ok(items.into_iter().skip(1).collect())
+    }
+}
+
+/// Text value without the padding (space or NUL to an even length)
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).trim_end_matches(['\0', ' ']).trim_start().to_string()
+}
+
+/// Unsigned short (US)
+fn us(value: &[u8]) -> u16 {
+    value.get(..2).map(|b| u16::from_le_bytes([b[0], b[1]])).unwrap_or(0)
+}
+
+/// First value of a decimal string (DS), e.g. "40\400" -> 40
+fn decimal(value: &[u8]) -> Option<f64> {
+    text(value).split('\\').next()?.trim().parse().ok()
+}
+
+/// DICOM date "20240131" as "31.01.2024"
+fn format_date(date: &str) -> String {
+    match (date.get(0..4), date.get(4..6), date.get(6..8)) {
+        (Some(year), Some(month), Some(day)) if date.len() == 8 => format!("{}.{}.{}", day, month, year),
+        _ => date.to_string(),
+    }
+}
+
+fn unsupported_syntax(uid: &str) -> Error {
+    let message = match UNSUPPORTED_SYNTAX_NAMES.iter().find(|(known, _)| *known == uid) {
+        Some((_, name)) => format!("Die DICOM-Übertragungssyntax wird nicht unterstützt: {} ({})", uid, name),
+        None if uid.is_empty() => "Die DICOM-Datei enthält keine Übertragungssyntax.".to_string(),
+        None => format!("Die DICOM-Übertragungssyntax wird nicht unterstützt: {}", uid),
+    };
+    dicom_error(WINCODEC_ERR_UNKNOWNIMAGEFORMAT, &message)
+}
+
+fn truncated() -> Error {
+    dicom_error(WINCODEC_ERR_BADHEADER, "Die DICOM-Datei ist unvollständig.")
+}
+
+fn dicom_error(code: HRESULT, message: &str) -> Error {
+    Error::new(code, HSTRING::from(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag_bytes(tag: Tag) -> Vec<u8> {
+        [tag.0.to_le_bytes(), tag.1.to_le_bytes()].concat()
+    }
+
+    /// Explicit VR element; `len` overrides the value length (for undefined lengths)
+    fn explicit(tag: Tag, vr: &[u8; 2], value: &[u8], len: Option<u32>) -> Vec<u8> {
+        let len = len.unwrap_or(value.len() as u32);
+        let mut out = tag_bytes(tag);
+        out.extend_from_slice(vr);
+        if LONG_VRS.contains(&vr) {
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(&len.to_le_bytes());
+        } else {
+            out.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        out.extend_from_slice(value);
+        out
+    }
+
+    /// Implicit VR element, also the layout of items and delimiters in either syntax
+    fn implicit(tag: Tag, value: &[u8], len: Option<u32>) -> Vec<u8> {
+        let mut out = tag_bytes(tag);
+        out.extend_from_slice(&len.unwrap_or(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(value);
+        out
+    }
+
+    /// Preamble, "DICM" and a file meta group with `syntax`, followed by `body`
+    fn part10(syntax: &str, body: &[u8]) -> Vec<u8> {
+        let mut uid = syntax.as_bytes().to_vec();
+        if uid.len() % 2 == 1 {
+            uid.push(0);
+        }
+        let mut out = vec![0u8; 128];
+        out.extend_from_slice(b"DICM");
+        out.extend(explicit(TRANSFER_SYNTAX, b"UI", &uid, None));
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn error_code(data: &[u8]) -> HRESULT {
+        match Header::parse(data) {
+            Ok(_) => panic!("the header was accepted"),
+            Err(e) => e.code(),
+        }
+    }
+
+    #[test]
+    fn reads_an_explicit_vr_header() {
+        let pixels = [1, 0, 2, 0, 3, 0, 4, 0];
+        let body = [
+            explicit(STUDY_DATE, b"DA", b"20240131", None),
+            explicit(MODALITY, b"CS", b"CT", None),
+            explicit(PHOTOMETRIC, b"CS", b"MONOCHROME2 ", None),
+            explicit(ROWS, b"US", &2u16.to_le_bytes(), None),
+            explicit(COLUMNS, b"US", &2u16.to_le_bytes(), None),
+            explicit(BITS_ALLOCATED, b"US", &16u16.to_le_bytes(), None),
+            explicit(BITS_STORED, b"US", &12u16.to_le_bytes(), None),
+            explicit(WINDOW_CENTER, b"DS", b"40\\400 ", None),
+            explicit(WINDOW_WIDTH, b"DS", b"80", None),
+            explicit(RESCALE_SLOPE, b"DS", b"0 ", None),
+            explicit(PIXEL_DATA, b"OW", &pixels, None),
+        ]
+        .concat();
+        let data = part10(EXPLICIT_LITTLE_ENDIAN, &body);
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.transfer_syntax, EXPLICIT_LITTLE_ENDIAN);
+        assert_eq!(header.study_date.as_deref(), Some("20240131"));
+        assert_eq!(header.modality.as_deref(), Some("CT"));
+        assert_eq!(header.photometric, "MONOCHROME2");
+        assert_eq!((header.columns, header.rows), (2, 2));
+        assert_eq!((header.bits_allocated, header.bits_stored), (16, 12));
+        assert_eq!(header.window, Some((40.0, 80.0)));
+        // A slope of 0 would flatten the image and is taken as 1
+        assert_eq!(header.rescale, (1.0, 0.0));
+        assert!(matches!(header.pixels, Some(PixelData::Native(p)) if p == pixels));
+        assert_eq!(format_date(header.study_date.as_deref().unwrap()), "31.01.2024");
+    }
+
+    #[test]
+    fn reads_a_bare_implicit_vr_data_set() {
+        let data = [
+            implicit(MODALITY, b"MR", None),
+            implicit(ROWS, &3u16.to_le_bytes(), None),
+            implicit(COLUMNS, &4u16.to_le_bytes(), None),
+            implicit(PIXEL_DATA, &[0; 12], None),
+        ]
+        .concat();
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.transfer_syntax, IMPLICIT_LITTLE_ENDIAN);
+        assert_eq!(header.modality.as_deref(), Some("MR"));
+        assert_eq!((header.columns, header.rows), (4, 3));
+        assert_eq!(header.samples_per_pixel, 1);
+    }
+
+    #[test]
+    fn refuses_files_that_are_not_dicom() {
+        assert_eq!(error_code(b"\x89PNG\r\n\x1a\n"), WINCODEC_ERR_UNKNOWNIMAGEFORMAT);
+        assert_eq!(error_code(&[]), WINCODEC_ERR_UNKNOWNIMAGEFORMAT);
+    }
+
+    #[test]
+    fn truncated_headers_are_errors() {
+        let element = explicit(MODALITY, b"CS", b"CT", None);
+        let data = part10(EXPLICIT_LITTLE_ENDIAN, &element);
+        // Cut anywhere inside the last element
+        for len in data.len() - element.len() + 1..data.len() {
+            assert!(Header::parse(&data[..len]).is_err(), "{} bytes", len);
+        }
+        // A length past the end of the file
+        let data = part10(EXPLICIT_LITTLE_ENDIAN, &explicit(PIXEL_DATA, b"OW", &[0; 4], Some(u32::MAX - 1)));
+        assert_eq!(error_code(&data), WINCODEC_ERR_BADHEADER);
+    }
+
+    #[test]
+    fn reads_the_data_set_in_the_transfer_syntax() {
+        let body = [implicit(ROWS, &5u16.to_le_bytes(), None), implicit(PIXEL_DATA, &[], None)].concat();
+        let data = part10(IMPLICIT_LITTLE_ENDIAN, &body);
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.transfer_syntax, IMPLICIT_LITTLE_ENDIAN);
+        assert_eq!(header.rows, 5);
+
+        // Read as explicit VR, the same bytes are not a valid data set
+        assert!(Header::parse(&part10(EXPLICIT_LITTLE_ENDIAN, &body)).map(|h| h.rows != 5).unwrap_or(true));
+    }
+
+    #[test]
+    fn reads_encapsulated_jpeg_fragments() {
+        let body = [
+            explicit(PIXEL_DATA, b"OB", &[], Some(UNDEFINED_LENGTH)),
+            // Basic offset table, then two fragments of the frame
+            implicit(ITEM, &[], None),
+            implicit(ITEM, &[0xFF, 0xD8], None),
+            implicit(ITEM, &[0xFF, 0xD9], None),
+            implicit(SEQUENCE_DELIMITER, &[], None),
+        ]
+        .concat();
+        let data = part10(JPEG_BASELINE, &body);
+        let header = Header::parse(&data).unwrap();
+        let Some(PixelData::Encapsulated(fragments)) = header.pixels else { panic!("no fragments") };
+        assert_eq!(fragments, [&[0xFF, 0xD8][..], &[0xFF, 0xD9][..]]);
+    }
+
+    #[test]
+    fn refuses_unsupported_transfer_syntaxes() {
+        // JPEG 2000 lossless, and a file meta group without a transfer syntax
+        assert_eq!(error_code(&part10("1.2.840.10008.1.2.4.90", &[])), WINCODEC_ERR_UNKNOWNIMAGEFORMAT);
+        assert_eq!(error_code(&part10("", &[])), WINCODEC_ERR_UNKNOWNIMAGEFORMAT);
+        assert_eq!(error_code(&part10("1.2.3", &[])), WINCODEC_ERR_UNKNOWNIMAGEFORMAT);
+    }
+
+    /// A sequence of undefined length with `depth` nested items, each closed by its delimiter
+    fn nested_sequence(depth: usize) -> Vec<u8> {
+        let mut out = explicit((0x0008, 0x1140), b"SQ", &[], Some(UNDEFINED_LENGTH));
+        for _ in 0..depth {
+            out.extend(implicit(ITEM, &[], Some(UNDEFINED_LENGTH)));
+            out.extend(explicit((0x0008, 0x1150), b"UI", b"1.2", None));
+        }
+        for _ in 0..depth {
+            out.extend(implicit(ITEM_DELIMITER, &[], None));
+        }
+        out.extend(implicit(SEQUENCE_DELIMITER, &[], None));
+        out
+    }
+
+    #[test]
+    fn skips_nested_sequences() {
+        let body = [nested_sequence(3), explicit(MODALITY, b"CS", b"US", None)].concat();
+        let data = part10(EXPLICIT_LITTLE_ENDIAN, &body);
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.modality.as_deref(), Some("US"));
+
+        let body = [nested_sequence(MAX_NESTING - 1), explicit(MODALITY, b"CS", b"US", None)].concat();
+        assert!(Header::parse(&part10(EXPLICIT_LITTLE_ENDIAN, &body)).is_ok());
+    }
+
+    #[test]
+    fn deep_nesting_is_refused() {
+        let body = [nested_sequence(MAX_NESTING), explicit(MODALITY, b"CS", b"US", None)].concat();
+        assert_eq!(error_code(&part10(EXPLICIT_LITTLE_ENDIAN, &body)), WINCODEC_ERR_BADHEADER);
+
+        // Far deeper than any stack would allow when recursing per level
+        let body = nested_sequence(1_000_000);
+        assert_eq!(error_code(&part10(EXPLICIT_LITTLE_ENDIAN, &body)), WINCODEC_ERR_BADHEADER);
+    }
+}
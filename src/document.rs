@@ -1,5 +1,11 @@
-use parking_lot::Mutex;
+use crate::annotations::Link;
+use crate::page_transform::PageTransform;
+use crate::pdf::{PdfFile, PdfStructure};
+use crate::window_level::{Gray16, WindowLevel};
+use once_cell::unsync::OnceCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::rc::Rc;
 use windows::{
     core::*,
     Win32::Graphics::{
@@ -8,23 +14,74 @@ use windows::{
     },
 };
 
-/// Vertical gap between pages in pixels (at zoom 1.0)
-pub const PAGE_GAP: i32 = 20;
+/// "Seitenabstand" in continuous mode, in pixels at zoom 1.0 (scaled with the pages): the
+/// gap between pages and the margin above the first and below the last page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageSpacing {
+    pub gap: i32,
+    pub margin: i32,
+}
+
+impl PageSpacing {
+    /// "Eng": pages nearly touch, for small screens
+    pub const NARROW: Self = Self { gap: 4, margin: 4 };
+    /// "Normal" (default)
+    pub const NORMAL: Self = Self { gap: 20, margin: 20 };
+    /// "Weit": a clear break between pages, e.g. for presentations
+    pub const WIDE: Self = Self { gap: 60, margin: 40 };
+}
 
 /// Maximum number of page bitmaps to keep in cache
 pub const MAX_CACHED_PAGES: usize = 20;
 
+/// "Doppelseiten teilen": image pages wider than this times their height are two pages
+pub const SPREAD_ASPECT: f32 = 1.2;
+
+/// Memory of 32bpp bitmaps of the given sizes, for the warning before very large documents.
+/// Saturates, so sizes from a crafted header cannot wrap around to a small estimate.
+pub fn bitmap_bytes(sizes: impl IntoIterator<Item = (u32, u32)>) -> u64 {
+    sizes
+        .into_iter()
+        .map(|(width, height)| (width as u64 * height as u64).saturating_mul(4))
+        .fold(0, u64::saturating_add)
+}
+
+/// Pages of `cached` to drop so that at most MAX_CACHED_PAGES stay: the farthest from
+/// `center_page`
+fn distant_pages(mut cached: Vec<usize>, center_page: usize) -> Vec<usize> {
+    if cached.len() <= MAX_CACHED_PAGES {
+        return Vec::new();
+    }
+    cached.sort_by_key(|&page| page.abs_diff(center_page));
+    cached.split_off(MAX_CACHED_PAGES)
+}
+
 /// Pre-computed layout information for multi-page rendering
 #[derive(Clone, Debug)]
 pub struct PageLayout {
     /// Y-position of each page's top edge (in scaled pixels)
     pub page_tops: Vec<i32>,
-    /// Total document height including all pages and gaps
+    /// Total document height including all pages, gaps and the outer margins
     pub total_height: i32,
     /// Width of the widest page (for horizontal centering)
     pub max_width: i32,
     /// Individual page dimensions (width, height) after rotation, scaled
     pub page_sizes: Vec<(i32, i32)>,
+    /// How each page is shown: its shown part (see `Document::display_rect`) and rotation.
+    /// Without the straightening, which is measured after the layout.
+    pub transforms: Vec<PageTransform>,
+}
+
+impl PageLayout {
+    /// Scroll position that shows `page` at the top of the view: the first page with the
+    /// margin above it, the others right at their top edge
+    pub fn scroll_target(&self, page: usize) -> i32 {
+        if page == 0 {
+            0
+        } else {
+            self.page_tops.get(page).copied().unwrap_or(0)
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -33,40 +90,111 @@ pub enum DocumentType {
     Pdf,
 }
 
+/// Render state of a page
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PageState {
+    Ready,
+    /// Size is known, pixels are rendered on demand
+    Pending,
+    /// Rendering failed
+    Failed,
+}
+
+/// Cloning is cheap: `App::on_paint` draws a clone of the state every frame, so pages and
+/// the bitmap cache are shared between clones instead of copying pixels per frame.
+#[derive(Clone)]
 pub struct Document {
     doc_type: DocumentType,
-    pages: Vec<PageData>,
-    bitmap_cache: Mutex<HashMap<usize, ID2D1Bitmap>>,
+    pages: Rc<Vec<PageData>>,
+    bitmap_cache: Rc<RefCell<BitmapCache>>,
+    /// Window of the 16-bit grayscale pages, `None` = the value range of each page
+    window_level: Rc<Cell<Option<WindowLevel>>>,
+    /// The open PDF of a PDF document; its pages are rendered from it on demand
+    pdf: Option<Rc<PdfFile>>,
+    /// Links and page labels of a PDF, set once read on a worker thread after loading
+    structure: Rc<OnceCell<PdfStructure>>,
+    /// Content rectangles for "Ränder automatisch zuschneiden", measured once per page
+    /// (`None` = nothing to crop), and whether pages were measured since the last layout
+    content_rects: Rc<RefCell<HashMap<usize, Option<[f32; 4]>>>>,
+    new_content_rects: Rc<Cell<bool>>,
+    /// Pages of a document with "Doppelseiten teilen": the image page each one is cut from
+    /// and its part of it; `None` = one page per image page
+    parts: Option<Rc<Vec<PagePart>>>,
+}
+
+/// A page cut from an image page (one half of a double-page scan)
+struct PagePart {
+    source: usize,
+    /// [left, top, right, bottom] in pixels of the image page
+    rect: [f32; 4],
+}
+
+/// D2D bitmaps of the render target that created them
+#[derive(Default)]
+struct BitmapCache {
+    target: Option<ID2D1RenderTarget>,
+    bitmaps: HashMap<usize, ID2D1Bitmap>,
 }
 
+#[derive(Default)]
 pub struct PageData {
     pub width: f32,
     pub height: f32,
+    /// Pixels per inch of `width`/`height` if known (image resolution, PDF render scale)
+    pub dpi: Option<f32>,
+    /// Container and pixel format of the original image before the conversion to 32bpp,
+    /// e.g. "TIFF, 16-Bit Graustufen" (`None` for PDF pages and pasted bitmaps)
+    pub source_format: Option<String>,
+    /// Header fields shown under "Eigenschaften", e.g. modality and study date of a DICOM image
+    pub tags: Vec<(&'static str, String)>,
     pub wic_bitmap: Option<IWICBitmap>,
     pub pixel_data: Option<Vec<u8>>,
     pub stride: u32,
+    /// ZIP/CBZ entry, decompressed and decoded on first access
+    pub archive_entry: Option<crate::archive::ArchiveEntry>,
+    /// Lazily decoded bitmap for `archive_entry`, `None` if it could not be read, so a
+    /// damaged entry is not decompressed again on every paint
+    pub decoded_bitmap: OnceCell<Option<IWICBitmap>>,
+    /// 16-bit grayscale source kept for window/level; the display bitmap is made from it
+    pub gray16: Option<Gray16>,
+    /// Pixels of a PDF page rendered on demand at width/height/stride, `None` if rendering failed
+    pub rendered_pixels: OnceCell<Option<Vec<u8>>>,
 }
 
 impl Document {
-    pub fn new_image(wic_bitmap: IWICBitmap, width: u32, height: u32) -> Self {
+    pub fn new_image(wic_bitmap: IWICBitmap, width: u32, height: u32, dpi: Option<f32>, source_format: Option<String>) -> Self {
         Self {
             doc_type: DocumentType::Image,
-            pages: vec![PageData {
+            pages: Rc::new(vec![PageData {
                 width: width as f32,
                 height: height as f32,
+                dpi,
+                source_format,
                 wic_bitmap: Some(wic_bitmap),
-                pixel_data: None,
-                stride: 0,
-            }],
-            bitmap_cache: Mutex::new(HashMap::new()),
+                ..Default::default()
+            }]),
+            bitmap_cache: Rc::default(),
+            window_level: Rc::default(),
+            pdf: None,
+            structure: Rc::default(),
+            content_rects: Rc::default(),
+            new_content_rects: Rc::default(),
+            parts: None,
         }
     }
 
-    pub fn new_pdf(pages: Vec<PageData>) -> Self {
+    /// `pages` have their display size set; the pixels are rendered from `pdf`
+    pub fn new_pdf(pages: Vec<PageData>, pdf: PdfFile) -> Self {
         Self {
             doc_type: DocumentType::Pdf,
-            pages,
-            bitmap_cache: Mutex::new(HashMap::new()),
+            pages: Rc::new(pages),
+            bitmap_cache: Rc::default(),
+            window_level: Rc::default(),
+            pdf: Some(Rc::new(pdf)),
+            structure: Rc::default(),
+            content_rects: Rc::default(),
+            new_content_rects: Rc::default(),
+            parts: None,
         }
     }
 
@@ -74,8 +202,14 @@ impl Document {
     pub fn new_multiframe_image(pages: Vec<PageData>) -> Self {
         Self {
             doc_type: DocumentType::Image,
-            pages,
-            bitmap_cache: Mutex::new(HashMap::new()),
+            pages: Rc::new(pages),
+            bitmap_cache: Rc::default(),
+            window_level: Rc::default(),
+            pdf: None,
+            structure: Rc::default(),
+            content_rects: Rc::default(),
+            new_content_rects: Rc::default(),
+            parts: None,
         }
     }
 
@@ -84,38 +218,251 @@ impl Document {
     }
 
     pub fn page_count(&self) -> usize {
-        self.pages.len()
+        self.parts.as_ref().map_or(self.pages.len(), |parts| parts.len())
     }
 
-    pub fn dimensions(&self) -> (f32, f32) {
-        if let Some(page) = self.pages.first() {
-            (page.width, page.height)
-        } else {
-            (0.0, 0.0)
+    /// "Doppelseiten teilen": the same pages with each image page wider than
+    /// `SPREAD_ASPECT` shown as two pages, cut at `split` (share of the width). `None`
+    /// shows each image page whole again. PDF pages are not split.
+    pub fn with_split(&self, split: Option<f32>) -> Document {
+        let parts = split.filter(|_| self.doc_type == DocumentType::Image).map(|split| {
+            let mut parts = Vec::with_capacity(self.pages.len() * 2);
+            for (source, p) in self.pages.iter().enumerate() {
+                if p.width > p.height * SPREAD_ASPECT {
+                    let cut = (p.width * split).round().clamp(1.0, p.width - 1.0);
+                    parts.push(PagePart { source, rect: [0.0, 0.0, cut, p.height] });
+                    parts.push(PagePart { source, rect: [cut, 0.0, p.width, p.height] });
+                } else {
+                    parts.push(PagePart { source, rect: [0.0, 0.0, p.width, p.height] });
+                }
+            }
+            Rc::new(parts)
+        });
+        Document {
+            parts,
+            // Page numbers change, so the measurements are made again
+            content_rects: Rc::default(),
+            new_content_rects: Rc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Any image page is shown as two pages
+    pub fn is_split(&self) -> bool {
+        self.parts.as_ref().is_some_and(|parts| parts.len() > self.pages.len())
+    }
+
+    /// Image page (frame, archive entry) a page is cut from; the page itself unless split
+    pub fn source_page(&self, page: usize) -> usize {
+        match self.parts {
+            Some(ref parts) => parts.get(page).map_or(page, |part| part.source),
+            None => page,
+        }
+    }
+
+    /// First page cut from an image page, the inverse of `source_page`
+    pub fn page_of_source(&self, source: usize) -> usize {
+        match self.parts {
+            Some(ref parts) => parts.iter().position(|part| part.source == source).unwrap_or(0),
+            None => source,
         }
     }
 
+    /// Part of its image page a split page shows, [left, top, right, bottom] in pixels of
+    /// the image page; `None` for pages shown whole
+    pub fn page_clip(&self, page: usize) -> Option<[f32; 4]> {
+        let part = self.parts.as_ref()?.get(page)?;
+        let p = self.pages.get(part.source)?;
+        (part.rect != [0.0, 0.0, p.width, p.height]).then_some(part.rect)
+    }
+
+    /// Size of the image page a page is cut from (`page_dimensions` unless split)
+    pub fn image_dimensions(&self, page: usize) -> (f32, f32) {
+        self.page_data(page).map_or((0.0, 0.0), |p| (p.width, p.height))
+    }
+
+    fn page_data(&self, page: usize) -> Option<&PageData> {
+        self.pages.get(self.source_page(page))
+    }
+
+    /// Pixels per inch of a page if known (see `PageData::dpi`)
+    pub fn page_dpi(&self, page: usize) -> Option<f32> {
+        self.page_data(page)?.dpi
+    }
+
     pub fn page_dimensions(&self, page: usize) -> (f32, f32) {
-        if let Some(p) = self.pages.get(page) {
-            (p.width, p.height)
-        } else {
-            (0.0, 0.0)
+        match self.page_clip(page) {
+            Some([left, top, right, bottom]) => (right - left, bottom - top),
+            None => self.image_dimensions(page),
+        }
+    }
+
+    /// Part of a page that is shown, [left, top, right, bottom] in source pixels: the whole
+    /// page, or with `auto_crop` its content without white margins (see auto_crop.rs). A
+    /// page is measured once its pixels are at hand; until then (pending PDF pages, archive
+    /// entries not decoded yet) the whole page is shown.
+    pub fn display_rect(&self, page: usize, auto_crop: bool) -> [f32; 4] {
+        let (width, height) = self.page_dimensions(page);
+        let full = [0.0, 0.0, width, height];
+        if !auto_crop {
+            return full;
         }
+        if let Some(rect) = self.content_rects.borrow().get(&page) {
+            return rect.unwrap_or(full);
+        }
+        let Some(rect) = self.measure_content(page) else {
+            return full;
+        };
+        self.content_rects.borrow_mut().insert(page, rect);
+        self.new_content_rects.set(true);
+        rect.unwrap_or(full)
+    }
+
+    /// Width and height of `display_rect`, unrotated
+    pub fn display_dimensions(&self, page: usize, auto_crop: bool) -> (f32, f32) {
+        let [left, top, right, bottom] = self.display_rect(page, auto_crop);
+        (right - left, bottom - top)
+    }
+
+    /// True once after pages were measured, so the layout is made again with their size
+    pub fn take_new_content_rects(&self) -> bool {
+        self.new_content_rects.replace(false)
+    }
+
+    /// Content rectangle of a page in source pixels, `Some(None)` if there is nothing to
+    /// crop or the pixels cannot be read, `None` while the pixels are not at hand
+    fn measure_content(&self, page: usize) -> Option<Option<[f32; 4]>> {
+        let p = self.page_data(page)?;
+        let clip = self.page_clip(page).unwrap_or([0.0, 0.0, p.width, p.height]);
+        // Measures the part of the page in pixels of `width` x `height`, as page pixels
+        let measure = |pixels: &[u8], (width, height): (u32, u32), stride: u32| {
+            let (scale_x, scale_y) = (width as f32 / p.width.max(1.0), height as f32 / p.height.max(1.0));
+            let left = ((clip[0] * scale_x) as u32).min(width);
+            let top = ((clip[1] * scale_y) as u32).min(height);
+            let right = ((clip[2] * scale_x) as u32).clamp(left, width);
+            let bottom = ((clip[3] * scale_y) as u32).clamp(top, height);
+            let start = (top * stride + left * 4) as usize;
+            let rect = crate::auto_crop::content_rect(pixels.get(start..)?, right - left, bottom - top, stride)?;
+            Some([
+                rect[0] as f32 / scale_x,
+                rect[1] as f32 / scale_y,
+                rect[2] as f32 / scale_x,
+                rect[3] as f32 / scale_y,
+            ])
+        };
+        let size = (p.width as u32, p.height as u32);
+        if let Some(ref data) = p.pixel_data {
+            return Some(measure(data, size, p.stride));
+        }
+        if self.pdf.is_some() {
+            return match p.rendered_pixels.get()? {
+                Some(data) => Some(measure(data, size, p.stride)),
+                None => Some(None),
+            };
+        }
+        if p.gray16.is_none() && p.wic_bitmap.is_none() && p.decoded_bitmap.get().is_none() {
+            return None;
+        }
+        let Some(bitmap) = self.get_wic_bitmap(page) else {
+            return Some(None);
+        };
+        let measured = unsafe {
+            (|| -> Result<_> {
+                let (mut width, mut height) = (0u32, 0u32);
+                bitmap.GetSize(&mut width, &mut height)?;
+                let format = bitmap.GetPixelFormat()?;
+                if format != GUID_WICPixelFormat32bppPBGRA && format != GUID_WICPixelFormat32bppBGRA {
+                    return Ok(None);
+                }
+                let rect = WICRect { X: 0, Y: 0, Width: width as i32, Height: height as i32 };
+                let lock = bitmap.Lock(&rect, WICBitmapLockRead.0 as u32)?;
+                let stride = lock.GetStride()?;
+                let (mut len, mut data) = (0u32, std::ptr::null_mut());
+                lock.GetDataPointer(&mut len, &mut data)?;
+                if data.is_null() {
+                    return Ok(None);
+                }
+                let pixels = std::slice::from_raw_parts(data, len as usize);
+                Ok(measure(pixels, (width, height), stride))
+            })()
+        };
+        Some(measured.unwrap_or(None))
+    }
+
+    /// Clickable links of a PDF page; none until the structure is read
+    pub fn links(&self, page: usize) -> &[Link] {
+        self.structure.get().and_then(|s| s.links.get(self.source_page(page))).map_or(&[], Vec::as_slice)
+    }
+
+    /// Page label of a PDF page, e.g. "iv" (see page_labels.rs); `None` = the page number
+    pub fn page_label(&self, page: usize) -> Option<&str> {
+        let labels = self.structure.get()?.labels.as_ref()?;
+        labels.get(self.source_page(page)).map(String::as_str)
     }
 
-    pub fn get_page_bitmap(&self, rt: &ID2D1HwndRenderTarget, page: usize) -> Result<ID2D1Bitmap> {
+    /// Takes the links and labels read by `pdf::read_structure`; shared with all clones.
+    /// False if the document already has them.
+    pub fn set_structure(&self, structure: PdfStructure) -> bool {
+        self.structure.set(structure).is_ok()
+    }
+
+    /// Physical page (0-based) for a page label or number typed by the user
+    pub fn find_page(&self, text: &str) -> Option<usize> {
+        let labels: Option<Vec<String>> = (0..self.page_count()).map(|page| self.page_label(page).map(str::to_string)).collect();
+        crate::page_labels::find(labels.as_deref(), self.page_count(), text)
+    }
+
+    /// Size of a page for the status bar, e.g. "210×297 mm · 794×1123 px"
+    pub fn page_size_text(&self, page: usize) -> String {
+        match self.page_data(page) {
+            Some(p) => {
+                let (width, height) = self.page_dimensions(page);
+                format_page_size(width, height, p.dpi)
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Source format of an image page with its resolution, e.g. "TIFF, 16-Bit Graustufen, 600 dpi"
+    pub fn format_text(&self, page: usize) -> Option<String> {
+        let p = self.page_data(page)?;
+        let format = p.source_format.as_ref()?;
+        Some(match p.dpi {
+            Some(dpi) => format!("{}, {:.0} dpi", format, dpi),
+            None => format.clone(),
+        })
+    }
+
+    /// Header fields of a page for the properties dialog
+    pub fn tags(&self, page: usize) -> &[(&'static str, String)] {
+        self.page_data(page).map(|p| p.tags.as_slice()).unwrap_or(&[])
+    }
+
+    /// Bitmap of the whole image page `page` is cut from (see `page_clip`)
+    pub fn get_page_bitmap(&self, rt: &ID2D1RenderTarget, page: usize) -> Result<ID2D1Bitmap> {
+        // Both halves of a split page share the bitmap
+        let page = self.source_page(page);
         // Check cache first
         {
-            let cache = self.bitmap_cache.lock();
-            if let Some(bitmap) = cache.get(&page) {
+            let mut cache = self.bitmap_cache.borrow_mut();
+            // Bitmaps of a previous (recreated) render target can't be drawn anymore
+            if cache.target.as_ref() != Some(rt) {
+                cache.bitmaps.clear();
+                cache.target = Some(rt.clone());
+            }
+            if let Some(bitmap) = cache.bitmaps.get(&page) {
                 return Ok(bitmap.clone());
             }
         }
 
-        // Create bitmap from page data
+        // Create bitmap from page data (pending PDF pages are rendered by App::render_pending_page)
         let page_data = self.pages.get(page).ok_or_else(Error::from_win32)?;
+        let pixel_data = page_data
+            .pixel_data
+            .as_ref()
+            .or_else(|| page_data.rendered_pixels.get().and_then(|p| p.as_ref()));
 
-        let bitmap = if let Some(ref wic_bitmap) = page_data.wic_bitmap {
+        let bitmap = if let Some(wic_bitmap) = self.get_wic_bitmap(page) {
             // Create D2D bitmap from WIC bitmap
             unsafe {
                 let props = D2D1_BITMAP_PROPERTIES {
@@ -127,9 +474,9 @@ impl Document {
                     dpiY: 96.0,
                 };
 
-                rt.CreateBitmapFromWicBitmap(wic_bitmap, Some(&props))?
+                rt.CreateBitmapFromWicBitmap(&wic_bitmap, Some(&props))?
             }
-        } else if let Some(ref pixel_data) = page_data.pixel_data {
+        } else if let Some(pixel_data) = pixel_data {
             // Create D2D bitmap from raw pixel data
             unsafe {
                 let size = windows::Win32::Graphics::Direct2D::Common::D2D_SIZE_U {
@@ -153,46 +500,132 @@ impl Document {
         };
 
         // Cache the bitmap
-        self.bitmap_cache.lock().insert(page, bitmap.clone());
+        self.bitmap_cache.borrow_mut().bitmaps.insert(page, bitmap.clone());
 
         Ok(bitmap)
     }
 
+    /// Number of cached page bitmaps and their estimated size in bytes (32bpp)
+    pub fn cache_stats(&self) -> (usize, usize) {
+        let cache = self.bitmap_cache.borrow();
+        let bytes = cache
+            .bitmaps
+            .values()
+            .map(|bitmap| {
+                let size = unsafe { bitmap.GetPixelSize() };
+                size.width as usize * size.height as usize * 4
+            })
+            .sum();
+        (cache.bitmaps.len(), bytes)
+    }
+
     #[allow(dead_code)]
     pub fn clear_cache(&self) {
-        self.bitmap_cache.lock().clear();
+        self.bitmap_cache.borrow_mut().bitmaps.clear();
+    }
+
+    /// Returns the WIC bitmap of a page, decoding archive entries on first access and
+    /// windowing 16-bit grayscale pages. Split pages give the whole image page.
+    pub fn get_wic_bitmap(&self, page: usize) -> Option<IWICBitmap> {
+        let p = self.page_data(page)?;
+        if let Some(ref gray) = p.gray16 {
+            return gray.display_bitmap(self.window_level.get().unwrap_or_else(|| gray.default_window())).ok();
+        }
+        if let Some(ref bitmap) = p.wic_bitmap {
+            return Some(bitmap.clone());
+        }
+        let entry = p.archive_entry.as_ref()?;
+        p.decoded_bitmap
+            .get_or_init(|| entry.read().and_then(|data| crate::wic::decode_from_memory(&data)).ok())
+            .clone()
+    }
+
+    /// Any page kept with 16-bit grayscale values
+    pub fn has_high_bit_depth(&self) -> bool {
+        self.pages.iter().any(|p| p.gray16.is_some())
     }
 
-    pub fn get_wic_bitmap(&self, page: usize) -> Option<&IWICBitmap> {
-        self.pages.get(page).and_then(|p| p.wic_bitmap.as_ref())
+    /// Current window of a 16-bit grayscale page, `None` for other pages
+    pub fn window_level(&self, page: usize) -> Option<WindowLevel> {
+        let gray = self.page_data(page)?.gray16.as_ref()?;
+        Some(self.window_level.get().unwrap_or_else(|| gray.default_window()))
     }
 
+    /// Sets the window of all 16-bit grayscale pages (`None` = back to each page's range);
+    /// their display bitmaps are made again on the next paint
+    pub fn set_window_level(&self, window: Option<WindowLevel>) {
+        self.window_level.set(window);
+        self.bitmap_cache.borrow_mut().bitmaps.clear();
+    }
+
+    /// Returns the pixels of a page, rendering a pending PDF page first. Split pages give
+    /// the whole image page.
     pub fn get_pixel_data(&self, page: usize) -> Option<(&[u8], u32, u32, u32)> {
-        self.pages.get(page).and_then(|p| {
-            p.pixel_data.as_ref().map(|data| {
-                (data.as_slice(), p.width as u32, p.height as u32, p.stride)
-            })
-        })
+        let p = self.page_data(page)?;
+        let data = match p.pixel_data {
+            Some(ref data) => data,
+            None => {
+                self.render_page(page);
+                p.rendered_pixels.get()?.as_ref()?
+            }
+        };
+        Some((data.as_slice(), p.width as u32, p.height as u32, p.stride))
+    }
+
+    pub fn page_state(&self, page: usize) -> PageState {
+        match self.page_data(page) {
+            Some(p) if self.pdf.is_some() && p.pixel_data.is_none() => match p.rendered_pixels.get() {
+                None => PageState::Pending,
+                Some(Some(_)) => PageState::Ready,
+                Some(None) => PageState::Failed,
+            },
+            _ => PageState::Ready,
+        }
+    }
+
+    /// Renders a pending PDF page. Does nothing for pages that are already rendered.
+    pub fn render_page(&self, page: usize) {
+        if let (Some(p), Some(pdf)) = (self.pages.get(page), self.pdf.as_ref()) {
+            if p.pixel_data.is_none() {
+                p.rendered_pixels.get_or_init(|| pdf.render_page_for_display(page).ok().map(|(pixels, _, _)| pixels));
+            }
+        }
+    }
+
+    /// Renders a PDF page again `target_px_width` pixels wide from the open document, e.g.
+    /// at a higher resolution than shown; `None` for image documents
+    #[allow(dead_code)]
+    pub fn render_pdf_page(&self, page: usize, target_px_width: u32) -> Option<Result<(Vec<u8>, u32, u32)>> {
+        Some(self.pdf.as_ref()?.render_page(page, target_px_width))
+    }
+
+    /// Password the PDF was opened with, so a reload does not ask again
+    pub fn pdf_password(&self) -> Option<&str> {
+        self.pdf.as_ref()?.password()
     }
 
     /// Compute layout for multi-page vertical stacking
     ///
     /// Returns pre-computed Y positions for each page top, total height,
-    /// and maximum width for horizontal centering.
-    pub fn compute_layout(&self, zoom: f32, rotation: i32) -> PageLayout {
+    /// and maximum width for horizontal centering. `rotation_of` gives the
+    /// rotation of each page, which can differ per page. With `auto_crop` pages take the
+    /// size of their content. The total height includes the margins of `spacing`, so the
+    /// last page can be scrolled up as far as the first one starts.
+    pub fn compute_layout(&self, zoom: f32, auto_crop: bool, spacing: PageSpacing, rotation_of: impl Fn(usize) -> i32) -> PageLayout {
         let mut page_tops = Vec::with_capacity(self.pages.len());
         let mut page_sizes = Vec::with_capacity(self.pages.len());
-        let mut current_y: i32 = 0;
+        let mut transforms = Vec::with_capacity(self.pages.len());
+        let scaled_gap = (spacing.gap as f32 * zoom) as i32;
+        let scaled_margin = (spacing.margin as f32 * zoom) as i32;
+        let mut current_y: i32 = scaled_margin;
         let mut max_width: i32 = 0;
-        let scaled_gap = (PAGE_GAP as f32 * zoom) as i32;
 
-        for (i, page) in self.pages.iter().enumerate() {
+        let page_count = self.page_count();
+        for i in 0..page_count {
             // Determine dimensions based on rotation
-            let (w, h) = if rotation == 90 || rotation == 270 {
-                (page.height, page.width)
-            } else {
-                (page.width, page.height)
-            };
+            let transform = PageTransform::displayed(self, i, rotation_of(i), auto_crop, 0.0);
+            let source = transform.source_rect(self, i);
+            let (w, h) = transform.output_size(source[2] - source[0], source[3] - source[1]);
 
             // Scale by zoom
             let scaled_w = (w * zoom) as i32;
@@ -200,21 +633,23 @@ impl Document {
 
             page_tops.push(current_y);
             page_sizes.push((scaled_w, scaled_h));
+            transforms.push(transform);
 
             max_width = max_width.max(scaled_w);
             current_y += scaled_h;
 
             // Add gap after each page except the last
-            if i < self.pages.len() - 1 {
+            if i < page_count - 1 {
                 current_y += scaled_gap;
             }
         }
 
         PageLayout {
             page_tops,
-            total_height: current_y,
+            total_height: current_y + scaled_margin,
             max_width,
             page_sizes,
+            transforms,
         }
     }
 
@@ -227,7 +662,8 @@ impl Document {
         scroll_y: i32,
         viewport_height: i32,
     ) -> (usize, usize) {
-        if self.pages.is_empty() {
+        let page_count = self.page_count();
+        if page_count == 0 {
             return (0, 0);
         }
 
@@ -246,47 +682,112 @@ impl Document {
 
         // Linear scan forward for last visible page
         let mut last_visible = first_visible;
-        for i in first_visible..self.pages.len() {
+        for i in first_visible..page_count {
             if layout.page_tops[i] >= viewport_bottom {
                 break;
             }
             last_visible = i + 1;
         }
 
-        (first_visible, last_visible.min(self.pages.len()))
+        (first_visible, last_visible.min(page_count))
     }
 
     /// Evict distant pages from cache to limit memory usage
     pub fn evict_distant_pages(&self, center_page: usize) {
-        let mut cache = self.bitmap_cache.lock();
+        let cache = &mut self.bitmap_cache.borrow_mut().bitmaps;
         if cache.len() <= MAX_CACHED_PAGES {
             return;
         }
-
-        // Collect page indices sorted by distance from center
-        let mut pages: Vec<usize> = cache.keys().copied().collect();
-        pages.sort_by_key(|&p| (p as i32 - center_page as i32).abs());
-
-        // Remove pages beyond the limit
-        for &page in pages.iter().skip(MAX_CACHED_PAGES) {
+        let center_page = self.source_page(center_page);
+        for page in distant_pages(cache.keys().copied().collect(), center_page) {
             cache.remove(&page);
         }
     }
 }
 
-impl Clone for Document {
-    fn clone(&self) -> Self {
-        // Note: WIC bitmaps and cache are not cloned
-        Self {
-            doc_type: self.doc_type,
-            pages: self.pages.iter().map(|p| PageData {
-                width: p.width,
-                height: p.height,
-                wic_bitmap: p.wic_bitmap.clone(),
-                pixel_data: p.pixel_data.clone(),
-                stride: p.stride,
-            }).collect(),
-            bitmap_cache: Mutex::new(HashMap::new()),
+/// Formats a pixel size, with the physical size in front when the resolution is known
+pub fn format_page_size(width: f32, height: f32, dpi: Option<f32>) -> String {
+    let pixels = format!("{:.0}×{:.0} px", width, height);
+    match dpi {
+        Some(dpi) if dpi.is_finite() && dpi > 0.0 => {
+            let mm = |px: f32| px / dpi * 25.4;
+            format!("{:.0}×{:.0} mm · {}", mm(width), mm(height), pixels)
+        }
+        _ => pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitmap_bytes_adds_up_the_pages() {
+        assert_eq!(bitmap_bytes([]), 0);
+        assert_eq!(bitmap_bytes([(794, 1123)]), 794 * 1123 * 4);
+        assert_eq!(bitmap_bytes((0..1000).map(|_| (2048, 2048))), 1000 * 2048 * 2048 * 4);
+        // 65535² × 4 is still exact
+        assert_eq!(bitmap_bytes([(u16::MAX as u32, u16::MAX as u32)]), 65535 * 65535 * 4);
+    }
+
+    #[test]
+    fn bitmap_bytes_saturates_instead_of_wrapping() {
+        // One page of u32::MAX² × 4 bytes is past u64; wrapped it would look small
+        assert_eq!(bitmap_bytes([(u32::MAX, u32::MAX)]), u64::MAX);
+        assert_eq!(bitmap_bytes([(u32::MAX, 1 << 31), (1, 1)]), u64::MAX);
+        // Many large pages add up past u64 as well
+        assert_eq!(bitmap_bytes((0..8).map(|_| (u32::MAX, u32::MAX >> 2))), u64::MAX);
+    }
+
+    #[test]
+    fn eviction_keeps_the_cache_within_its_budget() {
+        let pages: Vec<usize> = (0..MAX_CACHED_PAGES).collect();
+        assert!(distant_pages(pages.clone(), 0).is_empty());
+
+        // Five pages too many: the farthest from page 25 go
+        let pages: Vec<usize> = (20..20 + MAX_CACHED_PAGES + 5).collect();
+        let mut evicted = distant_pages(pages.clone(), 25);
+        evicted.sort();
+        assert_eq!(evicted, [40, 41, 42, 43, 44]);
+        let mut evicted = distant_pages(pages.iter().rev().copied().collect(), 40);
+        evicted.sort();
+        assert_eq!(evicted, [20, 21, 22, 23, 24]);
+        assert_eq!(pages.len() - evicted.len(), MAX_CACHED_PAGES);
+    }
+
+    #[test]
+    fn eviction_near_the_ends_of_the_document() {
+        let pages: Vec<usize> = (0..50).collect();
+        let mut evicted = distant_pages(pages.clone(), 0);
+        evicted.sort();
+        assert_eq!(evicted, (MAX_CACHED_PAGES..50).collect::<Vec<_>>());
+        // A center past the cached pages keeps the last ones
+        let mut evicted = distant_pages(pages, 100);
+        evicted.sort();
+        assert_eq!(evicted, (0..50 - MAX_CACHED_PAGES).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn page_size_shows_mm_when_the_resolution_is_known() {
+        // A4 rendered at 96 dpi and at twice that, and scanned at 300 dpi
+        assert_eq!(format_page_size(793.7, 1122.52, Some(96.0)), "210×297 mm · 794×1123 px");
+        assert_eq!(format_page_size(1587.4, 2245.0, Some(192.0)), "210×297 mm · 1587×2245 px");
+        assert_eq!(format_page_size(2480.0, 3508.0, Some(300.0)), "210×297 mm · 2480×3508 px");
+        // A3 landscape appendix
+        assert_eq!(format_page_size(1587.4, 1122.52, Some(96.0)), "420×297 mm · 1587×1123 px");
+    }
+
+    #[test]
+    fn page_size_rounds_to_whole_units() {
+        assert_eq!(format_page_size(99.4, 99.6, None), "99×100 px");
+        // 1 inch = 25.4 mm, 10 inches = 254 mm
+        assert_eq!(format_page_size(72.0, 720.0, Some(72.0)), "25×254 mm · 72×720 px");
+    }
+
+    #[test]
+    fn page_size_without_resolution_is_in_pixels() {
+        for dpi in [None, Some(0.0), Some(-96.0), Some(f32::NAN), Some(f32::INFINITY)] {
+            assert_eq!(format_page_size(640.0, 480.0, dpi), "640×480 px", "{dpi:?}");
         }
     }
 }
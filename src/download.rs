@@ -0,0 +1,324 @@
+//! HTTP(S) download of documents passed as URL on the command line
+//!
+//! Intranet links point directly at PDFs/images. The file is downloaded with WinHTTP
+//! into a private temp folder and opened from there; the temp copy is removed again
+//! when the document is closed or the application exits (see `TempDownload`). Esc aborts
+//! a download like a load (`CancelToken`).
+//! Small resources such as the update manifest are read into memory (`fetch_text`).
+
+use crate::cancel::CancelToken;
+use crate::utils::percent_decode;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use windows::{
+    core::*,
+    Win32::{Foundation::{GetLastError, WIN32_ERROR}, Networking::WinHttp::*},
+};
+
+// Timeouts in milliseconds (resolve, connect, send, receive)
+const RESOLVE_TIMEOUT_MS: i32 = 10_000;
+const CONNECT_TIMEOUT_MS: i32 = 15_000;
+const SEND_TIMEOUT_MS: i32 = 30_000;
+const RECEIVE_TIMEOUT_MS: i32 = 30_000;
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+static DOWNLOAD_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Returns true if the argument is an http:// or https:// URL
+pub fn is_url(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// A downloaded temp file, deleted (with its folder) when dropped
+pub struct TempDownload {
+    path: PathBuf,
+}
+
+impl TempDownload {
+    pub fn path(&self) -> &str {
+        self.path.to_str().unwrap_or_default()
+    }
+}
+
+impl Drop for TempDownload {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+        if let Some(dir) = self.path.parent() {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+/// RAII wrapper for WinHTTP handles
+struct InternetHandle(*mut std::ffi::c_void);
+
+impl InternetHandle {
+    fn new(handle: *mut std::ffi::c_void) -> Result<Self> {
+        if handle.is_null() {
+            Err(last_http_error())
+        } else {
+            Ok(Self(handle))
+        }
+    }
+}
+
+impl Drop for InternetHandle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = WinHttpCloseHandle(self.0);
+        }
+    }
+}
+
+/// Downloads `url` into a new temp folder.
+///
+/// `progress` is called after every chunk with the received byte count and the
+/// total size if the server sent a Content-Length. `cancel` is checked after every chunk;
+/// an aborted download returns E_CANCELLED and its temp file is removed.
+pub fn download_to_temp(url: &str, cancel: &CancelToken, mut progress: impl FnMut(u64, Option<u64>)) -> Result<TempDownload> {
+    unsafe {
+        let response = get(url)?;
+        let total = query_number(&response.request, WINHTTP_QUERY_CONTENT_LENGTH).map(|n| n as u64);
+        let content_type = query_string(&response.request, WINHTTP_QUERY_CONTENT_TYPE).unwrap_or_default();
+
+        // Create a private temp folder so the original filename can be kept
+        let counter = DOWNLOAD_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!("SimpliView-{}-{}", std::process::id(), counter));
+        std::fs::create_dir_all(&dir).map_err(|e| io_error(&e))?;
+        let download = TempDownload {
+            path: dir.join(file_name_for(&response.object, &content_type)),
+        };
+
+        let mut file = std::fs::File::create(&download.path).map_err(|e| io_error(&e))?;
+        let mut buffer = vec![0u8; READ_CHUNK_SIZE];
+        let mut received: u64 = 0;
+        progress(0, total);
+
+        loop {
+            cancel.check()?;
+            let read = response.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read]).map_err(|e| io_error(&e))?;
+            received += read as u64;
+            progress(received, total);
+        }
+
+        Ok(download)
+    }
+}
+
+/// Fetches a small text resource (e.g. the update manifest) into memory; longer
+/// responses are cut off after `max_len` bytes.
+pub fn fetch_text(url: &str, max_len: usize) -> Result<String> {
+    unsafe {
+        let response = get(url)?;
+        let mut body = Vec::new();
+        let mut buffer = vec![0u8; READ_CHUNK_SIZE];
+        while body.len() < max_len {
+            let read = response.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&buffer[..read]);
+        }
+        body.truncate(max_len);
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// A GET request the server answered with 200. Fields drop in declaration order,
+/// so the request is closed before its connection and session.
+struct Response {
+    request: InternetHandle,
+    _connection: InternetHandle,
+    _session: InternetHandle,
+    /// Path and query of the URL
+    object: String,
+}
+
+impl Response {
+    /// Reads the next chunk of the body; 0 at the end
+    unsafe fn read(&self, buffer: &mut [u8]) -> Result<usize> {
+        let mut read = 0u32;
+        if !WinHttpReadData(self.request.0, buffer.as_mut_ptr() as *mut _, buffer.len() as u32, &mut read).as_bool() {
+            return Err(last_http_error());
+        }
+        Ok(read as usize)
+    }
+}
+
+/// Sends a GET request for `url` and waits for the response headers
+unsafe fn get(url: &str) -> Result<Response> {
+    let url_wide: Vec<u16> = url.encode_utf16().collect();
+
+    // Split URL into host, port and path
+    let mut components = URL_COMPONENTS {
+        dwStructSize: std::mem::size_of::<URL_COMPONENTS>() as u32,
+        dwSchemeLength: u32::MAX,
+        dwHostNameLength: u32::MAX,
+        dwUrlPathLength: u32::MAX,
+        dwExtraInfoLength: u32::MAX,
+        ..Default::default()
+    };
+    if !WinHttpCrackUrl(&url_wide, 0, &mut components).as_bool() {
+        return Err(http_error(ERROR_WINHTTP_INVALID_URL, url));
+    }
+
+    let host = wide_part(components.lpszHostName, components.dwHostNameLength);
+    let mut object = wide_part(components.lpszUrlPath, components.dwUrlPathLength);
+    object.push_str(&wide_part(components.lpszExtraInfo, components.dwExtraInfoLength));
+    let secure = components.nScheme == WINHTTP_INTERNET_SCHEME_HTTPS;
+
+    let session = InternetHandle::new(WinHttpOpen(
+        w!("SimpliView"),
+        WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY,
+        PCWSTR::null(),
+        PCWSTR::null(),
+        0,
+    ))?;
+    WinHttpSetTimeouts(session.0, RESOLVE_TIMEOUT_MS, CONNECT_TIMEOUT_MS, SEND_TIMEOUT_MS, RECEIVE_TIMEOUT_MS);
+
+    let host_wide: Vec<u16> = host.encode_utf16().chain(std::iter::once(0)).collect();
+    let connection = InternetHandle::new(WinHttpConnect(
+        session.0,
+        PCWSTR(host_wide.as_ptr()),
+        components.nPort,
+        0,
+    ))?;
+
+    let object_wide: Vec<u16> = object.encode_utf16().chain(std::iter::once(0)).collect();
+    let request = InternetHandle::new(WinHttpOpenRequest(
+        connection.0,
+        w!("GET"),
+        PCWSTR(object_wide.as_ptr()),
+        PCWSTR::null(),
+        PCWSTR::null(),
+        std::ptr::null(),
+        if secure { WINHTTP_FLAG_SECURE } else { WINHTTP_OPEN_REQUEST_FLAGS(0) },
+    ))?;
+
+    if !WinHttpSendRequest(request.0, None, None, 0, 0, 0).as_bool()
+        || !WinHttpReceiveResponse(request.0, std::ptr::null_mut()).as_bool()
+    {
+        return Err(last_http_error());
+    }
+
+    // HTTP status (404, 403, ...)
+    let status = query_number(&request, WINHTTP_QUERY_STATUS_CODE).unwrap_or(0);
+    if status != 200 {
+        return Err(Error::new(
+            HRESULT(0x80004005u32 as i32),
+            HSTRING::from(format!("Der Server hat die Anfrage abgelehnt (HTTP {}).", status)),
+        ));
+    }
+
+    Ok(Response { request, _connection: connection, _session: session, object })
+}
+
+/// Derives a local file name from the URL path, falling back to the content type
+fn file_name_for(url_path: &str, content_type: &str) -> String {
+    let name = url_path
+        .split(['?', '#'])
+        .next()
+        .unwrap_or("")
+        .rsplit('/')
+        .next()
+        .map(percent_decode)
+        .unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !matches!(c, '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect();
+
+    if Path::new(&name).extension().is_some() {
+        return name;
+    }
+
+    let ext = match content_type.split(';').next().unwrap_or("").trim() {
+        "application/pdf" => "pdf",
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/bmp" => "bmp",
+        "image/tiff" => "tif",
+        "image/webp" => "webp",
+        _ => "pdf",
+    };
+    let stem = if name.is_empty() { "Download" } else { name.as_str() };
+    format!("{}.{}", stem, ext)
+}
+
+unsafe fn query_number(request: &InternetHandle, info: u32) -> Option<u32> {
+    let mut value = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let mut index = 0u32;
+    WinHttpQueryHeaders(
+        request.0,
+        info | WINHTTP_QUERY_FLAG_NUMBER,
+        PCWSTR::null(),
+        Some(&mut value as *mut _ as *mut _),
+        &mut size,
+        &mut index,
+    )
+    .as_bool()
+    .then_some(value)
+}
+
+unsafe fn query_string(request: &InternetHandle, info: u32) -> Option<String> {
+    let mut buffer = [0u16; 256];
+    let mut size = (buffer.len() * 2) as u32;
+    let mut index = 0u32;
+    WinHttpQueryHeaders(
+        request.0,
+        info,
+        PCWSTR::null(),
+        Some(buffer.as_mut_ptr() as *mut _),
+        &mut size,
+        &mut index,
+    )
+    .as_bool()
+    .then(|| String::from_utf16_lossy(&buffer[..(size / 2) as usize]))
+}
+
+unsafe fn wide_part(ptr: PWSTR, len: u32) -> String {
+    if ptr.is_null() || len == 0 {
+        String::new()
+    } else {
+        String::from_utf16_lossy(std::slice::from_raw_parts(ptr.0, len as usize))
+    }
+}
+
+fn last_http_error() -> Error {
+    let code = unsafe { GetLastError() };
+    http_error(code.0, "")
+}
+
+/// Maps WinHTTP error codes to readable messages
+fn http_error(code: u32, detail: &str) -> Error {
+    let message = match code {
+        ERROR_WINHTTP_TIMEOUT => "Zeitüberschreitung beim Herunterladen.".to_string(),
+        ERROR_WINHTTP_NAME_NOT_RESOLVED => "Der Servername konnte nicht aufgelöst werden.".to_string(),
+        ERROR_WINHTTP_CANNOT_CONNECT | ERROR_WINHTTP_CONNECTION_ERROR => {
+            "Verbindung zum Server fehlgeschlagen.".to_string()
+        }
+        ERROR_WINHTTP_SECURE_FAILURE => {
+            "Sichere Verbindung fehlgeschlagen (TLS-/Zertifikatsfehler).".to_string()
+        }
+        ERROR_WINHTTP_INVALID_URL | ERROR_WINHTTP_UNRECOGNIZED_SCHEME => {
+            format!("Ungültige Adresse: {}", detail)
+        }
+        _ => format!("Download fehlgeschlagen (Fehler {}).", code),
+    };
+    Error::new(WIN32_ERROR(code).to_hresult(), HSTRING::from(message))
+}
+
+fn io_error(e: &std::io::Error) -> Error {
+    Error::new(
+        HRESULT(0x80004005u32 as i32),
+        HSTRING::from(format!("Temporäre Datei konnte nicht geschrieben werden: {}", e)),
+    )
+}
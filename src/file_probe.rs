@@ -0,0 +1,109 @@
+//! Quick facts about a file before it is opened: "PDF, 134 Seiten, 18.0 MB"
+//!
+//! Shown in the open dialog for the selected file. Nothing is rendered: the page count
+//! comes from Windows.Data.Pdf, which reads the trailer and the page tree but not the
+//! pages. Paths beyond MAX_PATH get no count, as Windows.Data.Pdf could only read them
+//! whole (see `PdfSource`). Results are cached per path and modification time. The probe
+//! runs on a worker thread and is given up after PROBE_TIMEOUT, so a slow network file
+//! does not hold up the dialog; a count that could not be read is left out of the text.
+//! Selecting a file again while its probe still runs starts no second one.
+
+use crate::pdf::PdfLoader;
+use crate::utils::{format_file_size, long_path};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+
+/// Longest wait for a probe; the worker finishes anyway and fills the cache
+const PROBE_TIMEOUT: Duration = Duration::from_millis(400);
+
+/// Probe results by path
+static CACHE: Lazy<Mutex<HashMap<String, Entry>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// Paths a worker is probing
+static IN_FLIGHT: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Marks a path as being probed until dropped
+struct InFlight(String);
+
+impl InFlight {
+    /// `None` if a probe of `path` is already running
+    fn start(path: &str) -> Option<Self> {
+        IN_FLIGHT.lock().insert(path.to_string()).then(|| Self(path.to_string()))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().remove(&self.0);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Modification time the entry was made for
+    modified: SystemTime,
+    size: u64,
+    /// `None` if not a PDF or unreadable
+    pages: Option<usize>,
+}
+
+/// "PDF, 134 Seiten, 18.0 MB" for `path`; `None` for folders, missing files, while an
+/// earlier probe of `path` is still running or when the probe took longer than
+/// PROBE_TIMEOUT
+pub fn describe(path: &str) -> Option<String> {
+    let in_flight = InFlight::start(path)?;
+    let (sender, receiver) = mpsc::channel();
+    let worker_path = path.to_string();
+    std::thread::Builder::new()
+        .name("SimpliView File Probe".to_string())
+        .spawn(move || {
+            let _ = sender.send(probe(&worker_path));
+            drop(in_flight);
+        })
+        .ok()?;
+    let (size, pages) = receiver.recv_timeout(PROBE_TIMEOUT).ok()??;
+
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_uppercase();
+    let mut parts = Vec::new();
+    if !extension.is_empty() {
+        parts.push(extension);
+    }
+    match pages {
+        Some(1) => parts.push("1 Seite".to_string()),
+        Some(pages) => parts.push(format!("{} Seiten", pages)),
+        None => {}
+    }
+    parts.push(format_file_size(size));
+    Some(parts.join(", "))
+}
+
+/// Size and page count of a file, from the cache if it has not changed since
+fn probe(path: &str) -> Option<(u64, Option<usize>)> {
+    let metadata = std::fs::metadata(long_path(path)).ok().filter(|m| m.is_file())?;
+    let modified = metadata.modified().ok()?;
+    if let Some(entry) = CACHE.lock().get(path).filter(|entry| entry.modified == modified) {
+        return Some((entry.size, entry.pages));
+    }
+
+    let is_pdf = Path::new(path).extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+    let pages = if is_pdf { pdf_page_count(path) } else { None };
+    CACHE.lock().insert(path.to_string(), Entry { modified, size: metadata.len(), pages });
+    Some((metadata.len(), pages))
+}
+
+fn pdf_page_count(path: &str) -> Option<usize> {
+    if long_path(path) != path {
+        return None;
+    }
+    // Windows.Data.Pdf needs COM on this thread
+    let com = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+    let pages = PdfLoader::page_count(path).ok();
+    if com {
+        unsafe { CoUninitialize() };
+    }
+    pages
+}
@@ -0,0 +1,168 @@
+//! Watches the open file for changes by other programs
+//!
+//! When the file shown is replaced or deleted, the view no longer matches it and an export
+//! by copying the original would write other bytes than shown. A watcher thread waits for
+//! change notifications on the file's folder and compares the file's size and modification
+//! time. Once the file has been quiet for `DEBOUNCE` and nobody writes it any more,
+//! `WM_APP_FILE_CHANGED` is posted: the status bar then offers "Neu laden", or marks the
+//! document as deleted.
+//!
+//! Exports to the open file itself are announced with `expect_own_write`, so saving over
+//! the original does not prompt.
+
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Storage::FileSystem::*,
+        System::Threading::{CreateEventW, SetEvent, WaitForMultipleObjects, INFINITE},
+        UI::WindowsAndMessaging::{PostMessageW, WM_APP},
+    },
+};
+
+/// Posted to the main window; wparam is a `FileChange`, lparam owns a `Box<String>` with
+/// the watched path (see `take_path`)
+pub const WM_APP_FILE_CHANGED: u32 = WM_APP + 12;
+
+/// How long the file must stay unchanged before a change is reported
+const DEBOUNCE: Duration = Duration::from_millis(750);
+/// Rescan interval while a change is settling
+const POLL_INTERVAL_MS: u32 = 250;
+/// Changes within this time after `expect_own_write` are our own export
+const OWN_WRITE_GRACE: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileChange {
+    /// Written or replaced (also when it comes back after having been deleted)
+    Modified = 0,
+    Deleted = 1,
+}
+
+impl FileChange {
+    pub fn from_wparam(wparam: WPARAM) -> Self {
+        if wparam.0 == Self::Deleted as usize { Self::Deleted } else { Self::Modified }
+    }
+}
+
+pub struct FileWatcher {
+    /// Manual-reset event that ends the watcher thread
+    stop: HANDLE,
+    thread: Option<std::thread::JoinHandle<()>>,
+    /// Until when changes are our own export (see `expect_own_write`)
+    own_write: Arc<Mutex<Option<Instant>>>,
+}
+
+impl FileWatcher {
+    /// Starts watching `path` for the main window `hwnd`
+    pub fn start(hwnd: HWND, path: &str) -> Result<Self> {
+        let folder = Path::new(path).parent().map(Path::to_path_buf).ok_or_else(|| Error::from(E_INVALIDARG))?;
+        let change = unsafe {
+            FindFirstChangeNotificationW(
+                &HSTRING::from(crate::utils::long_path(&folder.to_string_lossy())),
+                FALSE,
+                FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_SIZE | FILE_NOTIFY_CHANGE_LAST_WRITE,
+            )?
+        };
+        let stop = unsafe { CreateEventW(None, TRUE, FALSE, None) }.inspect_err(|_| unsafe {
+            FindCloseChangeNotification(change);
+        })?;
+
+        // Handles are not Send; the raw values are only used by the thread
+        let (hwnd_raw, stop_raw, change_raw) = (hwnd.0, stop.0, change.0);
+        let own_write = Arc::new(Mutex::new(None));
+        let thread_own_write = own_write.clone();
+        let file = PathBuf::from(path);
+        let thread = std::thread::Builder::new()
+            .name("SimpliView File Watch".to_string())
+            .spawn(move || {
+                let change = FindChangeNotificationHandle(change_raw);
+                watch(HWND(hwnd_raw), &file, HANDLE(stop_raw), change, &thread_own_write);
+                unsafe {
+                    FindCloseChangeNotification(change);
+                }
+            })
+            .map_err(|e| Error::new(E_FAIL, HSTRING::from(e.to_string())))?;
+
+        Ok(Self { stop, thread: Some(thread), own_write })
+    }
+
+    /// Call right before and after writing the watched file ourselves: changes until
+    /// OWN_WRITE_GRACE later are taken as the new state without a notice
+    pub fn expect_own_write(&self) {
+        *self.own_write.lock() = Some(Instant::now() + OWN_WRITE_GRACE);
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            SetEvent(self.stop);
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        unsafe {
+            CloseHandle(self.stop);
+        }
+    }
+}
+
+/// Takes ownership of the path posted with `WM_APP_FILE_CHANGED`
+///
+/// # Safety
+/// `lparam` must come from a `WM_APP_FILE_CHANGED` posted by the watcher thread.
+pub unsafe fn take_path(lparam: LPARAM) -> String {
+    *Box::from_raw(lparam.0 as *mut String)
+}
+
+/// Size and modification time, `None` while the file does not exist
+fn file_state(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(crate::utils::long_path(&path.to_string_lossy())).ok().filter(|m| m.is_file())?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+fn watch(hwnd: HWND, file: &Path, stop: HANDLE, change: FindChangeNotificationHandle, own_write: &Mutex<Option<Instant>>) {
+    let mut reported = file_state(file);
+    // Last state seen and since when, while a change is settling
+    let mut pending: Option<(Option<(u64, SystemTime)>, Instant)> = None;
+
+    loop {
+        let timeout = if pending.is_some() { POLL_INTERVAL_MS } else { INFINITE };
+        let result = unsafe { WaitForMultipleObjects(&[stop, HANDLE(change.0)], FALSE, timeout) };
+        match result {
+            r if r == WAIT_OBJECT_0 => break,
+            r if r.0 == WAIT_OBJECT_0.0 + 1 => unsafe {
+                FindNextChangeNotification(change);
+            },
+            WAIT_TIMEOUT => {}
+            // Handle gone (e.g. the folder was deleted): stop quietly
+            _ => break,
+        }
+
+        let state = file_state(file);
+        if own_write.lock().is_some_and(|until| Instant::now() < until) {
+            reported = state;
+            pending = None;
+            continue;
+        }
+        match pending {
+            _ if state == reported => pending = None,
+            Some((seen, _)) if seen != state => pending = Some((state, Instant::now())),
+            None => pending = Some((state, Instant::now())),
+            Some((_, since)) if since.elapsed() >= DEBOUNCE && (state.is_none() || !crate::inbox::is_being_written(file)) => {
+                let kind = if state.is_some() { FileChange::Modified } else { FileChange::Deleted };
+                reported = state;
+                pending = None;
+                let path = Box::into_raw(Box::new(file.to_string_lossy().to_string()));
+                if !unsafe { PostMessageW(hwnd, WM_APP_FILE_CHANGED, WPARAM(kind as usize), LPARAM(path as isize)) }.as_bool() {
+                    drop(unsafe { Box::from_raw(path) });
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Folder listing for Back/Next navigation
+//!
+//! Without "Unterordner einbeziehen" only the folder of the opened file is listed. With
+//! it, the walk starts one level higher so sibling folders are included (an archive with
+//! one folder per day continues into the next day). Both run on a worker thread, because
+//! huge folders and recursive walks of network shares are slow; the result is posted as
+//! `WM_APP_FOLDER_SCANNED`.
+//!
+//! A listing is capped at `max_files()` (FolderScanLimit in the settings, 10 000 by
+//! default). Entries are streamed into a bounded heap that keeps the first files in
+//! natural order, so a folder of 200 000 files never becomes one giant sorted list.
+//! Recursive walks are also bounded by `MAX_DEPTH`, so that a file opened from a drive
+//! root cannot start a scan of the whole drive, and never leave the --restricted root.
+//! Hidden, system and temporary files and the usual noise (Thumbs.db, Office lock files,
+//! macOS "._" resource forks) are left out.
+
+use crate::app::FOLDER_EXTENSIONS;
+use crate::utils::{natural_sort_key, NaturalKey};
+use std::collections::BinaryHeap;
+use std::path::Path;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Storage::FileSystem::*,
+        UI::WindowsAndMessaging::{PostMessageW, WM_APP},
+    },
+};
+
+/// Posted to the main window; lparam owns a `Box<FolderScan>` (see `take_result`)
+pub const WM_APP_FOLDER_SCANNED: u32 = WM_APP + 6;
+
+/// Folder levels below the walk root
+const MAX_DEPTH: usize = 4;
+/// Files listed when FolderScanLimit is not set
+const DEFAULT_MAX_FILES: usize = 10_000;
+/// Attributes of files that are never shown
+const SKIPPED_ATTRIBUTES: u32 = FILE_ATTRIBUTE_HIDDEN.0 | FILE_ATTRIBUTE_SYSTEM.0 | FILE_ATTRIBUTE_TEMPORARY.0;
+
+/// Result of a folder scan
+pub struct FolderScan {
+    /// File the walk was started for
+    pub path: String,
+    /// Folder the walk started in; file names are shown relative to it
+    pub root: String,
+    pub files: Vec<String>,
+    /// Subfolders were included
+    pub recursive: bool,
+    /// `max_files()` was reached
+    pub truncated: bool,
+}
+
+/// Most files listed by a scan; the rest of the folder is left out
+pub fn max_files() -> usize {
+    crate::settings::folder_scan_limit().map_or(DEFAULT_MAX_FILES, |limit| limit as usize)
+}
+
+/// Lists the supported files of the folder of `path` in natural order on the calling
+/// thread; the fallback if no worker thread can be started
+pub fn scan_folder(path: &str) -> (Vec<String>, usize) {
+    let scan = scan(path, false, None, max_files());
+    let current_index = scan.files.iter().position(|f| f.eq_ignore_ascii_case(path)).unwrap_or(0);
+    (scan.files, current_index)
+}
+
+/// Starts a scan for `path` on a worker thread, including subfolders if `recursive`;
+/// false if the thread could not start
+pub fn start_scan(hwnd: HWND, path: &str, recursive: bool, restricted_root: Option<&str>) -> bool {
+    let path = path.to_string();
+    let restricted_root = restricted_root.map(str::to_string);
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new().name("SimpliView Folder Scan".to_string()).spawn(move || {
+        // A walk through subfolders can take a while; its length is not known up front
+        if recursive {
+            crate::statusbar::post_progress(HWND(hwnd_raw), Some((0, 0)));
+        }
+        let scan = Box::into_raw(Box::new(scan(&path, recursive, restricted_root.as_deref(), max_files())));
+        if !unsafe { PostMessageW(HWND(hwnd_raw), WM_APP_FOLDER_SCANNED, WPARAM(0), LPARAM(scan as isize)) }.as_bool() {
+            drop(unsafe { Box::from_raw(scan) });
+        }
+    })
+    .is_ok()
+}
+
+/// Takes ownership of the result posted with `WM_APP_FOLDER_SCANNED`
+///
+/// # Safety
+/// `lparam` must come from a `WM_APP_FOLDER_SCANNED` posted by `start_scan`.
+pub unsafe fn take_result(lparam: LPARAM) -> FolderScan {
+    *Box::from_raw(lparam.0 as *mut FolderScan)
+}
+
+/// Lists at most `limit` files for `path`; the file itself is always among them
+fn scan(path: &str, recursive: bool, restricted_root: Option<&str>, limit: usize) -> FolderScan {
+    let folder = Path::new(path).parent().unwrap_or(Path::new(path)).to_string_lossy().to_string();
+    let (root, depth) = if recursive { (walk_root(path, restricted_root), MAX_DEPTH) } else { (folder.clone(), 0) };
+    let mut files = Vec::new();
+    let mut truncated = false;
+    if !collect_files(&root, depth, limit, &mut files, &mut truncated) {
+        files = vec![path.to_string()];
+    } else if !recursive && !files.iter().any(|f| f.eq_ignore_ascii_case(path)) {
+        // The opened file lies beyond the cap (or is hidden): keep it navigable
+        files.push(path.to_string());
+        files.sort_by_cached_key(|f| natural_sort_key(f));
+        files.truncate(limit.max(1));
+        if !files.iter().any(|f| f.eq_ignore_ascii_case(path)) {
+            files.pop();
+            files.push(path.to_string());
+        }
+    }
+    FolderScan { path: path.to_string(), root, files, recursive, truncated }
+}
+
+/// `path` relative to `root` if it lies in a subfolder of it
+pub fn relative_name(path: &str, root: &str) -> Option<String> {
+    let relative = Path::new(path).strip_prefix(root).ok()?;
+    (relative.components().count() > 1).then(|| relative.to_string_lossy().to_string())
+}
+
+/// Parent of the file's folder, unless that is a drive root or outside the restricted root
+fn walk_root(path: &str, restricted_root: Option<&str>) -> String {
+    let folder = Path::new(path).parent().unwrap_or(Path::new(path));
+    let within_restriction = |candidate: &Path| {
+        restricted_root.is_none_or(|root| crate::deeplink::is_within_root(&candidate.to_string_lossy(), root))
+    };
+    match folder.parent() {
+        Some(parent) if parent.parent().is_some() && within_restriction(parent) => parent.to_string_lossy().to_string(),
+        _ => folder.to_string_lossy().to_string(),
+    }
+}
+
+/// Appends the supported files of `folder`, then those of its subfolders up to `depth`
+/// levels down, until `limit` files are listed; `truncated` is set if files were left out.
+/// Returns false if `folder` cannot be listed.
+fn collect_files(folder: &str, depth: usize, limit: usize, files: &mut Vec<String>, truncated: &mut bool) -> bool {
+    // Max-heap on the sort key: holds the first `capacity` files of the folder in natural order
+    let capacity = limit.saturating_sub(files.len());
+    let mut found: BinaryHeap<(NaturalKey, String)> = BinaryHeap::new();
+    let mut subfolders: Vec<String> = Vec::new();
+
+    unsafe {
+        let search_pattern = format!("{}\\*", folder);
+        let pattern_wide = crate::utils::to_wide_path(&search_pattern);
+        let mut find_data = WIN32_FIND_DATAW::default();
+        let Ok(handle) = FindFirstFileW(PCWSTR(pattern_wide.as_ptr()), &mut find_data) else {
+            return false;
+        };
+
+        loop {
+            let filename_len = find_data.cFileName.iter().position(|&c| c == 0).unwrap_or(find_data.cFileName.len());
+            let filename = String::from_utf16_lossy(&find_data.cFileName[..filename_len]);
+            let attributes = find_data.dwFileAttributes;
+            if attributes & FILE_ATTRIBUTE_DIRECTORY.0 == 0 {
+                if attributes & SKIPPED_ATTRIBUTES == 0 && is_listed(&filename) {
+                    let path = format!("{}\\{}", folder, filename);
+                    let key = natural_sort_key(&filename);
+                    if found.len() < capacity {
+                        found.push((key, path));
+                    } else {
+                        *truncated = true;
+                        if found.peek().is_some_and(|(largest, _)| key < *largest) {
+                            found.pop();
+                            found.push((key, path));
+                        }
+                    }
+                }
+            } else if depth > 0
+                && filename != "."
+                && filename != ".."
+                && attributes & (FILE_ATTRIBUTE_HIDDEN.0 | FILE_ATTRIBUTE_SYSTEM.0) == 0
+                // Junctions could loop back into the tree
+                && attributes & FILE_ATTRIBUTE_REPARSE_POINT.0 == 0
+            {
+                subfolders.push(format!("{}\\{}", folder, filename));
+            }
+            if !FindNextFileW(handle, &mut find_data).as_bool() {
+                break;
+            }
+        }
+        let _ = FindClose(handle);
+    }
+
+    // Use natural sort (file2 before file10); folder-major: a folder's files, then its subfolders
+    files.extend(found.into_sorted_vec().into_iter().map(|(_, path)| path));
+    subfolders.sort_by_cached_key(|f| natural_sort_key(f));
+    for subfolder in subfolders {
+        if files.len() >= limit {
+            *truncated = true;
+            break;
+        }
+        collect_files(&subfolder, depth - 1, limit, files, truncated);
+    }
+    true
+}
+
+/// Supported extension and not one of the files that only clutter a folder
+fn is_listed(filename: &str) -> bool {
+    let Some(dot_pos) = filename.rfind('.') else {
+        return false;
+    };
+    let ext = filename[dot_pos + 1..].to_lowercase();
+    FOLDER_EXTENSIONS.contains(&ext.as_str())
+        // Office/Acrobat lock files and macOS resource forks copied from a Mac
+        && !filename.starts_with("~$")
+        && !filename.starts_with("._")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Folder of its own for a test with empty files of these names
+    fn test_dir(name: &str, files: &[String]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("SimpliView-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    fn file_names(files: &[String]) -> Vec<&str> {
+        files.iter().map(|f| f.rsplit(['\\', '/']).next().unwrap()).collect()
+    }
+
+    /// "scan 1.jpg" to "scan 25.jpg", plus files that are never listed
+    fn scans() -> Vec<String> {
+        let mut files: Vec<String> = (1..=25).map(|i| format!("scan {}.jpg", i)).collect();
+        files.extend(["~$bericht.pdf", "._scan 1.jpg", "notizen.txt", "Thumbs.db"].map(String::from));
+        files
+    }
+
+    #[test]
+    fn large_folders_keep_the_first_files_in_natural_order() {
+        let dir = test_dir("FolderCap", &scans());
+        let (mut files, mut truncated) = (Vec::new(), false);
+        assert!(collect_files(&dir.to_string_lossy(), 0, 10, &mut files, &mut truncated));
+        assert!(truncated);
+        // Not "scan 1", "scan 10", "scan 11", ... as a plain string sort would give
+        let expected: Vec<String> = (1..=10).map(|i| format!("scan {}.jpg", i)).collect();
+        assert_eq!(file_names(&files), expected);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn folders_within_the_cap_are_listed_whole() {
+        let dir = test_dir("FolderUncapped", &scans());
+        let (mut files, mut truncated) = (Vec::new(), false);
+        assert!(collect_files(&dir.to_string_lossy(), 0, 25, &mut files, &mut truncated));
+        assert!(!truncated);
+        assert_eq!(files.len(), 25);
+        assert_eq!(file_names(&files)[..3], ["scan 1.jpg", "scan 2.jpg", "scan 3.jpg"]);
+        assert_eq!(file_names(&files)[24], "scan 25.jpg");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn opened_file_past_the_cap_stays_listed() {
+        let dir = test_dir("FolderOpenedLate", &scans());
+        let opened = dir.join("scan 20.jpg").to_string_lossy().to_string();
+        let scan = scan(&opened, false, None, 10);
+        assert!(scan.truncated);
+        assert_eq!(scan.files.len(), 10);
+        // The first nine files, then the opened one in place of the tenth
+        let expected: Vec<String> = (1..=9).map(|i| format!("scan {}.jpg", i)).collect();
+        assert_eq!(file_names(&scan.files[..9]), expected);
+        assert_eq!(scan.files[9], opened);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_folders_are_not_listed() {
+        let (mut files, mut truncated) = (Vec::new(), false);
+        let missing = std::env::temp_dir().join("SimpliView-does-not-exist");
+        assert!(!collect_files(&missing.to_string_lossy(), 0, 10, &mut files, &mut truncated));
+        assert!(files.is_empty());
+    }
+}
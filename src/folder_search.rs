@@ -0,0 +1,527 @@
+//! "Ordner durchsuchen": finds the files of the current folder that contain a text
+//!
+//! The files of the Back/Next list are searched on worker threads: PDFs through their
+//! text layer (pdf_text.rs), images through text recognition (ocr.rs). Recognition is slow
+//! and takes much memory, so at most `MAX_CONCURRENT_OCR` images are recognized at once,
+//! and the text of every file is kept in the text index (text_index.rs) where allowed, so
+//! searching the same folder again is quick until a file changes. ZIP archives and DICOM files are not
+//! searched, scanned PDFs without a text layer are not recognized.
+//!
+//! Matches come back file by file with WM_APP_SEARCH and are listed in a small window with
+//! file, page and the text around the match; choosing one opens the file at that page
+//! (WM_APP_SEARCH_REQUEST). Esc in the window or closing it stops the search.
+
+use crate::cancel::CancelToken;
+use crate::text_index::Stamp;
+use crate::wic::WicLoader;
+use parking_lot::{Condvar, Mutex};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED},
+        UI::{Controls::*, HiDpi::GetDpiForWindow, Input::KeyboardAndMouse::{GetFocus, SetFocus}, WindowsAndMessaging::*},
+    },
+};
+
+/// Posted to the main window by the search threads; lparam owns a `Box<SearchMessage>`
+/// (see `take_message`)
+pub const WM_APP_SEARCH: u32 = WM_APP + 15;
+/// Posted by the search window to its owner; wparam is a `SearchRequest`, lparam the
+/// index of the chosen match for `Open`
+pub const WM_APP_SEARCH_REQUEST: u32 = WM_APP + 16;
+
+/// Image files searched through text recognition
+const IMAGE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp"];
+/// Files searched at once
+const MAX_WORKERS: usize = 4;
+/// Images recognized at once
+const MAX_CONCURRENT_OCR: usize = 2;
+/// Pages of one file listed, the first matches
+const MAX_HITS_PER_FILE: usize = 100;
+/// Characters shown before and after a match
+const SNIPPET_CONTEXT: usize = 40;
+
+const IDD_SEARCH: isize = 220;
+const IDC_TERM: i32 = 221;
+const IDC_STATUS: i32 = 222;
+const IDC_LIST: i32 = 223;
+/// Title and width of the list columns (96 DPI)
+const COLUMNS: [(&str, i32); 3] = [("Datei", 150), ("Seite", 45), ("Fundstelle", 330)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchRequest {
+    /// Enter or "Suchen" with the text of the search field
+    Search = 0,
+    /// A match was chosen
+    Open = 1,
+    /// The window was closed
+    Stop = 2,
+}
+
+impl SearchRequest {
+    pub fn from_wparam(wparam: WPARAM) -> Self {
+        match wparam.0 {
+            1 => Self::Open,
+            2 => Self::Stop,
+            _ => Self::Search,
+        }
+    }
+}
+
+pub struct SearchJob {
+    /// Tells the messages of this search from those of a stopped earlier one
+    pub id: u32,
+    pub term: String,
+    pub files: Vec<String>,
+    /// Whether texts are read from and kept in the text index; not in restricted
+    /// deployments and safe mode
+    pub use_index: bool,
+}
+
+#[derive(Clone)]
+pub struct SearchHit {
+    pub path: String,
+    /// 0-based
+    pub page: usize,
+    /// Text around the match on one line
+    pub snippet: String,
+}
+
+pub enum SearchEvent {
+    /// One more file was searched; its matching pages
+    File(Vec<SearchHit>),
+    /// All threads have ended
+    Done(SearchSummary),
+}
+
+pub struct SearchSummary {
+    /// Files not searched for their type (archives, DICOM, images without OCR)
+    pub skipped: usize,
+    /// (path, error message)
+    pub failed: Vec<(String, String)>,
+    /// Why images were not searched, ready to show (no OCR language installed)
+    pub ocr_unavailable: Option<String>,
+    pub cancelled: bool,
+}
+
+pub struct SearchMessage {
+    pub id: u32,
+    pub event: SearchEvent,
+}
+
+/// Searches the files of `job` on worker threads until done or `cancel` is set and posts
+/// the matches and the summary to `hwnd`. False if the threads could not be started.
+pub fn start(hwnd: HWND, job: SearchJob, cancel: Arc<AtomicBool>) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView Folder Search".to_string())
+        .spawn(move || {
+            let hwnd = HWND(hwnd_raw);
+            // WinRT needs COM on this thread (see `run`)
+            let com = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+            let summary = run(hwnd, &job, &cancel);
+            if com {
+                unsafe { CoUninitialize() };
+            }
+            // A stopped search may have been replaced by a newer one showing its progress
+            if !summary.cancelled {
+                crate::statusbar::post_progress(hwnd, None);
+            }
+            post(hwnd, job.id, SearchEvent::Done(summary));
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the message posted with WM_APP_SEARCH
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_SEARCH message and be taken only once.
+pub unsafe fn take_message(lparam: LPARAM) -> SearchMessage {
+    *Box::from_raw(lparam.0 as *mut SearchMessage)
+}
+
+fn post(hwnd: HWND, id: u32, event: SearchEvent) {
+    let message = Box::into_raw(Box::new(SearchMessage { id, event }));
+    if !unsafe { PostMessageW(hwnd, WM_APP_SEARCH, WPARAM(0), LPARAM(message as isize)) }.as_bool() {
+        drop(unsafe { Box::from_raw(message) });
+    }
+}
+
+/// Limits how many images are recognized at once
+struct OcrSlots {
+    free: Mutex<usize>,
+    released: Condvar,
+}
+
+struct OcrSlot<'a>(&'a OcrSlots);
+
+impl OcrSlots {
+    fn acquire(&self) -> OcrSlot<'_> {
+        let mut free = self.free.lock();
+        while *free == 0 {
+            self.released.wait(&mut free);
+        }
+        *free -= 1;
+        OcrSlot(self)
+    }
+}
+
+impl Drop for OcrSlot<'_> {
+    fn drop(&mut self) {
+        *self.0.free.lock() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// State shared by the worker threads of one search
+struct Search<'a> {
+    hwnd: HWND,
+    job: &'a SearchJob,
+    cancel: &'a AtomicBool,
+    /// Lowercase, whitespace collapsed (see `normalized`)
+    term: Vec<char>,
+    /// Whether images can be recognized
+    ocr: bool,
+    ocr_slots: OcrSlots,
+    /// Index of the next file to take
+    next: AtomicUsize,
+    done: AtomicUsize,
+    skipped: AtomicUsize,
+    failed: Mutex<Vec<(String, String)>>,
+}
+
+enum Texts {
+    Read(Vec<String>),
+    Skipped,
+    Cancelled,
+}
+
+fn run(hwnd: HWND, job: &SearchJob, cancel: &AtomicBool) -> SearchSummary {
+    let has_images = job.files.iter().any(|path| IMAGE_EXTENSIONS.contains(&extension(path).as_str()));
+    let ocr_unavailable = if has_images { crate::ocr::check_available().err() } else { None };
+    let search = Search {
+        hwnd,
+        job,
+        cancel,
+        term: normalized(&job.term).into_iter().map(fold_case).collect(),
+        ocr: has_images && ocr_unavailable.is_none(),
+        ocr_slots: OcrSlots { free: Mutex::new(MAX_CONCURRENT_OCR), released: Condvar::new() },
+        next: AtomicUsize::new(0),
+        done: AtomicUsize::new(0),
+        skipped: AtomicUsize::new(0),
+        failed: Mutex::new(Vec::new()),
+    };
+
+    let workers = std::thread::available_parallelism().map_or(2, |n| n.get()).clamp(1, MAX_WORKERS).min(job.files.len().max(1));
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let _ = std::thread::Builder::new().name("SimpliView Folder Search Worker".to_string()).spawn_scoped(scope, || search_files(&search));
+        }
+    });
+
+    SearchSummary {
+        skipped: search.skipped.load(Ordering::Relaxed),
+        failed: search.failed.into_inner(),
+        ocr_unavailable,
+        cancelled: cancel.load(Ordering::Relaxed),
+    }
+}
+
+/// Worker thread: takes the next file until none are left
+fn search_files(search: &Search) {
+    // WIC and WinRT need COM on this thread
+    let com = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+    let loader = WicLoader::new().ok();
+    let total = search.job.files.len();
+    while !search.cancel.load(Ordering::Relaxed) {
+        let Some(path) = search.job.files.get(search.next.fetch_add(1, Ordering::Relaxed)) else {
+            break;
+        };
+        let hits = match texts(search, loader.as_ref(), path) {
+            Ok(Texts::Read(pages)) => hits(path, &pages, &search.term),
+            Ok(Texts::Skipped) => {
+                search.skipped.fetch_add(1, Ordering::Relaxed);
+                Vec::new()
+            }
+            Ok(Texts::Cancelled) => break,
+            Err(message) => {
+                search.failed.lock().push((path.clone(), message));
+                Vec::new()
+            }
+        };
+        let done = search.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !search.cancel.load(Ordering::Relaxed) {
+            crate::statusbar::post_progress(search.hwnd, Some((done, total)));
+        }
+        post(search.hwnd, search.job.id, SearchEvent::File(hits));
+    }
+    if com {
+        unsafe { CoUninitialize() };
+    }
+}
+
+/// Page texts of `path`, from the text index while the file is unchanged
+fn texts(search: &Search, loader: Option<&WicLoader>, path: &str) -> std::result::Result<Texts, String> {
+    let extension = extension(path);
+    let is_pdf = extension == "pdf";
+    if !(is_pdf || search.ocr && IMAGE_EXTENSIONS.contains(&extension.as_str())) {
+        return Ok(Texts::Skipped);
+    }
+    let stamp = Stamp::of(path).ok_or_else(|| "Die Datei kann nicht gelesen werden.".to_string())?;
+    if let Some(pages) = search.job.use_index.then(|| crate::text_index::load(path, stamp)).flatten() {
+        return Ok(Texts::Read(pages));
+    }
+
+    let describe = |e: Error| crate::load_error::describe(&e);
+    let pages = if is_pdf {
+        crate::pdf_text::page_texts(path).map_err(describe)?
+    } else {
+        let loader = loader.ok_or_else(|| "Die Bildverarbeitung ist nicht verfügbar.".to_string())?;
+        let _slot = search.ocr_slots.acquire();
+        let doc = loader.load(path, &CancelToken::new(HWND(0))).map_err(describe)?;
+        let mut pages = Vec::new();
+        for page in 0..doc.page_count() {
+            // Half-read files are not stored in the index
+            if search.cancel.load(Ordering::Relaxed) {
+                return Ok(Texts::Cancelled);
+            }
+            let pixels = loader.get_page_pixels(&doc, page, 0, crate::ocr::max_dimension()).map_err(describe)?;
+            pages.push(crate::ocr::recognize_text(&pixels)?);
+        }
+        pages
+    };
+    // Nothing is stored once stopped, e.g. by "Suchindex löschen"
+    if search.job.use_index && !search.cancel.load(Ordering::Relaxed) {
+        crate::text_index::store(path, stamp, &pages);
+    }
+    Ok(Texts::Read(pages))
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase()
+}
+
+/// The pages of `pages` containing `term`, each with the text around its first match
+fn hits(path: &str, pages: &[String], term: &[char]) -> Vec<SearchHit> {
+    if term.is_empty() {
+        return Vec::new();
+    }
+    let mut hits = Vec::new();
+    for (page, text) in pages.iter().enumerate() {
+        let chars = normalized(text);
+        let folded: Vec<char> = chars.iter().copied().map(fold_case).collect();
+        let Some(at) = folded.windows(term.len()).position(|window| window == term) else {
+            continue;
+        };
+        let start = at.saturating_sub(SNIPPET_CONTEXT);
+        let end = (at + term.len() + SNIPPET_CONTEXT).min(chars.len());
+        let mut snippet: String = chars[start..end].iter().collect();
+        if start > 0 {
+            snippet = format!("…{}", snippet.trim_start());
+        }
+        if end < chars.len() {
+            snippet = format!("{}…", snippet.trim_end());
+        }
+        hits.push(SearchHit { path: path.to_string(), page, snippet });
+        if hits.len() == MAX_HITS_PER_FILE {
+            break;
+        }
+    }
+    hits
+}
+
+/// Characters of `text` with every run of whitespace (line breaks too) as one space, so
+/// a term matches across the lines of the page
+fn normalized(text: &str) -> Vec<char> {
+    let mut chars: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.trim().chars() {
+        let c = if c.is_whitespace() { ' ' } else { c };
+        if c != ' ' || chars.last() != Some(&' ') {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+/// Lowercase, one character for one so positions stay comparable
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// The "Ordner durchsuchen" window: a modeless dialog owned by the main window. Closing
+/// only hides it.
+pub struct SearchPanel {
+    hwnd: HWND,
+    list: HWND,
+}
+
+impl SearchPanel {
+    pub fn new(owner: HWND, instance: HMODULE) -> Result<Self> {
+        unsafe {
+            let hwnd = CreateDialogParamW(instance, PCWSTR(IDD_SEARCH as *const u16), owner, Some(search_dialog_proc), LPARAM(0));
+            if hwnd.0 == 0 {
+                return Err(Error::from_win32());
+            }
+            let list = GetDlgItem(hwnd, IDC_LIST);
+            let style = LVS_EX_FULLROWSELECT | LVS_EX_INFOTIP | LVS_EX_DOUBLEBUFFER;
+            SendMessageW(list, LVM_SETEXTENDEDLISTVIEWSTYLE, WPARAM(style as usize), LPARAM(style as isize));
+            let scale = |value: i32| value * GetDpiForWindow(owner).max(96) as i32 / 96;
+            for (i, (title, width)) in COLUMNS.iter().enumerate() {
+                let mut title: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+                let column = LVCOLUMNW {
+                    mask: LVCF_TEXT | LVCF_WIDTH,
+                    cx: scale(*width),
+                    pszText: PWSTR(title.as_mut_ptr()),
+                    ..Default::default()
+                };
+                SendMessageW(list, LVM_INSERTCOLUMNW, WPARAM(i), LPARAM(&column as *const _ as isize));
+            }
+            crate::dialogs::center_on_owner(hwnd);
+            Ok(Self { hwnd, list })
+        }
+    }
+
+    /// Shows the window for `folder` with the search field focused
+    pub fn show(&self, folder: &str) {
+        unsafe {
+            let name = Path::new(folder).file_name().map_or(folder.to_string(), |n| n.to_string_lossy().to_string());
+            let _ = SetWindowTextW(self.hwnd, &HSTRING::from(format!("Ordner durchsuchen – {}", name)));
+            ShowWindow(self.hwnd, SW_SHOW);
+            let term = GetDlgItem(self.hwnd, IDC_TERM);
+            SetFocus(term);
+            SendMessageW(term, EM_SETSEL, WPARAM(0), LPARAM(-1));
+        }
+    }
+
+    /// Keyboard handling of the window (Tab, Enter, Esc); true if `msg` was for it
+    pub fn is_dialog_message(&self, msg: &MSG) -> bool {
+        unsafe { IsWindowVisible(self.hwnd).as_bool() && IsDialogMessageW(self.hwnd, msg).as_bool() }
+    }
+
+    /// Text of the search field
+    pub fn term(&self) -> String {
+        let mut buffer = [0u16; 260];
+        let len = unsafe { GetDlgItemTextW(self.hwnd, IDC_TERM, &mut buffer) } as usize;
+        String::from_utf16_lossy(&buffer[..len]).trim().to_string()
+    }
+
+    pub fn set_status(&self, text: &str) {
+        unsafe {
+            let _ = SetDlgItemTextW(self.hwnd, IDC_STATUS, &HSTRING::from(text));
+        }
+    }
+
+    pub fn clear(&self) {
+        unsafe {
+            SendMessageW(self.list, LVM_DELETEALLITEMS, WPARAM(0), LPARAM(0));
+        }
+    }
+
+    /// Appends `hits`; their list positions are their indexes in the app's list of matches
+    pub fn add_hits(&self, hits: &[SearchHit]) {
+        unsafe {
+            let first = SendMessageW(self.list, LVM_GETITEMCOUNT, WPARAM(0), LPARAM(0)).0 as i32;
+            for (i, hit) in hits.iter().enumerate() {
+                let name = Path::new(&hit.path).file_name().map_or(hit.path.clone(), |n| n.to_string_lossy().to_string());
+                let texts = [name, (hit.page + 1).to_string(), hit.snippet.clone()];
+                for (column, text) in texts.iter().enumerate() {
+                    let mut text: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+                    let item = LVITEMW {
+                        mask: LVIF_TEXT,
+                        iItem: first + i as i32,
+                        iSubItem: column as i32,
+                        pszText: PWSTR(text.as_mut_ptr()),
+                        ..Default::default()
+                    };
+                    let message = if column == 0 { LVM_INSERTITEMW } else { LVM_SETITEMW };
+                    SendMessageW(self.list, message, WPARAM(0), LPARAM(&item as *const _ as isize));
+                }
+            }
+        }
+    }
+}
+
+/// Moves the search field, button, status and list along with the window size
+unsafe fn layout(hwnd: HWND, width: i32, height: i32) {
+    let (term, button, status, list) = (GetDlgItem(hwnd, IDC_TERM), GetDlgItem(hwnd, IDOK.0), GetDlgItem(hwnd, IDC_STATUS), GetDlgItem(hwnd, IDC_LIST));
+    if list.0 == 0 {
+        return;
+    }
+    let term_rect = crate::dialogs::dialog_item_rect(hwnd, term);
+    let button_rect = crate::dialogs::dialog_item_rect(hwnd, button);
+    let status_rect = crate::dialogs::dialog_item_rect(hwnd, status);
+    let list_rect = crate::dialogs::dialog_item_rect(hwnd, list);
+    let margin = term_rect.left;
+    let gap = button_rect.left - term_rect.right;
+    let button_left = width - margin - (button_rect.right - button_rect.left);
+    let flags = SWP_NOZORDER | SWP_NOACTIVATE;
+    let _ = SetWindowPos(button, None, button_left, button_rect.top, 0, 0, flags | SWP_NOSIZE);
+    let _ = SetWindowPos(term, None, 0, 0, (button_left - gap - margin).max(0), term_rect.bottom - term_rect.top, flags | SWP_NOMOVE);
+    let _ = SetWindowPos(status, None, 0, 0, (width - 2 * margin).max(0), status_rect.bottom - status_rect.top, flags | SWP_NOMOVE);
+    let _ = SetWindowPos(list, None, 0, 0, (width - 2 * margin).max(0), (height - list_rect.top - margin).max(0), flags | SWP_NOMOVE);
+}
+
+extern "system" fn search_dialog_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> isize {
+    unsafe {
+        let owner = GetWindow(hwnd, GW_OWNER);
+        let list = GetDlgItem(hwnd, IDC_LIST);
+        match msg {
+            WM_INITDIALOG => 1,
+            WM_SIZE => {
+                layout(hwnd, (lparam.0 & 0xFFFF) as i32, ((lparam.0 >> 16) & 0xFFFF) as i32);
+                1
+            }
+            WM_COMMAND => match (wparam.0 & 0xFFFF) as i32 {
+                // Enter opens the selected match while the list has the focus
+                1 => {
+                    let selected = SendMessageW(list, LVM_GETNEXTITEM, WPARAM(usize::MAX), LPARAM(LVNI_SELECTED as isize)).0;
+                    if GetFocus() == list && selected >= 0 {
+                        let _ = PostMessageW(owner, WM_APP_SEARCH_REQUEST, WPARAM(SearchRequest::Open as usize), LPARAM(selected));
+                    } else {
+                        let _ = PostMessageW(owner, WM_APP_SEARCH_REQUEST, WPARAM(SearchRequest::Search as usize), LPARAM(0));
+                    }
+                    1
+                }
+                // Esc and the close button
+                2 => {
+                    ShowWindow(hwnd, SW_HIDE);
+                    let _ = PostMessageW(owner, WM_APP_SEARCH_REQUEST, WPARAM(SearchRequest::Stop as usize), LPARAM(0));
+                    1
+                }
+                _ => 0,
+            },
+            WM_NOTIFY if (*(lparam.0 as *const NMHDR)).idFrom == IDC_LIST as usize => {
+                match (*(lparam.0 as *const NMHDR)).code {
+                    LVN_ITEMACTIVATE => {
+                        let activate = &*(lparam.0 as *const NMITEMACTIVATE);
+                        if activate.iItem >= 0 {
+                            let _ = PostMessageW(owner, WM_APP_SEARCH_REQUEST, WPARAM(SearchRequest::Open as usize), LPARAM(activate.iItem as isize));
+                        }
+                    }
+                    // The whole text around the match when hovering an entry
+                    LVN_GETINFOTIPW => {
+                        let tip = &mut *(lparam.0 as *mut NMLVGETINFOTIPW);
+                        let mut text = vec![0u16; tip.cchTextMax.max(1) as usize];
+                        let item = LVITEMW {
+                            iSubItem: 2,
+                            pszText: PWSTR(text.as_mut_ptr()),
+                            cchTextMax: text.len() as i32,
+                            ..Default::default()
+                        };
+                        let len = SendMessageW(list, LVM_GETITEMTEXTW, WPARAM(tip.iItem as usize), LPARAM(&item as *const _ as isize)).0 as usize;
+                        if len > 0 {
+                            std::ptr::copy_nonoverlapping(text.as_ptr(), tip.pszText.0, len + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                1
+            }
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! "Ordnerstatistik": file count, total size and PDF pages of the current folder
+//!
+//! For administrators sizing an archive export. The files of the Back/Next list are
+//! walked on a worker thread: sizes are summed per file type and every PDF is opened just
+//! far enough to read its page count (`PdfLoader::page_count`). A folder of hundreds of
+//! PDFs takes a while, so the status bar shows the progress and Esc stops the walk; the
+//! figures then cover the files done so far. The result comes back with
+//! WM_APP_FOLDER_STATS.
+
+use crate::pdf::PdfLoader;
+use crate::utils::format_file_size;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use windows::Win32::{
+    Foundation::*,
+    System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED},
+    UI::WindowsAndMessaging::*,
+};
+
+/// Posted to the main window; lparam owns a `Box<FolderStats>` (see `take_result`)
+pub const WM_APP_FOLDER_STATS: u32 = WM_APP + 10;
+
+/// Files and bytes of one extension
+pub struct TypeStats {
+    pub extension: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+pub struct FolderStats {
+    pub folder: String,
+    /// Files in the list; `files` is fewer if the walk was stopped
+    pub total: usize,
+    pub files: usize,
+    pub bytes: u64,
+    /// Most frequent type first
+    pub types: Vec<TypeStats>,
+    pub pdf_pages: usize,
+    /// PDFs whose pages could not be counted (encrypted, damaged or unreachable)
+    pub unreadable_pdfs: usize,
+    pub cancelled: bool,
+}
+
+impl FolderStats {
+    /// Text for the result dialog and the clipboard
+    pub fn report(&self) -> String {
+        let mut text = format!(
+            "Ordnerstatistik\n\nOrdner: {}\nDateien: {}\nGesamtgröße: {}\nPDF-Seiten: {}",
+            self.folder,
+            self.files,
+            format_file_size(self.bytes),
+            self.pdf_pages
+        );
+        if self.unreadable_pdfs > 0 {
+            text.push_str(&format!(" ({} PDF-Dateien nicht lesbar)", self.unreadable_pdfs));
+        }
+        if !self.types.is_empty() {
+            text.push_str("\n\nNach Dateityp:");
+            for file_type in &self.types {
+                text.push_str(&format!(
+                    "\n{}: {} Dateien, {}",
+                    file_type.extension.to_uppercase(),
+                    file_type.files,
+                    format_file_size(file_type.bytes)
+                ));
+            }
+        }
+        if self.cancelled {
+            text.push_str(&format!("\n\nAbgebrochen nach {} von {} Dateien.", self.files, self.total));
+        }
+        text
+    }
+}
+
+/// Walks `files` on a worker thread until done or `cancel` is set and posts the result
+/// to `hwnd`. False if the thread could not be started.
+pub fn start(hwnd: HWND, folder: String, files: Vec<String>, cancel: Arc<AtomicBool>) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView Folder Statistics".to_string())
+        .spawn(move || {
+            let hwnd = HWND(hwnd_raw);
+            // Windows.Data.Pdf needs COM on this thread
+            let com = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+            let stats = collect(hwnd, folder, &files, &cancel);
+            if com {
+                unsafe { CoUninitialize() };
+            }
+            crate::statusbar::post_progress(hwnd, None);
+            let result = Box::into_raw(Box::new(stats));
+            if !unsafe { PostMessageW(hwnd, WM_APP_FOLDER_STATS, WPARAM(0), LPARAM(result as isize)) }.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
+            }
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the result posted with WM_APP_FOLDER_STATS
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_FOLDER_STATS message and be taken only once.
+pub unsafe fn take_result(lparam: LPARAM) -> FolderStats {
+    *Box::from_raw(lparam.0 as *mut FolderStats)
+}
+
+fn collect(hwnd: HWND, folder: String, files: &[String], cancel: &AtomicBool) -> FolderStats {
+    let mut stats = FolderStats {
+        folder,
+        total: files.len(),
+        files: 0,
+        bytes: 0,
+        types: Vec::new(),
+        pdf_pages: 0,
+        unreadable_pdfs: 0,
+        cancelled: false,
+    };
+
+    for (i, path) in files.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            stats.cancelled = true;
+            break;
+        }
+        crate::statusbar::post_progress(hwnd, Some((i, files.len())));
+
+        let size = std::fs::metadata(crate::utils::long_path(path)).map_or(0, |m| m.len());
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if extension == "pdf" {
+            match PdfLoader::page_count(path) {
+                Ok(pages) => stats.pdf_pages += pages,
+                Err(_) => stats.unreadable_pdfs += 1,
+            }
+        }
+
+        stats.files += 1;
+        stats.bytes += size;
+        match stats.types.iter_mut().find(|t| t.extension == extension) {
+            Some(file_type) => {
+                file_type.files += 1;
+                file_type.bytes += size;
+            }
+            None => stats.types.push(TypeStats { extension, files: 1, bytes: size }),
+        }
+    }
+
+    stats.types.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.extension.cmp(&b.extension)));
+    stats
+}
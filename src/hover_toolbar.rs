@@ -0,0 +1,159 @@
+//! Quick actions for a single page in continuous mode
+//!
+//! Resting the mouse near the top-right corner of a page shows a small floating bar with
+//! rotate left/right and copy for exactly that page. It appears after `SHOW_DELAY` so it
+//! does not flash up while the mouse just passes by, stays while the cursor is on the page
+//! and fades out over `FADE_DURATION` once it leaves (or vanishes at once while animations
+//! are off in Windows, see `theme::SystemSettings`). App drives it from mouse moves and a
+//! timer; D2DRenderer draws it and reports the button rectangles back for hit testing.
+
+use std::time::{Duration, Instant};
+use windows::Win32::Graphics::Direct2D::Common::D2D_RECT_F;
+
+/// Timer ID on the main window while the bar is waiting to appear or fading out
+pub const HOVER_TOOLBAR_TIMER: usize = 1;
+/// Timer interval, short enough for a smooth fade
+pub const TIMER_INTERVAL_MS: u32 = 30;
+
+const SHOW_DELAY: Duration = Duration::from_millis(400);
+const FADE_DURATION: Duration = Duration::from_millis(250);
+/// Size of the corner area (view pixels) that triggers the bar
+const CORNER_SIZE: f32 = 120.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HoverAction {
+    RotateLeft,
+    RotateRight,
+    Copy,
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    Hidden,
+    Pending { page: usize, since: Instant },
+    Shown { page: usize },
+    Fading { page: usize, since: Instant },
+}
+
+pub struct HoverToolbar {
+    enabled: bool,
+    /// Fade out; off while Windows shows no animations
+    animate: bool,
+    phase: Phase,
+    /// Button rectangles of the last frame (view coordinates)
+    buttons: Vec<(HoverAction, D2D_RECT_F)>,
+}
+
+impl HoverToolbar {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, animate: true, phase: Phase::Hidden, buttons: Vec::new() }
+    }
+
+    pub fn set_animate(&mut self, animate: bool) {
+        self.animate = animate;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.hide();
+        }
+    }
+
+    /// True if `(x, y)` lies in the corner area of a page rectangle
+    pub fn in_corner(page_rect: &D2D_RECT_F, x: f32, y: f32) -> bool {
+        x >= page_rect.right - CORNER_SIZE && x < page_rect.right && y >= page_rect.top && y < page_rect.top + CORNER_SIZE
+    }
+
+    /// Cursor update: `page` is the page under the cursor, `corner` whether the cursor is
+    /// in that page's corner area. Returns true if the bar has to be repainted.
+    pub fn track(&mut self, page: Option<usize>, corner: bool) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let corner_page = page.filter(|_| corner);
+        let (phase, repaint) = match self.phase {
+            Phase::Hidden => match corner_page {
+                Some(p) => (Phase::Pending { page: p, since: Instant::now() }, false),
+                None => (Phase::Hidden, false),
+            },
+            Phase::Pending { page: pending, since } => match corner_page {
+                Some(p) if p == pending => (Phase::Pending { page: p, since }, false),
+                Some(p) => (Phase::Pending { page: p, since: Instant::now() }, false),
+                None => (Phase::Hidden, false),
+            },
+            Phase::Shown { page: shown } if page == Some(shown) => (Phase::Shown { page: shown }, false),
+            Phase::Shown { .. } if !self.animate => (Phase::Hidden, true),
+            Phase::Shown { page: shown } => (Phase::Fading { page: shown, since: Instant::now() }, false),
+            // Coming back before it is gone brings it back at full opacity
+            Phase::Fading { page: fading, .. } if page == Some(fading) => (Phase::Shown { page: fading }, true),
+            Phase::Fading { page: fading, since } => match corner_page {
+                Some(p) => (Phase::Pending { page: p, since: Instant::now() }, true),
+                None => (Phase::Fading { page: fading, since }, false),
+            },
+        };
+        self.phase = phase;
+        repaint
+    }
+
+    /// Timer tick: shows the bar after the delay and advances the fade.
+    /// Returns true if the bar has to be repainted.
+    pub fn tick(&mut self) -> bool {
+        match self.phase {
+            Phase::Pending { page, since } if since.elapsed() >= SHOW_DELAY => {
+                self.phase = Phase::Shown { page };
+                true
+            }
+            Phase::Fading { since, .. } if since.elapsed() >= FADE_DURATION => {
+                self.hide();
+                true
+            }
+            Phase::Fading { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// The timer is only needed while waiting to appear or fading out
+    pub fn needs_timer(&self) -> bool {
+        matches!(self.phase, Phase::Pending { .. } | Phase::Fading { .. })
+    }
+
+    /// Hides immediately (drag-to-pan, another document, disabled)
+    pub fn hide(&mut self) {
+        self.phase = Phase::Hidden;
+        self.buttons.clear();
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        matches!(self.phase, Phase::Hidden)
+    }
+
+    /// Page the bar is drawn on and its opacity
+    pub fn visible_page(&self) -> Option<(usize, f32)> {
+        match self.phase {
+            Phase::Shown { page } => Some((page, 1.0)),
+            Phase::Fading { page, since } => {
+                let progress = since.elapsed().as_secs_f32() / FADE_DURATION.as_secs_f32();
+                Some((page, (1.0 - progress).clamp(0.0, 1.0)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn set_buttons(&mut self, buttons: Vec<(HoverAction, D2D_RECT_F)>) {
+        self.buttons = buttons;
+    }
+
+    /// Page and action of the button at a view point
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<(usize, HoverAction)> {
+        let (page, _) = self.visible_page()?;
+        self.buttons
+            .iter()
+            .find(|(_, r)| x >= r.left && x < r.right && y >= r.top && y < r.bottom)
+            .map(|&(action, _)| (page, action))
+    }
+}
@@ -0,0 +1,224 @@
+//! Scan inbox mode ("Eingangsordner überwachen")
+//!
+//! Reception scans into a hot folder and the viewer should always show the newest
+//! arrival. A watcher thread waits for change notifications on the folder and posts
+//! `WM_APP_INBOX_FILE` once a new supported file is complete: its size has not changed for
+//! `SETTLE_TIME` and nobody holds it open for writing any more. Files that exist when
+//! watching starts are not announced.
+//!
+//! Scanner software often writes under a temporary name and renames at the end. The
+//! folder is rescanned by name on every notification, so a renamed file simply is a new
+//! candidate and the old name is dropped; it settles again under its final name.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Storage::FileSystem::*,
+        System::Threading::{CreateEventW, SetEvent, WaitForMultipleObjects, INFINITE},
+        UI::WindowsAndMessaging::{PostMessageW, WM_APP},
+    },
+};
+
+/// Posted to the main window; lparam owns a `Box<String>` with the path (see `take_path`)
+pub const WM_APP_INBOX_FILE: u32 = WM_APP + 5;
+
+/// How long a new file's size must stay the same before it counts as written
+const SETTLE_TIME: Duration = Duration::from_millis(1500);
+/// Rescan interval while files are still settling
+const POLL_INTERVAL_MS: u32 = 250;
+
+pub struct InboxWatcher {
+    folder: String,
+    /// Manual-reset event that ends the watcher thread
+    stop: HANDLE,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl InboxWatcher {
+    /// Starts watching `folder` for the main window `hwnd`
+    pub fn start(hwnd: HWND, folder: &str) -> Result<Self> {
+        if !Path::new(folder).is_dir() {
+            return Err(Error::new(
+                ERROR_PATH_NOT_FOUND.to_hresult(),
+                HSTRING::from(format!("Der Ordner existiert nicht:\n{}", folder)),
+            ));
+        }
+        let change = unsafe {
+            FindFirstChangeNotificationW(
+                &HSTRING::from(folder),
+                FALSE,
+                FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_SIZE | FILE_NOTIFY_CHANGE_LAST_WRITE,
+            )?
+        };
+        let stop = unsafe { CreateEventW(None, TRUE, FALSE, None) }.inspect_err(|_| unsafe {
+            FindCloseChangeNotification(change);
+        })?;
+
+        // Handles are not Send; the raw values are only used by the thread
+        let (hwnd_raw, stop_raw, change_raw) = (hwnd.0, stop.0, change.0);
+        let path = PathBuf::from(folder);
+        let thread = std::thread::Builder::new()
+            .name("SimpliView Inbox".to_string())
+            .spawn(move || {
+                let change = FindChangeNotificationHandle(change_raw);
+                watch(HWND(hwnd_raw), &path, HANDLE(stop_raw), change);
+                unsafe {
+                    FindCloseChangeNotification(change);
+                }
+            })
+            .map_err(|e| Error::new(E_FAIL, HSTRING::from(e.to_string())))?;
+
+        Ok(Self { folder: folder.to_string(), stop, thread: Some(thread) })
+    }
+
+    pub fn folder(&self) -> &str {
+        &self.folder
+    }
+}
+
+impl Drop for InboxWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            SetEvent(self.stop);
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        unsafe {
+            CloseHandle(self.stop);
+        }
+    }
+}
+
+/// Takes ownership of the path posted with `WM_APP_INBOX_FILE`
+///
+/// # Safety
+/// `lparam` must come from a `WM_APP_INBOX_FILE` posted by the watcher thread.
+pub unsafe fn take_path(lparam: LPARAM) -> String {
+    *Box::from_raw(lparam.0 as *mut String)
+}
+
+/// Newest supported file in `folder` (shown when watching starts without a file)
+pub fn newest_file(folder: &str) -> Option<String> {
+    supported_files(Path::new(folder))
+        .into_iter()
+        .max_by_key(|file| file.modified)
+        .map(|file| file.path.to_string_lossy().to_string())
+}
+
+struct FolderFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// A new file that is possibly still being written
+struct Candidate {
+    size: u64,
+    since: Instant,
+}
+
+fn watch(hwnd: HWND, folder: &Path, stop: HANDLE, change: FindChangeNotificationHandle) {
+    let mut known: HashSet<PathBuf> = supported_files(folder).into_iter().map(|file| file.path).collect();
+    let mut candidates: HashMap<PathBuf, Candidate> = HashMap::new();
+
+    loop {
+        let timeout = if candidates.is_empty() { INFINITE } else { POLL_INTERVAL_MS };
+        let result = unsafe { WaitForMultipleObjects(&[stop, HANDLE(change.0)], FALSE, timeout) };
+        match result {
+            r if r == WAIT_OBJECT_0 => break,
+            r if r.0 == WAIT_OBJECT_0.0 + 1 => unsafe {
+                FindNextChangeNotification(change);
+            },
+            WAIT_TIMEOUT => {}
+            // Handle gone (e.g. the folder was deleted): stop quietly
+            _ => break,
+        }
+
+        if let Some(path) = rescan(folder, &mut known, &mut candidates) {
+            let path = Box::into_raw(Box::new(path));
+            if !unsafe { PostMessageW(hwnd, WM_APP_INBOX_FILE, WPARAM(0), LPARAM(path as isize)) }.as_bool() {
+                drop(unsafe { Box::from_raw(path) });
+            }
+        }
+    }
+}
+
+/// Updates the candidates and returns the newest file that finished being written
+fn rescan(folder: &Path, known: &mut HashSet<PathBuf>, candidates: &mut HashMap<PathBuf, Candidate>) -> Option<String> {
+    let files = supported_files(folder);
+    let present: HashSet<&PathBuf> = files.iter().map(|file| &file.path).collect();
+    // Renamed or deleted: a file coming back under this name is new again
+    known.retain(|path| present.contains(path));
+    candidates.retain(|path, _| present.contains(path));
+
+    let mut ready: Vec<&FolderFile> = Vec::new();
+    for file in files.iter().filter(|file| !known.contains(&file.path)) {
+        match candidates.get_mut(&file.path) {
+            Some(candidate) if candidate.size != file.size => {
+                candidate.size = file.size;
+                candidate.since = Instant::now();
+            }
+            Some(candidate) if candidate.since.elapsed() >= SETTLE_TIME && !is_being_written(&file.path) => {
+                ready.push(file);
+            }
+            Some(_) => {}
+            None => {
+                candidates.insert(file.path.clone(), Candidate { size: file.size, since: Instant::now() });
+            }
+        }
+    }
+
+    for file in &ready {
+        candidates.remove(&file.path);
+        known.insert(file.path.clone());
+    }
+    ready.into_iter().max_by_key(|file| file.modified).map(|file| file.path.to_string_lossy().to_string())
+}
+
+/// True while another process has the file open for writing
+pub fn is_being_written(path: &Path) -> bool {
+    // Sharing read access only fails if a writer has it open
+    let handle = unsafe {
+        CreateFileW(
+            &HSTRING::from(path.to_string_lossy().as_ref()),
+            FILE_GENERIC_READ.0,
+            FILE_SHARE_READ,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+    };
+    match handle {
+        Ok(handle) => {
+            unsafe {
+                CloseHandle(handle);
+            }
+            false
+        }
+        Err(_) => true,
+    }
+}
+
+fn supported_files(folder: &Path) -> Vec<FolderFile> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let extension = path.extension()?.to_str()?.to_lowercase();
+            if !crate::app::FOLDER_EXTENSIONS.contains(&extension.as_str()) {
+                return None;
+            }
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            Some(FolderFile { path, size: metadata.len(), modified: metadata.modified().ok()? })
+        })
+        .collect()
+}
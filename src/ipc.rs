@@ -0,0 +1,307 @@
+//! Remote control of a running viewer
+//!
+//! Practice software drives an open SimpliView through the named pipe
+//! `\\.\pipe\SimpliView-<session id>`: one command line per connection, answered with one
+//! line of JSON (`{"ok":true,...}` or `{"ok":false,"error":"..."}`). The pipe thread only
+//! parses; every command is posted to the UI thread as `WM_APP_IPC_COMMAND` and runs
+//! through the same code as the toolbar, including --restricted and --viewonly.
+//!
+//! Commands: `open <path>`, `goto-page <n>`, `zoom <percent>|fit`, `close`, `status`.
+//! `SimpliView.exe --send <command>` is the matching client for scripts.
+//!
+//! The pipe's DACL only admits the user running the viewer and remote clients are
+//! rejected. If another viewer in the session already owns the pipe, this one does not
+//! serve (FILE_FLAG_FIRST_PIPE_INSTANCE), so commands always reach the first viewer.
+
+use std::sync::mpsc;
+use std::time::Duration;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Security::{Authorization::*, *},
+        Storage::FileSystem::*,
+        System::{
+            Pipes::*,
+            RemoteDesktop::ProcessIdToSessionId,
+            Threading::{GetCurrentProcess, GetCurrentProcessId, OpenProcessToken},
+        },
+        UI::WindowsAndMessaging::{PostMessageW, WM_APP},
+    },
+};
+
+/// Posted to the main window; lparam owns a `Box<IpcRequest>` (see `take_request`)
+pub const WM_APP_IPC_COMMAND: u32 = WM_APP + 4;
+
+const BUFFER_SIZE: u32 = 4096;
+/// Longest accepted command line (paths can be long, but not unbounded)
+const MAX_LINE: usize = 32 * 1024;
+/// Open can show dialogs (unapplied changes, errors), so the UI may take a while
+const REPLY_TIMEOUT: Duration = Duration::from_secs(120);
+const CONNECT_TIMEOUT_MS: u32 = 5000;
+
+pub enum IpcCommand {
+    Open(String),
+    /// 1-based page number
+    GotoPage(usize),
+    /// Zoom in percent, `None` = fit to page
+    Zoom(Option<f32>),
+    Close,
+    Status,
+}
+
+/// A command waiting for its reply from the UI thread
+pub struct IpcRequest {
+    pub command: IpcCommand,
+    reply: mpsc::Sender<String>,
+}
+
+impl IpcRequest {
+    pub fn reply(&self, response: String) {
+        // The client may have given up already
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Takes ownership of the request posted with `WM_APP_IPC_COMMAND`
+///
+/// # Safety
+/// `lparam` must come from a `WM_APP_IPC_COMMAND` posted by the pipe thread.
+pub unsafe fn take_request(lparam: LPARAM) -> Box<IpcRequest> {
+    Box::from_raw(lparam.0 as *mut IpcRequest)
+}
+
+/// Pipe name for the current Windows session (pipe names are machine-wide)
+fn pipe_name() -> HSTRING {
+    let mut session = 0u32;
+    unsafe {
+        let _ = ProcessIdToSessionId(GetCurrentProcessId(), &mut session);
+    }
+    HSTRING::from(format!("\\\\.\\pipe\\SimpliView-{}", session))
+}
+
+pub fn parse(line: &str) -> std::result::Result<IpcCommand, String> {
+    let line = line.trim();
+    let (name, argument) = line.split_once(' ').map_or((line, ""), |(n, a)| (n, a.trim()));
+    match name {
+        "open" if !argument.is_empty() => Ok(IpcCommand::Open(argument.trim_matches('"').to_string())),
+        "open" => Err("open erwartet einen Dateipfad.".to_string()),
+        "goto-page" => argument
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n >= 1)
+            .map(IpcCommand::GotoPage)
+            .ok_or_else(|| "goto-page erwartet eine Seitenzahl ab 1.".to_string()),
+        "zoom" if argument == "fit" => Ok(IpcCommand::Zoom(None)),
+        "zoom" => argument
+            .trim_end_matches('%')
+            .parse::<f32>()
+            .ok()
+            .filter(|p| (10.0..=1000.0).contains(p))
+            .map(|p| IpcCommand::Zoom(Some(p)))
+            .ok_or_else(|| "zoom erwartet einen Wert von 10 bis 1000 (Prozent) oder \"fit\".".to_string()),
+        "close" => Ok(IpcCommand::Close),
+        "status" => Ok(IpcCommand::Status),
+        _ => Err(format!("Unbekannter Befehl: {}", name)),
+    }
+}
+
+/// JSON string literal
+pub fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn ok_response() -> String {
+    "{\"ok\":true}".to_string()
+}
+
+pub fn error_response(message: &str) -> String {
+    format!("{{\"ok\":false,\"error\":{}}}", json_string(message))
+}
+
+/// Starts the pipe server thread for the main window
+pub fn start_server(hwnd: HWND) {
+    // HWND is not Send; the raw value is only used for PostMessageW
+    let hwnd = hwnd.0;
+    let _ = std::thread::Builder::new()
+        .name("SimpliView IPC".to_string())
+        .spawn(move || {
+            let _ = serve(HWND(hwnd));
+        });
+}
+
+fn serve(hwnd: HWND) -> Result<()> {
+    // Never serve without the user-only DACL
+    let security = UserOnlySecurity::new()?;
+    let name = pipe_name();
+    let pipe = unsafe {
+        CreateNamedPipeW(
+            &name,
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            1,
+            BUFFER_SIZE,
+            BUFFER_SIZE,
+            0,
+            Some(&security.attributes),
+        )
+    };
+    if pipe.is_invalid() {
+        return Err(Error::from_win32());
+    }
+
+    loop {
+        let connected = unsafe { ConnectNamedPipe(pipe, None) }.as_bool()
+            || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED;
+        if connected {
+            let response = match read_line(pipe) {
+                Some(line) => match parse(&line) {
+                    Ok(command) => dispatch(hwnd, command),
+                    Err(message) => error_response(&message),
+                },
+                None => error_response("Ungültige Anfrage."),
+            };
+            let _ = write_line(pipe, &response);
+            unsafe {
+                let _ = FlushFileBuffers(pipe);
+            }
+        }
+        unsafe {
+            let _ = DisconnectNamedPipe(pipe);
+        }
+    }
+}
+
+/// Hands a command to the UI thread and waits for its reply
+fn dispatch(hwnd: HWND, command: IpcCommand) -> String {
+    let (sender, receiver) = mpsc::channel();
+    let request = Box::into_raw(Box::new(IpcRequest { command, reply: sender }));
+    let posted = unsafe { PostMessageW(hwnd, WM_APP_IPC_COMMAND, WPARAM(0), LPARAM(request as isize)) }.as_bool();
+    if !posted {
+        // Not delivered, so the request is still ours
+        drop(unsafe { Box::from_raw(request) });
+        return error_response("SimpliView wird gerade beendet.");
+    }
+    receiver
+        .recv_timeout(REPLY_TIMEOUT)
+        .unwrap_or_else(|_| error_response("SimpliView hat nicht rechtzeitig geantwortet."))
+}
+
+/// Reads up to the first line break (or the end of the request)
+fn read_line(handle: HANDLE) -> Option<String> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; BUFFER_SIZE as usize];
+    while !data.contains(&b'\n') && data.len() < MAX_LINE {
+        let mut read = 0u32;
+        let ok = unsafe {
+            ReadFile(handle, Some(buffer.as_mut_ptr() as *mut _), buffer.len() as u32, Some(&mut read), None)
+        };
+        if !ok.as_bool() || read == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..read as usize]);
+    }
+    let line = data.split(|&b| b == b'\n').next()?;
+    String::from_utf8(line.to_vec()).ok().map(|s| s.trim_end_matches('\r').to_string())
+}
+
+fn write_line(handle: HANDLE, line: &str) -> Result<()> {
+    let data = format!("{}\n", line);
+    let mut written = 0u32;
+    unsafe { WriteFile(handle, Some(data.as_bytes()), Some(&mut written), None) }.ok()
+}
+
+/// `--send`: sends one command to the viewer running in this session and returns its reply
+pub fn send(command: &str) -> Result<String> {
+    let name = pipe_name();
+    unsafe {
+        if !WaitNamedPipeW(&name, CONNECT_TIMEOUT_MS).as_bool() {
+            return Err(Error::new(
+                ERROR_FILE_NOT_FOUND.to_hresult(),
+                HSTRING::from("In dieser Sitzung läuft kein SimpliView."),
+            ));
+        }
+        // Identification level only: the server may check who we are, not act as us
+        let pipe = CreateFileW(
+            &name,
+            (GENERIC_READ | GENERIC_WRITE).0,
+            FILE_SHARE_NONE,
+            None,
+            OPEN_EXISTING,
+            SECURITY_SQOS_PRESENT | SECURITY_IDENTIFICATION,
+            None,
+        )?;
+        let result = write_line(pipe, command.trim()).map(|_| read_line(pipe).unwrap_or_default());
+        let _ = CloseHandle(pipe);
+        result
+    }
+}
+
+/// Security attributes whose DACL grants access to the current user only
+struct UserOnlySecurity {
+    descriptor: PSECURITY_DESCRIPTOR,
+    attributes: SECURITY_ATTRIBUTES,
+}
+
+impl UserOnlySecurity {
+    fn new() -> Result<Self> {
+        // Protected DACL with a single "generic all" entry for the user's SID
+        let sddl = HSTRING::from(format!("D:P(A;;GA;;;{})", current_user_sid()?));
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(&sddl, SDDL_REVISION_1, &mut descriptor, None).ok()?;
+        }
+        Ok(Self {
+            descriptor,
+            attributes: SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: descriptor.0,
+                bInheritHandle: FALSE,
+            },
+        })
+    }
+}
+
+impl Drop for UserOnlySecurity {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = windows::Win32::System::Memory::LocalFree(HLOCAL(self.descriptor.0 as isize));
+        }
+    }
+}
+
+/// String SID ("S-1-5-21-...") of the user running this process
+fn current_user_sid() -> Result<String> {
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).ok()?;
+        let mut size = 0u32;
+        let _ = GetTokenInformation(token, TokenUser, None, 0, &mut size);
+        // u64 elements keep TOKEN_USER (which holds a pointer) aligned
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        let result = GetTokenInformation(token, TokenUser, Some(buffer.as_mut_ptr() as *mut _), size, &mut size);
+        let _ = CloseHandle(token);
+        result.ok()?;
+
+        let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+        let mut sid = PWSTR::null();
+        ConvertSidToStringSidW(user.User.Sid, &mut sid).ok()?;
+        let text = sid.to_string();
+        let _ = windows::Win32::System::Memory::LocalFree(HLOCAL(sid.0 as isize));
+        text.map_err(|_| Error::from(E_UNEXPECTED))
+    }
+}
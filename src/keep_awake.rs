@@ -0,0 +1,48 @@
+//! Keeps the system and the display awake during long activities
+//!
+//! Printing and exporting hold a `KeepAwake` guard; a slideshow or presentation mode holds
+//! one for as long as it runs. `SetThreadExecutionState(ES_CONTINUOUS | ...)` stays in
+//! effect until it is reset, so the guard resets it on drop and early returns or errors
+//! cannot leave the PC sleepless. While a guard is active `App` also swallows
+//! SC_SCREENSAVE / SC_MONITORPOWER.
+
+use std::cell::Cell;
+use windows::Win32::System::Power::*;
+
+thread_local! {
+    static ACTIVE_GUARDS: Cell<usize> = const { Cell::new(0) };
+}
+
+pub struct KeepAwake(());
+
+impl KeepAwake {
+    pub fn new() -> Self {
+        ACTIVE_GUARDS.with(|count| {
+            if count.get() == 0 {
+                unsafe {
+                    SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED);
+                }
+            }
+            count.set(count.get() + 1);
+        });
+        Self(())
+    }
+}
+
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        ACTIVE_GUARDS.with(|count| {
+            count.set(count.get() - 1);
+            if count.get() == 0 {
+                unsafe {
+                    SetThreadExecutionState(ES_CONTINUOUS);
+                }
+            }
+        });
+    }
+}
+
+/// Whether any guard is alive (screen saver and monitor power-off are suppressed)
+pub fn is_active() -> bool {
+    ACTIVE_GUARDS.with(|count| count.get() > 0)
+}
@@ -0,0 +1,75 @@
+//! Human-readable load errors
+//!
+//! WIC and Windows.Data.Pdf report failures as raw HRESULTs. These are mapped to a short
+//! German cause for the error box and for the in-canvas error panel shown during folder
+//! navigation (see `App::open_document_internal`).
+
+use windows::{
+    core::*,
+    Win32::Foundation::*,
+};
+
+/// A file that failed to load, shown in the canvas instead of a document
+#[derive(Clone, Debug)]
+pub struct LoadFailure {
+    pub path: String,
+    pub message: String,
+}
+
+const E_INVALID_DATA: HRESULT = HRESULT(0x8007000Du32 as i32);
+const E_HANDLE_EOF: HRESULT = HRESULT(0x80070026u32 as i32);
+const E_SHARING_VIOLATION: HRESULT = HRESULT(0x80070020u32 as i32);
+const E_LOCK_VIOLATION: HRESULT = HRESULT(0x80070021u32 as i32);
+const E_FILE_NOT_FOUND: HRESULT = HRESULT(0x80070002u32 as i32);
+const E_PATH_NOT_FOUND: HRESULT = HRESULT(0x80070003u32 as i32);
+
+/// Error for file types SimpliView cannot open
+pub fn unsupported_format() -> Error {
+    Error::new(WINCODEC_ERR_UNKNOWNIMAGEFORMAT, HSTRING::new())
+}
+
+/// Maps a load error to a readable cause. Messages set by our own loaders
+/// (archive, download) are already readable and are used as is.
+pub fn describe(e: &Error) -> String {
+    let code = e.code();
+    let cause = match code {
+        WINCODEC_ERR_BADHEADER
+        | WINCODEC_ERR_BADIMAGE
+        | WINCODEC_ERR_BADSTREAMDATA
+        | WINCODEC_ERR_STREAMREAD
+        | WINCODEC_ERR_FRAMEMISSING
+        | WINCODEC_ERR_BADMETADATAHEADER
+        | WINCODEC_ERR_UNEXPECTEDSIZE
+        | E_INVALID_DATA
+        | E_HANDLE_EOF => Some("Die Datei ist beschädigt oder unvollständig."),
+        WINCODEC_ERR_UNKNOWNIMAGEFORMAT | WINCODEC_ERR_UNSUPPORTEDPIXELFORMAT => {
+            Some("Das Dateiformat wird nicht unterstützt.")
+        }
+        WINCODEC_ERR_COMPONENTNOTFOUND => {
+            Some("Für dieses Format ist kein Codec installiert (z. B. WebP-Erweiterung aus dem Microsoft Store).")
+        }
+        E_SHARING_VIOLATION | E_LOCK_VIOLATION => {
+            Some("Die Datei wird von einem anderen Programm verwendet.")
+        }
+        E_ACCESSDENIED => Some("Der Zugriff auf die Datei wurde verweigert."),
+        E_FILE_NOT_FOUND | E_PATH_NOT_FOUND => Some("Die Datei wurde nicht gefunden."),
+        E_OUTOFMEMORY | WINCODEC_ERR_IMAGESIZEOUTOFRANGE => {
+            Some("Die Datei ist zu groß, um angezeigt zu werden.")
+        }
+        _ => None,
+    };
+
+    // A message differing from the system text for this code was set by our own loaders
+    let message = e.message().to_string();
+    let custom = !message.is_empty() && message != code.message();
+
+    match (cause, custom) {
+        (_, true) => message,
+        (Some(cause), false) => cause.to_string(),
+        (None, false) => format!(
+            "Unbekannter Fehler (Code 0x{:08X}).{}",
+            code.0 as u32,
+            if message.is_empty() { String::new() } else { format!("\n{}", message) }
+        ),
+    }
+}
@@ -1,29 +1,88 @@
 #![windows_subsystem = "windows"]
 
+mod annotations;
 mod app;
+mod archive;
+mod auto_crop;
+mod batch;
+mod batch_rotate;
+mod blink;
+mod cancel;
+mod capabilities;
+mod clipboard;
+mod cloud_file;
+mod contact_sheet;
 mod d2d;
+mod debug_overlay;
+mod deeplink;
+mod deskew;
+mod dicom;
 mod dialogs;
 mod document;
+mod download;
+mod file_probe;
+mod file_watch;
+mod folder_scan;
+mod folder_search;
+mod folder_stats;
+mod hover_toolbar;
 mod icons;
+mod inbox;
+mod ipc;
+mod keep_awake;
+mod load_error;
 mod menu;
+mod menu_bar;
+mod minimap;
+mod ocr;
+mod operation_log;
+mod page_files;
+mod page_labels;
+mod page_picker;
+mod page_transform;
 mod pdf;
+mod pdf_extract;
+mod pdf_structure;
+mod pdf_text;
+mod pins;
+mod policy;
+mod print_job;
+mod print_stamp;
 mod registration;
+mod render_test;
+mod retry;
+mod safe_mode;
 mod scroll;
+mod settings;
+mod shortcuts;
 mod statusbar;
+mod text_index;
 mod theme;
 mod toolbar;
+mod update_check;
 mod utils;
+mod view_history;
 mod view_window;
+mod wallpaper;
+mod watermark;
 mod wic;
 mod window;
+mod window_level;
 
-use app::App;
+use app::{App, StartupOptions};
 use std::env;
 use windows::{
     core::*,
-    Win32::System::Com::*,
+    Win32::System::{
+        Com::*,
+        Console::{AttachConsole, ATTACH_PARENT_PROCESS},
+    },
 };
 
+/// Exit codes of the registration commands
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+
 fn main() -> Result<()> {
     // Initialize COM for WIC and WinRT
     unsafe {
@@ -31,50 +90,140 @@ fn main() -> Result<()> {
     }
 
     // Parse command-line arguments
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
     
-    // Handle registration commands
-    if args.contains(&String::from("--register")) {
-        match registration::register_file_associations() {
-            Ok(_) => show_message("Erfolg", "Dateiverknüpfungen wurden erfolgreich registriert."),
-            Err(e) => show_message("Fehler", &format!("Dateiverknüpfungen konnten nicht registriert werden: {:?}", e)),
-        }
+    // --send <command>: remote-control the viewer running in this session (see ipc.rs).
+    // Prints the JSON reply for scripts; no message boxes, the exit code tells success.
+    if let Some(pos) = args.iter().position(|a| a == "--send") {
+        let command = args[pos + 1..].join(" ");
+        let reply = ipc::send(&command).unwrap_or_else(|e| ipc::error_response(&e.message().to_string()));
+        println!("{}", reply);
         unsafe { CoUninitialize(); }
-        return Ok(());
+        std::process::exit(if reply.starts_with("{\"ok\":true") { 0 } else { 1 });
     }
 
-    if args.contains(&String::from("--unregister")) {
-        match registration::unregister_file_associations() {
-            Ok(_) => show_message("Erfolg", "Dateiverknüpfungen wurden erfolgreich entfernt."),
-            Err(e) => show_message("Fehler", &format!("Dateiverknüpfungen konnten nicht entfernt werden: {:?}", e)),
-        }
+    // --export-folder / --print-folder: headless batch processing (see batch.rs)
+    if let Some(code) = batch::run(&args) {
         unsafe { CoUninitialize(); }
-        return Ok(());
+        std::process::exit(code);
+    }
+
+    // --render-test: draws a document offscreen into a PNG (see render_test.rs)
+    if let Some(code) = render_test::run(&args) {
+        unsafe { CoUninitialize(); }
+        std::process::exit(code);
+    }
+
+    // Registration commands. From cmd/PowerShell or a deployment script they report in the
+    // calling console and exit with 0 on success, 1 otherwise; started without a console
+    // (e.g. from a shortcut) they show message boxes.
+    let registration_command = ["--register", "--unregister", "--diagnose", "--repair"]
+        .iter()
+        .any(|command| args.iter().any(|a| a == command));
+    let console = registration_command && unsafe { AttachConsole(ATTACH_PARENT_PROCESS) }.as_bool();
+
+    if let Some(pos) = args.iter().position(|a| a == "--register") {
+        // Optional extension list: --register pdf,png,tif
+        let extension_list = args.get(pos + 1).filter(|a| !a.starts_with("--")).map(|s| s.as_str());
+        let file_types = match registration::select_file_types(extension_list) {
+            Ok(types) => types,
+            Err(msg) => {
+                report(console, "Fehler", &format!("Dateiverknüpfungen konnten nicht registriert werden: {}", msg), false);
+                exit(EXIT_FAILURE);
+            }
+        };
+
+        // A restricted installation registers its deep-link handler as restricted, too
+        let restricted = args
+            .iter()
+            .position(|a| a == "--restricted")
+            .and_then(|i| args.get(i + 1))
+            .map(|s| s.as_str());
+        let restricted_verb = args.iter().any(|a| a == "--with-restricted-verb");
+        let success = match registration::register_file_associations(&file_types, restricted, restricted_verb) {
+            Ok(extensions) => report(
+                console,
+                "Erfolg",
+                &format!("Dateiverknüpfungen wurden erfolgreich registriert:\n{}", extensions.join(", ")),
+                true,
+            ),
+            Err(e) => report(console, "Fehler", &format!("Dateiverknüpfungen konnten nicht registriert werden: {:?}", e), false),
+        };
+        exit(if success { EXIT_SUCCESS } else { EXIT_FAILURE });
+    }
+
+    if args.contains(&String::from("--unregister")) {
+        let success = match registration::unregister_file_associations() {
+            Ok(_) => report(console, "Erfolg", "Dateiverknüpfungen wurden erfolgreich entfernt.", true),
+            Err(e) => report(console, "Fehler", &format!("Dateiverknüpfungen konnten nicht entfernt werden: {:?}", e), false),
+        };
+        exit(if success { EXIT_SUCCESS } else { EXIT_FAILURE });
     }
 
     if args.contains(&String::from("--diagnose")) {
         let status = registration::get_registration_status();
+        let stale = registration::stale_registrations();
+        let all_ok = status.iter().all(|(_, ok)| *ok);
+        let code = if all_ok && stale.is_empty() { EXIT_SUCCESS } else { EXIT_FAILURE };
+
+        // --json: for scripts, printed even without a console (redirected output)
+        if args.iter().any(|a| a == "--json") {
+            println!("{}", diagnose_json(&status, &stale));
+            exit(code);
+        }
+
         let mut report = String::from("SimpliView Registration Status:\n\n");
-        let mut all_ok = true;
         for (name, ok) in &status {
             let symbol = if *ok { "✓" } else { "✗" };
             report.push_str(&format!("{} {}\n", symbol, name));
-            if !*ok {
-                all_ok = false;
-            }
         }
         report.push_str(&format!("\n{}", if all_ok {
             "All registrations OK. Use Windows Settings to set SimpliView as default."
         } else {
             "Some registrations missing. Run --register first."
         }));
-        show_message("SimpliView Diagnostics", &report);
-        unsafe { CoUninitialize(); }
-        return Ok(());
+
+        if console {
+            for (label, exe) in &stale {
+                report.push_str(&format!("\nVeraltet: {} ({})", label, exe));
+            }
+            if !stale.is_empty() {
+                report.push_str("\nMit --repair reparieren.");
+            }
+            println!("{}", report);
+        } else if stale.is_empty() {
+            show_message("SimpliView Diagnostics", &report);
+        } else {
+            // Offer to repair entries pointing at a moved executable
+            report.push_str("\n\nVeraltete Einträge jetzt reparieren?");
+            if ask_question("SimpliView Diagnostics", &report) {
+                show_repair_result(console, registration::repair_registrations());
+            }
+        }
+        exit(code);
+    }
+
+    if args.contains(&String::from("--repair")) {
+        let success = show_repair_result(console, registration::repair_registrations());
+        exit(if success { EXIT_SUCCESS } else { EXIT_FAILURE });
     }
 
-    let mut file_to_open = None;
+    let mut file_to_open: Option<String> = None;
     let mut restricted_path = None;
+    let mut start_page = None;
+    let mut view_only = false;
+    let mut restrict_to_folder = false;
+    let mut watermark_text = None;
+    let mut watermark_opacity = None;
+    let mut watermark_angle = None;
+    let mut watch_folder = None;
+    let mut default_filter = None;
+    let mut start_folder = None;
+    let mut confirm_exit = false;
+    let mut safe_mode = false;
+    let mut pdf_dpi = None;
+    let mut topmost = false;
+    let mut password = None;
     
     // Parse arguments
     let mut i = 1;
@@ -85,6 +234,73 @@ fn main() -> Result<()> {
                  restricted_path = Some(args[i+1].clone());
                  i += 1;
              }
+        } else if arg == "--viewonly" {
+            view_only = true;
+        } else if arg == "--confirm-exit" {
+            // For embedding software: ask before a stray Alt+F4 closes the viewer
+            confirm_exit = true;
+        } else if arg == "--topmost" {
+            // For embedding software: keep the viewer above its own window
+            topmost = true;
+        } else if arg == "--safe-mode" {
+            // Ignore saved settings, no inbox watcher and no remote control (see safe_mode.rs)
+            safe_mode = true;
+        } else if arg == "--restrict-to-folder" {
+            // From the open_restricted context-menu verb: restrict to the file's own folder
+            restrict_to_folder = true;
+        } else if arg == "--watermark" {
+            watermark_text = args.get(i + 1).cloned();
+            i += 1;
+        } else if arg == "--watermark-opacity" {
+            watermark_opacity = args.get(i + 1).and_then(|v| v.parse::<u32>().ok());
+            i += 1;
+        } else if arg == "--watermark-angle" {
+            watermark_angle = args.get(i + 1).and_then(|v| v.parse::<i32>().ok());
+            i += 1;
+        } else if arg == "--watch" {
+            // Scan inbox: open new files in this folder automatically (see inbox.rs)
+            watch_folder = args.get(i + 1).cloned();
+            i += 1;
+        } else if arg == "--default-filter" {
+            // Preselected filter of the open dialog: pdf, images, archives or all
+            default_filter = args.get(i + 1).cloned();
+            i += 1;
+        } else if arg == "--start-folder" {
+            start_folder = args.get(i + 1).cloned();
+            i += 1;
+        } else if arg == "--page" {
+            match args.get(i + 1).and_then(|v| v.parse::<usize>().ok()).filter(|&n| n >= 1) {
+                Some(n) => start_page = Some(n),
+                None => {
+                    show_message("Fehler beim Starten", "--page erwartet eine Seitenzahl ab 1.");
+                    return Ok(());
+                }
+            }
+            i += 1;
+        } else if arg == "--password" {
+            // For embedding software that knows the document password. Taken out of `args`,
+            // so the only copy is zeroed after use.
+            password = args.get_mut(i + 1).map(|v| pdf::Password::new(std::mem::take(v)));
+            i += 1;
+        } else if arg == "--password-stdin" {
+            // The same without the password in the process command line
+            match pdf::Password::read_stdin() {
+                Some(p) => password = Some(p),
+                None => {
+                    show_message("Fehler beim Starten", "--password-stdin erwartet das Passwort als erste Zeile der Standardeingabe.");
+                    return Ok(());
+                }
+            }
+        } else if arg == "--pdf-dpi" {
+            // Renders PDF pages at this resolution instead of the "Renderqualität" setting
+            match args.get(i + 1).and_then(|v| v.parse::<u32>().ok()).filter(|dpi| (36..=1200).contains(dpi)) {
+                Some(dpi) => pdf_dpi = Some(dpi),
+                None => {
+                    show_message("Fehler beim Starten", "--pdf-dpi erwartet eine Auflösung von 36 bis 1200 dpi.");
+                    return Ok(());
+                }
+            }
+            i += 1;
         } else if !arg.starts_with("--") {
             if file_to_open.is_none() {
                 file_to_open = Some(arg.clone());
@@ -95,6 +311,29 @@ fn main() -> Result<()> {
         i += 1;
     }
 
+    // simpliview://open?file=...&page=... deep link
+    let mut from_deep_link = false;
+    if let Some(uri) = file_to_open.as_deref().filter(|a| deeplink::is_deep_link(a)) {
+        match deeplink::parse(uri) {
+            Ok(link) => {
+                file_to_open = Some(link.file);
+                start_page = link.page.or(start_page);
+                from_deep_link = true;
+            }
+            Err(msg) => {
+                show_message("Ungültiger Link", &format!("Der Link konnte nicht geöffnet werden:\n{}\n\n{}", uri, msg));
+                return Ok(());
+            }
+        }
+    }
+
+    if restrict_to_folder && restricted_path.is_none() {
+        restricted_path = file_to_open
+            .as_deref()
+            .and_then(|f| std::path::Path::new(f).parent())
+            .map(|p| p.to_string_lossy().to_string());
+    }
+
     // Validate restricted path if provided
     if let Some(ref path) = restricted_path {
         // Check for common CLI quoting errors (quote inside string)
@@ -114,8 +353,43 @@ fn main() -> Result<()> {
         }
     }
 
+    // Deep links may only open files below the restricted root
+    if let (true, Some(root), Some(file)) = (from_deep_link, restricted_path.as_deref(), file_to_open.as_deref()) {
+        if !deeplink::is_within_root(file, root) {
+            show_message("Ungültiger Link", &format!("Die Datei liegt außerhalb des freigegebenen Verzeichnisses:\n{}", file));
+            return Ok(());
+        }
+    }
+
+    // The previous startup did not reach the message loop: offer safe mode
+    if safe_mode::begin_startup() && !safe_mode {
+        safe_mode = dialogs::ask_safe_mode();
+    }
+    if safe_mode {
+        safe_mode::enable();
+    }
+
+    let capabilities = capabilities::Capabilities::from_startup(view_only);
+    if password.is_some() && !capabilities.allows_password_handoff() {
+        show_message("Fehler beim Starten", "Die Übergabe eines Passworts (--password, --password-stdin) ist in dieser Installation nicht erlaubt.");
+        return Ok(());
+    }
+
     // Create and run the application
-    let mut app = App::new(file_to_open, restricted_path)?;
+    let mut app = App::new(StartupOptions {
+        file_to_open,
+        restricted_path,
+        start_page,
+        capabilities,
+        watermark: watermark::Watermark::from_startup(watermark_text, watermark_opacity, watermark_angle),
+        watch_folder,
+        default_filter,
+        start_folder,
+        confirm_exit,
+        pdf_dpi,
+        topmost,
+        password,
+    })?;
     let result = app.run();
 
     // Cleanup COM
@@ -144,3 +418,71 @@ fn show_message(title: &str, message: &str) {
         );
     }
 }
+
+/// Returns true if the repair succeeded
+fn show_repair_result(console: bool, result: Result<usize>) -> bool {
+    match result {
+        Ok(0) => report(console, "Erfolg", "Alle Dateiverknüpfungen zeigen bereits auf diese Programmdatei.", true),
+        Ok(n) => report(console, "Erfolg", &format!("{} veraltete Einträge wurden repariert.", n), true),
+        Err(e) => report(console, "Fehler", &format!("Dateiverknüpfungen konnten nicht repariert werden: {:?}", e), false),
+    }
+}
+
+/// Result of a registration command: a line on stdout (stderr for errors) with a console,
+/// a message box without. Returns `success`.
+fn report(console: bool, title: &str, message: &str, success: bool) -> bool {
+    match (console, success) {
+        (true, true) => println!("{}: {}", title, message),
+        (true, false) => eprintln!("{}: {}", title, message),
+        (false, _) => show_message(title, message),
+    }
+    success
+}
+
+/// `--diagnose --json`: every registration check, stale entries, the executable, its
+/// version and the extensions it can register
+fn diagnose_json(status: &[(String, bool)], stale: &[(String, String)]) -> String {
+    use ipc::json_string;
+    let checks: Vec<String> = status
+        .iter()
+        .map(|(name, ok)| format!("{{\"name\":{},\"ok\":{}}}", json_string(name), ok))
+        .collect();
+    let stale: Vec<String> = stale
+        .iter()
+        .map(|(name, exe)| format!("{{\"name\":{},\"registered_exe\":{}}}", json_string(name), json_string(exe)))
+        .collect();
+    let extensions: Vec<String> = registration::supported_extensions().into_iter().map(json_string).collect();
+    let exe = env::current_exe().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+    format!(
+        "{{\"ok\":{},\"exe\":{},\"version\":{},\"extensions\":[{}],\"checks\":[{}],\"stale\":[{}]}}",
+        status.iter().all(|(_, ok)| *ok) && stale.is_empty(),
+        json_string(&exe),
+        json_string(env!("CARGO_PKG_VERSION")),
+        extensions.join(","),
+        checks.join(","),
+        stale.join(",")
+    )
+}
+
+/// Ends a command-line run
+fn exit(code: i32) -> ! {
+    unsafe { CoUninitialize(); }
+    std::process::exit(code)
+}
+
+/// Yes/No question, returns true for "Ja"
+fn ask_question(title: &str, message: &str) -> bool {
+    use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, IDYES, MB_ICONQUESTION, MB_YESNO};
+
+    let title_wide: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+    let message_wide: Vec<u16> = message.encode_utf16().chain(std::iter::once(0)).collect();
+
+    unsafe {
+        MessageBoxW(
+            None,
+            PCWSTR(message_wide.as_ptr()),
+            PCWSTR(title_wide.as_ptr()),
+            MB_YESNO | MB_ICONQUESTION,
+        ) == IDYES
+    }
+}
@@ -1,4 +1,4 @@
-use crate::icons;
+use crate::{icons, shortcuts, wallpaper::WallpaperPosition};
 use parking_lot::Mutex;
 use std::sync::Arc;
 use windows::{
@@ -13,18 +13,210 @@ use windows::{
 };
 
 // Context menu command IDs
-pub const IDM_FIT_TO_PAGE: u32 = 200;
-pub const IDM_ROTATE_LEFT: u32 = 201;
-pub const IDM_ROTATE_RIGHT: u32 = 202;
+pub const IDM_REOPEN_LAST_FILE: u32 = 203;
+pub const IDM_HOVER_TOOLBAR: u32 = 204;
+pub const IDM_KEEP_VIEW: u32 = 205;
+pub const IDM_INBOX: u32 = 206;
+pub const IDM_INBOX_SOUND: u32 = 207;
+pub const IDM_INCLUDE_SUBFOLDERS: u32 = 208;
+pub const IDM_ESCAPE_EXITS: u32 = 209;
+// Wallpaper commands: IDM_WALLPAPER + target * 3 + position (target 0 = all monitors)
+const IDM_WALLPAPER: u32 = 210;
+const MAX_WALLPAPER_MONITORS: usize = 8;
+// Ordner-Stapelverarbeitung submenu
+pub const IDM_BATCH_ROTATE: u32 = 238;
+// "Text erkennen (OCR)"
+pub const IDM_OCR: u32 = 239;
+// "Ordner durchsuchen"
+pub const IDM_FOLDER_SEARCH: u32 = 248;
+// "Suchindex löschen"
+pub const IDM_CLEAR_TEXT_INDEX: u32 = 305;
+// "Immer im Vordergrund" (Ansicht)
+pub const IDM_TOPMOST: u32 = 249;
+// "Kompaktmodus" (Ansicht)
+pub const IDM_COMPACT: u32 = 291;
+// "Ausschnitt anheften"
+pub const IDM_PIN_SELECTION: u32 = 292;
+// "Ränder automatisch zuschneiden" (Ansicht)
+pub const IDM_AUTO_CROP: u32 = 293;
+// "Schräglage automatisch korrigieren" and "Schräglage dieser Seite korrigieren" (Ansicht)
+pub const IDM_DESKEW: u32 = 294;
+pub const IDM_DESKEW_PAGE: u32 = 295;
+// "Doppelseiten teilen" and "Teilung anpassen..." (Ansicht)
+pub const IDM_SPLIT_SPREADS: u32 = 296;
+pub const IDM_SPLIT_POSITION: u32 = 297;
+// "Seiten als Dateien kopieren"
+pub const IDM_COPY_PAGE_FILES: u32 = 298;
+// "Als PDF drucken"
+pub const IDM_PRINT_PDF: u32 = 299;
+// Mausrad submenu, one radio group from IDM_WHEEL_SCROLL to IDM_WHEEL_PAGE
+pub const IDM_WHEEL_SCROLL: u32 = 240;
+pub const IDM_WHEEL_ZOOM: u32 = 241;
+pub const IDM_WHEEL_PAGE: u32 = 242;
+pub const IDM_WHEEL_INVERT: u32 = 243;
+// Renderqualität submenu, one radio group from IDM_RENDER_FAST to IDM_RENDER_SCREEN
+pub const IDM_RENDER_FAST: u32 = 244;
+pub const IDM_RENDER_STANDARD: u32 = 245;
+pub const IDM_RENDER_HIGH: u32 = 246;
+pub const IDM_RENDER_SCREEN: u32 = 247;
+// Zoom submenu, one radio group from IDM_ZOOM_25 to IDM_FIT_TO_PAGE
+pub const IDM_ZOOM_25: u32 = 250;
+pub const IDM_ZOOM_CUSTOM: u32 = 255;
+pub const IDM_FIT_WIDTH: u32 = 256;
+pub const IDM_FIT_HEIGHT: u32 = 257;
+pub const IDM_FIT_TO_PAGE: u32 = 258;
+// Ansicht submenu
+pub const IDM_VIEW_CONTINUOUS: u32 = 260;
+pub const IDM_VIEW_SINGLE_PAGE: u32 = 261;
+pub const IDM_FULLSCREEN: u32 = 262;
+pub const IDM_MENU_BAR: u32 = 263;
+pub const IDM_HIGH_BIT_DEPTH: u32 = 264;
+pub const IDM_RESET_WINDOW_LEVEL: u32 = 265;
+pub const IDM_BLINK_DIFFERENCE: u32 = 266;
+pub const IDM_UNDO_VIEW: u32 = 267;
+pub const IDM_REDO_VIEW: u32 = 268;
+pub const IDM_ANNOTATIONS: u32 = 269;
+// Rotation and document commands
+pub const IDM_ROTATE_LEFT: u32 = 270;
+pub const IDM_ROTATE_RIGHT: u32 = 271;
+pub const IDM_EXPORT: u32 = 272;
+pub const IDM_PRINT: u32 = 273;
+pub const IDM_PROPERTIES: u32 = 274;
+pub const IDM_ROTATE_180: u32 = 275;
+pub const IDM_CONTACT_SHEET: u32 = 276;
+pub const IDM_FOLDER_STATS: u32 = 277;
+pub const IDM_EXTRACT_PAGES: u32 = 278;
+pub const IDM_GO_TO_PAGE: u32 = 279;
+// "Standarddrehung für neue Dokumente": IDM_DEFAULT_ROTATION + (PDF ? 4 : 0) + rotation / 90
+const IDM_DEFAULT_ROTATION: u32 = 320;
+// Seitenabstand submenu (Ansicht), one radio group from IDM_PAGE_SPACING_NARROW to IDM_PAGE_SPACING_WIDE
+pub const IDM_PAGE_SPACING_NARROW: u32 = 330;
+pub const IDM_PAGE_SPACING_NORMAL: u32 = 331;
+pub const IDM_PAGE_SPACING_WIDE: u32 = 332;
+// "Übersichtsleiste" (Ansicht)
+pub const IDM_MINIMAP: u32 = 333;
+
+/// Zoom presets in percent, IDs from IDM_ZOOM_25 upwards
+const ZOOM_PRESETS: [u32; 5] = [25, 50, 100, 200, 400];
+
+/// Zoom factor of a preset item (IDM_ZOOM_25..)
+pub fn zoom_preset(id: u32) -> Option<f32> {
+    let index = id.checked_sub(IDM_ZOOM_25)? as usize;
+    ZOOM_PRESETS.get(index).map(|&percent| percent as f32 / 100.0)
+}
+
+/// Preset item matching `zoom`, if any
+pub fn zoom_preset_item(zoom: f32) -> Option<u32> {
+    let percent = zoom * 100.0;
+    ZOOM_PRESETS
+        .iter()
+        .position(|&preset| (percent - preset as f32).abs() < 0.5)
+        .map(|index| IDM_ZOOM_25 + index as u32)
+}
+
+/// Decodes a wallpaper command ID into the monitor index (`None` = all) and position
+pub fn wallpaper_command(id: u32) -> Option<(Option<usize>, WallpaperPosition)> {
+    let offset = id.checked_sub(IDM_WALLPAPER)? as usize;
+    let position = WallpaperPosition::ALL[offset % 3];
+    match offset / 3 {
+        0 => Some((None, position)),
+        target if target <= MAX_WALLPAPER_MONITORS => Some((Some(target - 1), position)),
+        _ => None,
+    }
+}
+
+/// Decodes a default rotation command ID into (for PDF documents, degrees)
+pub fn default_rotation_command(id: u32) -> Option<(bool, i32)> {
+    let offset = id.checked_sub(IDM_DEFAULT_ROTATION)?;
+    (offset < 8).then(|| (offset >= 4, (offset % 4) as i32 * 90))
+}
+
+fn default_rotation_item(pdf: bool, rotation: i32) -> u32 {
+    IDM_DEFAULT_ROTATION + if pdf { 4 } else { 0 } + (rotation / 90) as u32
+}
+
+/// View state shown by the check marks, refreshed before the menu opens
+pub struct MenuState {
+    /// Active item of the zoom group (a preset, fit mode or IDM_ZOOM_CUSTOM), `None` without document
+    pub zoom_item: Option<u32>,
+    /// Continuous layout for multi-page documents, `None` for a single page
+    pub continuous: Option<bool>,
+    pub fullscreen: bool,
+    /// "Immer im Vordergrund"
+    pub topmost: bool,
+    /// "Kompaktmodus"
+    pub compact: bool,
+    /// "Ränder automatisch zuschneiden"
+    pub auto_crop: bool,
+    /// "Schräglage automatisch korrigieren"
+    pub deskew: bool,
+    /// The current page is shown straightened, `None` if it has no measured tilt
+    pub deskew_page: Option<bool>,
+    /// "Doppelseiten teilen"
+    pub split_spreads: bool,
+    /// Active item of the Seitenabstand group, `None` for values set in the registry
+    pub page_spacing_item: Option<u32>,
+    /// "Übersichtsleiste"
+    pub minimap: bool,
+    /// The document has double pages shown split, whose split can be moved
+    pub split_position: bool,
+    /// "16-Bit-Graustufen erhalten" setting
+    pub keep_high_bit_depth: bool,
+    /// The document has 16-bit grayscale pages whose window can be reset
+    pub window_level: bool,
+    /// Active item of the Mausrad group (IDM_WHEEL_SCROLL..=IDM_WHEEL_PAGE)
+    pub wheel_item: u32,
+    /// "Richtung umkehren"
+    pub invert_wheel: bool,
+    /// Active item of the Renderqualität group, `None` while --pdf-dpi applies
+    pub render_quality_item: Option<u32>,
+    /// "Standarddrehung für neue Dokumente" of images and PDF documents, in degrees
+    pub default_rotation: (i32, i32),
+    /// "Differenz beim Blinkvergleich"
+    pub blink_difference: bool,
+    /// Steps for "Ansicht rückgängig" / "Ansicht wiederherstellen"
+    pub undo_view: bool,
+    pub redo_view: bool,
+    /// "Seiten extrahieren" is possible: a PDF is open and no watermark is required
+    pub extract_pages: bool,
+    /// "Anmerkungen" is possible: a PDF is open
+    pub annotations: bool,
+    /// "Ausschnitt anheften" is possible: a region is selected
+    pub pin_selection: bool,
+}
 
 pub struct ContextMenu {
     menu: HMENU,
     pending_command: Arc<Mutex<Option<u32>>>,
     bitmaps: Vec<HBITMAP>, // Keep bitmaps alive
+    /// "Zoom" submenu and its position
+    zoom_menu: (HMENU, u32),
+    view_menu: HMENU,
+    page_spacing_menu: HMENU,
+    wheel_menu: HMENU,
+    render_quality_menu: HMENU,
+    default_rotation_menu: HMENU,
+    /// "Ordner-Stapelverarbeitung" submenu, removed in view-only mode
+    batch_menu: HMENU,
+    /// "Als Hintergrundbild" festlegen" submenu and its position (absent in restricted/view-only mode)
+    wallpaper_menu: Option<(HMENU, u32)>,
 }
 
 impl ContextMenu {
-    pub fn new() -> Result<Self> {
+    /// `reopen_last_file` is the current "Letzte Datei beim Start öffnen" setting,
+    /// `None` hides the item (restricted mode never reopens files).
+    /// `hover_toolbar`, `keep_view`, `inbox_sound`, `include_subfolders` and `escape_exits` are
+    /// the current "Schnellaktionen auf Seiten anzeigen", "Ansicht beibehalten", "Ton bei neuem
+    /// Dokument", "Unterordner einbeziehen" and "Esc beendet das Programm" settings.
+    pub fn new(
+        allow_wallpaper: bool,
+        reopen_last_file: Option<bool>,
+        hover_toolbar: bool,
+        keep_view: bool,
+        inbox_sound: bool,
+        include_subfolders: bool,
+        escape_exits: bool,
+    ) -> Result<Self> {
         unsafe {
             let menu = CreatePopupMenu()?;
             let mut bitmaps = Vec::new();
@@ -34,11 +226,138 @@ impl ContextMenu {
             let bmp_rotate_left = Self::load_png_as_bitmap(icons::ICON_ROTATE_LEFT)?;
             let bmp_rotate_right = Self::load_png_as_bitmap(icons::ICON_ROTATE_RIGHT)?;
 
-            // Add menu items with icons
-            Self::append_menu_item_with_icon(menu, IDM_FIT_TO_PAGE, w!("Fit to Page"), bmp_fit);
+            // Zoom: presets, custom value and fit modes
+            let zoom_menu = CreatePopupMenu()?;
+            for (i, percent) in ZOOM_PRESETS.iter().enumerate() {
+                let label = HSTRING::from(format!("{} %", percent));
+                let _ = AppendMenuW(zoom_menu, MF_STRING, (IDM_ZOOM_25 + i as u32) as usize, &label);
+            }
+            let _ = AppendMenuW(zoom_menu, MF_STRING, IDM_ZOOM_CUSTOM as usize, w!("Anpassen..."));
+            let _ = AppendMenuW(zoom_menu, MF_SEPARATOR, 0, None);
+            let _ = AppendMenuW(zoom_menu, MF_STRING, IDM_FIT_WIDTH as usize, w!("An Breite"));
+            let _ = AppendMenuW(zoom_menu, MF_STRING, IDM_FIT_HEIGHT as usize, w!("An Höhe"));
+            Self::append_menu_item_with_icon(zoom_menu, IDM_FIT_TO_PAGE, w!("An Seite"), bmp_fit);
+            let _ = AppendMenuW(menu, MF_POPUP, zoom_menu.0 as usize, w!("Zoom"));
+            let zoom_position = GetMenuItemCount(menu) as u32 - 1;
+
+            // Ansicht: page layout, fullscreen and navigation options
+            let view_menu = CreatePopupMenu()?;
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_VIEW_CONTINUOUS as usize, w!("Fortlaufend"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_VIEW_SINGLE_PAGE as usize, w!("Einzelseite"));
+            let page_spacing_menu = CreatePopupMenu()?;
+            let _ = AppendMenuW(page_spacing_menu, MF_STRING, IDM_PAGE_SPACING_NARROW as usize, w!("Eng"));
+            let _ = AppendMenuW(page_spacing_menu, MF_STRING, IDM_PAGE_SPACING_NORMAL as usize, w!("Normal"));
+            let _ = AppendMenuW(page_spacing_menu, MF_STRING, IDM_PAGE_SPACING_WIDE as usize, w!("Weit"));
+            let _ = AppendMenuW(view_menu, MF_POPUP, page_spacing_menu.0 as usize, w!("Seitenabstand"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_MINIMAP as usize, w!("Übersichtsleiste"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_AUTO_CROP as usize, w!("Ränder automatisch zuschneiden"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_DESKEW as usize, w!("Schräglage automatisch korrigieren"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_DESKEW_PAGE as usize, w!("Schräglage dieser Seite korrigieren"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_SPLIT_SPREADS as usize, w!("Doppelseiten teilen"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_SPLIT_POSITION as usize, w!("Teilung anpassen..."));
+            let label = HSTRING::from(shortcuts::menu_label("Gehe zu Seite...", shortcuts::GO_TO_PAGE));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_GO_TO_PAGE as usize, &label);
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            let label = HSTRING::from(shortcuts::menu_label("Vollbild", shortcuts::FULLSCREEN));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_FULLSCREEN as usize, &label);
+            let label = HSTRING::from(shortcuts::menu_label("Immer im Vordergrund", shortcuts::TOPMOST));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_TOPMOST as usize, &label);
+            let label = HSTRING::from(shortcuts::menu_label("Kompaktmodus", shortcuts::COMPACT));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_COMPACT as usize, &label);
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_MENU_BAR as usize, w!("Menüleiste anzeigen"));
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            let flags = if keep_view { MF_STRING | MF_CHECKED } else { MF_STRING };
+            let _ = AppendMenuW(view_menu, flags, IDM_KEEP_VIEW as usize, w!("Ansicht beibehalten"));
+            let flags = if include_subfolders { MF_STRING | MF_CHECKED } else { MF_STRING };
+            let _ = AppendMenuW(view_menu, flags, IDM_INCLUDE_SUBFOLDERS as usize, w!("Unterordner einbeziehen"));
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_HIGH_BIT_DEPTH as usize, w!("16-Bit-Graustufen erhalten"));
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_RESET_WINDOW_LEVEL as usize, w!("Fensterung zurücksetzen"));
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_BLINK_DIFFERENCE as usize, w!("Differenz beim Blinkvergleich"));
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            let _ = AppendMenuW(view_menu, MF_STRING, IDM_ANNOTATIONS as usize, w!("Anmerkungen..."));
+            let _ = AppendMenuW(menu, MF_POPUP, view_menu.0 as usize, w!("Ansicht"));
+
+            // Drehen
             let _ = AppendMenuW(menu, MF_SEPARATOR, 0, None);
-            Self::append_menu_item_with_icon(menu, IDM_ROTATE_LEFT, w!("Rotate Left"), bmp_rotate_left);
-            Self::append_menu_item_with_icon(menu, IDM_ROTATE_RIGHT, w!("Rotate Right"), bmp_rotate_right);
+            Self::append_menu_item_with_icon(menu, IDM_ROTATE_LEFT, w!("Nach links drehen"), bmp_rotate_left);
+            Self::append_menu_item_with_icon(menu, IDM_ROTATE_RIGHT, w!("Nach rechts drehen"), bmp_rotate_right);
+            let label = HSTRING::from(shortcuts::menu_label("Um 180° drehen", shortcuts::ROTATE_180));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_ROTATE_180 as usize, &label);
+
+            // Document commands (Export/Print removed by set_capabilities in view-only mode)
+            let _ = AppendMenuW(menu, MF_SEPARATOR, 0, None);
+            let label = HSTRING::from(shortcuts::menu_label("Exportieren...", shortcuts::EXPORT));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_EXPORT as usize, &label);
+            let _ = AppendMenuW(menu, MF_STRING, IDM_CONTACT_SHEET as usize, w!("Übersichtsbild exportieren..."));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_EXTRACT_PAGES as usize, w!("Seiten extrahieren..."));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_COPY_PAGE_FILES as usize, w!("Seiten als Dateien kopieren..."));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_OCR as usize, w!("Text erkennen (OCR)..."));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_PIN_SELECTION as usize, w!("Ausschnitt anheften"));
+            let label = HSTRING::from(shortcuts::menu_label("Drucken...", shortcuts::PRINT));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_PRINT as usize, &label);
+            let _ = AppendMenuW(menu, MF_STRING, IDM_PRINT_PDF as usize, w!("Als PDF drucken..."));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_PROPERTIES as usize, w!("Eigenschaften"));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_FOLDER_STATS as usize, w!("Ordnerstatistik..."));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_FOLDER_SEARCH as usize, w!("Ordner durchsuchen..."));
+            let _ = AppendMenuW(menu, MF_STRING, IDM_CLEAR_TEXT_INDEX as usize, w!("Suchindex löschen"));
+            let batch_menu = CreatePopupMenu()?;
+            let _ = AppendMenuW(batch_menu, MF_STRING, IDM_BATCH_ROTATE as usize, w!("Alle drehen..."));
+            let _ = AppendMenuW(menu, MF_POPUP, batch_menu.0 as usize, w!("Ordner-Stapelverarbeitung"));
+
+            // Wallpaper submenu, filled with the current monitors in `show`
+            let wallpaper_menu = if allow_wallpaper {
+                let submenu = CreatePopupMenu()?;
+                let _ = AppendMenuW(menu, MF_SEPARATOR, 0, None);
+                let _ = AppendMenuW(menu, MF_POPUP, submenu.0 as usize, w!("Als Hintergrundbild festlegen"));
+                Some((submenu, GetMenuItemCount(menu) as u32 - 1))
+            } else {
+                None
+            };
+
+            // Scan inbox mode (checked while a folder is watched)
+            let _ = AppendMenuW(menu, MF_SEPARATOR, 0, None);
+            let _ = AppendMenuW(menu, MF_STRING, IDM_INBOX as usize, w!("Eingangsordner überwachen..."));
+            let flags = if inbox_sound { MF_STRING | MF_CHECKED } else { MF_STRING };
+            let _ = AppendMenuW(menu, flags, IDM_INBOX_SOUND as usize, w!("Ton bei neuem Dokument"));
+
+            // Settings
+            let _ = AppendMenuW(menu, MF_SEPARATOR, 0, None);
+            let flags = if hover_toolbar { MF_STRING | MF_CHECKED } else { MF_STRING };
+            let _ = AppendMenuW(menu, flags, IDM_HOVER_TOOLBAR as usize, w!("Schnellaktionen auf Seiten anzeigen"));
+            let flags = if escape_exits { MF_STRING | MF_CHECKED } else { MF_STRING };
+            let _ = AppendMenuW(menu, flags, IDM_ESCAPE_EXITS as usize, w!("Esc beendet das Programm"));
+            let wheel_menu = CreatePopupMenu()?;
+            let _ = AppendMenuW(wheel_menu, MF_STRING, IDM_WHEEL_SCROLL as usize, w!("Bildlauf"));
+            let _ = AppendMenuW(wheel_menu, MF_STRING, IDM_WHEEL_ZOOM as usize, w!("Zoom"));
+            let _ = AppendMenuW(wheel_menu, MF_STRING, IDM_WHEEL_PAGE as usize, w!("Seitenwechsel"));
+            let _ = AppendMenuW(wheel_menu, MF_SEPARATOR, 0, None);
+            let _ = AppendMenuW(wheel_menu, MF_STRING, IDM_WHEEL_INVERT as usize, w!("Richtung umkehren"));
+            let _ = AppendMenuW(menu, MF_POPUP, wheel_menu.0 as usize, w!("Mausrad"));
+            let render_quality_menu = CreatePopupMenu()?;
+            let _ = AppendMenuW(render_quality_menu, MF_STRING, IDM_RENDER_FAST as usize, w!("Schnell"));
+            let _ = AppendMenuW(render_quality_menu, MF_STRING, IDM_RENDER_STANDARD as usize, w!("Standard"));
+            let _ = AppendMenuW(render_quality_menu, MF_STRING, IDM_RENDER_HIGH as usize, w!("Hoch"));
+            let _ = AppendMenuW(render_quality_menu, MF_STRING, IDM_RENDER_SCREEN as usize, w!("An Bildschirm anpassen"));
+            let _ = AppendMenuW(menu, MF_POPUP, render_quality_menu.0 as usize, w!("Renderqualität (PDF)"));
+            let default_rotation_menu = CreatePopupMenu()?;
+            for (pdf, title) in [(false, "Bilder"), (true, "PDF-Dokumente")] {
+                if pdf {
+                    let _ = AppendMenuW(default_rotation_menu, MF_SEPARATOR, 0, None);
+                }
+                let _ = AppendMenuW(default_rotation_menu, MF_STRING | MF_GRAYED, 0, &HSTRING::from(title));
+                for rotation in [0, 90, 180, 270] {
+                    let label = HSTRING::from(format!("{}°", rotation));
+                    let id = default_rotation_item(pdf, rotation);
+                    let _ = AppendMenuW(default_rotation_menu, MF_STRING, id as usize, &label);
+                }
+            }
+            let _ = AppendMenuW(menu, MF_POPUP, default_rotation_menu.0 as usize, w!("Standarddrehung für neue Dokumente"));
+            if let Some(checked) = reopen_last_file {
+                let flags = if checked { MF_STRING | MF_CHECKED } else { MF_STRING };
+                let _ = AppendMenuW(menu, flags, IDM_REOPEN_LAST_FILE as usize, w!("Letzte Datei beim Start öffnen"));
+            }
 
             // Store bitmaps to keep them alive
             bitmaps.push(bmp_fit);
@@ -49,6 +368,14 @@ impl ContextMenu {
                 menu,
                 pending_command: Arc::new(Mutex::new(None)),
                 bitmaps,
+                zoom_menu: (zoom_menu, zoom_position),
+                view_menu,
+                page_spacing_menu,
+                wheel_menu,
+                render_quality_menu,
+                default_rotation_menu,
+                batch_menu,
+                wallpaper_menu,
             })
         }
     }
@@ -67,6 +394,43 @@ impl ContextMenu {
         }
     }
 
+    /// Rebuilds the wallpaper submenu: positions directly for a single monitor,
+    /// otherwise one submenu each for all monitors and every single monitor
+    fn fill_wallpaper_menu(submenu: HMENU) {
+        unsafe {
+            while GetMenuItemCount(submenu) > 0 {
+                let _ = DeleteMenu(submenu, 0, MF_BYPOSITION);
+            }
+
+            let append_positions = |menu: HMENU, target: usize| {
+                for (i, position) in WallpaperPosition::ALL.into_iter().enumerate() {
+                    let id = IDM_WALLPAPER + (target * 3 + i) as u32;
+                    let _ = AppendMenuW(menu, MF_STRING, id as usize, position.label());
+                }
+            };
+
+            let monitors = crate::wallpaper::monitors().unwrap_or_default();
+            if monitors.len() <= 1 {
+                append_positions(submenu, 0);
+                return;
+            }
+
+            let targets = std::iter::once("Alle Bildschirme".to_string()).chain(
+                monitors
+                    .iter()
+                    .take(MAX_WALLPAPER_MONITORS)
+                    .enumerate()
+                    .map(|(i, m)| format!("Bildschirm {} ({}x{})", i + 1, m.width, m.height)),
+            );
+            for (target, label) in targets.enumerate() {
+                if let Ok(target_menu) = CreatePopupMenu() {
+                    append_positions(target_menu, target);
+                    let _ = AppendMenuW(submenu, MF_POPUP, target_menu.0 as usize, &HSTRING::from(label));
+                }
+            }
+        }
+    }
+
     fn load_png_as_bitmap(data: &[u8]) -> Result<HBITMAP> {
         unsafe {
             let factory: IWICImagingFactory =
@@ -121,6 +485,132 @@ impl ContextMenu {
         }
     }
 
+    /// Export/Print permissions; disallowed items are removed
+    pub fn set_capabilities(&self, export_allowed: bool, print_allowed: bool) {
+        unsafe {
+            if !export_allowed {
+                let _ = DeleteMenu(self.menu, IDM_EXPORT, MF_BYCOMMAND);
+                let _ = DeleteMenu(self.menu, IDM_CONTACT_SHEET, MF_BYCOMMAND);
+                let _ = DeleteMenu(self.menu, IDM_EXTRACT_PAGES, MF_BYCOMMAND);
+                let _ = DeleteMenu(self.menu, IDM_COPY_PAGE_FILES, MF_BYCOMMAND);
+                let _ = DeleteMenu(self.menu, IDM_OCR, MF_BYCOMMAND);
+                // Keeps the text of OCR and PDFs, like OCR
+                let _ = DeleteMenu(self.menu, IDM_FOLDER_SEARCH, MF_BYCOMMAND);
+                let _ = DeleteMenu(self.menu, IDM_CLEAR_TEXT_INDEX, MF_BYCOMMAND);
+                // Submenus have no command ID: find it by position
+                let position = (0..GetMenuItemCount(self.menu)).find(|&i| GetSubMenu(self.menu, i) == self.batch_menu);
+                if let Some(position) = position {
+                    let _ = DeleteMenu(self.menu, position as u32, MF_BYPOSITION);
+                }
+            }
+            if !print_allowed {
+                let _ = DeleteMenu(self.menu, IDM_PRINT, MF_BYCOMMAND);
+            }
+            // Writes a file, so it is an export as well
+            if !print_allowed || !export_allowed {
+                let _ = DeleteMenu(self.menu, IDM_PRINT_PDF, MF_BYCOMMAND);
+            }
+        }
+    }
+
+    /// Updates the radio and check marks of the submenus; call before `show`
+    pub fn refresh(&self, state: &MenuState) {
+        unsafe {
+            let (zoom_menu, _) = self.zoom_menu;
+            match state.zoom_item {
+                Some(item) => {
+                    let _ = CheckMenuRadioItem(zoom_menu, IDM_ZOOM_25, IDM_FIT_TO_PAGE, item, MF_BYCOMMAND.0);
+                }
+                None => {
+                    for id in IDM_ZOOM_25..=IDM_FIT_TO_PAGE {
+                        CheckMenuItem(zoom_menu, id, MF_UNCHECKED.0);
+                    }
+                }
+            }
+
+            // The layout choice only matters with several pages
+            let flag = if state.continuous.is_some() { MF_ENABLED } else { MF_GRAYED };
+            let _ = EnableMenuItem(self.view_menu, IDM_VIEW_CONTINUOUS, flag);
+            let _ = EnableMenuItem(self.view_menu, IDM_VIEW_SINGLE_PAGE, flag);
+            let _ = EnableMenuItem(self.view_menu, IDM_GO_TO_PAGE, flag);
+            match state.continuous {
+                Some(continuous) => {
+                    let item = if continuous { IDM_VIEW_CONTINUOUS } else { IDM_VIEW_SINGLE_PAGE };
+                    let _ = CheckMenuRadioItem(self.view_menu, IDM_VIEW_CONTINUOUS, IDM_VIEW_SINGLE_PAGE, item, MF_BYCOMMAND.0);
+                }
+                None => {
+                    CheckMenuItem(self.view_menu, IDM_VIEW_CONTINUOUS, MF_UNCHECKED.0);
+                    CheckMenuItem(self.view_menu, IDM_VIEW_SINGLE_PAGE, MF_UNCHECKED.0);
+                }
+            }
+            let flag = if state.fullscreen { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_FULLSCREEN, flag.0);
+            let flag = if state.topmost { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_TOPMOST, flag.0);
+            let flag = if state.compact { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_COMPACT, flag.0);
+            let flag = if state.auto_crop { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_AUTO_CROP, flag.0);
+            let flag = if state.deskew { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_DESKEW, flag.0);
+            let flag = if state.deskew_page == Some(true) { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_DESKEW_PAGE, flag.0);
+            let flag = if state.deskew_page.is_some() { MF_ENABLED } else { MF_GRAYED };
+            let _ = EnableMenuItem(self.view_menu, IDM_DESKEW_PAGE, flag);
+            let flag = if state.split_spreads { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_SPLIT_SPREADS, flag.0);
+            let flag = if state.minimap { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_MINIMAP, flag.0);
+            let flag = if state.split_position { MF_ENABLED } else { MF_GRAYED };
+            let _ = EnableMenuItem(self.view_menu, IDM_SPLIT_POSITION, flag);
+            let flag = if state.keep_high_bit_depth { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_HIGH_BIT_DEPTH, flag.0);
+            let flag = if state.window_level { MF_ENABLED } else { MF_GRAYED };
+            let _ = EnableMenuItem(self.view_menu, IDM_RESET_WINDOW_LEVEL, flag);
+            let flag = if state.extract_pages { MF_ENABLED } else { MF_GRAYED };
+            let _ = EnableMenuItem(self.menu, IDM_EXTRACT_PAGES, flag);
+            let flag = if state.annotations { MF_ENABLED } else { MF_GRAYED };
+            let _ = EnableMenuItem(self.view_menu, IDM_ANNOTATIONS, flag);
+            let flag = if state.pin_selection { MF_ENABLED } else { MF_GRAYED };
+            let _ = EnableMenuItem(self.menu, IDM_PIN_SELECTION, flag);
+            let flag = if state.blink_difference { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.view_menu, IDM_BLINK_DIFFERENCE, flag.0);
+
+            match state.page_spacing_item {
+                Some(item) => {
+                    let _ = CheckMenuRadioItem(self.page_spacing_menu, IDM_PAGE_SPACING_NARROW, IDM_PAGE_SPACING_WIDE, item, MF_BYCOMMAND.0);
+                }
+                None => {
+                    for id in IDM_PAGE_SPACING_NARROW..=IDM_PAGE_SPACING_WIDE {
+                        CheckMenuItem(self.page_spacing_menu, id, MF_UNCHECKED.0);
+                    }
+                }
+            }
+
+            let _ = CheckMenuRadioItem(self.wheel_menu, IDM_WHEEL_SCROLL, IDM_WHEEL_PAGE, state.wheel_item, MF_BYCOMMAND.0);
+            let flag = if state.invert_wheel { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.wheel_menu, IDM_WHEEL_INVERT, flag.0);
+
+            match state.render_quality_item {
+                Some(item) => {
+                    let _ = CheckMenuRadioItem(self.render_quality_menu, IDM_RENDER_FAST, IDM_RENDER_SCREEN, item, MF_BYCOMMAND.0);
+                }
+                None => {
+                    for id in IDM_RENDER_FAST..=IDM_RENDER_SCREEN {
+                        CheckMenuItem(self.render_quality_menu, id, MF_UNCHECKED.0);
+                    }
+                }
+            }
+
+            let (images, pdf_documents) = state.default_rotation;
+            for (pdf, rotation) in [(false, images), (true, pdf_documents)] {
+                let first = default_rotation_item(pdf, 0);
+                let item = default_rotation_item(pdf, rotation);
+                let _ = CheckMenuRadioItem(self.default_rotation_menu, first, first + 3, item, MF_BYCOMMAND.0);
+            }
+        }
+    }
+
     pub fn show(&self, hwnd: HWND, x: i32, y: i32) {
         unsafe {
             // If coordinates are -1, -1, use cursor position
@@ -132,6 +622,10 @@ impl ContextMenu {
                 (x, y)
             };
 
+            if let Some((submenu, _)) = self.wallpaper_menu {
+                Self::fill_wallpaper_menu(submenu);
+            }
+
             let cmd = TrackPopupMenu(
                 self.menu,
                 TPM_RETURNCMD | TPM_RIGHTBUTTON,
@@ -156,12 +650,26 @@ impl ContextMenu {
         self.pending_command.lock().take()
     }
 
+    /// Check mark of a setting item (IDM_REOPEN_LAST_FILE, IDM_HOVER_TOOLBAR, IDM_KEEP_VIEW,
+    /// IDM_INBOX, IDM_INBOX_SOUND, IDM_INCLUDE_SUBFOLDERS, IDM_ESCAPE_EXITS, IDM_MENU_BAR)
+    pub fn set_checked(&self, id: u32, checked: bool) {
+        unsafe {
+            let flag = if checked { MF_CHECKED } else { MF_UNCHECKED };
+            CheckMenuItem(self.menu, id, flag.0);
+        }
+    }
+
     pub fn set_document_loaded(&self, loaded: bool) {
         unsafe {
             let flag = if loaded { MF_ENABLED } else { MF_GRAYED };
-            let _ = EnableMenuItem(self.menu, IDM_FIT_TO_PAGE, flag);
-            let _ = EnableMenuItem(self.menu, IDM_ROTATE_LEFT, flag);
-            let _ = EnableMenuItem(self.menu, IDM_ROTATE_RIGHT, flag);
+            let (_, zoom_position) = self.zoom_menu;
+            let _ = EnableMenuItem(self.menu, zoom_position, flag | MF_BYPOSITION);
+            for id in [IDM_ROTATE_LEFT, IDM_ROTATE_RIGHT, IDM_ROTATE_180, IDM_EXPORT, IDM_CONTACT_SHEET, IDM_COPY_PAGE_FILES, IDM_PRINT, IDM_PRINT_PDF, IDM_PROPERTIES, IDM_FOLDER_STATS, IDM_FOLDER_SEARCH, IDM_BATCH_ROTATE, IDM_OCR] {
+                let _ = EnableMenuItem(self.menu, id, flag);
+            }
+            if let Some((_, position)) = self.wallpaper_menu {
+                let _ = EnableMenuItem(self.menu, position, flag | MF_BYPOSITION);
+            }
         }
     }
 }
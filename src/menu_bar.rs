@@ -0,0 +1,260 @@
+//! Classic menu bar (Datei / Ansicht / Hilfe), an optional alternative to the toolbar
+//!
+//! Off by default and switched with "Menüleiste anzeigen". Items use the command IDs of
+//! the toolbar and the context menu, so WM_COMMAND reaches the same handlers. Enabled and
+//! checked states are set in `refresh` on WM_INITMENUPOPUP, right before a menu opens.
+
+use crate::menu::*;
+use crate::shortcuts;
+use std::cell::Cell;
+use windows::{
+    core::*,
+    Win32::{Foundation::*, UI::WindowsAndMessaging::*},
+};
+
+// Toolbar command IDs reused by the menu bar
+const IDM_OPEN: u32 = 100;
+const IDM_INFO: u32 = 106;
+const IDM_EXIT: u32 = 107;
+/// "Zuletzt geöffnet": IDM_RECENT_FIRST + index into `settings::recent_files`
+pub const IDM_RECENT_FIRST: u32 = 280;
+pub const MAX_RECENT: usize = 8;
+pub const IDM_SHORTCUTS: u32 = 290;
+
+pub struct MenuBar {
+    menu: HMENU,
+    recent_menu: HMENU,
+    zoom_menu: HMENU,
+    view_menu: HMENU,
+    page_spacing_menu: HMENU,
+    /// Attached to the window; an attached menu is destroyed together with it
+    attached: Cell<bool>,
+}
+
+impl MenuBar {
+    /// Export and Print are left out when the capability policy disallows them
+    pub fn new(export_allowed: bool, print_allowed: bool) -> Result<Self> {
+        unsafe {
+            let menu = CreateMenu()?;
+
+            // Datei
+            let file_menu = CreatePopupMenu()?;
+            let recent_menu = CreatePopupMenu()?;
+            append(file_menu, IDM_OPEN, &shortcuts::menu_label("Ö&ffnen...", shortcuts::OPEN));
+            let _ = AppendMenuW(file_menu, MF_POPUP, recent_menu.0 as usize, w!("&Zuletzt geöffnet"));
+            let _ = AppendMenuW(file_menu, MF_SEPARATOR, 0, None);
+            if export_allowed {
+                append(file_menu, IDM_EXPORT, &shortcuts::menu_label("&Exportieren...", shortcuts::EXPORT));
+                append(file_menu, IDM_CONTACT_SHEET, "Ü&bersichtsbild exportieren...");
+                append(file_menu, IDM_EXTRACT_PAGES, "Seiten e&xtrahieren...");
+                append(file_menu, IDM_COPY_PAGE_FILES, "Seiten als Dateien &kopieren...");
+                append(file_menu, IDM_OCR, "&Text erkennen (OCR)...");
+            }
+            if print_allowed {
+                append(file_menu, IDM_PRINT, &shortcuts::menu_label("&Drucken...", shortcuts::PRINT));
+                if export_allowed {
+                    append(file_menu, IDM_PRINT_PDF, "Als &PDF drucken...");
+                }
+            }
+            append(file_menu, IDM_PROPERTIES, "E&igenschaften");
+            append(file_menu, IDM_FOLDER_STATS, "&Ordnerstatistik...");
+            if export_allowed {
+                append(file_menu, IDM_FOLDER_SEARCH, "Ordner d&urchsuchen...");
+                append(file_menu, IDM_CLEAR_TEXT_INDEX, "Suchinde&x löschen");
+                let batch_menu = CreatePopupMenu()?;
+                append(batch_menu, IDM_BATCH_ROTATE, "Alle &drehen...");
+                let _ = AppendMenuW(file_menu, MF_POPUP, batch_menu.0 as usize, w!("Ordner-&Stapelverarbeitung"));
+            }
+            let _ = AppendMenuW(file_menu, MF_SEPARATOR, 0, None);
+            append(file_menu, IDM_EXIT, "&Beenden");
+            let _ = AppendMenuW(menu, MF_POPUP, file_menu.0 as usize, w!("&Datei"));
+
+            // Ansicht
+            let view_menu = CreatePopupMenu()?;
+            let zoom_menu = CreatePopupMenu()?;
+            for id in IDM_ZOOM_25..IDM_ZOOM_CUSTOM {
+                if let Some(zoom) = zoom_preset(id) {
+                    let label = format!("{} %", (zoom * 100.0).round());
+                    if zoom == 1.0 {
+                        append(zoom_menu, id, &shortcuts::menu_label(&label, shortcuts::ZOOM_100));
+                    } else {
+                        append(zoom_menu, id, &label);
+                    }
+                }
+            }
+            append(zoom_menu, IDM_ZOOM_CUSTOM, "&Anpassen...");
+            let _ = AppendMenuW(zoom_menu, MF_SEPARATOR, 0, None);
+            append(zoom_menu, IDM_FIT_WIDTH, "An &Breite");
+            append(zoom_menu, IDM_FIT_HEIGHT, "An &Höhe");
+            append(zoom_menu, IDM_FIT_TO_PAGE, &shortcuts::menu_label("An &Seite", shortcuts::FIT_TO_PAGE));
+            let _ = AppendMenuW(view_menu, MF_POPUP, zoom_menu.0 as usize, w!("&Zoom"));
+            append(view_menu, IDM_UNDO_VIEW, &shortcuts::menu_label("Ansicht rückgän&gig", shortcuts::UNDO_VIEW));
+            append(view_menu, IDM_REDO_VIEW, &shortcuts::menu_label("Ansicht wieder&herstellen", shortcuts::REDO_VIEW));
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            append(view_menu, IDM_ROTATE_LEFT, &shortcuts::menu_label("Nach &links drehen", shortcuts::ROTATE_LEFT));
+            append(view_menu, IDM_ROTATE_RIGHT, &shortcuts::menu_label("Nach &rechts drehen", shortcuts::ROTATE_RIGHT));
+            append(view_menu, IDM_ROTATE_180, &shortcuts::menu_label("&Um 180° drehen", shortcuts::ROTATE_180));
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            append(view_menu, IDM_VIEW_CONTINUOUS, "&Fortlaufend");
+            append(view_menu, IDM_VIEW_SINGLE_PAGE, "&Einzelseite");
+            let page_spacing_menu = CreatePopupMenu()?;
+            append(page_spacing_menu, IDM_PAGE_SPACING_NARROW, "&Eng");
+            append(page_spacing_menu, IDM_PAGE_SPACING_NORMAL, "&Normal");
+            append(page_spacing_menu, IDM_PAGE_SPACING_WIDE, "&Weit");
+            let _ = AppendMenuW(view_menu, MF_POPUP, page_spacing_menu.0 as usize, w!("Seiten&abstand"));
+            append(view_menu, IDM_MINIMAP, "Übersichts&leiste");
+            append(view_menu, IDM_AUTO_CROP, "Ränder au&tomatisch zuschneiden");
+            append(view_menu, IDM_DESKEW, "Schräglage aut&omatisch korrigieren");
+            append(view_menu, IDM_DESKEW_PAGE, "&Schräglage dieser Seite korrigieren");
+            append(view_menu, IDM_SPLIT_SPREADS, "&Doppelseiten teilen");
+            append(view_menu, IDM_SPLIT_POSITION, "Teilung an&passen...");
+            append(view_menu, IDM_GO_TO_PAGE, &shortcuts::menu_label("Gehe &zu Seite...", shortcuts::GO_TO_PAGE));
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            append(view_menu, IDM_FULLSCREEN, &shortcuts::menu_label("&Vollbild", shortcuts::FULLSCREEN));
+            append(view_menu, IDM_TOPMOST, &shortcuts::menu_label("&Immer im Vordergrund", shortcuts::TOPMOST));
+            append(view_menu, IDM_COMPACT, &shortcuts::menu_label("&Kompaktmodus", shortcuts::COMPACT));
+            append(view_menu, IDM_MENU_BAR, "&Menüleiste anzeigen");
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            append(view_menu, IDM_HIGH_BIT_DEPTH, "16-&Bit-Graustufen erhalten");
+            append(view_menu, IDM_RESET_WINDOW_LEVEL, "Fensterung &zurücksetzen");
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            append(view_menu, IDM_BLINK_DIFFERENCE, "&Differenz beim Blinkvergleich");
+            let _ = AppendMenuW(view_menu, MF_SEPARATOR, 0, None);
+            append(view_menu, IDM_ANNOTATIONS, "A&nmerkungen...");
+            append(view_menu, IDM_PIN_SELECTION, "Ausschnitt an&heften");
+            let _ = AppendMenuW(menu, MF_POPUP, view_menu.0 as usize, w!("&Ansicht"));
+
+            // Hilfe
+            let help_menu = CreatePopupMenu()?;
+            append(help_menu, IDM_SHORTCUTS, &shortcuts::menu_label("&Tastenkürzel", shortcuts::HELP));
+            let _ = AppendMenuW(help_menu, MF_SEPARATOR, 0, None);
+            append(help_menu, IDM_INFO, "&Info über SimpliView");
+            let _ = AppendMenuW(menu, MF_POPUP, help_menu.0 as usize, w!("&Hilfe"));
+
+            Ok(Self { menu, recent_menu, zoom_menu, view_menu, page_spacing_menu, attached: Cell::new(false) })
+        }
+    }
+
+    /// Attaches or removes the menu bar; the client area changes height and WM_SIZE follows
+    pub fn set_visible(&self, hwnd: HWND, visible: bool) {
+        if visible == self.attached.get() {
+            return;
+        }
+        unsafe {
+            let _ = SetMenu(hwnd, if visible { self.menu } else { HMENU::default() });
+            let _ = DrawMenuBar(hwnd);
+        }
+        self.attached.set(visible);
+    }
+
+    /// Updates all items from the current view; call on WM_INITMENUPOPUP.
+    /// `open_enabled` is false while opening other files is disabled (file from the
+    /// command line), `recent` lists the files for "Zuletzt geöffnet".
+    pub fn refresh(&self, state: &MenuState, document_loaded: bool, open_enabled: bool, recent: &[String]) {
+        unsafe {
+            let enable = |id: u32, enabled: bool| {
+                let _ = EnableMenuItem(self.menu, id, if enabled { MF_ENABLED } else { MF_GRAYED });
+            };
+            enable(IDM_OPEN, open_enabled);
+            for id in [IDM_EXPORT, IDM_CONTACT_SHEET, IDM_COPY_PAGE_FILES, IDM_PRINT, IDM_PRINT_PDF, IDM_PROPERTIES, IDM_FOLDER_STATS, IDM_FOLDER_SEARCH, IDM_BATCH_ROTATE, IDM_OCR, IDM_ROTATE_LEFT, IDM_ROTATE_RIGHT, IDM_ROTATE_180, IDM_ZOOM_CUSTOM] {
+                enable(id, document_loaded);
+            }
+            for id in IDM_ZOOM_25..=IDM_FIT_TO_PAGE {
+                enable(id, document_loaded);
+            }
+
+            match state.zoom_item {
+                Some(item) => {
+                    let _ = CheckMenuRadioItem(self.zoom_menu, IDM_ZOOM_25, IDM_FIT_TO_PAGE, item, MF_BYCOMMAND.0);
+                }
+                None => {
+                    for id in IDM_ZOOM_25..=IDM_FIT_TO_PAGE {
+                        CheckMenuItem(self.zoom_menu, id, MF_UNCHECKED.0);
+                    }
+                }
+            }
+
+            enable(IDM_VIEW_CONTINUOUS, state.continuous.is_some());
+            enable(IDM_VIEW_SINGLE_PAGE, state.continuous.is_some());
+            enable(IDM_GO_TO_PAGE, state.continuous.is_some());
+            match state.continuous {
+                Some(continuous) => {
+                    let item = if continuous { IDM_VIEW_CONTINUOUS } else { IDM_VIEW_SINGLE_PAGE };
+                    let _ = CheckMenuRadioItem(self.view_menu, IDM_VIEW_CONTINUOUS, IDM_VIEW_SINGLE_PAGE, item, MF_BYCOMMAND.0);
+                }
+                None => {
+                    CheckMenuItem(self.view_menu, IDM_VIEW_CONTINUOUS, MF_UNCHECKED.0);
+                    CheckMenuItem(self.view_menu, IDM_VIEW_SINGLE_PAGE, MF_UNCHECKED.0);
+                }
+            }
+            match state.page_spacing_item {
+                Some(item) => {
+                    let _ = CheckMenuRadioItem(self.page_spacing_menu, IDM_PAGE_SPACING_NARROW, IDM_PAGE_SPACING_WIDE, item, MF_BYCOMMAND.0);
+                }
+                None => {
+                    for id in IDM_PAGE_SPACING_NARROW..=IDM_PAGE_SPACING_WIDE {
+                        CheckMenuItem(self.page_spacing_menu, id, MF_UNCHECKED.0);
+                    }
+                }
+            }
+            let check = |id: u32, checked: bool| {
+                CheckMenuItem(self.view_menu, id, if checked { MF_CHECKED.0 } else { MF_UNCHECKED.0 });
+            };
+            check(IDM_FULLSCREEN, state.fullscreen);
+            check(IDM_TOPMOST, state.topmost);
+            check(IDM_COMPACT, state.compact);
+            check(IDM_AUTO_CROP, state.auto_crop);
+            check(IDM_DESKEW, state.deskew);
+            check(IDM_DESKEW_PAGE, state.deskew_page == Some(true));
+            enable(IDM_DESKEW_PAGE, state.deskew_page.is_some());
+            check(IDM_SPLIT_SPREADS, state.split_spreads);
+            check(IDM_MINIMAP, state.minimap);
+            enable(IDM_SPLIT_POSITION, state.split_position);
+            check(IDM_MENU_BAR, self.attached.get());
+            check(IDM_HIGH_BIT_DEPTH, state.keep_high_bit_depth);
+            enable(IDM_RESET_WINDOW_LEVEL, state.window_level);
+            enable(IDM_EXTRACT_PAGES, state.extract_pages);
+            enable(IDM_ANNOTATIONS, state.annotations);
+            enable(IDM_PIN_SELECTION, state.pin_selection);
+            enable(IDM_UNDO_VIEW, state.undo_view);
+            enable(IDM_REDO_VIEW, state.redo_view);
+            check(IDM_BLINK_DIFFERENCE, state.blink_difference);
+
+            self.fill_recent_menu(recent, open_enabled);
+        }
+    }
+
+    fn fill_recent_menu(&self, recent: &[String], open_enabled: bool) {
+        unsafe {
+            while GetMenuItemCount(self.recent_menu) > 0 {
+                let _ = DeleteMenu(self.recent_menu, 0, MF_BYPOSITION);
+            }
+            if recent.is_empty() {
+                let _ = AppendMenuW(self.recent_menu, MF_STRING | MF_GRAYED, 0, w!("(keine)"));
+                return;
+            }
+            let flags = if open_enabled { MF_STRING } else { MF_STRING | MF_GRAYED };
+            for (i, path) in recent.iter().take(MAX_RECENT).enumerate() {
+                // "&1 C:\Scans\a.pdf"; a literal & in the path must not become a mnemonic
+                let label = format!("&{} {}", i + 1, path.replace('&', "&&"));
+                let _ = AppendMenuW(self.recent_menu, flags, (IDM_RECENT_FIRST as usize) + i, &HSTRING::from(label));
+            }
+        }
+    }
+}
+
+impl Drop for MenuBar {
+    fn drop(&mut self) {
+        if !self.attached.get() {
+            unsafe {
+                let _ = DestroyMenu(self.menu);
+            }
+        }
+    }
+}
+
+fn append(menu: HMENU, id: u32, label: &str) {
+    unsafe {
+        let _ = AppendMenuW(menu, MF_STRING, id as usize, &HSTRING::from(label));
+    }
+}
@@ -0,0 +1,83 @@
+//! "Übersichtsleiste": the whole continuous document as a narrow strip at the right edge
+//!
+//! The pages are scaled down from the `PageLayout` and drawn as light blocks, the part in
+//! view as a highlighted window over them. Clicking or dragging in the strip scrolls the
+//! view there. Documents shorter than `MIN_VIEWPORTS` views get no strip; they are quick
+//! to scroll anyway. App places the strip and hit-tests it; D2DRenderer draws it.
+
+use crate::document::PageLayout;
+use windows::Win32::Graphics::Direct2D::Common::D2D_RECT_F;
+
+/// Width of the strip in view pixels
+const WIDTH: f32 = 48.0;
+/// Space between the strip and the view edges, and around the pages inside the strip
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 4.0;
+/// Documents at least this many views long get a strip
+const MIN_VIEWPORTS: i32 = 2;
+
+/// Strip placed for one layout and view size
+pub struct Minimap {
+    /// Strip in view coordinates
+    pub rect: D2D_RECT_F,
+    /// Strip pixels per layout pixel
+    scale: f32,
+}
+
+impl Minimap {
+    /// Strip for `layout` in a view of the given size, `None` for short documents and
+    /// views too narrow to give up the room
+    pub fn new(layout: &PageLayout, viewport_width: i32, viewport_height: i32) -> Option<Self> {
+        if layout.total_height < viewport_height * MIN_VIEWPORTS || (viewport_width as f32) < WIDTH * 4.0 {
+            return None;
+        }
+        let inner_height = viewport_height as f32 - 2.0 * (MARGIN + PADDING);
+        let inner_width = WIDTH - 2.0 * PADDING;
+        if inner_height <= 0.0 || layout.max_width <= 0 {
+            return None;
+        }
+        let scale = (inner_height / layout.total_height as f32).min(inner_width / layout.max_width as f32);
+        let right = viewport_width as f32 - MARGIN;
+        let rect = D2D_RECT_F {
+            left: right - WIDTH,
+            top: MARGIN,
+            right,
+            bottom: MARGIN + 2.0 * PADDING + layout.total_height as f32 * scale,
+        };
+        Some(Self { rect, scale })
+    }
+
+    /// Block of each page, centered in the strip like the pages in the view
+    pub fn page_rects<'a>(&'a self, layout: &'a PageLayout) -> impl Iterator<Item = D2D_RECT_F> + 'a {
+        let center_x = (self.rect.left + self.rect.right) / 2.0;
+        layout.page_tops.iter().zip(&layout.page_sizes).map(move |(&top, &(width, height))| {
+            let top = self.rect.top + PADDING + top as f32 * self.scale;
+            let half_width = width as f32 * self.scale / 2.0;
+            // Pages of very long documents would vanish below one pixel
+            let height = (height as f32 * self.scale).max(1.0);
+            D2D_RECT_F { left: center_x - half_width, top, right: center_x + half_width, bottom: top + height }
+        })
+    }
+
+    /// Window over the part of the document in view
+    pub fn viewport_rect(&self, scroll_y: i32, viewport_height: i32) -> D2D_RECT_F {
+        let top = self.rect.top + PADDING + scroll_y as f32 * self.scale;
+        D2D_RECT_F {
+            left: self.rect.left + 1.0,
+            top,
+            right: self.rect.right - 1.0,
+            bottom: top + (viewport_height as f32 * self.scale).max(4.0),
+        }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.rect.left && x <= self.rect.right && y >= self.rect.top && y <= self.rect.bottom
+    }
+
+    /// Scroll position that centers the view on the document point under `y` in the
+    /// strip, not yet clamped to the scroll range
+    pub fn scroll_for(&self, y: f32, viewport_height: i32) -> i32 {
+        let document_y = (y - self.rect.top - PADDING) / self.scale;
+        (document_y - viewport_height as f32 / 2.0).round() as i32
+    }
+}
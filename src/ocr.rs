@@ -0,0 +1,206 @@
+//! "Text erkennen (OCR)": text of image-only scans via Windows.Media.Ocr
+//!
+//! The current page is taken as displayed (rotation applied, so the text is upright) and
+//! downscaled to the engine's `MaxImageDimension`. Recognition runs on a worker thread
+//! and the result comes back with WM_APP_OCR: the text line by line, and the word
+//! rectangles mapped back to source pixels of the page, like the selection, so they can
+//! be framed on the page for checking.
+//!
+//! The engine is created for the user's profile languages. Without an OCR pack for them,
+//! the first installed OCR language is used and the result says so; with none at all,
+//! the error names the pack to install.
+
+use crate::wic::ClipboardBitmapData;
+use windows::{
+    core::*,
+    Globalization::Language,
+    Graphics::Imaging::{BitmapPixelFormat, SoftwareBitmap},
+    Media::Ocr::{OcrEngine, OcrResult},
+    Storage::Streams::DataWriter,
+    Win32::{
+        Foundation::*,
+        Globalization::GetUserDefaultLocaleName,
+        System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED},
+        UI::WindowsAndMessaging::*,
+    },
+};
+
+/// Posted to the main window; lparam owns a `Box<OcrOutput>` (see `take_result`)
+pub const WM_APP_OCR: u32 = WM_APP + 14;
+
+/// Used when the engine does not report its limit
+const DEFAULT_MAX_DIMENSION: u32 = 2600;
+
+/// The page to recognize, as handed to the worker thread
+pub struct OcrPage {
+    pub page: usize,
+    /// Page as displayed, top-down BGRA (`WicLoader::get_page_pixels`)
+    pub pixels: ClipboardBitmapData,
+    /// Display rotation of the page, clockwise degrees
+    pub rotation: i32,
+    /// Unrotated page size in source pixels (`Document::page_dimensions`)
+    pub source_size: (f32, f32),
+}
+
+pub struct Recognized {
+    /// Lines separated by "\n"
+    pub text: String,
+    /// Display name of the language used
+    pub language: String,
+    /// Set when the user's language has no OCR pack and another one was used
+    pub note: Option<String>,
+    /// Word rectangles [left, top, right, bottom] in source pixels of the page
+    pub words: Vec<[f32; 4]>,
+}
+
+pub struct OcrOutput {
+    pub page: usize,
+    /// The error message, ready to show
+    pub result: std::result::Result<Recognized, String>,
+}
+
+/// Largest width or height the engine accepts
+pub fn max_dimension() -> u32 {
+    OcrEngine::MaxImageDimension().unwrap_or(DEFAULT_MAX_DIMENSION)
+}
+
+/// Ok if text can be recognized, else the message naming the OCR pack to install
+pub fn check_available() -> std::result::Result<(), String> {
+    engine().map(|_| ())
+}
+
+/// Recognizes `page` on a worker thread and posts the output to `hwnd`. False if the
+/// thread could not be started.
+pub fn start(hwnd: HWND, page: OcrPage) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView OCR".to_string())
+        .spawn(move || {
+            // WinRT needs COM on this thread
+            let com = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+            let output = OcrOutput { page: page.page, result: recognize(&page) };
+            if com {
+                unsafe { CoUninitialize() };
+            }
+            let result = Box::into_raw(Box::new(output));
+            if !unsafe { PostMessageW(HWND(hwnd_raw), WM_APP_OCR, WPARAM(0), LPARAM(result as isize)) }.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
+            }
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the output posted with WM_APP_OCR
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_OCR message and be taken only once.
+pub unsafe fn take_result(lparam: LPARAM) -> OcrOutput {
+    *Box::from_raw(lparam.0 as *mut OcrOutput)
+}
+
+/// Text of `pixels` (top-down BGRA, at most `max_dimension`), lines separated by "\n",
+/// without word positions (folder search). Call on a thread with COM initialized; the
+/// error message is ready to show.
+pub fn recognize_text(pixels: &ClipboardBitmapData) -> std::result::Result<String, String> {
+    let (engine, _) = engine()?;
+    let result = run(&engine, pixels).map_err(failed)?;
+    let mut lines = Vec::new();
+    for line in result.Lines().map_err(failed)? {
+        lines.push(line.Text().map_err(failed)?.to_string_lossy());
+    }
+    Ok(lines.join("\n"))
+}
+
+fn failed(e: Error) -> String {
+    format!("Die Texterkennung ist fehlgeschlagen:\n{}", e.message())
+}
+
+fn run(engine: &OcrEngine, pixels: &ClipboardBitmapData) -> Result<OcrResult> {
+    let writer = DataWriter::new()?;
+    writer.WriteBytes(&pixels.data)?;
+    let buffer = writer.DetachBuffer()?;
+    let bitmap = SoftwareBitmap::CreateCopyFromBuffer(&buffer, BitmapPixelFormat::Bgra8, pixels.width as i32, pixels.height as i32)?;
+    engine.RecognizeAsync(&bitmap)?.get()
+}
+
+fn recognize(page: &OcrPage) -> std::result::Result<Recognized, String> {
+    let (engine, note) = engine()?;
+    let result = run(&engine, &page.pixels).map_err(failed)?;
+
+    let mut text = String::new();
+    let mut words = Vec::new();
+    for line in result.Lines().map_err(failed)? {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&line.Text().map_err(failed)?.to_string_lossy());
+        for word in line.Words().map_err(failed)? {
+            if let Ok(rect) = word.BoundingRect() {
+                words.push(to_source(page, [rect.X, rect.Y, rect.X + rect.Width, rect.Y + rect.Height]));
+            }
+        }
+    }
+    let language = engine.RecognizerLanguage().and_then(|l| l.DisplayName()).map(|n| n.to_string_lossy()).unwrap_or_default();
+    Ok(Recognized { text, language, note, words })
+}
+
+/// Engine for the user's languages, else for the first installed OCR language with a note
+fn engine() -> std::result::Result<(OcrEngine, Option<String>), String> {
+    if let Ok(engine) = OcrEngine::TryCreateFromUserProfileLanguages() {
+        return Ok((engine, None));
+    }
+
+    let tag = user_language_tag();
+    let name = Language::CreateLanguage(&HSTRING::from(&tag))
+        .and_then(|l| l.DisplayName())
+        .map_or_else(|_| tag.clone(), |n| n.to_string_lossy());
+    let missing = format!("Für {} ist kein OCR-Sprachpaket installiert", name);
+    let fallback = OcrEngine::AvailableRecognizerLanguages()
+        .ok()
+        .filter(|languages| languages.Size().unwrap_or(0) > 0)
+        .and_then(|languages| languages.GetAt(0).ok());
+    match fallback.and_then(|language| Some((OcrEngine::TryCreateFromLanguage(&language).ok()?, language))) {
+        Some((engine, language)) => {
+            let used = language.DisplayName().map(|n| n.to_string_lossy()).unwrap_or_default();
+            Ok((engine, Some(format!("{}; erkannt wurde mit {}.", missing, used))))
+        }
+        None => Err(format!(
+            "{} (Sprachpaket \"Language.OCR~~~{}~0.0.1.0\").\n\nSie können es unter Einstellungen > Zeit und Sprache > Sprache hinzufügen: \
+             bei der Sprache \"Optionen\" wählen und \"Optische Zeichenerkennung\" installieren.",
+            missing, tag
+        )),
+    }
+}
+
+/// Locale of the user, e.g. "de-DE"
+fn user_language_tag() -> String {
+    let mut buffer = [0u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(&mut buffer) } as usize;
+    match len {
+        0 => "de-DE".to_string(),
+        // The length includes the terminating null
+        len => String::from_utf16_lossy(&buffer[..len - 1]),
+    }
+}
+
+/// Maps a rectangle of the recognized bitmap to source pixels of the unrotated page
+fn to_source(page: &OcrPage, [left, top, right, bottom]: [f32; 4]) -> [f32; 4] {
+    let (width, height) = page.source_size;
+    let rotated = page.rotation.rem_euclid(360);
+    let (shown_width, shown_height) = if rotated == 90 || rotated == 270 { (height, width) } else { (width, height) };
+    let scale_x = shown_width / page.pixels.width.max(1) as f32;
+    let scale_y = shown_height / page.pixels.height.max(1) as f32;
+
+    let point = |x: f32, y: f32| {
+        let (x, y) = (x * scale_x, y * scale_y);
+        match rotated {
+            90 => (y, height - x),
+            180 => (width - x, height - y),
+            270 => (width - y, x),
+            _ => (x, y),
+        }
+    };
+    let (x0, y0) = point(left, top);
+    let (x1, y1) = point(right, bottom);
+    [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]
+}
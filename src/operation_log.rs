@@ -0,0 +1,47 @@
+//! Recent operations for the "Details" section of error dialogs
+//!
+//! Support gets the last few things the user did (open, export, print, ...) together with
+//! the error code when an error box is copied with "Details kopieren".
+
+use std::collections::VecDeque;
+use windows::{core::*, Win32::System::SystemInformation::GetLocalTime};
+
+const MAX_ENTRIES: usize = 8;
+
+#[derive(Default)]
+pub struct OperationLog {
+    entries: VecDeque<String>,
+}
+
+impl OperationLog {
+    /// Adds an entry with the current time, dropping the oldest beyond `MAX_ENTRIES`
+    pub fn record(&mut self, operation: &str) {
+        let now = unsafe { GetLocalTime() };
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(format!(
+            "{:02}:{:02}:{:02} {}",
+            now.wHour, now.wMinute, now.wSecond, operation
+        ));
+    }
+
+    /// Details text: error code, file and the recent operations (oldest first)
+    pub fn details(&self, error: Option<&Error>, path: Option<&str>) -> String {
+        let mut text = String::new();
+        if let Some(error) = error {
+            text.push_str(&format!("Fehlercode: 0x{:08X}\n", error.code().0 as u32));
+        }
+        if let Some(path) = path {
+            text.push_str(&format!("Datei: {}\n", path));
+        }
+        if !self.entries.is_empty() {
+            text.push_str("\nLetzte Aktionen:\n");
+            for entry in &self.entries {
+                text.push_str(entry);
+                text.push('\n');
+            }
+        }
+        text.trim_end().to_string()
+    }
+}
@@ -0,0 +1,58 @@
+//! "Seiten als Dateien kopieren": pages as PNG files on the clipboard (CF_HDROP)
+//!
+//! To attach pages to an e-mail, the chosen pages are written as PNGs into a temp folder of
+//! this process and the files are put on the clipboard, so pasting into Outlook or Explorer
+//! attaches or copies them. Copying again replaces the previous files; the folder is
+//! removed when the viewer exits, and right away if writing a page fails.
+
+use crate::{document::Document, page_transform::PageTransform, watermark::Watermark, wic::WicLoader};
+use std::path::PathBuf;
+use windows::{core::*, Win32::Foundation::{E_FAIL, HWND}};
+
+/// The files of the last copy, removed with their folder on drop
+pub struct PageFiles {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl PageFiles {
+    /// Writes `pages` (0-based) of `doc`, each rotated by its entry in `rotations`, as
+    /// "<stem> - Seite <n>.png". Files of an earlier copy are deleted first.
+    pub fn write(
+        wic_loader: &WicLoader,
+        doc: &Document,
+        pages: &[usize],
+        rotations: &[i32],
+        stem: &str,
+        watermark: Option<&Watermark>,
+    ) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("SimpliView-Seiten-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(io_error)?;
+        // From here on a failure removes the folder with what was written so far
+        let mut page_files = Self { dir, files: Vec::with_capacity(pages.len()) };
+        for &page in pages {
+            let path = page_files.dir.join(format!("{} - Seite {}.png", stem, page + 1));
+            let path_text = path.to_string_lossy();
+            let rotation = rotations.get(page).copied().unwrap_or(0);
+            wic_loader.save(doc, &path_text, page, &PageTransform::rotated(rotation), watermark, false)?;
+            page_files.files.push(path);
+        }
+        Ok(page_files)
+    }
+
+    /// Puts the files on the clipboard
+    pub fn copy_to_clipboard(&self, hwnd: HWND) -> Result<()> {
+        crate::clipboard::write_files(hwnd, &self.files)
+    }
+}
+
+impl Drop for PageFiles {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::new(E_FAIL, HSTRING::from(e.to_string()))
+}
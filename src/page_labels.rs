@@ -0,0 +1,209 @@
+//! PDF page labels ("Seitenbeschriftungen")
+//!
+//! Documents can number their pages differently from the physical order, e.g. front
+//! matter i, ii, iii and the body 1, 2, 3 again, or "A-1" for an appendix. The labels come
+//! from the catalog's /PageLabels number tree: each entry starts a range at a page index
+//! with a numbering style (/S), a prefix (/P) and a first number (/St). The status bar
+//! shows them next to the physical page and "Gehe zu Seite" accepts them.
+
+use crate::pdf_structure::{get, Object, Pdf};
+
+/// Start of a labelled range
+struct Range {
+    /// 0-based physical page where the range begins
+    first_page: usize,
+    /// /S: b'D', b'R', b'r', b'A' or b'a'; `None` for labels that are the prefix only
+    style: Option<u8>,
+    prefix: String,
+    /// /St, the number of the range's first page
+    start: usize,
+}
+
+/// The label of every page, `None` if the document has no labels or they are just the
+/// page numbers 1, 2, 3...
+pub fn read(pdf: &mut Pdf, page_count: usize) -> Option<Vec<String>> {
+    let root = pdf.root()?;
+    let catalog = pdf.object(root).ok()?;
+    let tree = get(catalog.as_dict()?, b"PageLabels")?.clone();
+    let mut entries = Vec::new();
+    number_tree(pdf, &tree, &mut entries, 0);
+
+    let mut ranges: Vec<Range> = entries
+        .into_iter()
+        .filter_map(|(first_page, value)| {
+            let Ok(Object::Dict(dict)) = pdf.resolve(&value) else { return None };
+            let style = match get(&dict, b"S") {
+                Some(Object::Name(name)) if name.len() == 1 && b"DRrAa".contains(&name[0]) => Some(name[0]),
+                _ => None,
+            };
+            let prefix = get(&dict, b"P").and_then(|p| pdf.resolve(p).ok()).and_then(|p| p.as_text()).unwrap_or_default();
+            let start = get(&dict, b"St").and_then(|st| pdf.resolve(st).ok()).and_then(|st| st.as_integer()).unwrap_or(1);
+            Some(Range { first_page: usize::try_from(first_page).ok()?, style, prefix, start: usize::try_from(start).ok()?.max(1) })
+        })
+        .collect();
+    ranges.sort_by_key(|range| range.first_page);
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let labels: Vec<String> = (0..page_count)
+        .map(|page| match ranges.iter().rev().find(|range| range.first_page <= page) {
+            Some(range) => label(range.style, &range.prefix, range.start + page - range.first_page),
+            // Pages before the first range have no label in the file
+            None => (page + 1).to_string(),
+        })
+        .collect();
+    let plain = labels.iter().enumerate().all(|(page, label)| *label == (page + 1).to_string());
+    (!plain).then_some(labels)
+}
+
+/// Collects the /Nums pairs of a number tree, following /Kids
+fn number_tree(pdf: &mut Pdf, node: &Object, entries: &mut Vec<(i64, Object)>, depth: usize) {
+    if depth > 32 {
+        return;
+    }
+    let Ok(Object::Dict(node)) = pdf.resolve(node) else { return };
+    if let Some(Ok(Object::Array(nums))) = get(&node, b"Nums").map(|nums| pdf.resolve(nums)) {
+        for pair in nums.chunks_exact(2) {
+            if let Some(key) = pdf.resolve(&pair[0]).ok().and_then(|key| key.as_integer()) {
+                entries.push((key, pair[1].clone()));
+            }
+        }
+    }
+    if let Some(Ok(Object::Array(kids))) = get(&node, b"Kids").map(|kids| pdf.resolve(kids)) {
+        for kid in &kids {
+            number_tree(pdf, kid, entries, depth + 1);
+        }
+    }
+}
+
+/// Label of the page numbered `number` in a range with `style` and `prefix` (PDF 32000-1,
+/// 12.4.2): decimal, upper or lower roman, or letters A..Z, AA..ZZ, AAA.. (upper or lower)
+pub fn label(style: Option<u8>, prefix: &str, number: usize) -> String {
+    let number = match style {
+        Some(b'D') => number.to_string(),
+        Some(b'R') => roman(number),
+        Some(b'r') => roman(number).to_lowercase(),
+        Some(b'A') => letters(number),
+        Some(b'a') => letters(number).to_lowercase(),
+        _ => String::new(),
+    };
+    format!("{}{}", prefix, number)
+}
+
+fn roman(mut number: usize) -> String {
+    const DIGITS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut text = String::new();
+    for (value, digits) in DIGITS {
+        while number >= value {
+            text.push_str(digits);
+            number -= value;
+        }
+    }
+    text
+}
+
+/// 1 = A, 26 = Z, 27 = AA, 52 = ZZ, 53 = AAA
+fn letters(number: usize) -> String {
+    let Some(index) = number.checked_sub(1) else { return String::new() };
+    char::from(b'A' + (index % 26) as u8).to_string().repeat(index / 26 + 1)
+}
+
+/// Physical page (0-based) for what was typed into "Gehe zu Seite": a label of `labels`
+/// (exact, then ignoring case) or else a page number from 1 to `page_count`
+pub fn find(labels: Option<&[String]>, page_count: usize, text: &str) -> Option<usize> {
+    let text = text.trim();
+    if let Some(labels) = labels {
+        if let Some(page) = labels.iter().position(|label| label == text) {
+            return Some(page);
+        }
+        if let Some(page) = labels.iter().position(|label| label.eq_ignore_ascii_case(text)) {
+            return Some(page);
+        }
+    }
+    text.parse::<usize>().ok().filter(|page| (1..=page_count).contains(page)).map(|page| page - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PDF with only a catalog carrying `page_labels`; the cross-reference data is
+    /// rebuilt from the object headers
+    fn pdf_with_labels(page_labels: &str) -> Pdf {
+        let file = format!(
+            "%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R /PageLabels {} >>\nendobj\n2 0 obj\n<< /Type /Pages /Kids [] /Count 0 >>\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n",
+            page_labels
+        );
+        Pdf::open(file.into_bytes()).unwrap()
+    }
+
+    #[test]
+    fn numbering_styles() {
+        assert_eq!(label(Some(b'D'), "", 12), "12");
+        assert_eq!(label(Some(b'R'), "", 1994), "MCMXCIV");
+        assert_eq!(label(Some(b'r'), "", 4), "iv");
+        assert_eq!(label(Some(b'r'), "", 9), "ix");
+        assert_eq!(label(Some(b'A'), "", 1), "A");
+        assert_eq!(label(Some(b'A'), "", 26), "Z");
+        assert_eq!(label(Some(b'A'), "", 27), "AA");
+        assert_eq!(label(Some(b'a'), "", 53), "aaa");
+        assert_eq!(label(None, "Umschlag", 1), "Umschlag");
+    }
+
+    #[test]
+    fn prefix_goes_before_the_number() {
+        assert_eq!(label(Some(b'D'), "A-", 3), "A-3");
+        assert_eq!(label(Some(b'r'), "Vorwort ", 2), "Vorwort ii");
+    }
+
+    #[test]
+    fn ranges_with_prefix_and_start() {
+        let mut pdf = pdf_with_labels("<< /Nums [0 << /S /r >> 3 << /S /D /St 5 >> 5 << /S /D /P (A-) >> 6 << /P (Umschlag) >>] >>");
+        assert_eq!(read(&mut pdf, 7).unwrap(), ["i", "ii", "iii", "5", "6", "A-1", "Umschlag"]);
+    }
+
+    #[test]
+    fn ranges_from_kids_of_the_number_tree() {
+        let mut pdf = pdf_with_labels("<< /Kids [<< /Nums [0 << /S /R >>] >> << /Nums [2 << /S /a /St 3 >>] >>] >>");
+        assert_eq!(read(&mut pdf, 4).unwrap(), ["I", "II", "c", "d"]);
+    }
+
+    #[test]
+    fn plain_page_numbers_are_no_labels() {
+        let mut pdf = pdf_with_labels("<< /Nums [0 << /S /D >>] >>");
+        assert_eq!(read(&mut pdf, 3), None);
+    }
+
+    #[test]
+    fn pages_before_the_first_range_are_numbered() {
+        let mut pdf = pdf_with_labels("<< /Nums [2 << /S /R /St 10 >>] >>");
+        assert_eq!(read(&mut pdf, 4).unwrap(), ["1", "2", "X", "XI"]);
+    }
+
+    #[test]
+    fn find_prefers_labels_then_numbers() {
+        let labels: Vec<String> = ["i", "ii", "1", "2"].iter().map(|l| l.to_string()).collect();
+        assert_eq!(find(Some(&labels), 4, "ii"), Some(1));
+        assert_eq!(find(Some(&labels), 4, "II"), Some(1));
+        assert_eq!(find(Some(&labels), 4, " 2 "), Some(3));
+        assert_eq!(find(Some(&labels), 4, "4"), Some(3));
+        assert_eq!(find(None, 4, "4"), Some(3));
+        assert_eq!(find(None, 4, "5"), None);
+        assert_eq!(find(None, 4, "0"), None);
+    }
+}
@@ -0,0 +1,358 @@
+//! Page picker in the print dialog and in "Seiten extrahieren"
+//!
+//! A strip of page thumbnails with checkboxes lets the user print or extract any set of
+//! pages (e.g. 1, 4, 9-12) instead of typing a range blind. Thumbnails are rendered when
+//! the list view first asks for them, so only pages scrolled into view cost anything. Until
+//! a checkbox is changed the dialog's range (all / from-to, or the typed pages) applies;
+//! after that the checked pages do, unless "Nur Auswahl drucken" is chosen.
+
+use crate::{document::Document, wic::WicLoader};
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Graphics::Gdi::*,
+        UI::{
+            Controls::{Dialogs::PRINTDLGW, *},
+            WindowsAndMessaging::*,
+        },
+    },
+};
+
+/// Window property on the dialog pointing to the picker
+const PROP_NAME: PCWSTR = w!("SimpliView.PagePicker");
+
+const IDC_PAGE_LIST: i32 = 0x5A10;
+const IDC_PAGE_COUNT: i32 = 0x5A11;
+
+/// Thumbnail edge length in dialog units
+const THUMBNAIL_DLU: i32 = 48;
+
+/// State image indices of LVS_EX_CHECKBOXES
+const STATE_UNCHECKED: u32 = 1;
+const STATE_CHECKED: u32 = 2;
+
+pub struct PagePicker<'a> {
+    doc: Document,
+    rotations: Vec<i32>,
+    loader: &'a WicLoader,
+    checked: Vec<bool>,
+    /// Set once the user changed a checkbox; only then the checked pages are printed
+    modified: bool,
+    /// Ignore list notifications caused by filling the list
+    filling: bool,
+    /// Image list index of each page once its thumbnail was rendered
+    thumbnails: Vec<Option<i32>>,
+    thumbnail_size: i32,
+    images: HIMAGELIST,
+    label: HWND,
+    /// What is done with the pages, for the label: "drucken", "extrahieren"
+    action: &'static str,
+}
+
+impl<'a> PagePicker<'a> {
+    /// Picker for `doc` with the current page checked; `action` is the verb for the label
+    pub fn new(doc: Document, rotations: Vec<i32>, current_page: usize, loader: &'a WicLoader, action: &'static str) -> Self {
+        let page_count = doc.page_count();
+        let mut checked = vec![false; page_count];
+        if let Some(current) = checked.get_mut(current_page) {
+            *current = true;
+        }
+        Self {
+            doc,
+            rotations,
+            loader,
+            checked,
+            modified: false,
+            filling: false,
+            thumbnails: vec![None; page_count],
+            thumbnail_size: 0,
+            images: HIMAGELIST::default(),
+            label: HWND::default(),
+            action,
+        }
+    }
+
+    /// 0-based pages the user picked, `None` to use the dialog's range
+    pub fn selected_pages(&self) -> Option<Vec<usize>> {
+        self.modified.then(|| self.checked_pages())
+    }
+
+    fn checked_pages(&self) -> Vec<usize> {
+        self.checked.iter().enumerate().filter(|(_, &c)| c).map(|(page, _)| page).collect()
+    }
+
+    unsafe fn create_controls(&mut self, hdlg: HWND) {
+        let dlu = |x: i32, y: i32| {
+            let mut rect = RECT { left: 0, top: 0, right: x, bottom: y };
+            MapDialogRect(hdlg, &mut rect);
+            (rect.right, rect.bottom)
+        };
+        let (margin, label_height) = dlu(7, 10);
+        let (thumbnail_size, gap) = dlu(THUMBNAIL_DLU, 4);
+        // Thumbnail plus page number, checkbox and horizontal scroll bar
+        let (_, list_extra) = dlu(0, 30);
+        self.thumbnail_size = thumbnail_size;
+        let list_height = thumbnail_size + list_extra;
+
+        let mut client = RECT::default();
+        let _ = GetClientRect(hdlg, &mut client);
+        let top = crate::dialogs::make_room_above_buttons(hdlg, label_height + list_height + gap * 3);
+
+        let label = RECT { left: margin, top, right: client.right - margin, bottom: top + label_height };
+        self.label = crate::dialogs::add_dialog_control(
+            hdlg,
+            w!("STATIC"),
+            "",
+            WINDOW_STYLE::default(),
+            WINDOW_EX_STYLE::default(),
+            IDC_PAGE_COUNT,
+            label,
+        );
+
+        // Icons arranged top to bottom in a list one icon high form a horizontal strip
+        let list_top = label.bottom + gap;
+        let list_rect = RECT { left: margin, top: list_top, right: client.right - margin, bottom: list_top + list_height };
+        let list = crate::dialogs::add_dialog_control(
+            hdlg,
+            WC_LISTVIEWW,
+            "",
+            WS_TABSTOP | WINDOW_STYLE(LVS_ICON | LVS_ALIGNLEFT | LVS_AUTOARRANGE | LVS_SINGLESEL),
+            WS_EX_CLIENTEDGE,
+            IDC_PAGE_LIST,
+            list_rect,
+        );
+        let style = LVS_EX_CHECKBOXES | LVS_EX_DOUBLEBUFFER;
+        SendMessageW(list, LVM_SETEXTENDEDLISTVIEWSTYLE, WPARAM(style as usize), LPARAM(style as isize));
+
+        // The list view owns and destroys the image list. Index 0 stands in for
+        // thumbnails that could not be rendered.
+        self.images = ImageList_Create(thumbnail_size, thumbnail_size, ILC_COLOR32, self.doc.page_count() as i32 + 1, 16);
+        if let Some(blank) = self.thumbnail_bitmap(None) {
+            ImageList_Add(self.images, blank, HBITMAP::default());
+            let _ = DeleteObject(blank);
+        }
+        SendMessageW(list, LVM_SETIMAGELIST, WPARAM(LVSIL_NORMAL as usize), LPARAM(self.images.0));
+
+        self.filling = true;
+        for page in 0..self.doc.page_count() {
+            let mut text: Vec<u16> = (page + 1).to_string().encode_utf16().chain(std::iter::once(0)).collect();
+            let item = LVITEMW {
+                mask: LVIF_TEXT | LVIF_IMAGE,
+                iItem: page as i32,
+                pszText: PWSTR(text.as_mut_ptr()),
+                iImage: I_IMAGECALLBACK,
+                ..Default::default()
+            };
+            SendMessageW(list, LVM_INSERTITEMW, WPARAM(0), LPARAM(&item as *const _ as isize));
+            set_check(list, page, self.checked[page]);
+        }
+        self.filling = false;
+
+        if let Some(first) = self.checked.iter().position(|&c| c) {
+            SendMessageW(list, LVM_ENSUREVISIBLE, WPARAM(first), LPARAM(0));
+        }
+        self.update_label();
+    }
+
+    /// Image list index of a page's thumbnail, rendered on first use
+    unsafe fn thumbnail(&mut self, page: usize) -> i32 {
+        if let Some(index) = self.thumbnails.get(page).copied().flatten() {
+            return index;
+        }
+        let rotation = self.rotations.get(page).copied().unwrap_or(0);
+        let index = self
+            .loader
+            .get_thumbnail(&self.doc, page, rotation, self.thumbnail_size as u32)
+            .ok()
+            .and_then(|data| self.thumbnail_bitmap(Some(&data)))
+            .map(|bitmap| {
+                let index = ImageList_Add(self.images, bitmap, HBITMAP::default());
+                let _ = DeleteObject(bitmap);
+                index
+            })
+            .filter(|&index| index >= 0)
+            .unwrap_or(0);
+        if let Some(slot) = self.thumbnails.get_mut(page) {
+            *slot = Some(index);
+        }
+        index
+    }
+
+    /// Square 32bpp bitmap with the thumbnail centered on a transparent background
+    unsafe fn thumbnail_bitmap(&self, data: Option<&crate::wic::ClipboardBitmapData>) -> Option<HBITMAP> {
+        let size = self.thumbnail_size;
+        let bmi = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: size,
+                biHeight: size, // Bottom-up like the thumbnail pixels
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0 as u32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut bits = std::ptr::null_mut();
+        let bitmap = CreateDIBSection(None, &bmi, DIB_RGB_COLORS, &mut bits, None, 0).ok()?;
+        let canvas = std::slice::from_raw_parts_mut(bits as *mut u8, (size * size * 4) as usize);
+        canvas.fill(0);
+        if let Some(data) = data {
+            let width = (data.width as i32).min(size);
+            let height = (data.height as i32).min(size);
+            let (left, bottom) = ((size - width) / 2, (size - height) / 2);
+            let row_bytes = (width * 4) as usize;
+            for row in 0..height {
+                let source = (row * data.width as i32 * 4) as usize;
+                let target = (((row + bottom) * size + left) * 4) as usize;
+                canvas[target..target + row_bytes].copy_from_slice(&data.data[source..source + row_bytes]);
+            }
+        }
+        Some(bitmap)
+    }
+
+    unsafe fn on_item_changed(&mut self, change: &NMLISTVIEW) {
+        let state_changed = ((change.uNewState ^ change.uOldState) & LVIS_STATEIMAGEMASK.0) != 0;
+        if self.filling || (change.uChanged & LVIF_STATE).0 == 0 || !state_changed {
+            return;
+        }
+        let checked = (change.uNewState & LVIS_STATEIMAGEMASK.0) >> 12 == STATE_CHECKED;
+        if let Some(slot) = self.checked.get_mut(change.iItem as usize) {
+            if *slot != checked {
+                *slot = checked;
+                self.modified = true;
+                self.update_label();
+            }
+        }
+    }
+
+    unsafe fn update_label(&self) {
+        let text = if self.modified {
+            let pages = self.checked_pages();
+            match pages.len() {
+                0 => "Keine Seite ausgewählt".to_string(),
+                1 => format!("1 Seite ausgewählt: {}", format_pages(&pages)),
+                n => format!("{} Seiten ausgewählt: {}", n, format_pages(&pages)),
+            }
+        } else {
+            format!("Seiten ankreuzen, um eine eigene Auswahl zu {}:", self.action)
+        };
+        let _ = SetWindowTextW(self.label, &HSTRING::from(text));
+    }
+}
+
+/// Print dialog WM_INITDIALOG: adds the picker passed in `PRINTDLGW::lCustData`, if any
+///
+/// # Safety
+/// `lparam` must be the WM_INITDIALOG parameter of a print dialog hook, and the picker
+/// must outlive the dialog.
+pub unsafe fn attach(hdlg: HWND, lparam: LPARAM) {
+    let pd = &*(lparam.0 as *const PRINTDLGW);
+    if pd.lCustData.0 == 0 {
+        return;
+    }
+    attach_to(hdlg, pd.lCustData.0 as *mut PagePicker);
+}
+
+/// Adds `picker` above the OK and Cancel buttons of any dialog, on WM_INITDIALOG
+///
+/// # Safety
+/// `picker` must be valid and outlive the dialog.
+pub unsafe fn attach_to(hdlg: HWND, picker: *mut PagePicker) {
+    let _ = SetPropW(hdlg, PROP_NAME, HANDLE(picker as isize));
+    (*picker).create_controls(hdlg);
+}
+
+/// Dialog messages for the picker. Returns the dialog procedure's result if handled.
+///
+/// # Safety
+/// `hdlg` must be the dialog the picker was attached to (or none was).
+pub unsafe fn handle_message(hdlg: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> Option<usize> {
+    let picker = GetPropW(hdlg, PROP_NAME);
+    if picker.0 == 0 {
+        return None;
+    }
+    let picker = &mut *(picker.0 as *mut PagePicker);
+    match msg {
+        WM_NOTIFY => {
+            let header = &*(lparam.0 as *const NMHDR);
+            if header.idFrom != IDC_PAGE_LIST as usize {
+                return None;
+            }
+            match header.code {
+                LVN_GETDISPINFOW => {
+                    let info = &mut *(lparam.0 as *mut NMLVDISPINFOW);
+                    if (info.item.mask & LVIF_IMAGE).0 != 0 {
+                        info.item.iImage = picker.thumbnail(info.item.iItem as usize);
+                    }
+                    Some(1)
+                }
+                LVN_ITEMCHANGED => {
+                    picker.on_item_changed(&*(lparam.0 as *const NMLISTVIEW));
+                    Some(1)
+                }
+                _ => None,
+            }
+        }
+        // A modified selection must not be empty
+        WM_COMMAND if (wparam.0 & 0xFFFF) as i32 == IDOK.0 => {
+            if picker.selected_pages().is_some_and(|pages| pages.is_empty()) {
+                crate::dialogs::show_error(hdlg, "Bitte wählen Sie mindestens eine Seite aus.");
+                return Some(1);
+            }
+            None
+        }
+        WM_DESTROY => {
+            let _ = RemovePropW(hdlg, PROP_NAME);
+            None
+        }
+        _ => None,
+    }
+}
+
+unsafe fn set_check(list: HWND, item: usize, checked: bool) {
+    let state = if checked { STATE_CHECKED } else { STATE_UNCHECKED };
+    let item_state = LVITEMW {
+        state: LIST_VIEW_ITEM_STATE_FLAGS(state << 12),
+        stateMask: LVIS_STATEIMAGEMASK,
+        ..Default::default()
+    };
+    SendMessageW(list, LVM_SETITEMSTATE, WPARAM(item), LPARAM(&item_state as *const _ as isize));
+}
+
+/// 0-based pages of a typed list like "1, 4, 9-12" (or with ";" between the parts),
+/// sorted and without repeats. `None` if a part is no page of 1 to `page_count`.
+pub fn parse_pages(text: &str, page_count: usize) -> Option<Vec<usize>> {
+    let page = |part: &str| part.trim().parse::<usize>().ok().filter(|p| (1..=page_count).contains(p));
+    let mut pages = Vec::new();
+    for part in text.split([',', ';']).filter(|p| !p.trim().is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (page(first)?, page(last)?);
+                pages.extend((first.min(last) - 1)..first.max(last));
+            }
+            None => pages.push(page(part)? - 1),
+        }
+    }
+    pages.sort_unstable();
+    pages.dedup();
+    (!pages.is_empty()).then_some(pages)
+}
+
+/// Compact page list, 1-based: "1, 4, 9-12"
+pub fn format_pages(pages: &[usize]) -> String {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < pages.len() {
+        let start = pages[i];
+        let mut end = start;
+        while i + 1 < pages.len() && pages[i + 1] == end + 1 {
+            end += 1;
+            i += 1;
+        }
+        parts.push(if end > start { format!("{}-{}", start + 1, end + 1) } else { (start + 1).to_string() });
+        i += 1;
+    }
+    parts.join(", ")
+}
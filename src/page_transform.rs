@@ -0,0 +1,176 @@
+//! How a page is shown, for every output: the screen, export, the clipboard and print
+//! take a page through the same `PageTransform`, so none of them differs from the display.
+//!
+//! The steps are applied in this order, on the screen by D2DRenderer and in pixels by
+//! `WicLoader::transformed_page`:
+//! 1. the part of the page shown (auto-crop, a selection); split pages are already cut
+//!    from their image page (`Document::page_clip`)
+//! 2. the rotation in quarter turns
+//! 3. the straightening of a skewed scan (deskew.rs), about the center of the result
+
+use crate::document::Document;
+use windows::{Foundation::Numerics::Matrix3x2, Win32::Graphics::Imaging::WICRect};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PageTransform {
+    /// Part of the page in its pixels, [left, top, right, bottom]; `None` = the whole page
+    pub crop: Option<[f32; 4]>,
+    /// Clockwise, 0/90/180/270
+    pub rotation: i32,
+    /// Further clockwise turn in degrees that straightens the page
+    pub skew: f32,
+}
+
+impl PageTransform {
+    /// Only turned by `rotation`, e.g. for batch jobs and thumbnails
+    pub fn rotated(rotation: i32) -> Self {
+        Self { rotation, ..Default::default() }
+    }
+
+    /// A page as displayed: the part `auto_crop` leaves of it, turned and straightened
+    pub fn displayed(doc: &Document, page: usize, rotation: i32, auto_crop: bool, skew: f32) -> Self {
+        let (width, height) = doc.page_dimensions(page);
+        let shown = doc.display_rect(page, auto_crop);
+        Self { crop: (shown != [0.0, 0.0, width, height]).then_some(shown), rotation, skew }
+    }
+
+    /// Part of the page taken, in its pixels
+    pub fn source_rect(&self, doc: &Document, page: usize) -> [f32; 4] {
+        self.crop.unwrap_or_else(|| {
+            let (width, height) = doc.page_dimensions(page);
+            [0.0, 0.0, width, height]
+        })
+    }
+
+    /// `crop` in whole pixels for WIC, at least one pixel wide and high
+    pub fn crop_rect(&self) -> Option<WICRect> {
+        self.crop.map(|[left, top, right, bottom]| WICRect {
+            X: left as i32,
+            Y: top as i32,
+            Width: ((right - left) as i32).max(1),
+            Height: ((bottom - top) as i32).max(1),
+        })
+    }
+
+    /// Size of the output for a source part `width` x `height`: quarter turns swap the
+    /// sides, the straightening keeps them (see `deskew::straighten`)
+    pub fn output_size(&self, width: f32, height: f32) -> (f32, f32) {
+        if self.rotation % 180 == 90 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Display transform of the source part drawn centered at (`center_x`, `center_y`)
+    pub fn matrix(&self, center_x: f32, center_y: f32) -> Matrix3x2 {
+        crate::d2d::make_rotation_matrix(self.rotation as f32 + self.skew, center_x, center_y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `width` x `height` 32bpp pixels, each holding its own x and y in blue and green
+    fn labelled(width: usize, height: usize) -> Vec<u8> {
+        (0..height).flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 0, 255])).collect()
+    }
+
+    /// The pixels of `transform` applied to a labelled `width` x `height` page in the order
+    /// of `WicLoader::transformed_page`: the crop first, then the turn of the renderer's
+    /// pixel rotation. Returns them with the size of the cropped part.
+    fn pixel_output(transform: &PageTransform, width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+        let page = labelled(width, height);
+        let rect = transform.crop_rect().unwrap_or(WICRect { X: 0, Y: 0, Width: width as i32, Height: height as i32 });
+        let (part_width, part_height) = (rect.Width as usize, rect.Height as usize);
+        let part: Vec<u8> = (0..part_height)
+            .flat_map(|y| {
+                let start = ((rect.Y as usize + y) * width + rect.X as usize) * 4;
+                page[start..start + part_width * 4].to_vec()
+            })
+            .collect();
+        let turned = crate::pdf::rotate_pixels(&part, part_width as u32, part_height as u32, transform.rotation as u32);
+        (turned, part_width, part_height)
+    }
+
+    /// Label of the pixel at (x, y) of `width`-wide pixels
+    fn label(pixels: &[u8], width: usize, (x, y): (usize, usize)) -> (u8, u8) {
+        let i = (y * width + x) * 4;
+        (pixels[i], pixels[i + 1])
+    }
+
+    /// Where the display transform puts the point (x, y) of the source part, relative to
+    /// the top left of the turned part's bounding box
+    fn display_point(transform: &PageTransform, width: f32, height: f32, (x, y): (f32, f32)) -> (f32, f32) {
+        let (out_width, out_height) = transform.output_size(width, height);
+        let (center_x, center_y) = (out_width / 2.0, out_height / 2.0);
+        let m = transform.matrix(center_x, center_y);
+        // The source part is drawn unrotated, centered at the same point
+        let (x, y) = (center_x - width / 2.0 + x, center_y - height / 2.0 + y);
+        (x * m.M11 + y * m.M21 + m.M31, x * m.M12 + y * m.M22 + m.M32)
+    }
+
+    #[test]
+    fn display_and_pixel_output_turn_the_same_way() {
+        let (width, height) = (40, 30);
+        for crop in [None, Some([5.0, 3.0, 26.0, 18.0])] {
+            for rotation in [0, 90, 180, 270] {
+                let transform = PageTransform { crop, ..PageTransform::rotated(rotation) };
+                let (pixels, part_width, part_height) = pixel_output(&transform, width, height);
+                let (out_width, _) = transform.output_size(part_width as f32, part_height as f32);
+                let [left, top, ..] = crop.unwrap_or_default();
+                // Every pixel of the part lands where the display puts the pixel's center
+                for y in 0..part_height {
+                    for x in 0..part_width {
+                        let center = (x as f32 + 0.5, y as f32 + 0.5);
+                        let (dx, dy) = display_point(&transform, part_width as f32, part_height as f32, center);
+                        let shown = label(&pixels, out_width as usize, (dx as usize, dy as usize));
+                        assert_eq!(shown, ((left as usize + x) as u8, (top as usize + y) as u8), "{rotation}°, crop {crop:?}, ({x}, {y})");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crop_is_taken_before_the_turn() {
+        // The crop is in the pixels of the unturned page: its top left 10 x 5 pixels
+        let transform = PageTransform { crop: Some([0.0, 0.0, 10.0, 5.0]), ..PageTransform::rotated(90) };
+        let (pixels, part_width, part_height) = pixel_output(&transform, 40, 30);
+        assert_eq!(transform.output_size(part_width as f32, part_height as f32), (5.0, 10.0));
+        assert_eq!(pixels.len(), 5 * 10 * 4);
+        // Turned clockwise, the page's top left corner is at the top right, its (9, 4) at
+        // the bottom left; cropping after the turn would have taken the bottom left of the page
+        assert_eq!(label(&pixels, 5, (4, 0)), (0, 0));
+        assert_eq!(label(&pixels, 5, (0, 9)), (9, 4));
+        assert!(pixels.chunks_exact(4).all(|px| px[0] < 10 && px[1] < 5));
+    }
+
+    #[test]
+    fn straightening_turns_about_the_center() {
+        let transform = PageTransform { skew: 2.0, ..PageTransform::rotated(90) };
+        let (width, height) = (40.0, 30.0);
+        let (x, y) = display_point(&transform, width, height, (width / 2.0, height / 2.0));
+        assert!((x - 15.0).abs() < 1e-3 && (y - 20.0).abs() < 1e-3);
+        // Clockwise: the top left corner moves right
+        let (x, _) = display_point(&transform, width, height, (0.0, 0.0));
+        assert!(x > height);
+    }
+
+    #[test]
+    fn output_size_swaps_sides_on_quarter_turns() {
+        assert_eq!(PageTransform::rotated(0).output_size(4.0, 3.0), (4.0, 3.0));
+        assert_eq!(PageTransform::rotated(90).output_size(4.0, 3.0), (3.0, 4.0));
+        assert_eq!(PageTransform::rotated(180).output_size(4.0, 3.0), (4.0, 3.0));
+        assert_eq!(PageTransform::rotated(270).output_size(4.0, 3.0), (3.0, 4.0));
+    }
+
+    #[test]
+    fn crop_rect_keeps_at_least_a_pixel() {
+        let transform = PageTransform { crop: Some([10.6, 20.2, 10.8, 50.9]), ..Default::default() };
+        let rect = transform.crop_rect().unwrap();
+        assert_eq!((rect.X, rect.Y, rect.Width, rect.Height), (10, 20, 1, 30));
+        assert!(PageTransform::default().crop_rect().is_none());
+    }
+}
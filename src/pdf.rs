@@ -1,19 +1,103 @@
+use crate::cancel::CancelToken;
 use crate::document::{Document, PageData};
+use crate::settings::RenderQuality;
+use parking_lot::Mutex;
 use std::sync::atomic::{AtomicBool, Ordering};
 use windows::{
     core::*,
     Data::Pdf::*,
-    Foundation::Size,
+    Foundation::{Rect, Size},
+    Win32::Foundation::{E_BOUNDS, HWND, LPARAM, WIN32_ERROR, WPARAM},
+    Win32::UI::WindowsAndMessaging::{GetSystemMetrics, PostMessageW, SM_CXSCREEN, SM_CYSCREEN, WM_APP},
     Storage::*,
     Storage::Streams::*,
 };
 
-// Maximum render dimension to prevent memory issues with very large PDFs
-// 2048 pixels is sufficient for most displays while keeping memory usage reasonable
-const MAX_RENDER_DIMENSION: f64 = 2048.0;
+/// Posted to the main window; lparam owns a `Box<StructureResult>` (see `take_structure`)
+pub const WM_APP_PDF_STRUCTURE: u32 = WM_APP + 18;
+
+/// Limit for documents opened "In reduzierter Qualität"
+const REDUCED_RENDER_DIMENSION: u32 = 1024;
+/// Upper bound for any page bitmap, also with --pdf-dpi or on very large screens
+const LARGEST_RENDER_DIMENSION: u32 = 8192;
+/// "An Bildschirm anpassen": longest page side relative to the longer side of the primary monitor
+const SCREEN_RENDER_FACTOR: f64 = 1.5;
+
+// PdfPage.Size is in device-independent pixels (1/96 inch), not PDF points
+const PDF_DIPS_PER_INCH: f32 = 96.0;
+
+/// Size at which PDF pages are rendered for display: "Renderqualität" or --pdf-dpi
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderResolution {
+    /// Page size at 96 dpi, scaled down so neither side exceeds this many pixels
+    MaxDimension(u32),
+    /// Fixed resolution in dots per inch, still limited to LARGEST_RENDER_DIMENSION
+    Dpi(u32),
+}
+
+impl RenderResolution {
+    /// The limit of a "Renderqualität" setting
+    pub fn from_quality(quality: RenderQuality) -> Self {
+        match quality {
+            RenderQuality::Fast => Self::MaxDimension(1536),
+            RenderQuality::Standard => Self::MaxDimension(2048),
+            RenderQuality::High => Self::MaxDimension(4096),
+            RenderQuality::Screen => {
+                let screen = unsafe { GetSystemMetrics(SM_CXSCREEN).max(GetSystemMetrics(SM_CYSCREEN)) };
+                let dimension = (screen.max(0) as f64 * SCREEN_RENDER_FACTOR) as u32;
+                Self::MaxDimension(dimension.clamp(REDUCED_RENDER_DIMENSION, LARGEST_RENDER_DIMENSION))
+            }
+        }
+    }
+
+    /// Display width of a page of `page_size` (DIPs)
+    fn render_width(self, page_size: Size) -> u32 {
+        let longest = (page_size.Width as f64).max(page_size.Height as f64).max(1.0);
+        let scale = match self {
+            Self::MaxDimension(max_dimension) => (max_dimension as f64 / longest).min(1.0),
+            Self::Dpi(dpi) => (dpi as f64 / PDF_DIPS_PER_INCH as f64).min(LARGEST_RENDER_DIMENSION as f64 / longest),
+        };
+        ((page_size.Width as f64 * scale) as u32).max(1)
+    }
+}
+
+/// A document password held before a load: from `--password` / `--password-stdin`, or the
+/// one a reloaded PDF was opened with. There is no `Debug`, so it cannot end up in a log
+/// by accident, and the text is overwritten with zeros when dropped.
+pub struct Password(String);
+
+impl Password {
+    pub fn new(text: String) -> Self {
+        Self(text)
+    }
+
+    /// `--password-stdin`: the first line of standard input, so the password is not part of
+    /// the process command line. `None` without input.
+    pub fn read_stdin() -> Option<Self> {
+        let mut password = Self(String::new());
+        std::io::stdin().read_line(&mut password.0).ok()?;
+        let len = password.0.trim_end_matches(['\r', '\n']).len();
+        password.0.truncate(len);
+        (!password.0.is_empty()).then_some(password)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for Password {
+    fn drop(&mut self) {
+        // Zeros are valid UTF-8, so the String stays sound until it is freed
+        unsafe { self.0.as_bytes_mut() }.fill(0);
+    }
+}
 
 /// PDF loader that handles password-protected documents via Windows.Data.Pdf WinRT API.
 ///
+/// The loaded `PdfDocument` is kept in the returned `Document` (see `PdfFile`), so pages
+/// are rendered again without reopening the file or asking for the password again.
+///
 /// Password handling approach:
 /// - First load attempt is made without password (or with provided password)
 /// - If load fails with a password-related error, sets `needs_password` flag
@@ -21,184 +105,460 @@ const MAX_RENDER_DIMENSION: f64 = 2048.0;
 /// - WinRT handles the actual decryption internally
 pub struct PdfLoader {
     needs_password: AtomicBool,
+    /// "In reduzierter Qualität öffnen": pages render at most REDUCED_RENDER_DIMENSION
+    reduced_quality: AtomicBool,
+    resolution: Mutex<RenderResolution>,
 }
 
 impl PdfLoader {
     pub fn new() -> Self {
         Self {
             needs_password: AtomicBool::new(false),
+            reduced_quality: AtomicBool::new(false),
+            resolution: Mutex::new(RenderResolution::from_quality(RenderQuality::Standard)),
         }
     }
 
+    /// Applies to documents loaded afterwards
+    pub fn set_resolution(&self, resolution: RenderResolution) {
+        *self.resolution.lock() = resolution;
+    }
+
+    /// Applies to documents loaded afterwards
+    pub fn set_reduced_quality(&self, enabled: bool) {
+        self.reduced_quality.store(enabled, Ordering::SeqCst);
+    }
+
     /// Loads a PDF document, optionally with a password for encrypted files.
     ///
     /// # Arguments
     /// * `path` - Absolute path to the PDF file
     /// * `password` - Optional password for encrypted PDFs (supports Unicode)
+    /// * `cancel` - Checked between pages; aborts with `E_CANCELLED`
+    /// * `progress` - Gets (pages done, page count) while the page sizes are read
     ///
     /// # Returns
-    /// * `Ok(Document)` - Successfully loaded document with rendered pages
+    /// * `Ok(Document)` - Loaded document holding the open PDF; page 1 is rendered, the
+    ///   others render on demand
     /// * `Err` - Load failed; check `needs_password()` to determine if password is required
-    pub fn load(&self, path: &str, password: Option<&str>) -> Result<Document> {
+    pub fn load(
+        &self,
+        path: &str,
+        password: Option<&str>,
+        cancel: &CancelToken,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<Document> {
         self.needs_password.store(false, Ordering::SeqCst);
 
-        // Open file using WinRT StorageFile
-        let path_hstring: HSTRING = path.into();
-        let file = StorageFile::GetFileFromPathAsync(&path_hstring)?.get()?;
+        // Open file (StorageFile, or in-memory copy for long paths)
+        let source = PdfSource::open(path)?;
 
         // Load PDF document - with or without password
-        let pdf_doc = if let Some(pwd) = password {
-            // Password provided - attempt to load with it
-            let pwd_hstring: HSTRING = pwd.into();
-            match PdfDocument::LoadFromFileWithPasswordAsync(&file, &pwd_hstring)?.get() {
-                Ok(doc) => doc,
-                Err(e) => {
-                    // Wrong password - flag for retry
-                    if is_password_error(&e) {
-                        self.needs_password.store(true, Ordering::SeqCst);
-                    }
-                    return Err(e);
+        let pdf_doc = match source.load(password) {
+            Ok(doc) => doc,
+            Err(e) => {
+                // Password missing or wrong - flag for retry
+                if is_password_error(&e) {
+                    self.needs_password.store(true, Ordering::SeqCst);
                 }
+                return Err(e);
             }
+        };
+
+        let resolution = if self.reduced_quality.load(Ordering::SeqCst) {
+            RenderResolution::MaxDimension(REDUCED_RENDER_DIMENSION)
         } else {
-            // No password - try loading without one first
-            match PdfDocument::LoadFromFileAsync(&file)?.get() {
-                Ok(doc) => doc,
-                Err(e) => {
-                    // Check if this is a password-protected file
-                    if is_password_error(&e) {
-                        self.needs_password.store(true, Ordering::SeqCst);
-                    }
-                    return Err(e);
-                }
-            }
+            *self.resolution.lock()
         };
 
-        // Get page count
+        // Page count and sizes are known without rendering - pages render on demand
         let page_count = pdf_doc.PageCount()? as usize;
-
-        // Render each page to bitmap
         let mut pages = Vec::with_capacity(page_count);
+        let mut page_sizes = Vec::with_capacity(page_count);
 
         for i in 0..page_count {
+            cancel.check()?;
+            progress(i, page_count);
             let page = pdf_doc.GetPage(i as u32)?;
+            let geometry = PageGeometry::read(&page)?;
+            page.Close()?;
+
+            let (width, height) = render_size(geometry.size, resolution.render_width(geometry.size));
+            pages.push(PageData {
+                width: width as f32,
+                height: height as f32,
+                dpi: Some(PDF_DIPS_PER_INCH * width as f32 / geometry.size.Width),
+                stride: width * 4,
+                ..Default::default()
+            });
+            page_sizes.push(geometry);
+        }
+
+        let pdf = PdfFile {
+            document: pdf_doc,
+            password: password.map(|p| Password::new(p.to_string())),
+            pages: page_sizes,
+            resolution,
+        };
+        let document = Document::new_pdf(pages, pdf);
+        // Render page 1 right away so the first paint shows content
+        cancel.check()?;
+        document.render_page(0);
+        Ok(document)
+    }
+
+    pub fn needs_password(&self) -> bool {
+        self.needs_password.load(Ordering::SeqCst)
+    }
+
+    /// Page count only: opens the PDF without reading page sizes or rendering anything.
+    /// Encrypted files fail like in `load` without a password. Usable on worker threads
+    /// with COM initialized.
+    pub fn page_count(path: &str) -> Result<usize> {
+        let pdf_doc = PdfSource::open(path)?.load(None)?;
+        Ok(pdf_doc.PageCount()? as usize)
+    }
 
-            // Get original page size
-            let page_size: Size = page.Size()?;
-            let orig_width = page_size.Width as f64;
-            let orig_height = page_size.Height as f64;
-
-            // Create in-memory stream for rendering
-            let stream = InMemoryRandomAccessStream::new()?;
-
-            // Check if page needs to be scaled down
-            let max_dim = orig_width.max(orig_height);
-            if max_dim > MAX_RENDER_DIMENSION {
-                // Calculate scaled dimensions maintaining aspect ratio
-                let scale = MAX_RENDER_DIMENSION / max_dim;
-                let render_width = (orig_width * scale) as u32;
-                let render_height = (orig_height * scale) as u32;
-
-                // Create render options with size limit
-                let options = PdfPageRenderOptions::new()?;
-                options.SetDestinationWidth(render_width)?;
-                options.SetDestinationHeight(render_height)?;
-
-                // Render with options
-                page.RenderWithOptionsToStreamAsync(&stream, &options)?.get()?;
-            } else {
-                // Render at original size
-                page.RenderToStreamAsync(&stream)?.get()?;
+    /// Memory the rendered pages would take once all were shown: the page count times the
+    /// display size of the first page. Like `page_count`, reads no other page.
+    pub fn estimate_memory(&self, path: &str) -> Result<u64> {
+        let pdf_doc = PdfSource::open(path)?.load(None)?;
+        let page_count = pdf_doc.PageCount()? as usize;
+        if page_count == 0 {
+            return Ok(0);
+        }
+        let page = pdf_doc.GetPage(0)?;
+        let size = PageGeometry::read(&page)?.size;
+        page.Close()?;
+        let (width, height) = render_size(size, self.resolution.lock().render_width(size));
+        Ok(crate::document::bitmap_bytes((0..page_count).map(|_| (width, height))))
+    }
+}
+
+/// Links and page labels of a PDF. Windows.Data.Pdf exposes neither, so they are read
+/// from the file itself - on a worker thread after loading, as that reads the whole file
+/// once more. A file whose structure cannot be read just has neither.
+#[derive(Default)]
+pub struct PdfStructure {
+    /// Clickable links of each page
+    pub links: Vec<Vec<crate::annotations::Link>>,
+    /// Label of each page (see page_labels.rs), `None` = page numbers
+    pub labels: Option<Vec<String>>,
+}
+
+/// Structure read for a file, posted with WM_APP_PDF_STRUCTURE
+pub struct StructureResult {
+    /// File read, so a late result of another file is dropped
+    pub path: String,
+    /// Number passed to `read_structure`, telling apart loads of the same file
+    pub request: u64,
+    pub structure: PdfStructure,
+}
+
+/// Reads the links and labels of the PDF at `path` on a worker thread and posts them to
+/// `hwnd`. Only for files opened without a password: the structure of encrypted files
+/// cannot be read. False if the thread could not be started.
+pub fn read_structure(hwnd: HWND, path: String, request: u64, page_count: usize) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView PDF Structure".to_string())
+        .spawn(move || {
+            let Ok(mut pdf) = crate::pdf_structure::Pdf::read(&path) else { return };
+            let structure = PdfStructure {
+                links: crate::annotations::links(&mut pdf).unwrap_or_default(),
+                labels: crate::page_labels::read(&mut pdf, page_count),
+            };
+            let result = Box::into_raw(Box::new(StructureResult { path, request, structure }));
+            if !unsafe { PostMessageW(HWND(hwnd_raw), WM_APP_PDF_STRUCTURE, WPARAM(0), LPARAM(result as isize)) }.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
             }
+        })
+        .is_ok()
+}
 
-            // Read pixel data from the stream
-            let (pixel_data, actual_width, actual_height) = self.read_stream_to_pixels(&stream)?;
+/// Takes ownership of the result posted with WM_APP_PDF_STRUCTURE
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_PDF_STRUCTURE message and be taken only once.
+pub unsafe fn take_structure(lparam: LPARAM) -> StructureResult {
+    *Box::from_raw(lparam.0 as *mut StructureResult)
+}
 
-            // Store actual rendered dimensions (pixel data dimensions must match)
-            pages.push(PageData {
-                width: actual_width as f32,
-                height: actual_height as f32,
-                wic_bitmap: None,
-                pixel_data: Some(pixel_data),
-                stride: actual_width * 4,
-            });
+/// An open PDF document, kept by its `Document` for as long as the document is shown.
+///
+/// `Document` is not `Send`, so the WinRT objects in here are only used on the UI thread
+/// that loaded them.
+pub struct PdfFile {
+    document: PdfDocument,
+    /// Password the document was opened with, `None` for unencrypted files; zeroed when
+    /// the document is closed
+    password: Option<Password>,
+    /// How each page is rendered
+    pages: Vec<PageGeometry>,
+    /// Size of the pages rendered for display
+    resolution: RenderResolution,
+}
 
-            // Close the page
-            page.Close()?;
+impl PdfFile {
+    /// Password that opened the document, for reopening it without asking again
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_ref().map(Password::as_str)
+    }
+
+    /// Renders a page `target_px_width` pixels wide (height from the aspect ratio) into a
+    /// fresh 32bpp PBGRA buffer. Returns the pixels with their width and height.
+    pub fn render_page(&self, page: usize, target_px_width: u32) -> Result<(Vec<u8>, u32, u32)> {
+        let geometry = *self.pages.get(page).ok_or_else(|| Error::from(E_BOUNDS))?;
+        let (width, height) = render_size(geometry.size, target_px_width);
+        // The bitmap from Windows.Data.Pdf before the rotation it left out
+        let (render_width, render_height) = if geometry.rotation % 180 == 90 { (height, width) } else { (width, height) };
+        let pdf_page = self.document.GetPage(page as u32)?;
+        let stream = InMemoryRandomAccessStream::new()?;
+
+        // Always pass the destination size so the bitmap matches the layout size
+        let options = PdfPageRenderOptions::new()?;
+        if let Some(source) = geometry.source {
+            options.SetSourceRect(source)?;
+        }
+        options.SetDestinationWidth(render_width)?;
+        options.SetDestinationHeight(render_height)?;
+        pdf_page.RenderWithOptionsToStreamAsync(&stream, &options)?.get()?;
+        pdf_page.Close()?;
+
+        let (pixel_data, rendered_width, rendered_height) = read_stream_to_pixels(&stream)?;
+        if rendered_width != render_width || rendered_height != render_height {
+            return Err(Error::from(HRESULT(0x88982F61u32 as i32))); // WINCODEC_ERR_UNEXPECTEDSIZE
         }
+        Ok((rotate_pixels(&pixel_data, render_width, render_height, geometry.rotation), width, height))
+    }
 
-        Ok(Document::new_pdf(pages))
+    /// Renders a page at the size used for display (see `RenderResolution`)
+    pub fn render_page_for_display(&self, page: usize) -> Result<(Vec<u8>, u32, u32)> {
+        let geometry = *self.pages.get(page).ok_or_else(|| Error::from(E_BOUNDS))?;
+        self.render_page(page, self.resolution.render_width(geometry.size))
     }
+}
 
-    fn read_stream_to_pixels(
-        &self,
-        stream: &InMemoryRandomAccessStream,
-    ) -> Result<(Vec<u8>, u32, u32)> {
-        unsafe {
-            use windows::Win32::Graphics::Imaging::*;
-            use windows::Win32::System::Com::*;
-
-            let factory: IWICImagingFactory =
-                CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER)?;
-
-            // Get stream as IStream
-            stream.Seek(0)?;
-            let size = stream.Size()? as usize;
-
-            // Read data from WinRT stream
-            let reader = DataReader::CreateDataReader(&stream.GetInputStreamAt(0)?)?;
-            reader.LoadAsync(size as u32)?.get()?;
-
-            let mut buffer = vec![0u8; size];
-            reader.ReadBytes(&mut buffer)?;
-
-            // Create WIC stream from memory
-            let wic_stream = factory.CreateStream()?;
-            wic_stream.InitializeFromMemory(&buffer)?;
-
-            // Create decoder
-            let decoder = factory.CreateDecoderFromStream(
-                &wic_stream,
-                std::ptr::null(),
-                WICDecodeMetadataCacheOnDemand,
-            )?;
-
-            // Get frame
-            let frame = decoder.GetFrame(0)?;
-
-            // Convert to BGRA
-            let converter = factory.CreateFormatConverter()?;
-            converter.Initialize(
-                &frame,
-                &GUID_WICPixelFormat32bppPBGRA,
-                WICBitmapDitherTypeNone,
-                None,
-                0.0,
-                WICBitmapPaletteTypeMedianCut,
-            )?;
-
-            // Get actual dimensions
-            let mut actual_width = 0u32;
-            let mut actual_height = 0u32;
-            converter.GetSize(&mut actual_width, &mut actual_height)?;
-
-            // Read pixels
-            let stride = actual_width * 4;
-            let buffer_size = (stride * actual_height) as usize;
-            let mut pixel_data = vec![0u8; buffer_size];
-
-            converter.CopyPixels(
-                std::ptr::null(),
-                stride,
-                &mut pixel_data,
-            )?;
-
-            Ok((pixel_data, actual_width, actual_height))
+/// How a page is rendered so that it shows like in Acrobat: the crop box only, turned by
+/// the page's /Rotate.
+///
+/// PdfPage.Size is meant to include both, but depending on the producer it is the plain
+/// media box (e.g. scanners that store landscape pages as portrait media with /Rotate 90).
+/// Whatever Windows.Data.Pdf leaves out is done here: the crop box through the render
+/// options' source rectangle, the rotation by turning the rendered bitmap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PageGeometry {
+    /// Size of the page as shown, in DIPs
+    size: Size,
+    /// Part of the page to render, in the coordinates of PdfPage.Size; `None` = all of it
+    source: Option<Rect>,
+    /// Clockwise rotation still to apply to the rendered bitmap: 0, 90, 180 or 270
+    rotation: u32,
+}
+
+impl PageGeometry {
+    fn read(page: &PdfPage) -> Result<Self> {
+        let size = page.Size()?;
+        let rotation = match page.Rotation()? {
+            PdfPageRotation::Rotate90 => 90,
+            PdfPageRotation::Rotate180 => 180,
+            PdfPageRotation::Rotate270 => 270,
+            _ => 0,
+        };
+        // Without the boxes the page is shown as Windows.Data.Pdf reports it
+        let Ok(dimensions) = page.Dimensions() else {
+            return Ok(Self { size, source: None, rotation: 0 });
+        };
+        Ok(Self::compute(size, rotation, dimensions.MediaBox()?, dimensions.CropBox()?))
+    }
+
+    /// `reported` is PdfPage.Size; `media` and `crop` are the page boxes in DIPs with the
+    /// origin at the bottom left, as in the PDF.
+    fn compute(reported: Size, rotation: u32, media: Rect, crop: Rect) -> Self {
+        let unchanged = Self { size: reported, source: None, rotation: 0 };
+        if media.Width <= 0.0 || media.Height <= 0.0 {
+            return unchanged;
+        }
+        // The crop box is clipped to the media box; an empty or missing one means the media box
+        let left = crop.X.max(media.X);
+        let bottom = crop.Y.max(media.Y);
+        let right = (crop.X + crop.Width).min(media.X + media.Width);
+        let top = (crop.Y + crop.Height).min(media.Y + media.Height);
+        let crop = if right - left >= 1.0 && top - bottom >= 1.0 {
+            Rect { X: left, Y: bottom, Width: right - left, Height: top - bottom }
+        } else {
+            media
+        };
+
+        let turned = |width: f32, height: f32| if rotation % 180 == 90 { (height, width) } else { (width, height) };
+        let matches = |(width, height): (f32, f32)| (reported.Width - width).abs() < 2.0 && (reported.Height - height).abs() < 2.0;
+        let (shown_width, shown_height) = turned(crop.Width, crop.Height);
+        let shown = Size { Width: shown_width, Height: shown_height };
+
+        // The crop box relative to the media box, from its top left, as the page is rendered
+        // before (`rotation` 0) or after Windows.Data.Pdf turned it
+        let source = |rotation: u32| {
+            let (x0, y0) = (crop.X - media.X, media.Y + media.Height - (crop.Y + crop.Height));
+            let (x1, y1) = (x0 + crop.Width, y0 + crop.Height);
+            let (w, h) = (media.Width, media.Height);
+            let (ax, ay, bx, by) = match rotation {
+                90 => (h - y1, x0, h - y0, x1),
+                180 => (w - x1, h - y1, w - x0, h - y0),
+                270 => (y0, w - x1, y1, w - x0),
+                _ => (x0, y0, x1, y1),
+            };
+            Rect { X: ax, Y: ay, Width: bx - ax, Height: by - ay }
+        };
+
+        if matches((shown_width, shown_height)) {
+            // Crop box and rotation applied
+            unchanged
+        } else if matches((crop.Width, crop.Height)) {
+            // Crop box applied, rotation not
+            Self { size: shown, source: None, rotation }
+        } else if matches(turned(media.Width, media.Height)) {
+            // Rotation applied, crop box not
+            Self { size: shown, source: Some(source(rotation)), rotation: 0 }
+        } else if matches((media.Width, media.Height)) {
+            // Neither
+            Self { size: shown, source: Some(source(0)), rotation }
+        } else {
+            unchanged
         }
     }
+}
 
-    pub fn needs_password(&self) -> bool {
-        self.needs_password.load(Ordering::SeqCst)
+/// Turns a 32bpp bitmap clockwise by `rotation` degrees (0, 90, 180 or 270)
+pub(crate) fn rotate_pixels(pixels: &[u8], width: u32, height: u32, rotation: u32) -> Vec<u8> {
+    if rotation == 0 {
+        return pixels.to_vec();
+    }
+    let (width, height) = (width as usize, height as usize);
+    let out_width = if rotation == 180 { width } else { height };
+    let mut out = vec![0u8; pixels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (nx, ny) = match rotation {
+                90 => (height - 1 - y, x),
+                180 => (width - 1 - x, height - 1 - y),
+                _ => (y, width - 1 - x),
+            };
+            let source = (y * width + x) * 4;
+            let target = (ny * out_width + nx) * 4;
+            out[target..target + 4].copy_from_slice(&pixels[source..source + 4]);
+        }
+    }
+    out
+}
+
+/// Bitmap size of a page rendered `width` pixels wide, keeping the aspect ratio
+fn render_size(page_size: Size, width: u32) -> (u32, u32) {
+    let width = width.max(1);
+    let scale = width as f64 / (page_size.Width as f64).max(1.0);
+    (width, ((page_size.Height as f64 * scale) as u32).max(1))
+}
+
+fn read_stream_to_pixels(stream: &InMemoryRandomAccessStream) -> Result<(Vec<u8>, u32, u32)> {
+    unsafe {
+        use windows::Win32::Graphics::Imaging::*;
+        use windows::Win32::System::Com::*;
+
+        let factory: IWICImagingFactory =
+            CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER)?;
+
+        // Get stream as IStream
+        stream.Seek(0)?;
+        let size = stream.Size()? as usize;
+
+        // Read data from WinRT stream
+        let reader = DataReader::CreateDataReader(&stream.GetInputStreamAt(0)?)?;
+        reader.LoadAsync(size as u32)?.get()?;
+
+        let mut buffer = vec![0u8; size];
+        reader.ReadBytes(&mut buffer)?;
+
+        // Create WIC stream from memory
+        let wic_stream = factory.CreateStream()?;
+        wic_stream.InitializeFromMemory(&buffer)?;
+
+        // Create decoder
+        let decoder = factory.CreateDecoderFromStream(
+            &wic_stream,
+            std::ptr::null(),
+            WICDecodeMetadataCacheOnDemand,
+        )?;
+
+        // Get frame
+        let frame = decoder.GetFrame(0)?;
+
+        // Convert to BGRA
+        let converter = factory.CreateFormatConverter()?;
+        converter.Initialize(
+            &frame,
+            &GUID_WICPixelFormat32bppPBGRA,
+            WICBitmapDitherTypeNone,
+            None,
+            0.0,
+            WICBitmapPaletteTypeMedianCut,
+        )?;
+
+        // Get actual dimensions
+        let mut actual_width = 0u32;
+        let mut actual_height = 0u32;
+        converter.GetSize(&mut actual_width, &mut actual_height)?;
+
+        // Read pixels
+        let stride = actual_width * 4;
+        let buffer_size = (stride * actual_height) as usize;
+        let mut pixel_data = vec![0u8; buffer_size];
+
+        converter.CopyPixels(
+            std::ptr::null(),
+            stride,
+            &mut pixel_data,
+        )?;
+
+        Ok((pixel_data, actual_width, actual_height))
+    }
+}
+
+/// Where a PDF is loaded from. StorageFile rejects paths beyond MAX_PATH, so those
+/// are read through the long-path aware std::fs into an in-memory stream instead.
+enum PdfSource {
+    File(StorageFile),
+    Memory(InMemoryRandomAccessStream),
+}
+
+impl PdfSource {
+    fn open(path: &str) -> Result<Self> {
+        if crate::utils::long_path(path) == path {
+            let path_hstring: HSTRING = path.into();
+            let file = crate::retry::with_retry("PDF öffnen", path, || StorageFile::GetFileFromPathAsync(&path_hstring)?.get())?;
+            return Ok(Self::File(file));
+        }
+
+        let data = crate::retry::with_retry("PDF lesen", path, || {
+            std::fs::read(path).map_err(|e| match e.raw_os_error() {
+                Some(code) => Error::new(WIN32_ERROR(code as u32).to_hresult(), HSTRING::from(e.to_string())),
+                None => Error::new(HRESULT(0x80004005u32 as i32), HSTRING::from(e.to_string())),
+            })
+        })?;
+        let stream = InMemoryRandomAccessStream::new()?;
+        let writer = DataWriter::CreateDataWriter(&stream)?;
+        writer.WriteBytes(&data)?;
+        writer.StoreAsync()?.get()?;
+        writer.DetachStream()?;
+        stream.Seek(0)?;
+        Ok(Self::Memory(stream))
+    }
+
+    fn load(&self, password: Option<&str>) -> Result<PdfDocument> {
+        match (self, password) {
+            (Self::File(file), Some(pwd)) => PdfDocument::LoadFromFileWithPasswordAsync(file, &HSTRING::from(pwd))?.get(),
+            (Self::File(file), None) => PdfDocument::LoadFromFileAsync(file)?.get(),
+            (Self::Memory(stream), Some(pwd)) => PdfDocument::LoadFromStreamWithPasswordAsync(stream, &HSTRING::from(pwd))?.get(),
+            (Self::Memory(stream), None) => PdfDocument::LoadFromStreamAsync(stream)?.get(),
+        }
     }
 }
 
@@ -215,3 +575,78 @@ fn is_password_error(e: &Error) -> bool {
     code == 0x80070005      // E_ACCESSDENIED - password required
         || code == 0x8007052B   // ERROR_WRONG_PASSWORD - wrong password provided
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page_transform::PageTransform;
+
+    fn size(width: f32, height: f32) -> Size {
+        Size { Width: width, Height: height }
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect { X: x, Y: y, Width: width, Height: height }
+    }
+
+    /// Portrait media 600 x 800 with a crop box 500 x 600 inside it
+    const MEDIA: Rect = Rect { X: 0.0, Y: 0.0, Width: 600.0, Height: 800.0 };
+    const CROP: Rect = Rect { X: 50.0, Y: 100.0, Width: 500.0, Height: 600.0 };
+
+    #[test]
+    fn intrinsic_rotation_left_out_is_applied() {
+        // Scanner page: portrait media with /Rotate 90, reported unturned
+        let geometry = PageGeometry::compute(size(600.0, 800.0), 90, MEDIA, MEDIA);
+        assert_eq!(geometry, PageGeometry { size: size(800.0, 600.0), source: None, rotation: 90 });
+        // Reported turned already: nothing left to do
+        let geometry = PageGeometry::compute(size(800.0, 600.0), 90, MEDIA, MEDIA);
+        assert_eq!(geometry, PageGeometry { size: size(800.0, 600.0), source: None, rotation: 0 });
+    }
+
+    #[test]
+    fn crop_box_left_out_is_applied() {
+        // Neither applied: the crop box from the unturned page, then turned
+        let geometry = PageGeometry::compute(size(600.0, 800.0), 90, MEDIA, CROP);
+        assert_eq!(geometry, PageGeometry { size: size(600.0, 500.0), source: Some(rect(50.0, 100.0, 500.0, 600.0)), rotation: 90 });
+        // Rotation applied, crop box not: the crop box from the turned page
+        let geometry = PageGeometry::compute(size(800.0, 600.0), 90, MEDIA, CROP);
+        assert_eq!(geometry, PageGeometry { size: size(600.0, 500.0), source: Some(rect(100.0, 50.0, 600.0, 500.0)), rotation: 0 });
+        // Crop box applied, rotation not
+        let geometry = PageGeometry::compute(size(500.0, 600.0), 90, MEDIA, CROP);
+        assert_eq!(geometry, PageGeometry { size: size(600.0, 500.0), source: None, rotation: 90 });
+        // Both applied
+        let geometry = PageGeometry::compute(size(600.0, 500.0), 90, MEDIA, CROP);
+        assert_eq!(geometry, PageGeometry { size: size(600.0, 500.0), source: None, rotation: 0 });
+    }
+
+    #[test]
+    fn unknown_boxes_keep_the_reported_page() {
+        let unchanged = PageGeometry { size: size(300.0, 400.0), source: None, rotation: 0 };
+        assert_eq!(PageGeometry::compute(size(300.0, 400.0), 90, MEDIA, CROP), unchanged);
+        assert_eq!(PageGeometry::compute(size(300.0, 400.0), 90, rect(0.0, 0.0, 0.0, 0.0), CROP), unchanged);
+        // A crop box outside the media box means the media box
+        let geometry = PageGeometry::compute(size(600.0, 800.0), 0, MEDIA, rect(700.0, 900.0, 50.0, 50.0));
+        assert_eq!(geometry, PageGeometry { size: size(600.0, 800.0), source: None, rotation: 0 });
+    }
+
+    #[test]
+    fn user_rotation_follows_the_intrinsic_one() {
+        let geometry = PageGeometry::compute(size(600.0, 800.0), 90, MEDIA, CROP);
+        let (width, height) = (geometry.size.Width, geometry.size.Height);
+        // The layout turns the page as shown (intrinsic rotation applied) by the user's
+        for (user, expected) in [(0, (600.0, 500.0)), (90, (500.0, 600.0)), (180, (600.0, 500.0)), (270, (500.0, 600.0))] {
+            assert_eq!(PageTransform::rotated(user).output_size(width, height), expected, "{user}°");
+        }
+
+        // Pixels: a 3 x 2 page with a marker at the top left, turned by the page's 90°
+        // and then the user's 90°, ends up at the bottom right like one half turn
+        let mut pixels = vec![0u8; 3 * 2 * 4];
+        pixels[0] = 255;
+        let intrinsic = rotate_pixels(&pixels, 3, 2, 90);
+        let both = rotate_pixels(&intrinsic, 2, 3, 90);
+        assert_eq!(both, rotate_pixels(&pixels, 3, 2, 180));
+        assert_eq!(both[(2 * 3 - 1) * 4], 255);
+        // The page's turn alone puts it at the top right
+        assert_eq!(intrinsic[4], 255);
+    }
+}
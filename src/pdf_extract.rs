@@ -0,0 +1,311 @@
+//! "Seiten extrahieren": copies selected pages of a PDF into a new, smaller PDF
+//!
+//! Windows.Data.Pdf can only render, so the pages are copied at the object level: the
+//! source's cross-reference data (tables, xref streams and object streams, following
+//! /Prev) is read, the page tree is walked for the page objects, and everything the
+//! selected pages reach (content streams, fonts, images, annotations) is written out
+//! unchanged under new object numbers, under a new catalog and page tree. Streams are
+//! copied byte for byte, so vector content stays vector and nothing is re-encoded.
+//!
+//! References from the copied objects to pages that were not selected (links, the page
+//! tree, the catalog) become null. Encrypted files are declined. The source is read with
+//! pdf_structure.rs.
+
+use crate::pdf_structure::{get, io_error, Dict, Object, Pdf};
+use crate::utils::long_path;
+use std::collections::{HashMap, HashSet, VecDeque};
+use windows::{
+    core::*,
+    Win32::Foundation::E_INVALIDARG,
+};
+
+/// HRESULT_FROM_WIN32(ERROR_INVALID_DATA)
+const E_INVALID_DATA: HRESULT = HRESULT(0x8007000Du32 as i32);
+
+/// Writes the 0-based `pages` of `source` (in this order) as a new PDF to `target`
+pub fn extract_pages(source: &str, pages: &[usize], target: &str) -> Result<()> {
+    if pages.is_empty() {
+        return Err(Error::new(E_INVALIDARG, HSTRING::from("Es wurde keine Seite ausgewählt.")));
+    }
+    let mut pdf = Pdf::read(source)?;
+    if pdf.is_encrypted() {
+        return Err(Error::new(
+            E_INVALID_DATA,
+            HSTRING::from("Aus verschlüsselten PDF-Dateien können keine Seiten extrahiert werden."),
+        ));
+    }
+    let output = extract(&mut pdf, pages)?;
+    crate::utils::write_atomically(target, |temp| std::fs::write(long_path(temp), output).map_err(io_error))
+}
+
+/// The new file with `selected` (0-based) as its pages
+fn extract(pdf: &mut Pdf, selected: &[usize]) -> Result<Vec<u8>> {
+    let (pages, nodes) = pdf.pages()?;
+    let mut copier = Copier {
+        // 1 is the new catalog, 2 the new page tree
+        next: 3,
+        numbers: HashMap::new(),
+        queue: VecDeque::new(),
+        excluded: nodes,
+    };
+    if let Some(root) = pdf.root() {
+        copier.excluded.insert(root);
+    }
+    let mut selected_numbers = Vec::new();
+    for &page in selected {
+        let (number, _) = pages.get(page).ok_or_else(|| {
+            Error::new(E_INVALIDARG, HSTRING::from(format!("Die Seite {} gibt es in dieser Datei nicht.", page.saturating_add(1))))
+        })?;
+        selected_numbers.push(*number);
+    }
+    // Pages that were not selected are not copied, even when a link points to them
+    for (number, _) in &pages {
+        if !selected_numbers.contains(number) {
+            copier.excluded.insert(*number);
+        }
+    }
+    let kids: Vec<Object> = selected_numbers.iter().map(|&number| Object::Reference(copier.map(number))).collect();
+
+    let mut objects: Vec<(u32, Object)> = Vec::new();
+    while let Some(source) = copier.queue.pop_front() {
+        let new_number = copier.numbers[&source];
+        let mut object = pdf.object(source)?;
+        let page = pages.iter().find(|(page, _)| *page == source);
+        if let (Some((_, inherited)), Object::Dict(dict)) = (page, &mut object) {
+            // Beads belong to article threads, which are not copied
+            dict.retain(|(k, _)| k != b"Parent" && k != b"B");
+            for (key, value) in inherited {
+                if get(dict, key).is_none() {
+                    dict.push((key.clone(), value.clone()));
+                }
+            }
+        }
+        copier.remap(&mut object);
+        if let (Some(_), Object::Dict(dict)) = (page, &mut object) {
+            dict.push((b"Parent".to_vec(), Object::Reference(2)));
+        }
+        objects.push((new_number, object));
+    }
+    objects.push((1, Object::Dict(vec![(b"Type".to_vec(), Object::Name(b"Catalog".to_vec())), (b"Pages".to_vec(), Object::Reference(2))])));
+    objects.push((
+        2,
+        Object::Dict(vec![
+            (b"Type".to_vec(), Object::Name(b"Pages".to_vec())),
+            (b"Count".to_vec(), Object::Integer(kids.len() as i64)),
+            (b"Kids".to_vec(), Object::Array(kids)),
+        ]),
+    ));
+    objects.sort_by_key(|(number, _)| *number);
+    Ok(write(pdf, &objects, copier.next))
+}
+
+/// Serializes `objects` (numbered 1..size) with a classic xref table, in the version of
+/// the source
+fn write(pdf: &Pdf, objects: &[(u32, Object)], size: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pdf.len() / 2);
+    out.extend_from_slice(b"%PDF-");
+    out.extend_from_slice(pdf.version());
+    // Binary marker comment, so transfer programs treat the file as binary
+    out.extend_from_slice(b"\n%\xE2\xE3\xCF\xD3\n");
+
+    let mut offsets = vec![0usize; size as usize];
+    for (number, object) in objects {
+        offsets[*number as usize] = out.len();
+        out.extend_from_slice(format!("{} 0 obj\n", number).as_bytes());
+        write_object(&mut out, object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f\r\n", size).as_bytes());
+    for offset in &offsets[1..] {
+        out.extend_from_slice(format!("{:010} 00000 n\r\n", offset).as_bytes());
+    }
+    out.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", size, xref).as_bytes());
+    out
+}
+
+/// Assigns new object numbers to the objects reachable from the copied pages
+struct Copier {
+    next: u32,
+    /// Source object number to new object number
+    numbers: HashMap<u32, u32>,
+    /// Source objects numbered but not yet copied
+    queue: VecDeque<u32>,
+    /// Source objects that are not copied; references to them become null
+    excluded: HashSet<u32>,
+}
+
+impl Copier {
+    fn map(&mut self, source: u32) -> u32 {
+        if let Some(&number) = self.numbers.get(&source) {
+            return number;
+        }
+        let number = self.next;
+        self.next += 1;
+        self.numbers.insert(source, number);
+        self.queue.push_back(source);
+        number
+    }
+
+    /// Rewrites the references in `object` to the new numbers
+    fn remap(&mut self, object: &mut Object) {
+        match object {
+            Object::Reference(number) if self.excluded.contains(number) => *object = Object::Null,
+            Object::Reference(number) => *number = self.map(*number),
+            Object::Array(items) => items.iter_mut().for_each(|item| self.remap(item)),
+            Object::Dict(dict) => dict.iter_mut().for_each(|(_, value)| self.remap(value)),
+            Object::Stream(dict, _) => {
+                // Written as a direct value (see write_dict), so an indirect length is not copied
+                dict.retain(|(k, _)| k != b"Length");
+                dict.iter_mut().for_each(|(_, value)| self.remap(value));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_object(out: &mut Vec<u8>, object: &Object) {
+    match object {
+        Object::Null => out.extend_from_slice(b"null"),
+        Object::Bool(value) => out.extend_from_slice(if *value { b"true" } else { b"false" }),
+        Object::Integer(value) => out.extend_from_slice(value.to_string().as_bytes()),
+        Object::Real(text) | Object::String(text) => out.extend_from_slice(text),
+        Object::Name(name) => {
+            out.push(b'/');
+            out.extend_from_slice(name);
+        }
+        Object::Reference(number) => out.extend_from_slice(format!("{} 0 R", number).as_bytes()),
+        Object::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                write_object(out, item);
+            }
+            out.push(b']');
+        }
+        Object::Dict(dict) => write_dict(out, dict, None),
+        Object::Stream(dict, data) => {
+            write_dict(out, dict, Some(data.len()));
+            out.extend_from_slice(b"\nstream\n");
+            out.extend_from_slice(data);
+            out.extend_from_slice(b"\nendstream");
+        }
+    }
+}
+
+/// `length` replaces the stream's /Length, which may have been an indirect object
+fn write_dict(out: &mut Vec<u8>, dict: &Dict, length: Option<usize>) {
+    out.extend_from_slice(b"<<");
+    for (key, value) in dict {
+        if length.is_some() && key == b"Length" {
+            continue;
+        }
+        out.push(b'/');
+        out.extend_from_slice(key);
+        out.push(b' ');
+        write_object(out, value);
+        out.push(b'\n');
+    }
+    if let Some(length) = length {
+        out.extend_from_slice(format!("/Length {}\n", length).as_bytes());
+    }
+    out.extend_from_slice(b">>");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PDF with numbered `objects`, a classic xref table and /Root 1 0 R
+    fn file(objects: &[&str]) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = out.len();
+        out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        out.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes());
+        out
+    }
+
+    fn three_pages() -> Pdf {
+        Pdf::open(file(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R 4 0 R 5 0 R] /Count 3 /MediaBox [0 0 595 842] >>",
+            "<< /Type /Page /Parent 2 0 R /Contents 6 0 R >>",
+            "<< /Type /Page /Parent 2 0 R /Rotate 90 >>",
+            "<< /Type /Page /Parent 2 0 R /Annots [<< /Dest [3 0 R /Fit] >>] >>",
+            "<< /Length 3 >>\nstream\nq Q\nendstream",
+        ]))
+        .unwrap()
+    }
+
+    /// The offsets of the first xref section, found through startxref
+    fn xref_offsets(data: &[u8]) -> Vec<usize> {
+        // Byte for byte, so the offsets stay valid past the binary marker comment
+        let text: String = data.iter().map(|&b| if b.is_ascii() { b as char } else { '?' }).collect();
+        let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let xref: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+        let mut lines = text[xref..].lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let count: usize = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        lines.take(count).map(|line| line[..10].parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn writes_the_selected_pages_in_order() {
+        let output = extract(&mut three_pages(), &[2, 1]).unwrap();
+        assert!(output.starts_with(b"%PDF-1.4\n"));
+
+        let mut copy = Pdf::open(output).unwrap();
+        let (pages, nodes) = copy.pages().unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(nodes, HashSet::from([2]));
+        // The /Rotate of the second source page went with it, the MediaBox was inherited
+        let (_, first) = &pages[0];
+        let second = copy.object(pages[1].0).unwrap();
+        let second = second.as_dict().unwrap();
+        assert_eq!(get(second, b"Rotate").and_then(Object::as_integer), Some(90));
+        assert!(get(second, b"MediaBox").is_some());
+        assert!(first.iter().all(|(key, _)| key != b"Rotate"));
+        // The link to the first page, which was not copied, became null
+        let third = copy.object(pages[0].0).unwrap();
+        let annots = get(third.as_dict().unwrap(), b"Annots").unwrap().clone();
+        let Object::Array(annots) = annots else { panic!("annotations were not copied") };
+        let Some(Object::Dict(link)) = annots.first() else { panic!("link was not copied") };
+        let Some(Object::Array(dest)) = get(link, b"Dest") else { panic!("destination was not copied") };
+        assert!(matches!(dest[0], Object::Null));
+        assert!(dest[1].is_name(b"Fit"));
+    }
+
+    #[test]
+    fn xref_offsets_point_at_the_objects() {
+        let output = extract(&mut three_pages(), &[0]).unwrap();
+        let offsets = xref_offsets(&output);
+        // Catalog, page tree, page and its content stream
+        assert_eq!(offsets.len(), 5);
+        assert_eq!(offsets[0], 0);
+        for (number, &offset) in offsets.iter().enumerate().skip(1) {
+            assert!(output[offset..].starts_with(format!("{} 0 obj\n", number).as_bytes()), "object {}", number);
+        }
+        let mut copy = Pdf::open(output).unwrap();
+        let (pages, _) = copy.pages().unwrap();
+        let page = copy.object(pages[0].0).unwrap();
+        let contents = get(page.as_dict().unwrap(), b"Contents").unwrap().clone();
+        assert_eq!(copy.stream_data(&contents).unwrap(), b"q Q");
+    }
+
+    #[test]
+    fn pages_out_of_range_are_refused() {
+        assert!(extract(&mut three_pages(), &[0, 3]).is_err());
+        assert!(extract(&mut three_pages(), &[usize::MAX]).is_err());
+    }
+}
@@ -0,0 +1,1088 @@
+//! Reading the object structure of PDF files
+//!
+//! Windows.Data.Pdf renders pages but exposes nothing of the file itself, so features that
+//! need the objects (page extraction, annotations, text search) read them here: the
+//! cross-reference data (tables, xref streams and object streams, following /Prev), single
+//! objects, the page tree and the operations of content streams. Damaged cross-reference
+//! data is rebuilt by scanning for "N G obj". Only Flate is decoded, which is all xref and
+//! object streams and nearly all page content use; other stream data is left as is.
+//! Encrypted files are opened but cannot be read further (see `Pdf::is_encrypted`).
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::rc::Rc;
+use windows::{core::*, Win32::Foundation::WIN32_ERROR};
+
+/// HRESULT_FROM_WIN32(ERROR_INVALID_DATA)
+const E_INVALID_DATA: HRESULT = HRESULT(0x8007000Du32 as i32);
+
+/// Page attributes a page takes from its ancestors in the page tree
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Nesting limit for arrays and dictionaries; deeper is treated as damaged
+const MAX_DEPTH: usize = 64;
+/// Largest decoded stream; xref, object and content streams stay far below, a larger one
+/// is a decompression bomb
+const MAX_STREAM_SIZE: u64 = 128 * 1024 * 1024;
+
+pub type Dict = Vec<(Vec<u8>, Object)>;
+
+/// Page objects in order, each with the attributes inherited from the page tree
+pub type Pages = Vec<(u32, Dict)>;
+
+/// A parsed PDF object. Strings, names and reals keep their source spelling so they are
+/// written back exactly as read.
+#[derive(Clone)]
+pub enum Object {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(Vec<u8>),
+    /// Including the delimiters: `(...)` or `<...>`
+    String(Vec<u8>),
+    /// Without the leading slash, escapes (#20) untouched
+    Name(Vec<u8>),
+    Array(Vec<Object>),
+    Dict(Dict),
+    Stream(Dict, Vec<u8>),
+    Reference(u32),
+}
+
+impl Object {
+    pub fn as_dict(&self) -> Option<&Dict> {
+        match self {
+            Object::Dict(dict) | Object::Stream(dict, _) => Some(dict),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Object::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Integer or real
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Object::Integer(value) => Some(*value as f64),
+            Object::Real(text) => std::str::from_utf8(text).ok()?.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn is_name(&self, name: &[u8]) -> bool {
+        matches!(self, Object::Name(n) if n == name)
+    }
+
+    pub fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            Object::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The bytes of a string with escapes and hex digits decoded, e.g. the character codes
+    /// of text shown on a page
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Object::String(raw) => Some(string_bytes(raw)),
+            _ => None,
+        }
+    }
+
+    /// A text string (e.g. /Contents) as Rust string: UTF-16BE or UTF-8 with a byte order
+    /// mark, else PDFDocEncoding, read as Latin-1
+    pub fn as_text(&self) -> Option<String> {
+        let Object::String(raw) = self else {
+            return None;
+        };
+        let bytes = string_bytes(raw);
+        Some(if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        } else if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+            String::from_utf8_lossy(utf8).into_owned()
+        } else {
+            bytes.iter().map(|&b| b as char).collect()
+        })
+    }
+}
+
+/// The bytes of a string as written in the file, `(...)` with escapes or `<hex>`
+fn string_bytes(raw: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(raw.len());
+    if raw.first() == Some(&b'<') {
+        let digits: Vec<u8> = raw.iter().filter_map(|&b| (b as char).to_digit(16).map(|d| d as u8)).collect();
+        // An odd last digit is followed by an implied 0
+        for pair in digits.chunks(2) {
+            bytes.push(pair[0] << 4 | pair.get(1).copied().unwrap_or(0));
+        }
+        return bytes;
+    }
+    let inner = &raw[1..raw.len().saturating_sub(1).max(1)];
+    let mut i = 0;
+    while i < inner.len() {
+        let b = inner[i];
+        i += 1;
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let Some(&escaped) = inner.get(i) else { break };
+        i += 1;
+        match escaped {
+            b'n' => bytes.push(b'\n'),
+            b'r' => bytes.push(b'\r'),
+            b't' => bytes.push(b'\t'),
+            b'b' => bytes.push(0x08),
+            b'f' => bytes.push(0x0C),
+            b'0'..=b'7' => {
+                let mut value = (escaped - b'0') as u32;
+                for _ in 0..2 {
+                    match inner.get(i) {
+                        Some(&d @ b'0'..=b'7') => {
+                            value = value * 8 + (d - b'0') as u32;
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            // Line continuation
+            b'\r' => {
+                if inner.get(i) == Some(&b'\n') {
+                    i += 1;
+                }
+            }
+            b'\n' => {}
+            other => bytes.push(other),
+        }
+    }
+    bytes
+}
+
+pub fn get<'d>(dict: &'d Dict, key: &[u8]) -> Option<&'d Object> {
+    dict.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn damaged() -> Error {
+    Error::new(E_INVALID_DATA, HSTRING::from("Die PDF-Datei ist beschädigt oder wird nicht unterstützt."))
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, 0 | b'\t' | b'\n' | 0x0C | b'\r' | b' ')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(b, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+fn is_regular(b: u8) -> bool {
+    !is_whitespace(b) && !is_delimiter(b)
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    /// Parser at an offset read from the file (startxref, xref entries, object stream
+    /// index), which may point anywhere in a damaged or crafted file
+    fn at(data: &'a [u8], offset: usize) -> Result<Self> {
+        if offset >= data.len() {
+            return Err(damaged());
+        }
+        Ok(Self::new(data, offset))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while self.peek().is_some_and(|b| b != b'\r' && b != b'\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Run of regular characters (a number or keyword), possibly empty
+    fn token(&mut self) -> &'a [u8] {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(is_regular) {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn integer(&mut self) -> Result<i64> {
+        let token = self.token();
+        std::str::from_utf8(token).ok().and_then(|t| t.parse().ok()).ok_or_else(damaged)
+    }
+
+    fn keyword(&mut self, keyword: &[u8]) -> Result<()> {
+        if self.token() == keyword {
+            Ok(())
+        } else {
+            Err(damaged())
+        }
+    }
+
+    /// True (and skipped) if the next token is `keyword`
+    fn next_is(&mut self, keyword: &[u8]) -> bool {
+        let start = self.pos;
+        if self.token() == keyword {
+            true
+        } else {
+            self.pos = start;
+            false
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Object> {
+        if depth > MAX_DEPTH {
+            return Err(damaged());
+        }
+        self.skip_whitespace();
+        match self.peek().ok_or_else(damaged)? {
+            b'/' => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.peek().is_some_and(is_regular) {
+                    self.pos += 1;
+                }
+                Ok(Object::Name(self.data[start..self.pos].to_vec()))
+            }
+            b'(' => self.literal_string(),
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Ok(Object::Dict(self.dict_entries(depth)?))
+            }
+            b'<' => {
+                let start = self.pos;
+                let end = self.data[start..].iter().position(|&b| b == b'>').ok_or_else(damaged)?;
+                self.pos = start + end + 1;
+                Ok(Object::String(self.data[start..self.pos].to_vec()))
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Object::Array(items));
+                        }
+                        Some(_) => items.push(self.object(depth + 1)?),
+                        None => return Err(damaged()),
+                    }
+                }
+            }
+            b'+' | b'-' | b'.' | b'0'..=b'9' => self.number(),
+            _ => match self.token() {
+                b"true" => Ok(Object::Bool(true)),
+                b"false" => Ok(Object::Bool(false)),
+                b"null" => Ok(Object::Null),
+                _ => Err(damaged()),
+            },
+        }
+    }
+
+    /// Entries up to the closing `>>`, the opening `<<` already read
+    fn dict_entries(&mut self, depth: usize) -> Result<Dict> {
+        let mut dict = Dict::new();
+        loop {
+            self.skip_whitespace();
+            if self.data[self.pos..].starts_with(b">>") {
+                self.pos += 2;
+                return Ok(dict);
+            }
+            let Object::Name(key) = self.object(depth + 1)? else {
+                return Err(damaged());
+            };
+            let value = self.object(depth + 1)?;
+            dict.push((key, value));
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<Object> {
+        let start = self.pos;
+        let mut nesting = 0;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'\\' => self.pos += 1,
+                b'(' => nesting += 1,
+                b')' => {
+                    nesting -= 1;
+                    if nesting == 0 {
+                        return Ok(Object::String(self.data[start..self.pos].to_vec()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(damaged())
+    }
+
+    /// A number, or a reference `N G R`
+    fn number(&mut self) -> Result<Object> {
+        let token = self.token();
+        if token.contains(&b'.') {
+            return Ok(Object::Real(token.to_vec()));
+        }
+        let text = std::str::from_utf8(token).map_err(|_| damaged())?;
+        let Ok(value) = text.parse::<i64>() else {
+            // Integers too large for i64 are valid PDF; keep them as written
+            return Ok(Object::Real(token.to_vec()));
+        };
+        if value >= 0 && token[0].is_ascii_digit() {
+            let after_number = self.pos;
+            let generation = self.token();
+            if !generation.is_empty() && generation.iter().all(u8::is_ascii_digit) && self.token() == b"R" {
+                return Ok(Object::Reference(u32::try_from(value).map_err(|_| damaged())?));
+            }
+            self.pos = after_number;
+        }
+        Ok(Object::Integer(value))
+    }
+}
+
+/// Where an object is stored
+#[derive(Clone, Copy)]
+enum Location {
+    Free,
+    Offset(usize),
+    /// Object `index` of object stream `stream`
+    Compressed { stream: u32, index: usize },
+}
+
+pub struct Pdf {
+    data: Vec<u8>,
+    xref: HashMap<u32, Location>,
+    trailer: Dict,
+    object_streams: HashMap<u32, Rc<ObjectStream>>,
+}
+
+/// File errors with their Windows error code where there is one
+pub fn io_error(e: std::io::Error) -> Error {
+    match e.raw_os_error() {
+        Some(code) => Error::from(WIN32_ERROR(code as u32).to_hresult()),
+        None => Error::new(E_INVALID_DATA, HSTRING::from(e.to_string())),
+    }
+}
+
+/// A decoded object stream
+struct ObjectStream {
+    /// Number and offset into `data` of each object
+    index: Vec<(u32, usize)>,
+    data: Vec<u8>,
+}
+
+impl Pdf {
+    /// Reads the file at `path` (see `open`)
+    pub fn read(path: &str) -> Result<Self> {
+        Self::open(std::fs::read(crate::utils::long_path(path)).map_err(io_error)?)
+    }
+
+    /// Reads the cross-reference data of the file in `data`
+    pub fn open(mut data: Vec<u8>) -> Result<Self> {
+        // Offsets count from "%PDF", whatever precedes it
+        match find(&data[..data.len().min(1024)], b"%PDF-", 0) {
+            Some(0) => {}
+            Some(start) => {
+                data.drain(..start);
+            }
+            None => return Err(damaged()),
+        }
+
+        let mut pdf = Pdf { data, xref: HashMap::new(), trailer: Dict::new(), object_streams: HashMap::new() };
+        let read = pdf.read_xref();
+        // The catalog may be in an object stream, which cannot be read when encrypted
+        if read.is_ok() && pdf.is_encrypted() {
+            return Ok(pdf);
+        }
+        if read.is_err() || pdf.catalog().is_err() {
+            pdf.reconstruct_xref();
+            if !pdf.is_encrypted() {
+                pdf.catalog()?;
+            }
+        }
+        Ok(pdf)
+    }
+
+    /// Strings, streams and object streams of encrypted files cannot be read; callers
+    /// decline these files
+    pub fn is_encrypted(&self) -> bool {
+        get(&self.trailer, b"Encrypt").is_some()
+    }
+
+    /// Size of the file in bytes
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// "1.7" from the "%PDF-1.7" header
+    pub fn version(&self) -> &[u8] {
+        self.data.get(5..8).filter(|v| v[0].is_ascii_digit() && v[1] == b'.' && v[2].is_ascii_digit()).unwrap_or(b"1.7")
+    }
+
+    /// Object number of the catalog
+    pub fn root(&self) -> Option<u32> {
+        match get(&self.trailer, b"Root") {
+            Some(Object::Reference(root)) => Some(*root),
+            _ => None,
+        }
+    }
+
+    /// Reads the cross-reference sections from the last one back along /Prev
+    fn read_xref(&mut self) -> Result<()> {
+        let tail_start = self.data.len().saturating_sub(1024);
+        let startxref = rfind(&self.data[tail_start..], b"startxref").ok_or_else(damaged)? + tail_start;
+        let mut next = Some(Parser::at(&self.data, startxref + 9)?.integer()?);
+        let mut visited = HashSet::new();
+
+        while let Some(offset) = next.and_then(|o| usize::try_from(o).ok()) {
+            if !visited.insert(offset) {
+                break;
+            }
+            let mut parser = Parser::at(&self.data, offset)?;
+            let trailer = if parser.next_is(b"xref") {
+                self.read_xref_table(parser.pos)?
+            } else {
+                self.read_xref_stream(offset)?
+            };
+            // Hybrid files keep the compressed objects in an extra xref stream
+            if let Some(stream) = get(&trailer, b"XRefStm").and_then(Object::as_integer) {
+                if let Ok(stream) = usize::try_from(stream) {
+                    if visited.insert(stream) {
+                        self.read_xref_stream(stream)?;
+                    }
+                }
+            }
+            next = get(&trailer, b"Prev").and_then(Object::as_integer);
+            if self.trailer.is_empty() {
+                self.trailer = trailer;
+            }
+        }
+        Ok(())
+    }
+
+    /// Classic table after the "xref" keyword; returns the trailer dictionary
+    fn read_xref_table(&mut self, pos: usize) -> Result<Dict> {
+        let mut parser = Parser::at(&self.data, pos)?;
+        loop {
+            if parser.next_is(b"trailer") {
+                return match parser.object(0)? {
+                    Object::Dict(dict) => Ok(dict),
+                    _ => Err(damaged()),
+                };
+            }
+            let first = parser.integer()?;
+            let count = parser.integer()?;
+            for number in first..first.checked_add(count).ok_or_else(damaged)? {
+                let offset = parser.integer()?;
+                let _generation = parser.integer()?;
+                let location = match parser.token() {
+                    b"n" => Location::Offset(usize::try_from(offset).map_err(|_| damaged())?),
+                    b"f" => Location::Free,
+                    _ => return Err(damaged()),
+                };
+                // Newer sections are read first and win
+                if let Ok(number) = u32::try_from(number) {
+                    self.xref.entry(number).or_insert(location);
+                }
+            }
+        }
+    }
+
+    /// Cross-reference stream at `offset`; returns its dictionary as the trailer
+    fn read_xref_stream(&mut self, offset: usize) -> Result<Dict> {
+        let Object::Stream(dict, raw) = self.parse_indirect(offset)?.1 else {
+            return Err(damaged());
+        };
+        let data = decode_stream(&dict, &raw)?;
+        let widths: Vec<usize> = match get(&dict, b"W") {
+            Some(Object::Array(items)) => items.iter().map(|w| w.as_integer().and_then(|w| usize::try_from(w).ok())).collect::<Option<_>>(),
+            _ => None,
+        }
+        .filter(|w: &Vec<usize>| w.len() == 3 && w.iter().all(|&w| w <= 8))
+        .ok_or_else(damaged)?;
+        let size = get(&dict, b"Size").and_then(Object::as_integer).ok_or_else(damaged)?;
+        let index: Vec<i64> = match get(&dict, b"Index") {
+            Some(Object::Array(items)) => items.iter().map(Object::as_integer).collect::<Option<_>>().ok_or_else(damaged)?,
+            _ => vec![0, size],
+        };
+
+        let entry_len: usize = widths.iter().sum();
+        let mut entries = data.chunks_exact(entry_len.max(1));
+        for range in index.chunks_exact(2) {
+            for number in range[0]..range[0].saturating_add(range[1]) {
+                let Some(entry) = entries.next() else {
+                    return Ok(dict);
+                };
+                let mut fields = [0u64; 3];
+                let mut pos = 0;
+                for (field, &width) in fields.iter_mut().zip(&widths) {
+                    *field = entry[pos..pos + width].iter().fold(0, |v, &b| (v << 8) | b as u64);
+                    pos += width;
+                }
+                // Without a type field every entry is an uncompressed object
+                let kind = if widths[0] == 0 { 1 } else { fields[0] };
+                let location = match kind {
+                    0 => Location::Free,
+                    1 => Location::Offset(fields[1] as usize),
+                    2 => Location::Compressed { stream: fields[1] as u32, index: fields[2] as usize },
+                    _ => continue,
+                };
+                if let Ok(number) = u32::try_from(number) {
+                    self.xref.entry(number).or_insert(location);
+                }
+            }
+        }
+        Ok(dict)
+    }
+
+    /// Rebuilds the cross-reference data of a damaged file from the "N G obj" headers
+    fn reconstruct_xref(&mut self) {
+        self.xref.clear();
+        self.object_streams.clear();
+        let mut pos = 0;
+        while let Some(found) = find(&self.data, b"obj", pos) {
+            pos = found + 3;
+            if self.data.get(pos).is_some_and(|&b| is_regular(b)) {
+                continue; // "endobj" is followed by a line break, but has no "N G" before it
+            }
+            if let Some((number, start)) = object_header_before(&self.data, found) {
+                // Later copies of an object are newer
+                self.xref.insert(number, Location::Offset(start));
+            }
+        }
+
+        // Trailer: the last "trailer" dictionary, else an xref stream's dictionary
+        let mut trailer = rfind(&self.data, b"trailer")
+            .and_then(|at| match Parser::new(&self.data, at + 7).object(0) {
+                Ok(Object::Dict(dict)) => Some(dict),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let mut numbers: Vec<u32> = self.xref.keys().copied().collect();
+        numbers.sort_unstable();
+        for number in numbers {
+            let Ok(object) = self.object(number) else { continue };
+            let Some(dict) = object.as_dict() else { continue };
+            if get(dict, b"Type").is_some_and(|t| t.is_name(b"XRef")) {
+                for key in [&b"Root"[..], b"Encrypt"] {
+                    if get(&trailer, key).is_none() {
+                        if let Some(value) = get(dict, key) {
+                            trailer.push((key.to_vec(), value.clone()));
+                        }
+                    }
+                }
+            } else if get(dict, b"Type").is_some_and(|t| t.is_name(b"Catalog")) && get(&trailer, b"Root").is_none() {
+                trailer.push((b"Root".to_vec(), Object::Reference(number)));
+            } else if get(dict, b"Type").is_some_and(|t| t.is_name(b"ObjStm")) {
+                if let Ok(stream) = self.object_stream(number) {
+                    for (index, &(contained, _)) in stream.index.iter().enumerate() {
+                        self.xref.entry(contained).or_insert(Location::Compressed { stream: number, index });
+                    }
+                }
+            }
+        }
+        self.trailer = trailer;
+    }
+
+    /// The object `number`; missing and free objects are null
+    pub fn object(&mut self, number: u32) -> Result<Object> {
+        match self.xref.get(&number).copied() {
+            None | Some(Location::Free) => Ok(Object::Null),
+            Some(Location::Offset(offset)) => {
+                let (found, object) = self.parse_indirect(offset)?;
+                if found != number {
+                    return Err(damaged());
+                }
+                Ok(object)
+            }
+            Some(Location::Compressed { stream, index }) => {
+                let stream = self.object_stream(stream)?;
+                let (found, offset) = *stream.index.get(index).ok_or_else(damaged)?;
+                if found != number {
+                    return Err(damaged());
+                }
+                Parser::at(&stream.data, offset)?.object(0)
+            }
+        }
+    }
+
+    pub fn resolve(&mut self, object: &Object) -> Result<Object> {
+        match object {
+            Object::Reference(number) => self.object(*number),
+            other => Ok(other.clone()),
+        }
+    }
+
+    /// "N G obj ... endobj" at `offset`
+    fn parse_indirect(&mut self, offset: usize) -> Result<(u32, Object)> {
+        let mut parser = Parser::at(&self.data, offset)?;
+        let number = u32::try_from(parser.integer()?).map_err(|_| damaged())?;
+        let _generation = parser.integer()?;
+        parser.keyword(b"obj")?;
+        let object = parser.object(0)?;
+        let Object::Dict(dict) = object else {
+            return Ok((number, object));
+        };
+        if !parser.next_is(b"stream") {
+            return Ok((number, Object::Dict(dict)));
+        }
+
+        // The data starts after the end of line following "stream"
+        let mut start = parser.pos;
+        if self.data.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if self.data.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+        let length = match get(&dict, b"Length") {
+            Some(Object::Reference(length)) if *length != number => self.object(*length).ok().and_then(|l| l.as_integer()),
+            Some(length) => length.as_integer(),
+            None => None,
+        };
+        let end = length
+            .and_then(|l| usize::try_from(l).ok())
+            .map(|l| start.saturating_add(l))
+            .filter(|&end| {
+                let mut after = Parser::new(&self.data, end.min(self.data.len()));
+                end <= self.data.len() && after.next_is(b"endstream")
+            })
+            .or_else(|| {
+                // Wrong or missing /Length: up to "endstream", without the end of line before it
+                let mut end = find(&self.data, b"endstream", start)?;
+                if end > start && self.data[end - 1] == b'\n' {
+                    end -= 1;
+                }
+                if end > start && self.data[end - 1] == b'\r' {
+                    end -= 1;
+                }
+                Some(end)
+            })
+            .ok_or_else(damaged)?;
+        Ok((number, Object::Stream(dict, self.data[start..end].to_vec())))
+    }
+
+    /// Decoded object stream `number` with its (object number, offset) index
+    fn object_stream(&mut self, number: u32) -> Result<Rc<ObjectStream>> {
+        if let Some(stream) = self.object_streams.get(&number) {
+            return Ok(stream.clone());
+        }
+        // Object streams are never compressed themselves
+        let Some(Location::Offset(offset)) = self.xref.get(&number).copied() else {
+            return Err(damaged());
+        };
+        let Object::Stream(dict, raw) = self.parse_indirect(offset)?.1 else {
+            return Err(damaged());
+        };
+        let data = decode_stream(&dict, &raw)?;
+        let count = get(&dict, b"N").and_then(Object::as_integer).ok_or_else(damaged)?;
+        let first = get(&dict, b"First").and_then(Object::as_integer).and_then(|f| usize::try_from(f).ok()).ok_or_else(damaged)?;
+        let mut parser = Parser::new(&data, 0);
+        let mut index = Vec::new();
+        for _ in 0..count {
+            let contained = u32::try_from(parser.integer()?).map_err(|_| damaged())?;
+            let offset = usize::try_from(parser.integer()?).map_err(|_| damaged())?;
+            index.push((contained, first.checked_add(offset).ok_or_else(damaged)?));
+        }
+        let stream = Rc::new(ObjectStream { index, data });
+        self.object_streams.insert(number, stream.clone());
+        Ok(stream)
+    }
+
+    fn catalog(&mut self) -> Result<Dict> {
+        let root = get(&self.trailer, b"Root").cloned().ok_or_else(damaged)?;
+        match self.resolve(&root)? {
+            Object::Dict(dict) => Ok(dict),
+            _ => Err(damaged()),
+        }
+    }
+
+    /// Page objects in order with their inherited attributes, and the page tree nodes
+    pub fn pages(&mut self) -> Result<(Pages, HashSet<u32>)> {
+        let catalog = self.catalog()?;
+        let Some(Object::Reference(root)) = get(&catalog, b"Pages").cloned() else {
+            return Err(damaged());
+        };
+        let mut pages = Vec::new();
+        let mut nodes = HashSet::new();
+        self.walk_page_tree(root, Dict::new(), &mut pages, &mut nodes, 0)?;
+        Ok((pages, nodes))
+    }
+
+    fn walk_page_tree(&mut self, number: u32, mut inherited: Dict, pages: &mut Pages, nodes: &mut HashSet<u32>, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH || nodes.contains(&number) || pages.iter().any(|(page, _)| *page == number) {
+            return Err(damaged());
+        }
+        let object = self.object(number)?;
+        let dict = object.as_dict().ok_or_else(damaged)?;
+        for key in INHERITABLE {
+            if let Some(value) = get(dict, key) {
+                inherited.retain(|(k, _)| k != key);
+                inherited.push((key.to_vec(), value.clone()));
+            }
+        }
+        let kids = match get(dict, b"Kids") {
+            Some(kids) if !get(dict, b"Type").is_some_and(|t| t.is_name(b"Page")) => self.resolve(&kids.clone())?,
+            _ => {
+                pages.push((number, inherited));
+                return Ok(());
+            }
+        };
+        nodes.insert(number);
+        let Object::Array(kids) = kids else {
+            return Err(damaged());
+        };
+        for kid in kids {
+            if let Object::Reference(kid) = kid {
+                self.walk_page_tree(kid, inherited.clone(), pages, nodes, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decoded data of a stream, given directly or by reference
+    pub fn stream_data(&mut self, object: &Object) -> Result<Vec<u8>> {
+        match self.resolve(object)? {
+            Object::Stream(dict, raw) => decode_stream(&dict, &raw),
+            _ => Err(damaged()),
+        }
+    }
+}
+
+/// The operations of a content stream (or CMap) in order, each as operands and operator.
+/// Inline image data (BI ... ID ... EI) is skipped and stray delimiters are dropped, so
+/// damaged content yields what could be read.
+pub fn content_operations(data: &[u8]) -> Vec<(Vec<Object>, Vec<u8>)> {
+    let mut parser = Parser::new(data, 0);
+    let mut operations = Vec::new();
+    let mut operands = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            break;
+        }
+        let start = parser.pos;
+        if let Ok(object) = parser.object(0) {
+            operands.push(object);
+            continue;
+        }
+        parser.pos = start;
+        let operator = parser.token();
+        if operator.is_empty() {
+            parser.pos += 1;
+            operands.clear();
+            continue;
+        }
+        if operator == b"ID" {
+            // Binary image data up to an "EI" between whitespace
+            let mut from = parser.pos + 1;
+            parser.pos = data.len();
+            while let Some(at) = find(data, b"EI", from) {
+                if is_whitespace(data[at - 1]) && data.get(at + 2).is_none_or(|&b| is_whitespace(b)) {
+                    parser.pos = at + 2;
+                    break;
+                }
+                from = at + 2;
+            }
+            operands.clear();
+            continue;
+        }
+        operations.push((std::mem::take(&mut operands), operator.to_vec()));
+    }
+    operations
+}
+
+
+/// Stream data without its filters; only Flate (with PNG predictors) is needed for
+/// xref and object streams
+fn decode_stream(dict: &Dict, raw: &[u8]) -> Result<Vec<u8>> {
+    let filters: Vec<&Object> = match get(dict, b"Filter") {
+        None => Vec::new(),
+        Some(Object::Array(items)) => items.iter().collect(),
+        Some(filter) => vec![filter],
+    };
+    let mut data = match filters.as_slice() {
+        [] => return Ok(raw.to_vec()),
+        [filter] if filter.is_name(b"FlateDecode") || filter.is_name(b"Fl") => {
+            let mut data = Vec::new();
+            flate2::read::ZlibDecoder::new(raw).take(MAX_STREAM_SIZE + 1).read_to_end(&mut data).map_err(|_| damaged())?;
+            if data.len() as u64 > MAX_STREAM_SIZE {
+                return Err(damaged());
+            }
+            data
+        }
+        _ => return Err(damaged()),
+    };
+
+    let parms = match get(dict, b"DecodeParms") {
+        Some(Object::Array(items)) => items.first().and_then(Object::as_dict),
+        Some(parms) => parms.as_dict(),
+        None => None,
+    };
+    let integer = |key: &[u8], default: i64| parms.and_then(|p| get(p, key)).and_then(Object::as_integer).unwrap_or(default);
+    if integer(b"Predictor", 1) >= 10 {
+        let bits = integer(b"Colors", 1)
+            .checked_mul(integer(b"BitsPerComponent", 8))
+            .and_then(|bits| usize::try_from(bits).ok())
+            .ok_or_else(damaged)?;
+        let columns = usize::try_from(integer(b"Columns", 1)).map_err(|_| damaged())?;
+        let row_len = columns.checked_mul(bits).ok_or_else(damaged)?.div_ceil(8);
+        data = png_unpredict(&data, row_len, bits.div_ceil(8).max(1))?;
+    }
+    Ok(data)
+}
+
+/// Reverses the PNG row filters (a filter type byte before every row). `row_len` comes
+/// from the file; a row longer than the data is damage, not a reason to allocate it.
+fn png_unpredict(data: &[u8], row_len: usize, pixel_len: usize) -> Result<Vec<u8>> {
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    if row_len == 0 || row_len > data.len() {
+        return Err(damaged());
+    }
+    let mut out = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_len];
+    for chunk in data.chunks(row_len + 1) {
+        let (&filter, row) = chunk.split_first().ok_or_else(damaged)?;
+        let mut current = row.to_vec();
+        current.resize(row_len, 0);
+        for i in 0..row_len {
+            let left = if i >= pixel_len { current[i - pixel_len] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= pixel_len { previous[i - pixel_len] } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => {
+                    let p = left as i16 + up as i16 - up_left as i16;
+                    let (pa, pb, pc) = ((p - left as i16).abs(), (p - up as i16).abs(), (p - up_left as i16).abs());
+                    if pa <= pb && pa <= pc {
+                        left
+                    } else if pb <= pc {
+                        up
+                    } else {
+                        up_left
+                    }
+                }
+                _ => return Err(damaged()),
+            };
+            current[i] = current[i].wrapping_add(predicted);
+        }
+        out.extend_from_slice(&current);
+        previous = current;
+    }
+    Ok(out)
+}
+
+/// Object number and start of the "N G" before an "obj" keyword at `at`
+fn object_header_before(data: &[u8], at: usize) -> Option<(u32, usize)> {
+    let mut pos = at;
+    let skip_back = |pos: &mut usize, digits: bool| {
+        let start = *pos;
+        while *pos > 0 && (if digits { data[*pos - 1].is_ascii_digit() } else { is_whitespace(data[*pos - 1]) }) {
+            *pos -= 1;
+        }
+        *pos < start
+    };
+    if !(skip_back(&mut pos, false) && skip_back(&mut pos, true) && skip_back(&mut pos, false)) {
+        return None;
+    }
+    let number_end = pos;
+    if !skip_back(&mut pos, true) || (pos > 0 && is_regular(data[pos - 1])) {
+        return None;
+    }
+    let number = std::str::from_utf8(&data[pos..number_end]).ok()?.parse().ok()?;
+    Some((number, pos))
+}
+
+fn find(data: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|i| i + from)
+}
+
+fn rfind(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).rposition(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn flate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn dict(entries: &[(&[u8], Object)]) -> Dict {
+        entries.iter().map(|(key, value)| (key.to_vec(), value.clone())).collect()
+    }
+
+    fn predictor_parms(colors: i64, bits: i64, columns: i64) -> Object {
+        Object::Dict(dict(&[
+            (b"Predictor", Object::Integer(12)),
+            (b"Colors", Object::Integer(colors)),
+            (b"BitsPerComponent", Object::Integer(bits)),
+            (b"Columns", Object::Integer(columns)),
+        ]))
+    }
+
+    #[test]
+    fn decodes_png_predicted_rows() {
+        // Two rows of three bytes, the second with the "up" filter
+        let raw = flate(&[0, 1, 2, 3, 2, 1, 1, 1]);
+        let dict = dict(&[(b"Filter", Object::Name(b"FlateDecode".to_vec())), (b"DecodeParms", predictor_parms(1, 8, 3))]);
+        assert_eq!(decode_stream(&dict, &raw).unwrap(), [1, 2, 3, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_overflowing_predictor_parameters() {
+        let raw = flate(&[0, 1, 2, 3]);
+        for parms in [predictor_parms(i64::MAX, 8, 1), predictor_parms(1, 8, i64::MAX), predictor_parms(-1, 8, 1)] {
+            let dict = dict(&[(b"Filter", Object::Name(b"FlateDecode".to_vec())), (b"DecodeParms", parms)]);
+            assert!(decode_stream(&dict, &raw).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_rows_longer_than_the_data() {
+        assert!(png_unpredict(&[0, 1, 2, 3], 1 << 40, 1).is_err());
+        assert!(png_unpredict(&[0, 1, 2, 3], 0, 1).is_err());
+        assert!(png_unpredict(&[], 1 << 40, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_streams_inflating_past_the_limit() {
+        let raw = flate(&vec![0u8; MAX_STREAM_SIZE as usize + 1]);
+        let dict = dict(&[(b"Filter", Object::Name(b"FlateDecode".to_vec()))]);
+        assert!(decode_stream(&dict, &raw).is_err());
+    }
+
+    /// File with `objects` numbered from 1, a classic xref table and /Root 1 0 R
+    fn file(objects: &[&str]) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = out.len();
+        out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        out.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes());
+        out
+    }
+
+    const THREE_PAGES: [&str; 5] = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R 5 0 R] /Count 3 /MediaBox [0 0 595 842] >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+        "<< /Type /Page /Parent 2 0 R /Rotate 90 >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+    ];
+
+    /// Pdf over `data` with nothing read yet
+    fn unread(data: Vec<u8>) -> Pdf {
+        Pdf { data, xref: HashMap::new(), trailer: Dict::new(), object_streams: HashMap::new() }
+    }
+
+    fn replace(data: &[u8], from: &str, to: &str) -> Vec<u8> {
+        let at = find(data, from.as_bytes(), 0).unwrap();
+        [&data[..at], to.as_bytes(), &data[at + from.len()..]].concat()
+    }
+
+    #[test]
+    fn reads_a_classic_xref_table() {
+        let mut pdf = unread(file(&THREE_PAGES));
+        pdf.read_xref().unwrap();
+        assert_eq!(pdf.root(), Some(1));
+        assert_eq!(pdf.xref.len(), 6);
+        let (pages, nodes) = pdf.pages().unwrap();
+        assert_eq!(pages.iter().map(|(number, _)| *number).collect::<Vec<_>>(), [3, 4, 5]);
+        assert!(nodes.contains(&2));
+        // Inherited from the page tree
+        assert!(get(&pages[0].1, b"MediaBox").is_some());
+        assert_eq!(pdf.object(4).unwrap().as_dict().and_then(|d| get(d, b"Rotate")).and_then(Object::as_integer), Some(90));
+    }
+
+    #[test]
+    fn startxref_past_the_end_is_damaged() {
+        let data = file(&THREE_PAGES);
+        let xref = find(&data, b"xref\n0 ", 0).unwrap();
+        let data = replace(&data, &format!("startxref\n{}", xref), "startxref\n99999");
+        assert!(unread(data.clone()).read_xref().is_err());
+        // Opening falls back to the object headers
+        assert_eq!(Pdf::open(data).unwrap().pages().unwrap().0.len(), 3);
+    }
+
+    #[test]
+    fn object_offsets_past_the_end_are_damaged() {
+        let data = file(&THREE_PAGES);
+        let entry = format!("{:010} 00000 n", find(&data, b"4 0 obj", 0).unwrap());
+        let mut pdf = unread(replace(&data, &entry, "0000099999 00000 n"));
+        pdf.read_xref().unwrap();
+        assert!(pdf.object(4).is_err());
+        assert!(pdf.object(3).is_ok());
+    }
+
+    #[test]
+    fn overflowing_xref_sections_are_damaged() {
+        let data = replace(&file(&THREE_PAGES), "xref\n0 6", "xref\n9223372036854775807 6");
+        assert!(unread(data).read_xref().is_err());
+    }
+
+    #[test]
+    fn object_stream_offsets_are_checked() {
+        // Object stream 6 holds object 8 far past its data, object stream 7 at an offset
+        // that only /First takes past it
+        let mut objects = THREE_PAGES.to_vec();
+        objects.push("<< /Type /ObjStm /N 1 /First 8 /Length 7 >>\nstream\n8 99999\nendstream");
+        objects.push("<< /Type /ObjStm /N 1 /First 9223372036854775807 /Length 21 >>\nstream\n8 9223372036854775807\nendstream");
+        let mut pdf = unread(file(&objects));
+        pdf.read_xref().unwrap();
+        pdf.xref.insert(8, Location::Compressed { stream: 6, index: 0 });
+        assert!(pdf.object(8).is_err());
+        pdf.xref.insert(8, Location::Compressed { stream: 7, index: 0 });
+        assert!(pdf.object(8).is_err());
+    }
+
+    #[test]
+    fn truncated_files_do_not_panic() {
+        let data = file(&THREE_PAGES);
+        for len in 0..data.len() {
+            if let Ok(mut pdf) = Pdf::open(data[..len].to_vec()) {
+                let _ = pdf.pages();
+            }
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Text of PDF pages, for "Ordner durchsuchen"
+//!
+//! The text a page draws (Tj, TJ, ' and ") is mapped to Unicode through the font's
+//! /ToUnicode CMap, else through WinAnsiEncoding with the glyph names of /Differences.
+//! Moves to another line become line breaks and wide gaps spaces. The order is that of the
+//! content stream, which for most files is the reading order. Text in form XObjects and of
+//! composite fonts without /ToUnicode is not found, and scanned PDFs have no text at all.
+
+use crate::pdf_structure::{content_operations, get, Dict, Object, Pdf};
+use std::collections::HashMap;
+use windows::core::*;
+
+/// HRESULT_FROM_WIN32(ERROR_INVALID_DATA)
+const E_INVALID_DATA: HRESULT = HRESULT(0x8007000Du32 as i32);
+
+/// A TJ gap wider than this (thousandths of the font size) separates words
+const WORD_GAP: f64 = 200.0;
+/// Largest bfrange that is expanded, against damaged CMaps
+const MAX_RANGE: u32 = 0x10000;
+
+/// Glyph names of /Differences beyond single letters, "uniXXXX" and "uXXXX[XX]"
+const GLYPH_NAMES: [(&str, &str); 60] = [
+    ("space", " "),
+    ("exclam", "!"),
+    ("quotedbl", "\""),
+    ("numbersign", "#"),
+    ("dollar", "$"),
+    ("percent", "%"),
+    ("ampersand", "&"),
+    ("quotesingle", "'"),
+    ("quoteright", "’"),
+    ("quoteleft", "‘"),
+    ("parenleft", "("),
+    ("parenright", ")"),
+    ("asterisk", "*"),
+    ("plus", "+"),
+    ("comma", ","),
+    ("hyphen", "-"),
+    ("period", "."),
+    ("slash", "/"),
+    ("zero", "0"),
+    ("one", "1"),
+    ("two", "2"),
+    ("three", "3"),
+    ("four", "4"),
+    ("five", "5"),
+    ("six", "6"),
+    ("seven", "7"),
+    ("eight", "8"),
+    ("nine", "9"),
+    ("colon", ":"),
+    ("semicolon", ";"),
+    ("less", "<"),
+    ("equal", "="),
+    ("greater", ">"),
+    ("question", "?"),
+    ("at", "@"),
+    ("bracketleft", "["),
+    ("bracketright", "]"),
+    ("underscore", "_"),
+    ("adieresis", "ä"),
+    ("odieresis", "ö"),
+    ("udieresis", "ü"),
+    ("Adieresis", "Ä"),
+    ("Odieresis", "Ö"),
+    ("Udieresis", "Ü"),
+    ("germandbls", "ß"),
+    ("eacute", "é"),
+    ("egrave", "è"),
+    ("agrave", "à"),
+    ("ccedilla", "ç"),
+    ("endash", "–"),
+    ("emdash", "—"),
+    ("quotedblleft", "“"),
+    ("quotedblright", "”"),
+    ("quotedblbase", "„"),
+    ("bullet", "•"),
+    ("ellipsis", "…"),
+    ("section", "§"),
+    ("Euro", "€"),
+    ("fi", "fi"),
+    ("fl", "fl"),
+];
+
+/// Text of every page of the PDF file `path`, lines separated by "\n"; pages that cannot
+/// be read are empty
+pub fn page_texts(path: &str) -> Result<Vec<String>> {
+    let mut pdf = Pdf::read(path)?;
+    if pdf.is_encrypted() {
+        return Err(Error::new(E_INVALID_DATA, HSTRING::from("Der Text verschlüsselter PDF-Dateien kann nicht gelesen werden.")));
+    }
+    let (pages, _) = pdf.pages()?;
+    Ok(pages.iter().map(|(number, inherited)| page_text(&mut pdf, *number, inherited).unwrap_or_default()).collect())
+}
+
+fn page_text(pdf: &mut Pdf, number: u32, inherited: &Dict) -> Result<String> {
+    let page = pdf.object(number)?;
+    let Some(contents) = page.as_dict().and_then(|d| get(d, b"Contents")).cloned() else {
+        return Ok(String::new());
+    };
+    let data = match pdf.resolve(&contents)? {
+        Object::Array(parts) => {
+            let mut data = Vec::new();
+            for part in &parts {
+                data.extend(pdf.stream_data(part)?);
+                data.push(b'\n');
+            }
+            data
+        }
+        stream => pdf.stream_data(&stream)?,
+    };
+    let font_resources = font_resources(pdf, inherited);
+
+    let mut text = String::new();
+    let mut fonts: HashMap<Vec<u8>, Font> = HashMap::new();
+    let mut current: Option<Vec<u8>> = None;
+    // Baseline of the last Tm, to tell a new line from a move along the line
+    let mut line_y: Option<f64> = None;
+    for (operands, operator) in content_operations(&data) {
+        match operator.as_slice() {
+            b"Tf" => {
+                let Some(name) = operands.first().and_then(Object::as_name) else { continue };
+                if !fonts.contains_key(name) {
+                    let font = get(&font_resources, name).cloned().unwrap_or(Object::Null);
+                    fonts.insert(name.to_vec(), Font::load(pdf, &font));
+                }
+                current = Some(name.to_vec());
+            }
+            b"Tj" | b"'" | b"\"" => {
+                if operator != b"Tj" {
+                    new_line(&mut text);
+                }
+                let font = current.as_ref().and_then(|name| fonts.get(name));
+                if let (Some(font), Some(bytes)) = (font, operands.last().and_then(Object::as_bytes)) {
+                    font.decode(&bytes, &mut text);
+                }
+            }
+            b"TJ" => {
+                let (Some(font), Some(Object::Array(items))) = (current.as_ref().and_then(|name| fonts.get(name)), operands.first()) else {
+                    continue;
+                };
+                for item in items {
+                    match item.as_bytes() {
+                        Some(bytes) => font.decode(&bytes, &mut text),
+                        None if item.as_number().is_some_and(|gap| gap < -WORD_GAP) => space(&mut text),
+                        None => {}
+                    }
+                }
+            }
+            b"Td" | b"TD" => {
+                if operands.get(1).and_then(Object::as_number).is_some_and(|y| y.abs() > 0.01) {
+                    new_line(&mut text);
+                } else {
+                    space(&mut text);
+                }
+            }
+            b"Tm" => {
+                let y = operands.get(5).and_then(Object::as_number);
+                if line_y.is_some() && y != line_y {
+                    new_line(&mut text);
+                } else {
+                    space(&mut text);
+                }
+                line_y = y;
+            }
+            b"T*" => new_line(&mut text),
+            b"ET" => space(&mut text),
+            _ => {}
+        }
+    }
+
+    // Form feeds separate the pages in the text index
+    let text: String = text.chars().map(|c| if c.is_control() && c != '\n' { ' ' } else { c }).collect();
+    Ok(text.trim_end().to_string())
+}
+
+/// The /Font dictionary of the page resources
+fn font_resources(pdf: &mut Pdf, inherited: &Dict) -> Dict {
+    let Some(resources) = get(inherited, b"Resources").and_then(|r| pdf.resolve(r).ok()) else {
+        return Dict::new();
+    };
+    let Some(fonts) = resources.as_dict().and_then(|r| get(r, b"Font")).and_then(|f| pdf.resolve(f).ok()) else {
+        return Dict::new();
+    };
+    fonts.as_dict().cloned().unwrap_or_default()
+}
+
+fn new_line(text: &mut String) {
+    let trimmed = text.trim_end_matches(' ').len();
+    text.truncate(trimmed);
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+fn space(text: &mut String) {
+    if !text.is_empty() && !text.ends_with([' ', '\n']) {
+        text.push(' ');
+    }
+}
+
+struct Font {
+    /// Bytes per character code: 1 for simple fonts, usually 2 for composite (Type0) fonts
+    code_bytes: usize,
+    /// From /ToUnicode
+    to_unicode: HashMap<u32, String>,
+    /// Text of the 256 codes of a simple font; `None` for composite fonts
+    encoding: Option<Vec<String>>,
+}
+
+impl Font {
+    fn load(pdf: &mut Pdf, font: &Object) -> Self {
+        let dict = pdf.resolve(font).ok().and_then(|f| f.as_dict().cloned()).unwrap_or_default();
+        let composite = get(&dict, b"Subtype").is_some_and(|s| s.is_name(b"Type0"));
+        let mut font = Font { code_bytes: if composite { 2 } else { 1 }, to_unicode: HashMap::new(), encoding: None };
+        if let Some(cmap) = get(&dict, b"ToUnicode").and_then(|c| pdf.stream_data(c).ok()) {
+            font.read_cmap(&cmap);
+        }
+        if !composite {
+            font.code_bytes = 1;
+            font.encoding = Some(simple_encoding(pdf, &dict));
+        }
+        font
+    }
+
+    fn read_cmap(&mut self, data: &[u8]) {
+        for (operands, operator) in content_operations(data) {
+            match operator.as_slice() {
+                b"endcodespacerange" => {
+                    if let Some(low) = operands.first().and_then(Object::as_bytes).filter(|low| !low.is_empty()) {
+                        self.code_bytes = low.len().min(4);
+                    }
+                }
+                b"endbfchar" => {
+                    for pair in operands.chunks_exact(2) {
+                        if let (Some(code), Some(target)) = (pair[0].as_bytes(), pair[1].as_bytes()) {
+                            self.to_unicode.insert(code_value(&code), String::from_utf16_lossy(&utf16_units(&target)));
+                        }
+                    }
+                }
+                b"endbfrange" => {
+                    for range in operands.chunks_exact(3) {
+                        let (Some(low), Some(high)) = (range[0].as_bytes(), range[1].as_bytes()) else { continue };
+                        let (low, high) = (code_value(&low), code_value(&high));
+                        if high < low || high - low >= MAX_RANGE {
+                            continue;
+                        }
+                        match &range[2] {
+                            Object::Array(targets) => {
+                                for (code, target) in (low..=high).zip(targets) {
+                                    if let Some(target) = target.as_bytes() {
+                                        self.to_unicode.insert(code, String::from_utf16_lossy(&utf16_units(&target)));
+                                    }
+                                }
+                            }
+                            target => {
+                                // Consecutive codes map to consecutive characters
+                                let Some(mut units) = target.as_bytes().map(|t| utf16_units(&t)) else { continue };
+                                for code in low..=high {
+                                    self.to_unicode.insert(code, String::from_utf16_lossy(&units));
+                                    if let Some(last) = units.last_mut() {
+                                        *last = last.wrapping_add(1);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Appends the text of the character codes `bytes`
+    fn decode(&self, bytes: &[u8], text: &mut String) {
+        for code in bytes.chunks(self.code_bytes.max(1)) {
+            let code = code_value(code);
+            if let Some(mapped) = self.to_unicode.get(&code) {
+                text.push_str(mapped);
+            } else if let Some(mapped) = self.encoding.as_ref().and_then(|encoding| encoding.get(code as usize)) {
+                text.push_str(mapped);
+            }
+        }
+    }
+}
+
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |value, &b| value << 8 | b as u32)
+}
+
+/// UTF-16BE units of a CMap target; a single byte is taken as it is
+fn utf16_units(bytes: &[u8]) -> Vec<u16> {
+    if let [byte] = bytes {
+        return vec![*byte as u16];
+    }
+    bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect()
+}
+
+/// WinAnsiEncoding with the /Differences of the font's /Encoding. The other base encodings
+/// (Standard, MacRoman) differ in rarely used characters only and are read as WinAnsi.
+fn simple_encoding(pdf: &mut Pdf, font: &Dict) -> Vec<String> {
+    let mut table: Vec<String> = (0..=255u8).map(|code| win_ansi(code).map(String::from).unwrap_or_default()).collect();
+    let encoding = get(font, b"Encoding").and_then(|e| pdf.resolve(e).ok());
+    let Some(differences) = encoding.as_ref().and_then(Object::as_dict).and_then(|e| get(e, b"Differences")).and_then(|d| pdf.resolve(d).ok()) else {
+        return table;
+    };
+    let Object::Array(items) = differences else {
+        return table;
+    };
+    let mut code = 0usize;
+    for item in &items {
+        match item {
+            Object::Integer(start) => code = usize::try_from(*start).unwrap_or(usize::MAX),
+            Object::Name(name) => {
+                if let Some(slot) = table.get_mut(code) {
+                    // Names of subset fonts like "g12" say nothing about the character
+                    *slot = glyph_text(name).unwrap_or_default();
+                }
+                code = code.saturating_add(1);
+            }
+            _ => {}
+        }
+    }
+    table
+}
+
+fn win_ansi(code: u8) -> Option<char> {
+    const HIGH: [char; 32] = [
+        '€', '\0', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\0', 'Ž', '\0', '\0', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›',
+        'œ', '\0', 'ž', 'Ÿ',
+    ];
+    match code {
+        0x20..=0x7E | 0xA0..=0xFF => Some(code as char),
+        0x80..=0x9F => Some(HIGH[(code - 0x80) as usize]).filter(|&c| c != '\0'),
+        _ => None,
+    }
+}
+
+fn glyph_text(name: &[u8]) -> Option<String> {
+    let name = std::str::from_utf8(name).ok()?;
+    if name.len() == 1 && name.as_bytes()[0].is_ascii_alphabetic() {
+        return Some(name.to_string());
+    }
+    if let Some(&(_, text)) = GLYPH_NAMES.iter().find(|(glyph, _)| *glyph == name) {
+        return Some(text.to_string());
+    }
+    let hex = match name.strip_prefix("uni") {
+        Some(hex) => hex.get(..4)?,
+        None => name.strip_prefix('u').filter(|hex| (4..=6).contains(&hex.len()))?,
+    };
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?).map(String::from)
+}
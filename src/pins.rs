@@ -0,0 +1,172 @@
+//! "Ausschnitt anheften": a copy of the selection in a small window that stays on top
+//!
+//! The selected region is copied as displayed (rotation, watermark) and scaled to the
+//! current zoom, so a detail of one page can be kept in sight while scrolling to another.
+//! The copy does not follow the view. Pins are moved by dragging anywhere, closed with
+//! their close button, and all of them close when another document is opened.
+
+use crate::wic::ClipboardBitmapData;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Graphics::Gdi::*,
+        UI::WindowsAndMessaging::*,
+    },
+};
+
+/// Pins open at once
+pub const MAX_PINS: usize = 5;
+
+const CLASS_NAME: PCWSTR = w!("SimpliViewPin");
+
+/// Window of one pin, owned by the main window. The window may have been closed by the
+/// user already (see `is_open`).
+pub struct Pin {
+    hwnd: HWND,
+}
+
+impl Pin {
+    /// Opens a pin showing `pixels` (bottom-up BGRA as from `get_bitmap_for_clipboard`,
+    /// owned by the window from now on) at `size` (client pixels) with its top left corner at
+    /// `position` (screen), moved and shrunk to fit the work area of that monitor
+    pub fn new(owner: HWND, instance: HMODULE, title: &str, pixels: ClipboardBitmapData, position: POINT, size: (i32, i32)) -> Result<Self> {
+        unsafe {
+            let wc = WNDCLASSEXW {
+                cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
+                lpfnWndProc: Some(pin_wnd_proc),
+                hInstance: instance,
+                hCursor: LoadCursorW(None, IDC_SIZEALL)?,
+                lpszClassName: CLASS_NAME,
+                ..Default::default()
+            };
+            let _ = RegisterClassExW(&wc);
+
+            // Not activated on click, so the keyboard stays with the main window
+            let ex_style = WS_EX_TOOLWINDOW | WS_EX_TOPMOST | WS_EX_NOACTIVATE;
+            let style = WS_POPUP | WS_CAPTION | WS_SYSMENU;
+            // Frame and caption around the client area
+            let mut frame = RECT::default();
+            let _ = AdjustWindowRectEx(&mut frame, style, false, ex_style);
+            let rect = fit_to_work_area(position, size, frame);
+
+            let title = HSTRING::from(title);
+            let hwnd = CreateWindowExW(
+                ex_style,
+                CLASS_NAME,
+                &title,
+                style,
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                owner,
+                None,
+                instance,
+                None,
+            );
+            if hwnd.0 == 0 {
+                return Err(Error::from_win32());
+            }
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, Box::into_raw(Box::new(pixels)) as isize);
+            ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+            Ok(Self { hwnd })
+        }
+    }
+
+    /// False once the user has closed the pin
+    pub fn is_open(&self) -> bool {
+        unsafe { IsWindow(self.hwnd).as_bool() }
+    }
+
+    pub fn close(&self) {
+        if self.is_open() {
+            unsafe {
+                let _ = DestroyWindow(self.hwnd);
+            }
+        }
+    }
+}
+
+/// Window rectangle for a client area of `size` at `position`, with `frame` around it
+/// (from AdjustWindowRectEx of an empty rectangle), on the work area of the monitor at
+/// `position`. A client area too large for it shrinks keeping its aspect ratio.
+fn fit_to_work_area(position: POINT, size: (i32, i32), frame: RECT) -> RECT {
+    let (frame_width, frame_height) = (frame.right - frame.left, frame.bottom - frame.top);
+    let (client_width, client_height) = (size.0.max(1), size.1.max(1));
+    let mut monitor_info = MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+    if !unsafe { GetMonitorInfoW(MonitorFromPoint(position, MONITOR_DEFAULTTONEAREST), &mut monitor_info) }.as_bool() {
+        return RECT { left: position.x, top: position.y, right: position.x + client_width + frame_width, bottom: position.y + client_height + frame_height };
+    }
+    let work = monitor_info.rcWork;
+    let scale = ((work.right - work.left - frame_width) as f32 / client_width as f32)
+        .min((work.bottom - work.top - frame_height) as f32 / client_height as f32)
+        .min(1.0);
+    let width = (client_width as f32 * scale) as i32 + frame_width;
+    let height = (client_height as f32 * scale) as i32 + frame_height;
+    // The picture stays where the selection was; the caption goes above it
+    let left = (position.x + frame.left).clamp(work.left, (work.right - width).max(work.left));
+    let top = (position.y + frame.top).clamp(work.top, (work.bottom - height).max(work.top));
+    RECT { left, top, right: left + width, bottom: top + height }
+}
+
+extern "system" fn pin_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    unsafe {
+        let pixels = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const ClipboardBitmapData;
+        match msg {
+            // Dragging the picture moves the pin
+            WM_NCHITTEST => match DefWindowProcW(hwnd, msg, wparam, lparam) {
+                LRESULT(hit) if hit == HTCLIENT as isize => LRESULT(HTCAPTION as isize),
+                hit => hit,
+            },
+            WM_ERASEBKGND => LRESULT(1),
+            WM_PAINT => {
+                let mut ps = PAINTSTRUCT::default();
+                let hdc = BeginPaint(hwnd, &mut ps);
+                let mut client = RECT::default();
+                let _ = GetClientRect(hwnd, &mut client);
+                if let Some(pixels) = pixels.as_ref() {
+                    let info = BITMAPINFO {
+                        bmiHeader: BITMAPINFOHEADER {
+                            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                            biWidth: pixels.width as i32,
+                            biHeight: pixels.height as i32,
+                            biPlanes: 1,
+                            biBitCount: 32,
+                            biCompression: BI_RGB.0 as u32,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    };
+                    SetStretchBltMode(hdc, HALFTONE);
+                    let _ = SetBrushOrgEx(hdc, 0, 0, None);
+                    StretchDIBits(
+                        hdc,
+                        0,
+                        0,
+                        client.right,
+                        client.bottom,
+                        0,
+                        0,
+                        pixels.width as i32,
+                        pixels.height as i32,
+                        Some(pixels.data.as_ptr() as *const _),
+                        &info,
+                        DIB_RGB_COLORS,
+                        SRCCOPY,
+                    );
+                }
+                let _ = EndPaint(hwnd, &ps);
+                LRESULT(0)
+            }
+            WM_NCDESTROY => {
+                if !pixels.is_null() {
+                    SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+                    drop(Box::from_raw(pixels as *mut ClipboardBitmapData));
+                }
+                DefWindowProcW(hwnd, msg, wparam, lparam)
+            }
+            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+}
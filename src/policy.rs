@@ -0,0 +1,56 @@
+//! Registry policy values
+//!
+//! Administrators configure restricted deployments under `Software\Policies\SimpliView`.
+//! HKLM wins over HKCU. The key is kept outside `Software\SimpliView` so
+//! `--unregister` does not remove it.
+
+use windows::{
+    core::*,
+    Win32::System::Registry::*,
+};
+
+const POLICY_KEY: PCWSTR = w!("Software\\Policies\\SimpliView");
+
+/// Reads a REG_DWORD policy value
+pub fn read_dword(name: PCWSTR) -> Option<u32> {
+    [HKEY_LOCAL_MACHINE, HKEY_CURRENT_USER].into_iter().find_map(|root| {
+        let mut value = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let result = unsafe {
+            RegGetValueW(
+                root,
+                POLICY_KEY,
+                name,
+                RRF_RT_REG_DWORD,
+                None,
+                Some(&mut value as *mut _ as *mut _),
+                Some(&mut size),
+            )
+        };
+        (result.0 == 0).then_some(value)
+    })
+}
+
+/// Reads a REG_SZ policy value
+pub fn read_string(name: PCWSTR) -> Option<String> {
+    [HKEY_LOCAL_MACHINE, HKEY_CURRENT_USER].into_iter().find_map(|root| {
+        let mut buffer = [0u16; 512];
+        let mut size = (buffer.len() * 2) as u32;
+        let result = unsafe {
+            RegGetValueW(
+                root,
+                POLICY_KEY,
+                name,
+                RRF_RT_REG_SZ,
+                None,
+                Some(buffer.as_mut_ptr() as *mut _),
+                Some(&mut size),
+            )
+        };
+        if result.0 != 0 {
+            return None;
+        }
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        Some(String::from_utf16_lossy(&buffer[..len]))
+    })
+}
@@ -0,0 +1,271 @@
+//! GDI print job shared by the print dialog, `--print-folder` and "Als PDF drucken"
+//!
+//! Pages are scaled to fit the printable area between the header and footer bands
+//! (see print_stamp.rs), centered and drawn with StretchDIBits; the watermark and the
+//! header/footer lines go on top as GDI text.
+//!
+//! "Als PDF drucken" opens the "Microsoft Print to PDF" printer by name (`PdfPrinter`) and
+//! passes the target file as the output port of the job, so the driver asks for no name.
+
+use crate::print_stamp::{PrintStamp, StampFields};
+use crate::watermark::Watermark;
+use crate::wic::ClipboardBitmapData;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::{E_FAIL, HGLOBAL},
+        Graphics::Gdi::*,
+        System::Memory::{GlobalLock, GlobalUnlock},
+    },
+};
+
+// GDI print functions - manually linked since windows 0.48 doesn't expose them
+#[link(name = "gdi32")]
+extern "system" {
+    fn StartDocW(hdc: HDC, lpdi: *const DOCINFOW) -> i32;
+    fn EndDoc(hdc: HDC) -> i32;
+    fn StartPage(hdc: HDC) -> i32;
+    fn EndPage(hdc: HDC) -> i32;
+}
+
+#[repr(C)]
+struct DOCINFOW {
+    cb_size: i32,
+    lpsz_doc_name: PCWSTR,
+    lpsz_output: PCWSTR,
+    lpsz_datatype: PCWSTR,
+    fw_type: u32,
+}
+
+pub struct PrintJob {
+    hdc: HDC,
+    /// Document name for the spooler and %FILE%
+    name: String,
+    page_width: i32,
+    page_height: i32,
+    stamp: PrintStamp,
+    header_height: i32,
+    footer_height: i32,
+    date: String,
+    user: String,
+}
+
+impl PrintJob {
+    /// Starts a document on the printer DC, `None` if the spooler refused it. `output` is
+    /// the file the driver writes to instead of its port (print to file).
+    pub fn start(hdc: HDC, name: &str, output: Option<&str>) -> Option<Self> {
+        let name_wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        let output_wide: Option<Vec<u16>> = output.map(|path| path.encode_utf16().chain(std::iter::once(0)).collect());
+        let doc_info = DOCINFOW {
+            cb_size: std::mem::size_of::<DOCINFOW>() as i32,
+            lpsz_doc_name: PCWSTR(name_wide.as_ptr()),
+            lpsz_output: output_wide.as_ref().map_or(PCWSTR::null(), |path| PCWSTR(path.as_ptr())),
+            lpsz_datatype: PCWSTR::null(),
+            fw_type: 0,
+        };
+        unsafe {
+            if StartDocW(hdc, &doc_info) <= 0 {
+                return None;
+            }
+
+            // Header/footer bands are reserved above and below the image
+            let stamp = PrintStamp::load();
+            let (header_height, footer_height) = stamp.band_heights(hdc);
+            Some(Self {
+                hdc,
+                name: name.to_string(),
+                // Printable area in pixels at printer resolution
+                page_width: GetDeviceCaps(hdc, HORZRES),
+                page_height: GetDeviceCaps(hdc, VERTRES),
+                stamp,
+                header_height,
+                footer_height,
+                date: crate::print_stamp::print_date(),
+                user: crate::print_stamp::user_name(),
+            })
+        }
+    }
+
+    /// Prints one page image (bottom-up 32bpp BGRA). `page` is 0-based.
+    /// Returns false if the printer rejected the page.
+    pub fn print_page(&self, bitmap: &ClipboardBitmapData, watermark: Option<&Watermark>, page: usize, pages: usize) -> bool {
+        unsafe {
+            if StartPage(self.hdc) <= 0 {
+                return false;
+            }
+
+            // Scale to fit between header and footer while preserving the aspect ratio
+            let img_width = bitmap.width as i32;
+            let img_height = bitmap.height as i32;
+            let image_height = (self.page_height - self.header_height - self.footer_height).max(1);
+
+            let scale_x = self.page_width as f64 / img_width as f64;
+            let scale_y = image_height as f64 / img_height as f64;
+            let scale = scale_x.min(scale_y);
+
+            let dest_width = (img_width as f64 * scale) as i32;
+            let dest_height = (img_height as f64 * scale) as i32;
+            let dest_x = (self.page_width - dest_width) / 2;
+            let dest_y = self.header_height + (image_height - dest_height) / 2;
+
+            let bmi = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: img_width,
+                    biHeight: img_height, // Positive for bottom-up DIB
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0 as u32,
+                    biSizeImage: bitmap.data.len() as u32,
+                    biXPelsPerMeter: 0,
+                    biYPelsPerMeter: 0,
+                    biClrUsed: 0,
+                    biClrImportant: 0,
+                },
+                bmiColors: [RGBQUAD::default()],
+            };
+
+            // Set stretch mode for better quality
+            SetStretchBltMode(self.hdc, HALFTONE);
+            SetBrushOrgEx(self.hdc, 0, 0, None);
+
+            let result = StretchDIBits(
+                self.hdc,
+                dest_x,
+                dest_y,
+                dest_width,
+                dest_height,
+                0,
+                0,
+                img_width,
+                img_height,
+                Some(bitmap.data.as_ptr() as *const _),
+                &bmi,
+                DIB_RGB_COLORS,
+                SRCCOPY,
+            );
+            if result == 0 {
+                EndPage(self.hdc);
+                return false;
+            }
+
+            // Watermark as GDI text on top of the page image
+            if let Some(watermark) = watermark {
+                watermark.draw_gdi(self.hdc, dest_x, dest_y, dest_width, dest_height);
+            }
+
+            self.stamp.draw(self.hdc, self.page_width, self.page_height, &StampFields {
+                file: &self.name,
+                page: page + 1,
+                pages,
+                date: &self.date,
+                user: &self.user,
+            });
+
+            EndPage(self.hdc) > 0
+        }
+    }
+
+    /// Ends the document. The DC stays owned by the caller.
+    pub fn finish(self) {
+        // AbortDoc would be better after a failed page, but EndDoc is safe enough
+        unsafe {
+            EndDoc(self.hdc);
+        }
+    }
+}
+
+/// Printer of "Als PDF drucken", part of Windows since Windows 10
+pub const PDF_PRINTER: &str = "Microsoft Print to PDF";
+
+/// DC of the `PDF_PRINTER`, without showing the print dialog
+pub struct PdfPrinter {
+    pub hdc: HDC,
+}
+
+impl PdfPrinter {
+    /// Opens the printer with the paper turned to `landscape` or portrait; the other
+    /// settings are the printer's defaults. The error tells how to add a missing printer.
+    pub fn open(landscape: bool) -> Result<Self> {
+        let mut devmode = DEVMODEW {
+            dmSpecVersion: DM_SPECVERSION.0 as u16,
+            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+            dmFields: DM_ORIENTATION,
+            ..Default::default()
+        };
+        for (target, c) in devmode.dmDeviceName.iter_mut().zip(PDF_PRINTER.encode_utf16()) {
+            *target = c;
+        }
+        devmode.Anonymous1.Anonymous1.dmOrientation = if landscape { DMORIENT_LANDSCAPE } else { DMORIENT_PORTRAIT } as i16;
+        let hdc = unsafe { CreateDCW(w!("WINSPOOL"), &HSTRING::from(PDF_PRINTER), None, Some(&devmode)) };
+        if hdc.is_invalid() {
+            return Err(Error::new(
+                E_FAIL,
+                HSTRING::from(format!(
+                    "Der Drucker \"{}\" ist nicht installiert.\n\nEr lässt sich unter \"Windows-Features aktivieren oder deaktivieren\" mit dem Eintrag \"Microsoft Print to PDF\" hinzufügen.",
+                    PDF_PRINTER
+                )),
+            ));
+        }
+        Ok(Self { hdc: HDC(hdc.0) })
+    }
+}
+
+impl Drop for PdfPrinter {
+    fn drop(&mut self) {
+        unsafe {
+            DeleteDC(CreatedHDC(self.hdc.0));
+        }
+    }
+}
+
+/// Copies the printer driver makes itself, from the DEVMODE returned by PrintDlg
+pub fn driver_copies(devmode: HGLOBAL) -> u32 {
+    unsafe {
+        let mode = GlobalLock(devmode) as *const DEVMODEW;
+        if mode.is_null() {
+            return 1;
+        }
+        let copies = if (*mode).dmFields & DM_COPIES == DM_COPIES { (*mode).Anonymous1.Anonymous1.dmCopies } else { 1 };
+        let _ = GlobalUnlock(devmode);
+        copies.max(1) as u32
+    }
+}
+
+/// Order of the pages when the application makes `copies` copies itself: collated
+/// 1, 2, 3, 1, 2, 3, otherwise 1, 1, 2, 2, 3, 3
+pub fn copy_order(pages: &[usize], copies: u32, collate: bool) -> Vec<usize> {
+    let copies = copies.max(1) as usize;
+    if collate {
+        pages.iter().copied().cycle().take(pages.len() * copies).collect()
+    } else {
+        pages.iter().flat_map(|&page| std::iter::repeat_n(page, copies)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collated_copies_repeat_the_whole_range() {
+        assert_eq!(copy_order(&[0, 1, 2], 2, true), [0, 1, 2, 0, 1, 2]);
+        assert_eq!(copy_order(&[4, 7], 3, true), [4, 7, 4, 7, 4, 7]);
+    }
+
+    #[test]
+    fn uncollated_copies_repeat_each_page() {
+        assert_eq!(copy_order(&[0, 1, 2], 2, false), [0, 0, 1, 1, 2, 2]);
+        assert_eq!(copy_order(&[4, 7], 3, false), [4, 4, 4, 7, 7, 7]);
+    }
+
+    #[test]
+    fn one_copy_keeps_the_pages() {
+        for collate in [false, true] {
+            assert_eq!(copy_order(&[2, 0, 1], 1, collate), [2, 0, 1]);
+            // No copies asked for still prints once
+            assert_eq!(copy_order(&[2, 0, 1], 0, collate), [2, 0, 1]);
+            assert!(copy_order(&[], 3, collate).is_empty());
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Print header and footer ("Kopfzeile"/"Fußzeile")
+//!
+//! Compliance stamps every printed page with the source file, print date and page number.
+//! The lines are optional templates with placeholders (%FILE%, %PAGE%, %PAGES%, %DATE%,
+//! %USER%), edited in extra controls added to the print dialog and kept in the settings.
+//! Each enabled line gets its own band at the top or bottom of the printable area, so the
+//! page image is scaled into the remaining space and never overlapped. Lines that are too
+//! wide are shortened in the middle of the file name first, so page numbers stay readable.
+
+use crate::dialogs::add_dialog_control;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Graphics::Gdi::*,
+        System::SystemInformation::GetLocalTime,
+        UI::{Controls::*, WindowsAndMessaging::*},
+    },
+};
+
+const DEFAULT_HEADER: &str = "%FILE%";
+const DEFAULT_FOOTER: &str = "Gedruckt am %DATE% von %USER% – Seite %PAGE% von %PAGES%";
+
+/// Text size on paper
+const FONT_POINTS: i32 = 9;
+/// Maximum template length in the print dialog
+const MAX_TEMPLATE: usize = 512;
+
+// Controls added to the print dialog
+const IDC_HEADER_CHECK: i32 = 0x5A01;
+const IDC_HEADER_EDIT: i32 = 0x5A02;
+const IDC_FOOTER_CHECK: i32 = 0x5A03;
+const IDC_FOOTER_EDIT: i32 = 0x5A04;
+
+/// Values for the placeholders of one page
+pub struct StampFields<'a> {
+    pub file: &'a str,
+    pub page: usize,
+    pub pages: usize,
+    pub date: &'a str,
+    pub user: &'a str,
+}
+
+pub struct PrintStamp {
+    header: Option<String>,
+    footer: Option<String>,
+}
+
+impl PrintStamp {
+    /// Enabled lines from the settings
+    pub fn load() -> Self {
+        let (header_on, header) = crate::settings::print_header();
+        let (footer_on, footer) = crate::settings::print_footer();
+        Self {
+            header: header_on.then(|| header.unwrap_or_else(|| DEFAULT_HEADER.to_string())),
+            footer: footer_on.then(|| footer.unwrap_or_else(|| DEFAULT_FOOTER.to_string())),
+        }
+    }
+
+    /// Heights of the header and footer bands in device pixels (0 when off)
+    pub fn band_heights(&self, hdc: HDC) -> (i32, i32) {
+        let band = font_height(hdc) * 2;
+        (
+            if self.header.is_some() { band } else { 0 },
+            if self.footer.is_some() { band } else { 0 },
+        )
+    }
+
+    /// Draws the enabled lines centered in their bands of a `width` x `height` printable area
+    pub fn draw(&self, hdc: HDC, width: i32, height: i32, fields: &StampFields) {
+        if self.header.is_none() && self.footer.is_none() {
+            return;
+        }
+        let font_height = font_height(hdc);
+        let (header_band, footer_band) = self.band_heights(hdc);
+
+        unsafe {
+            let font = CreateFontW(
+                -font_height,
+                0,
+                0,
+                0,
+                FW_NORMAL.0 as i32,
+                0,
+                0,
+                0,
+                DEFAULT_CHARSET.0 as u32,
+                OUT_DEFAULT_PRECIS.0 as u32,
+                CLIP_DEFAULT_PRECIS.0 as u32,
+                ANTIALIASED_QUALITY.0 as u32,
+                (DEFAULT_PITCH.0 | FF_SWISS.0) as u32,
+                w!("Segoe UI"),
+            );
+            let saved = SaveDC(hdc);
+            SelectObject(hdc, font);
+            SetBkMode(hdc, TRANSPARENT);
+            SetTextColor(hdc, COLORREF(0));
+            SetTextAlign(hdc, TA_CENTER | TA_TOP);
+
+            if let Some(ref template) = self.header {
+                let line = fit_line(hdc, template, fields, width);
+                let _ = TextOutW(hdc, width / 2, (header_band - font_height) / 2, &line);
+            }
+            if let Some(ref template) = self.footer {
+                let line = fit_line(hdc, template, fields, width);
+                let _ = TextOutW(hdc, width / 2, height - footer_band + (footer_band - font_height) / 2, &line);
+            }
+
+            RestoreDC(hdc, saved);
+            DeleteObject(font);
+        }
+    }
+}
+
+/// Print date for %DATE%, taken once per job
+pub fn print_date() -> String {
+    let now = unsafe { GetLocalTime() };
+    format!("{:02}.{:02}.{} {:02}:{:02}", now.wDay, now.wMonth, now.wYear, now.wHour, now.wMinute)
+}
+
+/// Windows user name for %USER%
+pub fn user_name() -> String {
+    std::env::var("USERNAME").unwrap_or_default()
+}
+
+/// 9 pt at the printer's resolution
+fn font_height(hdc: HDC) -> i32 {
+    let dpi = unsafe { GetDeviceCaps(hdc, LOGPIXELSY) };
+    (dpi * FONT_POINTS / 72).max(1)
+}
+
+fn expand(template: &str, fields: &StampFields, file: &str) -> String {
+    template
+        .replace("%FILE%", file)
+        .replace("%PAGES%", &fields.pages.to_string())
+        .replace("%PAGE%", &fields.page.to_string())
+        .replace("%DATE%", fields.date)
+        .replace("%USER%", fields.user)
+}
+
+fn text_width(hdc: HDC, text: &[u16]) -> i32 {
+    let mut size = SIZE::default();
+    unsafe {
+        let _ = GetTextExtentPoint32W(hdc, text, &mut size);
+    }
+    size.cx
+}
+
+/// `keep` characters of `text` with "…" in place of the rest of the middle
+fn elide_middle(chars: &[char], keep: usize) -> String {
+    if keep >= chars.len() {
+        return chars.iter().collect();
+    }
+    let head = keep.div_ceil(2);
+    let tail = keep / 2;
+    chars[..head].iter().chain(std::iter::once(&'…')).chain(&chars[chars.len() - tail..]).collect()
+}
+
+/// Expanded line that fits `max_width`: shortens the file name first, then the whole line
+fn fit_line(hdc: HDC, template: &str, fields: &StampFields, max_width: i32) -> Vec<u16> {
+    let fits = |line: &str| {
+        let wide: Vec<u16> = line.encode_utf16().collect();
+        (text_width(hdc, &wide) <= max_width).then_some(wide)
+    };
+    // Largest `keep` in 0..=len for which `candidate(keep)` fits
+    let longest_fitting = |len: usize, candidate: &dyn Fn(usize) -> String| {
+        let (mut low, mut high) = (0, len);
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            if fits(&candidate(mid)).is_some() {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        fits(&candidate(low))
+    };
+
+    let full = expand(template, fields, fields.file);
+    if let Some(line) = fits(&full) {
+        return line;
+    }
+
+    let file: Vec<char> = fields.file.chars().collect();
+    if template.contains("%FILE%") {
+        if let Some(line) = longest_fitting(file.len(), &|keep| expand(template, fields, &elide_middle(&file, keep))) {
+            return line;
+        }
+    }
+
+    let line: Vec<char> = full.chars().collect();
+    longest_fitting(line.len(), &|keep| elide_middle(&line, keep)).unwrap_or_default()
+}
+
+/// Print dialog WM_INITDIALOG: grows the dialog and adds the header/footer controls above
+/// the OK/Cancel row, filled from the settings
+///
+/// # Safety
+/// `hdlg` must be the print dialog during WM_INITDIALOG.
+pub unsafe fn add_dialog_controls(hdlg: HWND) {
+    let dlu = |x: i32, y: i32| {
+        let mut rect = RECT { left: 0, top: 0, right: x, bottom: y };
+        MapDialogRect(hdlg, &mut rect);
+        (rect.right, rect.bottom)
+    };
+    let (margin, row_height) = dlu(7, 14);
+    let (label_width, gap) = dlu(50, 4);
+    let (_, hint_height) = dlu(0, 10);
+
+    let mut client = RECT::default();
+    let _ = GetClientRect(hdlg, &mut client);
+    let mut top = crate::dialogs::make_room_above_buttons(hdlg, (row_height + gap) * 2 + hint_height + gap * 2);
+
+    let edit_left = margin + label_width;
+    let rows = [
+        (IDC_HEADER_CHECK, IDC_HEADER_EDIT, "Kopfzeile", crate::settings::print_header(), DEFAULT_HEADER),
+        (IDC_FOOTER_CHECK, IDC_FOOTER_EDIT, "Fußzeile", crate::settings::print_footer(), DEFAULT_FOOTER),
+    ];
+    for (check_id, edit_id, label, (enabled, template), default) in rows {
+        let checkbox = RECT { left: margin, top, right: edit_left, bottom: top + row_height };
+        add_dialog_control(hdlg, w!("BUTTON"), label, WS_TABSTOP | WINDOW_STYLE(BS_AUTOCHECKBOX as u32), WINDOW_EX_STYLE::default(), check_id, checkbox);
+        let edit = RECT { left: edit_left, top, right: client.right - margin, bottom: top + row_height };
+        let edit_style = WS_TABSTOP | WINDOW_STYLE(ES_AUTOHSCROLL as u32);
+        add_dialog_control(hdlg, w!("EDIT"), template.as_deref().unwrap_or(default), edit_style, WS_EX_CLIENTEDGE, edit_id, edit);
+        SendDlgItemMessageW(hdlg, edit_id, EM_LIMITTEXT, WPARAM(MAX_TEMPLATE), LPARAM(0));
+        if enabled {
+            let _ = CheckDlgButton(hdlg, check_id, BST_CHECKED);
+        }
+        top += row_height + gap;
+    }
+    let hint = RECT { left: edit_left, top, right: client.right - margin, bottom: top + hint_height };
+    add_dialog_control(
+        hdlg,
+        w!("STATIC"),
+        "Platzhalter: %FILE% %PAGE% %PAGES% %DATE% %USER%",
+        WINDOW_STYLE::default(),
+        WINDOW_EX_STYLE::default(),
+        -1,
+        hint,
+    );
+}
+
+/// Print dialog IDOK: stores the header/footer choice in the settings
+///
+/// # Safety
+/// `hdlg` must be the print dialog after `add_dialog_controls`.
+pub unsafe fn save_dialog_controls(hdlg: HWND) {
+    let read = |check_id: i32, edit_id: i32| {
+        let mut buffer = [0u16; MAX_TEMPLATE + 1];
+        let len = GetDlgItemTextW(hdlg, edit_id, &mut buffer) as usize;
+        (IsDlgButtonChecked(hdlg, check_id) == BST_CHECKED.0, String::from_utf16_lossy(&buffer[..len]))
+    };
+    let (enabled, template) = read(IDC_HEADER_CHECK, IDC_HEADER_EDIT);
+    crate::settings::set_print_header(enabled, &template);
+    let (enabled, template) = read(IDC_FOOTER_CHECK, IDC_FOOTER_EDIT);
+    crate::settings::set_print_footer(enabled, &template);
+}
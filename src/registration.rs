@@ -10,6 +10,9 @@
 //! 2. **Capabilities** - Describes the application and its supported types.
 //! 3. **RegisteredApplications** - Makes the app selectable in Windows Settings.
 //! 4. **OpenWithProgids** - Adds the app to "Open with" context menu.
+//! 5. **URL Protocol** - `simpliview://` deep links from other applications.
+//! 6. **open_restricted verb** (optional) - "Mit SimpliView öffnen (eingeschränkt)" in the
+//!    context menu, restricting the viewer to the file's folder.
 //!
 //! ## Important Notes:
 //!
@@ -18,6 +21,7 @@
 //! - User must manually choose SimpliView as default via Settings or "Open with" dialog.
 //! - Per-user registration (HKCU) - no admin elevation required.
 
+use crate::deeplink::PROTOCOL_SCHEME;
 use windows::{
     core::*,
     Win32::{
@@ -35,12 +39,18 @@ const APP_COMPANY: &str = "SimpliView";
 // Legacy ProgID to clean up
 const LEGACY_PROG_ID: &str = "SimpliView.Document.1";
 
-struct FileTypeInfo {
+// Optional context-menu verb opening a file restricted to its own folder
+const RESTRICTED_VERB: &str = "open_restricted";
+const RESTRICTED_VERB_NAME: &str = "Mit SimpliView öffnen (eingeschränkt)";
+
+pub struct FileTypeInfo {
     extension: &'static str,
     prog_id: &'static str,
     description: &'static str,
     perceived_type: &'static str,
     content_type: &'static str,
+    /// Claimed by a plain `--register`; other types only when listed explicitly
+    by_default: bool,
 }
 
 const FILE_TYPES: &[FileTypeInfo] = &[
@@ -50,6 +60,7 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView PDF Document",
         perceived_type: "Document",
         content_type: "application/pdf",
+        by_default: true,
     },
     FileTypeInfo {
         extension: ".jpg",
@@ -57,6 +68,7 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView Image",
         perceived_type: "Image",
         content_type: "image/jpeg",
+        by_default: true,
     },
     FileTypeInfo {
         extension: ".jpeg",
@@ -64,6 +76,7 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView Image",
         perceived_type: "Image",
         content_type: "image/jpeg",
+        by_default: true,
     },
     FileTypeInfo {
         extension: ".png",
@@ -71,6 +84,7 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView Image",
         perceived_type: "Image",
         content_type: "image/png",
+        by_default: true,
     },
     FileTypeInfo {
         extension: ".bmp",
@@ -78,6 +92,7 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView Image",
         perceived_type: "Image",
         content_type: "image/bmp",
+        by_default: true,
     },
     FileTypeInfo {
         extension: ".tif",
@@ -85,6 +100,7 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView Image",
         perceived_type: "Image",
         content_type: "image/tiff",
+        by_default: true,
     },
     FileTypeInfo {
         extension: ".tiff",
@@ -92,6 +108,7 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView Image",
         perceived_type: "Image",
         content_type: "image/tiff",
+        by_default: true,
     },
     FileTypeInfo {
         extension: ".webp",
@@ -99,9 +116,117 @@ const FILE_TYPES: &[FileTypeInfo] = &[
         description: "SimpliView Image",
         perceived_type: "Image",
         content_type: "image/webp",
+        by_default: true,
+    },
+    FileTypeInfo {
+        extension: ".gif",
+        prog_id: "SimpliView.AssocFile.Image",
+        description: "SimpliView Image",
+        perceived_type: "Image",
+        content_type: "image/gif",
+        by_default: true,
+    },
+    FileTypeInfo {
+        extension: ".ico",
+        prog_id: "SimpliView.AssocFile.Image",
+        description: "SimpliView Image",
+        perceived_type: "Image",
+        content_type: "image/x-icon",
+        by_default: true,
+    },
+    FileTypeInfo {
+        extension: ".heic",
+        prog_id: "SimpliView.AssocFile.Image",
+        description: "SimpliView Image",
+        perceived_type: "Image",
+        content_type: "image/heic",
+        by_default: true,
+    },
+    FileTypeInfo {
+        extension: ".avif",
+        prog_id: "SimpliView.AssocFile.Image",
+        description: "SimpliView Image",
+        perceived_type: "Image",
+        content_type: "image/avif",
+        by_default: true,
+    },
+    FileTypeInfo {
+        extension: ".cbz",
+        prog_id: "SimpliView.AssocFile.Archive",
+        description: "SimpliView Image Archive",
+        perceived_type: "Compressed",
+        content_type: "application/vnd.comicbook+zip",
+        by_default: true,
+    },
+    FileTypeInfo {
+        extension: ".zip",
+        prog_id: "SimpliView.AssocFile.Archive",
+        description: "SimpliView Image Archive",
+        perceived_type: "Compressed",
+        content_type: "application/zip",
+        by_default: true,
+    },
+    FileTypeInfo {
+        extension: ".dcm",
+        prog_id: "SimpliView.AssocFile.Image",
+        description: "SimpliView Image",
+        perceived_type: "Image",
+        content_type: "application/dicom",
+        by_default: false,
     },
 ];
 
+const CAPABILITIES_KEY: &str = "Software\\SimpliView\\Capabilities";
+// Extensions chosen with `--register <list>`, comma separated (e.g. ".pdf,.png")
+const REGISTERED_EXTENSIONS_VALUE: &str = "RegisteredExtensions";
+
+/// Selects the FILE_TYPES entries for `--register pdf,png,tif`.
+/// `None` selects the default types (all but DICOM). The error string is shown to the user as is.
+pub fn select_file_types(list: Option<&str>) -> std::result::Result<Vec<&'static FileTypeInfo>, String> {
+    let list = match list {
+        Some(list) => list,
+        None => return Ok(default_file_types()),
+    };
+
+    let mut selected: Vec<&'static FileTypeInfo> = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let ext = format!(".{}", name.trim_start_matches('.').to_lowercase());
+        let ft = FILE_TYPES
+            .iter()
+            .find(|ft| ft.extension == ext)
+            .ok_or_else(|| format!("Unbekannter Dateityp \"{}\".", name))?;
+        if !selected.iter().any(|s| s.extension == ft.extension) {
+            selected.push(ft);
+        }
+    }
+
+    if selected.is_empty() {
+        return Err("Es wurde kein Dateityp angegeben.".to_string());
+    }
+    Ok(selected)
+}
+
+/// Extensions `--register` can claim, with the leading dot
+pub fn supported_extensions() -> Vec<&'static str> {
+    FILE_TYPES.iter().map(|ft| ft.extension).collect()
+}
+
+fn default_file_types() -> Vec<&'static FileTypeInfo> {
+    FILE_TYPES.iter().filter(|ft| ft.by_default).collect()
+}
+
+/// File types of the current registration (the default types if no subset was stored)
+fn registered_file_types() -> Vec<&'static FileTypeInfo> {
+    let stored = unsafe { read_string_value(HKEY_CURRENT_USER, CAPABILITIES_KEY, REGISTERED_EXTENSIONS_VALUE) };
+    match stored {
+        Some(list) => FILE_TYPES
+            .iter()
+            .filter(|ft| list.split(',').any(|e| e.eq_ignore_ascii_case(ft.extension)))
+            .collect(),
+        None => default_file_types(),
+    }
+}
+
 /// Helper to check if registry operation succeeded
 fn reg_ok(result: WIN32_ERROR) -> bool {
     result.0 == 0
@@ -127,6 +252,28 @@ unsafe fn set_string_value(hkey: HKEY, name: PCWSTR, value: &str) -> bool {
     ))
 }
 
+/// Read a REG_SZ value, `None` if the key or value does not exist
+unsafe fn read_string_value(parent: HKEY, subkey: &str, name: &str) -> Option<String> {
+    let subkey_wide = to_wide(subkey);
+    let name_wide = to_wide(name);
+    let mut buffer = [0u16; 1024];
+    let mut size = (buffer.len() * 2) as u32;
+    let result = RegGetValueW(
+        parent,
+        PCWSTR(subkey_wide.as_ptr()),
+        PCWSTR(name_wide.as_ptr()),
+        RRF_RT_REG_SZ,
+        None,
+        Some(buffer.as_mut_ptr() as *mut _),
+        Some(&mut size),
+    );
+    if !reg_ok(result) {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
 /// Create a registry key and return the handle
 unsafe fn create_key(parent: HKEY, subkey: &str) -> Option<HKEY> {
     let subkey_wide = to_wide(subkey);
@@ -180,44 +327,62 @@ unsafe fn delete_value(hkey: HKEY, name: &str) {
     let _ = RegDeleteValueW(hkey, PCWSTR(name_wide.as_ptr()));
 }
 
-/// Register SimpliView as a file handler (per-user registration)
-pub fn register_file_associations() -> Result<()> {
-    let exe_path = std::env::current_exe()
-        .map_err(|_| Error::from_win32())?
-        .to_string_lossy()
-        .to_string();
+/// Register SimpliView as a file handler (per-user registration).
+///
+/// Only the given `file_types` are claimed (see `select_file_types`); the subset is stored
+/// so `--unregister` and `--diagnose` work on the same types. `restricted_path` is passed
+/// on to the protocol handler command so deep links honour the same restriction as the
+/// installation. `restricted_verb` adds the "open_restricted" context-menu verb.
+/// Returns the registered extensions.
+pub fn register_file_associations(
+    file_types: &[&'static FileTypeInfo],
+    restricted_path: Option<&str>,
+    restricted_verb: bool,
+) -> Result<Vec<&'static str>> {
+    let exe_path = current_exe_path()?;
 
     unsafe {
-        // 0. Clean up legacy registration
+        // 0. Clean up legacy registration and types from a previous, larger selection
         cleanup_legacy_registration();
+        for ft in registered_file_types() {
+            if !file_types.iter().any(|t| t.extension == ft.extension) {
+                remove_extension_mapping(ft);
+            }
+        }
 
         // 1. Register ProgIDs for each type
-        register_prog_ids(&exe_path)?;
+        register_prog_ids(&exe_path, file_types, restricted_verb)?;
 
         // 2. Register Application Capabilities
-        register_capabilities(&exe_path)?;
+        register_capabilities(&exe_path, file_types)?;
 
         // 3. Register in RegisteredApplications
         register_in_registered_applications()?;
 
         // 4. Register OpenWithProgids for each extension
-        register_extension_mappings()?;
+        register_extension_mappings(file_types)?;
 
         // 5. Register in Applications key
-        register_application(&exe_path)?;
+        register_application(&exe_path, file_types)?;
+
+        // 6. Register simpliview:// protocol
+        register_protocol(&exe_path, restricted_path)?;
 
-        // 6. Notify shell of changes
+        // 7. Notify shell of changes
         notify_shell_of_changes();
     }
 
-    Ok(())
+    Ok(file_types.iter().map(|ft| ft.extension).collect())
 }
 
 /// Unregister SimpliView file associations
 pub fn unregister_file_associations() -> Result<()> {
+    // Read the registered subset before the Capabilities key is removed
+    let file_types = registered_file_types();
+
     unsafe {
         // Remove all ProgIDs
-        let prog_ids = ["SimpliView.AssocFile.PDF", "SimpliView.AssocFile.Image", LEGACY_PROG_ID];
+        let prog_ids = ["SimpliView.AssocFile.PDF", "SimpliView.AssocFile.Image", "SimpliView.AssocFile.Archive", LEGACY_PROG_ID];
         for pid in prog_ids {
             delete_tree(HKEY_CURRENT_USER, &format!("Software\\Classes\\{}", pid));
         }
@@ -236,13 +401,8 @@ pub fn unregister_file_associations() -> Result<()> {
         }
 
         // Remove extension OpenWithProgids entries
-        for ft in FILE_TYPES {
-            let ext_path = format!("Software\\Classes\\{}\\OpenWithProgids", ft.extension);
-            if let Some(hkey) = open_key(HKEY_CURRENT_USER, &ext_path, KEY_SET_VALUE) {
-                delete_value(hkey, ft.prog_id);
-                delete_value(hkey, LEGACY_PROG_ID); // Clean up legacy too
-                let _ = RegCloseKey(hkey);
-            }
+        for ft in file_types {
+            remove_extension_mapping(ft);
         }
 
         // Remove from Applications
@@ -251,24 +411,37 @@ pub fn unregister_file_associations() -> Result<()> {
             &format!("Software\\Classes\\Applications\\{}.exe", APP_NAME),
         );
 
+        // Remove URL protocol
+        delete_tree(HKEY_CURRENT_USER, &format!("Software\\Classes\\{}", PROTOCOL_SCHEME));
+
         notify_shell_of_changes();
     }
 
     Ok(())
 }
 
+/// Remove our OpenWithProgids entries of one extension
+unsafe fn remove_extension_mapping(ft: &FileTypeInfo) {
+    let ext_path = format!("Software\\Classes\\{}\\OpenWithProgids", ft.extension);
+    if let Some(hkey) = open_key(HKEY_CURRENT_USER, &ext_path, KEY_SET_VALUE) {
+        delete_value(hkey, ft.prog_id);
+        delete_value(hkey, LEGACY_PROG_ID); // Clean up legacy too
+        let _ = RegCloseKey(hkey);
+    }
+}
+
 unsafe fn cleanup_legacy_registration() {
     delete_tree(HKEY_CURRENT_USER, &format!("Software\\Classes\\{}", LEGACY_PROG_ID));
 }
 
 /// Register the ProgIDs
-unsafe fn register_prog_ids(exe_path: &str) -> Result<()> {
+unsafe fn register_prog_ids(exe_path: &str, file_types: &[&FileTypeInfo], restricted_verb: bool) -> Result<()> {
     // We only need to register unique ProgIDs
-    let unique_prog_ids: std::collections::HashSet<&str> = FILE_TYPES.iter().map(|ft| ft.prog_id).collect();
+    let unique_prog_ids: std::collections::HashSet<&str> = file_types.iter().map(|ft| ft.prog_id).collect();
 
     for prog_id in unique_prog_ids {
         // Find one file type info that uses this ProgID to get details
-        let info = FILE_TYPES.iter().find(|ft| ft.prog_id == prog_id).unwrap();
+        let info = file_types.iter().find(|ft| ft.prog_id == prog_id).unwrap();
 
         let prog_id_path = format!("Software\\Classes\\{}", prog_id);
 
@@ -313,15 +486,31 @@ unsafe fn register_prog_ids(exe_path: &str) -> Result<()> {
             set_string_value(hkey, PCWSTR::null(), &command_value);
             let _ = RegCloseKey(hkey);
         }
+
+        // Optional shell\open_restricted verb; the folder is derived from the file at startup
+        let verb_path = format!("{}\\shell\\{}", prog_id_path, RESTRICTED_VERB);
+        if restricted_verb {
+            if let Some(hkey) = create_key(HKEY_CURRENT_USER, &verb_path) {
+                set_string_value(hkey, PCWSTR::null(), RESTRICTED_VERB_NAME);
+                let _ = RegCloseKey(hkey);
+            }
+            if let Some(hkey) = create_key(HKEY_CURRENT_USER, &format!("{}\\command", verb_path)) {
+                let command_value = format!("\"{}\" \"%1\" --restrict-to-folder", exe_path);
+                set_string_value(hkey, PCWSTR::null(), &command_value);
+                let _ = RegCloseKey(hkey);
+            }
+        } else {
+            delete_tree(HKEY_CURRENT_USER, &verb_path);
+        }
     }
 
     Ok(())
 }
 
 /// Register application capabilities
-unsafe fn register_capabilities(exe_path: &str) -> Result<()> {
+unsafe fn register_capabilities(exe_path: &str, file_types: &[&FileTypeInfo]) -> Result<()> {
     // Create Capabilities key
-    if let Some(hkey) = create_key(HKEY_CURRENT_USER, "Software\\SimpliView\\Capabilities") {
+    if let Some(hkey) = create_key(HKEY_CURRENT_USER, CAPABILITIES_KEY) {
         let app_name = to_wide("ApplicationName");
         set_string_value(hkey, PCWSTR(app_name.as_ptr()), APP_NAME);
 
@@ -335,12 +524,18 @@ unsafe fn register_capabilities(exe_path: &str) -> Result<()> {
         let app_icon = to_wide("ApplicationIcon");
         set_string_value(hkey, PCWSTR(app_icon.as_ptr()), &format!("{},0", exe_path));
 
+        // Chosen subset for --unregister / --diagnose
+        let extensions: Vec<&str> = file_types.iter().map(|ft| ft.extension).collect();
+        let registered = to_wide(REGISTERED_EXTENSIONS_VALUE);
+        set_string_value(hkey, PCWSTR(registered.as_ptr()), &extensions.join(","));
+
         let _ = RegCloseKey(hkey);
     }
 
-    // Create FileAssociations subkey
+    // Create FileAssociations subkey (recreated so deselected types disappear)
+    delete_tree(HKEY_CURRENT_USER, "Software\\SimpliView\\Capabilities\\FileAssociations");
     if let Some(hkey) = create_key(HKEY_CURRENT_USER, "Software\\SimpliView\\Capabilities\\FileAssociations") {
-        for ft in FILE_TYPES {
+        for ft in file_types {
             let ext_wide = to_wide(ft.extension);
             set_string_value(hkey, PCWSTR(ext_wide.as_ptr()), ft.prog_id);
         }
@@ -357,7 +552,7 @@ unsafe fn register_in_registered_applications() -> Result<()> {
         set_string_value(
             hkey,
             PCWSTR(app_name_wide.as_ptr()),
-            "Software\\SimpliView\\Capabilities",
+            CAPABILITIES_KEY,
         );
         let _ = RegCloseKey(hkey);
     }
@@ -365,8 +560,8 @@ unsafe fn register_in_registered_applications() -> Result<()> {
 }
 
 /// Register extension mappings (OpenWithProgids)
-unsafe fn register_extension_mappings() -> Result<()> {
-    for ft in FILE_TYPES {
+unsafe fn register_extension_mappings(file_types: &[&FileTypeInfo]) -> Result<()> {
+    for ft in file_types {
         let ext_path = format!("Software\\Classes\\{}\\OpenWithProgids", ft.extension);
 
         if let Some(hkey) = create_key(HKEY_CURRENT_USER, &ext_path) {
@@ -390,7 +585,7 @@ unsafe fn register_extension_mappings() -> Result<()> {
 }
 
 /// Register in Applications key
-unsafe fn register_application(exe_path: &str) -> Result<()> {
+unsafe fn register_application(exe_path: &str, file_types: &[&FileTypeInfo]) -> Result<()> {
     let app_path = format!("Software\\Classes\\Applications\\{}.exe", APP_NAME);
 
     // Create application key
@@ -418,9 +613,10 @@ unsafe fn register_application(exe_path: &str) -> Result<()> {
         let _ = RegCloseKey(hkey);
     }
 
-    // Create SupportedTypes subkey
+    // Create SupportedTypes subkey (recreated so deselected types disappear)
+    delete_tree(HKEY_CURRENT_USER, &format!("{}\\SupportedTypes", app_path));
     if let Some(hkey) = create_key(HKEY_CURRENT_USER, &format!("{}\\SupportedTypes", app_path)) {
-        for ft in FILE_TYPES {
+        for ft in file_types {
             let ext_wide = to_wide(ft.extension);
             let empty: Vec<u16> = vec![0];
             let _ = RegSetValueExW(
@@ -440,6 +636,167 @@ unsafe fn register_application(exe_path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Register the simpliview:// URL protocol
+unsafe fn register_protocol(exe_path: &str, restricted_path: Option<&str>) -> Result<()> {
+    let protocol_path = format!("Software\\Classes\\{}", PROTOCOL_SCHEME);
+
+    let hkey = create_key(HKEY_CURRENT_USER, &protocol_path)
+        .ok_or_else(Error::from_win32)?;
+    set_string_value(hkey, PCWSTR::null(), "URL:SimpliView Protocol");
+    let url_protocol = to_wide("URL Protocol");
+    set_string_value(hkey, PCWSTR(url_protocol.as_ptr()), "");
+    let _ = RegCloseKey(hkey);
+
+    if let Some(hkey) = create_key(HKEY_CURRENT_USER, &format!("{}\\DefaultIcon", protocol_path)) {
+        set_string_value(hkey, PCWSTR::null(), &format!("{},0", exe_path));
+        let _ = RegCloseKey(hkey);
+    }
+
+    // The URI is always passed as a single quoted argument and parsed by SimpliView itself
+    if let Some(hkey) = create_key(HKEY_CURRENT_USER, &format!("{}\\shell\\open\\command", protocol_path)) {
+        let command_value = match restricted_path {
+            Some(root) => format!("\"{}\" --restricted \"{}\" \"%1\"", exe_path, root),
+            None => format!("\"{}\" \"%1\"", exe_path),
+        };
+        set_string_value(hkey, PCWSTR::null(), &command_value);
+        let _ = RegCloseKey(hkey);
+    }
+
+    Ok(())
+}
+
+/// A shell command we registered, with the DefaultIcon key next to it
+struct CommandKey {
+    label: String,
+    command_key: String,
+    icon_key: String,
+}
+
+/// All command keys of the current registration
+fn registered_command_keys() -> Vec<CommandKey> {
+    let mut prog_ids: Vec<&str> = registered_file_types().iter().map(|ft| ft.prog_id).collect();
+    prog_ids.sort_unstable();
+    prog_ids.dedup();
+
+    let mut keys: Vec<CommandKey> = prog_ids
+        .iter()
+        .map(|prog_id| CommandKey {
+            label: format!("ProgID {}", prog_id),
+            command_key: format!("Software\\Classes\\{}\\shell\\open\\command", prog_id),
+            icon_key: format!("Software\\Classes\\{}\\DefaultIcon", prog_id),
+        })
+        .collect();
+
+    // Missing verbs are skipped by the callers (no command value)
+    keys.extend(prog_ids.iter().map(|prog_id| CommandKey {
+        label: format!("ProgID {} ({})", prog_id, RESTRICTED_VERB),
+        command_key: format!("Software\\Classes\\{}\\shell\\{}\\command", prog_id, RESTRICTED_VERB),
+        icon_key: format!("Software\\Classes\\{}\\DefaultIcon", prog_id),
+    }));
+
+    let app_path = format!("Software\\Classes\\Applications\\{}.exe", APP_NAME);
+    keys.push(CommandKey {
+        label: format!("Application {}.exe", APP_NAME),
+        command_key: format!("{}\\shell\\open\\command", app_path),
+        icon_key: format!("{}\\DefaultIcon", app_path),
+    });
+
+    let protocol_path = format!("Software\\Classes\\{}", PROTOCOL_SCHEME);
+    keys.push(CommandKey {
+        label: format!("URL Protocol {}://", PROTOCOL_SCHEME),
+        command_key: format!("{}\\shell\\open\\command", protocol_path),
+        icon_key: format!("{}\\DefaultIcon", protocol_path),
+    });
+
+    keys
+}
+
+/// Splits a registered command into the executable path and the remaining arguments.
+/// Handles quoted and unquoted paths.
+fn split_command(command: &str) -> (&str, &str) {
+    let command = command.trim_start();
+    if let Some(quoted) = command.strip_prefix('"') {
+        return match quoted.find('"') {
+            Some(end) => (&quoted[..end], &quoted[end + 1..]),
+            None => (quoted, ""),
+        };
+    }
+    // Unquoted: the path may contain spaces, so cut after ".exe"
+    let end = command
+        .to_ascii_lowercase()
+        .find(".exe")
+        .map(|i| i + 4)
+        .or_else(|| command.find(' '))
+        .unwrap_or(command.len());
+    (&command[..end], &command[end..])
+}
+
+/// Case-insensitive path comparison (Windows paths)
+fn same_path(a: &str, b: &str) -> bool {
+    let normalize = |p: &str| p.trim().replace('/', "\\").to_lowercase();
+    normalize(a) == normalize(b)
+}
+
+fn current_exe_path() -> Result<String> {
+    Ok(std::env::current_exe()
+        .map_err(|_| Error::from_win32())?
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Registered commands that point at another executable: (label, old exe path)
+pub fn stale_registrations() -> Vec<(String, String)> {
+    let exe_path = match current_exe_path() {
+        Ok(path) => path,
+        Err(_) => return Vec::new(),
+    };
+
+    registered_command_keys()
+        .into_iter()
+        .filter_map(|key| {
+            let command = unsafe { read_string_value(HKEY_CURRENT_USER, &key.command_key, "") }?;
+            let (registered_exe, _) = split_command(&command);
+            (!same_path(registered_exe, &exe_path)).then(|| (key.label, registered_exe.to_string()))
+        })
+        .collect()
+}
+
+/// Points stale command and DefaultIcon values at the current executable.
+/// Arguments after the executable (e.g. `--restricted`) are kept. Returns the number of repaired entries.
+pub fn repair_registrations() -> Result<usize> {
+    let exe_path = current_exe_path()?;
+    let mut repaired = 0;
+
+    unsafe {
+        for key in registered_command_keys() {
+            let command = match read_string_value(HKEY_CURRENT_USER, &key.command_key, "") {
+                Some(command) => command,
+                None => continue,
+            };
+            let (registered_exe, args) = split_command(&command);
+            if same_path(registered_exe, &exe_path) {
+                continue;
+            }
+
+            let hkey = open_key(HKEY_CURRENT_USER, &key.command_key, KEY_SET_VALUE).ok_or_else(Error::from_win32)?;
+            set_string_value(hkey, PCWSTR::null(), &format!("\"{}\"{}", exe_path, args));
+            let _ = RegCloseKey(hkey);
+
+            if let Some(hkey) = open_key(HKEY_CURRENT_USER, &key.icon_key, KEY_SET_VALUE) {
+                set_string_value(hkey, PCWSTR::null(), &format!("{},0", exe_path));
+                let _ = RegCloseKey(hkey);
+            }
+            repaired += 1;
+        }
+
+        if repaired > 0 {
+            notify_shell_of_changes();
+        }
+    }
+
+    Ok(repaired)
+}
+
 /// Notify Windows Shell of file association changes
 fn notify_shell_of_changes() {
     unsafe {
@@ -471,7 +828,7 @@ pub fn get_registration_status() -> Vec<(String, bool)> {
         // Check Capabilities
         let caps_ok = open_key(
             HKEY_CURRENT_USER,
-            "Software\\SimpliView\\Capabilities",
+            CAPABILITIES_KEY,
             KEY_READ,
         ).map(|hkey| {
             let _ = RegCloseKey(hkey);
@@ -479,8 +836,8 @@ pub fn get_registration_status() -> Vec<(String, bool)> {
         }).unwrap_or(false);
         results.push(("Capabilities".to_string(), caps_ok));
 
-        // Check each file type
-        for ft in FILE_TYPES {
+        // Check each registered file type
+        for ft in registered_file_types() {
             // ProgID
              let prog_id_ok = open_key(
                 HKEY_CURRENT_USER,
@@ -513,6 +870,39 @@ pub fn get_registration_status() -> Vec<(String, bool)> {
             }).unwrap_or(false);
             results.push((format!("OpenWithProgids for {}", ft.extension), ext_ok));
         }
+
+        // URL protocol (the command key is what the shell actually needs)
+        let protocol_ok = open_key(
+            HKEY_CURRENT_USER,
+            &format!("Software\\Classes\\{}\\shell\\open\\command", PROTOCOL_SCHEME),
+            KEY_READ,
+        ).map(|hkey| {
+            let _ = RegCloseKey(hkey);
+            true
+        }).unwrap_or(false);
+        results.push((format!("URL Protocol {}://", PROTOCOL_SCHEME), protocol_ok));
+
+        // open_restricted verb - only reported if registered for at least one ProgID
+        let mut prog_ids: Vec<&str> = registered_file_types().iter().map(|ft| ft.prog_id).collect();
+        prog_ids.sort_unstable();
+        prog_ids.dedup();
+        let verb_status: Vec<(&str, bool)> = prog_ids
+            .into_iter()
+            .map(|prog_id| {
+                let command_key = format!("Software\\Classes\\{}\\shell\\{}\\command", prog_id, RESTRICTED_VERB);
+                (prog_id, read_string_value(HKEY_CURRENT_USER, &command_key, "").is_some())
+            })
+            .collect();
+        if verb_status.iter().any(|(_, ok)| *ok) {
+            for (prog_id, ok) in verb_status {
+                results.push((format!("Verb {} for {}", RESTRICTED_VERB, prog_id), ok));
+            }
+        }
+    }
+
+    // Commands still pointing at an old location of the executable
+    for (label, old_exe) in stale_registrations() {
+        results.push((format!("{} veraltet (zeigt auf {})", label, old_exe), false));
     }
 
     results
@@ -0,0 +1,152 @@
+//! `--render-test <input> <output.png>`: draws a document offscreen and writes it as PNG
+//!
+//! `[--zoom <percent>] [--rotation <0|90|180|270>] [--page <n>] [--viewport <W>x<H>]`
+//!
+//! The document is loaded with the loaders of the batch mode and drawn by `D2DRenderer`
+//! with the same `draw_document` / `draw_document_multipage` code as the window, into a
+//! bitmap of the viewport size (default 1024x768, 100 %, page 1). Documents with several
+//! pages are drawn as the continuous view scrolled to the top of the page. Meant for
+//! golden-image tests of the rendering math and for reproducing rendering issues reported
+//! from the field. No window is created; the exit code is 0 if the PNG was written, 1 if
+//! loading, drawing or writing failed and 2 for invalid arguments.
+
+use crate::{
+    archive::ArchiveLoader,
+    d2d::{D2DRenderer, BACKGROUND_COLOR},
+    document::PageSpacing,
+    page_transform::PageTransform,
+    pdf::PdfLoader,
+    watermark::Watermark,
+    wic::WicLoader,
+};
+use windows::{
+    core::*,
+    Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS},
+};
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_INVALID_ARGUMENTS: i32 = 2;
+
+const DEFAULT_VIEWPORT: (u32, u32) = (1024, 768);
+/// Largest viewport side, so a typo does not allocate gigabytes
+const MAX_VIEWPORT: u32 = 16384;
+
+struct Options {
+    input: String,
+    output: String,
+    /// 1.0 = 100 %
+    zoom: f32,
+    rotation: i32,
+    /// 0-based
+    page: usize,
+    viewport: (u32, u32),
+}
+
+/// Runs `--render-test` if present and returns the exit code
+pub fn run(args: &[String]) -> Option<i32> {
+    let position = args.iter().position(|a| a == "--render-test")?;
+
+    // Built as a GUI program: write to the console of a calling cmd/PowerShell, if any
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+
+    let options = match parse(args, position) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Fehler: {}", message);
+            return Some(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+    match render(&options) {
+        Ok(()) => {
+            println!("OK      {}", options.output);
+            Some(EXIT_OK)
+        }
+        Err(e) => {
+            eprintln!("Fehler: {}", crate::load_error::describe(&e));
+            Some(EXIT_FAILED)
+        }
+    }
+}
+
+fn parse(args: &[String], position: usize) -> std::result::Result<Options, String> {
+    let (Some(input), Some(output)) = (args.get(position + 1), args.get(position + 2)) else {
+        return Err("--render-test erwartet <Eingabedatei> <Ausgabe.png>.".to_string());
+    };
+    let value = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+
+    let zoom = match value("--zoom") {
+        Some(text) => text
+            .trim_end_matches('%')
+            .parse::<f32>()
+            .ok()
+            .filter(|percent| (10.0..=1000.0).contains(percent))
+            .ok_or("--zoom erwartet einen Wert von 10 bis 1000 (Prozent).")?
+            / 100.0,
+        None => 1.0,
+    };
+    let rotation = match value("--rotation") {
+        Some(text) => text
+            .parse::<i32>()
+            .ok()
+            .filter(|r| [0, 90, 180, 270].contains(r))
+            .ok_or("--rotation erwartet 0, 90, 180 oder 270.")?,
+        None => 0,
+    };
+    let page = match value("--page") {
+        Some(text) => text.parse::<usize>().ok().filter(|&n| n >= 1).ok_or("--page erwartet eine Seitenzahl ab 1.")? - 1,
+        None => 0,
+    };
+    let viewport = match value("--viewport") {
+        Some(text) => text
+            .split_once(['x', 'X'])
+            .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)))
+            .filter(|&(w, h)| (1..=MAX_VIEWPORT).contains(&w) && (1..=MAX_VIEWPORT).contains(&h))
+            .ok_or(format!("--viewport erwartet <Breite>x<Höhe> in Pixeln, höchstens {}.", MAX_VIEWPORT))?,
+        None => DEFAULT_VIEWPORT,
+    };
+
+    Ok(Options { input: input.clone(), output: output.clone(), zoom, rotation, page, viewport })
+}
+
+fn render(options: &Options) -> Result<()> {
+    let wic_loader = WicLoader::new()?;
+    let doc = crate::batch::load(&wic_loader, &PdfLoader::new(), &ArchiveLoader::new(), &options.input)?;
+    let page_count = doc.page_count();
+    if options.page >= page_count {
+        return Err(Error::new(
+            HRESULT(0x80070057u32 as i32), // E_INVALIDARG
+            HSTRING::from(format!("Das Dokument hat nur {} Seite(n).", page_count)),
+        ));
+    }
+
+    let (width, height) = options.viewport;
+    let bitmap = crate::wic::bitmap_from_pbgra(width, height, &vec![0u8; width as usize * height as usize * 4])?;
+    let mut renderer = D2DRenderer::offscreen(&bitmap)?;
+    // A watermark policy applies here as in the window
+    renderer.set_watermark(Watermark::from_startup(None, None, None));
+
+    // PDF pages are rendered on demand; the window does that after its first paint
+    let layout = (page_count > 1).then(|| doc.compute_layout(options.zoom, false, PageSpacing::NORMAL, |_| options.rotation));
+    let scroll_y = layout.as_ref().map_or(0, |layout| layout.scroll_target(options.page));
+    let (first, last) = match layout {
+        Some(ref layout) => doc.find_visible_pages(layout, scroll_y, height as i32),
+        None => (0, 1),
+    };
+    for page in first..last {
+        doc.render_page(page);
+    }
+
+    renderer.begin_draw()?;
+    renderer.clear(BACKGROUND_COLOR);
+    let drawn = match layout {
+        Some(ref layout) => renderer.draw_document_multipage(&doc, layout, options.zoom, 0, scroll_y, |_| 0.0),
+        None => renderer.draw_document(&doc, options.zoom, &PageTransform::rotated(options.rotation), options.page, 0, 0),
+    };
+    renderer.end_draw()?;
+    drawn?;
+
+    crate::wic::save_png(&bitmap, &options.output)
+}
@@ -0,0 +1,75 @@
+//! Retries for transient errors of network shares
+//!
+//! Files on a flaky WLAN share sometimes fail to open with ERROR_NETNAME_DELETED or a
+//! sharing violation that is gone a moment later. File access in the load paths goes
+//! through `with_retry`, which tries again up to `MAX_RETRIES` times with a doubling pause
+//! before the error is reported. Every retry is noted for the operation log, so support can
+//! see patterns in the "Details" of a later error (see `take_log`).
+
+use parking_lot::{const_mutex, Mutex};
+use std::time::Duration;
+use windows::core::*;
+
+const MAX_RETRIES: u32 = 3;
+/// Pause before the first retry; doubled for each further one (100, 200, 400 ms)
+const FIRST_DELAY: Duration = Duration::from_millis(100);
+/// Retry notes kept until the UI thread takes them; batch runs never do
+const MAX_LOG_ENTRIES: usize = 32;
+
+/// Win32 errors of network shares and locked files that usually pass on their own
+const TRANSIENT_WIN32_ERRORS: [u32; 8] = [
+    32,   // ERROR_SHARING_VIOLATION
+    33,   // ERROR_LOCK_VIOLATION
+    54,   // ERROR_NETWORK_BUSY
+    59,   // ERROR_UNEXP_NET_ERR
+    64,   // ERROR_NETNAME_DELETED
+    121,  // ERROR_SEM_TIMEOUT
+    1231, // ERROR_NETWORK_UNREACHABLE
+    1236, // ERROR_CONNECTION_ABORTED
+];
+
+static RETRY_LOG: Mutex<Vec<String>> = const_mutex(Vec::new());
+
+/// True for errors worth another try
+pub fn is_transient(e: &Error) -> bool {
+    let code = e.code().0 as u32;
+    // HRESULT_FROM_WIN32: facility 7 with the Win32 code in the low word
+    code & 0xFFFF_0000 == 0x8007_0000 && TRANSIENT_WIN32_ERRORS.contains(&(code & 0xFFFF))
+}
+
+/// Runs `f`, retrying transient errors. `operation` and `path` go into the log line.
+pub fn with_retry<T>(operation: &str, path: &str, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut delay = FIRST_DELAY;
+    let mut retry = 0;
+    loop {
+        match f() {
+            Err(e) if retry < MAX_RETRIES && is_transient(&e) => {
+                retry += 1;
+                log(format!(
+                    "Wiederholung {}/{} ({}, 0x{:08X}): {}",
+                    retry,
+                    MAX_RETRIES,
+                    operation,
+                    e.code().0 as u32,
+                    path
+                ));
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Retry notes since the last call, oldest first
+pub fn take_log() -> Vec<String> {
+    std::mem::take(&mut *RETRY_LOG.lock())
+}
+
+fn log(entry: String) {
+    let mut entries = RETRY_LOG.lock();
+    if entries.len() == MAX_LOG_ENTRIES {
+        entries.remove(0);
+    }
+    entries.push(entry);
+}
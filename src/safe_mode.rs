@@ -0,0 +1,50 @@
+//! Abgesicherter Modus (--safe-mode)
+//!
+//! For a viewer that crashes while starting, e.g. on a damaged setting or the last file it
+//! reopens: the saved settings are ignored (defaults apply and nothing is written back),
+//! the folder search keeps no text index, and the inbox watcher and the remote-control
+//! pipe stay off. The status bar offers to
+//! reset the settings for good.
+//!
+//! A sentinel file is written when the startup begins and removed once the message loop
+//! is reached. If it is still there at the next start, that startup did not finish and
+//! the user is offered safe mode.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ACTIVE.store(true, Ordering::Relaxed);
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Writes the sentinel; true if the previous startup did not finish
+pub fn begin_startup() -> bool {
+    let Some(path) = sentinel_path() else {
+        return false;
+    };
+    let crashed = path.exists();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(&path, std::process::id().to_string());
+    crashed
+}
+
+/// Removes the sentinel once the startup is complete
+pub fn startup_finished() {
+    if let Some(path) = sentinel_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// `%LOCALAPPDATA%\SimpliView\Startup.lock`
+fn sentinel_path() -> Option<PathBuf> {
+    let local_app_data = std::env::var_os("LOCALAPPDATA")?;
+    Some(PathBuf::from(local_app_data).join("SimpliView").join("Startup.lock"))
+}
@@ -0,0 +1,464 @@
+//! Per-user settings
+//!
+//! Stored under `HKCU\Software\SimpliView\Settings`, so `--unregister` removes them together
+//! with the registration. Settings made by administrators are policies (see policy.rs).
+//! In safe mode (see safe_mode.rs) nothing is read or written and the defaults apply.
+
+use windows::{
+    core::*,
+    Win32::{Foundation::ERROR_FILE_NOT_FOUND, System::Registry::*},
+};
+
+const SETTINGS_KEY: PCWSTR = w!("Software\\SimpliView\\Settings");
+const REOPEN_LAST_FILE: PCWSTR = w!("ReopenLastFile");
+const LAST_FILE: PCWSTR = w!("LastFile");
+const HOVER_TOOLBAR: PCWSTR = w!("HoverToolbar");
+const KEEP_VIEW: PCWSTR = w!("KeepView");
+const PRINT_HEADER: PCWSTR = w!("PrintHeader");
+const PRINT_HEADER_TEMPLATE: PCWSTR = w!("PrintHeaderTemplate");
+const PRINT_FOOTER: PCWSTR = w!("PrintFooter");
+const PRINT_FOOTER_TEMPLATE: PCWSTR = w!("PrintFooterTemplate");
+const INBOX_SOUND: PCWSTR = w!("InboxSound");
+const OPEN_FILTER: PCWSTR = w!("OpenFilter");
+const OPEN_FOLDER: PCWSTR = w!("OpenFolder");
+const INCLUDE_SUBFOLDERS: PCWSTR = w!("IncludeSubfolders");
+const ESCAPE_EXITS: PCWSTR = w!("EscapeExits");
+const CONFIRM_EXIT: PCWSTR = w!("ConfirmExit");
+const RECENT_FILES: PCWSTR = w!("RecentFiles");
+const MENU_BAR: PCWSTR = w!("MenuBar");
+const TOPMOST: PCWSTR = w!("Topmost");
+const COMPACT_MODE: PCWSTR = w!("CompactMode");
+const AUTO_CROP: PCWSTR = w!("AutoCrop");
+const DESKEW: PCWSTR = w!("Deskew");
+const SPLIT_SPREADS: PCWSTR = w!("SplitSpreads");
+const PAGE_GAP: PCWSTR = w!("PageGap");
+const PAGE_MARGIN: PCWSTR = w!("PageMargin");
+const MINIMAP: PCWSTR = w!("Minimap");
+const HIGH_BIT_DEPTH: PCWSTR = w!("HighBitDepth");
+const FOLDER_SCAN_LIMIT: PCWSTR = w!("FolderScanLimit");
+const LARGE_DOCUMENT_LIMIT: PCWSTR = w!("LargeDocumentLimitMB");
+const WHEEL_MODE: PCWSTR = w!("WheelMode");
+const INVERT_WHEEL: PCWSTR = w!("InvertWheel");
+const RENDER_QUALITY: PCWSTR = w!("RenderQuality");
+const DEFAULT_ROTATION_IMAGES: PCWSTR = w!("DefaultRotationImages");
+const DEFAULT_ROTATION_PDF: PCWSTR = w!("DefaultRotationPdf");
+const CONTACT_SHEET_COLUMNS: PCWSTR = w!("ContactSheetColumns");
+const CONTACT_SHEET_MARGIN: PCWSTR = w!("ContactSheetMargin");
+const CONTACT_SHEET_PAGE_NUMBERS: PCWSTR = w!("ContactSheetPageNumbers");
+const UPDATE_CHECK: PCWSTR = w!("UpdateCheck");
+const UPDATE_MANIFEST_URL: PCWSTR = w!("UpdateManifestUrl");
+
+/// What the plain mouse wheel does; Ctrl+Wheel does the other of scrolling and zooming
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WheelMode {
+    /// "Bildlauf" (default)
+    Scroll,
+    Zoom,
+    /// "Seitenwechsel": one page or file per notch
+    PageChange,
+}
+
+/// "Renderqualität" of PDF pages (see `pdf::RenderResolution`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderQuality {
+    /// "Schnell": at most 1536 pixels
+    Fast,
+    /// "Standard": at most 2048 pixels (default)
+    Standard,
+    /// "Hoch": at most 4096 pixels
+    High,
+    /// "An Bildschirm anpassen": from the resolution of the primary monitor
+    Screen,
+}
+
+/// "Letzte Datei beim Start öffnen" (off by default)
+pub fn reopen_last_file() -> bool {
+    read_dword(REOPEN_LAST_FILE).is_some_and(|v| v != 0)
+}
+
+pub fn set_reopen_last_file(enabled: bool) {
+    write_dword(REOPEN_LAST_FILE, enabled as u32);
+}
+
+/// Last successfully opened local file
+pub fn last_file() -> Option<String> {
+    read_string(LAST_FILE).filter(|p| !p.is_empty())
+}
+
+pub fn set_last_file(path: &str) {
+    write_string(LAST_FILE, path);
+}
+
+/// Rotate/copy bar on a hovered page in continuous mode (on by default)
+pub fn hover_toolbar() -> bool {
+    read_dword(HOVER_TOOLBAR).is_none_or(|v| v != 0)
+}
+
+pub fn set_hover_toolbar(enabled: bool) {
+    write_dword(HOVER_TOOLBAR, enabled as u32);
+}
+
+/// "Ansicht beibehalten": zoom, rotation and position carry over in folder navigation (off by default)
+pub fn keep_view() -> bool {
+    read_dword(KEEP_VIEW).is_some_and(|v| v != 0)
+}
+
+pub fn set_keep_view(enabled: bool) {
+    write_dword(KEEP_VIEW, enabled as u32);
+}
+
+/// Print header line: enabled (off by default) and template (`None` = never edited)
+pub fn print_header() -> (bool, Option<String>) {
+    (read_dword(PRINT_HEADER).is_some_and(|v| v != 0), read_string(PRINT_HEADER_TEMPLATE))
+}
+
+pub fn set_print_header(enabled: bool, template: &str) {
+    write_dword(PRINT_HEADER, enabled as u32);
+    write_string(PRINT_HEADER_TEMPLATE, template);
+}
+
+/// Print footer line: enabled (off by default) and template (`None` = never edited)
+pub fn print_footer() -> (bool, Option<String>) {
+    (read_dword(PRINT_FOOTER).is_some_and(|v| v != 0), read_string(PRINT_FOOTER_TEMPLATE))
+}
+
+pub fn set_print_footer(enabled: bool, template: &str) {
+    write_dword(PRINT_FOOTER, enabled as u32);
+    write_string(PRINT_FOOTER_TEMPLATE, template);
+}
+
+/// Sound when the scan inbox opens a new document (on by default)
+pub fn inbox_sound() -> bool {
+    read_dword(INBOX_SOUND).is_none_or(|v| v != 0)
+}
+
+pub fn set_inbox_sound(enabled: bool) {
+    write_dword(INBOX_SOUND, enabled as u32);
+}
+
+/// Preselected filter of the open dialog ("pdf", "images", "archives", "all"); set by deployment
+pub fn open_filter() -> Option<String> {
+    read_string(OPEN_FILTER).filter(|s| !s.is_empty())
+}
+
+/// Folder the open dialog starts in; set by deployment
+pub fn open_folder() -> Option<String> {
+    read_string(OPEN_FOLDER).filter(|s| !s.is_empty())
+}
+
+/// "Unterordner in die Navigation einbeziehen" in the open dialog
+pub fn include_subfolders() -> bool {
+    read_dword(INCLUDE_SUBFOLDERS).is_some_and(|v| v != 0)
+}
+
+pub fn set_include_subfolders(enabled: bool) {
+    write_dword(INCLUDE_SUBFOLDERS, enabled as u32);
+}
+
+/// "Esc beendet das Programm" (off by default)
+pub fn escape_exits() -> bool {
+    read_dword(ESCAPE_EXITS).is_some_and(|v| v != 0)
+}
+
+pub fn set_escape_exits(enabled: bool) {
+    write_dword(ESCAPE_EXITS, enabled as u32);
+}
+
+/// "SimpliView beenden?" on close. `None` if never set: then only --confirm-exit asks;
+/// "Nicht mehr fragen" stores `false`, which also overrides the flag.
+pub fn confirm_exit() -> Option<bool> {
+    read_dword(CONFIRM_EXIT).map(|v| v != 0)
+}
+
+pub fn set_confirm_exit(enabled: bool) {
+    write_dword(CONFIRM_EXIT, enabled as u32);
+}
+
+/// "Zuletzt geöffnet", newest first; stored as one value separated by '|', which
+/// cannot occur in Windows paths
+pub fn recent_files() -> Vec<String> {
+    read_string(RECENT_FILES)
+        .map(|list| list.split('|').filter(|p| !p.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Moves `path` to the top of the recent files, keeping at most `max` entries
+pub fn add_recent_file(path: &str, max: usize) {
+    let mut files = recent_files();
+    files.retain(|f| !f.eq_ignore_ascii_case(path));
+    files.insert(0, path.to_string());
+    files.truncate(max);
+    write_string(RECENT_FILES, &files.join("|"));
+}
+
+/// Classic menu bar above the toolbar (off by default)
+pub fn menu_bar() -> bool {
+    read_dword(MENU_BAR).is_some_and(|v| v != 0)
+}
+
+pub fn set_menu_bar(enabled: bool) {
+    write_dword(MENU_BAR, enabled as u32);
+}
+
+/// "Immer im Vordergrund" (off by default; --topmost turns it on for one session)
+pub fn topmost() -> bool {
+    read_dword(TOPMOST).is_some_and(|v| v != 0)
+}
+
+pub fn set_topmost(enabled: bool) {
+    write_dword(TOPMOST, enabled as u32);
+}
+
+/// "Kompaktmodus": no title bar, status bar or menu bar, toolbar only at the top edge (off by default)
+pub fn compact_mode() -> bool {
+    read_dword(COMPACT_MODE).is_some_and(|v| v != 0)
+}
+
+pub fn set_compact_mode(enabled: bool) {
+    write_dword(COMPACT_MODE, enabled as u32);
+}
+
+/// "Ränder automatisch zuschneiden": scans are shown without their white margins (off by default)
+pub fn auto_crop() -> bool {
+    read_dword(AUTO_CROP).is_some_and(|v| v != 0)
+}
+
+pub fn set_auto_crop(enabled: bool) {
+    write_dword(AUTO_CROP, enabled as u32);
+}
+
+/// "Schräglage automatisch korrigieren": crooked scans are shown straight (off by default)
+pub fn deskew() -> bool {
+    read_dword(DESKEW).is_some_and(|v| v != 0)
+}
+
+pub fn set_deskew(enabled: bool) {
+    write_dword(DESKEW, enabled as u32);
+}
+
+/// "Doppelseiten teilen": scans of two facing pages are shown as two pages (off by default)
+pub fn split_spreads() -> bool {
+    read_dword(SPLIT_SPREADS).is_some_and(|v| v != 0)
+}
+
+pub fn set_split_spreads(enabled: bool) {
+    write_dword(SPLIT_SPREADS, enabled as u32);
+}
+
+/// Largest page gap or margin taken from the registry, in pixels at 100 %
+const MAX_PAGE_SPACING: u32 = 500;
+
+/// "Seitenabstand" of the continuous view ("Normal" by default). The menu offers the
+/// presets of `PageSpacing`; other values can be set in the registry.
+pub fn page_spacing() -> crate::document::PageSpacing {
+    let defaults = crate::document::PageSpacing::NORMAL;
+    let read = |name, default: i32| read_dword(name).map_or(default, |v| v.min(MAX_PAGE_SPACING) as i32);
+    crate::document::PageSpacing { gap: read(PAGE_GAP, defaults.gap), margin: read(PAGE_MARGIN, defaults.margin) }
+}
+
+pub fn set_page_spacing(spacing: crate::document::PageSpacing) {
+    write_dword(PAGE_GAP, spacing.gap as u32);
+    write_dword(PAGE_MARGIN, spacing.margin as u32);
+}
+
+/// "Übersichtsleiste" at the right edge of long documents in continuous mode (on by default)
+pub fn minimap() -> bool {
+    read_dword(MINIMAP).is_none_or(|v| v != 0)
+}
+
+pub fn set_minimap(enabled: bool) {
+    write_dword(MINIMAP, enabled as u32);
+}
+
+/// "16-Bit-Graustufen erhalten": window/level instead of a fixed 8-bit conversion (off by default)
+pub fn high_bit_depth() -> bool {
+    read_dword(HIGH_BIT_DEPTH).is_some_and(|v| v != 0)
+}
+
+pub fn set_high_bit_depth(enabled: bool) {
+    write_dword(HIGH_BIT_DEPTH, enabled as u32);
+}
+
+/// Most files listed for Back/Next, set by administrators or support only (no UI)
+pub fn folder_scan_limit() -> Option<u32> {
+    read_dword(FOLDER_SCAN_LIMIT).filter(|&limit| limit > 0)
+}
+
+/// Estimated memory in bytes above which opening a document asks first (default 1.5 GB).
+/// Set by administrators or support only (no UI), in MB; 0 turns the warning off.
+pub fn large_document_limit() -> Option<u64> {
+    let megabytes = read_dword(LARGE_DOCUMENT_LIMIT).unwrap_or(1536);
+    (megabytes > 0).then_some(megabytes as u64 * 1024 * 1024)
+}
+
+/// "Mausrad": stored as 0 = Bildlauf, 1 = Zoom, 2 = Seitenwechsel
+pub fn wheel_mode() -> WheelMode {
+    match read_dword(WHEEL_MODE) {
+        Some(1) => WheelMode::Zoom,
+        Some(2) => WheelMode::PageChange,
+        _ => WheelMode::Scroll,
+    }
+}
+
+pub fn set_wheel_mode(mode: WheelMode) {
+    write_dword(WHEEL_MODE, mode as u32);
+}
+
+/// "Richtung umkehren" for scrolling and page change with the wheel (off by default)
+pub fn invert_wheel() -> bool {
+    read_dword(INVERT_WHEEL).is_some_and(|v| v != 0)
+}
+
+pub fn set_invert_wheel(enabled: bool) {
+    write_dword(INVERT_WHEEL, enabled as u32);
+}
+
+/// "Renderqualität": stored as 0 = Standard, 1 = Schnell, 2 = Hoch, 3 = An Bildschirm anpassen
+pub fn render_quality() -> RenderQuality {
+    match read_dword(RENDER_QUALITY) {
+        Some(1) => RenderQuality::Fast,
+        Some(2) => RenderQuality::High,
+        Some(3) => RenderQuality::Screen,
+        _ => RenderQuality::Standard,
+    }
+}
+
+pub fn set_render_quality(quality: RenderQuality) {
+    let value = match quality {
+        RenderQuality::Standard => 0,
+        RenderQuality::Fast => 1,
+        RenderQuality::High => 2,
+        RenderQuality::Screen => 3,
+    };
+    write_dword(RENDER_QUALITY, value);
+}
+
+/// "Standarddrehung für neue Dokumente" in degrees (0, 90, 180 or 270), separately for PDF
+/// documents and everything else; 0 by default
+pub fn default_rotation(pdf: bool) -> i32 {
+    let name = if pdf { DEFAULT_ROTATION_PDF } else { DEFAULT_ROTATION_IMAGES };
+    match read_dword(name) {
+        Some(rotation @ (90 | 180 | 270)) => rotation as i32,
+        _ => 0,
+    }
+}
+
+pub fn set_default_rotation(pdf: bool, rotation: i32) {
+    let name = if pdf { DEFAULT_ROTATION_PDF } else { DEFAULT_ROTATION_IMAGES };
+    write_dword(name, rotation as u32);
+}
+
+/// Grid of the last "Übersichtsbild exportieren"
+pub fn contact_sheet_options() -> crate::contact_sheet::Options {
+    let defaults = crate::contact_sheet::Options::default();
+    crate::contact_sheet::Options {
+        columns: read_dword(CONTACT_SHEET_COLUMNS).unwrap_or(defaults.columns),
+        margin: read_dword(CONTACT_SHEET_MARGIN).unwrap_or(defaults.margin),
+        page_numbers: read_dword(CONTACT_SHEET_PAGE_NUMBERS).map_or(defaults.page_numbers, |v| v != 0),
+    }
+}
+
+pub fn set_contact_sheet_options(options: &crate::contact_sheet::Options) {
+    write_dword(CONTACT_SHEET_COLUMNS, options.columns);
+    write_dword(CONTACT_SHEET_MARGIN, options.margin);
+    write_dword(CONTACT_SHEET_PAGE_NUMBERS, options.page_numbers as u32);
+}
+
+/// Version manifest for "Nach Updates suchen", if the check is switched on (`UpdateCheck`,
+/// off by default, set by administrators or support only (no UI)) and a URL is configured
+pub fn update_manifest_url() -> Option<String> {
+    if read_dword(UPDATE_CHECK).is_none_or(|v| v == 0) {
+        return None;
+    }
+    read_string(UPDATE_MANIFEST_URL).filter(|url| crate::download::is_url(url))
+}
+
+/// "Einstellungen zurücksetzen" in safe mode: deletes all settings, so the next start
+/// begins with the defaults
+pub fn reset() -> Result<()> {
+    let result = unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, SETTINGS_KEY) };
+    if result.is_ok() || result == ERROR_FILE_NOT_FOUND {
+        Ok(())
+    } else {
+        Err(result.to_hresult().into())
+    }
+}
+
+fn read_dword(name: PCWSTR) -> Option<u32> {
+    if crate::safe_mode::is_active() {
+        return None;
+    }
+    let mut value = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            SETTINGS_KEY,
+            name,
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut value as *mut _ as *mut _),
+            Some(&mut size),
+        )
+    };
+    (result.0 == 0).then_some(value)
+}
+
+fn read_string(name: PCWSTR) -> Option<String> {
+    if crate::safe_mode::is_active() {
+        return None;
+    }
+    // Long paths (\\?\...) can exceed MAX_PATH
+    let mut buffer = vec![0u16; 4096];
+    let mut size = (buffer.len() * 2) as u32;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            SETTINGS_KEY,
+            name,
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr() as *mut _),
+            Some(&mut size),
+        )
+    };
+    if result.0 != 0 {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
+// Write failures (e.g. a locked-down profile) only mean the setting is not remembered
+fn write_dword(name: PCWSTR, value: u32) {
+    if crate::safe_mode::is_active() {
+        return;
+    }
+    unsafe {
+        let _ = RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            SETTINGS_KEY,
+            name,
+            REG_DWORD.0,
+            Some(&value as *const _ as *const _),
+            std::mem::size_of::<u32>() as u32,
+        );
+    }
+}
+
+fn write_string(name: PCWSTR, value: &str) {
+    if crate::safe_mode::is_active() {
+        return;
+    }
+    let wide: Vec<u16> = value.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        let _ = RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            SETTINGS_KEY,
+            name,
+            REG_SZ.0,
+            Some(wide.as_ptr() as *const _),
+            (wide.len() * 2) as u32,
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Keyboard shortcuts as shown in menus and the "Tastenkürzel" overview (F1)
+//!
+//! The keys are handled in `App::handle_accelerator`. Menu items and the overview take
+//! their texts from here, so every place that shows a key shows the one the handler uses.
+
+use crate::capabilities::Capabilities;
+
+pub const OPEN: &str = "Strg+O";
+pub const EXPORT: &str = "Strg+E";
+pub const PRINT: &str = "Strg+P";
+pub const COPY: &str = "Strg+C";
+pub const PASTE: &str = "Strg+V";
+pub const ROTATE_LEFT: &str = "Strg+Links";
+pub const ROTATE_RIGHT: &str = "Strg+Rechts";
+pub const ROTATE_180: &str = "Strg+Umschalt+R";
+pub const ZOOM_100: &str = "Num /";
+pub const FIT_TO_PAGE: &str = "Num *";
+pub const FULLSCREEN: &str = "F11";
+pub const TOPMOST: &str = "Strg+Umschalt+T";
+pub const COMPACT: &str = "F9";
+pub const UNDO_VIEW: &str = "Strg+Z";
+pub const REDO_VIEW: &str = "Strg+Y";
+pub const GO_TO_PAGE: &str = "Strg+G";
+pub const HELP: &str = "F1";
+
+/// What a shortcut needs to be active
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Needs {
+    Nothing,
+    Open,
+    Export,
+    Print,
+    Clipboard,
+}
+
+struct Shortcut {
+    keys: &'static str,
+    action: &'static str,
+    needs: Needs,
+}
+
+const fn shortcut(keys: &'static str, action: &'static str, needs: Needs) -> Shortcut {
+    Shortcut { keys, action, needs }
+}
+
+/// Categories of the overview with their shortcuts
+const GROUPS: [(&str, &[Shortcut]); 4] = [
+    (
+        "Datei",
+        &[
+            shortcut(OPEN, "Datei öffnen", Needs::Open),
+            shortcut(PASTE, "Bild oder Datei einfügen", Needs::Open),
+            shortcut(EXPORT, "Exportieren", Needs::Export),
+            shortcut(PRINT, "Drucken", Needs::Print),
+            shortcut(COPY, "In die Zwischenablage kopieren", Needs::Clipboard),
+        ],
+    ),
+    (
+        "Ansicht",
+        &[
+            shortcut("+ / -", "Vergrößern / Verkleinern", Needs::Nothing),
+            shortcut(ZOOM_100, "Zoom 100 %", Needs::Nothing),
+            shortcut(FIT_TO_PAGE, "An Seite anpassen", Needs::Nothing),
+            shortcut(ROTATE_LEFT, "Nach links drehen", Needs::Nothing),
+            shortcut(ROTATE_RIGHT, "Nach rechts drehen", Needs::Nothing),
+            shortcut(ROTATE_180, "Um 180° drehen", Needs::Nothing),
+            shortcut(FULLSCREEN, "Vollbild", Needs::Nothing),
+            shortcut(TOPMOST, "Immer im Vordergrund", Needs::Nothing),
+            shortcut(COMPACT, "Kompaktmodus", Needs::Nothing),
+            shortcut("Strg+Z / Strg+Y", "Ansicht rückgängig / wiederherstellen", Needs::Nothing),
+            shortcut("Strg+Ziehen", "Fensterung von 16-Bit-Graustufen", Needs::Nothing),
+            shortcut("B (halten)", "Blinkvergleich mit der Vergleichsseite", Needs::Nothing),
+            shortcut("Umschalt+B", "Aktuelle Seite als Vergleichsseite merken", Needs::Nothing),
+        ],
+    ),
+    (
+        "Navigation",
+        &[
+            shortcut("Links / Bild auf", "Vorherige Seite bzw. Datei", Needs::Nothing),
+            shortcut("Rechts / Bild ab", "Nächste Seite bzw. Datei", Needs::Nothing),
+            shortcut("Pos1 / Ende", "Erste / letzte Seite", Needs::Nothing),
+            shortcut(GO_TO_PAGE, "Gehe zu Seite", Needs::Nothing),
+            shortcut("Tab / F6", "Fokus wechseln", Needs::Nothing),
+        ],
+    ),
+    (
+        "Allgemein",
+        &[
+            shortcut(HELP, "Diese Übersicht", Needs::Nothing),
+            shortcut("Esc", "Auswahl, Vollbild oder Hinweis schließen", Needs::Nothing),
+            shortcut("Strg+Umschalt+D", "Diagnoseanzeige", Needs::Nothing),
+        ],
+    ),
+];
+
+/// A category of the overview: title and (keys, action) rows
+pub type ShortcutGroup = (&'static str, Vec<(&'static str, &'static str)>);
+
+/// The shortcuts that work in this session; view-only mode and a file from the command
+/// line switch some of them off
+pub fn active_groups(capabilities: &Capabilities, open_enabled: bool) -> Vec<ShortcutGroup> {
+    let active = |needs: Needs| match needs {
+        Needs::Nothing => true,
+        Needs::Open => open_enabled,
+        Needs::Export => capabilities.export,
+        Needs::Print => capabilities.print,
+        Needs::Clipboard => capabilities.clipboard,
+    };
+    GROUPS
+        .iter()
+        .map(|(title, shortcuts)| {
+            let rows = shortcuts.iter().filter(|s| active(s.needs)).map(|s| (s.keys, s.action)).collect();
+            (*title, rows)
+        })
+        .filter(|(_, rows): &ShortcutGroup| !rows.is_empty())
+        .collect()
+}
+
+/// Menu item text with the shortcut right-aligned
+pub fn menu_label(label: &str, shortcut: &str) -> String {
+    format!("{}\t{}", label, shortcut)
+}
@@ -1,13 +1,16 @@
+use crate::app::ZoomMode;
 use crate::icons;
-use crate::utils::{load_png_from_memory, make_long};
+use crate::utils::{button_width, load_png_from_memory, make_long};
 use parking_lot::Mutex;
+use std::cell::Cell;
 use std::sync::Arc;
 use windows::{
     core::*,
     Win32::{
         Foundation::*,
         Graphics::Gdi::*,
-        UI::{Controls::*, WindowsAndMessaging::*},
+        System::Com::*,
+        UI::{Accessibility::*, Controls::*, HiDpi::GetDpiForWindow, WindowsAndMessaging::*},
     },
 };
 
@@ -20,18 +23,56 @@ pub const ID_ZOOM_WIDTH: u16 = 304;
 
 pub const ID_FILENAME: u16 = 400;
 pub const ID_FILEINFO: u16 = 401;
+/// "Fortsetzen" while scan inbox mode is paused (see inbox.rs)
+pub const ID_INBOX_RESUME: u16 = 402;
+/// "Einstellungen zurücksetzen", shown in safe mode only
+pub const ID_RESET_SETTINGS: u16 = 403;
+/// "Datei wurde geändert – Neu laden" and "Ignorieren", shown when another program changed
+/// the open file (see file_watch.rs)
+pub const ID_RELOAD_CHANGED: u16 = 404;
+pub const ID_IGNORE_CHANGED: u16 = 405;
 pub const ID_ZOOM_TEXT: u16 = 399;
 pub const ID_SPRING: u16 = 9999;
 pub const ID_SPRING_RIGHT: u16 = 9998;
+/// Room kept for the file name at the minimum window width, at 96 DPI
+const MIN_FILENAME_WIDTH: i32 = 80;
+/// Zoom text beyond the source resolution (dark orange, COLORREF is 0x00BBGGRR)
+const PAST_SOURCE_COLOR: COLORREF = COLORREF(0x000060C0);
+/// Widest progress bar in the free space left of the zoom group, at 96 DPI
+const MAX_PROGRESS_WIDTH: i32 = 160;
+
+/// Progress from a worker thread: wparam = current step, lparam = total steps (0 = unknown,
+/// shown as a marquee; -1 hides the bar). See `post_progress`.
+pub const WM_APP_PROGRESS: u32 = WM_APP + 7;
 
 pub struct StatusBar {
     rebar_hwnd: HWND,
     toolbar_hwnd: HWND,
     image_list: HIMAGELIST,
     pending_zoom_command: Arc<Mutex<Option<f32>>>,
-    current_zoom: f32,
+    // Zoom with its fit mode as shown, e.g. "Anpassen · 87 %"
+    zoom_label: String,
+    zoom_mode: ZoomMode,
+    /// Zoom beyond the source resolution (see `set_zoom`)
+    past_source: bool,
     filename: String,
     info_text: String,
+    // Unapplied display changes (rotation), shown as "*" before the filename
+    modified: bool,
+    // The open file was deleted, shown as "(gelöscht)" before the filename
+    source_deleted: bool,
+    // Measured tilt of the page in degrees and whether it is corrected, shown after the info
+    skew: Option<(f32, bool)>,
+    // Scroll position in percent of the scroll range, shown last, `None` if nothing scrolls
+    scroll_percent: Option<u32>,
+    // Annotates the zoom text button with an accessible name/value for screen readers
+    acc_props: Option<IAccPropServices>,
+    // Narrowest width without overlapping sections, measured in `resize` (0 before the first)
+    min_width: Cell<i32>,
+    // Hidden while idle, placed over the left spring so the layout stays untouched
+    progress_hwnd: HWND,
+    // Last (current, total) shown, `None` while hidden
+    progress: Cell<Option<(usize, usize)>>,
 }
 
 impl StatusBar {
@@ -135,14 +176,39 @@ impl StatusBar {
             let _ = SetWindowTheme(rebar_hwnd, w!("Explorer"), None);
             let _ = SetWindowTheme(toolbar_hwnd, w!("Explorer"), None);
 
+            let progress_hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                PROGRESS_CLASSW,
+                None,
+                WS_CHILD | WINDOW_STYLE(PBS_SMOOTH),
+                0,
+                0,
+                0,
+                0,
+                toolbar_hwnd,
+                HMENU(2002isize),
+                instance,
+                None,
+            );
+
             Ok(Self {
                 rebar_hwnd,
                 toolbar_hwnd,
                 image_list,
                 pending_zoom_command: Arc::new(Mutex::new(None)),
-                current_zoom: 1.0,
+                zoom_label: zoom_text(1.0),
+                zoom_mode: ZoomMode::Fixed(1.0),
+                past_source: false,
                 filename: String::from("Dateiname |"),
                 info_text: String::from("Bildinformation"),
+                modified: false,
+                source_deleted: false,
+                skew: None,
+                scroll_percent: None,
+                acc_props: CoCreateInstance(&CLSID_AccPropServices, None, CLSCTX_INPROC_SERVER).ok(),
+                min_width: Cell::new(0),
+                progress_hwnd,
+                progress: Cell::new(None),
             })
         }
     }
@@ -247,6 +313,25 @@ impl StatusBar {
         // 9: Fit Vertical (Icon only)
         add_icon_button(&mut buttons, ID_ZOOM_HEIGHT as i32, 3);
 
+        // 10: Resume scan inbox (text only, hidden until the inbox is paused)
+        add_text_button(&mut buttons, ID_INBOX_RESUME as i32, -1, "Fortsetzen");
+        if let Some(resume) = buttons.last_mut() {
+            resume.fsState |= TBSTATE_HIDDEN as u8;
+        }
+
+        // 11: Safe mode notice (text only, hidden unless started with --safe-mode)
+        add_text_button(&mut buttons, ID_RESET_SETTINGS as i32, -1, "Abgesicherter Modus: Einstellungen zurücksetzen");
+        if let Some(reset) = buttons.last_mut() {
+            reset.fsState |= TBSTATE_HIDDEN as u8;
+        }
+
+        // 12, 13: The open file was changed by another program (hidden until then)
+        add_text_button(&mut buttons, ID_RELOAD_CHANGED as i32, -1, "Datei wurde geändert – Neu laden");
+        add_text_button(&mut buttons, ID_IGNORE_CHANGED as i32, -1, "Ignorieren");
+        for button in buttons.iter_mut().rev().take(2) {
+            button.fsState |= TBSTATE_HIDDEN as u8;
+        }
+
         buttons
     }
 
@@ -259,11 +344,31 @@ impl StatusBar {
         }
     }
 
-    pub fn resize(&self, parent_width: i32, parent_height: i32) {
+    /// Narrowest width at which the file name, page and zoom sections still fit side by side
+    pub fn min_width(&self) -> i32 {
+        self.min_width.get()
+    }
+
+    /// Adds the move of the bar to `hdwp`, so it moves together with the view and the top
+    /// toolbar (see `App::on_resize`), and lays out the sections for `parent_width`
+    pub fn defer_resize(&self, hdwp: HDWP, parent_width: i32, parent_height: i32) -> Result<HDWP> {
+        let height = self.height();
+        let hdwp = unsafe { DeferWindowPos(hdwp, self.rebar_hwnd, None, 0, parent_height - height, parent_width, height, SWP_NOZORDER | SWP_NOACTIVATE)? };
+        self.layout(parent_width);
+        Ok(hdwp)
+    }
+
+    /// Moves the bar and lays out the sections again after their content changed
+    fn resize(&self, parent_width: i32, parent_height: i32) {
+        let height = self.height();
         unsafe {
-            let height = self.height();
             let _ = SetWindowPos(self.rebar_hwnd, None, 0, parent_height - height, parent_width, height, SWP_NOZORDER);
+        }
+        self.layout(parent_width);
+    }
 
+    fn layout(&self, parent_width: i32) {
+        unsafe {
             // 1. Reset to autosize to get natural widths
             let mut tbbi_reset = TBBUTTONINFOW {
                 cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
@@ -294,7 +399,7 @@ impl StatusBar {
             }
 
             let mut w_right = 0;
-            for i in 7..10 {
+            for i in 7..12 {
                 let mut r = RECT::default();
                 if SendMessageW(self.toolbar_hwnd, TB_GETITEMRECT, WPARAM(i), LPARAM(&mut r as *mut _ as isize)).0 != 0 {
                     w_right += r.right - r.left;
@@ -303,6 +408,8 @@ impl StatusBar {
 
             // 3. Constrain Left section if needed
             let fixed_non_spring = w_center + w_right + 40;
+            let min_filename = MIN_FILENAME_WIDTH * GetDpiForWindow(self.toolbar_hwnd).max(96) as i32 / 96;
+            self.min_width.set(fixed_non_spring + min_filename);
             let available_left = (parent_width - fixed_non_spring).max(0);
             
             if w_left > available_left {
@@ -313,7 +420,7 @@ impl StatusBar {
                 let mut tbbi0 = TBBUTTONINFOW {
                     cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
                     dwMask: TBIF_SIZE,
-                    cx: final_w0 as u16,
+                    cx: button_width(final_w0),
                     ..Default::default()
                 };
                 SendMessageW(self.toolbar_hwnd, TB_SETBUTTONINFOW, WPARAM(ID_FILENAME as usize), LPARAM(&mut tbbi0 as *mut _ as isize));
@@ -321,7 +428,7 @@ impl StatusBar {
                 let mut tbbi1 = TBBUTTONINFOW {
                     cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
                     dwMask: TBIF_SIZE,
-                    cx: final_w1 as u16,
+                    cx: button_width(final_w1),
                     ..Default::default()
                 };
                 SendMessageW(self.toolbar_hwnd, TB_SETBUTTONINFOW, WPARAM(ID_FILEINFO as usize), LPARAM(&mut tbbi1 as *mut _ as isize));
@@ -336,7 +443,7 @@ impl StatusBar {
             let mut tbbi_sl = TBBUTTONINFOW {
                 cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
                 dwMask: TBIF_SIZE,
-                cx: sl as u16,
+                cx: button_width(sl),
                 ..Default::default()
             };
             SendMessageW(self.toolbar_hwnd, TB_SETBUTTONINFOW, WPARAM(ID_SPRING as usize), LPARAM(&mut tbbi_sl as *mut _ as isize));
@@ -344,20 +451,121 @@ impl StatusBar {
             let mut tbbi_sr = TBBUTTONINFOW {
                 cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
                 dwMask: TBIF_SIZE,
-                cx: sr as u16,
+                cx: button_width(sr),
                 ..Default::default()
             };
             SendMessageW(self.toolbar_hwnd, TB_SETBUTTONINFOW, WPARAM(ID_SPRING_RIGHT as usize), LPARAM(&mut tbbi_sr as *mut _ as isize));
             
+            if self.progress.get().is_some() {
+                self.place_progress();
+            }
             let _ = InvalidateRect(self.rebar_hwnd, None, true);
         }
     }
 
-    pub fn set_zoom(&mut self, zoom: f32) {
-        self.current_zoom = zoom;
-        let percent = (zoom * 100.0).round() as i32;
-        let text = format!(" {:03} % ", percent);
-        self.update_zoom_text(&text);
+    /// Shows `current` of `total` steps in the progress bar; `total` 0 shows a marquee for
+    /// work of unknown length. Paints right away, since callers usually block the UI thread.
+    pub fn set_progress(&self, current: usize, total: usize) {
+        let previous = self.progress.replace(Some((current, total)));
+        unsafe {
+            let marquee = total == 0;
+            if previous.map(|(_, total)| total == 0) != Some(marquee) {
+                let style = GetWindowLongW(self.progress_hwnd, GWL_STYLE) as u32;
+                let style = if marquee { style | PBS_MARQUEE } else { style & !PBS_MARQUEE };
+                SetWindowLongW(self.progress_hwnd, GWL_STYLE, style as i32);
+                SendMessageW(self.progress_hwnd, PBM_SETMARQUEE, WPARAM(marquee as usize), LPARAM(0));
+            }
+            if !marquee {
+                let total = total.min(i32::MAX as usize);
+                SendMessageW(self.progress_hwnd, PBM_SETRANGE32, WPARAM(0), LPARAM(total as isize));
+                SendMessageW(self.progress_hwnd, PBM_SETPOS, WPARAM(current.min(total)), LPARAM(0));
+            }
+            if previous.is_none() {
+                self.place_progress();
+            }
+            let _ = UpdateWindow(self.progress_hwnd);
+        }
+    }
+
+    /// Hides the progress bar
+    pub fn clear_progress(&self) {
+        if self.progress.take().is_some() {
+            unsafe {
+                SendMessageW(self.progress_hwnd, PBM_SETMARQUEE, WPARAM(0), LPARAM(0));
+                ShowWindow(self.progress_hwnd, SW_HIDE);
+            }
+        }
+    }
+
+    /// Fits the progress bar into the left spring, vertically centered; hidden if the
+    /// window is too narrow to leave room for it
+    fn place_progress(&self) {
+        unsafe {
+            let mut spring = RECT::default();
+            SendMessageW(self.toolbar_hwnd, TB_GETRECT, WPARAM(ID_SPRING as usize), LPARAM(&mut spring as *mut _ as isize));
+            let dpi = GetDpiForWindow(self.toolbar_hwnd).max(96) as i32;
+            let margin = 8 * dpi / 96;
+            let width = (spring.right - spring.left - 2 * margin).min(MAX_PROGRESS_WIDTH * dpi / 96);
+            if width < 4 * margin {
+                ShowWindow(self.progress_hwnd, SW_HIDE);
+                return;
+            }
+            let height = (spring.bottom - spring.top) / 2;
+            let top = spring.top + (spring.bottom - spring.top - height) / 2;
+            let _ = SetWindowPos(self.progress_hwnd, HWND_TOP, spring.right - margin - width, top, width, height, SWP_NOACTIVATE);
+            ShowWindow(self.progress_hwnd, SW_SHOWNA);
+        }
+    }
+
+    /// Shows the zoom with the name of its fit mode, and the button of that mode pressed.
+    /// `past_source`: more than one screen pixel per source pixel, the zoom text turns
+    /// orange and its tooltip says the page only gets larger, not sharper.
+    pub fn set_zoom(&mut self, zoom: f32, mode: ZoomMode, past_source: bool) {
+        self.zoom_label = match mode.label() {
+            Some(label) => format!("{} · {}", label, zoom_text(zoom)),
+            None => zoom_text(zoom),
+        };
+        self.past_source = past_source;
+        self.update_zoom_text(&format!(" {} ", self.zoom_label));
+        if past_source {
+            self.update_zoom_accessibility(&format!("{} (über der Quellauflösung)", self.zoom_label));
+        } else {
+            self.update_zoom_accessibility(&self.zoom_label);
+        }
+        unsafe {
+            for (id, fit) in [(ID_ZOOM_FIT, ZoomMode::FitPage), (ID_ZOOM_WIDTH, ZoomMode::FitWidth), (ID_ZOOM_HEIGHT, ZoomMode::FitHeight)] {
+                SendMessageW(self.toolbar_hwnd, TB_CHECKBUTTON, WPARAM(id as usize), LPARAM((mode == fit) as isize));
+            }
+        }
+        // The label got longer or shorter, the sections have to move
+        if mode.label() != self.zoom_mode.label() {
+            unsafe {
+                let parent = GetParent(self.rebar_hwnd);
+                let mut parent_rect = RECT::default();
+                let _ = GetClientRect(parent, &mut parent_rect);
+                self.resize(parent_rect.right, parent_rect.bottom);
+            }
+        }
+        self.zoom_mode = mode;
+    }
+
+    /// The button text " 75 % " is padded for the layout; screen readers get "Zoom" as
+    /// name and "75 %" as value instead
+    fn update_zoom_accessibility(&self, text: &str) {
+        let Some(ref acc_props) = self.acc_props else { return };
+        unsafe {
+            // MSAA child IDs of toolbar buttons are the 1-based button index
+            let index = SendMessageW(self.toolbar_hwnd, TB_COMMANDTOINDEX, WPARAM(ID_ZOOM_TEXT as usize), LPARAM(0)).0;
+            if index < 0 {
+                return;
+            }
+            let child = index as u32 + 1;
+            let object = OBJID_CLIENT.0 as u32;
+            let _ = acc_props.SetHwndPropStr(self.toolbar_hwnd, object, child, PROPID_ACC_NAME, w!("Zoom"));
+            let value = HSTRING::from(text);
+            let _ = acc_props.SetHwndPropStr(self.toolbar_hwnd, object, child, PROPID_ACC_VALUE, &value);
+            NotifyWinEvent(EVENT_OBJECT_VALUECHANGE, self.toolbar_hwnd, OBJID_CLIENT.0, child as i32);
+        }
     }
 
     fn update_zoom_text(&self, text: &str) {
@@ -382,10 +590,12 @@ impl StatusBar {
         let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
         let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(filename);
 
-        if stem.chars().count() <= max_base_len {
+        // Count user-perceived characters so emoji and accents are never cut in half
+        let clusters = crate::utils::grapheme_clusters(stem);
+        if clusters.len() <= max_base_len {
             filename.to_string()
         } else {
-            let truncated: String = stem.chars().take(max_base_len).collect();
+            let truncated: String = clusters[..max_base_len].concat();
             if ext.is_empty() {
                 format!("{}...", truncated)
             } else {
@@ -402,36 +612,132 @@ impl StatusBar {
         self.update_info_display();
     }
 
+    /// Shows download progress (percentage if the size is known, otherwise bytes)
+    pub fn set_download_progress(&mut self, received: u64, total: Option<u64>) {
+        self.info_text = match total {
+            Some(total) if total > 0 => format!(" Lade... {} %", received * 100 / total),
+            _ => format!(" Lade... {:.1} MB", received as f64 / (1024.0 * 1024.0)),
+        };
+        self.update_info_display();
+    }
+
+    /// Shows the download of an online-only cloud file before it is opened
+    pub fn set_cloud_progress(&mut self, received: u64, total: u64) {
+        self.info_text = match (received.min(total) * 100).checked_div(total) {
+            Some(percent) => format!(" Wird aus der Cloud geladen… {} %", percent),
+            None => String::from(" Wird aus der Cloud geladen…"),
+        };
+        self.update_info_display();
+    }
+
+    /// Shows the number of copies while a print job makes them page by page
+    pub fn set_printing_copies(&mut self, copies: u32, collate: bool) {
+        let order = if collate { "sortiert" } else { "nicht sortiert" };
+        self.info_text = format!(" Drucke {} Exemplare ({})…", copies, order);
+        self.update_info_display();
+        unsafe {
+            let _ = UpdateWindow(self.toolbar_hwnd);
+        }
+    }
+
+    /// Back to the placeholder text shown when no document is open
+    pub fn clear_file_info(&mut self) {
+        self.filename = String::from("Dateiname |");
+        self.info_text = String::from("Bildinformation");
+        self.update_info_display();
+    }
+
+    /// Shows filename with a short failure note (file could not be loaded)
+    pub fn set_load_failed(&mut self, filename: &str) {
+        let display_name = Self::truncate_filename(filename, 30);
+        self.filename = format!("{} |", display_name);
+        self.info_text = String::from(" Fehler beim Laden");
+        self.update_info_display();
+    }
+
+    /// `page_label` is the PDF page label of `current_page`, shown as "Seite iv (7/250)"
+    #[allow(clippy::too_many_arguments)]
     pub fn set_file_info(
         &mut self,
         filename: &str,
         dimensions: &str,
+        format: Option<&str>,
         file_size: u64,
         current_page: usize,
         total_pages: usize,
+        page_label: Option<&str>,
     ) {
         let display_name = Self::truncate_filename(filename, 30);
         self.filename = format!("{} |", display_name);
-        let size_str = if file_size >= 1024 * 1024 {
-            format!("{:.1} MB", file_size as f64 / (1024.0 * 1024.0))
-        } else if file_size >= 1024 {
-            format!("{:.1} KB", file_size as f64 / 1024.0)
-        } else {
-            format!("{} B", file_size)
-        };
-        let page_str = if total_pages > 1 {
+        let size_str = crate::utils::format_file_size(file_size);
+        let page_str = if let Some(label) = page_label {
+            format!(" | Seite {} ({}/{})", label, current_page + 1, total_pages)
+        } else if total_pages > 1 {
             format!(" | Page {}/{}", current_page + 1, total_pages)
         } else {
             String::new()
         };
-        self.info_text = format!(" {} | {}{}", dimensions, size_str, page_str);
+        let format_str = format.map(|f| format!(" | {}", f)).unwrap_or_default();
+        self.info_text = format!(" {}{} | {}{}", dimensions, format_str, size_str, page_str);
         self.update_info_display();
     }
 
+    /// Marks the document as having unapplied changes
+    pub fn set_modified(&mut self, modified: bool) {
+        if self.modified != modified {
+            self.modified = modified;
+            self.update_info_display();
+        }
+    }
+
+    /// Shows the measured tilt of the current page ("Schräglage automatisch korrigieren")
+    pub fn set_skew(&mut self, skew: Option<(f32, bool)>) {
+        if self.skew != skew {
+            self.skew = skew;
+            self.update_info_display();
+        }
+    }
+
+    /// Shows how far down the document is scrolled, e.g. "43 %"
+    pub fn set_scroll_percent(&mut self, percent: Option<u32>) {
+        if self.scroll_percent != percent {
+            self.scroll_percent = percent;
+            self.update_info_display();
+        }
+    }
+
+    /// Marks the open file as deleted by another program
+    pub fn set_source_deleted(&mut self, deleted: bool) {
+        if self.source_deleted != deleted {
+            self.source_deleted = deleted;
+            self.update_info_display();
+        }
+    }
+
     fn update_info_display(&self) {
         unsafe {
-            let fname_wide: Vec<u16> = self.filename.encode_utf16().chain(std::iter::once(0)).collect();
-            let info_wide: Vec<u16> = self.info_text.encode_utf16().chain(std::iter::once(0)).collect();
+            let marker = match (self.source_deleted, self.modified) {
+                (true, true) => "(gelöscht) *",
+                (true, false) => "(gelöscht) ",
+                (false, true) => "*",
+                (false, false) => "",
+            };
+            let fname_wide: Vec<u16> = marker.encode_utf16().chain(self.filename.encode_utf16()).chain(std::iter::once(0)).collect();
+            let skew = match self.skew {
+                Some((angle, corrected)) => {
+                    let angle = format!("{:.1}", angle).replace('.', ",");
+                    format!(" | Schräglage {}°{}", angle, if corrected { "" } else { " (nicht korrigiert)" })
+                }
+                None => String::new(),
+            };
+            let scroll = self.scroll_percent.map(|percent| format!(" | {} %", percent)).unwrap_or_default();
+            let info_wide: Vec<u16> = self
+                .info_text
+                .encode_utf16()
+                .chain(skew.encode_utf16())
+                .chain(scroll.encode_utf16())
+                .chain(std::iter::once(0))
+                .collect();
             let tbbi_f = TBBUTTONINFOW {
                 cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
                 dwMask: TBIF_TEXT,
@@ -454,6 +760,41 @@ impl StatusBar {
         }
     }
 
+    /// Shows the "Fortsetzen" button while automatic opening from the inbox is paused
+    pub fn set_inbox_paused(&self, paused: bool) {
+        unsafe {
+            SendMessageW(self.toolbar_hwnd, TB_HIDEBUTTON, WPARAM(ID_INBOX_RESUME as usize), LPARAM(!paused as isize));
+            let parent = GetParent(self.rebar_hwnd);
+            let mut parent_rect = RECT::default();
+            let _ = GetClientRect(parent, &mut parent_rect);
+            self.resize(parent_rect.right, parent_rect.bottom);
+        }
+    }
+
+    /// Shows the safe mode notice with its "Einstellungen zurücksetzen" button
+    pub fn set_safe_mode(&self, active: bool) {
+        unsafe {
+            SendMessageW(self.toolbar_hwnd, TB_HIDEBUTTON, WPARAM(ID_RESET_SETTINGS as usize), LPARAM(!active as isize));
+            let parent = GetParent(self.rebar_hwnd);
+            let mut parent_rect = RECT::default();
+            let _ = GetClientRect(parent, &mut parent_rect);
+            self.resize(parent_rect.right, parent_rect.bottom);
+        }
+    }
+
+    /// Shows "Datei wurde geändert – Neu laden" / "Ignorieren"
+    pub fn set_file_changed(&self, changed: bool) {
+        unsafe {
+            for id in [ID_RELOAD_CHANGED, ID_IGNORE_CHANGED] {
+                SendMessageW(self.toolbar_hwnd, TB_HIDEBUTTON, WPARAM(id as usize), LPARAM(!changed as isize));
+            }
+            let parent = GetParent(self.rebar_hwnd);
+            let mut parent_rect = RECT::default();
+            let _ = GetClientRect(parent, &mut parent_rect);
+            self.resize(parent_rect.right, parent_rect.bottom);
+        }
+    }
+
     pub fn poll_zoom_command(&self) -> Option<f32> {
         self.pending_zoom_command.lock().take()
     }
@@ -466,7 +807,10 @@ impl StatusBar {
         }
     }
 
-    #[allow(dead_code)]
+    pub fn hwnd(&self) -> HWND {
+        self.rebar_hwnd
+    }
+
     pub fn toolbar_hwnd(&self) -> HWND {
         self.toolbar_hwnd
     }
@@ -500,10 +844,17 @@ impl StatusBar {
                 let tooltip_text: &str = match nmtbgit.iItem {
                     x if x == ID_ZOOM_OUT as i32 => "Verkleinern (- / Strg+Mausrad)",
                     x if x == ID_ZOOM_IN as i32 => "Vergrößern (+ / Strg+Mausrad)",
+                    x if x == ID_ZOOM_TEXT as i32 && self.past_source => {
+                        "Über der Quellauflösung: die Seite wird nur größer, nicht schärfer. Zoom zurücksetzen (/)"
+                    }
                     x if x == ID_ZOOM_TEXT as i32 => "Zoom zurücksetzen (/)",
                     x if x == ID_ZOOM_FIT as i32 => "An Fenster anpassen (*)",
                     x if x == ID_ZOOM_HEIGHT as i32 => "An Höhe anpassen",
                     x if x == ID_ZOOM_WIDTH as i32 => "An Breite anpassen",
+                    x if x == ID_INBOX_RESUME as i32 => "Neue Dokumente aus dem Eingangsordner wieder automatisch öffnen",
+                    x if x == ID_RESET_SETTINGS as i32 => "Gespeicherte Einstellungen werden nicht geladen. Klicken, um sie dauerhaft zurückzusetzen.",
+                    x if x == ID_RELOAD_CHANGED as i32 => "Die Datei wurde von einem anderen Programm geändert. Klicken, um sie neu zu laden.",
+                    x if x == ID_IGNORE_CHANGED as i32 => "Die angezeigte Fassung behalten",
                     _ => return None,
                 };
 
@@ -577,13 +928,13 @@ impl StatusBar {
                             };
 
                             // Get current zoom text
-                            let zoom_percent = (self.current_zoom * 100.0).round() as i32;
-                            let text = format!("{:03} %", zoom_percent);
-                            let mut text_wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+                            let mut text_wide: Vec<u16> = self.zoom_label.encode_utf16().chain(std::iter::once(0)).collect();
 
                             // Draw centered text
                             SetBkMode(hdc, TRANSPARENT);
-                            SetTextColor(hdc, COLORREF(0x00000000)); // Black text
+                            // Button text of the theme, so high-contrast themes stay legible;
+                            // orange beyond the source resolution
+                            SetTextColor(hdc, if self.past_source { PAST_SOURCE_COLOR } else { COLORREF(GetSysColor(COLOR_BTNTEXT)) });
 
                             let mut draw_rect = rect;
                             DrawTextW(
@@ -619,3 +970,25 @@ impl Drop for StatusBar {
         }
     }
 }
+
+/// Reports progress to the status bar of `hwnd` from a worker thread (see `WM_APP_PROGRESS`).
+/// `None` hides the bar.
+pub fn post_progress(hwnd: HWND, progress: Option<(usize, usize)>) {
+    let (current, total) = match progress {
+        Some((current, total)) => (current, total as isize),
+        None => (0, -1),
+    };
+    unsafe {
+        let _ = PostMessageW(hwnd, WM_APP_PROGRESS, WPARAM(current), LPARAM(total));
+    }
+}
+
+/// Zoom for display, e.g. "150 %", "87,3 %" (tenths below 100 % where they show)
+pub fn zoom_text(zoom: f32) -> String {
+    let tenths = (zoom * 1000.0).round() as i32;
+    if tenths < 1000 && tenths % 10 != 0 {
+        format!("{},{} %", tenths / 10, tenths % 10)
+    } else {
+        format!("{} %", (zoom * 100.0).round() as i32)
+    }
+}
@@ -0,0 +1,86 @@
+//! On-disk cache of document text for "Ordner durchsuchen"
+//!
+//! Reading the text layer of a PDF is quick, but recognizing an image takes a second or
+//! more per page, so the text of every searched file is kept in
+//! `%LOCALAPPDATA%\SimpliView\TextIndex`: one file per document, named after a hash of its
+//! path. The first line holds size, modification time and path of the document, the pages
+//! follow separated by form feeds. An entry whose document has changed since is not used
+//! and is replaced once the text has been read again.
+//!
+//! The entries hold document text in plain form, so restricted deployments and safe mode
+//! search without the index (`SearchJob::use_index`), view-only mode has no folder search
+//! at all, and "Suchindex löschen" deletes the whole index (`clear`).
+
+use crate::utils::long_path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use windows::{core::*, Win32::Foundation::E_FAIL};
+
+/// Separates the pages of an entry
+const PAGE_SEPARATOR: char = '\u{0C}';
+
+/// The version of a file an entry was made from
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    size: u64,
+    /// Nanoseconds since 1970
+    modified: u128,
+}
+
+impl Stamp {
+    /// Current size and modification time of `path`, `None` if it cannot be read
+    pub fn of(path: &str) -> Option<Self> {
+        let metadata = std::fs::metadata(long_path(path)).ok().filter(|m| m.is_file())?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+        Some(Self { size: metadata.len(), modified })
+    }
+}
+
+/// Page texts stored for `path`, if they were read from this version of the file
+pub fn load(path: &str, stamp: Stamp) -> Option<Vec<String>> {
+    let content = std::fs::read_to_string(entry_path(path)?).ok()?;
+    let (header, pages) = content.split_once('\n')?;
+    if header != self::header(path, stamp) {
+        return None;
+    }
+    Some(pages.split(PAGE_SEPARATOR).map(str::to_string).collect())
+}
+
+/// Stores the page texts of `path`; failures only cost a new extraction next time
+pub fn store(path: &str, stamp: Stamp, pages: &[String]) {
+    let Some(entry) = entry_path(path) else { return };
+    if let Some(folder) = entry.parent() {
+        let _ = std::fs::create_dir_all(folder);
+    }
+    let pages: Vec<String> = pages.iter().map(|page| page.replace(PAGE_SEPARATOR, " ")).collect();
+    let content = format!("{}\n{}", header(path, stamp), pages.join(&PAGE_SEPARATOR.to_string()));
+    let _ = crate::utils::write_atomically(&entry.to_string_lossy(), |temp| {
+        std::fs::write(long_path(temp), content).map_err(|e| Error::new(E_FAIL, HSTRING::from(e.to_string())))
+    });
+}
+
+/// Deletes all entries; an index that was never written is not an error
+pub fn clear() -> std::io::Result<()> {
+    let Some(folder) = index_folder() else { return Ok(()) };
+    match std::fs::remove_dir_all(long_path(&folder.to_string_lossy())) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn header(path: &str, stamp: Stamp) -> String {
+    format!("{}\t{}\t{}", stamp.size, stamp.modified, path.replace(['\n', '\r'], " "))
+}
+
+/// `%LOCALAPPDATA%\SimpliView\TextIndex\<hash>.txt`
+fn entry_path(path: &str) -> Option<PathBuf> {
+    // FNV-1a of the path; Windows paths are case-insensitive
+    let hash = path.to_lowercase().bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    Some(index_folder()?.join(format!("{:016x}.txt", hash)))
+}
+
+/// `%LOCALAPPDATA%\SimpliView\TextIndex`
+fn index_folder() -> Option<PathBuf> {
+    let local_app_data = std::env::var_os("LOCALAPPDATA")?;
+    Some(PathBuf::from(local_app_data).join("SimpliView").join("TextIndex"))
+}
@@ -2,14 +2,67 @@ use windows::{
     core::*,
     Win32::{
         Foundation::*,
-        Graphics::Dwm::*,
+        Graphics::{Direct2D::Common::D2D1_COLOR_F, Dwm::*, Gdi::*},
         System::Registry::*,
-        UI::WindowsAndMessaging::*,
+        UI::{Accessibility::*, WindowsAndMessaging::*},
     },
 };
 
 pub struct Theme;
 
+/// Display settings of Windows the viewer follows, queried again on WM_SETTINGCHANGE and
+/// WM_THEMECHANGED
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SystemSettings {
+    /// "Animationen in Windows anzeigen" (SPI_GETCLIENTAREAANIMATION); fades and smooth
+    /// scrolling jump straight to their end when off
+    pub animations: bool,
+    /// A high-contrast theme is active; the canvas then takes its colors from the theme
+    pub high_contrast: bool,
+}
+
+impl SystemSettings {
+    pub fn query() -> Self {
+        unsafe {
+            let mut animations = TRUE;
+            let _ = SystemParametersInfoW(
+                SPI_GETCLIENTAREAANIMATION,
+                0,
+                Some(&mut animations as *mut _ as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            );
+            let mut contrast = HIGHCONTRASTW { cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32, ..Default::default() };
+            let high_contrast = SystemParametersInfoW(
+                SPI_GETHIGHCONTRAST,
+                contrast.cbSize,
+                Some(&mut contrast as *mut _ as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+            .as_bool()
+                && contrast.dwFlags.contains(HCF_HIGHCONTRASTON);
+            Self { animations: animations.as_bool(), high_contrast }
+        }
+    }
+
+    /// Background of the canvas: `default`, or the window color of a high-contrast theme
+    pub fn canvas_color(&self, default: D2D1_COLOR_F) -> D2D1_COLOR_F {
+        if self.high_contrast { sys_color(COLOR_WINDOW) } else { default }
+    }
+
+    /// Lines between the canvas and the bars: `default`, or the text color of a
+    /// high-contrast theme so they stay visible on its background
+    pub fn separator_color(&self, default: COLORREF) -> COLORREF {
+        if self.high_contrast { COLORREF(unsafe { GetSysColor(COLOR_WINDOWTEXT) }) } else { default }
+    }
+}
+
+/// A system color (GetSysColor) for Direct2D
+fn sys_color(index: SYS_COLOR_INDEX) -> D2D1_COLOR_F {
+    let color = unsafe { GetSysColor(index) };
+    let channel = |shift: u32| ((color >> shift) & 0xFF) as f32 / 255.0;
+    D2D1_COLOR_F { r: channel(0), g: channel(8), b: channel(16), a: 1.0 }
+}
+
 impl Theme {
     /// Check if Windows is using dark mode
     #[allow(dead_code)]
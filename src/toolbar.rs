@@ -1,6 +1,7 @@
 use crate::icons;
-use crate::utils::{load_png_from_memory, make_long};
+use crate::utils::{button_width, load_png_from_memory, make_long};
 use parking_lot::Mutex;
+use std::cell::Cell;
 use std::sync::Arc;
 use windows::{
     core::*,
@@ -22,6 +23,11 @@ pub const ID_INFO: u16 = 106;
 pub const ID_CLOSE: u16 = 107;
 pub const ID_PRINT: u16 = 108;
 pub const ID_SPRING: u16 = 9999;
+/// Space right of the Close button
+const SPRING_PADDING: i32 = 15;
+/// Buttons hidden from the top toolbar when the window is too narrow, first group first;
+/// their commands stay in the menus and on the keyboard
+const OVERFLOW_GROUPS: [&[u16]; 4] = [&[ID_INFO], &[ID_PRINT], &[ID_EXPORT], &[ID_ROTATE_LEFT, ID_ROTATE_RIGHT]];
 
 #[derive(Clone, Copy, Debug)]
 #[allow(dead_code)]
@@ -50,6 +56,11 @@ pub struct Toolbar {
     pending_command: Arc<Mutex<Option<ToolbarCommand>>>,
     is_dark: bool,
     toolbar_type: ToolbarType,
+    // Hard-disabled in view-only mode, regardless of document state
+    export_allowed: bool,
+    print_allowed: bool,
+    // Width of the buttons that are never hidden, measured in `resize` (0 before the first)
+    min_width: Cell<i32>,
 }
 
 impl Toolbar {
@@ -192,6 +203,9 @@ impl Toolbar {
                 pending_command: Arc::new(Mutex::new(None)),
                 is_dark: false,
                 toolbar_type,
+                export_allowed: true,
+                print_allowed: true,
+                min_width: Cell::new(0),
             })
         }
     }
@@ -335,24 +349,35 @@ impl Toolbar {
         }
     }
 
-    pub fn resize(&self, parent_width: i32, y: i32) {
-        unsafe {
+    /// Narrowest width that still shows Open, Back/Next and Close (top toolbar)
+    pub fn min_width(&self) -> i32 {
+        self.min_width.get()
+    }
+
+    /// Adds the move of the bar to `hdwp`, so it moves together with the view and the
+    /// status bar (see `App::on_resize`), and lays out the buttons for `parent_width`
+    pub fn defer_resize(&self, hdwp: HDWP, parent_width: i32, y: i32) -> Result<HDWP> {
+        let hdwp = unsafe {
             let mut rebar_rect = RECT::default();
             let _ = GetWindowRect(self.rebar_hwnd, &mut rebar_rect);
             let height = rebar_rect.bottom - rebar_rect.top;
             let effective_height = if height > 0 { height } else { 28 };
 
-            let _ = SetWindowPos(
-                self.rebar_hwnd,
-                None,
-                0,
-                y,
-                parent_width,
-                effective_height,
-                SWP_NOZORDER,
-            );
+            DeferWindowPos(hdwp, self.rebar_hwnd, None, 0, y, parent_width, effective_height, SWP_NOZORDER | SWP_NOACTIVATE)?
+        };
+        self.layout(parent_width);
+        Ok(hdwp)
+    }
 
+    fn layout(&self, parent_width: i32) {
+        unsafe {
             if let ToolbarType::Top = self.toolbar_type {
+                // Measure with every button shown; no repaint until the final layout
+                SendMessageW(self.toolbar_hwnd, WM_SETREDRAW, WPARAM(0), LPARAM(0));
+                for id in OVERFLOW_GROUPS.iter().flat_map(|group| group.iter()) {
+                    SendMessageW(self.toolbar_hwnd, TB_HIDEBUTTON, WPARAM(*id as usize), LPARAM(0));
+                }
+
                 // Reset spring
                 let mut tbbi_reset = TBBUTTONINFOW {
                     cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
@@ -366,6 +391,7 @@ impl Toolbar {
 
                 let button_count = SendMessageW(self.toolbar_hwnd, TB_BUTTONCOUNT, WPARAM(0), LPARAM(0)).0 as usize;
                 let mut used_width = 0;
+                let mut overflow_widths = [0; OVERFLOW_GROUPS.len()];
                 
                 for i in 0..button_count {
                      let mut btn = TBBUTTON::default();
@@ -375,18 +401,35 @@ impl Toolbar {
                          let mut rect = RECT::default();
                          SendMessageW(self.toolbar_hwnd, TB_GETITEMRECT, WPARAM(i), LPARAM(&mut rect as *mut _ as isize));
                          used_width += rect.right - rect.left;
+                         if let Some(group) = OVERFLOW_GROUPS.iter().position(|g| g.contains(&(btn.idCommand as u16))) {
+                             overflow_widths[group] += rect.right - rect.left;
+                         }
                      }
                 }
+                self.min_width.set(used_width - overflow_widths.iter().sum::<i32>() + SPRING_PADDING);
+
+                // Hide low-priority buttons until the rest fits
+                for (group, width) in OVERFLOW_GROUPS.iter().zip(overflow_widths) {
+                    if used_width + SPRING_PADDING <= parent_width {
+                        break;
+                    }
+                    for id in group.iter() {
+                        SendMessageW(self.toolbar_hwnd, TB_HIDEBUTTON, WPARAM(*id as usize), LPARAM(1));
+                    }
+                    used_width -= width;
+                }
 
-                let spring_width = (parent_width - used_width - 15).max(0);
+                let spring_width = (parent_width - used_width - SPRING_PADDING).max(0);
                 
                 let mut tbbi_spring = TBBUTTONINFOW {
                     cbSize: std::mem::size_of::<TBBUTTONINFOW>() as u32,
                     dwMask: TBIF_SIZE,
-                    cx: spring_width as u16,
+                    cx: button_width(spring_width),
                     ..Default::default()
                 };
                 SendMessageW(self.toolbar_hwnd, TB_SETBUTTONINFOW, WPARAM(ID_SPRING as usize), LPARAM(&mut tbbi_spring as *mut _ as isize));
+                SendMessageW(self.toolbar_hwnd, WM_SETREDRAW, WPARAM(1), LPARAM(0));
+                let _ = InvalidateRect(self.toolbar_hwnd, None, true);
             } else {
                 SendMessageW(self.toolbar_hwnd, TB_AUTOSIZE, WPARAM(0), LPARAM(0));
             }
@@ -411,27 +454,38 @@ impl Toolbar {
         }
     }
 
-    #[allow(dead_code)]
     pub fn hwnd(&self) -> HWND {
         self.rebar_hwnd
     }
 
+    pub fn toolbar_hwnd(&self) -> HWND {
+        self.toolbar_hwnd
+    }
+
+    /// Export/Print permissions; disallowed buttons stay disabled even with a document
+    pub fn set_capabilities(&mut self, export_allowed: bool, print_allowed: bool) {
+        self.export_allowed = export_allowed;
+        self.print_allowed = print_allowed;
+        unsafe {
+            if !export_allowed {
+                SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_EXPORT as usize), LPARAM(0));
+            }
+            if !print_allowed {
+                SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_PRINT as usize), LPARAM(0));
+            }
+        }
+    }
+
     pub fn set_document_loaded(&self, loaded: bool) {
         unsafe {
             let enable = if loaded { 1isize } else { 0isize };
+            let export = if loaded && self.export_allowed { 1isize } else { 0isize };
+            let print = if loaded && self.print_allowed { 1isize } else { 0isize };
             // Enable/disable document-dependent buttons
-            SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_EXPORT as usize), LPARAM(enable));
+            SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_EXPORT as usize), LPARAM(export));
             SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_ROTATE_LEFT as usize), LPARAM(enable));
             SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_ROTATE_RIGHT as usize), LPARAM(enable));
-            SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_PRINT as usize), LPARAM(enable));
-        }
-    }
-
-    pub fn set_navigation_enabled(&self, enabled: bool) {
-        unsafe {
-            let enable = if enabled { 1isize } else { 0isize };
-            SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_PREV_PAGE as usize), LPARAM(enable));
-            SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_NEXT_PAGE as usize), LPARAM(enable));
+            SendMessageW(self.toolbar_hwnd, TB_ENABLEBUTTON, WPARAM(ID_PRINT as usize), LPARAM(print));
         }
     }
 
@@ -0,0 +1,101 @@
+//! "Nach Updates suchen" in the Info dialog
+//!
+//! Off by default: offered only when the setting `UpdateCheck` is on and `UpdateManifestUrl`
+//! names a version manifest, and never in restricted deployments. The manifest is either a
+//! bare version number or JSON with a "version" field and an optional "url" for the release
+//! notes. Nothing is downloaded or installed; the result only says whether a newer release
+//! exists. The manifest is fetched on a worker thread and the result comes back with
+//! WM_APP_UPDATE_CHECK.
+
+use std::cmp::Ordering;
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+pub const WM_APP_UPDATE_CHECK: u32 = WM_APP + 9;
+/// A manifest is a few lines; anything longer is cut off
+const MAX_MANIFEST_LEN: usize = 16 * 1024;
+
+pub enum UpdateStatus {
+    /// The running version is the newest (or newer, e.g. a test build)
+    Current,
+    Available { version: String, url: Option<String> },
+    /// Download failed or the manifest has no version
+    Failed(String),
+}
+
+/// Fetches the manifest on a worker thread and posts the comparison with `current` to
+/// `hwnd`. False if the thread could not be started.
+pub fn start(hwnd: HWND, url: String, current: String) -> bool {
+    let hwnd_raw = hwnd.0;
+    std::thread::Builder::new()
+        .name("SimpliView Update Check".to_string())
+        .spawn(move || {
+            let result = Box::into_raw(Box::new(check(&url, &current)));
+            let posted = unsafe { PostMessageW(HWND(hwnd_raw), WM_APP_UPDATE_CHECK, WPARAM(0), LPARAM(result as isize)) };
+            if !posted.as_bool() {
+                drop(unsafe { Box::from_raw(result) });
+            }
+        })
+        .is_ok()
+}
+
+/// Takes ownership of the result posted with WM_APP_UPDATE_CHECK
+///
+/// # Safety
+/// `lparam` must come from a WM_APP_UPDATE_CHECK message and be taken only once.
+pub unsafe fn take_result(lparam: LPARAM) -> UpdateStatus {
+    *Box::from_raw(lparam.0 as *mut UpdateStatus)
+}
+
+fn check(url: &str, current: &str) -> UpdateStatus {
+    let manifest = match crate::download::fetch_text(url, MAX_MANIFEST_LEN) {
+        Ok(manifest) => manifest,
+        Err(e) => return UpdateStatus::Failed(e.message().to_string()),
+    };
+    match parse_manifest(&manifest) {
+        Some((version, url)) if compare_versions(&version, current) == Ordering::Greater => {
+            UpdateStatus::Available { version, url }
+        }
+        Some(_) => UpdateStatus::Current,
+        None => UpdateStatus::Failed("Die Versionsangabe des Servers ist ungültig.".to_string()),
+    }
+}
+
+/// Version and release-notes URL from a manifest: `{"version":"1.2.0","url":"..."}` or
+/// just `1.2.0`
+fn parse_manifest(text: &str) -> Option<(String, Option<String>)> {
+    let text = text.trim_start_matches('\u{FEFF}').trim();
+    let (version, url) = if text.starts_with('{') {
+        (json_field(text, "version")?, json_field(text, "url"))
+    } else {
+        (text.lines().next()?.trim().to_string(), None)
+    };
+    let valid = !version.is_empty() && version.split('.').all(|part| part.parse::<u32>().is_ok());
+    valid.then_some((version, url))
+}
+
+/// String value of a top-level field in flat JSON; escapes other than \" and \\ are not
+/// expected in a manifest
+fn json_field(json: &str, key: &str) -> Option<String> {
+    let start = json.find(&format!("\"{}\"", key))? + key.len() + 2;
+    let rest = json[start..].trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => value.push(chars.next()?),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Compares dotted version numbers part by part; missing parts count as 0 (1.2 == 1.2.0.0)
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u32> { v.split('.').map(|p| p.trim().parse().unwrap_or(0)).collect() };
+    let (a, b) = (parts(a), parts(b));
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|order| order.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
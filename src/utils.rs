@@ -3,9 +3,15 @@
 use windows::{
     core::*,
     Win32::{
+        Foundation::*,
         Graphics::Gdi::*,
         Graphics::Imaging::*,
+        Storage::FileSystem::{
+            DeleteFileW, GetFileVersionInfoSizeW, GetFileVersionInfoW, MoveFileExW, VerQueryValueW, MOVEFILE_REPLACE_EXISTING,
+            MOVEFILE_WRITE_THROUGH, VS_FIXEDFILEINFO,
+        },
         System::Com::*,
+        UI::{Controls::*, Input::KeyboardAndMouse::SetFocus, WindowsAndMessaging::*},
     },
 };
 
@@ -15,6 +21,12 @@ pub const fn make_long(lo: u16, hi: u16) -> u32 {
     (lo as u32) | ((hi as u32) << 16)
 }
 
+/// Toolbar button width for TBBUTTONINFOW::cx, clamped to the u16 range instead of wrapping
+#[inline]
+pub fn button_width(width: i32) -> u16 {
+    width.clamp(0, u16::MAX as i32) as u16
+}
+
 /// Loads a PNG image from memory and returns an HBITMAP
 pub fn load_png_from_memory(data: &[u8]) -> Result<HBITMAP> {
     unsafe {
@@ -74,3 +86,478 @@ pub fn load_png_from_memory(data: &[u8]) -> Result<HBITMAP> {
         Ok(bitmap)
     }
 }
+
+/// Gives a toolbar the keyboard focus and highlights its first enabled button, so the
+/// arrow keys move between buttons and Enter/Space press the highlighted one
+pub fn focus_toolbar(toolbar: HWND) {
+    unsafe {
+        SetFocus(toolbar);
+        let count = SendMessageW(toolbar, TB_BUTTONCOUNT, WPARAM(0), LPARAM(0)).0 as usize;
+        let first_enabled = (0..count).find(|&i| {
+            let mut button = TBBUTTON::default();
+            SendMessageW(toolbar, TB_GETBUTTON, WPARAM(i), LPARAM(&mut button as *mut _ as isize));
+            button.fsState & TBSTATE_ENABLED as u8 != 0 && button.fsStyle & BTNS_SEP as u8 == 0
+        });
+        if let Some(index) = first_enabled {
+            SendMessageW(toolbar, TB_SETHOTITEM, WPARAM(index), LPARAM(0));
+        }
+    }
+}
+
+/// Sort key for file names in Explorer order: digit runs compare as numbers
+/// ("scan_2" before "scan_10"), case is ignored and letters follow the user's locale
+/// (umlauts next to their base letter). Compared with CompareStringEx and
+/// SORT_DIGITSASNUMBERS; `fallback_sort_key` only if that call fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NaturalKey {
+    /// File name as UTF-16 for CompareStringEx
+    name: Vec<u16>,
+}
+
+impl Ord for NaturalKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // PCWSTR::null() = LOCALE_NAME_USER_DEFAULT
+        let result = unsafe {
+            windows::Win32::Globalization::CompareStringEx(
+                PCWSTR::null(),
+                windows::Win32::Globalization::NORM_IGNORECASE | windows::Win32::Globalization::SORT_DIGITSASNUMBERS,
+                &self.name,
+                &other.name,
+                None,
+                None,
+                LPARAM(0),
+            )
+        };
+        let ordering = match result {
+            1 => std::cmp::Ordering::Less,    // CSTR_LESS_THAN
+            2 => std::cmp::Ordering::Equal,   // CSTR_EQUAL
+            3 => std::cmp::Ordering::Greater, // CSTR_GREATER_THAN
+            _ => fallback_sort_key(&String::from_utf16_lossy(&self.name))
+                .cmp(&fallback_sort_key(&String::from_utf16_lossy(&other.name))),
+        };
+        // Names equal but for case still need a fixed order (consistent with Eq)
+        ordering.then_with(|| self.name.cmp(&other.name))
+    }
+}
+
+impl PartialOrd for NaturalKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Natural sort key of the file name of `path` (see `NaturalKey`)
+pub fn natural_sort_key(path: &str) -> NaturalKey {
+    let filename = std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or(path);
+    NaturalKey { name: filename.encode_utf16().collect() }
+}
+
+/// Natural order without the locale: splits into lowercase text and numeric segments
+/// Example: "file2.txt" < "file10.txt" (unlike lexicographic sort)
+fn fallback_sort_key(filename: &str) -> Vec<(String, u64)> {
+    let filename = filename.to_lowercase();
+
+    let mut result = Vec::new();
+    let mut current_text = String::new();
+    let mut current_num = String::new();
+    let mut in_number = false;
+
+    for ch in filename.chars() {
+        if ch.is_ascii_digit() {
+            if !in_number && !current_text.is_empty() {
+                result.push((current_text.clone(), 0));
+                current_text.clear();
+            }
+            in_number = true;
+            current_num.push(ch);
+        } else {
+            if in_number && !current_num.is_empty() {
+                let num = current_num.parse::<u64>().unwrap_or(0);
+                result.push((String::new(), num));
+                current_num.clear();
+            }
+            in_number = false;
+            current_text.push(ch);
+        }
+    }
+
+    // Push remaining
+    if !current_text.is_empty() {
+        result.push((current_text, 0));
+    }
+    if !current_num.is_empty() {
+        let num = current_num.parse::<u64>().unwrap_or(0);
+        result.push((String::new(), num));
+    }
+
+    result
+}
+
+/// For a UNC path (`\\server\share\...`) whose share cannot be reached, returns the
+/// share root (`\\server\share`); `None` for local paths or reachable shares.
+pub fn unreachable_unc_share(path: &str) -> Option<String> {
+    let rest = path.strip_prefix(r"\\")?;
+    if rest.starts_with(r"?\") || rest.starts_with(r".\") {
+        return None;
+    }
+    let mut parts = rest.split('\\').filter(|p| !p.is_empty());
+    let server = parts.next()?;
+    let share = parts.next()?;
+    let root = format!(r"\\{}\{}", server, share);
+    if std::path::Path::new(&format!(r"{}\", root)).exists() {
+        None
+    } else {
+        Some(root)
+    }
+}
+
+/// File size as "1.5 MB", "12.0 KB" or "512 B"
+pub fn format_file_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Decodes %XX escapes (UTF-8) in a URL component
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = s.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Paths at or above this length (in UTF-16 units) need the `\\?\` prefix.
+/// 248 rather than 260 because directory paths leave room for an 8.3 file name.
+const LONG_PATH_THRESHOLD: usize = 248;
+
+/// Returns `path` in a form Win32 file APIs accept beyond MAX_PATH: long absolute paths
+/// get the `\\?\` (drive) or `\\?\UNC\` (network share) prefix, short paths are unchanged.
+pub fn long_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.encode_utf16().count() < LONG_PATH_THRESHOLD {
+        return path.to_string();
+    }
+
+    // The prefix disables Win32 path normalization, so separators must be backslashes
+    let normalized = path.replace('/', "\\");
+    let bytes = normalized.as_bytes();
+    if let Some(unc) = normalized.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
+    } else if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        format!(r"\\?\{}", normalized)
+    } else {
+        // Relative paths cannot be prefixed
+        normalized
+    }
+}
+
+/// Null-terminated UTF-16 path for Win32 file APIs, long-path prefixed when needed
+pub fn to_wide_path(path: &str) -> Vec<u16> {
+    long_path(path).encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Writes `target` through a temporary file next to it ("<target>.tmp"): `write` gets the
+/// temporary path, and only when it succeeded does the file replace `target`. A failed or
+/// interrupted export thus never leaves a truncated file under the target name, and an
+/// existing file stays as it was. The temporary file is deleted on failure.
+pub fn write_atomically(target: &str, write: impl FnOnce(&str) -> Result<()>) -> Result<()> {
+    let temp = format!("{}.tmp", target);
+    let temp_wide = to_wide_path(&temp);
+    let result = write(&temp).and_then(|()| unsafe {
+        MoveFileExW(PCWSTR(temp_wide.as_ptr()), PCWSTR(to_wide_path(target).as_ptr()), MOVEFILE_REPLACE_EXISTING | MOVEFILE_WRITE_THROUGH)
+            .ok()
+    });
+    if result.is_err() {
+        unsafe {
+            let _ = DeleteFileW(PCWSTR(temp_wide.as_ptr()));
+        }
+    }
+    result
+}
+
+/// Splits text into user-perceived characters (approximate grapheme clusters):
+/// combining marks, variation selectors, skin-tone modifiers and ZWJ sequences stay
+/// attached to their base character, regional indicators pair up into flags.
+pub fn grapheme_clusters(text: &str) -> Vec<&str> {
+    fn is_extend(c: char) -> bool {
+        matches!(c as u32,
+            0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F
+            | 0xE0100..=0xE01EF | 0x200D)
+    }
+    fn is_regional_indicator(c: char) -> bool {
+        (0x1F1E6..=0x1F1FF).contains(&(c as u32))
+    }
+
+    let mut clusters = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    let mut regional_count = 0;
+
+    for (i, c) in text.char_indices() {
+        let joins = match prev {
+            None => false,
+            Some(p) => {
+                is_extend(c)
+                    || p == '\u{200D}'
+                    || (is_regional_indicator(c) && is_regional_indicator(p) && regional_count % 2 == 1)
+            }
+        };
+        if !joins && i > start {
+            clusters.push(&text[start..i]);
+            start = i;
+        }
+        regional_count = if is_regional_indicator(c) { regional_count + 1 } else { 0 };
+        prev = Some(c);
+    }
+    if start < text.len() {
+        clusters.push(&text[start..]);
+    }
+    clusters
+}
+
+/// FILEVERSION from the VERSIONINFO resource of an executable, e.g. "1.1.2.0"
+pub fn file_version(path: &str) -> Option<String> {
+    let wide = to_wide_path(path);
+    unsafe {
+        let size = GetFileVersionInfoSizeW(PCWSTR(wide.as_ptr()), None);
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        if !GetFileVersionInfoW(PCWSTR(wide.as_ptr()), 0, size, data.as_mut_ptr() as *mut _).as_bool() {
+            return None;
+        }
+        let mut info = std::ptr::null_mut();
+        let mut len = 0u32;
+        if !VerQueryValueW(data.as_ptr() as *const _, w!("\\"), &mut info, &mut len).as_bool() || info.is_null() {
+            return None;
+        }
+        fixed_file_version(std::slice::from_raw_parts(info as *const u8, len as usize))
+    }
+}
+
+/// FILEVERSION of the VS_FIXEDFILEINFO of a version resource (its root "\"), which may
+/// be unaligned
+fn fixed_file_version(block: &[u8]) -> Option<String> {
+    if block.len() < std::mem::size_of::<VS_FIXEDFILEINFO>() {
+        return None;
+    }
+    let info = unsafe { std::ptr::read_unaligned(block.as_ptr() as *const VS_FIXEDFILEINFO) };
+    if info.dwSignature != 0xFEEF04BD {
+        return None;
+    }
+    Some(format!(
+        "{}.{}.{}.{}",
+        info.dwFileVersionMS >> 16,
+        info.dwFileVersionMS & 0xFFFF,
+        info.dwFileVersionLS >> 16,
+        info.dwFileVersionLS & 0xFFFF
+    ))
+}
+
+/// Version of the running executable; the package version if it has no resource
+pub fn app_version() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| file_version(&exe.to_string_lossy()))
+        .unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by_cached_key(|n| natural_sort_key(n));
+        names
+    }
+
+    #[test]
+    fn digit_runs_compare_as_numbers() {
+        assert_eq!(sorted(&["scan_10.png", "scan_2.png", "scan_1.png"]), ["scan_1.png", "scan_2.png", "scan_10.png"]);
+    }
+
+    #[test]
+    fn leading_zeros_keep_the_numeric_order() {
+        assert_eq!(sorted(&["page_010.tif", "page_2.tif", "page_009.tif"]), ["page_2.tif", "page_009.tif", "page_010.tif"]);
+        // Same number, different spelling: still a fixed order, never equal
+        assert_ne!(natural_sort_key("scan_007.png").cmp(&natural_sort_key("scan_7.png")), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn every_digit_group_counts() {
+        assert_eq!(sorted(&["v1.2.10.pdf", "v1.10.1.pdf", "v1.2.9.pdf"]), ["v1.2.9.pdf", "v1.2.10.pdf", "v1.10.1.pdf"]);
+        assert_eq!(sorted(&["2024-10-1.jpg", "2024-3-15.jpg", "2024-3-5.jpg"]), ["2024-3-5.jpg", "2024-3-15.jpg", "2024-10-1.jpg"]);
+    }
+
+    #[test]
+    fn unicode_names_ignore_case() {
+        assert_eq!(sorted(&["Übersicht 10.png", "übersicht 2.png"]), ["übersicht 2.png", "Übersicht 10.png"]);
+        assert_eq!(sorted(&["b.jpg", "C.jpg", "A.jpg"]), ["A.jpg", "b.jpg", "C.jpg"]);
+        assert_eq!(sorted(&["📷 12.jpg", "📷 3.jpg"]), ["📷 3.jpg", "📷 12.jpg"]);
+    }
+
+    #[test]
+    fn only_the_file_name_is_compared() {
+        assert_eq!(sorted(&["/a/scan 10.png", "/b/scan 9.png"]), ["/b/scan 9.png", "/a/scan 10.png"]);
+    }
+
+    /// Empty folder of its own for a test
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("SimpliView-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn failed_write_keeps_the_target() {
+        let dir = test_dir("atomic-fail");
+        let target = dir.join("export.png");
+        std::fs::write(&target, b"old").unwrap();
+        let target = target.to_string_lossy().into_owned();
+
+        let result = write_atomically(&target, |temp| {
+            std::fs::write(temp, b"half").map_err(crate::pdf_structure::io_error)?;
+            Err(Error::from(E_FAIL))
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&target).unwrap(), b"old");
+        assert!(!std::path::Path::new(&format!("{}.tmp", target)).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn successful_write_replaces_the_target() {
+        let dir = test_dir("atomic-ok");
+        let target = dir.join("export.png");
+        std::fs::write(&target, b"old").unwrap();
+        let target = target.to_string_lossy().into_owned();
+
+        write_atomically(&target, |temp| std::fs::write(temp, b"new").map_err(crate::pdf_structure::io_error)).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+        assert!(!std::path::Path::new(&format!("{}.tmp", target)).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Path of exactly `len` UTF-16 units: nested "abcdefghij" folders under `root`
+    fn deep_path(root: &str, len: usize) -> String {
+        let mut path = root.to_string();
+        while path.encode_utf16().count() < len {
+            path.push_str(if path.ends_with('\\') { "abcdefghij" } else { "\\" });
+        }
+        path.truncate(len);
+        path
+    }
+
+    #[test]
+    fn short_paths_stay_as_they_are() {
+        assert_eq!(long_path(r"C:\Scans\Brief.pdf"), r"C:\Scans\Brief.pdf");
+        assert_eq!(long_path("C:/Scans/Brief.pdf"), "C:/Scans/Brief.pdf");
+        let short = deep_path(r"C:\", LONG_PATH_THRESHOLD - 1);
+        assert_eq!(long_path(&short), short);
+    }
+
+    #[test]
+    fn long_drive_paths_get_the_prefix() {
+        let path = deep_path(r"C:\", 300);
+        assert_eq!(long_path(&path), format!(r"\\?\{}", path));
+        let at_threshold = deep_path(r"D:\", LONG_PATH_THRESHOLD);
+        assert_eq!(long_path(&at_threshold), format!(r"\\?\{}", at_threshold));
+        // Already prefixed paths are left alone
+        let prefixed = format!(r"\\?\{}", path);
+        assert_eq!(long_path(&prefixed), prefixed);
+    }
+
+    #[test]
+    fn long_share_paths_get_the_unc_prefix() {
+        let path = deep_path(r"\\server\share\", 400);
+        assert_eq!(long_path(&path), format!(r"\\?\UNC\{}", &path[2..]));
+    }
+
+    #[test]
+    fn long_paths_get_backslashes() {
+        let path = deep_path(r"C:\", 300);
+        assert_eq!(long_path(&path.replace('\\', "/")), format!(r"\\?\{}", path));
+        // Relative paths cannot be prefixed
+        let relative = deep_path(r"Scans\", 300);
+        assert_eq!(long_path(&relative.replace('\\', "/")), relative);
+    }
+
+    #[test]
+    fn path_length_counts_utf16_units() {
+        // 123 emoji are 246 UTF-16 units, but only 123 chars
+        let path = format!(r"C:\{}", "😀".repeat(123));
+        assert!(path.chars().count() < LONG_PATH_THRESHOLD);
+        assert_eq!(long_path(&path), format!(r"\\?\{}", path));
+
+        let wide = to_wide_path(&path);
+        assert_eq!(wide.last(), Some(&0));
+        assert_eq!(String::from_utf16(&wide[..wide.len() - 1]).unwrap(), format!(r"\\?\{}", path));
+    }
+
+    #[test]
+    fn graphemes_keep_marks_and_modifiers_on_their_base() {
+        assert_eq!(grapheme_clusters(""), Vec::<&str>::new());
+        assert_eq!(grapheme_clusters("Brief"), ["B", "r", "i", "e", "f"]);
+        // e + combining acute accent
+        assert_eq!(grapheme_clusters("Caf\u{65}\u{301}!"), ["C", "a", "f", "e\u{301}", "!"]);
+        // Skin tone modifier and emoji variation selector
+        assert_eq!(grapheme_clusters("👍🏽❤️x"), ["👍🏽", "❤️", "x"]);
+    }
+
+    #[test]
+    fn graphemes_keep_joined_emoji_and_flags_whole() {
+        // Family: man ZWJ woman ZWJ girl
+        let family = "👨\u{200D}👩\u{200D}👧";
+        assert_eq!(grapheme_clusters(&format!("a{family}b")), ["a", family, "b"]);
+        // Regional indicators pair up, an odd one stays alone
+        assert_eq!(grapheme_clusters("🇩🇪🇫🇷"), ["🇩🇪", "🇫🇷"]);
+        assert_eq!(grapheme_clusters("🇩🇪🇫"), ["🇩🇪", "🇫"]);
+    }
+
+    /// VS_FIXEDFILEINFO with `signature` and file version 1.2.3.4, at `offset` in the buffer
+    fn fixed_file_info(signature: u32, offset: usize) -> Vec<u8> {
+        let mut block = vec![0xCC; offset];
+        // Signature, structure version, file version MS and LS, product version MS and LS
+        for value in [signature, 0x0001_0000, 0x0001_0002, 0x0003_0004, 0x0001_0002, 0x0003_0004] {
+            block.extend_from_slice(&value.to_le_bytes());
+        }
+        // Masks, flags, OS, type, subtype and date
+        block.resize(offset + 52, 0);
+        block
+    }
+
+    #[test]
+    fn reads_the_file_version_of_fixed_file_info() {
+        assert_eq!(fixed_file_version(&fixed_file_info(0xFEEF04BD, 0)).as_deref(), Some("1.2.3.4"));
+        // Inside a version resource the block need not be aligned
+        assert_eq!(fixed_file_version(&fixed_file_info(0xFEEF04BD, 1)[1..]).as_deref(), Some("1.2.3.4"));
+        let mut high = fixed_file_info(0xFEEF04BD, 0);
+        high[8..16].copy_from_slice(&[0xFF, 0xFF, 0x0A, 0x00, 0x00, 0x00, 0xE8, 0x07]);
+        assert_eq!(fixed_file_version(&high).as_deref(), Some("10.65535.2024.0"));
+    }
+
+    #[test]
+    fn refuses_short_or_unsigned_fixed_file_info() {
+        assert_eq!(fixed_file_version(&fixed_file_info(0, 0)), None);
+        assert_eq!(fixed_file_version(&fixed_file_info(0xFEEF04BD, 0)[..51]), None);
+        assert_eq!(fixed_file_version(&[]), None);
+    }
+}
@@ -0,0 +1,226 @@
+//! Undo and redo of view changes (Strg+Z / Strg+Y)
+//!
+//! Zoom, fit, rotation and layout commands record the view before they change it, so an
+//! accidental rotate or fit click can be taken back exactly, including the scroll position
+//! and page. Commands in quick succession (wheel zoom, held + key) count as one step.
+//! The history belongs to the open document and is cleared when another one opens.
+
+use crate::app::{AppState, ZoomMode};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const MAX_ENTRIES: usize = 50;
+/// Changes closer together than this are undone together
+const COALESCE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The parts of `AppState` that make up the view
+#[derive(Clone, PartialEq)]
+pub struct ViewSnapshot {
+    pub zoom: f32,
+    pub zoom_mode: ZoomMode,
+    pub rotation: i32,
+    pub page_rotations: HashMap<usize, i32>,
+    pub multi_page_view: bool,
+    pub current_page: usize,
+    pub scroll_x: i32,
+    pub scroll_y: i32,
+}
+
+impl ViewSnapshot {
+    pub fn capture(state: &AppState) -> Self {
+        Self {
+            zoom: state.zoom,
+            zoom_mode: state.zoom_mode,
+            rotation: state.rotation,
+            page_rotations: state.page_rotations.clone(),
+            multi_page_view: state.multi_page_view,
+            current_page: state.current_page,
+            scroll_x: state.scroll_x,
+            scroll_y: state.scroll_y,
+        }
+    }
+
+    /// Writes the view back; the caller recomputes layout and scrollbars
+    pub fn restore(&self, state: &mut AppState) {
+        state.zoom = self.zoom;
+        state.zoom_mode = self.zoom_mode;
+        state.rotation = self.rotation;
+        state.page_rotations = self.page_rotations.clone();
+        state.multi_page_view = self.multi_page_view;
+        state.current_page = self.current_page.min(state.total_pages.saturating_sub(1));
+        state.scroll_x = self.scroll_x;
+        state.scroll_y = self.scroll_y;
+    }
+}
+
+#[derive(Default)]
+pub struct ViewHistory {
+    undo: Vec<ViewSnapshot>,
+    redo: Vec<ViewSnapshot>,
+    last_record: Option<Instant>,
+}
+
+impl ViewHistory {
+    /// Records the view before a command changes it; a new change drops the redo steps
+    pub fn record(&mut self, before: ViewSnapshot) {
+        self.record_at(before, Instant::now());
+    }
+
+    fn record_at(&mut self, before: ViewSnapshot, now: Instant) {
+        let coalesce = self.last_record.is_some_and(|last| now - last < COALESCE_INTERVAL);
+        self.last_record = Some(now);
+        if coalesce || self.undo.last() == Some(&before) {
+            return;
+        }
+        if self.undo.len() == MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+        self.undo.push(before);
+        self.redo.clear();
+    }
+
+    /// The view to go back to; `current` becomes the redo step
+    pub fn undo(&mut self, current: ViewSnapshot) -> Option<ViewSnapshot> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        self.last_record = None;
+        Some(previous)
+    }
+
+    /// The view undone last; `current` becomes an undo step again
+    pub fn redo(&mut self, current: ViewSnapshot) -> Option<ViewSnapshot> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        self.last_record = None;
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(zoom: f32) -> ViewSnapshot {
+        ViewSnapshot {
+            zoom,
+            zoom_mode: ZoomMode::Fixed(zoom),
+            rotation: 0,
+            page_rotations: HashMap::new(),
+            multi_page_view: false,
+            current_page: 0,
+            scroll_x: 0,
+            scroll_y: 0,
+        }
+    }
+
+    /// History with one step for each of `zooms`, recorded far enough apart
+    fn history(zooms: &[f32]) -> (ViewHistory, Instant) {
+        let mut history = ViewHistory::default();
+        let mut now = Instant::now();
+        for &zoom in zooms {
+            history.record_at(view(zoom), now);
+            now += COALESCE_INTERVAL * 2;
+        }
+        (history, now)
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_steps() {
+        let (mut history, _) = history(&[1.0, 2.0]);
+        assert!(history.can_undo() && !history.can_redo());
+        assert!(history.undo(view(3.0)) == Some(view(2.0)));
+        assert!(history.undo(view(2.0)) == Some(view(1.0)));
+        assert!(history.undo(view(1.0)).is_none());
+        assert!(history.redo(view(1.0)) == Some(view(2.0)));
+        assert!(history.redo(view(2.0)) == Some(view(3.0)));
+        assert!(history.redo(view(3.0)).is_none());
+        assert!(history.undo(view(3.0)) == Some(view(2.0)));
+    }
+
+    #[test]
+    fn new_change_drops_the_redo_steps() {
+        let (mut history, now) = history(&[1.0, 2.0]);
+        history.undo(view(3.0));
+        assert!(history.can_redo());
+        history.record_at(view(2.0), now);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn quick_changes_are_one_step() {
+        let mut history = ViewHistory::default();
+        let start = Instant::now();
+        history.record_at(view(1.0), start);
+        history.record_at(view(1.1), start + COALESCE_INTERVAL / 2);
+        history.record_at(view(1.2), start + COALESCE_INTERVAL);
+        assert!(history.undo(view(1.3)) == Some(view(1.0)));
+        assert!(!history.can_undo());
+        // After an undo the next change is a step of its own again
+        history.record_at(view(1.0), start + COALESCE_INTERVAL * 3 / 2);
+        assert!(history.can_undo());
+    }
+
+    #[test]
+    fn unchanged_view_is_recorded_once() {
+        let (mut history, _) = history(&[1.0, 1.0, 1.0]);
+        assert!(history.undo(view(2.0)) == Some(view(1.0)));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped() {
+        let zooms: Vec<f32> = (0..MAX_ENTRIES + 10).map(|i| i as f32).collect();
+        let (mut history, _) = history(&zooms);
+        let mut current = view(-1.0);
+        let mut undone = Vec::new();
+        while let Some(previous) = history.undo(current) {
+            undone.push(previous.zoom);
+            current = previous;
+        }
+        assert_eq!(undone.len(), MAX_ENTRIES);
+        assert_eq!(undone.last(), Some(&10.0));
+    }
+
+    #[test]
+    fn clear_forgets_everything() {
+        let (mut history, _) = history(&[1.0, 2.0]);
+        history.undo(view(3.0));
+        history.clear();
+        assert!(!history.can_undo() && !history.can_redo());
+    }
+
+    #[test]
+    fn restore_brings_back_the_captured_view() {
+        let mut state = AppState { total_pages: 10, ..Default::default() };
+        state.zoom = 2.0;
+        state.zoom_mode = ZoomMode::FitWidth;
+        state.rotation = 90;
+        state.page_rotations.insert(3, 180);
+        state.multi_page_view = true;
+        state.current_page = 7;
+        state.scroll_x = 12;
+        state.scroll_y = 3400;
+        let snapshot = ViewSnapshot::capture(&state);
+
+        let mut restored = AppState { total_pages: 10, ..Default::default() };
+        snapshot.restore(&mut restored);
+        assert!(ViewSnapshot::capture(&restored) == snapshot);
+
+        // A page beyond the document is clamped to its last one
+        let mut shorter = AppState { total_pages: 5, ..Default::default() };
+        snapshot.restore(&mut shorter);
+        assert_eq!(shorter.current_page, 4);
+    }
+}
@@ -3,7 +3,7 @@ use windows::{
     Win32::{
         Foundation::*,
         Graphics::Gdi::*,
-        UI::WindowsAndMessaging::*,
+        UI::{Controls::WM_MOUSELEAVE, Input::KeyboardAndMouse::{GetFocus, SetFocus}, WindowsAndMessaging::*},
     },
 };
 
@@ -37,7 +37,7 @@ impl ViewWindow {
                 WINDOW_EX_STYLE::default(),
                 VIEW_CLASS_NAME,
                 None,
-                WS_CHILD | WS_VISIBLE | WS_CLIPSIBLINGS | WS_HSCROLL | WS_VSCROLL,
+                WS_CHILD | WS_VISIBLE | WS_CLIPSIBLINGS | WS_TABSTOP | WS_HSCROLL | WS_VSCROLL,
                 0,
                 0,
                 0,
@@ -60,20 +60,27 @@ impl ViewWindow {
         self.hwnd
     }
 
-    pub fn resize(&self, x: i32, y: i32, width: i32, height: i32) {
+    /// Adds the move to `hdwp`, so the view moves together with the bars (see `App::on_resize`)
+    pub fn defer_resize(&self, hdwp: HDWP, x: i32, y: i32, width: i32, height: i32) -> Result<HDWP> {
+        unsafe { DeferWindowPos(hdwp, self.hwnd, None, x, y, width, height, SWP_NOZORDER | SWP_NOACTIVATE) }
+    }
+
+    /// Window text = accessible name read by screen readers
+    /// (e.g. "Dokumentansicht, Seite 3 von 10, 75 %"). Only set on changes, because every
+    /// SetWindowTextW raises a name-change event.
+    pub fn set_accessible_name(&self, name: &str) {
         unsafe {
-            let _ = SetWindowPos(
-                self.hwnd,
-                None,
-                x,
-                y,
-                width,
-                height,
-                SWP_NOZORDER,
-            );
+            let mut buffer = [0u16; 256];
+            let len = GetWindowTextW(self.hwnd, &mut buffer) as usize;
+            if String::from_utf16_lossy(&buffer[..len]) != name {
+                let _ = SetWindowTextW(self.hwnd, &HSTRING::from(name));
+            }
         }
     }
 
+    /// Marks the canvas dirty. Windows merges all invalidations into one WM_PAINT that is
+    /// only generated once the input queue is empty, so a burst of mouse moves or wheel
+    /// notches while panning results in a single frame (presented on vsync by D2D).
     pub fn invalidate(&self) {
         unsafe {
             let _ = InvalidateRect(self.hwnd, None, false);
@@ -94,14 +101,38 @@ extern "system" fn view_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: L
                 LRESULT(0)
             }
             WM_ERASEBKGND => LRESULT(1), // Prevent flicker
+
+            // Clicking the canvas gives it the keyboard focus
+            WM_LBUTTONDOWN | WM_RBUTTONDOWN if GetFocus() != hwnd => {
+                SetFocus(hwnd);
+                let parent = GetParent(hwnd);
+                SendMessageW(parent, msg, wparam, lparam)
+            }
+
+            // Parent draws the keyboard focus border
+            WM_SETFOCUS | WM_KILLFOCUS => {
+                let _ = InvalidateRect(hwnd, None, false);
+                LRESULT(0)
+            }
             
             // Forward input to parent for handling
-            WM_LBUTTONDOWN | WM_LBUTTONUP | WM_RBUTTONDOWN | WM_MOUSEMOVE | WM_MOUSEWHEEL
+            WM_LBUTTONDOWN | WM_LBUTTONUP | WM_RBUTTONDOWN | WM_MOUSEMOVE | WM_MOUSELEAVE | WM_MOUSEWHEEL
             | WM_KEYDOWN | WM_KEYUP | WM_HSCROLL | WM_VSCROLL => {
                 let parent = GetParent(hwnd);
                 SendMessageW(parent, msg, wparam, lparam)
             }
             
+            // Right-drag zooms into a rectangle; the context menu is only generated
+            // (by DefWindowProc) if the parent did not consume the release as a drag
+            WM_RBUTTONUP => {
+                let parent = GetParent(hwnd);
+                if SendMessageW(parent, msg, wparam, lparam).0 == 1 {
+                    LRESULT(0)
+                } else {
+                    DefWindowProcW(hwnd, msg, wparam, lparam)
+                }
+            }
+
             WM_SETCURSOR => {
                  let parent = GetParent(hwnd);
                  if SendMessageW(parent, msg, wparam, lparam).0 == 1 {
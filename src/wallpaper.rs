@@ -0,0 +1,150 @@
+//! Desktop wallpaper
+//!
+//! "Als Hintergrundbild festlegen" in the context menu saves the current page (rotated as
+//! shown) as PNG in the temp folder and hands it to IDesktopWallpaper, either for all
+//! monitors or a single one. Windows keeps referring to the file per monitor, so each
+//! target gets its own file that is only replaced by the next wallpaper for that target.
+
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        System::{Com::*, Registry::*},
+        UI::Shell::*,
+    },
+};
+
+const ACTIVE_DESKTOP_POLICY_KEY: PCWSTR = w!("Software\\Microsoft\\Windows\\CurrentVersion\\Policies\\ActiveDesktop");
+const SYSTEM_POLICY_KEY: PCWSTR = w!("Software\\Microsoft\\Windows\\CurrentVersion\\Policies\\System");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WallpaperPosition {
+    Fill,
+    Fit,
+    Center,
+}
+
+impl WallpaperPosition {
+    pub const ALL: [Self; 3] = [Self::Fill, Self::Fit, Self::Center];
+
+    pub fn label(self) -> PCWSTR {
+        match self {
+            Self::Fill => w!("Ausfüllen"),
+            Self::Fit => w!("Anpassen"),
+            Self::Center => w!("Zentriert"),
+        }
+    }
+
+    fn to_dwpos(self) -> DESKTOP_WALLPAPER_POSITION {
+        match self {
+            Self::Fill => DWPOS_FILL,
+            Self::Fit => DWPOS_FIT,
+            Self::Center => DWPOS_CENTER,
+        }
+    }
+}
+
+/// An attached monitor as reported by IDesktopWallpaper
+pub struct Monitor {
+    id: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+fn desktop_wallpaper() -> Result<IDesktopWallpaper> {
+    unsafe { CoCreateInstance(&DesktopWallpaper, None, CLSCTX_ALL) }
+}
+
+/// Active monitors in IDesktopWallpaper order (detached ones have no rectangle and are skipped)
+pub fn monitors() -> Result<Vec<Monitor>> {
+    let wallpaper = desktop_wallpaper()?;
+    let mut monitors = Vec::new();
+    unsafe {
+        for i in 0..wallpaper.GetMonitorDevicePathCount()? {
+            let path = wallpaper.GetMonitorDevicePathAt(i)?;
+            let id = path.to_string().unwrap_or_default();
+            CoTaskMemFree(Some(path.0 as *const _));
+            if let Ok(rect) = wallpaper.GetMonitorRECT(&HSTRING::from(id.as_str())) {
+                monitors.push(Monitor {
+                    id,
+                    width: rect.right - rect.left,
+                    height: rect.bottom - rect.top,
+                });
+            }
+        }
+    }
+    Ok(monitors)
+}
+
+/// File the wallpaper for a target is written to (`None` = all monitors)
+pub fn image_path(monitor: Option<usize>) -> String {
+    let name = match monitor {
+        Some(index) => format!("SimpliView-Hintergrund-{}.png", index + 1),
+        None => "SimpliView-Hintergrund.png".to_string(),
+    };
+    std::env::temp_dir().join(name).to_string_lossy().into_owned()
+}
+
+/// Applies an image as wallpaper on one monitor (`Some(index)` into `monitors()`) or all
+pub fn apply(image_path: &str, monitor: Option<usize>, position: WallpaperPosition) -> Result<()> {
+    if blocked_by_policy() {
+        return Err(Error::new(
+            E_ACCESSDENIED,
+            HSTRING::from("Das Ändern des Hintergrundbilds ist durch eine Gruppenrichtlinie gesperrt."),
+        ));
+    }
+
+    let wallpaper = desktop_wallpaper()?;
+    let monitor_id = match monitor {
+        Some(index) => {
+            let monitors = monitors()?;
+            let monitor = monitors.get(index).ok_or_else(|| {
+                Error::new(E_INVALIDARG, HSTRING::from("Der Bildschirm ist nicht mehr angeschlossen."))
+            })?;
+            HSTRING::from(monitor.id.as_str())
+        }
+        // A null monitor ID sets the wallpaper on all monitors
+        None => HSTRING::new(),
+    };
+
+    unsafe {
+        wallpaper.SetPosition(position.to_dwpos())?;
+        let path = crate::utils::to_wide_path(image_path);
+        if monitor_id.is_empty() {
+            wallpaper.SetWallpaper(PCWSTR::null(), PCWSTR(path.as_ptr()))
+        } else {
+            wallpaper.SetWallpaper(&monitor_id, PCWSTR(path.as_ptr()))
+        }
+    }
+}
+
+/// Windows' own wallpaper policies (IDesktopWallpaper may silently ignore them)
+fn blocked_by_policy() -> bool {
+    [HKEY_LOCAL_MACHINE, HKEY_CURRENT_USER].into_iter().any(|root| unsafe {
+        let mut value = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let no_changing = RegGetValueW(
+            root,
+            ACTIVE_DESKTOP_POLICY_KEY,
+            w!("NoChangingWallPaper"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut value as *mut _ as *mut _),
+            Some(&mut size),
+        ).0 == 0 && value != 0;
+
+        // A fixed wallpaper set by policy (REG_SZ "Wallpaper")
+        let mut size = 0u32;
+        let fixed = RegGetValueW(
+            root,
+            SYSTEM_POLICY_KEY,
+            w!("Wallpaper"),
+            RRF_RT_REG_SZ,
+            None,
+            None,
+            Some(&mut size),
+        ).0 == 0;
+
+        no_changing || fixed
+    })
+}
@@ -0,0 +1,216 @@
+//! Compliance watermark
+//!
+//! Restricted deployments can stamp every displayed, printed, exported or copied page
+//! with a diagonal, translucent text (e.g. "%USERNAME% %DATE%"). The text is tiled
+//! across the page. Screen and export draw it with Direct2D/DirectWrite, printing
+//! uses GDI so the text stays vector on the printer.
+
+use crate::d2d::{get_dwrite_factory, make_rotation_matrix};
+use windows::{
+    core::*,
+    Foundation::Numerics::Matrix3x2,
+    Win32::{
+        Foundation::COLORREF,
+        Graphics::{
+            Direct2D::{Common::*, *},
+            DirectWrite::*,
+            Gdi::*,
+        },
+        System::SystemInformation::GetLocalTime,
+    },
+};
+
+const DEFAULT_OPACITY_PERCENT: u32 = 15;
+const DEFAULT_ANGLE_DEGREES: i32 = 30;
+
+const POLICY_TEXT: PCWSTR = w!("WatermarkText");
+const POLICY_OPACITY: PCWSTR = w!("WatermarkOpacity");
+const POLICY_ANGLE: PCWSTR = w!("WatermarkAngle");
+
+// Rough average glyph width relative to the font size (Segoe UI Bold), used for tiling
+const AVG_CHAR_WIDTH: f32 = 0.6;
+
+#[derive(Clone, Debug)]
+pub struct Watermark {
+    /// Text with placeholders (%USERNAME%, %COMPUTERNAME%, %DATE%)
+    template: String,
+    /// 0.0 - 1.0
+    opacity: f32,
+    /// Degrees, counter-clockwise
+    angle: i32,
+}
+
+impl Watermark {
+    /// Watermark from command line, falling back to the registry policy.
+    /// Returns `None` if no watermark text is configured.
+    pub fn from_startup(text: Option<String>, opacity_percent: Option<u32>, angle: Option<i32>) -> Option<Self> {
+        let template = text
+            .or_else(|| crate::policy::read_string(POLICY_TEXT))
+            .filter(|t| !t.trim().is_empty())?;
+        let opacity_percent = opacity_percent
+            .or_else(|| crate::policy::read_dword(POLICY_OPACITY))
+            .unwrap_or(DEFAULT_OPACITY_PERCENT)
+            .clamp(1, 100);
+        let angle = angle
+            .or_else(|| crate::policy::read_dword(POLICY_ANGLE).map(|v| v as i32))
+            .unwrap_or(DEFAULT_ANGLE_DEGREES);
+
+        Some(Self {
+            template,
+            opacity: opacity_percent as f32 / 100.0,
+            angle: angle.rem_euclid(360),
+        })
+    }
+
+    /// Text with placeholders expanded (evaluated on every render)
+    pub fn text(&self) -> String {
+        let now = unsafe { GetLocalTime() };
+        let date = format!(
+            "{:02}.{:02}.{:04} {:02}:{:02}",
+            now.wDay, now.wMonth, now.wYear, now.wHour, now.wMinute
+        );
+        self.template
+            .replace("%USERNAME%", &std::env::var("USERNAME").unwrap_or_default())
+            .replace("%COMPUTERNAME%", &std::env::var("COMPUTERNAME").unwrap_or_default())
+            .replace("%DATE%", &date)
+    }
+
+    /// Font size scales with the page so the watermark looks the same at any zoom
+    fn font_size(width: f32, height: f32) -> f32 {
+        (width.min(height) / 16.0).max(8.0)
+    }
+
+    /// Centers of the text tiles covering a page of the given size (relative to its
+    /// top-left corner). The grid extends beyond the page so rotated rows reach the corners.
+    fn tile_centers(width: f32, height: f32, text_width: f32, font_size: f32) -> Vec<(f32, f32)> {
+        let step_x = text_width + font_size * 3.0;
+        let step_y = font_size * 5.0;
+        let margin = width.max(height) / 2.0;
+
+        let mut centers = Vec::new();
+        let mut row = 0;
+        let mut y = -margin;
+        while y < height + margin {
+            // Offset every other row for a brick pattern
+            let mut x = -margin + if row % 2 == 1 { step_x / 2.0 } else { 0.0 };
+            while x < width + margin {
+                centers.push((x, y));
+                x += step_x;
+            }
+            y += step_y;
+            row += 1;
+        }
+        centers
+    }
+
+    /// Draws the watermark tiled across `rect` on a Direct2D render target.
+    /// Expects an identity transform on entry and restores it afterwards.
+    pub fn draw_d2d(&self, rt: &ID2D1RenderTarget, rect: &D2D_RECT_F) -> Result<()> {
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
+        if width <= 0.0 || height <= 0.0 {
+            return Ok(());
+        }
+
+        let text: Vec<u16> = self.text().encode_utf16().collect();
+        let font_size = Self::font_size(width, height);
+        let text_width = text.len() as f32 * font_size * AVG_CHAR_WIDTH;
+
+        unsafe {
+            let format = get_dwrite_factory()?.CreateTextFormat(
+                w!("Segoe UI"),
+                None,
+                DWRITE_FONT_WEIGHT_BOLD,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                font_size,
+                w!("de-de"),
+            )?;
+            format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+            format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+            format.SetWordWrapping(DWRITE_WORD_WRAPPING_NO_WRAP)?;
+
+            let color = D2D1_COLOR_F { r: 0.5, g: 0.5, b: 0.5, a: self.opacity };
+            let brush = rt.CreateSolidColorBrush(&color, None)?;
+
+            let mut old_transform = Matrix3x2::default();
+            rt.GetTransform(&mut old_transform);
+            rt.PushAxisAlignedClip(rect, D2D1_ANTIALIAS_MODE_PER_PRIMITIVE);
+
+            for (cx, cy) in Self::tile_centers(width, height, text_width, font_size) {
+                let center_x = rect.left + cx;
+                let center_y = rect.top + cy;
+                // D2D rotates clockwise for positive angles
+                rt.SetTransform(&make_rotation_matrix(-(self.angle as f32), center_x, center_y));
+
+                let layout = D2D_RECT_F {
+                    left: center_x - text_width,
+                    top: center_y - font_size,
+                    right: center_x + text_width,
+                    bottom: center_y + font_size,
+                };
+                rt.DrawText(
+                    &text,
+                    &format,
+                    &layout,
+                    &brush,
+                    D2D1_DRAW_TEXT_OPTIONS_NONE,
+                    DWRITE_MEASURING_MODE_NATURAL,
+                );
+            }
+
+            rt.PopAxisAlignedClip();
+            rt.SetTransform(&old_transform);
+        }
+
+        Ok(())
+    }
+
+    /// Draws the watermark with GDI into the given device rectangle (printing)
+    pub fn draw_gdi(&self, hdc: HDC, left: i32, top: i32, width: i32, height: i32) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let text: Vec<u16> = self.text().encode_utf16().collect();
+        let font_size = Self::font_size(width as f32, height as f32);
+        let text_width = text.len() as f32 * font_size * AVG_CHAR_WIDTH;
+
+        // GDI text has no alpha - approximate the opacity with a lighter grey on white paper
+        let level = (255.0 - self.opacity * 127.0) as u32;
+        let color = COLORREF(level | (level << 8) | (level << 16));
+
+        unsafe {
+            let font = CreateFontW(
+                -(font_size as i32),
+                0,
+                self.angle * 10,
+                self.angle * 10,
+                FW_BOLD.0 as i32,
+                0,
+                0,
+                0,
+                DEFAULT_CHARSET.0 as u32,
+                OUT_DEFAULT_PRECIS.0 as u32,
+                CLIP_DEFAULT_PRECIS.0 as u32,
+                ANTIALIASED_QUALITY.0 as u32,
+                (DEFAULT_PITCH.0 | FF_SWISS.0) as u32,
+                w!("Segoe UI"),
+            );
+
+            let saved = SaveDC(hdc);
+            IntersectClipRect(hdc, left, top, left + width, top + height);
+            SelectObject(hdc, font);
+            SetBkMode(hdc, TRANSPARENT);
+            SetTextColor(hdc, color);
+            SetTextAlign(hdc, TA_CENTER | TA_BASELINE);
+
+            for (cx, cy) in Self::tile_centers(width as f32, height as f32, text_width, font_size) {
+                let _ = TextOutW(hdc, left + cx as i32, top + cy as i32, &text);
+            }
+
+            RestoreDC(hdc, saved);
+            DeleteObject(font);
+        }
+    }
+}
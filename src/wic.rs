@@ -1,11 +1,15 @@
+use crate::cancel::CancelToken;
 use crate::document::Document;
-use std::cell::RefCell;
+use crate::page_transform::PageTransform;
+use crate::watermark::Watermark;
+use crate::window_level::Gray16;
+use std::cell::{Cell, RefCell};
 use windows::{
     core::*,
     Win32::{
-        Foundation::{GENERIC_READ, GENERIC_WRITE},
+        Foundation::{GENERIC_READ, GENERIC_WRITE, VARIANT_FALSE, VARIANT_TRUE, WINCODEC_ERR_COMPONENTNOTFOUND},
         Graphics::Imaging::*,
-        System::Com::{StructuredStorage::IPropertyBag2, *},
+        System::Com::{StructuredStorage::{IPropertyBag2, PROPBAG2}, *},
     },
 };
 
@@ -26,6 +30,232 @@ fn get_wic_factory() -> Result<IWICImagingFactory> {
     })
 }
 
+/// Reads the pixel size and source format (see `source_format`) of an encoded image in
+/// memory without decoding the pixels
+pub fn read_header_from_memory(data: &[u8]) -> Result<(u32, u32, Option<String>)> {
+    let factory = get_wic_factory()?;
+
+    unsafe {
+        let stream = factory.CreateStream()?;
+        stream.InitializeFromMemory(data)?;
+
+        let decoder = factory.CreateDecoderFromStream(
+            &stream,
+            std::ptr::null(),
+            WICDecodeMetadataCacheOnDemand,
+        )?;
+
+        let frame = decoder.GetFrame(0)?;
+        let mut width = 0u32;
+        let mut height = 0u32;
+        frame.GetSize(&mut width, &mut height)?;
+
+        Ok((width, height, source_format(&factory, &decoder, &frame)))
+    }
+}
+
+/// Readable names of the container formats
+const CONTAINER_NAMES: [(GUID, &str); 9] = [
+    (GUID_ContainerFormatTiff, "TIFF"),
+    (GUID_ContainerFormatPng, "PNG"),
+    (GUID_ContainerFormatJpeg, "JPEG"),
+    (GUID_ContainerFormatBmp, "BMP"),
+    (GUID_ContainerFormatGif, "GIF"),
+    (GUID_ContainerFormatWebp, "WebP"),
+    (GUID_ContainerFormatIco, "ICO"),
+    (GUID_ContainerFormatHeif, "HEIF"),
+    (GUID_ContainerFormatWmp, "JPEG XR"),
+];
+
+/// Readable names of common pixel formats; others are described from WIC's component info
+const PIXEL_FORMAT_NAMES: [(GUID, &str); 30] = [
+    (GUID_WICPixelFormatBlackWhite, "1-Bit Schwarzweiß"),
+    (GUID_WICPixelFormat1bppIndexed, "1-Bit Palette"),
+    (GUID_WICPixelFormat2bppIndexed, "2-Bit Palette"),
+    (GUID_WICPixelFormat4bppIndexed, "4-Bit Palette"),
+    (GUID_WICPixelFormat8bppIndexed, "8-Bit Palette"),
+    (GUID_WICPixelFormat2bppGray, "2-Bit Graustufen"),
+    (GUID_WICPixelFormat4bppGray, "4-Bit Graustufen"),
+    (GUID_WICPixelFormat8bppGray, "8-Bit Graustufen"),
+    (GUID_WICPixelFormat16bppGray, "16-Bit Graustufen"),
+    (GUID_WICPixelFormat16bppGrayHalf, "16-Bit Graustufen (Gleitkomma)"),
+    (GUID_WICPixelFormat32bppGrayFloat, "32-Bit Graustufen (Gleitkomma)"),
+    (GUID_WICPixelFormat8bppAlpha, "8-Bit Alpha"),
+    (GUID_WICPixelFormat16bppBGR555, "16-Bit RGB"),
+    (GUID_WICPixelFormat16bppBGR565, "16-Bit RGB"),
+    (GUID_WICPixelFormat16bppBGRA5551, "16-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat24bppBGR, "24-Bit RGB"),
+    (GUID_WICPixelFormat24bppRGB, "24-Bit RGB"),
+    (GUID_WICPixelFormat32bppBGR, "24-Bit RGB"),
+    (GUID_WICPixelFormat32bppRGB, "24-Bit RGB"),
+    (GUID_WICPixelFormat32bppBGRA, "32-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat32bppPBGRA, "32-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat32bppRGBA, "32-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat32bppPRGBA, "32-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat48bppRGB, "48-Bit RGB"),
+    (GUID_WICPixelFormat48bppBGR, "48-Bit RGB"),
+    (GUID_WICPixelFormat64bppRGBA, "64-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat64bppBGRA, "64-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat64bppPRGBA, "64-Bit RGB mit Alpha"),
+    (GUID_WICPixelFormat32bppCMYK, "32-Bit CMYK"),
+    (GUID_WICPixelFormat64bppCMYK, "64-Bit CMYK"),
+];
+
+/// Container and pixel format of a frame before the conversion to 32bpp PBGRA,
+/// e.g. "TIFF, 16-Bit Graustufen" or "PNG, 32-Bit RGB mit Alpha"
+fn source_format(factory: &IWICImagingFactory, decoder: &IWICBitmapDecoder, frame: &IWICBitmapFrameDecode) -> Option<String> {
+    let pixel_format = unsafe { frame.GetPixelFormat() }.ok()?;
+    let pixels = match PIXEL_FORMAT_NAMES.iter().find(|(guid, _)| *guid == pixel_format) {
+        Some((_, name)) => name.to_string(),
+        None => unsafe {
+            let info: IWICPixelFormatInfo2 = factory.CreateComponentInfo(&pixel_format).ok()?.cast().ok()?;
+            let bits = info.GetBitsPerPixel().ok()?;
+            let channels = info.GetChannelCount().ok()?;
+            let alpha = info.SupportsTransparency().is_ok_and(|a| a.as_bool());
+            format!("{}-Bit, {} Kanäle{}", bits, channels, if alpha { " mit Alpha" } else { "" })
+        },
+    };
+    let container = unsafe { decoder.GetContainerFormat() }
+        .ok()
+        .and_then(|guid| CONTAINER_NAMES.iter().find(|(g, _)| *g == guid))
+        .map(|(_, name)| *name);
+    Some(match container {
+        Some(container) => format!("{}, {}", container, pixels),
+        None => pixels,
+    })
+}
+
+/// Horizontal resolution stored in the image, `None` if missing or implausible
+fn frame_dpi(frame: &IWICBitmapFrameDecode) -> Option<f32> {
+    let mut dpi_x = 0.0f64;
+    let mut dpi_y = 0.0f64;
+    unsafe { frame.GetResolution(&mut dpi_x, &mut dpi_y).ok()? };
+    (dpi_x.is_finite() && dpi_x >= 1.0).then_some(dpi_x as f32)
+}
+
+/// Decodes the first frame of an encoded image in memory into a PBGRA WIC bitmap
+pub fn decode_from_memory(data: &[u8]) -> Result<IWICBitmap> {
+    let factory = get_wic_factory()?;
+
+    unsafe {
+        let stream = factory.CreateStream()?;
+        stream.InitializeFromMemory(data)?;
+
+        let decoder = factory.CreateDecoderFromStream(
+            &stream,
+            std::ptr::null(),
+            WICDecodeMetadataCacheOnDemand,
+        )?;
+
+        let frame = decoder.GetFrame(0)?;
+
+        // Convert to BGRA format
+        let converter = factory.CreateFormatConverter()?;
+        converter.Initialize(
+            &frame,
+            &GUID_WICPixelFormat32bppPBGRA,
+            WICBitmapDitherTypeNone,
+            None,
+            0.0,
+            WICBitmapPaletteTypeMedianCut,
+        )?;
+
+        // CacheOnLoad copies the pixels, so the bitmap does not borrow `data`
+        factory.CreateBitmapFromSource(&converter, WICBitmapCacheOnLoad)
+    }
+}
+
+/// WIC bitmap over a copy of 32bpp PBGRA pixels (stride = width * 4)
+pub fn bitmap_from_pbgra(width: u32, height: u32, data: &[u8]) -> Result<IWICBitmap> {
+    let factory = get_wic_factory()?;
+    unsafe { factory.CreateBitmapFromMemory(width, height, &GUID_WICPixelFormat32bppPBGRA, width * 4, data) }
+}
+
+/// Writes `bitmap` as a PNG file (`--render-test`)
+pub fn save_png(bitmap: &IWICBitmap, path: &str) -> Result<()> {
+    let factory = get_wic_factory()?;
+    let source: IWICBitmapSource = bitmap.cast()?;
+    crate::utils::write_atomically(path, |temp| unsafe {
+        WicLoader::encode(&factory, &GUID_ContainerFormatPng, temp, &source, false, false)
+    })
+}
+
+/// Quality of lossy WebP export (0.0-1.0); the codec default is noticeably blurrier
+const WEBP_QUALITY: f32 = 0.9;
+
+/// Sets one encoder option in the property bag of a new frame
+unsafe fn write_encoder_option(props: &IPropertyBag2, name: PCWSTR, value: VARIANT) -> Result<()> {
+    let option = PROPBAG2 {
+        pstrName: PWSTR(name.0 as *mut u16),
+        ..Default::default()
+    };
+    props.Write(1, &option, &value)
+}
+
+fn variant_bool(value: bool) -> VARIANT {
+    let mut variant = VARIANT::default();
+    unsafe {
+        let inner = &mut variant.Anonymous.Anonymous;
+        inner.vt = VT_BOOL;
+        inner.Anonymous.boolVal = if value { VARIANT_TRUE } else { VARIANT_FALSE };
+    }
+    variant
+}
+
+fn variant_f32(value: f32) -> VARIANT {
+    let mut variant = VARIANT::default();
+    unsafe {
+        let inner = &mut variant.Anonymous.Anonymous;
+        inner.vt = VT_R4;
+        inner.Anonymous.fltVal = value;
+    }
+    variant
+}
+
+/// Returns a PBGRA copy of `source` with the watermark drawn over it
+fn apply_watermark(factory: &IWICImagingFactory, source: &IWICBitmapSource, watermark: &Watermark) -> Result<IWICBitmap> {
+    use windows::Win32::Graphics::{Direct2D::{Common::*, *}, Dxgi::Common::*};
+
+    unsafe {
+        let converter = factory.CreateFormatConverter()?;
+        converter.Initialize(
+            source,
+            &GUID_WICPixelFormat32bppPBGRA,
+            WICBitmapDitherTypeNone,
+            None,
+            0.0,
+            WICBitmapPaletteTypeMedianCut,
+        )?;
+        let bitmap = factory.CreateBitmapFromSource(&converter, WICBitmapCacheOnLoad)?;
+
+        let mut width = 0u32;
+        let mut height = 0u32;
+        bitmap.GetSize(&mut width, &mut height)?;
+
+        // 96 DPI so one DIP equals one pixel of the bitmap
+        let props = D2D1_RENDER_TARGET_PROPERTIES {
+            r#type: D2D1_RENDER_TARGET_TYPE_SOFTWARE,
+            pixelFormat: D2D1_PIXEL_FORMAT {
+                format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                alphaMode: D2D1_ALPHA_MODE_PREMULTIPLIED,
+            },
+            dpiX: 96.0,
+            dpiY: 96.0,
+            usage: D2D1_RENDER_TARGET_USAGE_NONE,
+            minLevel: D2D1_FEATURE_LEVEL_DEFAULT,
+        };
+        let rt = crate::d2d::get_d2d_factory()?.CreateWicBitmapRenderTarget(&bitmap, &props)?;
+
+        rt.BeginDraw();
+        let rect = D2D_RECT_F { left: 0.0, top: 0.0, right: width as f32, bottom: height as f32 };
+        let drawn = watermark.draw_d2d(&rt, &rect);
+        rt.EndDraw(None, None)?;
+        drawn?;
+
+        Ok(bitmap)
+    }
+}
+
 pub struct ClipboardBitmapData {
     pub width: u32,
     pub height: u32,
@@ -33,37 +263,112 @@ pub struct ClipboardBitmapData {
 }
 
 pub struct WicLoader {
-    _marker: std::marker::PhantomData<()>,
+    /// "16-Bit-Graustufen erhalten": keep 16-bit grayscale frames for window/level
+    keep_high_bit_depth: Cell<bool>,
+    /// "In reduzierter Qualität öffnen": frames are scaled down to REDUCED_MAX_DIMENSION
+    reduced_quality: Cell<bool>,
 }
 
+/// Longest side of a frame loaded in reduced quality
+const REDUCED_MAX_DIMENSION: u32 = 4096;
+
 impl WicLoader {
     pub fn new() -> Result<Self> {
         // Initialize WIC factory
         let _ = get_wic_factory()?;
         Ok(Self {
-            _marker: std::marker::PhantomData,
+            keep_high_bit_depth: Cell::new(false),
+            reduced_quality: Cell::new(false),
         })
     }
 
-    pub fn load(&self, path: &str) -> Result<Document> {
+    /// Applies to documents loaded afterwards
+    pub fn set_reduced_quality(&self, enabled: bool) {
+        self.reduced_quality.set(enabled);
+    }
+
+    /// Memory the decoded frames of an image would take, from the decoder's header only
+    pub fn estimate_memory(path: &str) -> Result<u64> {
         let factory = get_wic_factory()?;
+        unsafe {
+            let path_wide = crate::utils::to_wide_path(path);
+            let decoder = factory.CreateDecoderFromFilename(PCWSTR(path_wide.as_ptr()), None, GENERIC_READ, WICDecodeMetadataCacheOnDemand)?;
+            let mut sizes = Vec::new();
+            for i in 0..decoder.GetFrameCount()? {
+                let (mut width, mut height) = (0u32, 0u32);
+                decoder.GetFrame(i)?.GetSize(&mut width, &mut height)?;
+                sizes.push((width, height));
+            }
+            Ok(crate::document::bitmap_bytes(sizes))
+        }
+    }
 
+    /// Converts a frame to a PBGRA bitmap, scaled down in reduced quality. Returns the bitmap
+    /// with its size.
+    fn decode_frame(&self, factory: &IWICImagingFactory, frame: &IWICBitmapFrameDecode, width: u32, height: u32) -> Result<(IWICBitmap, u32, u32)> {
         unsafe {
-            // Create decoder from file
-            let path_wide: Vec<u16> = path.encode_utf16().chain(std::iter::once(0)).collect();
-            let decoder = factory.CreateDecoderFromFilename(
-                PCWSTR(path_wide.as_ptr()),
+            let converter = factory.CreateFormatConverter()?;
+            converter.Initialize(
+                frame,
+                &GUID_WICPixelFormat32bppPBGRA,
+                WICBitmapDitherTypeNone,
                 None,
-                GENERIC_READ,
-                WICDecodeMetadataCacheOnDemand,
+                0.0,
+                WICBitmapPaletteTypeMedianCut,
             )?;
 
+            let longest = width.max(height);
+            if !self.reduced_quality.get() || longest <= REDUCED_MAX_DIMENSION {
+                return Ok((factory.CreateBitmapFromSource(&converter, WICBitmapCacheOnLoad)?, width, height));
+            }
+            let scale = REDUCED_MAX_DIMENSION as f64 / longest as f64;
+            let (scaled_width, scaled_height) = (((width as f64 * scale) as u32).max(1), ((height as f64 * scale) as u32).max(1));
+            let scaler = factory.CreateBitmapScaler()?;
+            scaler.Initialize(&converter, scaled_width, scaled_height, WICBitmapInterpolationModeFant)?;
+            Ok((factory.CreateBitmapFromSource(&scaler, WICBitmapCacheOnLoad)?, scaled_width, scaled_height))
+        }
+    }
+
+    /// Applies to documents loaded afterwards
+    pub fn set_keep_high_bit_depth(&self, enabled: bool) {
+        self.keep_high_bit_depth.set(enabled);
+    }
+
+    /// The 16-bit values of a 16-bit grayscale frame, if they are kept for window/level
+    fn read_gray16(&self, frame: &IWICBitmapFrameDecode, width: u32, height: u32) -> Result<Option<Gray16>> {
+        unsafe {
+            // Window/level needs the full frame, so reduced quality shows 8-bit pixels
+            if !self.keep_high_bit_depth.get() || self.reduced_quality.get() || frame.GetPixelFormat()? != GUID_WICPixelFormat16bppGray {
+                return Ok(None);
+            }
+            let mut pixels = vec![0u16; width as usize * height as usize];
+            let bytes = std::slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, pixels.len() * 2);
+            frame.CopyPixels(std::ptr::null(), width * 2, bytes)?;
+            Ok(Some(Gray16::new(width, height, pixels)))
+        }
+    }
+
+    pub fn load(&self, path: &str, cancel: &CancelToken) -> Result<Document> {
+        let factory = get_wic_factory()?;
+
+        unsafe {
+            // Create decoder from file
+            let path_wide = crate::utils::to_wide_path(path);
+            let decoder = crate::retry::with_retry("Bild öffnen", path, || {
+                factory.CreateDecoderFromFilename(
+                    PCWSTR(path_wide.as_ptr()),
+                    None,
+                    GENERIC_READ,
+                    WICDecodeMetadataCacheOnDemand,
+                )
+            })?;
+
             // Get frame count for multi-page support (TIFF)
             let frame_count = decoder.GetFrameCount()?;
 
             if frame_count > 1 {
                 // Multi-frame image (e.g., multi-page TIFF)
-                return self.load_multiframe(&factory, &decoder, frame_count);
+                return self.load_multiframe(&factory, &decoder, frame_count, cancel);
             }
 
             // Single frame image
@@ -74,21 +379,24 @@ impl WicLoader {
             let mut height = 0u32;
             frame.GetSize(&mut width, &mut height)?;
 
-            // Convert to BGRA format
-            let converter = factory.CreateFormatConverter()?;
-            converter.Initialize(
-                &frame,
-                &GUID_WICPixelFormat32bppPBGRA,
-                WICBitmapDitherTypeNone,
-                None,
-                0.0,
-                WICBitmapPaletteTypeMedianCut,
-            )?;
+            if let Some(gray16) = self.read_gray16(&frame, width, height)? {
+                return Ok(Document::new_multiframe_image(vec![crate::document::PageData {
+                    width: width as f32,
+                    height: height as f32,
+                    dpi: frame_dpi(&frame),
+                    source_format: source_format(&factory, &decoder, &frame),
+                    gray16: Some(gray16),
+                    ..Default::default()
+                }]));
+            }
 
-            // Create WIC bitmap
-            let wic_bitmap = factory.CreateBitmapFromSource(&converter, WICBitmapCacheOnLoad)?;
+            // Convert to BGRA and create the WIC bitmap
+            cancel.check()?;
+            let (wic_bitmap, shown_width, shown_height) = self.decode_frame(&factory, &frame, width, height)?;
 
-            Ok(Document::new_image(wic_bitmap, width, height))
+            let dpi = frame_dpi(&frame).map(|dpi| dpi * shown_width as f32 / width as f32);
+            let format = source_format(&factory, &decoder, &frame);
+            Ok(Document::new_image(wic_bitmap, shown_width, shown_height, dpi, format))
         }
     }
 
@@ -98,6 +406,7 @@ impl WicLoader {
         factory: &IWICImagingFactory,
         decoder: &IWICBitmapDecoder,
         frame_count: u32,
+        cancel: &CancelToken,
     ) -> Result<Document> {
         use crate::document::PageData;
 
@@ -105,32 +414,36 @@ impl WicLoader {
 
         unsafe {
             for i in 0..frame_count {
+                // Partially decoded frames are dropped with `pages` on cancel
+                cancel.check()?;
                 let frame = decoder.GetFrame(i)?;
 
                 let mut width = 0u32;
                 let mut height = 0u32;
                 frame.GetSize(&mut width, &mut height)?;
 
-                // Convert to BGRA format
-                let converter = factory.CreateFormatConverter()?;
-                converter.Initialize(
-                    &frame,
-                    &GUID_WICPixelFormat32bppPBGRA,
-                    WICBitmapDitherTypeNone,
-                    None,
-                    0.0,
-                    WICBitmapPaletteTypeMedianCut,
-                )?;
-
-                // Create WIC bitmap for this frame
-                let wic_bitmap = factory.CreateBitmapFromSource(&converter, WICBitmapCacheOnLoad)?;
+                if let Some(gray16) = self.read_gray16(&frame, width, height)? {
+                    pages.push(PageData {
+                        width: width as f32,
+                        height: height as f32,
+                        dpi: frame_dpi(&frame),
+                        source_format: source_format(factory, decoder, &frame),
+                        gray16: Some(gray16),
+                        ..Default::default()
+                    });
+                    continue;
+                }
+
+                // Convert to BGRA and create the WIC bitmap for this frame
+                let (wic_bitmap, shown_width, shown_height) = self.decode_frame(factory, &frame, width, height)?;
 
                 pages.push(PageData {
-                    width: width as f32,
-                    height: height as f32,
+                    width: shown_width as f32,
+                    height: shown_height as f32,
+                    dpi: frame_dpi(&frame).map(|dpi| dpi * shown_width as f32 / width as f32),
+                    source_format: source_format(factory, decoder, &frame),
                     wic_bitmap: Some(wic_bitmap),
-                    pixel_data: None,
-                    stride: 0,
+                    ..Default::default()
                 });
             }
         }
@@ -138,7 +451,75 @@ impl WicLoader {
         Ok(Document::new_multiframe_image(pages))
     }
 
-    pub fn save(&self, doc: &Document, path: &str, page: usize) -> Result<()> {
+    /// Source bitmap of a page - decoded image or rendered PDF pixels
+    /// Applies a rotation of 0/90/180/270 degrees
+    fn rotate(factory: &IWICImagingFactory, source: IWICBitmapSource, rotation: i32) -> Result<IWICBitmapSource> {
+        let transform = match rotation {
+            90 => WICBitmapTransformRotate90,
+            180 => WICBitmapTransformRotate180,
+            270 => WICBitmapTransformRotate270,
+            _ => return Ok(source),
+        };
+        unsafe {
+            let flip_rotator = factory.CreateBitmapFlipRotator()?;
+            flip_rotator.Initialize(&source, transform)?;
+            flip_rotator.cast()
+        }
+    }
+
+    /// Pixels of a page; for a half of a split double page only that half
+    fn page_source(factory: &IWICImagingFactory, doc: &Document, page: usize) -> Result<IWICBitmapSource> {
+        let bitmap = if let Some(wic_bitmap) = doc.get_wic_bitmap(page) {
+            wic_bitmap
+        } else if let Some((data, width, height, stride)) = doc.get_pixel_data(page) {
+            unsafe {
+                factory.CreateBitmapFromMemory(
+                    width,
+                    height,
+                    &GUID_WICPixelFormat32bppBGRA,
+                    stride,
+                    data,
+                )?
+            }
+        } else {
+            return Err(Error::from_win32());
+        };
+        let Some([left, top, right, bottom]) = doc.page_clip(page) else {
+            return bitmap.cast();
+        };
+        unsafe {
+            // The clip is in pixels of the image page, the bitmap may be of another size
+            let (mut width, mut height) = (0u32, 0u32);
+            bitmap.GetSize(&mut width, &mut height)?;
+            let (page_width, page_height) = doc.image_dimensions(page);
+            let (scale_x, scale_y) = (width as f32 / page_width.max(1.0), height as f32 / page_height.max(1.0));
+            let x = ((left * scale_x).round() as i32).clamp(0, width as i32 - 1);
+            let y = ((top * scale_y).round() as i32).clamp(0, height as i32 - 1);
+            let rect = WICRect {
+                X: x,
+                Y: y,
+                Width: ((right * scale_x).round() as i32).clamp(x + 1, width as i32) - x,
+                Height: ((bottom * scale_y).round() as i32).clamp(y + 1, height as i32) - y,
+            };
+            let clipper = factory.CreateBitmapClipper()?;
+            clipper.Initialize(&bitmap, &rect)?;
+            clipper.cast()
+        }
+    }
+
+    /// Encodes a page to `path`, rotated by `rotation` degrees (clockwise, as displayed)
+    /// Encodes one page. `webp_lossless` selects lossless WebP; lossy WebP uses `WEBP_QUALITY`.
+    /// With `crop` (unrotated source pixels) only that part of the page is written.
+    #[allow(clippy::too_many_arguments)]
+    pub fn save(
+        &self,
+        doc: &Document,
+        path: &str,
+        page: usize,
+        transform: &PageTransform,
+        watermark: Option<&Watermark>,
+        webp_lossless: bool,
+    ) -> Result<()> {
         let factory = get_wic_factory()?;
 
         // Determine output format from extension
@@ -158,151 +539,206 @@ impl WicLoader {
         };
 
         unsafe {
-            // Create stream for output
-            let path_wide: Vec<u16> = path.encode_utf16().chain(std::iter::once(0)).collect();
-            let stream = factory.CreateStream()?;
-            stream.InitializeFromFilename(PCWSTR(path_wide.as_ptr()), GENERIC_WRITE.0)?;
-
-            // Create encoder
-            let encoder = factory.CreateEncoder(container_format, std::ptr::null())?;
-            encoder.Initialize(&stream, WICBitmapEncoderNoCache)?;
-
-            // Create frame
-            let mut frame: Option<IWICBitmapFrameEncode> = None;
-            let mut props: Option<IPropertyBag2> = None;
-            encoder.CreateNewFrame(&mut frame, &mut props)?;
-
-            let frame = frame.ok_or_else(Error::from_win32)?;
-            frame.Initialize(props.as_ref())?;
+            // Get source bitmap with watermark burned in if configured
+            // (frames of animated images are pages, so this is the frame currently shown)
+            let mut source = Self::transformed_page(&factory, doc, page, transform)?;
+            if let Some(watermark) = watermark {
+                source = apply_watermark(&factory, &source, watermark)?.cast()?;
+            }
 
-            // Get source bitmap
-            if let Some(wic_bitmap) = doc.get_wic_bitmap(page) {
-                let mut width = 0u32;
-                let mut height = 0u32;
-                wic_bitmap.GetSize(&mut width, &mut height)?;
+            // Encoded into a temporary file that replaces `path` only once complete
+            crate::utils::write_atomically(path, |temp| {
+                Self::encode(&factory, container_format, temp, &source, ext == "webp", webp_lossless)
+            })
+        }
+    }
 
-                frame.SetSize(width, height)?;
+    /// Writes `source` as the only frame of a `container_format` file at `path`. The
+    /// encoder and stream are released on return, so the file is closed afterwards.
+    unsafe fn encode(
+        factory: &IWICImagingFactory,
+        container_format: &GUID,
+        path: &str,
+        source: &IWICBitmapSource,
+        webp: bool,
+        webp_lossless: bool,
+    ) -> Result<()> {
+        // Create encoder before the stream so a missing codec leaves no empty file behind.
+        // Windows ships no WebP encoder; it needs a third-party WIC codec.
+        let encoder = factory.CreateEncoder(container_format, std::ptr::null()).map_err(|e| {
+            if e.code() == WINCODEC_ERR_COMPONENTNOTFOUND && webp {
+                Error::new(
+                    e.code(),
+                    HSTRING::from("Auf diesem System ist kein WebP-Encoder installiert. Bitte wählen Sie ein anderes Format, z. B. PNG."),
+                )
+            } else {
+                e
+            }
+        })?;
+
+        // Create stream for output
+        let path_wide = crate::utils::to_wide_path(path);
+        let stream = factory.CreateStream()?;
+        stream.InitializeFromFilename(PCWSTR(path_wide.as_ptr()), GENERIC_WRITE.0)?;
+        encoder.Initialize(&stream, WICBitmapEncoderNoCache)?;
+
+        // Create frame
+        let mut frame: Option<IWICBitmapFrameEncode> = None;
+        let mut props: Option<IPropertyBag2> = None;
+        encoder.CreateNewFrame(&mut frame, &mut props)?;
+
+        let frame = frame.ok_or_else(Error::from_win32)?;
+        if let (true, Some(props)) = (webp, props.as_ref()) {
+            // Encoders that lack an option ignore it, so failures are not fatal
+            let _ = write_encoder_option(props, w!("Lossless"), variant_bool(webp_lossless));
+            if !webp_lossless {
+                let _ = write_encoder_option(props, w!("ImageQuality"), variant_f32(WEBP_QUALITY));
+            }
+        }
+        frame.Initialize(props.as_ref())?;
 
-                // Set pixel format
-                let mut pixel_format = GUID_WICPixelFormat32bppBGRA;
-                frame.SetPixelFormat(&mut pixel_format)?;
+        let mut width = 0u32;
+        let mut height = 0u32;
+        source.GetSize(&mut width, &mut height)?;
 
-                // Write pixels
-                frame.WriteSource(wic_bitmap, std::ptr::null())?;
-            } else if let Some((data, width, height, stride)) = doc.get_pixel_data(page) {
-                frame.SetSize(width, height)?;
+        frame.SetSize(width, height)?;
 
-                let mut pixel_format = GUID_WICPixelFormat32bppBGRA;
-                frame.SetPixelFormat(&mut pixel_format)?;
+        // Set pixel format
+        let mut pixel_format = GUID_WICPixelFormat32bppBGRA;
+        frame.SetPixelFormat(&mut pixel_format)?;
 
-                frame.WritePixels(height, stride, data)?;
-            } else {
-                return Err(Error::from_win32());
-            }
+        // Write pixels
+        frame.WriteSource(source, std::ptr::null())?;
 
-            frame.Commit()?;
-            encoder.Commit()?;
-        }
+        frame.Commit()?;
+        encoder.Commit()?;
 
         Ok(())
     }
 
-    #[allow(dead_code)]
+    /// Wraps top-down 32bpp PBGRA pixels (e.g. pasted from the clipboard)
     pub fn create_bitmap_from_data(
         &self,
         width: u32,
         height: u32,
         data: &[u8],
     ) -> Result<IWICBitmap> {
-        let factory = get_wic_factory()?;
-
-        unsafe {
-            let stride = width * 4;
-            factory.CreateBitmapFromMemory(
-                width,
-                height,
-                &GUID_WICPixelFormat32bppPBGRA,
-                stride,
-                data,
-            )
-        }
+        bitmap_from_pbgra(width, height, data)
     }
 
     pub fn get_bitmap_for_clipboard(
         &self,
         doc: &Document,
         page: usize,
-        rotation: i32,
+        transform: &PageTransform,
+        watermark: Option<&Watermark>,
     ) -> Result<ClipboardBitmapData> {
         let factory = get_wic_factory()?;
 
         unsafe {
-            // Get source bitmap
-            let source = if let Some(wic_bitmap) = doc.get_wic_bitmap(page) {
-                wic_bitmap.clone()
-            } else if let Some((data, width, height, stride)) = doc.get_pixel_data(page) {
-                factory.CreateBitmapFromMemory(
-                    width,
-                    height,
-                    &GUID_WICPixelFormat32bppBGRA,
-                    stride,
-                    data,
-                )?
-            } else {
-                return Err(Error::from_win32());
-            };
+            let rotated = Self::transformed_page(&factory, doc, page, transform)?;
 
-            // Apply rotation if needed
-            let rotated: IWICBitmapSource = if rotation != 0 {
-                let transform = match rotation {
-                    90 => WICBitmapTransformRotate90,
-                    180 => WICBitmapTransformRotate180,
-                    270 => WICBitmapTransformRotate270,
-                    _ => WICBitmapTransformRotate0,
-                };
-                let flip_rotator = factory.CreateBitmapFlipRotator()?;
-                flip_rotator.Initialize(&source, transform)?;
-                flip_rotator.cast()?
-            } else {
-                source.cast()?
+            // Watermark after rotation so it matches the on-screen orientation
+            let rotated: IWICBitmapSource = match watermark {
+                Some(watermark) => apply_watermark(&factory, &rotated, watermark)?.cast()?,
+                None => rotated,
             };
 
-            // Convert to non-premultiplied BGRA for clipboard
-            let converter = factory.CreateFormatConverter()?;
-            converter.Initialize(
-                &rotated,
-                &GUID_WICPixelFormat32bppBGRA,
-                WICBitmapDitherTypeNone,
-                None,
-                0.0,
-                WICBitmapPaletteTypeMedianCut,
-            )?;
+            Self::to_dib(&factory, &rotated)
+        }
+    }
 
-            // Get dimensions
-            let mut width = 0u32;
-            let mut height = 0u32;
-            converter.GetSize(&mut width, &mut height)?;
-
-            // Read pixels
-            let stride = width * 4;
-            let buffer_size = (stride * height) as usize;
-            let mut data = vec![0u8; buffer_size];
-            converter.CopyPixels(std::ptr::null(), stride, &mut data)?;
-
-            // Flip vertically for DIB format (bottom-up)
-            let row_size = stride as usize;
-            let mut flipped = vec![0u8; buffer_size];
-            for y in 0..height as usize {
-                let src_row = y * row_size;
-                let dst_row = (height as usize - 1 - y) * row_size;
-                flipped[dst_row..dst_row + row_size].copy_from_slice(&data[src_row..src_row + row_size]);
+    /// The page taken through `transform` in the order the display applies it (see
+    /// page_transform.rs): cropped in source pixels, turned, then straightened
+    unsafe fn transformed_page(factory: &IWICImagingFactory, doc: &Document, page: usize, transform: &PageTransform) -> Result<IWICBitmapSource> {
+        let source: IWICBitmapSource = match transform.crop_rect() {
+            Some(rect) => {
+                let clipper = factory.CreateBitmapClipper()?;
+                clipper.Initialize(&Self::page_source(factory, doc, page)?, &rect)?;
+                clipper.cast()?
             }
+            None => Self::page_source(factory, doc, page)?,
+        };
+        let rotated = Self::rotate(factory, source, transform.rotation)?;
+        if transform.skew == 0.0 {
+            return Ok(rotated);
+        }
+        let pixels = Self::to_pixels(factory, &rotated)?;
+        let data = crate::deskew::straighten(&pixels, -transform.skew);
+        bitmap_from_pbgra(pixels.width, pixels.height, &data)?.cast()
+    }
 
-            Ok(ClipboardBitmapData {
-                width,
-                height,
-                data: flipped,
-            })
+    /// Page downscaled to fit `max_size` x `max_size`, rotated as displayed (print dialog
+    /// page picker). Same pixel layout as `get_bitmap_for_clipboard`.
+    pub fn get_thumbnail(&self, doc: &Document, page: usize, rotation: i32, max_size: u32) -> Result<ClipboardBitmapData> {
+        let factory = get_wic_factory()?;
+        unsafe { Self::to_dib(&factory, &Self::scaled_page(&factory, doc, page, rotation, max_size)?) }
+    }
+
+    /// Like `get_thumbnail`, but top-down as in `SoftwareBitmap` (text recognition)
+    pub fn get_page_pixels(&self, doc: &Document, page: usize, rotation: i32, max_size: u32) -> Result<ClipboardBitmapData> {
+        let factory = get_wic_factory()?;
+        unsafe { Self::to_pixels(&factory, &Self::scaled_page(&factory, doc, page, rotation, max_size)?) }
+    }
+
+    /// Page rotated as displayed and downscaled to fit `max_size` x `max_size`
+    unsafe fn scaled_page(factory: &IWICImagingFactory, doc: &Document, page: usize, rotation: i32, max_size: u32) -> Result<IWICBitmapSource> {
+        let rotated = Self::rotate(factory, Self::page_source(factory, doc, page)?, rotation)?;
+        let mut width = 0u32;
+        let mut height = 0u32;
+        rotated.GetSize(&mut width, &mut height)?;
+        let scale = (max_size as f64 / width.max(height).max(1) as f64).min(1.0);
+
+        let scaler = factory.CreateBitmapScaler()?;
+        scaler.Initialize(
+            &rotated,
+            ((width as f64 * scale) as u32).max(1),
+            ((height as f64 * scale) as u32).max(1),
+            WICBitmapInterpolationModeFant,
+        )?;
+        scaler.cast()
+    }
+
+    /// Bottom-up, non-premultiplied 32bpp BGRA pixels as GDI and the clipboard expect them
+    unsafe fn to_dib(factory: &IWICImagingFactory, source: &IWICBitmapSource) -> Result<ClipboardBitmapData> {
+        let pixels = Self::to_pixels(factory, source)?;
+
+        // Flip vertically for DIB format (bottom-up)
+        let row_size = pixels.width as usize * 4;
+        let mut flipped = vec![0u8; pixels.data.len()];
+        for y in 0..pixels.height as usize {
+            let src_row = y * row_size;
+            let dst_row = (pixels.height as usize - 1 - y) * row_size;
+            flipped[dst_row..dst_row + row_size].copy_from_slice(&pixels.data[src_row..src_row + row_size]);
         }
+
+        Ok(ClipboardBitmapData { data: flipped, ..pixels })
+    }
+
+    /// Top-down, non-premultiplied 32bpp BGRA pixels
+    unsafe fn to_pixels(factory: &IWICImagingFactory, source: &IWICBitmapSource) -> Result<ClipboardBitmapData> {
+        // Convert to non-premultiplied BGRA for clipboard
+        let converter = factory.CreateFormatConverter()?;
+        converter.Initialize(
+            source,
+            &GUID_WICPixelFormat32bppBGRA,
+            WICBitmapDitherTypeNone,
+            None,
+            0.0,
+            WICBitmapPaletteTypeMedianCut,
+        )?;
+
+        // Get dimensions
+        let mut width = 0u32;
+        let mut height = 0u32;
+        converter.GetSize(&mut width, &mut height)?;
+
+        // Read pixels
+        let stride = width * 4;
+        let buffer_size = (stride * height) as usize;
+        let mut data = vec![0u8; buffer_size];
+        converter.CopyPixels(std::ptr::null(), stride, &mut data)?;
+
+        Ok(ClipboardBitmapData { width, height, data })
     }
 }
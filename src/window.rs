@@ -20,6 +20,12 @@ const CLASS_NAME: PCWSTR = w!("SimpliViewWindow");
 pub struct Window {
     hwnd: HWND,
     instance: HMODULE,
+    /// Placement and style to restore when leaving fullscreen
+    restore: Option<(WINDOWPLACEMENT, i32)>,
+    /// "Immer im Vordergrund"
+    topmost: bool,
+    /// "Kompaktmodus": no title bar, only the thin resizing border
+    compact: bool,
 }
 
 impl Window {
@@ -31,7 +37,7 @@ impl Window {
             // Initialize common controls
             let icc = INITCOMMONCONTROLSEX {
                 dwSize: std::mem::size_of::<INITCOMMONCONTROLSEX>() as u32,
-                dwICC: ICC_BAR_CLASSES | ICC_STANDARD_CLASSES,
+                dwICC: ICC_BAR_CLASSES | ICC_STANDARD_CLASSES | ICC_PROGRESS_CLASS,
             };
             InitCommonControlsEx(&icc);
 
@@ -106,7 +112,7 @@ impl Window {
                 return Err(Error::from_win32());
             }
 
-            Ok(Self { hwnd, instance })
+            Ok(Self { hwnd, instance, restore: None, topmost: false, compact: false })
         }
     }
 
@@ -127,6 +133,174 @@ impl Window {
         }
     }
 
+    pub fn is_fullscreen(&self) -> bool {
+        self.restore.is_some()
+    }
+
+    pub fn is_topmost(&self) -> bool {
+        self.topmost
+    }
+
+    /// Keeps the window above all other windows that are not topmost; fullscreen keeps the
+    /// choice and leaving it applies it again
+    pub fn set_topmost(&mut self, topmost: bool) {
+        self.topmost = topmost;
+        self.apply_topmost();
+    }
+
+    fn apply_topmost(&self) {
+        unsafe {
+            let _ = SetWindowPos(
+                self.hwnd,
+                if self.topmost { HWND_TOPMOST } else { HWND_NOTOPMOST },
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    /// Removes the title bar, keeping the border for resizing; the window can still be moved
+    /// with Alt+Leertaste and Win+arrow keys. In fullscreen the style applies on leaving it.
+    pub fn set_compact(&mut self, compact: bool) {
+        self.compact = compact;
+        let compact_style = |style: i32| if compact { style & !(WS_CAPTION.0 as i32) } else { style | WS_CAPTION.0 as i32 };
+        if let Some((_, style)) = self.restore.as_mut() {
+            *style = compact_style(*style);
+            return;
+        }
+        unsafe {
+            SetWindowLongW(self.hwnd, GWL_STYLE, compact_style(GetWindowLongW(self.hwnd, GWL_STYLE)));
+            let _ = SetWindowPos(
+                self.hwnd,
+                None,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+            );
+        }
+    }
+
+    /// Borderless over the whole monitor, or back to the previous placement
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        if fullscreen == self.is_fullscreen() {
+            return;
+        }
+        unsafe {
+            if let Some((placement, style)) = self.restore.take() {
+                SetWindowLongW(self.hwnd, GWL_STYLE, style);
+                let _ = SetWindowPlacement(self.hwnd, &placement);
+                let _ = SetWindowPos(
+                    self.hwnd,
+                    None,
+                    0,
+                    0,
+                    0,
+                    0,
+                    SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+                );
+                // The monitor of the saved placement may be gone by now
+                self.move_onto_screen();
+                self.apply_topmost();
+                return;
+            }
+
+            let mut placement = WINDOWPLACEMENT {
+                length: std::mem::size_of::<WINDOWPLACEMENT>() as u32,
+                ..Default::default()
+            };
+            let mut monitor_info = MONITORINFO {
+                cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                ..Default::default()
+            };
+            if !GetWindowPlacement(self.hwnd, &mut placement).as_bool()
+                || !GetMonitorInfoW(MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST), &mut monitor_info).as_bool()
+            {
+                return;
+            }
+            let style = GetWindowLongW(self.hwnd, GWL_STYLE);
+            self.restore = Some((placement, style));
+
+            SetWindowLongW(self.hwnd, GWL_STYLE, style & !(WS_OVERLAPPEDWINDOW.0 as i32));
+            self.cover_monitor(monitor_info.rcMonitor);
+        }
+    }
+
+    /// False if the window no longer intersects any monitor, e.g. after undocking
+    pub fn is_on_screen(&self) -> bool {
+        unsafe {
+            let mut rect = RECT::default();
+            let _ = GetWindowRect(self.hwnd, &mut rect);
+            MonitorFromRect(&rect, MONITOR_DEFAULTTONULL).0 != 0
+        }
+    }
+
+    /// After a display change: in fullscreen the window covers its monitor again (the
+    /// resolution may have changed), otherwise an off-screen window moves onto the nearest
+    /// work area. Leave fullscreen first if its monitor is gone (see `is_on_screen`).
+    pub fn fit_to_monitors(&self) {
+        if !self.is_fullscreen() {
+            self.move_onto_screen();
+            return;
+        }
+        unsafe {
+            let mut monitor_info = MONITORINFO {
+                cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                ..Default::default()
+            };
+            if GetMonitorInfoW(MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST), &mut monitor_info).as_bool() {
+                self.cover_monitor(monitor_info.rcMonitor);
+            }
+        }
+    }
+
+    fn cover_monitor(&self, monitor: RECT) {
+        unsafe {
+            let _ = SetWindowPos(
+                self.hwnd,
+                HWND_TOP,
+                monitor.left,
+                monitor.top,
+                monitor.right - monitor.left,
+                monitor.bottom - monitor.top,
+                SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+            );
+        }
+    }
+
+    /// Moves the window onto the work area of the nearest monitor if it is off-screen,
+    /// shrinking it where that monitor is smaller
+    fn move_onto_screen(&self) {
+        unsafe {
+            if IsIconic(self.hwnd).as_bool() || self.is_on_screen() {
+                return;
+            }
+            let mut rect = RECT::default();
+            let _ = GetWindowRect(self.hwnd, &mut rect);
+            let mut monitor_info = MONITORINFO {
+                cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                ..Default::default()
+            };
+            if !GetMonitorInfoW(MonitorFromRect(&rect, MONITOR_DEFAULTTONEAREST), &mut monitor_info).as_bool() {
+                return;
+            }
+            let work = monitor_info.rcWork;
+            let width = (rect.right - rect.left).min(work.right - work.left);
+            let height = (rect.bottom - rect.top).min(work.bottom - work.top);
+            let x = rect.left.clamp(work.left, work.right - width);
+            let y = rect.top.clamp(work.top, work.bottom - height);
+            let _ = SetWindowPos(self.hwnd, None, x, y, width, height, SWP_NOZORDER | SWP_NOACTIVATE);
+        }
+    }
+
     pub fn set_title(&self, title: &str) {
         let title_wide: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
         unsafe {
@@ -215,18 +389,13 @@ extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM
                 if statusbar.0 != 0 {
                     SendMessageW(statusbar, WM_SIZE, WPARAM(0), LPARAM(0));
                 }
-                // Invalidate to repaint content area
-                let _ = InvalidateRect(hwnd, None, false);
                 DefWindowProcW(hwnd, msg, wparam, lparam)
             }
             WM_PAINT => {
-                // Default paint handler - App should handle this via message callback
+                // Default paint handler - App should handle this via message callback. Until
+                // then nothing is painted: a fill here flashes behind the children.
                 let mut ps = PAINTSTRUCT::default();
-                let hdc = BeginPaint(hwnd, &mut ps);
-                // Fill with a neutral background color to prevent artifacts
-                let brush = CreateSolidBrush(COLORREF(0x1E1E1E)); // Dark gray
-                let _ = FillRect(hdc, &ps.rcPaint, brush);
-                let _ = DeleteObject(brush);
+                BeginPaint(hwnd, &mut ps);
                 let _ = EndPaint(hwnd, &ps);
                 LRESULT(0)
             }
@@ -0,0 +1,103 @@
+//! Window/level for 16-bit grayscale images (radiographs, scanner raw output)
+//!
+//! With "16-Bit-Graustufen erhalten" the loader keeps the 16-bit pixels of such images
+//! instead of converting them to 8 bits per channel right away. The display bitmap is made
+//! from them through a lookup table for the current window (width and center in 16-bit
+//! values), so changing the window never reads the file again. Ctrl+drag changes it like
+//! in DICOM viewers: horizontally the width, vertically the center.
+//!
+//! Export, print and clipboard use the display bitmap, i.e. they get what is shown.
+
+use std::cell::RefCell;
+use windows::{core::*, Win32::Graphics::Imaging::IWICBitmap};
+
+const MAX_VALUE: f32 = u16::MAX as f32;
+/// Narrowest window, so the lookup table never divides by zero
+const MIN_WIDTH: f32 = 2.0;
+/// Drag distance in pixels that changes width or center by the current width
+const DRAG_PIXELS_PER_WIDTH: f32 = 400.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowLevel {
+    pub center: f32,
+    pub width: f32,
+}
+
+impl WindowLevel {
+    /// Window over the range of values present in the image
+    pub fn from_range(min: u16, max: u16) -> Self {
+        let width = (max as f32 - min as f32).max(MIN_WIDTH);
+        Self { center: min as f32 + width / 2.0, width }
+    }
+
+    /// Window after a mouse drag from the point where `self` was current: right widens,
+    /// down raises the center (the image gets darker)
+    pub fn dragged(self, dx: i32, dy: i32) -> Self {
+        let step = self.width.max(64.0) / DRAG_PIXELS_PER_WIDTH;
+        Self {
+            center: (self.center + dy as f32 * step).clamp(0.0, MAX_VALUE),
+            width: (self.width + dx as f32 * step).clamp(MIN_WIDTH, MAX_VALUE + 1.0),
+        }
+    }
+
+    /// OSD text, e.g. "Fenster 4000 / Zentrum 2048"
+    pub fn label(&self) -> String {
+        format!("Fenster {:.0} / Zentrum {:.0}", self.width, self.center)
+    }
+
+    /// Gray value for each 16-bit value
+    fn lookup_table(&self) -> Vec<u8> {
+        let low = self.center - self.width / 2.0;
+        (0..=u16::MAX)
+            .map(|value| (((value as f32 - low) / self.width).clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect()
+    }
+}
+
+/// 16-bit grayscale pixels of a page and the display bitmap last made from them
+pub struct Gray16 {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<u16>,
+    /// Smallest and largest value present, for the initial window
+    range: (u16, u16),
+    /// Initial window given by the file (DICOM header), instead of the value range
+    default: Option<WindowLevel>,
+    display: RefCell<Option<(WindowLevel, IWICBitmap)>>,
+}
+
+impl Gray16 {
+    pub fn new(width: u32, height: u32, pixels: Vec<u16>) -> Self {
+        let range = pixels
+            .iter()
+            .fold((u16::MAX, u16::MIN), |(min, max), &value| (min.min(value), max.max(value)));
+        let range = if range.0 > range.1 { (0, u16::MAX) } else { range };
+        Self { width, height, pixels, range, default: None, display: RefCell::new(None) }
+    }
+
+    pub fn with_default_window(self, window: WindowLevel) -> Self {
+        Self { default: Some(window), ..self }
+    }
+
+    pub fn default_window(&self) -> WindowLevel {
+        self.default.unwrap_or_else(|| WindowLevel::from_range(self.range.0, self.range.1))
+    }
+
+    /// Display bitmap (32bpp PBGRA) for `window`, made again only when the window changed
+    pub fn display_bitmap(&self, window: WindowLevel) -> Result<IWICBitmap> {
+        if let Some((current, ref bitmap)) = *self.display.borrow() {
+            if current == window {
+                return Ok(bitmap.clone());
+            }
+        }
+        let table = window.lookup_table();
+        let mut bgra = Vec::with_capacity(self.pixels.len() * 4);
+        for &value in &self.pixels {
+            let gray = table[value as usize];
+            bgra.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+        let bitmap = crate::wic::bitmap_from_pbgra(self.width, self.height, &bgra)?;
+        *self.display.borrow_mut() = Some((window, bitmap.clone()));
+        Ok(bitmap)
+    }
+}